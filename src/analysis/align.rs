@@ -0,0 +1,154 @@
+//! Alignment of two captures of the same performance, such as a live session and a
+//! known-good recording, that started at different times
+
+use crate::{capture::CaptureEvent, midi::MidiMessage};
+use std::{fmt, time::Duration};
+
+/// Notes of each capture tried against each other when looking for the offset
+const MAX_NOTES: usize = 64;
+/// Notes closer than this after shifting are taken as the same note
+const TOLERANCE: Duration = Duration::from_millis(20);
+
+/// Time offset that lines up two captures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    /// Microseconds added to the times of the first capture to find the same events in the
+    /// second
+    pub offset: i64,
+    /// What the captures were lined up by
+    pub by: &'static str,
+    /// Notes of the first capture that have a match in the second at this offset
+    pub matched: usize,
+    pub notes: usize,
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+.3} s by {}", self.offset as f64 / 1e6, self.by)?;
+        if self.notes > 0 {
+            write!(f, ", {} of {} notes matched", self.matched, self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the time in microseconds and number of every sounding Note On, up to `MAX_NOTES`
+fn notes(events: &[CaptureEvent]) -> Vec<(i64, u8)> {
+    events
+        .iter()
+        .filter_map(|event| match event.message {
+            Some(MidiMessage::NoteOn { note, velocity, .. }) if velocity > 0 => {
+                Some((event.time.as_micros() as i64, note))
+            }
+            _ => None,
+        })
+        .take(MAX_NOTES)
+        .collect()
+}
+
+/// Returns the number of notes of `first` that have the same note in `second` once shifted
+fn matches(first: &[(i64, u8)], second: &[(i64, u8)], offset: i64) -> usize {
+    let tolerance = TOLERANCE.as_micros() as i64;
+    first
+        .iter()
+        .filter(|(time, note)| {
+            second
+                .iter()
+                .any(|(t, n)| n == note && (time + offset - t).abs() <= tolerance)
+        })
+        .count()
+}
+
+/// Finds the offset that lines up `second` with `first`. Both captures are lined up by their
+/// first Start message if they both have one, and otherwise by the offset under which the
+/// most notes match, the smallest offset winning ties. Returns `None` if neither works
+pub fn align(first: &[CaptureEvent], second: &[CaptureEvent]) -> Option<Alignment> {
+    let start = |events: &[CaptureEvent]| {
+        events
+            .iter()
+            .find(|e| e.message == Some(MidiMessage::Start))
+            .map(|e| e.time.as_micros() as i64)
+    };
+    if let (Some(first), Some(second)) = (start(first), start(second)) {
+        return Some(Alignment {
+            offset: second - first,
+            by: "Start",
+            matched: 0,
+            notes: 0,
+        });
+    }
+
+    let (first, second) = (notes(first), notes(second));
+    // Every pairing of the same note is a candidate
+    let mut candidates: Vec<i64> = first
+        .iter()
+        .flat_map(|(time, note)| {
+            second
+                .iter()
+                .filter(move |(_, n)| n == note)
+                .map(move |(t, _)| t - time)
+        })
+        .collect();
+    candidates.sort_by_key(|offset| offset.abs());
+    candidates.dedup();
+    let (offset, matched) = candidates
+        .into_iter()
+        .map(|offset| (offset, matches(&first, &second, offset)))
+        .fold(None, |best: Option<(i64, usize)>, candidate| match best {
+            Some(best) if best.1 >= candidate.1 => Some(best),
+            _ => Some(candidate),
+        })?;
+    Some(Alignment {
+        offset,
+        by: "notes",
+        matched,
+        notes: first.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    /// Captures the bytes, each at its time in milliseconds
+    fn capture(bytes: &[(u64, u8)]) -> Vec<CaptureEvent> {
+        let mut capture = Capture::new();
+        bytes
+            .iter()
+            .map(|(ms, byte)| capture.process(Duration::from_millis(*ms), *byte))
+            .collect()
+    }
+
+    /// Captures a Note On of each note, 100 ms apart from `start`
+    fn melody(start: u64, notes: &[u8]) -> Vec<CaptureEvent> {
+        let bytes: Vec<(u64, u8)> = notes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, note)| {
+                let time = start + i as u64 * 100;
+                [(time, 0x90), (time, *note), (time, 100)]
+            })
+            .collect();
+        capture(&bytes)
+    }
+
+    #[test]
+    fn offsets() {
+        let live = capture(&[(500, 0xF8), (1200, 0xFA)]);
+        let reference = capture(&[(300, 0xFA)]);
+        let alignment = align(&live, &reference).unwrap();
+        assert_eq!((alignment.offset, alignment.by), (-900_000, "Start"));
+
+        // The reference misses the first note of the live capture
+        let live = melody(2000, &[60, 64, 67, 64, 72, 60]);
+        let reference = melody(5105, &[64, 67, 64, 72, 60]);
+        let alignment = align(&live, &reference).unwrap();
+        assert_eq!(alignment.offset, 3_005_000);
+        assert_eq!(
+            alignment.to_string(),
+            "+3.005 s by notes, 5 of 6 notes matched"
+        );
+        assert_eq!(align(&live, &capture(&[(0, 0xF8)])), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! The state of each channel: the notes held, for seeing at a glance which keys a
+//! controller left sounding, the values of the controllers moved most recently, the
+//! Pitch Bend and pressure, and how busy the channel is
+
+use crate::{
+    analysis::snapshot::Snapshot,
+    capture::CaptureEvent,
+    midi::{MidiChannelMode, MidiMessage},
+};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Most controllers followed at once. The one left alone the longest is dropped first
+const MAX_CONTROLS: usize = 16;
+
+/// Controllers that Reset All Controllers sets, with their values, as recommended by RP-015
+const RESET_CONTROLS: [(u8, u8); 10] = [
+    (1, 0),
+    (11, 127),
+    (64, 0),
+    (65, 0),
+    (66, 0),
+    (67, 0),
+    (98, 127),
+    (99, 127),
+    (100, 127),
+    (101, 127),
+];
+
+/// The last value of a controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Control {
+    /// Channel from 0 to 15
+    pub channel: u8,
+    pub control: u8,
+    pub value: u8,
+}
+
+/// How busy a channel is and the sound it plays
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Activity {
+    /// Notes started on the channel
+    pub notes: u32,
+    /// Time of the last message of the channel
+    pub last: Option<Duration>,
+    /// Last Program Change, from 0 to 127
+    pub program: Option<u8>,
+    /// Bank Select MSB and LSB as one 14 bit number, once either was received
+    pub bank: Option<u16>,
+}
+
+/// Follows the notes held, the controllers moved, the Pitch Bend and pressure, and the
+/// activity of each of the 16 channels
+#[derive(Debug, Default)]
+pub struct ChannelTracker {
+    /// A bit per note of each channel, set while the note is held
+    held: [u128; 16],
+    /// A bit per channel that has played a note
+    played: u16,
+    /// Most recently moved first
+    controls: Vec<Control>,
+    /// Last value of every controller received, by channel and controller number
+    values: BTreeMap<(u8, u8), u8>,
+    /// Pitch Bend of each channel from 0 to 16383, once one was received
+    bends: [Option<u16>; 16],
+    /// Channel Pressure of each channel, once one was received
+    pressures: [Option<u8>; 16],
+    /// Note and value of the last Poly Pressure of each channel, while the note is held
+    poly_pressures: [Option<(u8, u8)>; 16],
+    activity: [Activity; 16],
+}
+
+impl ChannelTracker {
+    /// Creates a tracker that has not seen any notes
+    pub fn new() -> ChannelTracker {
+        ChannelTracker::default()
+    }
+
+    /// Returns `true` if the note is held on the channel, from 0 to 15
+    pub fn is_held(&self, channel: u8, note: u8) -> bool {
+        self.held[channel as usize & 0x0F] & (1 << (note & 0x7F)) != 0
+    }
+
+    /// Returns the number of notes held on the channel
+    pub fn held_count(&self, channel: u8) -> u32 {
+        self.held[channel as usize & 0x0F].count_ones()
+    }
+
+    /// Returns the channels that have played a note, in order
+    pub fn played(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..16).filter(|channel| self.played & (1 << channel) != 0)
+    }
+
+    /// Returns the controllers moved most recently, the latest first
+    pub fn controls(&self) -> &[Control] {
+        &self.controls
+    }
+
+    /// Returns the Pitch Bend of the channel, if one was received
+    pub fn bend(&self, channel: u8) -> Option<u16> {
+        self.bends[channel as usize & 0x0F]
+    }
+
+    /// Returns the Channel Pressure of the channel, if one was received
+    pub fn pressure(&self, channel: u8) -> Option<u8> {
+        self.pressures[channel as usize & 0x0F]
+    }
+
+    /// Returns the note and value of the last Poly Pressure of the channel, while its note
+    /// is held
+    pub fn poly_pressure(&self, channel: u8) -> Option<(u8, u8)> {
+        self.poly_pressures[channel as usize & 0x0F]
+    }
+
+    /// Returns how busy the channel is and the sound it plays
+    pub fn activity(&self, channel: u8) -> &Activity {
+        &self.activity[channel as usize & 0x0F]
+    }
+
+    /// Returns the values of every channel, to be compared with those of a later moment
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            controls: self.values.clone(),
+            programs: self.activity.map(|a| a.program),
+            banks: self.activity.map(|a| a.bank),
+            bends: self.bends,
+            pressures: self.pressures,
+            held: self.held,
+        }
+    }
+
+    /// Updates the state with the next event of the capture. Note On with velocity 0
+    /// releases a note like Note Off, and the Channel Mode messages that end every note
+    /// release all those of their channel. Reset All Controllers centers the Pitch Bend,
+    /// zeroes the pressure, and sets the controllers of `RESET_CONTROLS` that were received.
+    /// System Reset releases every note
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        if let (Some(_), Some(channel)) = (&event.message, event.channel) {
+            self.activity[channel as usize & 0x0F].last = Some(event.time);
+        }
+        match &event.message {
+            Some(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }) if *velocity > 0 => {
+                self.held[*channel as usize] |= 1 << note;
+                self.played |= 1 << channel;
+                self.activity[*channel as usize].notes += 1;
+            }
+            Some(MidiMessage::NoteOn { channel, note, .. })
+            | Some(MidiMessage::NoteOff { channel, note, .. }) => {
+                let channel = *channel as usize;
+                self.held[channel] &= !(1 << note);
+                if self.poly_pressures[channel].is_some_and(|(n, _)| n == *note) {
+                    self.poly_pressures[channel] = None;
+                }
+            }
+            Some(MidiMessage::ChannelMode {
+                channel,
+                mode: MidiChannelMode::ResetAllControllers,
+            }) => {
+                let channel = *channel as usize;
+                self.bends[channel] = self.bends[channel].map(|_| 8192);
+                self.pressures[channel] = self.pressures[channel].map(|_| 0);
+                self.poly_pressures[channel] = None;
+                for (control, value) in RESET_CONTROLS {
+                    if let Some(v) = self.values.get_mut(&(channel as u8, control)) {
+                        *v = value;
+                    }
+                }
+            }
+            Some(MidiMessage::ChannelMode { channel, mode })
+                if !matches!(mode, MidiChannelMode::LocalControl(_)) =>
+            {
+                self.held[*channel as usize] = 0;
+                self.poly_pressures[*channel as usize] = None;
+            }
+            Some(MidiMessage::PitchBend { channel, value }) => {
+                self.bends[*channel as usize] = Some(*value);
+            }
+            Some(MidiMessage::ChannelPressure { channel, pressure }) => {
+                self.pressures[*channel as usize] = Some(*pressure);
+            }
+            Some(MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            }) if self.is_held(*channel, *note) => {
+                self.poly_pressures[*channel as usize] = Some((*note, *pressure));
+            }
+            Some(MidiMessage::ProgramChange { channel, program }) => {
+                self.activity[*channel as usize].program = Some(*program);
+            }
+            Some(MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            }) => {
+                let bank = &mut self.activity[*channel as usize].bank;
+                match control {
+                    0 => *bank = Some(bank.unwrap_or(0) & 0x7F | (*value as u16) << 7),
+                    32 => *bank = Some(bank.unwrap_or(0) & !0x7F | *value as u16),
+                    _ => {}
+                }
+                self.values.insert((*channel, *control), *value);
+                self.controls
+                    .retain(|c| (c.channel, c.control) != (*channel, *control));
+                self.controls.insert(
+                    0,
+                    Control {
+                        channel: *channel,
+                        control: *control,
+                        value: *value,
+                    },
+                );
+                self.controls.truncate(MAX_CONTROLS);
+            }
+            Some(MidiMessage::SystemReset) => {
+                self.held = [0; 16];
+                self.poly_pressures = [None; 16];
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn follows_held_notes() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        let bytes = [
+            0x90, 60, 100, 64, 100, 0x99, 36, 127, 0x80, 60, 0, 0x99, 36, 0,
+        ];
+        for byte in bytes {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert!(tracker.is_held(0, 64));
+        assert!(!tracker.is_held(0, 60));
+        assert!(!tracker.is_held(9, 36));
+        assert_eq!(tracker.played().collect::<Vec<_>>(), [0, 9]);
+        assert_eq!(tracker.held_count(0), 1);
+
+        // All Notes Off
+        for byte in [0xB0, 123, 0] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert_eq!(tracker.held_count(0), 0);
+    }
+
+    #[test]
+    fn follows_recent_controllers() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        for byte in [0xB0, 11, 20, 1, 64, 11, 90, 0xB1, 11, 5] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let controls: Vec<(u8, u8, u8)> = tracker
+            .controls()
+            .iter()
+            .map(|c| (c.channel, c.control, c.value))
+            .collect();
+        assert_eq!(controls, [(1, 11, 5), (0, 11, 90), (0, 1, 64)]);
+    }
+
+    #[test]
+    fn follows_bend_and_pressure() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        let bytes = [0xE2, 0, 0x60, 0xD2, 90, 0x92, 60, 100, 0xA2, 60, 30, 61, 40];
+        for byte in bytes {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert_eq!(tracker.bend(2), Some(0x3000));
+        assert_eq!(tracker.pressure(2), Some(90));
+        // Pressure of a note that is not held is ignored
+        assert_eq!(tracker.poly_pressure(2), Some((60, 30)));
+        assert_eq!(tracker.bend(0), None);
+
+        // Reset All Controllers
+        for byte in [0xB2, 121, 0] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert_eq!(tracker.bend(2), Some(8192));
+        assert_eq!(tracker.pressure(2), Some(0));
+        assert_eq!(tracker.poly_pressure(2), None);
+    }
+
+    #[test]
+    fn snapshots_state() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        let bytes = [0xB0, 1, 90, 7, 100, 0x90, 60, 100, 0xC0, 3];
+        for byte in bytes {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.controls.get(&(0, 7)), Some(&100));
+        assert_eq!(snapshot.programs[0], Some(3));
+        assert_eq!(snapshot.held[0], 1 << 60);
+
+        // Reset All Controllers sets the Modulation Wheel but leaves the volume
+        for byte in [0xB0, 121, 0] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let reset = tracker.snapshot();
+        assert_eq!(reset.controls.get(&(0, 1)), Some(&0));
+        assert_eq!(reset.controls.get(&(0, 7)), Some(&100));
+    }
+
+    #[test]
+    fn follows_activity() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        let bytes = [0x93, 60, 100, 0x83, 60, 0, 0xB3, 0, 1, 32, 2, 0xC3, 5];
+        for (i, byte) in bytes.into_iter().enumerate() {
+            tracker.observe(&capture.process(Duration::from_millis(i as u64), byte));
+        }
+        assert_eq!(
+            *tracker.activity(3),
+            Activity {
+                notes: 1,
+                last: Some(Duration::from_millis(12)),
+                program: Some(5),
+                bank: Some(130),
+            }
+        );
+        assert_eq!(*tracker.activity(0), Activity::default());
+    }
+}
@@ -0,0 +1,215 @@
+//! Quality of received MIDI Timing Clock
+
+use crate::{capture::CaptureEvent, midi::MidiMessage, smf::CLOCKS_PER_QUARTER};
+use std::{fmt, time::Duration};
+
+/// Weight of each new interval in the running average clock interval
+const MEAN_GAIN: f64 = 1.0 / 24.0;
+
+/// An interval this many times longer than the average means clocks were lost
+const DROPOUT_RATIO: f64 = 1.5;
+
+/// Points lost per percent of average interval deviation
+const JITTER_PENALTY: f64 = 10.0;
+
+/// Points lost per percent of average tempo change between consecutive beats
+const DRIFT_PENALTY: f64 = 20.0;
+
+/// Points lost per percent of clocks that went missing
+const DROPOUT_PENALTY: f64 = 10.0;
+
+/// Summary of clock quality. All scores range from 0 (unusable) to 100 (perfect)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockQuality {
+    /// Overall sync quality
+    pub score: u8,
+    /// Steadiness of individual clock intervals
+    pub jitter: u8,
+    /// Stability of the tempo from beat to beat
+    pub drift: u8,
+    /// Absence of missing clocks
+    pub dropouts: u8,
+    /// Tempo derived from the average clock interval
+    pub bpm: f64,
+}
+
+impl fmt::Display for ClockQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sync {} (jitter {} drift {} dropouts {}) {:.1} BPM",
+            self.score, self.jitter, self.drift, self.dropouts, self.bpm
+        )
+    }
+}
+
+/// Measures the timing of received MIDI Timing Clock messages
+#[derive(Debug, Default)]
+pub struct ClockAnalyzer {
+    /// Time of the last clock
+    last: Option<Duration>,
+    /// Running average clock interval in microseconds
+    mean: Option<f64>,
+    /// Sum of interval deviations relative to the running average
+    deviation: f64,
+    /// Number of measured intervals
+    intervals: u64,
+    /// Number of clocks estimated to be missing
+    missed: u64,
+    /// Start of the beat being measured and the number of clocks since
+    beat: Option<(Duration, u32)>,
+    /// Length of the last complete beat in microseconds
+    last_beat: Option<f64>,
+    /// Sum of relative changes between consecutive beat lengths
+    beat_change: f64,
+    /// Number of compared beats
+    beats: u64,
+}
+
+impl ClockAnalyzer {
+    /// Creates an analyzer that has not seen any clocks
+    pub fn new() -> ClockAnalyzer {
+        ClockAnalyzer::default()
+    }
+
+    /// Updates the analysis with the next event of the capture
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        match event.message {
+            Some(MidiMessage::TimingClock) => self.clock(event.time),
+            // Transport changes legitimately interrupt the clock
+            Some(MidiMessage::Start) | Some(MidiMessage::Stop) => {
+                self.last = None;
+                self.beat = None;
+                self.last_beat = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self, time: Duration) {
+        if let Some(last) = self.last.replace(time) {
+            let interval = time.saturating_sub(last).as_micros() as f64;
+            match self.mean {
+                // Clocks stamped with the same time, as in files without timing, say
+                // nothing about the interval
+                _ if interval == 0.0 => {}
+                Some(mean) if interval > mean * DROPOUT_RATIO => {
+                    // Lost clocks are not counted as jitter
+                    self.missed = self
+                        .missed
+                        .saturating_add((interval / mean).round() as u64 - 1);
+                    self.beat = None;
+                    self.last_beat = None;
+                }
+                Some(mean) => {
+                    self.deviation += (interval - mean).abs() / mean;
+                    self.intervals += 1;
+                    self.mean = Some(mean + (interval - mean) * MEAN_GAIN);
+                }
+                None => self.mean = Some(interval),
+            }
+        }
+
+        let (start, count) = match self.beat {
+            Some((start, count)) => (start, count + 1),
+            None => {
+                self.beat = Some((time, 0));
+                return;
+            }
+        };
+        if count < CLOCKS_PER_QUARTER {
+            self.beat = Some((start, count));
+            return;
+        }
+        let length = time.saturating_sub(start).as_micros() as f64;
+        if let Some(last_beat) = self.last_beat.filter(|&last_beat| last_beat > 0.0) {
+            self.beat_change += (length - last_beat).abs() / last_beat;
+            self.beats += 1;
+        }
+        self.last_beat = Some(length);
+        self.beat = Some((time, 0));
+    }
+
+    /// Returns the clock quality, or `None` if too few clocks were received to judge
+    pub fn quality(&self) -> Option<ClockQuality> {
+        let mean = self.mean?;
+        if self.intervals == 0 {
+            return None;
+        }
+        let jitter_pct = 100.0 * self.deviation / self.intervals as f64;
+        let drift_pct = if self.beats > 0 {
+            100.0 * self.beat_change / self.beats as f64
+        } else {
+            0.0
+        };
+        let dropout_pct = 100.0 * self.missed as f64 / (self.intervals + self.missed) as f64;
+
+        let jitter = score(jitter_pct * JITTER_PENALTY);
+        let drift = score(drift_pct * DRIFT_PENALTY);
+        let dropouts = score(dropout_pct * DROPOUT_PENALTY);
+        // The worst aspect dominates since any one of them breaks sync
+        let mean_score = (jitter as f64 + drift as f64 + dropouts as f64) / 3.0;
+        let worst = jitter.min(drift).min(dropouts) as f64;
+        Some(ClockQuality {
+            score: ((mean_score + worst) / 2.0).round() as u8,
+            jitter,
+            drift,
+            dropouts,
+            bpm: 60e6 / (mean * CLOCKS_PER_QUARTER as f64),
+        })
+    }
+}
+
+/// Converts a penalty in points into a score
+fn score(penalty: f64) -> u8 {
+    (100.0 - penalty).clamp(0.0, 100.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    fn analyze(times: impl Iterator<Item = u64>) -> Option<ClockQuality> {
+        let mut capture = Capture::new();
+        let mut analyzer = ClockAnalyzer::new();
+        for micros in times {
+            analyzer.observe(&capture.process(Duration::from_micros(micros), 0xF8));
+        }
+        analyzer.quality()
+    }
+
+    #[test]
+    fn steady_clock() {
+        // 120 BPM
+        let quality = analyze((0..200).map(|n| n * 20_833)).unwrap();
+        assert_eq!(quality.score, 100);
+        assert!((quality.bpm - 120.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn jittery_clock() {
+        let quality = analyze((0..200).map(|n| n * 20_000 + (n % 2) * 400)).unwrap();
+        assert!(quality.jitter < 90, "{}", quality);
+        assert_eq!(quality.dropouts, 100);
+    }
+
+    #[test]
+    fn dropped_clocks() {
+        let quality = analyze((0..200).filter(|n| n % 50 != 0).map(|n| n * 20_000)).unwrap();
+        assert_eq!(quality.jitter, 100);
+        assert!(quality.dropouts < 100, "{}", quality);
+    }
+
+    #[test]
+    fn simultaneous_clocks() {
+        assert_eq!(analyze((0..2000).map(|_| 0)), None);
+        let quality = analyze((0..200).map(|n| n / 2 * 20_000)).unwrap();
+        assert_eq!(quality.dropouts, 100);
+    }
+
+    #[test]
+    fn no_clock() {
+        assert_eq!(analyze(0..1), None);
+    }
+}
@@ -0,0 +1,511 @@
+//! Byte by byte comparison of two SysEx dumps, such as a patch before and after a knob was
+//! turned. The bytes that differ are named by their parameter in the formats of Roland and
+//! Yamaha gear that are known: Roland Data Set (DT1) messages, with the GS parameters by
+//! name and the others by address, Yamaha DX7 voice and bank dumps, and XG parameter
+//! changes
+
+use crate::midi::hex;
+
+/// A byte that differs between two dumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// Offset in the message, `F0` being at 0 as in a `.syx` file
+    pub offset: usize,
+    /// The byte of each dump, none past the end of the shorter one
+    pub a: Option<u8>,
+    pub b: Option<u8>,
+    pub parameter: Option<String>,
+}
+
+/// Compares the data of two SysEx messages, without `F0` and `F7`
+pub fn diff(a: &[u8], b: &[u8]) -> Vec<Difference> {
+    (0..a.len().max(b.len()))
+        .filter(|&i| a.get(i) != b.get(i))
+        .map(|i| Difference {
+            offset: i + 1,
+            a: a.get(i).copied(),
+            b: b.get(i).copied(),
+            parameter: parameter(a, i).or_else(|| parameter(b, i)),
+        })
+        .collect()
+}
+
+/// Describes the differences of two SysEx messages, a line for the sizes and one for each
+/// byte that differs
+pub fn report(a: &[u8], b: &[u8]) -> Vec<String> {
+    let differences = diff(a, b);
+    let format = format(a).or_else(|| format(b)).unwrap_or("Unknown format");
+    if differences.is_empty() {
+        return vec![format!("{}, identical, {} bytes", format, a.len() + 2)];
+    }
+    let mut lines = vec![format!(
+        "{}, {} bytes differ, {} and {} bytes",
+        format,
+        differences.len(),
+        a.len() + 2,
+        b.len() + 2
+    )];
+    let byte = |b: Option<u8>| b.map_or("--".to_string(), |b| format!("{:02X}", b));
+    for d in differences {
+        let line = format!(
+            "{:>6}  {}  {}  {}",
+            d.offset,
+            byte(d.a),
+            byte(d.b),
+            d.parameter.unwrap_or_default()
+        );
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// Names the format of a SysEx message, if it is known
+pub fn format(data: &[u8]) -> Option<&'static str> {
+    if let Some(roland) = Roland::parse(data) {
+        return Some(roland.format);
+    }
+    match data {
+        [0x43, sn, 0x00, 0x01, 0x1B, ..] if sn >> 4 == 0 && data.len() == DX7_VOICE + 6 => {
+            Some("Yamaha DX7 voice dump")
+        }
+        [0x43, sn, 0x09, 0x20, 0x00, ..] if sn >> 4 == 0 && data.len() == DX7_BANK + 6 => {
+            Some("Yamaha DX7 32 voice bank dump")
+        }
+        [0x43, sn, 0x4C, _, _, _, ..] if sn >> 4 == 1 => Some("Yamaha XG parameter change"),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the last byte of a SysEx message is the checksum its format calls for,
+/// the 7 bit complement of the sum of the address and data of Roland Data Set messages and
+/// of the data of DX7 dumps. `None` if the format is not known to have one
+pub fn checksum(data: &[u8]) -> Option<bool> {
+    let (last, covered) = data.split_last()?;
+    let start = match Roland::parse(data) {
+        Some(roland) => roland.command + 1,
+        None if format(data)?.starts_with("Yamaha DX7") => 5,
+        None => return None,
+    };
+    let sum: u32 = covered[start..].iter().map(|b| *b as u32).sum();
+    Some((sum + *last as u32).is_multiple_of(128))
+}
+
+/// Names the parameter of the byte at `index` of the data of a SysEx message, if the
+/// format of the message is known
+pub fn parameter(data: &[u8], index: usize) -> Option<String> {
+    if index >= data.len() {
+        return None;
+    }
+    if let Some(roland) = Roland::parse(data) {
+        return roland.parameter(data, index);
+    }
+    match data {
+        [0x43, sn, format @ (0x00 | 0x09), ..] if sn >> 4 == 0 => {
+            let len = match format {
+                0x00 => DX7_VOICE,
+                _ => DX7_BANK,
+            };
+            if data.len() != len + 6 {
+                return None;
+            }
+            let name = match index {
+                0 => "Manufacturer ID".to_string(),
+                1 => "Substatus and Channel".to_string(),
+                2 => "Format".to_string(),
+                3 | 4 => "Byte Count".to_string(),
+                i if i == data.len() - 1 => "Checksum".to_string(),
+                i if len == DX7_VOICE => dx7_voice(i - 5),
+                i => {
+                    let (voice, offset) = ((i - 5) / 128, (i - 5) % 128);
+                    format!("Voice {} {}", voice + 1, dx7_packed(offset))
+                }
+            };
+            Some(name)
+        }
+        [0x43, sn, 0x4C, a, b, c, ..] if sn >> 4 == 1 => Some(match index {
+            0 => "Manufacturer ID".to_string(),
+            1 => "Substatus and Channel".to_string(),
+            2 => "Model ID".to_string(),
+            3..=5 => "Address".to_string(),
+            i => {
+                let address = add(&[*a, *b, *c], i - 6);
+                format!("XG {} at {}", xg_parameter(&address), hex(&address))
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// Data bytes of a DX7 voice and of a bank of 32 packed voices
+const DX7_VOICE: usize = 155;
+const DX7_BANK: usize = 4096;
+
+/// Parameters of a DX7 operator in a voice dump
+const DX7_OPERATOR: [&str; 21] = [
+    "EG Rate 1",
+    "EG Rate 2",
+    "EG Rate 3",
+    "EG Rate 4",
+    "EG Level 1",
+    "EG Level 2",
+    "EG Level 3",
+    "EG Level 4",
+    "Breakpoint",
+    "Left Depth",
+    "Right Depth",
+    "Left Curve",
+    "Right Curve",
+    "Rate Scaling",
+    "Amp Mod Sensitivity",
+    "Key Velocity Sensitivity",
+    "Output Level",
+    "Oscillator Mode",
+    "Frequency Coarse",
+    "Frequency Fine",
+    "Detune",
+];
+
+/// Parameters of a DX7 voice after its operators, up to its name
+const DX7_COMMON: [&str; 19] = [
+    "Pitch EG Rate 1",
+    "Pitch EG Rate 2",
+    "Pitch EG Rate 3",
+    "Pitch EG Rate 4",
+    "Pitch EG Level 1",
+    "Pitch EG Level 2",
+    "Pitch EG Level 3",
+    "Pitch EG Level 4",
+    "Algorithm",
+    "Feedback",
+    "Oscillator Key Sync",
+    "LFO Speed",
+    "LFO Delay",
+    "LFO Pitch Mod Depth",
+    "LFO Amp Mod Depth",
+    "LFO Key Sync",
+    "LFO Waveform",
+    "Pitch Mod Sensitivity",
+    "Transpose",
+];
+
+/// Parameters of a DX7 operator packed in a bank, several sharing a byte
+const DX7_PACKED_OPERATOR: [&str; 17] = [
+    "EG Rate 1",
+    "EG Rate 2",
+    "EG Rate 3",
+    "EG Rate 4",
+    "EG Level 1",
+    "EG Level 2",
+    "EG Level 3",
+    "EG Level 4",
+    "Breakpoint",
+    "Left Depth",
+    "Right Depth",
+    "Left and Right Curves",
+    "Detune and Rate Scaling",
+    "Key Velocity and Amp Mod Sensitivities",
+    "Output Level",
+    "Frequency Coarse and Oscillator Mode",
+    "Frequency Fine",
+];
+
+/// Parameters of a DX7 voice packed in a bank after its operators, up to its name
+const DX7_PACKED_COMMON: [&str; 16] = [
+    "Pitch EG Rate 1",
+    "Pitch EG Rate 2",
+    "Pitch EG Rate 3",
+    "Pitch EG Rate 4",
+    "Pitch EG Level 1",
+    "Pitch EG Level 2",
+    "Pitch EG Level 3",
+    "Pitch EG Level 4",
+    "Algorithm",
+    "Oscillator Key Sync and Feedback",
+    "LFO Speed",
+    "LFO Delay",
+    "LFO Pitch Mod Depth",
+    "LFO Amp Mod Depth",
+    "Pitch Mod Sensitivity, LFO Waveform, and LFO Key Sync",
+    "Transpose",
+];
+
+/// Names a parameter of a DX7 voice dump by its offset. Operator 6 comes first
+fn dx7_voice(offset: usize) -> String {
+    let operators = 6 * DX7_OPERATOR.len();
+    match offset {
+        i if i < operators => format!(
+            "OP{} {}",
+            6 - i / DX7_OPERATOR.len(),
+            DX7_OPERATOR[i % DX7_OPERATOR.len()]
+        ),
+        i if i < operators + DX7_COMMON.len() => DX7_COMMON[i - operators].to_string(),
+        i => format!("Voice Name {}", i - operators - DX7_COMMON.len() + 1),
+    }
+}
+
+/// Names a parameter of a voice packed in a DX7 bank by its offset in the voice
+fn dx7_packed(offset: usize) -> String {
+    let operators = 6 * DX7_PACKED_OPERATOR.len();
+    match offset {
+        i if i < operators => format!(
+            "OP{} {}",
+            6 - i / DX7_PACKED_OPERATOR.len(),
+            DX7_PACKED_OPERATOR[i % DX7_PACKED_OPERATOR.len()]
+        ),
+        i if i < operators + DX7_PACKED_COMMON.len() => {
+            DX7_PACKED_COMMON[i - operators].to_string()
+        }
+        i => format!("Voice Name {}", i - operators - DX7_PACKED_COMMON.len() + 1),
+    }
+}
+
+/// The layout of a Roland Data Set message: `41 device model.. 12 address data checksum`
+struct Roland {
+    format: &'static str,
+    /// Index of the command
+    command: usize,
+    /// Bytes of the address, which counts data bytes in base 128
+    address: usize,
+    gs: bool,
+}
+
+impl Roland {
+    fn parse(data: &[u8]) -> Option<Roland> {
+        let (format, command, address, gs) = match data {
+            [0x41, _, 0x42, 0x12, ..] => ("Roland GS DT1", 3, 3, true),
+            [0x41, _, 0x45, 0x12, ..] => ("Roland Sound Canvas DT1", 3, 3, false),
+            [0x41, _, 0x16, 0x12, ..] => ("Roland MT-32 DT1", 3, 3, false),
+            [0x41, _, 0x6A, 0x12, ..] => ("Roland JV-1080 DT1", 3, 4, false),
+            [0x41, _, 0x00, 0x10, 0x12, ..] => ("Roland XV-5080 DT1", 4, 4, false),
+            _ => return None,
+        };
+        // At least a data byte and the checksum
+        if data.len() < command + address + 3 {
+            return None;
+        }
+        Some(Roland {
+            format,
+            command,
+            address,
+            gs,
+        })
+    }
+
+    fn parameter(&self, data: &[u8], index: usize) -> Option<String> {
+        let start = self.command + 1 + self.address;
+        let name = match index {
+            0 => "Manufacturer ID".to_string(),
+            1 => "Device ID".to_string(),
+            i if i < self.command => "Model ID".to_string(),
+            i if i == self.command => "Command".to_string(),
+            i if i < start => "Address".to_string(),
+            i if i == data.len() - 1 => "Checksum".to_string(),
+            i => {
+                let address = add(&data[self.command + 1..start], i - start);
+                match gs_parameter(&address).filter(|_| self.gs) {
+                    Some(name) => format!("GS {} at {}", name, hex(&address)),
+                    None => format!("Address {}", hex(&address)),
+                }
+            }
+        };
+        Some(name)
+    }
+}
+
+/// Adds to an address whose bytes are digits in base 128
+fn add(address: &[u8], count: usize) -> Vec<u8> {
+    let mut carry = count;
+    let mut sum = address.to_vec();
+    for byte in sum.iter_mut().rev() {
+        let total = *byte as usize + carry;
+        *byte = (total % 128) as u8;
+        carry = total / 128;
+    }
+    sum
+}
+
+/// Names the GS parameter at an address
+fn gs_parameter(a: &[u8]) -> Option<String> {
+    let name = match *a {
+        [0x40, 0x00, 0x00..=0x03] => "Master Tune".to_string(),
+        [0x40, 0x00, 0x04] => "Master Volume".to_string(),
+        [0x40, 0x00, 0x05] => "Master Key Shift".to_string(),
+        [0x40, 0x00, 0x06] => "Master Pan".to_string(),
+        [0x40, 0x00, 0x7F] => "System Mode Set".to_string(),
+        [0x40, 0x01, 0x00..=0x0F] => "Patch Name".to_string(),
+        [0x40, 0x01, offset @ 0x30..=0x3F] => [
+            "Reverb Macro",
+            "Reverb Character",
+            "Reverb Pre-LPF",
+            "Reverb Level",
+            "Reverb Time",
+            "Reverb Delay Feedback",
+            "",
+            "",
+            "Chorus Macro",
+            "Chorus Pre-LPF",
+            "Chorus Level",
+            "Chorus Feedback",
+            "Chorus Delay",
+            "Chorus Rate",
+            "Chorus Depth",
+            "Chorus Send Level to Reverb",
+        ][offset as usize - 0x30]
+            .to_string(),
+        [0x40, block @ 0x10..=0x1F, offset] => {
+            // The drum part comes first
+            let part = match block & 0x0F {
+                0 => 10,
+                b @ 1..=9 => b,
+                b => b + 1,
+            };
+            format!("Part {} {}", part, gs_part_parameter(offset)?)
+        }
+        _ => return None,
+    };
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Names a parameter of a GS part
+fn gs_part_parameter(offset: u8) -> Option<&'static str> {
+    Some(match offset {
+        0x00 | 0x01 => "Tone Number",
+        0x02 => "Rx Channel",
+        0x13 => "Mono/Poly Mode",
+        0x15 => "Use for Rhythm Part",
+        0x16 => "Pitch Key Shift",
+        0x19 => "Part Level",
+        0x1A => "Velocity Sense Depth",
+        0x1B => "Velocity Sense Offset",
+        0x1C => "Part Pan",
+        0x1D => "Key Range Low",
+        0x1E => "Key Range High",
+        0x21 => "Chorus Send Level",
+        0x22 => "Reverb Send Level",
+        0x30 => "Vibrato Rate",
+        0x31 => "Vibrato Depth",
+        0x32 => "TVF Cutoff Frequency",
+        0x33 => "TVF Resonance",
+        0x34 => "TVF and TVA Envelope Attack",
+        0x35 => "TVF and TVA Envelope Decay",
+        0x36 => "TVF and TVA Envelope Release",
+        0x37 => "Vibrato Delay",
+        _ => return None,
+    })
+}
+
+/// Names the XG parameter at an address
+fn xg_parameter(a: &[u8]) -> String {
+    match *a {
+        [0x00, 0x00, 0x00..=0x03] => "Master Tune".to_string(),
+        [0x00, 0x00, 0x04] => "Master Volume".to_string(),
+        [0x00, 0x00, 0x05] => "Master Attenuator".to_string(),
+        [0x00, 0x00, 0x06] => "Master Transpose".to_string(),
+        [0x02, 0x01, 0x00] => "Reverb Type".to_string(),
+        [0x02, 0x01, 0x20] => "Chorus Type".to_string(),
+        [0x02, 0x01, 0x40] => "Variation Type".to_string(),
+        [0x08, part, offset] => {
+            let name = match offset {
+                0x01 => "Bank Select MSB",
+                0x02 => "Bank Select LSB",
+                0x03 => "Program Number",
+                0x04 => "Rcv Channel",
+                0x05 => "Mono/Poly Mode",
+                0x07 => "Part Mode",
+                0x08 => "Note Shift",
+                0x0B => "Volume",
+                0x0E => "Pan",
+                0x11 => "Dry Level",
+                0x12 => "Chorus Send",
+                0x13 => "Reverb Send",
+                0x14 => "Variation Send",
+                0x15 => "Vibrato Rate",
+                0x16 => "Vibrato Depth",
+                0x17 => "Vibrato Delay",
+                0x18 => "Filter Cutoff Frequency",
+                0x19 => "Filter Resonance",
+                0x1A => "EG Attack Time",
+                0x1B => "EG Decay Time",
+                0x1C => "EG Release Time",
+                _ => "parameter",
+            };
+            format!("Part {} {}", part + 1, name)
+        }
+        _ => "parameter".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_roland_addresses() {
+        // Part 1 Velocity Sense Offset and Part Pan, from 40 11 1B
+        let a = [0x41, 0x10, 0x42, 0x12, 0x40, 0x11, 0x1B, 0x40, 0x40, 0x14];
+        let b = [0x41, 0x10, 0x42, 0x12, 0x40, 0x11, 0x1B, 0x40, 0x20, 0x34];
+        assert_eq!(format(&a), Some("Roland GS DT1"));
+        assert_eq!(
+            diff(&a, &b),
+            [
+                Difference {
+                    offset: 9,
+                    a: Some(0x40),
+                    b: Some(0x20),
+                    parameter: Some("GS Part 1 Part Pan at 40 11 1C".to_string()),
+                },
+                Difference {
+                    offset: 10,
+                    a: Some(0x14),
+                    b: Some(0x34),
+                    parameter: Some("Checksum".to_string()),
+                },
+            ]
+        );
+        // Addresses carry over in base 128
+        assert_eq!(add(&[0x40, 0x10, 0x7F], 2), [0x40, 0x11, 0x01]);
+        let jv = [0x41, 0x10, 0x6A, 0x12, 0x03, 0x00, 0x00, 0x0C, 0x01, 0x70];
+        assert_eq!(parameter(&jv, 8).unwrap(), "Address 03 00 00 0C");
+    }
+
+    #[test]
+    fn checks_checksums() {
+        let gs_reset = [0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41];
+        assert_eq!(checksum(&gs_reset), Some(true));
+        let mut wrong = gs_reset;
+        wrong[8] = 0x42;
+        assert_eq!(checksum(&wrong), Some(false));
+        let mut voice = vec![0x43, 0x00, 0x00, 0x01, 0x1B];
+        voice.extend([1; DX7_VOICE]);
+        voice.push((256 - DX7_VOICE) as u8);
+        assert_eq!(checksum(&voice), Some(true));
+        // Identity Request
+        assert_eq!(checksum(&[0x7E, 0x7F, 0x06, 0x01]), None);
+    }
+
+    #[test]
+    fn names_dx7_parameters() {
+        let mut voice = vec![0x43, 0x00, 0x00, 0x01, 0x1B];
+        voice.extend([0; DX7_VOICE]);
+        voice.push(0);
+        let mut changed = voice.clone();
+        changed[5 + 16] = 99;
+        changed[5 + 6 * 21 + 8] = 31;
+        changed.push(0x10);
+        let report = report(&voice, &changed);
+        assert_eq!(
+            report,
+            [
+                "Yamaha DX7 voice dump, 3 bytes differ, 163 and 164 bytes",
+                "    22  00  63  OP6 Output Level",
+                "   140  00  1F  Algorithm",
+                "   162  --  10",
+            ]
+        );
+        assert_eq!(dx7_voice(150), "Voice Name 6");
+        assert_eq!(dx7_packed(17 + 14), "OP5 Output Level");
+        assert_eq!(dx7_packed(111), "Oscillator Key Sync and Feedback");
+        let xg = [0x43, 0x10, 0x4C, 0x08, 0x00, 0x0B, 0x64];
+        assert_eq!(parameter(&xg, 6).unwrap(), "XG Part 1 Volume at 08 00 0B");
+    }
+}
@@ -0,0 +1,38 @@
+//! Checks against the General MIDI Level 1 specification
+
+use crate::midi::{MidiAnalysis, MidiMessage};
+
+/// Channel reserved for percussion (channel 10)
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// Range of notes with an assigned GM percussion sound
+const PERCUSSION_NOTES: std::ops::RangeInclusive<u8> = 35..=81;
+
+/// Controllers that GM Level 1 devices are required to respond to
+const GM_CONTROLLERS: [u8; 9] = [1, 6, 7, 10, 11, 38, 64, 100, 101];
+
+/// Returns an analysis if the message is outside of what a GM Level 1 device supports
+pub fn check(message: &MidiMessage) -> Option<MidiAnalysis> {
+    match *message {
+        MidiMessage::NoteOn { channel, note, .. } | MidiMessage::NoteOff { channel, note, .. }
+            if channel == PERCUSSION_CHANNEL && !PERCUSSION_NOTES.contains(&note) =>
+        {
+            Some(MidiAnalysis::Warning(format!(
+                "GM: Note {} has no percussion sound assigned on channel 10",
+                note
+            )))
+        }
+        MidiMessage::ProgramChange { channel, .. } if channel == PERCUSSION_CHANNEL => {
+            Some(MidiAnalysis::Info(
+                "GM: Program Change is ignored on the percussion channel".to_string(),
+            ))
+        }
+        MidiMessage::ControlChange { control, .. } if !GM_CONTROLLERS.contains(&control) => {
+            Some(MidiAnalysis::Info(format!(
+                "GM: Controller {} is not required by General MIDI",
+                control
+            )))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,113 @@
+//! Histograms of the time between consecutive messages and between Timing Clocks
+//!
+//! Timing problems show up as peaks where there should be none: a USB interface that
+//! buffers messages and sends them every few milliseconds makes a peak near zero, for the
+//! messages of a burst, and another at the buffering period
+
+use crate::{capture::CaptureEvent, midi::MidiMessage};
+use std::time::Duration;
+
+/// Upper bounds of the buckets in microseconds, halfway between the round intervals they
+/// are named after on a logarithmic scale. The last bucket has no bound
+const BOUNDS: [u64; 12] = [
+    150, 300, 700, 1_500, 3_000, 7_000, 15_000, 30_000, 70_000, 150_000, 300_000, 700_000,
+];
+
+/// Names of the buckets, the interval each is centered on
+pub const LABELS: [&str; 13] = [
+    "~0.1 ms", "~0.2 ms", "~0.5 ms", "~1 ms", "~2 ms", "~5 ms", "~10 ms", "~20 ms", "~50 ms",
+    "~100 ms", "~200 ms", "~500 ms", ">0.7 s",
+];
+
+/// Counts of intervals in buckets of `LABELS`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    counts: [usize; LABELS.len()],
+}
+
+impl Histogram {
+    /// Counts an interval in its bucket
+    pub fn add(&mut self, interval: Duration) {
+        let micros = interval.as_micros();
+        let bucket = BOUNDS.partition_point(|bound| *bound as u128 <= micros);
+        self.counts[bucket] += 1;
+    }
+
+    /// Returns the count of each bucket of `LABELS`
+    pub fn counts(&self) -> &[usize; LABELS.len()] {
+        &self.counts
+    }
+
+    /// Returns the number of intervals counted
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+/// Intervals between the messages of a capture, Timing Clocks apart
+#[derive(Debug, Clone, Default)]
+pub struct IntervalAnalyzer {
+    /// Intervals between consecutive messages other than Timing Clock
+    pub messages: Histogram,
+    /// Intervals between consecutive Timing Clocks
+    pub clocks: Histogram,
+    last_message: Option<Duration>,
+    last_clock: Option<Duration>,
+}
+
+impl IntervalAnalyzer {
+    /// Creates an analyzer that has not seen any messages
+    pub fn new() -> IntervalAnalyzer {
+        IntervalAnalyzer::default()
+    }
+
+    /// Counts the interval since the last message of the same kind, if the event completes
+    /// a message
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        let (histogram, last) = match event.message {
+            Some(MidiMessage::TimingClock) => (&mut self.clocks, &mut self.last_clock),
+            Some(_) => (&mut self.messages, &mut self.last_message),
+            None => return,
+        };
+        if let Some(last) = last.replace(event.time) {
+            histogram.add(event.time.saturating_sub(last));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn buckets_intervals() {
+        let mut histogram = Histogram::default();
+        for micros in [0, 149, 150, 4_000, 999_000_000] {
+            histogram.add(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.counts()[0], 2);
+        assert_eq!(histogram.counts()[1], 1);
+        assert_eq!(LABELS[5], "~5 ms");
+        assert_eq!(histogram.counts()[5], 1);
+        assert_eq!(histogram.counts()[12], 1);
+        assert_eq!(histogram.total(), 5);
+
+        // Bursts every 4 ms, and clocks at 120 BPM in between
+        let mut capture = Capture::new();
+        let mut intervals = IntervalAnalyzer::new();
+        for burst in 0..10u64 {
+            let time = Duration::from_millis(burst * 4);
+            for byte in [0x90, 0x3C, 0x64, 0x80, 0x3C, 0x00] {
+                intervals.observe(&capture.process(time, byte));
+            }
+        }
+        for clock in 0..5u64 {
+            let time = Duration::from_micros(clock * 20_833);
+            intervals.observe(&capture.process(time, 0xF8));
+        }
+        assert_eq!(intervals.messages.counts()[0], 10);
+        assert_eq!(intervals.messages.counts()[5], 9);
+        assert_eq!(intervals.clocks.counts()[7], 4);
+    }
+}
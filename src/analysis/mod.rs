@@ -0,0 +1,70 @@
+//! Analyzers that look at the capture as a whole rather than byte by byte
+
+pub mod align;
+pub mod channels;
+pub mod clock;
+pub mod diff;
+mod gm;
+pub mod intervals;
+pub mod mpe;
+pub mod rate;
+mod settings;
+pub mod smoothness;
+pub mod snapshot;
+pub mod stats;
+pub mod summary;
+pub mod sysex;
+pub mod watch;
+
+pub use settings::{Settings, Strictness};
+
+use crate::{
+    analysis::{clock::ClockAnalyzer, stats::Statistics},
+    capture::{Capture, CaptureEvent, SENT},
+};
+use std::{
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Result of running the analyzers again over an existing capture
+pub struct Reanalysis {
+    /// The settings the capture was analyzed with
+    pub settings: Settings,
+    /// Newly analyzed events, one per event given
+    pub events: Vec<CaptureEvent>,
+    /// Capture state after the last byte, ready to continue with newer bytes
+    pub capture: Capture,
+    pub clock: ClockAnalyzer,
+    pub stats: Statistics,
+}
+
+/// Analyzes the bytes of the given events again with new settings on a background thread.
+/// Bytes miditerm sent itself are parsed but not counted, and notices are kept as they are
+pub fn reanalyze(events: Vec<CaptureEvent>, settings: Settings) -> Receiver<Reanalysis> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut capture = Capture::with_settings(settings);
+        let mut clock = ClockAnalyzer::new();
+        let mut stats = Statistics::new();
+        let events = events
+            .iter()
+            .map(|event| {
+                let event = capture.reprocess(event);
+                if event.source != SENT && !event.notice {
+                    clock.observe(&event);
+                    stats.observe(&event);
+                }
+                event
+            })
+            .collect();
+        let _ = tx.send(Reanalysis {
+            settings,
+            events,
+            capture,
+            clock,
+            stats,
+        });
+    });
+    rx
+}
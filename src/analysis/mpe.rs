@@ -0,0 +1,188 @@
+//! Gestures of the notes of MIDI Polyphonic Expression controllers, which give every
+//! sounding note a channel of its own so its Pitch Bend, Channel Pressure, and CC74 shape
+//! that note alone
+
+use crate::{capture::CaptureEvent, midi::MidiMessage};
+
+/// Most notes followed at once. Released notes are dropped first to make room
+const MAX_NOTES: usize = 8;
+/// Most values kept of each gesture
+const MAX_SAMPLES: usize = 256;
+/// Pitch Bend range of MPE member channels in semitones
+pub const BEND_RANGE: f64 = 48.0;
+/// Control Change of the third dimension of MPE, often timbre or slide
+const TIMBRE: u8 = 74;
+
+/// Last values received on a channel, which the next note on it starts from
+#[derive(Debug, Clone, Copy)]
+struct Expression {
+    bend: u16,
+    pressure: u8,
+    timbre: u8,
+}
+
+impl Default for Expression {
+    fn default() -> Self {
+        Expression {
+            bend: 8192,
+            pressure: 0,
+            timbre: 64,
+        }
+    }
+}
+
+/// The gesture of one note
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpeNote {
+    /// Channel from 0 to 15
+    pub channel: u8,
+    pub note: u8,
+    pub velocity: u8,
+    pub released: bool,
+    /// Pitch Bend values from 0 to 16383
+    pub bend: Vec<u16>,
+    /// Channel Pressure values
+    pub pressure: Vec<u8>,
+    /// CC74 values
+    pub timbre: Vec<u8>,
+}
+
+impl MpeNote {
+    /// Returns the current Pitch Bend of the note in semitones
+    pub fn semitones(&self) -> f64 {
+        let bend = *self.bend.last().unwrap_or(&8192) as f64;
+        (bend - 8192.0) / 8192.0 * BEND_RANGE
+    }
+}
+
+/// Follows the gestures of the most recent notes
+#[derive(Debug, Default)]
+pub struct MpeTracker {
+    channels: [Expression; 16],
+    /// Most recent note first
+    notes: Vec<MpeNote>,
+}
+
+impl MpeTracker {
+    /// Creates a tracker that has not seen any notes
+    pub fn new() -> MpeTracker {
+        MpeTracker::default()
+    }
+
+    /// Returns the followed notes, most recent first
+    pub fn notes(&self) -> &[MpeNote] {
+        &self.notes
+    }
+
+    /// Updates the gestures with the next event of the capture
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        match event.message {
+            Some(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }) if velocity > 0 => {
+                let expression = self.channels[channel as usize];
+                self.notes.insert(
+                    0,
+                    MpeNote {
+                        channel,
+                        note,
+                        velocity,
+                        released: false,
+                        bend: vec![expression.bend],
+                        pressure: vec![expression.pressure],
+                        timbre: vec![expression.timbre],
+                    },
+                );
+                if self.notes.len() > MAX_NOTES {
+                    let oldest = match self.notes.iter().rposition(|n| n.released) {
+                        Some(i) => i,
+                        None => self.notes.len() - 1,
+                    };
+                    self.notes.remove(oldest);
+                }
+            }
+            Some(MidiMessage::NoteOn { channel, note, .. })
+            | Some(MidiMessage::NoteOff { channel, note, .. }) => {
+                if let Some(held) = self
+                    .notes
+                    .iter_mut()
+                    .find(|n| !n.released && n.channel == channel && n.note == note)
+                {
+                    held.released = true;
+                }
+            }
+            Some(MidiMessage::PitchBend { channel, value }) => {
+                self.channels[channel as usize].bend = value;
+                for note in self.held(channel) {
+                    push(&mut note.bend, value);
+                }
+            }
+            Some(MidiMessage::ChannelPressure { channel, pressure }) => {
+                self.channels[channel as usize].pressure = pressure;
+                for note in self.held(channel) {
+                    push(&mut note.pressure, pressure);
+                }
+            }
+            Some(MidiMessage::ControlChange {
+                channel,
+                control: TIMBRE,
+                value,
+            }) => {
+                self.channels[channel as usize].timbre = value;
+                for note in self.held(channel) {
+                    push(&mut note.timbre, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the notes held on a channel, which its expression applies to
+    fn held(&mut self, channel: u8) -> impl Iterator<Item = &mut MpeNote> {
+        self.notes
+            .iter_mut()
+            .filter(move |n| !n.released && n.channel == channel)
+    }
+}
+
+/// Adds a value to a gesture, forgetting the oldest values once it is full
+fn push<T>(values: &mut Vec<T>, value: T) {
+    if values.len() == MAX_SAMPLES {
+        values.remove(0);
+    }
+    values.push(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn notes_follow_their_channel() {
+        let mut capture = Capture::new();
+        let mut tracker = MpeTracker::new();
+        let bytes = [
+            0xD1, 20, // Pressure before the note is its starting value
+            0x91, 60, 100, 0x92, 64, 90, // Two notes on their own channels
+            0xE1, 0x00, 0x50, 0xD1, 80, 0xB2, 74, 100, // Gestures of each
+            0x81, 60, 0, 0xE1, 0x00, 0x40, // Released notes no longer move
+        ];
+        for byte in bytes {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let [second, first] = tracker.notes() else {
+            panic!("two notes expected");
+        };
+        assert_eq!(first.note, 60);
+        assert!(first.released);
+        assert_eq!(first.pressure, [20, 80]);
+        assert_eq!(first.bend, [8192, 10240]);
+        assert_eq!(first.semitones(), 12.0);
+        assert_eq!(second.timbre, [64, 100]);
+        assert_eq!(second.bend, [8192]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Messages per second and the share of the 31.25 kbaud MIDI bus taken over the last
+//! minute, for seeing why timing falls apart on dense sequences
+
+use crate::capture::CaptureEvent;
+use std::{collections::VecDeque, time::Duration};
+
+/// Seconds of history kept
+pub const HISTORY: u64 = 60;
+/// Bytes a MIDI DIN bus carries in a second: 31250 baud, 10 bits a byte
+const BUS_BYTES_PER_SECOND: f64 = 3125.0;
+/// Share of the bus past which it is close to saturation
+const SATURATION: f64 = 0.8;
+/// Seconds in a row the bus must be close to saturation before it is reported
+const SUSTAINED: usize = 3;
+
+/// What was received in one second of the capture
+#[derive(Debug, Clone, Default)]
+struct Second {
+    /// Seconds since the start of the capture
+    second: u64,
+    messages: u32,
+    /// Bytes of each input, as each has a bus of its own
+    bytes: Vec<u32>,
+}
+
+/// Counts of the last minute of the capture
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    /// Oldest first, only the seconds anything was received in
+    seconds: VecDeque<Second>,
+}
+
+/// Messages and bus use of one second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub messages: u32,
+    /// Share of the bus taken by the busiest input, from 0, where 1 is saturated
+    pub utilization: f64,
+}
+
+impl RateMeter {
+    pub fn new() -> RateMeter {
+        RateMeter::default()
+    }
+
+    /// Counts the next event of the capture
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        let second = event.time.as_secs();
+        if self.seconds.back().is_none_or(|s| s.second < second) {
+            self.seconds.push_back(Second {
+                second,
+                ..Second::default()
+            });
+        }
+        while self
+            .seconds
+            .front()
+            .is_some_and(|s| s.second + HISTORY <= second)
+        {
+            self.seconds.pop_front();
+        }
+        // Events are in time order, so late ones are counted in the latest second
+        let Some(last) = self.seconds.back_mut() else {
+            return;
+        };
+        if event.message.is_some() {
+            last.messages += 1;
+        }
+        let source = event.source as usize;
+        if last.bytes.len() <= source {
+            last.bytes.resize(source + 1, 0);
+        }
+        last.bytes[source] += 1;
+    }
+
+    /// Returns the rates of the complete seconds of the last minute before `now`, oldest
+    /// first
+    pub fn rates(&self, now: Duration) -> Vec<Rate> {
+        let end = now.as_secs();
+        let start = end.saturating_sub(HISTORY);
+        let mut rates = vec![
+            Rate {
+                messages: 0,
+                utilization: 0.0
+            };
+            (end - start) as usize
+        ];
+        for s in self
+            .seconds
+            .iter()
+            .filter(|s| (start..end).contains(&s.second))
+        {
+            let busiest = s.bytes.iter().max().copied().unwrap_or(0);
+            rates[(s.second - start) as usize] = Rate {
+                messages: s.messages,
+                utilization: busiest as f64 / BUS_BYTES_PER_SECOND,
+            };
+        }
+        rates
+    }
+
+    /// Returns the lowest utilization of the last few complete seconds if all of them came
+    /// close to saturating the bus
+    pub fn saturation(&self, now: Duration) -> Option<f64> {
+        let rates = self.rates(now);
+        let recent = rates.get(rates.len().checked_sub(SUSTAINED)?..)?;
+        let lowest = recent
+            .iter()
+            .map(|rate| rate.utilization)
+            .fold(f64::INFINITY, f64::min);
+        (lowest >= SATURATION).then_some(lowest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn rates_and_saturation() {
+        let mut capture = Capture::new();
+        let mut meter = RateMeter::new();
+        // Clock at 120 BPM for a second, then three seconds of a saturated bus
+        for i in 0..48 {
+            meter.observe(&capture.process(Duration::from_millis(i * 20), 0xF8));
+        }
+        for second in 1..4 {
+            for i in 0..3000 {
+                let time = Duration::from_secs(second) + Duration::from_micros(i * 320);
+                meter.observe(&capture.process(time, 0xFE));
+            }
+        }
+        let rates = meter.rates(Duration::from_millis(4500));
+        assert_eq!(rates.len(), 4);
+        assert_eq!(rates[0].messages, 48);
+        assert!((rates[0].utilization - 48.0 / 3125.0).abs() < 1e-9);
+        assert_eq!(rates[3].messages, 3000);
+        assert_eq!(meter.saturation(Duration::from_secs(4)), Some(0.96));
+        assert_eq!(meter.saturation(Duration::from_secs(3)), None);
+
+        // A minute later the counts are gone
+        let rates = meter.rates(Duration::from_secs(70));
+        assert_eq!(rates.len(), HISTORY as usize);
+        assert!(rates.iter().all(|rate| rate.messages == 0));
+    }
+}
@@ -0,0 +1,124 @@
+//! User adjustable analysis settings
+
+use crate::{
+    analysis::gm,
+    midi::{notes::NoteNaming, MidiAnalysis, MidiMessage},
+};
+use anyhow::bail;
+use std::{fmt, str::FromStr};
+
+/// How harshly questionable MIDI is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Warnings are reported as informational
+    Lenient,
+    /// Analyses are reported as the parser produced them
+    #[default]
+    Normal,
+    /// Warnings are reported as violations
+    Strict,
+}
+
+impl Strictness {
+    /// Returns the next level, wrapping around
+    pub fn next(self) -> Strictness {
+        match self {
+            Strictness::Lenient => Strictness::Normal,
+            Strictness::Normal => Strictness::Strict,
+            Strictness::Strict => Strictness::Lenient,
+        }
+    }
+}
+
+impl fmt::Display for Strictness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strictness::Lenient => "lenient",
+            Strictness::Normal => "normal",
+            Strictness::Strict => "strict",
+        })
+    }
+}
+
+impl FromStr for Strictness {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lenient" => Ok(Strictness::Lenient),
+            "normal" => Ok(Strictness::Normal),
+            "strict" => Ok(Strictness::Strict),
+            _ => bail!("Unknown strictness `{}`", s),
+        }
+    }
+}
+
+/// Settings that change how the capture is analyzed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Settings {
+    pub strictness: Strictness,
+    /// Check messages against the General MIDI Level 1 specification
+    pub gm: bool,
+    /// How notes are named in the analysis
+    pub naming: NoteNaming,
+}
+
+impl Settings {
+    /// Adjusts the parser's analysis of a byte according to the settings
+    pub fn review(&self, message: Option<&MidiMessage>, analysis: MidiAnalysis) -> MidiAnalysis {
+        let analysis = match (self.gm, message, &analysis) {
+            // Issues found by the parser take precedence over GM remarks
+            (true, Some(message), MidiAnalysis::Comment(_) | MidiAnalysis::Info(_)) => {
+                gm::check(message).unwrap_or(analysis)
+            }
+            _ => analysis,
+        };
+        match (self.strictness, analysis) {
+            (Strictness::Lenient, MidiAnalysis::Warning(s)) => MidiAnalysis::Info(s),
+            (Strictness::Strict, MidiAnalysis::Warning(s)) => MidiAnalysis::Violation(s),
+            (_, analysis) => analysis,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strictness() {
+        let warning = || MidiAnalysis::Warning("Orphaned data byte".to_string());
+        let mut settings = Settings::default();
+        assert_eq!(settings.review(None, warning()), warning());
+        settings.strictness = Strictness::Lenient;
+        assert!(matches!(
+            settings.review(None, warning()),
+            MidiAnalysis::Info(_)
+        ));
+        settings.strictness = Strictness::Strict;
+        assert!(matches!(
+            settings.review(None, warning()),
+            MidiAnalysis::Violation(_)
+        ));
+    }
+
+    #[test]
+    fn general_midi() {
+        let note = MidiMessage::NoteOn {
+            channel: 9,
+            note: 20,
+            velocity: 100,
+        };
+        let comment = MidiAnalysis::Comment("Note On".to_string());
+        let settings = Settings::default();
+        assert_eq!(settings.review(Some(&note), comment.clone()), comment);
+        let settings = Settings {
+            gm: true,
+            ..Settings::default()
+        };
+        assert!(matches!(
+            settings.review(Some(&note), comment),
+            MidiAnalysis::Warning(_)
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+//! Smoothness of continuous controller streams
+//!
+//! A controller that moves in coarse steps is heard as stair-stepping, or zipper noise,
+//! whose energy sits at the rate the value is updated. Coarse steps sent rarely are the
+//! worst case, while fine steps sent often blur into a continuous sweep. Each Control
+//! Change and Pitch Bend stream is scored by the size of its steps, weighted by how
+//! slowly they arrive
+
+use crate::{capture::CaptureEvent, midi::MidiMessage};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    time::Duration,
+};
+
+/// Updates further apart than this belong to separate gestures, so the pause between them
+/// does not lower the update rate
+const GESTURE_GAP: Duration = Duration::from_millis(100);
+
+/// Update rate in Hz at which steps are too frequent to be heard as steps
+const SMOOTH_RATE: f64 = 200.0;
+
+/// Points lost per percent of full scale RMS step at `SMOOTH_RATE` or faster
+const STEP_PENALTY: f64 = 10.0;
+
+/// Changes needed before a stream is scored
+const MIN_CHANGES: usize = 8;
+
+/// A continuous controller of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Controller {
+    Control(u8),
+    PitchBend,
+}
+
+impl fmt::Display for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Controller::Control(control) => write!(f, "CC {}", control),
+            Controller::PitchBend => write!(f, "Pitch Bend"),
+        }
+    }
+}
+
+/// Smoothness of one controller stream. The score ranges from 0 (coarse stair-steps) to
+/// 100 (indistinguishable from a continuous sweep)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothness {
+    /// Channel from 0 to 15
+    pub channel: u8,
+    pub controller: Controller,
+    pub score: u8,
+    /// Root mean square of the steps between consecutive values in percent of full scale
+    pub step: f64,
+    /// Resolution implied by the smallest step, in bits
+    pub resolution: f64,
+    /// Updates per second while the controller moves, if their timing is known
+    pub rate: Option<f64>,
+    /// Number of value changes
+    pub changes: usize,
+}
+
+impl fmt::Display for Smoothness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Ch {} {}: smoothness {} (steps {:.2}% RMS, {:.1} bits",
+            self.channel + 1,
+            self.controller,
+            self.score,
+            self.step,
+            self.resolution
+        )?;
+        if let Some(rate) = self.rate {
+            write!(f, ", {:.0} Hz", rate)?;
+        }
+        write!(f, ", {} changes)", self.changes)
+    }
+}
+
+/// Step sizes and update rate of one controller stream
+#[derive(Debug, Default)]
+struct Stream {
+    /// Time and value of the last update
+    last: Option<(Duration, u16)>,
+    /// Number of values in the full scale of the controller
+    scale: f64,
+    changes: usize,
+    /// Sum of the squared steps in percent of full scale
+    squares: f64,
+    smallest: Option<u16>,
+    /// Distinct values sent, to tell switches from continuous controllers
+    values: BTreeSet<u16>,
+    /// Time spent within gestures and the number of updates it covers
+    moving: Duration,
+    intervals: usize,
+}
+
+impl Stream {
+    fn update(&mut self, time: Duration, value: u16) {
+        self.values.insert(value);
+        let Some((last_time, last_value)) = self.last.replace((time, value)) else {
+            return;
+        };
+        let interval = time.saturating_sub(last_time);
+        if interval <= GESTURE_GAP {
+            self.moving += interval;
+            self.intervals += 1;
+        }
+        let step = value.abs_diff(last_value);
+        if step == 0 {
+            return;
+        }
+        let percent = 100.0 * step as f64 / self.scale;
+        self.squares += percent * percent;
+        self.changes += 1;
+        self.smallest = Some(self.smallest.map_or(step, |s| s.min(step)));
+    }
+
+    fn smoothness(&self, channel: u8, controller: Controller) -> Option<Smoothness> {
+        // Switches such as sustain pedals only ever jump between two values
+        if self.changes < MIN_CHANGES || self.values.len() <= 2 {
+            return None;
+        }
+        let step = (self.squares / self.changes as f64).sqrt();
+        let rate =
+            (!self.moving.is_zero()).then(|| self.intervals as f64 / self.moving.as_secs_f64());
+        // Slow updates spread the same steps over audible frequencies
+        let slowness = rate.map_or(1.0, |rate| (SMOOTH_RATE / rate).max(1.0));
+        let score = (100.0 - step * slowness * STEP_PENALTY).clamp(0.0, 100.0);
+        Some(Smoothness {
+            channel,
+            controller,
+            score: score.round() as u8,
+            step,
+            resolution: (self.scale / self.smallest? as f64).log2(),
+            rate,
+            changes: self.changes,
+        })
+    }
+}
+
+/// Measures the stair-stepping of every Control Change and Pitch Bend stream of a capture
+#[derive(Debug, Default)]
+pub struct SmoothnessAnalyzer {
+    streams: BTreeMap<(u8, Controller), Stream>,
+}
+
+impl SmoothnessAnalyzer {
+    /// Creates an analyzer that has not seen any controllers
+    pub fn new() -> SmoothnessAnalyzer {
+        SmoothnessAnalyzer::default()
+    }
+
+    /// Updates the analysis with the next event of the capture
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        let (channel, controller, value, scale) = match event.message {
+            Some(MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            }) => (channel, Controller::Control(control), value as u16, 128.0),
+            Some(MidiMessage::PitchBend { channel, value }) => {
+                (channel, Controller::PitchBend, value, 16384.0)
+            }
+            _ => return,
+        };
+        self.streams
+            .entry((channel, controller))
+            .or_insert_with(|| Stream {
+                scale,
+                ..Stream::default()
+            })
+            .update(event.time, value);
+    }
+
+    /// Returns the smoothness of every stream that moved enough to be judged
+    pub fn reports(&self) -> Vec<Smoothness> {
+        self.streams
+            .iter()
+            .filter_map(|((channel, controller), stream)| stream.smoothness(*channel, *controller))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    /// Analyzes messages sent every `interval` microseconds
+    fn analyze(messages: &[Vec<u8>], interval: u64) -> Vec<Smoothness> {
+        let mut capture = Capture::new();
+        let mut analyzer = SmoothnessAnalyzer::new();
+        for (n, message) in messages.iter().enumerate() {
+            let time = Duration::from_micros(n as u64 * interval);
+            for byte in message {
+                analyzer.observe(&capture.process(time, *byte));
+            }
+        }
+        analyzer.reports()
+    }
+
+    #[test]
+    fn fine_fast_steps_are_smooth() {
+        // A 14-bit pitch bend sweep at 500 Hz
+        let sweep: Vec<Vec<u8>> = (0..100_u16)
+            .map(|n| {
+                let value = 8192 + n * 16;
+                vec![0xE0, (value & 0x7F) as u8, (value >> 7) as u8]
+            })
+            .collect();
+        let reports = analyze(&sweep, 2_000);
+        assert_eq!(reports.len(), 1);
+        let report = reports[0];
+        assert_eq!(report.controller, Controller::PitchBend);
+        assert!(report.score >= 95, "{}", report);
+        assert!((report.resolution - 10.0).abs() < 1e-9);
+        assert!((report.rate.unwrap() - 500.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn coarse_slow_steps_zipper() {
+        // CC 74 moving in steps of 8 at 20 Hz
+        let sweep: Vec<Vec<u8>> = (0..16).map(|n| vec![0xB1, 74, n * 8]).collect();
+        let reports = analyze(&sweep, 50_000);
+        assert_eq!(reports.len(), 1);
+        let report = reports[0];
+        assert_eq!(
+            (report.channel, report.controller),
+            (1, Controller::Control(74))
+        );
+        assert!(report.score < 20, "{}", report);
+        assert!((report.resolution - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn switches_are_not_scored() {
+        let pedal: Vec<Vec<u8>> = (0..20).map(|n| vec![0xB0, 64, (n % 2) * 127]).collect();
+        assert!(analyze(&pedal, 100_000).is_empty());
+    }
+}
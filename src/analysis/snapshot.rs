@@ -0,0 +1,195 @@
+//! The state of every channel kept at one moment, and compared with a later one to see what
+//! a SysEx dump, a reset, or a patch change actually changed
+
+use crate::{
+    config::Names,
+    midi::{controls, notes::NoteNaming},
+};
+use std::{collections::BTreeMap, fmt};
+
+/// The values of the channels received up to a moment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Value of each controller by channel from 0 to 15 and controller number
+    pub controls: BTreeMap<(u8, u8), u8>,
+    /// Last Program Change of each channel
+    pub programs: [Option<u8>; 16],
+    /// Bank Select MSB and LSB of each channel as one 14 bit number
+    pub banks: [Option<u16>; 16],
+    /// Pitch Bend of each channel from 0 to 16383
+    pub bends: [Option<u16>; 16],
+    /// Channel Pressure of each channel
+    pub pressures: [Option<u8>; 16],
+    /// A bit per note of each channel, set while the note is held
+    pub held: [u128; 16],
+}
+
+/// What part of the state of a channel changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Program,
+    Bank,
+    Bend,
+    Pressure,
+    Control(u8),
+    /// A held note has the value 1, a released one none
+    Note(u8),
+}
+
+/// A value of a channel that is not the same in both snapshots, none where it was never
+/// received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    /// Channel from 0 to 15
+    pub channel: u8,
+    pub part: Part,
+    pub before: Option<u16>,
+    pub after: Option<u16>,
+}
+
+/// Compares two snapshots, channel by channel, with the sound first, then the controllers,
+/// then the notes
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let mut changes = vec![];
+    for channel in 0..16u8 {
+        let c = channel as usize;
+        let mut push = |part, before: Option<u16>, after: Option<u16>| {
+            if before != after {
+                changes.push(Change {
+                    channel,
+                    part,
+                    before,
+                    after,
+                });
+            }
+        };
+        push(
+            Part::Program,
+            before.programs[c].map(u16::from),
+            after.programs[c].map(u16::from),
+        );
+        push(Part::Bank, before.banks[c], after.banks[c]);
+        push(Part::Bend, before.bends[c], after.bends[c]);
+        push(
+            Part::Pressure,
+            before.pressures[c].map(u16::from),
+            after.pressures[c].map(u16::from),
+        );
+        for control in 0..128 {
+            let value = |snapshot: &Snapshot| {
+                snapshot
+                    .controls
+                    .get(&(channel, control))
+                    .map(|v| *v as u16)
+            };
+            push(Part::Control(control), value(before), value(after));
+        }
+        for note in 0..128u8 {
+            let held = |snapshot: &Snapshot| (snapshot.held[c] & (1 << note) != 0).then_some(1);
+            push(Part::Note(note), held(before), held(after));
+        }
+    }
+    changes
+}
+
+/// A value of a change as it is shown, `-` where it was never received
+struct Value(Part, Option<u16>);
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1) {
+            (Part::Note(_), Some(_)) => write!(f, "held"),
+            (Part::Note(_), None) => write!(f, "released"),
+            (_, None) => write!(f, "-"),
+            (Part::Bend, Some(value)) => write!(f, "{:+}", value as i32 - 8192),
+            (_, Some(value)) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Describes the changes from one snapshot to another, a line for their number and one for
+/// each change, with the controllers and notes named
+pub fn report(
+    before: &Snapshot,
+    after: &Snapshot,
+    names: &Names,
+    naming: NoteNaming,
+) -> Vec<String> {
+    let changes = diff(before, after);
+    let mut lines = vec![match changes.len() {
+        0 => "No change since the snapshot".to_string(),
+        1 => "1 change since the snapshot".to_string(),
+        n => format!("{} changes since the snapshot", n),
+    }];
+    for change in changes {
+        let part = match change.part {
+            Part::Program => "Program".to_string(),
+            Part::Bank => "Bank".to_string(),
+            Part::Bend => "Pitch Bend".to_string(),
+            Part::Pressure => "Channel Pressure".to_string(),
+            Part::Control(control) => match names.controls.get(&control.to_string()) {
+                Some(name) => format!("CC {} {}", control, name),
+                None => format!("CC {} {}", control, controls::get_controller_name(control)),
+            },
+            Part::Note(note) => format!("Note {}", naming.name(note)),
+        };
+        lines.push(format!(
+            "Ch {:>2}  {:<30}  {} -> {}",
+            change.channel + 1,
+            part,
+            Value(change.part, change.before),
+            Value(change.part, change.after)
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_changes() {
+        let mut before = Snapshot::default();
+        before.controls.insert((0, 7), 100);
+        before.bends[0] = Some(8192);
+        before.held[1] = 1 << 60;
+        let mut after = before.clone();
+        assert_eq!(
+            report(&before, &after, &Names::default(), NoteNaming::English),
+            ["No change since the snapshot"]
+        );
+
+        after.controls.insert((0, 7), 64);
+        after.controls.insert((0, 10), 0);
+        after.bends[0] = Some(0);
+        after.programs[0] = Some(5);
+        after.held[1] = 0;
+        let changes = diff(&before, &after);
+        assert_eq!(
+            changes[0],
+            Change {
+                channel: 0,
+                part: Part::Program,
+                before: None,
+                after: Some(5)
+            }
+        );
+        assert_eq!(changes.len(), 5);
+        let lines = report(&before, &after, &Names::default(), NoteNaming::English);
+        assert_eq!(lines[0], "5 changes since the snapshot");
+        assert_eq!(
+            lines[2],
+            format!("Ch  1  {:<30}  +0 -> -8192", "Pitch Bend")
+        );
+        assert_eq!(
+            lines[3],
+            format!("Ch  1  {:<30}  100 -> 64", "CC 7 Channel volume")
+        );
+        assert_eq!(lines[4], format!("Ch  1  {:<30}  - -> 0", "CC 10 Pan"));
+        assert_eq!(
+            lines[5],
+            format!("Ch  2  {:<30}  held -> released", "Note C4")
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Counts of what a capture contains
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{MidiAnalysis, MidiMessage},
+};
+use serde_json::json;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Running totals of the bytes, messages, and issues of a capture
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    /// Bytes received
+    pub bytes: usize,
+    /// Completed messages by name
+    pub messages: BTreeMap<&'static str, usize>,
+    /// Completed channel messages by channel
+    pub channels: [usize; 16],
+    /// Completed channel messages of each channel by name
+    pub channel_messages: [BTreeMap<&'static str, usize>; 16],
+    pub infos: usize,
+    pub warnings: usize,
+    pub violations: usize,
+    /// Messages sent without their status byte, each saving a byte
+    pub running_status: usize,
+    /// Bytes of complete System Exclusive messages, including `F0` and `F7`
+    pub sysex_bytes: usize,
+    /// Times of the first and last bytes
+    first: Option<Duration>,
+    last: Duration,
+    /// Time of the last completed message
+    last_message: Option<Duration>,
+    /// Shortest and longest time between consecutive messages
+    min_gap: Option<Duration>,
+    max_gap: Option<Duration>,
+}
+
+impl Statistics {
+    /// Creates empty statistics
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    /// Counts the next event of the capture
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        self.bytes += 1;
+        self.first.get_or_insert(event.time);
+        self.last = self.last.max(event.time);
+        if let Some(message) = &event.message {
+            *self.messages.entry(message.name()).or_default() += 1;
+            if let Some(channel) = message.channel() {
+                self.channels[channel as usize & 0x0F] += 1;
+                *self.channel_messages[channel as usize & 0x0F]
+                    .entry(message.name())
+                    .or_default() += 1;
+            }
+            if event.raw.first().is_some_and(|b| b & 0x80 == 0) {
+                self.running_status += 1;
+            }
+            if let MidiMessage::SystemExclusive(data) = message {
+                self.sysex_bytes += data.len() + 2;
+            }
+            if let Some(previous) = self.last_message {
+                let gap = event.time.saturating_sub(previous);
+                self.min_gap = Some(self.min_gap.map_or(gap, |min| min.min(gap)));
+                self.max_gap = Some(self.max_gap.map_or(gap, |max| max.max(gap)));
+            }
+            self.last_message = Some(event.time);
+        }
+        match event.analysis {
+            MidiAnalysis::Info(_) => self.infos += 1,
+            MidiAnalysis::Warning(_) => self.warnings += 1,
+            MidiAnalysis::Violation(_) => self.violations += 1,
+            _ => {}
+        }
+    }
+
+    /// Returns the number of completed messages
+    pub fn message_count(&self) -> usize {
+        self.messages.values().sum()
+    }
+
+    /// Returns the time from the first to the last byte
+    pub fn duration(&self) -> Duration {
+        self.last.saturating_sub(self.first.unwrap_or_default())
+    }
+
+    /// Returns the average number of bytes per second, if the capture lasted any time
+    pub fn byte_rate(&self) -> Option<f64> {
+        let seconds = self.duration().as_secs_f64();
+        (seconds > 0.0).then(|| self.bytes as f64 / seconds)
+    }
+
+    /// Returns the statistics as a JSON object, with times in seconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let channels: BTreeMap<String, usize> = self
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(ch, count)| ((ch + 1).to_string(), *count))
+            .collect();
+        json!({
+            "bytes": self.bytes,
+            "messages": self.message_count(),
+            "message_types": self.messages,
+            "channels": channels,
+            "running_status_bytes_saved": self.running_status,
+            "sysex_bytes": self.sysex_bytes,
+            "infos": self.infos,
+            "warnings": self.warnings,
+            "violations": self.violations,
+            "duration": self.duration().as_secs_f64(),
+            "byte_rate": self.byte_rate(),
+            "min_gap": self.min_gap.map(|gap| gap.as_secs_f64()),
+            "max_gap": self.max_gap.map(|gap| gap.as_secs_f64()),
+        })
+    }
+}
+
+/// Conditions that end a capture automatically, and budgets that raise alarms
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// Longest time to capture for, from when the capture starts
+    pub duration: Option<Duration>,
+    /// Most bytes to capture
+    pub bytes: Option<usize>,
+    /// Most complete messages to capture
+    pub messages: Option<usize>,
+    pub budgets: Budgets,
+}
+
+/// Byte counts that raise an alarm when they are exceeded, without ending the capture
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budgets {
+    /// Most bytes of System Exclusive messages
+    pub sysex: Option<usize>,
+    /// Most bytes in total
+    pub total: Option<usize>,
+}
+
+impl Budgets {
+    /// Returns an alarm for each budget the capture has exceeded. The exceeded budgets are
+    /// removed, so each alarm is raised only once
+    pub fn check(&mut self, stats: &Statistics) -> Vec<String> {
+        let mut alarms = vec![];
+        if let Some(sysex) = self.sysex.filter(|b| stats.sysex_bytes > *b) {
+            alarms.push(format!(
+                "More than {} of SysEx received",
+                format_size(sysex)
+            ));
+            self.sysex = None;
+        }
+        if let Some(total) = self.total.filter(|b| stats.bytes > *b) {
+            alarms.push(format!("Capture exceeds {}", format_size(total)));
+            self.total = None;
+        }
+        alarms
+    }
+}
+
+/// Formats a number of bytes in the largest unit that divides it
+pub fn format_size(bytes: usize) -> String {
+    match bytes {
+        0 => "0 bytes".to_string(),
+        _ if bytes.is_multiple_of(1 << 30) => format!("{} GB", bytes >> 30),
+        _ if bytes.is_multiple_of(1 << 20) => format!("{} MB", bytes >> 20),
+        _ if bytes.is_multiple_of(1 << 10) => format!("{} KB", bytes >> 10),
+        _ => format!("{} bytes", bytes),
+    }
+}
+
+impl Limits {
+    /// Returns why the capture should end, if it has reached a limit after running for `elapsed`
+    pub fn reached(&self, stats: &Statistics, elapsed: Duration) -> Option<String> {
+        if let Some(duration) = self.duration.filter(|d| elapsed >= *d) {
+            return Some(format!("Duration of {:?} reached", duration));
+        }
+        if let Some(bytes) = self.bytes.filter(|b| stats.bytes >= *b) {
+            return Some(format!("Limit of {} bytes reached", bytes));
+        }
+        if let Some(messages) = self.messages.filter(|m| stats.message_count() >= *m) {
+            return Some(format!("Limit of {} messages reached", messages));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn count_messages() {
+        let mut capture = Capture::new();
+        let mut stats = Statistics::new();
+        for byte in [0x90, 60, 100, 62, 100, 0xF8, 0x99, 36, 90, 0x40] {
+            stats.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert_eq!(stats.bytes, 10);
+        assert_eq!(stats.message_count(), 4);
+        assert_eq!(stats.messages["Note On"], 3);
+        assert_eq!(stats.channels[0], 2);
+        assert_eq!(stats.channels[9], 1);
+        assert_eq!(stats.channel_messages[9]["Note On"], 1);
+        assert_eq!(stats.running_status, 1);
+    }
+
+    #[test]
+    fn limits() {
+        let mut capture = Capture::new();
+        let mut stats = Statistics::new();
+        for byte in [0x90, 60, 100, 0xF8] {
+            stats.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let second = Duration::from_secs(1);
+        assert_eq!(Limits::default().reached(&stats, second), None);
+        let limits = Limits {
+            duration: Some(second),
+            bytes: Some(5),
+            messages: Some(2),
+            budgets: Budgets::default(),
+        };
+        assert!(limits.reached(&stats, second).is_some());
+        assert!(limits.reached(&stats, Duration::ZERO).is_some());
+        stats.messages.clear();
+        assert_eq!(limits.reached(&stats, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn budgets() {
+        let mut capture = Capture::new();
+        let mut stats = Statistics::new();
+        let mut budgets = Budgets {
+            sysex: Some(4),
+            total: Some(2048),
+        };
+        for byte in [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7] {
+            stats.observe(&capture.process(Duration::ZERO, byte));
+        }
+        assert_eq!(stats.sysex_bytes, 6);
+        assert_eq!(
+            budgets.check(&stats),
+            ["More than 4 bytes of SysEx received"]
+        );
+        assert!(budgets.check(&stats).is_empty());
+        stats.bytes = 2049;
+        assert_eq!(budgets.check(&stats), ["Capture exceeds 2 KB"]);
+    }
+
+    #[test]
+    fn timing_summary() {
+        let mut capture = Capture::new();
+        let mut stats = Statistics::new();
+        for (ms, byte) in [(0, 0xF8), (10, 0xF8), (40, 0xF8), (100, 0xFE)] {
+            stats.observe(&capture.process(Duration::from_millis(ms), byte));
+        }
+        let json = stats.to_json();
+        assert_eq!(json["duration"], 0.1);
+        assert_eq!(json["byte_rate"], 40.0);
+        assert_eq!(json["min_gap"], 0.01);
+        assert_eq!(json["max_gap"], 0.06);
+        assert_eq!(json["message_types"]["Timing Clock"], 3);
+    }
+}
@@ -0,0 +1,216 @@
+//! Summary printed when a session ends
+
+use crate::analysis::{
+    clock::ClockQuality,
+    smoothness::Smoothness,
+    stats::{format_size, Statistics},
+};
+use serde_json::json;
+use std::{cmp::Reverse, collections::BTreeMap, fmt, time::Duration};
+
+/// Number of channels listed as the top talkers
+const TOP_TALKERS: usize = 3;
+/// Number of message types listed by name
+const TOP_MESSAGES: usize = 5;
+
+/// What a session captured, with why it ended
+#[derive(Debug, Clone)]
+pub struct Summary {
+    /// Why the session ended, such as the end of the file or Ctrl-C
+    pub ended: String,
+    pub stats: Statistics,
+    pub clock: Option<ClockQuality>,
+    pub smoothness: Vec<Smoothness>,
+    /// Estimated network jitter, for sources that send timestamps
+    pub jitter: Option<Duration>,
+}
+
+/// A channel with many messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Talker {
+    /// Channel from 0 to 15
+    channel: u8,
+    messages: usize,
+    /// Share of all channel messages
+    share: f64,
+    /// Message sent most on the channel
+    top: &'static str,
+}
+
+impl Summary {
+    /// Returns the channels with the most messages, busiest first
+    fn talkers(&self) -> Vec<Talker> {
+        let total: usize = self.stats.channels.iter().sum();
+        let mut talkers: Vec<Talker> = (0..16)
+            .filter(|ch| self.stats.channels[*ch] > 0)
+            .map(|ch| Talker {
+                channel: ch as u8,
+                messages: self.stats.channels[ch],
+                share: self.stats.channels[ch] as f64 / total as f64,
+                top: most(&self.stats.channel_messages[ch]),
+            })
+            .collect();
+        // Stable, so ties stay in channel order
+        talkers.sort_by_key(|talker| Reverse(talker.messages));
+        talkers.truncate(TOP_TALKERS);
+        talkers
+    }
+
+    /// Returns the summary as a JSON object, with times in seconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let talkers: Vec<serde_json::Value> = self
+            .talkers()
+            .iter()
+            .map(|talker| {
+                json!({
+                    "channel": talker.channel + 1,
+                    "messages": talker.messages,
+                    "share": talker.share,
+                    "top_message": talker.top,
+                })
+            })
+            .collect();
+        let smoothness: Vec<serde_json::Value> = self
+            .smoothness
+            .iter()
+            .map(|report| {
+                json!({
+                    "channel": report.channel + 1,
+                    "controller": report.controller.to_string(),
+                    "score": report.score,
+                })
+            })
+            .collect();
+        json!({
+            "ended": self.ended,
+            "duration": self.stats.duration().as_secs_f64(),
+            "bytes": self.stats.bytes,
+            "messages": self.stats.message_count(),
+            "byte_rate": self.stats.byte_rate(),
+            "message_types": self.stats.messages,
+            "severities": {
+                "info": self.stats.infos,
+                "warning": self.stats.warnings,
+                "violation": self.stats.violations,
+            },
+            "bpm": self.clock.map(|clock| clock.bpm),
+            "sync": self.clock.map(|clock| clock.score),
+            "jitter": self.jitter.map(|jitter| jitter.as_secs_f64()),
+            "top_talkers": talkers,
+            "smoothness": smoothness,
+        })
+    }
+}
+
+/// Returns the name counted most, the first in order on ties
+fn most(counts: &BTreeMap<&'static str, usize>) -> &'static str {
+    counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| **count)
+        .map_or("", |(name, _)| name)
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(f, "Session ended: {}", self.ended)?;
+        writeln!(f, "  Duration     {:.3} s", stats.duration().as_secs_f64())?;
+        let rate = match stats.byte_rate() {
+            Some(rate) => format!(", {:.1} bytes/s", rate),
+            None => String::new(),
+        };
+        writeln!(
+            f,
+            "  Captured     {}, {} messages{}",
+            format_size(stats.bytes),
+            stats.message_count(),
+            rate
+        )?;
+        writeln!(
+            f,
+            "  Severities   {} info, {} warnings, {} violations",
+            stats.infos, stats.warnings, stats.violations
+        )?;
+        if let Some(clock) = self.clock {
+            writeln!(f, "  Clock        {}", clock)?;
+        }
+        if let Some(jitter) = self.jitter {
+            writeln!(
+                f,
+                "  Jitter       {:.2} ms on the network",
+                jitter.as_secs_f64() * 1e3
+            )?;
+        }
+        let talkers: Vec<String> = self
+            .talkers()
+            .iter()
+            .map(|talker| {
+                format!(
+                    "ch {}: {} ({:.0}%, mostly {})",
+                    talker.channel + 1,
+                    talker.messages,
+                    talker.share * 100.0,
+                    talker.top
+                )
+            })
+            .collect();
+        if !talkers.is_empty() {
+            writeln!(f, "  Top talkers  {}", talkers.join(", "))?;
+        }
+        let mut messages: Vec<(&str, usize)> =
+            stats.messages.iter().map(|(n, c)| (*n, *c)).collect();
+        messages.sort_by_key(|(_, count)| Reverse(*count));
+        if !messages.is_empty() {
+            let mut names: Vec<String> = messages
+                .iter()
+                .take(TOP_MESSAGES)
+                .map(|(name, count)| format!("{} {}", name, count))
+                .collect();
+            if messages.len() > TOP_MESSAGES {
+                names.push(format!("{} more types", messages.len() - TOP_MESSAGES));
+            }
+            writeln!(f, "  Messages     {}", names.join(", "))?;
+        }
+        for report in &self.smoothness {
+            writeln!(f, "  Smoothness   {}", report)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn summary_block() {
+        let mut capture = Capture::new();
+        let mut stats = Statistics::new();
+        let bytes = [0x99, 36, 100, 36, 0, 0xB0, 7, 100, 0x99, 38, 100, 0xF8];
+        for (ms, byte) in bytes.into_iter().enumerate() {
+            stats.observe(&capture.process(Duration::from_millis(ms as u64 * 100), byte));
+        }
+        let summary = Summary {
+            ended: "Interrupted".to_string(),
+            stats,
+            clock: None,
+            smoothness: vec![],
+            jitter: None,
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Session ended: Interrupted\n\
+             \x20 Duration     1.100 s\n\
+             \x20 Captured     12 bytes, 5 messages, 10.9 bytes/s\n\
+             \x20 Severities   1 info, 0 warnings, 0 violations\n\
+             \x20 Top talkers  ch 10: 3 (75%, mostly Note On), ch 1: 1 (25%, mostly Control Change)\n\
+             \x20 Messages     Note On 3, Control Change 1, Timing Clock 1\n"
+        );
+        let json = summary.to_json();
+        assert_eq!(json["top_talkers"][0]["channel"], 10);
+        assert_eq!(json["severities"]["info"], 1);
+        assert_eq!(json["bpm"], serde_json::Value::Null);
+    }
+}
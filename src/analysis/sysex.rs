@@ -0,0 +1,286 @@
+//! Inference of the structure of the System Exclusive messages of a device from many
+//! similar dumps, as a start for reverse engineering them. Dumps of the same length are
+//! taken to be the same kind of message, and compared byte by byte for the bytes that
+//! never change, the ones that do, names, and a checksum
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Shortest run of printable bytes taken for text
+const MIN_TEXT: usize = 4;
+/// Longest run of changing bytes described byte by byte
+const MAX_VALUES: usize = 16;
+
+/// How a checksum byte is computed from the bytes it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// The sum of the bytes and the checksum is a multiple of 128, as used by Roland and
+    /// Yamaha
+    Complement,
+    /// The lower 7 bits of the sum of the bytes
+    Sum,
+    /// The bytes XORed together
+    Xor,
+}
+
+impl Method {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Complement => "7 bit complement of the sum",
+            Method::Sum => "7 bit sum",
+            Method::Xor => "XOR",
+        }
+    }
+
+    /// Returns the checksum of the bytes from the running totals of a dump before and
+    /// after them
+    fn compute(&self, before: (u32, u8), after: (u32, u8)) -> u8 {
+        let sum = after.0.wrapping_sub(before.0);
+        match self {
+            Method::Complement => ((128 - sum % 128) % 128) as u8,
+            Method::Sum => (sum % 128) as u8,
+            Method::Xor => (after.1 ^ before.1) & 0x7F,
+        }
+    }
+}
+
+/// What a run of bytes of a message holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// The same bytes in every dump
+    Constant(Vec<u8>),
+    /// Printable bytes that change between dumps, such as a name
+    Text,
+    /// A byte that changes between dumps, with the lowest and highest value seen
+    Value { min: u8, max: u8 },
+    /// A run of changing bytes too long to describe byte by byte
+    Bytes,
+    /// A checksum of the bytes from `start` up to it
+    Checksum { start: usize, method: Method },
+}
+
+/// A run of bytes of a message, by their offset in the data after `F0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub start: usize,
+    pub len: usize,
+    pub kind: Kind,
+}
+
+/// The inferred structure of the dumps of one length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    /// Data bytes, without `F0` and `F7`
+    pub len: usize,
+    /// Dumps it was inferred from
+    pub count: usize,
+    pub fields: Vec<Field>,
+}
+
+impl Structure {
+    /// Returns the bytes every dump starts with
+    pub fn header(&self) -> &[u8] {
+        match self.fields.first() {
+            Some(Field {
+                kind: Kind::Constant(bytes),
+                ..
+            }) => bytes,
+            _ => &[],
+        }
+    }
+}
+
+/// Infers the structure of the data of SysEx dumps, without `F0` and `F7`, a structure
+/// for each length of dump from the shortest
+pub fn infer(dumps: &[Vec<u8>]) -> Vec<Structure> {
+    let mut lengths: BTreeMap<usize, Vec<&[u8]>> = BTreeMap::new();
+    for dump in dumps.iter().filter(|dump| !dump.is_empty()) {
+        lengths.entry(dump.len()).or_default().push(dump);
+    }
+    lengths
+        .into_iter()
+        .map(|(len, dumps)| Structure {
+            len,
+            count: dumps.len(),
+            fields: fields(&dumps),
+        })
+        .collect()
+}
+
+/// Splits dumps of the same length into fields
+fn fields(dumps: &[&[u8]]) -> Vec<Field> {
+    let len = dumps[0].len();
+    let constant: Vec<bool> = (0..len)
+        .map(|i| dumps.iter().all(|dump| dump[i] == dumps[0][i]))
+        .collect();
+    let checksum = checksum(dumps, &constant);
+    let mut fields = vec![];
+    let mut start = 0;
+    while start < len {
+        if let Some((position, kind)) = &checksum {
+            if start == *position {
+                fields.push(Field {
+                    start,
+                    len: 1,
+                    kind: kind.clone(),
+                });
+                start += 1;
+                continue;
+            }
+        }
+        let end = (start + 1..len)
+            .find(|i| {
+                constant[*i] != constant[start] || checksum.as_ref().is_some_and(|c| c.0 == *i)
+            })
+            .unwrap_or(len);
+        if constant[start] {
+            fields.push(Field {
+                start,
+                len: end - start,
+                kind: Kind::Constant(dumps[0][start..end].to_vec()),
+            });
+        } else {
+            changing(dumps, start, end, &mut fields);
+        }
+        start = end;
+    }
+    fields
+}
+
+/// Adds the fields of a run of bytes that change between dumps, picking out the text
+fn changing(dumps: &[&[u8]], start: usize, end: usize, fields: &mut Vec<Field>) {
+    let printable = |i: &usize| dumps.iter().all(|dump| (0x20..=0x7E).contains(&dump[*i]));
+    // Start of the bytes before the text found so far
+    let mut rest = start;
+    let mut i = start;
+    while i < end {
+        if !printable(&i) {
+            i += 1;
+            continue;
+        }
+        let text_end = (i..end).find(|j| !printable(j)).unwrap_or(end);
+        if text_end - i >= MIN_TEXT {
+            values(dumps, rest, i, fields);
+            fields.push(Field {
+                start: i,
+                len: text_end - i,
+                kind: Kind::Text,
+            });
+            rest = text_end;
+        }
+        i = text_end;
+    }
+    values(dumps, rest, end, fields);
+}
+
+/// Adds the fields of a run of changing bytes without text
+fn values(dumps: &[&[u8]], start: usize, end: usize, fields: &mut Vec<Field>) {
+    if end - start > MAX_VALUES {
+        fields.push(Field {
+            start,
+            len: end - start,
+            kind: Kind::Bytes,
+        });
+        return;
+    }
+    for i in start..end {
+        let values = dumps.iter().map(|dump| dump[i]);
+        fields.push(Field {
+            start: i,
+            len: 1,
+            kind: Kind::Value {
+                min: values.clone().min().unwrap_or(0),
+                max: values.max().unwrap_or(0),
+            },
+        });
+    }
+}
+
+/// Finds a checksum in the last two bytes that matches every dump, over the most bytes,
+/// returning its offset and field
+fn checksum(dumps: &[&[u8]], constant: &[bool]) -> Option<(usize, Kind)> {
+    let len = constant.len();
+    if len < 2 {
+        return None;
+    }
+    // Running sums and XORs of each dump before each of its bytes
+    let totals: Vec<Vec<(u32, u8)>> = dumps
+        .iter()
+        .map(|dump| {
+            let mut totals = vec![(0, 0)];
+            for byte in dump.iter() {
+                let (sum, xor) = totals[totals.len() - 1];
+                totals.push((sum + *byte as u32, xor ^ byte));
+            }
+            totals
+        })
+        .collect();
+    for position in (len - 2..len).rev() {
+        // A checksum seen with fewer values could match one of the many candidates by chance
+        let values: BTreeSet<u8> = dumps.iter().map(|dump| dump[position]).collect();
+        if values.len() < 3 {
+            continue;
+        }
+        // The covered bytes have to change for the checksum to say anything
+        let Some(last_changing) = (0..position).rev().find(|i| !constant[*i]) else {
+            continue;
+        };
+        for start in 0..=last_changing {
+            for method in [Method::Complement, Method::Sum, Method::Xor] {
+                let matches = dumps.iter().zip(&totals).all(|(dump, totals)| {
+                    method.compute(totals[start], totals[position]) == dump[position]
+                });
+                if matches {
+                    return Some((position, Kind::Checksum { start, method }));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roland_dumps() {
+        // DT1 messages of a 4 byte address, a name, and two parameters
+        let dump = |address: u8, name: &[u8; 6], a: u8, b: u8| {
+            let mut data = vec![0x41, 0x10, 0x6A, 0x12, 0x03, 0x00, address, 0x00];
+            data.extend(name);
+            data.extend([a, b]);
+            let sum: u32 = data[4..].iter().map(|b| *b as u32).sum();
+            data.push(((128 - sum % 128) % 128) as u8);
+            data
+        };
+        let dumps = [
+            dump(0x00, b"Piano ", 10, 64),
+            dump(0x10, b"Organ ", 12, 0),
+            dump(0x12, b"Strngs", 127, 3),
+            vec![0x7E, 0x10, 0x06, 0x01],
+        ];
+        let structures = infer(&dumps);
+        assert_eq!(structures.len(), 2);
+        assert_eq!(structures[0].count, 1);
+        let structure = &structures[1];
+        assert_eq!(structure.header(), [0x41, 0x10, 0x6A, 0x12, 0x03, 0x00]);
+        let kinds: Vec<&Kind> = structure.fields.iter().map(|field| &field.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &Kind::Constant(vec![0x41, 0x10, 0x6A, 0x12, 0x03, 0x00]),
+                &Kind::Value { min: 0, max: 0x12 },
+                &Kind::Constant(vec![0x00]),
+                &Kind::Text,
+                &Kind::Value { min: 10, max: 127 },
+                &Kind::Value { min: 0, max: 64 },
+                &Kind::Checksum {
+                    start: 4,
+                    method: Method::Complement
+                },
+            ]
+        );
+        assert_eq!(structure.fields[3].start, 8);
+        assert_eq!(structure.fields[3].len, 6);
+    }
+}
@@ -0,0 +1,131 @@
+//! Values of one controller of one channel over time, for a chart of how a pedal or wheel
+//! moves
+
+use crate::{analysis::smoothness::Controller, capture::CaptureEvent, midi::MidiMessage};
+use std::{collections::VecDeque, time::Duration};
+
+/// Updates older than this before the latest are forgotten
+const KEPT: Duration = Duration::from_secs(60);
+
+/// The updates of a watched controller
+#[derive(Debug, Clone)]
+pub struct Watch {
+    /// Channel from 0 to 15
+    pub channel: u8,
+    pub controller: Controller,
+    /// Times of the updates, with their values from 0 to 1
+    points: VecDeque<(Duration, f64)>,
+}
+
+impl Watch {
+    /// Watches a controller of a channel from 0 to 15, before any update is received
+    pub fn new(channel: u8, controller: Controller) -> Watch {
+        Watch {
+            channel,
+            controller,
+            points: VecDeque::new(),
+        }
+    }
+
+    /// Returns the channel and controller a message updates, with its value from 0 to 1
+    pub fn update(message: &MidiMessage) -> Option<(u8, Controller, f64)> {
+        match *message {
+            MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            } => Some((channel, Controller::Control(control), value as f64 / 127.0)),
+            MidiMessage::PitchBend { channel, value } => {
+                Some((channel, Controller::PitchBend, value as f64 / 16383.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Keeps the value of an event that updates the watched controller
+    pub fn observe(&mut self, event: &CaptureEvent) {
+        let Some((channel, controller, value)) = event.message.as_ref().and_then(Watch::update)
+        else {
+            return;
+        };
+        if (channel, controller) != (self.channel, self.controller) {
+            return;
+        }
+        self.points.push_back((event.time, value));
+        while self
+            .points
+            .front()
+            .is_some_and(|(time, _)| *time + KEPT < event.time)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    /// Returns the latest value, if any update was received
+    pub fn value(&self) -> Option<f64> {
+        self.points.back().map(|(_, value)| *value)
+    }
+
+    /// Returns the number of updates kept
+    pub fn updates(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns the value held at the end of each of `columns` slices of the `window` that
+    /// ends with the latest update, `None` before the first update
+    pub fn series(&self, window: Duration, columns: usize) -> Vec<Option<f64>> {
+        let Some((end, _)) = self.points.back() else {
+            return vec![None; columns];
+        };
+        let mut points = self.points.iter().peekable();
+        let mut held = None;
+        (1..=columns)
+            .map(|column| {
+                // The window may start before the capture, so times are shifted by it
+                let until = *end + window.mul_f64(column as f64 / columns as f64);
+                while let Some((_, value)) = points.next_if(|(time, _)| *time + window <= until) {
+                    held = Some(*value);
+                }
+                held
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn holds_values_over_time() {
+        let mut capture = Capture::new();
+        let mut watch = Watch::new(0, Controller::Control(1));
+        assert_eq!(watch.series(Duration::from_secs(4), 2), [None, None]);
+        let messages: [(u64, [u8; 3]); 4] = [
+            (0, [0xB0, 0x01, 0x00]),
+            (1_000, [0xB1, 0x01, 0x7F]),
+            (2_500, [0xB0, 0x02, 0x7F]),
+            (3_000, [0xB0, 0x01, 0x7F]),
+        ];
+        for (millis, bytes) in messages {
+            for byte in bytes {
+                let event = capture.process(Duration::from_millis(millis), byte);
+                watch.observe(&event);
+            }
+        }
+        // Channel 2 and CC 2 are not watched
+        assert_eq!(watch.updates(), 2);
+        assert_eq!(watch.value(), Some(1.0));
+        assert_eq!(
+            watch.series(Duration::from_secs(5), 5),
+            [None, Some(0.0), Some(0.0), Some(0.0), Some(1.0)]
+        );
+
+        let bend = MidiMessage::PitchBend {
+            channel: 3,
+            value: 16383,
+        };
+        assert_eq!(Watch::update(&bend), Some((3, Controller::PitchBend, 1.0)));
+    }
+}
@@ -0,0 +1,167 @@
+//! Compact binary format for storing raw captures with timestamps
+//!
+//! A capture file starts with the magic bytes `MTCAP`, followed by a version byte.
+//! Every received byte is then stored as the number of microseconds since the previous
+//! byte, encoded as an unsigned LEB128 integer, followed by the byte itself
+
+use anyhow::{bail, Context};
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::Duration,
+};
+
+const MAGIC: &[u8; 5] = b"MTCAP";
+const VERSION: u8 = 1;
+
+/// Writes timestamped bytes in the capture format
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    last: Duration,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Writes the file header and returns a writer ready to accept bytes
+    pub fn new(mut writer: W) -> Result<CaptureWriter<W>, anyhow::Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(CaptureWriter {
+            writer,
+            last: Duration::ZERO,
+        })
+    }
+
+    /// Returns a writer that appends to an existing capture whose last byte was received
+    /// at `last`. The writer must be positioned at the end of the file
+    pub fn resume(writer: W, last: Duration) -> CaptureWriter<W> {
+        CaptureWriter { writer, last }
+    }
+
+    /// Appends a byte received at `time`. Times must not decrease
+    pub fn write(&mut self, time: Duration, byte: u8) -> Result<(), anyhow::Error> {
+        let mut delta = time.saturating_sub(self.last).as_micros() as u64;
+        self.last = self.last.max(time);
+        let mut buffer = [0_u8; 11];
+        let mut n = 0;
+        loop {
+            let low = (delta & 0x7F) as u8;
+            delta >>= 7;
+            buffer[n] = if delta == 0 { low } else { low | 0x80 };
+            n += 1;
+            if delta == 0 {
+                break;
+            }
+        }
+        buffer[n] = byte;
+        self.writer.write_all(&buffer[..=n])?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads timestamped bytes from the capture format
+pub struct CaptureReader<R: Read> {
+    reader: R,
+    time: Duration,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Checks the file header and returns a reader positioned at the first byte
+    pub fn new(mut reader: R) -> Result<CaptureReader<R>, anyhow::Error> {
+        let mut header = [0_u8; 6];
+        reader
+            .read_exact(&mut header)
+            .context("Unable to read capture header")?;
+        if &header[..5] != MAGIC {
+            bail!("Not a miditerm capture file");
+        }
+        if header[5] != VERSION {
+            bail!("Unsupported capture file version {}", header[5]);
+        }
+        Ok(CaptureReader {
+            reader,
+            time: Duration::ZERO,
+        })
+    }
+
+    /// Reads a single byte, returning `None` at the end of the file
+    fn read_u8(&mut self) -> Result<Option<u8>, anyhow::Error> {
+        let mut byte = [0_u8];
+        match self.reader.read_exact(&mut byte) {
+            Ok(()) => Ok(Some(byte[0])),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads the next byte and the time it was received
+    pub fn next_byte(&mut self) -> Result<Option<(Duration, u8)>, anyhow::Error> {
+        let mut delta = 0_u64;
+        let mut shift = 0;
+        loop {
+            let Some(b) = self.read_u8()? else {
+                if shift == 0 {
+                    return Ok(None);
+                }
+                bail!("Capture file ends within a record");
+            };
+            if shift > 63 {
+                bail!("Invalid timestamp in capture file");
+            }
+            delta |= ((b & 0x7F) as u64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        let Some(byte) = self.read_u8()? else {
+            bail!("Capture file ends within a record");
+        };
+        self.time += Duration::from_micros(delta);
+        Ok(Some((self.time, byte)))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<(Duration, u8), anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_byte().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let bytes = [
+            (Duration::from_micros(0), 0x90_u8),
+            (Duration::from_micros(0), 60),
+            (Duration::from_micros(320), 100),
+            (Duration::from_secs(3600), 0xF8),
+        ];
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+        for (time, byte) in bytes {
+            writer.write(time, byte).unwrap();
+        }
+        let data = writer.writer;
+        assert_eq!(&data[..6], b"MTCAP\x01");
+        assert_eq!(&data[6..10], &[0x00, 0x90, 0x00, 60]);
+        assert_eq!(&data[10..13], &[0xC0, 0x02, 100]);
+
+        let reader = CaptureReader::new(data.as_slice()).unwrap();
+        let read: Vec<(Duration, u8)> = reader.map(Result::unwrap).collect();
+        assert_eq!(read, bytes);
+    }
+
+    #[test]
+    fn bad_header() {
+        assert!(CaptureReader::new(&b"MThd\x00\x00"[..]).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Indexing of captured events for fast filtering
+//!
+//! Events are grouped by input, status, and channel as they arrive, so that a filtered view of the
+//! capture can be built by merging the groups that pass the filter instead of checking
+//! every event. Only the events of those groups are checked for the severity and text of
+//! their analysis
+
+use crate::capture::CaptureEvent;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+};
+
+/// Statuses of every kind of message, without the channel of channel messages
+pub const MESSAGE_STATUSES: [u8; 18] = [
+    0x80, 0x90, 0xA0, 0xB0, 0xC0, 0xD0, 0xE0, 0xF0, 0xF1, 0xF2, 0xF3, 0xF6, 0xF8, 0xFA, 0xFB, 0xFC,
+    0xFE, 0xFF,
+];
+
+/// Severities of the analysis, in the order of the bits of `Filter::hidden_severities`
+pub const SEVERITIES: [&str; 4] = ["comment", "info", "warning", "violation"];
+
+/// Selects which events of a capture are shown
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    /// Bit mask of the channels to hide, channel 1 being the least significant bit
+    pub hidden_channels: u16,
+    /// Statuses of the messages to hide, without the channel of channel messages
+    pub hidden_statuses: BTreeSet<u8>,
+    /// Inputs to hide, when several are captured at once. Not kept between sessions, which
+    /// may capture other inputs
+    #[serde(skip)]
+    pub hidden_sources: BTreeSet<u8>,
+    /// Bit mask of the severities of the analysis to hide, in the order of `SEVERITIES`
+    pub hidden_severities: u8,
+    /// Only events whose analysis contains this text, regardless of case, are shown
+    pub pattern: String,
+}
+
+impl Filter {
+    /// Returns `true` if the filter shows every event
+    pub fn is_empty(&self) -> bool {
+        self.hidden_channels == 0
+            && self.hidden_statuses.is_empty()
+            && self.hidden_sources.is_empty()
+            && self.hidden_severities == 0
+            && self.pattern.is_empty()
+    }
+
+    /// Returns `true` if only groups of events are filtered, so the index alone selects the
+    /// events that pass
+    fn is_grouped(&self) -> bool {
+        self.hidden_severities == 0 && self.pattern.is_empty()
+    }
+
+    /// Returns `true` if events of the given group pass the filter
+    fn accepts(&self, (source, status, channel): Group) -> bool {
+        let source_shown = !self.hidden_sources.contains(&source);
+        let status_shown = status.is_none_or(|s| !self.hidden_statuses.contains(&s));
+        let channel_shown = channel.is_none_or(|ch| self.hidden_channels & (1 << ch) == 0);
+        source_shown && status_shown && channel_shown
+    }
+
+    /// Returns `true` if the severity and text of the analysis of the event pass the filter
+    fn accepts_analysis(&self, event: &CaptureEvent) -> bool {
+        let severity = SEVERITIES
+            .iter()
+            .position(|s| *s == event.analysis.severity())
+            .unwrap_or(0);
+        self.hidden_severities & (1 << severity) == 0
+            && (self.pattern.is_empty()
+                || (event.analysis.text().to_lowercase()).contains(&self.pattern.to_lowercase()))
+    }
+
+    /// Returns `true` if the event passes the filter
+    pub fn matches(&self, event: &CaptureEvent) -> bool {
+        self.accepts(group(event)) && self.accepts_analysis(event)
+    }
+}
+
+/// Input, status, and channel shared by the events of a group
+type Group = (u8, Option<u8>, Option<u8>);
+
+fn group(event: &CaptureEvent) -> Group {
+    (event.source, event.status, event.channel)
+}
+
+/// Positions of the events of a capture grouped by input, status, and channel
+#[derive(Debug, Default)]
+pub struct EventIndex {
+    groups: BTreeMap<Group, Vec<usize>>,
+    len: usize,
+}
+
+impl EventIndex {
+    /// Creates an empty index
+    pub fn new() -> EventIndex {
+        EventIndex::default()
+    }
+
+    /// Adds the next event of the capture to the index
+    pub fn push(&mut self, event: &CaptureEvent) {
+        self.groups.entry(group(event)).or_default().push(self.len);
+        self.len += 1;
+    }
+
+    /// Returns the positions of all events of the capture that pass the filter, in capture
+    /// order
+    pub fn select(&self, filter: &Filter, events: &[CaptureEvent]) -> Vec<usize> {
+        let groups: Vec<&Vec<usize>> = self
+            .groups
+            .iter()
+            .filter(|(group, _)| filter.accepts(**group))
+            .map(|(_, positions)| positions)
+            .collect();
+        let mut selected = Vec::with_capacity(groups.iter().map(|g| g.len()).sum());
+
+        // k-way merge of the sorted groups
+        let mut heads: BinaryHeap<Reverse<(usize, usize, usize)>> = groups
+            .iter()
+            .enumerate()
+            .filter_map(|(g, positions)| positions.first().map(|p| Reverse((*p, g, 0))))
+            .collect();
+        while let Some(Reverse((position, g, i))) = heads.pop() {
+            if filter.is_grouped() || filter.accepts_analysis(&events[position]) {
+                selected.push(position);
+            }
+            if let Some(next) = groups[g].get(i + 1) {
+                heads.push(Reverse((*next, g, i + 1)));
+            }
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn select_matches_scan() {
+        let mut capture = Capture::new();
+        let mut index = EventIndex::new();
+        let mut events = vec![];
+        let bytes = [
+            0x90, 60, 100, 0xF8, 62, 100, 0xB9, 7, 127, 0xF0, 0x7E, 0xF8, 0x01, 0xF7, 0x99, 36, 90,
+            0xFE,
+        ];
+        for byte in bytes {
+            let event = capture.process(Duration::ZERO, byte);
+            index.push(&event);
+            events.push(event);
+        }
+
+        let mut filter = Filter::default();
+        assert_eq!(
+            index.select(&filter, &events),
+            (0..bytes.len()).collect::<Vec<_>>()
+        );
+
+        filter.hidden_statuses.insert(0xF8);
+        filter.hidden_channels = 1 << 9;
+        let scanned: Vec<usize> = (0..events.len())
+            .filter(|i| filter.matches(&events[*i]))
+            .collect();
+        assert_eq!(index.select(&filter, &events), scanned);
+        assert_eq!(scanned, vec![0, 1, 2, 4, 5, 9, 10, 12, 13, 17]);
+
+        // Every byte is a comment
+        filter.pattern = "SYSEX data".to_string();
+        assert_eq!(index.select(&filter, &events), [10, 12]);
+        filter.hidden_severities = 0b0001;
+        assert!(index.select(&filter, &events).is_empty());
+        filter.hidden_severities = 0;
+        filter.pattern.clear();
+
+        // A second input with a parser of its own
+        let event = capture.process_from(1, Duration::ZERO, 0xFA);
+        index.push(&event);
+        events.push(event);
+        filter.hidden_sources.insert(0);
+        assert_eq!(index.select(&filter, &events), vec![bytes.len()]);
+    }
+}
@@ -0,0 +1,251 @@
+//! Timestamped capture of an analyzed MIDI byte stream
+
+pub mod format;
+mod index;
+mod timeline;
+
+pub use index::{EventIndex, Filter, MESSAGE_STATUSES, SEVERITIES};
+pub use timeline::{TimeFormat, Timeline};
+
+use crate::{
+    analysis::Settings,
+    midi::{MidiAnalysis, MidiMessage, MidiParser},
+};
+use std::time::Duration;
+
+/// Source of the messages miditerm sends itself, logged among the received ones but left out
+/// of their statistics
+pub const SENT: u8 = u8::MAX;
+
+/// A single received byte along with everything the analyzer had to say about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureEvent {
+    /// Time since the start of the capture
+    pub time: Duration,
+    /// The byte as it was received
+    pub byte: u8,
+    /// Status of the message the byte belongs to, without the channel of channel messages.
+    /// `None` for data bytes received without a running status
+    pub status: Option<u8>,
+    /// Channel the byte belongs to, if it is part of a channel message
+    pub channel: Option<u8>,
+    /// The message completed by this byte, if any
+    pub message: Option<MidiMessage>,
+    /// Bytes of the completed message as they appeared on the wire.
+    /// Under running status this will not include a status byte.
+    /// Empty if `message` is `None`
+    pub raw: Vec<u8>,
+    /// Analysis of this byte
+    pub analysis: MidiAnalysis,
+    /// Input the byte was received from, when several are captured at once
+    pub source: u8,
+    /// The event tells what happened to the source, such as a port that was disconnected,
+    /// in its analysis instead of carrying a received byte. `byte` is then zero
+    pub notice: bool,
+}
+
+impl CaptureEvent {
+    /// Creates the entry of the capture telling what happened to the input `source` at
+    /// `time`, so gaps in the capture show among the bytes and are logged with them
+    pub fn notice(source: u8, time: Duration, analysis: MidiAnalysis) -> CaptureEvent {
+        CaptureEvent {
+            time,
+            byte: 0,
+            status: None,
+            channel: None,
+            message: None,
+            raw: vec![],
+            analysis,
+            source,
+            notice: true,
+        }
+    }
+
+    /// Returns `true` if the byte is a status byte
+    pub fn is_status(&self) -> bool {
+        !self.notice && self.byte & 0x80 != 0
+    }
+
+    /// Returns the byte received, or none for a notice
+    pub fn received(&self) -> &[u8] {
+        match self.notice {
+            true => &[],
+            false => std::slice::from_ref(&self.byte),
+        }
+    }
+}
+
+/// Parser state of one input of a capture
+struct Input {
+    parser: MidiParser,
+    pending: Vec<u8>,
+}
+
+impl Input {
+    fn new() -> Input {
+        Input {
+            parser: MidiParser::new(),
+            pending: vec![],
+        }
+    }
+}
+
+/// Runs received bytes through the MIDI parser and tracks the raw bytes of each message.
+/// Every input has a parser of its own, so the running status and messages in progress of
+/// one input are not garbled by the bytes of another
+pub struct Capture {
+    inputs: Vec<Input>,
+    settings: Settings,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Capture {
+    /// Creates a new capture with a fresh parser
+    pub fn new() -> Capture {
+        Capture::with_settings(Settings::default())
+    }
+
+    /// Creates a new capture with a fresh parser that analyzes using the given settings
+    pub fn with_settings(settings: Settings) -> Capture {
+        Capture {
+            inputs: vec![Input::new()],
+            settings,
+        }
+    }
+
+    /// Forgets the messages in progress and the running status of every input, as the bytes
+    /// that would complete them were lost, such as while a port was disconnected
+    pub fn interrupt(&mut self) {
+        for input in &mut self.inputs {
+            *input = Input::new();
+        }
+    }
+
+    /// Parses the given byte received at `time` into a `CaptureEvent`
+    pub fn process(&mut self, time: Duration, byte: u8) -> CaptureEvent {
+        self.process_from(0, time, byte)
+    }
+
+    /// Parses the given byte received from the input `source` at `time` into a `CaptureEvent`
+    pub fn process_from(&mut self, source: u8, time: Duration, byte: u8) -> CaptureEvent {
+        let index = source as usize;
+        if self.inputs.len() <= index {
+            self.inputs.resize_with(index + 1, Input::new);
+        }
+        let input = &mut self.inputs[index];
+        let realtime = byte >= 0xF8;
+        let system = byte >= 0xF0;
+        if !realtime {
+            // End of Exclusive terminates the pending SysEx rather than starting a new message
+            if byte & 0x80 != 0 && byte != 0xF7 {
+                input.pending.clear();
+            }
+            input.pending.push(byte);
+        }
+
+        let (message, analysis) = input.parser.parse_midi(byte);
+        let analysis = self.settings.review(message.as_ref(), analysis);
+        let status = if realtime {
+            Some(byte)
+        } else if byte == 0xF7 {
+            Some(0xF0)
+        } else {
+            input
+                .parser
+                .get_state()
+                .or((byte & 0x80 != 0).then_some(byte))
+                .map(|s| if s < 0xF0 { s & 0xF0 } else { s })
+        };
+        let channel = if system {
+            None
+        } else {
+            input.parser.get_channel()
+        };
+        // The first data byte of note messages is the note number
+        let analysis = match (status, &message) {
+            (Some(0x80 | 0x90 | 0xA0), None) if byte & 0x80 == 0 => {
+                analysis.map_text(|text| format!("{} ({})", text, self.settings.naming.name(byte)))
+            }
+            _ => analysis,
+        };
+
+        let raw = match (&message, realtime) {
+            (Some(_), true) => vec![byte],
+            (Some(_), false) => std::mem::take(&mut input.pending),
+            (None, _) => vec![],
+        };
+
+        CaptureEvent {
+            time,
+            byte,
+            status,
+            channel,
+            message,
+            raw,
+            analysis,
+            source,
+            notice: false,
+        }
+    }
+
+    /// Analyzes an event of an earlier capture again. A notice is kept as it is, and the
+    /// messages in progress before it are forgotten as they were then
+    pub fn reprocess(&mut self, event: &CaptureEvent) -> CaptureEvent {
+        if !event.notice {
+            return self.process_from(event.source, event.time, event.byte);
+        }
+        self.interrupt();
+        event.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_keep_their_running_status() {
+        let mut capture = Capture::new();
+        // A Note On of one input interleaved with a Control Change of another
+        let bytes = [
+            (0, 0x90),
+            (1, 0xB1),
+            (0, 60),
+            (1, 7),
+            (0, 100),
+            (1, 127),
+            (0, 62),
+        ];
+        let events: Vec<CaptureEvent> = bytes
+            .into_iter()
+            .map(|(source, byte)| capture.process_from(source, Duration::ZERO, byte))
+            .collect();
+        assert_eq!(events[4].raw, [0x90, 60, 100]);
+        assert_eq!(events[4].source, 0);
+        assert_eq!(events[5].raw, [0xB1, 7, 127]);
+        assert_eq!(events[5].source, 1);
+        assert_eq!(events[6].status, Some(0x90));
+    }
+
+    #[test]
+    fn interruptions_forget_running_status() {
+        let mut capture = Capture::new();
+        for byte in [0x90, 60, 100, 62] {
+            capture.process(Duration::ZERO, byte);
+        }
+        // The velocity of the Note On was lost while the port was disconnected
+        capture.interrupt();
+        let events: Vec<CaptureEvent> = [64, 0x80, 62, 0]
+            .into_iter()
+            .map(|byte| capture.process(Duration::ZERO, byte))
+            .collect();
+        assert_eq!(events[0].status, None);
+        assert_eq!(events[0].message, None);
+        assert_eq!(events[3].raw, [0x80, 62, 0]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Mapping of received bytes onto the capture timeline
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Weight of each new sample in the jitter estimate, as used by RTP (RFC 3550)
+const JITTER_GAIN: f64 = 1.0 / 16.0;
+
+/// How times on the capture timeline are written for people
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeFormat {
+    /// `12.345678 s`
+    Seconds,
+    /// `12345.678 ms`
+    Milliseconds,
+    /// `00:00:12.345678`, as accepted by query expressions
+    Clock,
+}
+
+impl TimeFormat {
+    pub fn format(self, time: Duration) -> String {
+        match self {
+            TimeFormat::Seconds => format!("{:.6} s", time.as_secs_f64()),
+            TimeFormat::Milliseconds => format!("{:.3} ms", time.as_secs_f64() * 1e3),
+            TimeFormat::Clock => {
+                let seconds = time.as_secs();
+                format!(
+                    "{:02}:{:02}:{:02}.{:06}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60,
+                    time.subsec_micros()
+                )
+            }
+        }
+    }
+}
+
+/// Assigns capture times to received bytes.
+///
+/// By default bytes are placed at their local arrival time. Network sources may also
+/// supply the sender's timestamp for each byte, in which case the timeline can use those
+/// instead so that delays introduced by the network do not distort the capture.
+/// The clock of each input is aligned to the local clock on its own, using the least
+/// delayed byte of the input seen so far
+#[derive(Debug)]
+pub struct Timeline {
+    start: Instant,
+    use_source: bool,
+    /// Alignment of the clock of each input that sent timestamped bytes
+    clocks: BTreeMap<u8, Clock>,
+    /// Most recent time assigned, used to keep the timeline monotonic
+    last: Duration,
+}
+
+/// Alignment of the clock of one input to the local clock
+#[derive(Debug)]
+struct Clock {
+    /// Smallest observed difference between arrival time and source timestamp in microseconds
+    offset: i128,
+    /// Difference between arrival time and source timestamp of the last timestamped byte
+    last_transit: i128,
+    /// Interarrival jitter estimate in microseconds
+    jitter: f64,
+}
+
+impl Timeline {
+    /// Creates a timeline starting at `start`
+    pub fn new(start: Instant, use_source: bool) -> Timeline {
+        Timeline {
+            start,
+            use_source,
+            clocks: BTreeMap::new(),
+            last: Duration::ZERO,
+        }
+    }
+
+    /// Returns `true` if source timestamps are used when present
+    pub fn uses_source(&self) -> bool {
+        self.use_source
+    }
+
+    /// Switches between source timestamps and arrival times
+    pub fn toggle_source(&mut self) {
+        self.use_source = !self.use_source;
+    }
+
+    /// Returns the estimated jitter introduced between the sources and this machine, the
+    /// largest of all inputs, or `None` if no timestamped bytes have been received
+    pub fn jitter(&self) -> Option<Duration> {
+        self.clocks
+            .values()
+            .map(|clock| Duration::from_micros(clock.jitter.round() as u64))
+            .max()
+    }
+
+    /// Returns the capture time of now, never before the last time assigned
+    pub fn now(&self) -> Duration {
+        self.last.max(self.start.elapsed())
+    }
+
+    /// Returns the capture time of a byte of the input `source` that arrived at `arrival`
+    /// carrying the optional source timestamp `timestamp`
+    pub fn time(&mut self, arrival: Instant, source: u8, timestamp: Option<Duration>) -> Duration {
+        let arrived = arrival.saturating_duration_since(self.start);
+        let Some(timestamp) = timestamp else {
+            return self.advance(arrived);
+        };
+
+        let transit = arrived.as_micros() as i128 - timestamp.as_micros() as i128;
+        let clock = self.clocks.entry(source).or_insert(Clock {
+            offset: transit,
+            last_transit: transit,
+            jitter: 0.0,
+        });
+        clock.jitter += ((transit - clock.last_transit).abs() as f64 - clock.jitter) * JITTER_GAIN;
+        clock.last_transit = transit;
+        clock.offset = clock.offset.min(transit);
+
+        if !self.use_source {
+            return self.advance(arrived);
+        }
+        let micros = (timestamp.as_micros() as i128 + clock.offset).clamp(0, u64::MAX as i128);
+        self.advance(Duration::from_micros(micros as u64))
+    }
+
+    /// Never lets time run backwards when the alignment of the source clock improves
+    fn advance(&mut self, time: Duration) -> Duration {
+        self.last = self.last.max(time);
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_timestamps_remove_jitter() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(start, true);
+        // Bytes sent every 10 ms that arrive 5 ms late, give or take 2 ms
+        let delays = [5, 7, 3, 6, 4];
+        let mut times = vec![];
+        for (i, delay) in delays.iter().enumerate() {
+            let sent = Duration::from_millis(10 * i as u64);
+            let arrival = start + sent + Duration::from_millis(*delay);
+            times.push(timeline.time(arrival, 0, Some(sent)));
+        }
+        // Aligned to the least delayed byte seen at the time
+        assert_eq!(times[1], Duration::from_millis(15));
+        assert_eq!(times[4], Duration::from_millis(43));
+        assert!(timeline.jitter().unwrap() > Duration::ZERO);
+
+        timeline.toggle_source();
+        let arrival = start + Duration::from_millis(56);
+        assert_eq!(
+            timeline.time(arrival, 0, Some(Duration::from_millis(50))),
+            Duration::from_millis(56)
+        );
+    }
+
+    #[test]
+    fn arrival_time_without_timestamps() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(start, true);
+        let arrival = start + Duration::from_millis(20);
+        assert_eq!(timeline.time(arrival, 0, None), Duration::from_millis(20));
+        assert_eq!(timeline.jitter(), None);
+    }
+
+    #[test]
+    fn aligns_each_input() {
+        let start = Instant::now();
+        let mut timeline = Timeline::new(start, true);
+        // The clock of the second agent started a minute after that of the first
+        let arrival = start + Duration::from_secs(61);
+        assert_eq!(
+            timeline.time(arrival, 0, Some(Duration::from_secs(60))),
+            Duration::from_secs(61)
+        );
+        assert_eq!(
+            timeline.time(arrival, 1, Some(Duration::from_secs(1))),
+            Duration::from_secs(61)
+        );
+        let arrival = start + Duration::from_secs(62);
+        assert_eq!(
+            timeline.time(arrival, 1, Some(Duration::from_secs(2))),
+            Duration::from_secs(62)
+        );
+        // Timestamps far from the local clock do not overflow
+        assert_eq!(
+            timeline.time(arrival, 2, Some(Duration::MAX)),
+            Duration::from_secs(62)
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! `miditerm agent`
+
+use crate::config::Config;
+use crate::midi;
+use crate::source::{server, Source, SourceEvent};
+use anyhow::{bail, Context};
+use std::{ffi::OsString, time::Instant};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct AgentArgs {
+    /// Address of the `miditerm monitor --listen` to push the bytes to,
+    /// `tcp:HOST:PORT` or `unix:PATH`
+    #[structopt(long)]
+    connect: String,
+
+    /// Name or path of the serial device to capture, or `-` to read raw MIDI bytes piped
+    /// into stdin. Defaults to the `port` of the configuration file
+    #[structopt(long, allow_hyphen_values = true, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Baud rate of the serial port. Defaults to the `baud` of the configuration file
+    #[structopt(long)]
+    baud: Option<u32>,
+
+    /// Number the server tells the bytes of this agent apart by
+    #[structopt(long, default_value = "0")]
+    source_id: u8,
+}
+
+pub fn run(args: AgentArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let baud = args.baud.or(config.baud).unwrap_or(midi::MIDI_BAUD_RATE);
+    let source = match args.port.or_else(|| config.port.clone()) {
+        Some(port) if port == "-" => Source::Stdin,
+        Some(port) => Source::Serial { port, baud },
+        None => bail!("`--port` is required"),
+    };
+    let mut server = server::connect(&args.connect)?;
+    let start = Instant::now();
+    let rx = source.spawn()?;
+
+    let mut next = None;
+    loop {
+        let event = match next.take() {
+            Some(event) => event,
+            None => match rx.recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            },
+        };
+        match event {
+            SourceEvent::Byte { arrival, byte, .. } => {
+                // Bytes read at once travel in one frame
+                let mut bytes = vec![byte];
+                while let Ok(event) = rx.try_recv() {
+                    match event {
+                        SourceEvent::Byte {
+                            arrival: time,
+                            byte,
+                            ..
+                        } if time == arrival => bytes.push(byte),
+                        event => {
+                            next = Some(event);
+                            break;
+                        }
+                    }
+                }
+                let frame = server::encode(args.source_id, arrival.duration_since(start), &bytes);
+                server
+                    .write_all(&frame)
+                    .context(format!("Unable to send to `{}`", args.connect))?;
+            }
+            SourceEvent::Disconnected(notice)
+            | SourceEvent::Reconnected(notice)
+            | SourceEvent::Notice(notice) => {
+                eprintln!("{}", notice)
+            }
+            SourceEvent::Closed => break,
+            SourceEvent::Error(e) => bail!(e),
+        }
+    }
+    Ok(())
+}
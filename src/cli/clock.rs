@@ -0,0 +1,295 @@
+//! `miditerm clock`
+//!
+//! Sends 24 Timing Clocks per quarter note out a port at a steady tempo, for driving
+//! sequencers, arpeggiators, and delays under test. The clocks keep running while the
+//! transport is stopped, so followers stay locked to the tempo. Keys while it runs:
+//!
+//! - `Space` to stop, and to continue from where it stopped
+//! - `Enter` or `s` to start from the beginning of the song
+//! - `Up` and `Down` to change the tempo by 1 BPM, `PageUp` and `PageDown` by 10,
+//!   `Right` and `Left` by 0.1
+//! - `q`, `Esc`, or `Ctrl-C` to stop and quit
+
+use crate::{midi::MidiMessage, source};
+use anyhow::{bail, Context};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use std::{
+    ffi::OsString,
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Timing Clocks per quarter note
+const PPQN: u32 = 24;
+/// Timing Clocks per sixteenth note, the unit of Song Position
+const CLOCKS_PER_SIXTEENTH: u32 = PPQN / 4;
+/// Slowest and fastest tempo sent
+const MIN_BPM: f64 = 20.0;
+const MAX_BPM: f64 = 300.0;
+/// Time before a clock is due from which the clock is waited for by spinning instead of
+/// sleeping, which could oversleep
+const SPIN: Duration = Duration::from_millis(2);
+
+#[derive(Debug, StructOpt)]
+pub struct ClockArgs {
+    /// Name or path of the serial device or raw MIDI device (`/dev/snd/midiC1D0`) to send
+    /// the clock to
+    #[structopt(long, parse(from_os_str))]
+    port: OsString,
+
+    /// Tempo in beats per minute, from 20 to 300
+    #[structopt(long, default_value = "120")]
+    bpm: f64,
+
+    /// Song Position to start from in sixteenth notes, sent before a Continue in place of
+    /// the Start
+    #[structopt(long)]
+    song_position: Option<u16>,
+
+    /// Only send clocks until `Space` is pressed, instead of starting right away
+    #[structopt(long)]
+    stopped: bool,
+}
+
+/// Tempo and transport of the clock
+#[derive(Debug)]
+struct Clock {
+    bpm: f64,
+    running: bool,
+    /// Clocks sent while running since the beginning of the song
+    clocks: u32,
+}
+
+impl Clock {
+    fn new(bpm: f64) -> Clock {
+        Clock {
+            bpm: bpm.clamp(MIN_BPM, MAX_BPM),
+            running: false,
+            clocks: 0,
+        }
+    }
+
+    /// Returns the time between two clocks
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(60.0 / (self.bpm * PPQN as f64))
+    }
+
+    fn change_bpm(&mut self, change: f64) {
+        // Rounded so repeated tenths do not drift away from them
+        self.bpm = ((self.bpm + change) * 10.0)
+            .round()
+            .clamp(MIN_BPM * 10.0, MAX_BPM * 10.0)
+            / 10.0;
+    }
+
+    /// Returns a Timing Clock, counting it if the transport is running
+    fn tick(&mut self) -> Vec<u8> {
+        if self.running {
+            self.clocks += 1;
+        }
+        MidiMessage::TimingClock.to_bytes()
+    }
+
+    /// Returns a Start, which starts from the beginning of the song
+    fn start(&mut self) -> Vec<u8> {
+        self.running = true;
+        self.clocks = 0;
+        MidiMessage::Start.to_bytes()
+    }
+
+    /// Returns a Stop, or a Continue if the transport is stopped
+    fn toggle(&mut self) -> Vec<u8> {
+        self.running = !self.running;
+        match self.running {
+            true => MidiMessage::Continue.to_bytes(),
+            false => MidiMessage::Stop.to_bytes(),
+        }
+    }
+
+    /// Returns a Song Position that moves the stopped transport to `sixteenths`
+    fn locate(&mut self, sixteenths: u16) -> Vec<u8> {
+        self.clocks = sixteenths as u32 * CLOCKS_PER_SIXTEENTH;
+        MidiMessage::SongPosition(sixteenths).to_bytes()
+    }
+
+    /// Returns the position in the song as bar, beat, and sixteenth in 4/4
+    fn position(&self) -> String {
+        let sixteenths = self.clocks / CLOCKS_PER_SIXTEENTH;
+        format!(
+            "{}.{}.{}",
+            sixteenths / 16 + 1,
+            sixteenths / 4 % 4 + 1,
+            sixteenths % 4 + 1
+        )
+    }
+
+    /// Returns the status line
+    fn status(&self) -> String {
+        let transport = if self.running { "Playing" } else { "Stopped" };
+        format!(
+            "{:>5.1} BPM  {:<7}  {}",
+            self.bpm,
+            transport,
+            self.position()
+        )
+    }
+}
+
+pub fn run(args: ClockArgs) -> Result<(), anyhow::Error> {
+    if !(MIN_BPM..=MAX_BPM).contains(&args.bpm) {
+        bail!(
+            "`--bpm` must be from {} to {}, not {}",
+            MIN_BPM,
+            MAX_BPM,
+            args.bpm
+        );
+    }
+    let mut port = source::open_output(&args.port)
+        .context(format!("Unable to open `{}`", args.port.to_string_lossy()))?;
+
+    // Without a terminal to read keys from, the clock runs until interrupted
+    let keys = io::stdin().is_terminal();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if keys {
+        println!("Space stops and continues, Enter starts over, arrows change the tempo, q quits");
+        enable_raw_mode().context("Unable to read keys from the terminal")?;
+    } else {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .context("Unable to install Ctrl-C handler")?;
+    }
+    let result = play(&args, &mut port, keys, &interrupted);
+    if keys {
+        disable_raw_mode().context("Failed to disable raw mode")?;
+    }
+    println!();
+    result
+}
+
+/// Sends clocks until quit, then stops the transport
+fn play(
+    args: &ClockArgs,
+    port: &mut dyn Write,
+    keys: bool,
+    interrupted: &AtomicBool,
+) -> Result<(), anyhow::Error> {
+    let mut send = |bytes: Vec<u8>| -> Result<(), anyhow::Error> {
+        port.write_all(&bytes)
+            .and_then(|_| port.flush())
+            .context(format!(
+                "Unable to write to `{}`",
+                args.port.to_string_lossy()
+            ))
+    };
+    let mut clock = Clock::new(args.bpm);
+    if let Some(sixteenths) = args.song_position {
+        send(clock.locate(sixteenths))?;
+    }
+    if !args.stopped {
+        match args.song_position {
+            Some(_) => send(clock.toggle())?,
+            None => send(clock.start())?,
+        }
+    }
+    show(&clock.status());
+
+    // Clocks are due at fixed steps from the previous one, so late wakeups do not add up
+    let mut due = Instant::now();
+    while !interrupted.load(Ordering::SeqCst) {
+        let Some(key) = wait(due, keys)? else {
+            let beat = clock.running && clock.clocks.is_multiple_of(PPQN);
+            send(clock.tick())?;
+            due += clock.interval();
+            if beat {
+                show(&clock.status());
+            }
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char(' ') => send(clock.toggle())?,
+            KeyCode::Char('s') | KeyCode::Enter => send(clock.start())?,
+            KeyCode::Up => clock.change_bpm(1.0),
+            KeyCode::Down => clock.change_bpm(-1.0),
+            KeyCode::PageUp => clock.change_bpm(10.0),
+            KeyCode::PageDown => clock.change_bpm(-10.0),
+            KeyCode::Right => clock.change_bpm(0.1),
+            KeyCode::Left => clock.change_bpm(-0.1),
+            _ => continue,
+        }
+        show(&clock.status());
+    }
+    if clock.running {
+        send(clock.toggle())?;
+    }
+    Ok(())
+}
+
+/// Waits until `due`, returning early with a key if one is pressed
+fn wait(due: Instant, keys: bool) -> Result<Option<KeyEvent>, anyhow::Error> {
+    loop {
+        let left = due.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(None);
+        }
+        if left <= SPIN {
+            std::hint::spin_loop();
+        } else if !keys {
+            thread::sleep(left - SPIN);
+        } else if event::poll(left - SPIN)? {
+            if let Event::Key(key) = event::read()? {
+                return Ok(Some(key));
+            }
+        }
+    }
+}
+
+/// Replaces the status line
+fn show(status: &str) {
+    print!("\r{}", status);
+    let _ = io::stdout().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport() {
+        let mut clock = Clock::new(120.0);
+        assert_eq!(clock.interval(), Duration::from_secs_f64(0.5 / 24.0));
+        assert_eq!(clock.start(), [0xFA]);
+        for _ in 0..PPQN * 5 + CLOCKS_PER_SIXTEENTH {
+            assert_eq!(clock.tick(), [0xF8]);
+        }
+        assert_eq!(clock.position(), "2.2.2");
+
+        // Clocks sent while stopped do not move the song
+        assert_eq!(clock.toggle(), [0xFC]);
+        clock.tick();
+        assert_eq!(clock.position(), "2.2.2");
+        assert_eq!(clock.locate(17), [0xF2, 17, 0]);
+        assert_eq!(clock.toggle(), [0xFB]);
+        assert_eq!(clock.status(), "120.0 BPM  Playing  2.1.2");
+
+        for _ in 0..3 {
+            clock.change_bpm(-0.1);
+        }
+        assert_eq!(clock.bpm, 119.7);
+        clock.change_bpm(1000.0);
+        assert_eq!(clock.bpm, MAX_BPM);
+    }
+}
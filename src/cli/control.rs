@@ -0,0 +1,151 @@
+//! Commands read from stdin while `miditerm monitor --headless --commands` runs, one per
+//! line, so a capture can be steered over an SSH pipe or from a script without the TUI
+
+use crate::cli::send;
+use crate::store;
+use anyhow::bail;
+use std::{
+    io::{self, BufRead},
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Commands as listed by `help`
+pub const HELP: &str = "\
+Commands:
+  pause                 stop printing and writing the outputs, still analyzing
+  resume                print and write the outputs again
+  mark [TEXT]           print a numbered mark with the time, such as `mark knob turned`
+  send MESSAGE          send a message to `--out`, written like `miditerm send`, such as `send note-on 1 60 100`
+  filter NAME on|off    show or hide a message type such as `clock`, or a channel from 1 to 16
+  save PATH             write the capture so far to a capture file, such as `bug.mtcap`
+  help                  list the commands
+  quit                  end the capture";
+
+/// A command given on stdin
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    Pause,
+    Resume,
+    Mark(String),
+    /// Bytes to send to MIDI Out
+    Send(Vec<u8>),
+    /// Shows or hides message statuses and channels, channel 1 being the least significant
+    /// bit of the mask
+    Filter {
+        statuses: Vec<u8>,
+        channels: u16,
+        shown: bool,
+    },
+    Save(PathBuf),
+    Help,
+    Quit,
+}
+
+impl FromStr for Control {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        let control = match name.to_lowercase().as_str() {
+            "pause" => Control::Pause,
+            "resume" => Control::Resume,
+            "mark" => Control::Mark(rest.to_string()),
+            "send" => Control::Send(send::parse_message(rest)?),
+            "filter" => {
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                let [target, state] = words[..] else {
+                    bail!("`filter` takes a message type or channel and `on` or `off`");
+                };
+                let shown = match state.to_lowercase().as_str() {
+                    "on" | "show" => true,
+                    "off" | "hide" => false,
+                    _ => bail!("`{}` is neither `on` nor `off`", state),
+                };
+                match target.parse::<u8>() {
+                    Ok(ch @ 1..=16) => Control::Filter {
+                        statuses: vec![],
+                        channels: 1 << (ch - 1),
+                        shown,
+                    },
+                    Ok(ch) => bail!("`{}` is not a channel from 1 to 16", ch),
+                    Err(_) => Control::Filter {
+                        statuses: store::parse_types(target)?,
+                        channels: 0,
+                        shown,
+                    },
+                }
+            }
+            "save" if rest.is_empty() => bail!("`save` takes the path of the capture file"),
+            "save" => Control::Save(PathBuf::from(rest)),
+            "help" | "?" => Control::Help,
+            "quit" | "exit" => Control::Quit,
+            _ => bail!("Unknown command `{}`, `help` lists the commands", name),
+        };
+        Ok(control)
+    }
+}
+
+/// Reads the lines of stdin on a new thread, skipping empty ones. The receiver disconnects
+/// at the end of stdin
+pub fn spawn_stdin() -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !line.trim().is_empty() && tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let parse = |s: &str| s.parse::<Control>();
+        assert_eq!(parse(" Pause ").unwrap(), Control::Pause);
+        assert_eq!(
+            parse("mark knob  turned").unwrap(),
+            Control::Mark("knob  turned".to_string())
+        );
+        assert_eq!(
+            parse("send note-on 1 60 100").unwrap(),
+            Control::Send(vec![0x90, 60, 100])
+        );
+        assert_eq!(
+            parse("filter clock off").unwrap(),
+            Control::Filter {
+                statuses: vec![0xF8],
+                channels: 0,
+                shown: false
+            }
+        );
+        assert_eq!(
+            parse("filter 10 on").unwrap(),
+            Control::Filter {
+                statuses: vec![],
+                channels: 1 << 9,
+                shown: true
+            }
+        );
+        assert!(parse("filter 17 on").is_err());
+        assert!(parse("filter clock").is_err());
+        assert_eq!(
+            parse("save bug.mtcap").unwrap(),
+            Control::Save(PathBuf::from("bug.mtcap"))
+        );
+        assert!(parse("save").is_err());
+        assert!(parse("bogus").is_err());
+    }
+}
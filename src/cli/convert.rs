@@ -0,0 +1,62 @@
+//! `miditerm convert`
+
+use crate::analysis::stats::Limits;
+use crate::capture::Timeline;
+use crate::cli::{
+    self,
+    format::{Format, FORMAT_NAMES},
+    AnalysisArgs, ExportArgs, PcapArgs,
+};
+use crate::config::Config;
+use anyhow::Context;
+use std::{path::PathBuf, time::Instant};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct ConvertArgs {
+    /// File to convert. `.syx`, `.pcap`, `.pcapng`, and `.mtcap` files are recognized by
+    /// their extension, anything else is read as raw MIDI bytes
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// File to write, or directory for `syx`
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+
+    /// Format of the output. Guessed from the extension of the output if omitted
+    #[structopt(long, possible_values = FORMAT_NAMES)]
+    format: Option<Format>,
+
+    #[structopt(flatten)]
+    export: ExportArgs,
+
+    #[structopt(flatten)]
+    pcap: PcapArgs,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+}
+
+pub fn run(args: ConvertArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let format = match args.format {
+        Some(format) => format,
+        None => Format::from_path(&args.output)
+            .context("Unable to tell the output format from its extension, use `--format`")?,
+    };
+    let settings = args.analysis.settings(config);
+    let sink = format.open(args.output, &args.export, config, settings)?;
+
+    let source = args.pcap.file_source(args.input);
+    let timeline = Timeline::new(Instant::now(), true);
+    cli::run_headless(
+        source,
+        timeline,
+        settings,
+        Limits::default(),
+        None,
+        &mut [sink],
+        None,
+    )
+    .context("Error converting MIDI")?;
+    Ok(())
+}
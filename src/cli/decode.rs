@@ -0,0 +1,69 @@
+//! `miditerm decode`
+
+use crate::cli::{
+    self, AnalysisArgs, Display, FilterArgs, LimitArgs, OutputArgs, PcapArgs, PrintArgs, View,
+};
+use crate::config::Config;
+use crate::source::{hex, Source};
+use crate::state::UiState;
+use anyhow::Context;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct DecodeArgs {
+    /// File to decode, `-` for raw bytes piped into standard input, or bytes in hexadecimal
+    /// such as `"90 3C 7F"` or `0x90,0x3C,0x7F`. `.syx`, `.pcap`, `.pcapng`, and `.mtcap`
+    /// files are recognized by their extension, anything else is read as raw MIDI bytes
+    input: String,
+
+    #[structopt(flatten)]
+    pcap: PcapArgs,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+
+    #[structopt(flatten)]
+    filter: FilterArgs,
+
+    #[structopt(flatten)]
+    limits: LimitArgs,
+
+    #[structopt(flatten)]
+    print: PrintArgs,
+
+    #[structopt(flatten)]
+    outputs: OutputArgs,
+}
+
+pub fn run(args: DecodeArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let path = PathBuf::from(&args.input);
+    let source = if args.input == "-" {
+        Source::Stdin
+    } else if path.exists() {
+        args.pcap.file_source(path)
+    } else {
+        Source::Bytes(hex::parse(&args.input).context(format!(
+            "`{}` is neither a file nor hexadecimal bytes",
+            args.input
+        ))?)
+    };
+    cli::run_capture(
+        Some(source),
+        &args.outputs,
+        args.analysis.settings(config),
+        &args.limits,
+        true,
+        View {
+            display: Display::Print,
+            filter: args.filter.filter(&config.filter)?,
+            config,
+            print: &args.print,
+            out: None,
+            commands: false,
+            reference: None,
+            state: UiState::default(),
+            state_store: None,
+        },
+    )
+}
@@ -0,0 +1,38 @@
+//! `miditerm sysex-diff`
+
+use crate::{analysis::diff, syx};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct DiffArgs {
+    /// `.syx` file of the first dump
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+
+    /// `.syx` file of the second dump. Files of several messages are compared message by
+    /// message
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+}
+
+pub fn run(args: DiffArgs) -> Result<(), anyhow::Error> {
+    let a = syx::load(&args.a)?;
+    let b = syx::load(&args.b)?;
+    println!("Offsets count from F0, the bytes of {:?} first", args.a);
+    for i in 0..a.len().max(b.len()) {
+        let lines = match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => diff::report(a, b),
+            (Some(_), None) => vec![format!("Only in {:?}", args.a)],
+            (None, _) => vec![format!("Only in {:?}", args.b)],
+        };
+        let mut lines = lines.into_iter();
+        if let Some(first) = lines.next() {
+            println!("Message {}: {}", i + 1, first);
+        }
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Output file formats shared by the subcommands that write files
+
+use crate::analysis::Settings;
+use crate::cli::ExportArgs;
+use crate::config::Config;
+use crate::export::array::Language;
+use crate::sink::{
+    ArrayExporter, CaptureRecorder, CastExporter, CcLaneExporter, CsvLogger, JsonlLogger,
+    MidicsvExporter, PianoRollExporter, Sink, SmfRecorder, StoreSink, SyxExporter, UmpWriter,
+};
+use crate::smf;
+use crate::store::SqliteStore;
+use anyhow::{anyhow, bail};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Names of the formats as accepted by `--format`
+pub const FORMAT_NAMES: &[&str] = &[
+    "smf",
+    "midicsv",
+    "csv",
+    "jsonl",
+    "mtcap",
+    "ump",
+    "syx",
+    "session",
+    "pianoroll",
+    "cclanes",
+    "c",
+    "rust",
+    "cast",
+];
+
+/// Formats a capture can be written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Type 0 Standard MIDI File
+    Smf,
+    /// Standard MIDI File events in the text format of `midicsv`
+    Midicsv,
+    /// One CSV row per message with timestamps
+    Csv,
+    /// One JSON object per message
+    Jsonl,
+    /// Timestamped capture for replay
+    Mtcap,
+    /// Universal MIDI Packets
+    Ump,
+    /// One `.syx` file per SysEx message
+    Syx,
+    /// Sqlite session database
+    Session,
+    /// SVG piano roll of the notes
+    PianoRoll,
+    /// SVG chart of controller values over time
+    CcLanes,
+    /// C byte array with a comment per message
+    C,
+    /// Rust byte array with a comment per message
+    Rust,
+    /// asciicast recording of the TUI replaying the capture
+    Cast,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "smf" | "mid" | "midi" => Ok(Format::Smf),
+            "midicsv" => Ok(Format::Midicsv),
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "json" => Ok(Format::Jsonl),
+            "mtcap" => Ok(Format::Mtcap),
+            "ump" => Ok(Format::Ump),
+            "syx" => Ok(Format::Syx),
+            "session" | "db" | "sqlite" => Ok(Format::Session),
+            "pianoroll" | "svg" => Ok(Format::PianoRoll),
+            "cclanes" => Ok(Format::CcLanes),
+            "c" | "h" => Ok(Format::C),
+            "rust" | "rs" => Ok(Format::Rust),
+            "cast" | "asciicast" => Ok(Format::Cast),
+            _ => Err(anyhow!("Unknown format `{}`", s)),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the format named by the extension of `path`
+    pub fn from_path(path: &Path) -> Option<Format> {
+        super::extension(path).and_then(|ext| ext.parse().ok())
+    }
+
+    /// Creates the sink writing this format to `path` with the settings of `export`.
+    /// Charts name notes as `settings` asks, and recordings of the TUI replay the capture
+    /// with `config` and `settings`
+    pub fn open(
+        self,
+        path: PathBuf,
+        export: &ExportArgs,
+        config: &Config,
+        settings: Settings,
+    ) -> Result<Box<dyn Sink>, anyhow::Error> {
+        let (ppq, bpm) = (export.ppq, export.bpm);
+        if matches!(self, Format::Smf | Format::Midicsv) {
+            smf::check_timing(ppq, bpm)?;
+        }
+        let sink: Box<dyn Sink> = match self {
+            Format::Smf => Box::new(SmfRecorder::new(path, ppq, bpm)),
+            Format::Midicsv => Box::new(MidicsvExporter::new(path, ppq, bpm)),
+            Format::Csv => Box::new(CsvLogger::create(&path)?),
+            Format::Jsonl => Box::new(JsonlLogger::create(&path)?),
+            Format::Mtcap => Box::new(CaptureRecorder::create(&path)?),
+            Format::Ump => Box::new(UmpWriter::open(&path.to_string_lossy(), 0)?),
+            Format::Syx => Box::new(SyxExporter::new(path)?),
+            Format::Session => {
+                if path.exists() {
+                    bail!("`{:?}` already exists", path);
+                }
+                Box::new(StoreSink::new(Box::new(SqliteStore::open(&path)?)))
+            }
+            Format::PianoRoll => Box::new(PianoRollExporter::new(path, settings.naming)),
+            Format::CcLanes => Box::new(CcLaneExporter::new(
+                path,
+                export.controls.iter().copied().collect(),
+            )),
+            Format::C => Box::new(ArrayExporter::new(path, Language::C)),
+            Format::Rust => Box::new(ArrayExporter::new(path, Language::Rust)),
+            Format::Cast => Box::new(CastExporter::new(
+                path,
+                config.clone(),
+                settings,
+                export.cast_size,
+                export.cast_fps,
+            )),
+        };
+        Ok(sink)
+    }
+}
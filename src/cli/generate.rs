@@ -0,0 +1,275 @@
+//! `miditerm generate`
+//!
+//! Test streams for validating other parsers and MIDI implementations. Patterns are
+//!
+//! - `notes`, a chromatic sweep of Note On and Note Off over all 128 notes
+//! - `cc`, every controller ramping from 0 to 127. The Channel Mode messages of controllers
+//!   120 to 127 are left out, as they reset the receiver or turn its keyboard off
+//! - `running-status`, valid but tricky streams: running status over several message
+//!   types, Note On with velocity 0, realtime bytes inside messages and SysEx, and SysEx
+//!   ended by a status byte other than End of Exclusive
+//! - `sysex`, non-commercial SysEx messages of several sizes
+//! - `violations`, orphaned data bytes, messages cut short, undefined status bytes, End of
+//!   Exclusive without SysEx, and running status after SysEx
+
+use crate::{cli, midi, source};
+use anyhow::{bail, Context};
+use std::{ffi::OsString, fs, io::Write, path::PathBuf, thread, time::Duration};
+use structopt::StructOpt;
+
+/// Manufacturer ID reserved for non-commercial use, which devices ignore
+const NON_COMMERCIAL: u8 = 0x7D;
+
+#[derive(Debug, StructOpt)]
+pub struct GenerateArgs {
+    /// Patterns to generate, in order
+    #[structopt(
+        required = true,
+        possible_values = &["notes", "cc", "running-status", "sysex", "violations"]
+    )]
+    patterns: Vec<String>,
+
+    /// Name or path of the serial device or raw MIDI device to send the stream to
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Raw file to write the stream to. The bytes are printed in hexadecimal, a message
+    /// per line, if neither this nor `--port` is given
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Channel of the channel messages, from 1 to 16
+    #[structopt(long, default_value = "1")]
+    channel: u8,
+
+    /// Velocity of the Note On messages of `notes`
+    #[structopt(long, default_value = "100")]
+    velocity: u8,
+
+    /// Step of the values of `cc`
+    #[structopt(long, default_value = "1")]
+    step: u8,
+
+    /// Data bytes of each message of `sysex`, the manufacturer ID included
+    #[structopt(
+        long,
+        use_delimiter = true,
+        default_value = "1,2,127,128,256,1024,4096"
+    )]
+    sysex_sizes: Vec<usize>,
+
+    /// Pause between messages sent to `--port`, such as `10ms`, for receivers that cannot
+    /// keep up with the full rate of the wire
+    #[structopt(long, parse(try_from_str = cli::parse_duration))]
+    gap: Option<Duration>,
+}
+
+pub fn run(args: GenerateArgs) -> Result<(), anyhow::Error> {
+    if !(1..=16).contains(&args.channel) {
+        bail!("`--channel` must be from 1 to 16");
+    }
+    if args.velocity > 127 || args.step == 0 || args.step > 127 {
+        bail!("`--velocity` must be up to 127, and `--step` from 1 to 127");
+    }
+    let channel = args.channel - 1;
+    let mut messages = vec![];
+    for pattern in &args.patterns {
+        messages.extend(match pattern.as_str() {
+            "notes" => notes(channel, args.velocity),
+            "cc" => controllers(channel, args.step),
+            "running-status" => running_status(channel),
+            "sysex" => sysex(&args.sysex_sizes),
+            _ => violations(channel),
+        });
+    }
+
+    if let Some(path) = &args.output {
+        fs::write(path, messages.concat()).context(format!("Unable to write {:?}", path))?;
+        println!(
+            "Wrote {} bytes to {:?}",
+            messages.iter().map(Vec::len).sum::<usize>(),
+            path
+        );
+    }
+    if let Some(name) = &args.port {
+        let mut port = source::open_output(name)
+            .context(format!("Unable to open `{}`", name.to_string_lossy()))?;
+        for bytes in &messages {
+            port.write_all(bytes)
+                .and_then(|_| port.flush())
+                .context(format!("Unable to write to `{}`", name.to_string_lossy()))?;
+            if let Some(gap) = args.gap {
+                thread::sleep(gap);
+            }
+        }
+    }
+    if args.output.is_none() && args.port.is_none() {
+        for bytes in &messages {
+            println!("{}", midi::hex(bytes));
+        }
+    }
+    Ok(())
+}
+
+/// Note On and Note Off of every note in turn
+fn notes(channel: u8, velocity: u8) -> Vec<Vec<u8>> {
+    (0..=127)
+        .flat_map(|note| {
+            [
+                vec![0x90 | channel, note, velocity],
+                vec![0x80 | channel, note, 0x40],
+            ]
+        })
+        .collect()
+}
+
+/// Every controller but the Channel Mode messages ramping up, ending on 127
+fn controllers(channel: u8, step: u8) -> Vec<Vec<u8>> {
+    let mut values: Vec<u8> = (0..=127).step_by(step as usize).collect();
+    if values.last() != Some(&127) {
+        values.push(127);
+    }
+    (0..120)
+        .flat_map(|control| {
+            values
+                .iter()
+                .map(move |value| vec![0xB0 | channel, control, *value])
+        })
+        .collect()
+}
+
+/// Valid streams that parsers often get wrong
+fn running_status(channel: u8) -> Vec<Vec<u8>> {
+    let note_on = 0x90 | channel;
+    vec![
+        // Note On under running status, then released with velocity 0
+        vec![note_on, 60, 100],
+        vec![64, 100],
+        vec![67, 100],
+        vec![60, 0],
+        vec![64, 0],
+        vec![67, 0],
+        // Realtime bytes inside a message and between running status messages
+        vec![note_on, 60, 0xF8, 100],
+        vec![0xFE, 60, 0xF8, 0],
+        // Running status of messages with one and two data bytes
+        vec![0xB0 | channel, 7, 100],
+        vec![10, 64],
+        vec![0xC0 | channel, 5],
+        vec![6],
+        vec![0xE0 | channel, 0x00, 0x40],
+        vec![0x7F, 0x7F],
+        vec![0x00, 0x40],
+        // A System Common message ends running status, so the status is sent again
+        vec![note_on, 72, 100],
+        vec![0xF6],
+        vec![note_on, 72, 0],
+        // Realtime bytes inside SysEx
+        vec![0xF0, NON_COMMERCIAL, 0x01, 0xF8, 0x02, 0xFE, 0x03, 0xF7],
+        // SysEx ended by another status byte instead of End of Exclusive, which is allowed
+        vec![0xF0, NON_COMMERCIAL, 0x01, 0x02, note_on, 60, 100],
+        vec![note_on, 60, 0],
+    ]
+}
+
+/// Non-commercial SysEx with `sizes` data bytes, counting up after the ID
+fn sysex(sizes: &[usize]) -> Vec<Vec<u8>> {
+    sizes
+        .iter()
+        .map(|size| {
+            let mut bytes = vec![0xF0];
+            bytes.extend(
+                [NON_COMMERCIAL]
+                    .into_iter()
+                    .chain((0..=127).cycle())
+                    .take(*size),
+            );
+            bytes.push(0xF7);
+            bytes
+        })
+        .collect()
+}
+
+/// Streams that break the MIDI specification, each ending where a receiver should have
+/// recovered
+fn violations(channel: u8) -> Vec<Vec<u8>> {
+    let note_on = 0x90 | channel;
+    vec![
+        // Data bytes after a System Common message ended running status
+        vec![0xF6, 60, 100],
+        // A Note On cut short by a Control Change
+        vec![note_on, 60, 0xB0 | channel, 7, 100],
+        // Undefined status bytes
+        vec![0xF4],
+        vec![0xF5],
+        vec![0xF9],
+        vec![0xFD],
+        // End of Exclusive without SysEx
+        vec![0xF7],
+        // Running status after SysEx, which ends it
+        vec![0xF0, NON_COMMERCIAL, 0x01, 0xF7, 60, 100],
+        // Song Position cut short
+        vec![0xF2, 0x00, 0xF6],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::{MidiAnalysis, MidiMessage, MidiParser};
+
+    /// Returns the messages parsed from the chunks, and the number of chunks with a warning
+    /// or violation
+    fn parse(chunks: &[Vec<u8>]) -> (Vec<MidiMessage>, usize) {
+        let mut parser = MidiParser::new();
+        let mut messages = vec![];
+        let mut flagged = 0;
+        for chunk in chunks {
+            let mut problem = false;
+            for byte in chunk {
+                let (message, analysis) = parser.parse_midi(*byte);
+                messages.extend(message);
+                problem |= matches!(
+                    analysis,
+                    MidiAnalysis::Warning(_) | MidiAnalysis::Violation(_)
+                );
+            }
+            flagged += problem as usize;
+        }
+        (messages, flagged)
+    }
+
+    #[test]
+    fn patterns() {
+        let (messages, flagged) = parse(&notes(9, 100));
+        assert_eq!((messages.len(), flagged), (256, 0));
+        assert_eq!(controllers(0, 8)[..2], [vec![0xB0, 0, 0], vec![0xB0, 0, 8]]);
+        assert_eq!(controllers(0, 8).len(), 120 * 17);
+
+        let (messages, flagged) = parse(&running_status(0));
+        assert_eq!(flagged, 0);
+        let notes = messages
+            .iter()
+            .filter(|m| matches!(m, MidiMessage::NoteOn { .. }))
+            .count();
+        assert_eq!(notes, 12);
+
+        let sizes = sysex(&[1, 300]);
+        assert_eq!(sizes[0], [0xF0, NON_COMMERCIAL, 0xF7]);
+        assert_eq!(sizes[1].len(), 302);
+        assert_eq!(
+            parse(&sizes),
+            (
+                vec![
+                    MidiMessage::SystemExclusive(vec![NON_COMMERCIAL]),
+                    MidiMessage::SystemExclusive(sizes[1][1..301].to_vec()),
+                ],
+                0
+            )
+        );
+
+        // Messages cut short by another status byte are dropped without a warning
+        let chunks = violations(0);
+        assert_eq!(parse(&chunks).1, chunks.len() - 2);
+    }
+}
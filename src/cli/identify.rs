@@ -0,0 +1,159 @@
+//! `miditerm identify`
+
+use crate::{
+    cli,
+    config::Config,
+    midi::{self, sysex, MidiMessage, MidiParser},
+    source::{self, SourceEvent},
+};
+use anyhow::{bail, Context};
+use std::{
+    ffi::OsString,
+    io::Write,
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Device ID that addresses every device
+const ALL_DEVICES: u8 = 0x7F;
+
+#[derive(Debug, StructOpt)]
+pub struct IdentifyArgs {
+    /// Name or path of the serial device or raw MIDI device to send the Identity Request
+    /// out of. Defaults to the `port` of the configuration file
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Port the replies come back on, if not `--port` itself
+    #[structopt(long, parse(from_os_str))]
+    input: Option<OsString>,
+
+    /// Baud rate of serial ports. Defaults to the `baud` of the configuration file
+    #[structopt(long)]
+    baud: Option<u32>,
+
+    /// Device ID to ask, from 0 to 127, where 127 asks every device
+    #[structopt(long, default_value = "127")]
+    device: u8,
+
+    /// How long to wait for replies. Every device that replies in that time is reported
+    #[structopt(long, default_value = "1s", parse(try_from_str = cli::parse_duration))]
+    timeout: Duration,
+}
+
+pub fn run(args: IdentifyArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let Some(port) = args.port.or_else(|| config.port.clone()) else {
+        bail!("`--port` is required");
+    };
+    if args.device > ALL_DEVICES {
+        bail!("`--device` must be from 0 to 127");
+    }
+    let baud = args.baud.or(config.baud).unwrap_or(midi::MIDI_BAUD_RATE);
+    let input = args.input.unwrap_or_else(|| port.clone());
+    let (reader, mut writer) = if input != port {
+        let (_, writer) = source::open_duplex(&port, baud)
+            .context(format!("Unable to open `{}`", port.to_string_lossy()))?;
+        let (reader, _) = source::open_duplex(&input, baud)
+            .context(format!("Unable to open `{}`", input.to_string_lossy()))?;
+        (reader, writer)
+    } else {
+        source::open_duplex(&port, baud)
+            .context(format!("Unable to open `{}`", port.to_string_lossy()))?
+    };
+
+    let rx = source::spawn_reader(reader);
+    writer
+        .write_all(&[0xF0, 0x7E, args.device, 0x06, 0x01, 0xF7])
+        .and_then(|_| writer.flush())
+        .context(format!("Unable to write to `{}`", port.to_string_lossy()))?;
+    let deadline = Instant::now() + args.timeout;
+    let mut parser = MidiParser::new();
+    let mut replies = 0;
+    loop {
+        let byte = match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(SourceEvent::Byte { byte, .. }) => byte,
+            Ok(SourceEvent::Error(e)) => {
+                bail!("Unable to read `{}`: {}", input.to_string_lossy(), e)
+            }
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let Some(MidiMessage::SystemExclusive(data)) = parser.parse_midi(byte).0 else {
+            continue;
+        };
+        // Replies to a request sent to every device come from any of them
+        let Some(identity) = sysex::identity(&data)
+            .filter(|identity| args.device == ALL_DEVICES || identity.device == args.device)
+        else {
+            continue;
+        };
+        if replies > 0 {
+            println!();
+        }
+        print!("{}", report(&identity));
+        replies += 1;
+    }
+    if replies == 0 {
+        bail!(
+            "No Identity Reply on `{}` within {:.1} s",
+            input.to_string_lossy(),
+            args.timeout.as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
+/// Describes a device by its Identity Reply
+fn report(identity: &sysex::Identity) -> String {
+    let id = midi::hex(&identity.manufacturer);
+    let manufacturer = match sysex::manufacturer(&identity.manufacturer) {
+        Some(m) => format!("{} ({}), {}", m.manufacturer, id, m.group.name()),
+        None => format!("Unknown ({})", id),
+    };
+    // Codes are shown as sent, least significant byte first, as manufacturers list them
+    let code = |value: u16| {
+        format!(
+            "{} ({})",
+            value,
+            midi::hex(&[(value & 0x7F) as u8, (value >> 7) as u8])
+        )
+    };
+    let version = identity.version;
+    format!(
+        "Device        {}\nManufacturer  {}\nFamily        {}\nModel         {}\nVersion       {}.{}.{}.{} ({})\n",
+        identity.device,
+        manufacturer,
+        code(identity.family),
+        code(identity.model),
+        version[0],
+        version[1],
+        version[2],
+        version[3],
+        midi::hex(&version)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_report() {
+        let identity = sysex::Identity {
+            device: 16,
+            manufacturer: vec![0x41],
+            family: 0x42,
+            model: 0x100,
+            version: [0, 1, 0, 0],
+        };
+        assert_eq!(
+            report(&identity),
+            "Device        16\n\
+             Manufacturer  Roland Corporation (41), Japan\n\
+             Family        66 (42 00)\n\
+             Model         256 (00 02)\n\
+             Version       0.1.0.0 (00 01 00 00)\n"
+        );
+    }
+}
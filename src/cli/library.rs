@@ -0,0 +1,186 @@
+//! `miditerm library`
+//!
+//! Lists, names, and sends again the SysEx dumps cataloged with `--library`, for keeping
+//! the patches of gear that has no librarian of its own
+
+use crate::{
+    cli,
+    config::Config,
+    library::{self, Added, Library},
+    source, syx,
+};
+use anyhow::{bail, Context};
+use std::{ffi::OsString, io::Write, path::PathBuf, thread, time::Duration, time::SystemTime};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct LibraryArgs {
+    /// Library directory to use instead of `~/.local/share/miditerm/library`
+    #[structopt(long, parse(from_os_str))]
+    dir: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, StructOpt)]
+enum Action {
+    /// List the dumps with their number, name, manufacturer, size, CRC-32, and the time
+    /// they were received
+    List,
+    /// Add the messages of `.syx` files, such as dumps from elsewhere
+    Add {
+        #[structopt(required = true, parse(from_os_str))]
+        files: Vec<PathBuf>,
+    },
+    /// Name a dump, given by its number or its name
+    Rename { dump: String, name: String },
+    /// Send dumps, given by their numbers or names, out a port
+    Send {
+        #[structopt(required = true)]
+        dumps: Vec<String>,
+
+        /// Name or path of the serial device or raw MIDI device to send to. Defaults to the
+        /// `port` of the configuration file
+        #[structopt(long, parse(from_os_str))]
+        port: Option<OsString>,
+
+        /// Time between dumps, which older devices need to store each one
+        #[structopt(long, default_value = "200ms", parse(try_from_str = cli::parse_duration))]
+        gap: Duration,
+    },
+    /// Take dumps out of the library and delete their files
+    Remove {
+        #[structopt(required = true)]
+        dumps: Vec<String>,
+    },
+}
+
+pub fn run(args: LibraryArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let Some(dir) = args.dir.or_else(Library::default_dir) else {
+        bail!("No home directory to keep the library in, give `--dir`");
+    };
+    let mut library = Library::open(&dir)?;
+    match args.action {
+        Action::List => print!("{}", list(&library)),
+        Action::Add { files } => {
+            for path in files {
+                for data in syx::load(&path)? {
+                    match library.add(&data, SystemTime::now())? {
+                        Added::New(number) => {
+                            println!("Added {:?} as dump {}", path, number)
+                        }
+                        Added::Known(number) => {
+                            println!("{:?} is already dump {}", path, number)
+                        }
+                    }
+                }
+            }
+        }
+        Action::Rename { dump, name } => {
+            library.rename(&dump, &name)?;
+            println!(
+                "Named dump {} `{}`",
+                library.find(&name)?.number,
+                name.trim()
+            );
+        }
+        Action::Send { dumps, port, gap } => {
+            let Some(port) = port.or_else(|| config.port.clone()) else {
+                bail!("`--port` is required");
+            };
+            // Every dump is checked before any is sent
+            let messages = dumps
+                .iter()
+                .map(|key| {
+                    let dump = library.find(key)?;
+                    Ok((dump, library.load(dump)?))
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            let mut out = source::open_output(&port)
+                .context(format!("Unable to open `{}`", port.to_string_lossy()))?;
+            for (i, (dump, data)) in messages.iter().enumerate() {
+                if i > 0 {
+                    thread::sleep(gap);
+                }
+                out.write_all(&[&[0xF0], &data[..], &[0xF7]].concat())
+                    .and_then(|_| out.flush())
+                    .context(format!("Unable to write to `{}`", port.to_string_lossy()))?;
+                println!(
+                    "Sent dump {} `{}`, {} bytes, to `{}`",
+                    dump.number,
+                    dump.name,
+                    dump.size,
+                    port.to_string_lossy()
+                );
+            }
+        }
+        Action::Remove { dumps } => {
+            for key in dumps {
+                let dump = library.remove(&key)?;
+                println!("Removed dump {} `{}`", dump.number, dump.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists the dumps of a library as a table
+fn list(library: &Library) -> String {
+    if library.dumps().is_empty() {
+        return "The library is empty, catalog dumps with `monitor --library`\n".to_string();
+    }
+    let width = library
+        .dumps()
+        .iter()
+        .map(|dump| dump.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let mut text = format!(
+        "{:>4}  {:<width$}  {:<24}  {:>7}  {:<8}  {}\n",
+        "#", "Name", "Manufacturer", "Bytes", "CRC-32", "Received (UTC)"
+    );
+    for dump in library.dumps() {
+        let mut manufacturer = dump.manufacturer_name();
+        if manufacturer.chars().count() > 24 {
+            manufacturer = manufacturer.chars().take(23).collect::<String>() + "…";
+        }
+        text += &format!(
+            "{:>4}  {:<width$}  {:<24}  {:>7}  {:<8}  {}\n",
+            dump.number,
+            dump.name,
+            manufacturer,
+            dump.size,
+            dump.crc,
+            library::format_time(dump.received)
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, time::UNIX_EPOCH};
+
+    #[test]
+    fn lists_dumps() {
+        let dir = env::temp_dir().join(format!("miditerm-library-list-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut library = Library::open(&dir).unwrap();
+        assert!(list(&library).starts_with("The library is empty"));
+        let time = UNIX_EPOCH + Duration::from_secs(1_710_018_902);
+        library.add(&[0x41, 0x10, 0x42], time).unwrap();
+        library.rename("1", "Fat Bass").unwrap();
+        let text = list(&library);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "   #  Name      Manufacturer                Bytes  CRC-32    Received (UTC)"
+        );
+        assert!(lines[1].starts_with("   1  Fat Bass  Roland Corporation              5  "));
+        assert!(lines[1].ends_with("  2024-03-09 21:15:02"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
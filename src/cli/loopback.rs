@@ -0,0 +1,347 @@
+//! `miditerm loopback`
+//!
+//! Qualifies a MIDI interface by sending test messages out of one port and receiving them
+//! on another, or on the same port with its output wired back into its input. Each test
+//! message is a non-commercial SysEx carrying its sequence number, the time it was sent, and
+//! a pattern of data bytes, so every message that comes back is checked byte for byte and
+//! timed from its own contents
+
+use crate::{
+    cli,
+    config::Config,
+    midi,
+    source::{self, SourceEvent},
+};
+use anyhow::{bail, Context};
+use std::{
+    ffi::OsString,
+    io::Write,
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Manufacturer ID reserved for non-commercial use, which devices ignore
+const NON_COMMERCIAL: u8 = 0x7D;
+/// Byte after the ID that marks the test messages
+const TAG: u8 = 0x4C;
+/// Bytes of the sequence number and of the send time in microseconds, 7 bits in each
+const SEQUENCE_BYTES: usize = 3;
+const TIME_BYTES: usize = 5;
+/// Bytes of a test message before its pattern: `F0`, the ID, the tag, the sequence number,
+/// and the send time
+const HEADER: usize = 3 + SEQUENCE_BYTES + TIME_BYTES;
+/// Upper bounds of the buckets of the latency histogram in milliseconds
+const BUCKETS: [f64; 8] = [0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+/// Width of the longest bar of the histogram
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, StructOpt)]
+pub struct LoopbackArgs {
+    /// Name or path of the serial device or raw MIDI device to send the test messages out of
+    #[structopt(long, parse(from_os_str))]
+    port: OsString,
+
+    /// Port the test messages come back on, if not `--port` itself
+    #[structopt(long, parse(from_os_str))]
+    input: Option<OsString>,
+
+    /// Baud rate of serial ports. Defaults to the `baud` of the configuration file
+    #[structopt(long)]
+    baud: Option<u32>,
+
+    /// Number of test messages to send
+    #[structopt(long, default_value = "1000")]
+    count: u32,
+
+    /// Data bytes of the pattern of each message, after its sequence number and send time
+    #[structopt(long, default_value = "16")]
+    size: usize,
+
+    /// Time between the starts of the test messages, such as `10ms`
+    #[structopt(long, default_value = "10ms", parse(try_from_str = cli::parse_duration))]
+    interval: Duration,
+
+    /// How long to wait for the last messages to come back after sending them
+    #[structopt(long, default_value = "1s", parse(try_from_str = cli::parse_duration))]
+    timeout: Duration,
+}
+
+pub fn run(args: LoopbackArgs, config: &Config) -> Result<(), anyhow::Error> {
+    if args.count == 0 || args.count >= 1 << (7 * SEQUENCE_BYTES) {
+        bail!(
+            "`--count` must be from 1 to {}",
+            (1 << (7 * SEQUENCE_BYTES)) - 1
+        );
+    }
+    let baud = args.baud.or(config.baud).unwrap_or(midi::MIDI_BAUD_RATE);
+    let (reader, mut writer) = match &args.input {
+        Some(input) if *input != args.port => {
+            let (_, writer) = source::open_duplex(&args.port, baud)
+                .context(format!("Unable to open `{}`", args.port.to_string_lossy()))?;
+            let (reader, _) = source::open_duplex(input, baud)
+                .context(format!("Unable to open `{}`", input.to_string_lossy()))?;
+            (reader, writer)
+        }
+        _ => source::open_duplex(&args.port, baud)
+            .context(format!("Unable to open `{}`", args.port.to_string_lossy()))?,
+    };
+    let input = args.input.as_deref().unwrap_or(&args.port);
+    println!(
+        "Sending {} test messages out of `{}` and receiving them on `{}`",
+        args.count,
+        args.port.to_string_lossy(),
+        input.to_string_lossy()
+    );
+
+    let rx = source::spawn_reader(reader);
+    let start = Instant::now();
+    let mut checker = Checker::new(start, args.count, args.size);
+    let receive = |until: Instant, checker: &mut Checker| -> Result<(), anyhow::Error> {
+        while !checker.done() {
+            match rx.recv_timeout(until.saturating_duration_since(Instant::now())) {
+                Ok(SourceEvent::Byte { arrival, byte, .. }) => checker.observe(arrival, byte),
+                Ok(SourceEvent::Error(e)) => {
+                    bail!("Unable to read `{}`: {}", input.to_string_lossy(), e)
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("`{}` stopped", input.to_string_lossy())
+                }
+            }
+        }
+        Ok(())
+    };
+    for sequence in 0..args.count {
+        receive(start + args.interval * sequence, &mut checker)?;
+        let bytes = message(sequence, start.elapsed(), args.size);
+        writer
+            .write_all(&bytes)
+            .and_then(|_| writer.flush())
+            .context(format!(
+                "Unable to write to `{}`",
+                args.port.to_string_lossy()
+            ))?;
+    }
+    receive(Instant::now() + args.timeout, &mut checker)?;
+
+    let report = checker.report();
+    let bytes = HEADER + args.size + 1;
+    println!(
+        "Sent      {} messages of {} bytes, one every {:.1} ms",
+        args.count,
+        bytes,
+        args.interval.as_secs_f64() * 1e3
+    );
+    println!(
+        "Wire      {:.2} ms per message at {} baud, included in the latency",
+        (bytes * 10) as f64 / baud as f64 * 1e3,
+        baud
+    );
+    print!("{}", report);
+    if checker.dropped() > 0 || checker.corrupted > 0 {
+        bail!("The loopback dropped or corrupted messages");
+    }
+    Ok(())
+}
+
+/// Returns the test message `sequence` sent at `time`
+fn message(sequence: u32, time: Duration, size: usize) -> Vec<u8> {
+    let mut bytes = vec![0xF0, NON_COMMERCIAL, TAG];
+    bytes.extend(seven_bit(sequence as u64, SEQUENCE_BYTES));
+    bytes.extend(seven_bit(time.as_micros() as u64, TIME_BYTES));
+    bytes.extend((0..size).map(|i| ((sequence as usize * 7 + i) % 128) as u8));
+    bytes.push(0xF7);
+    bytes
+}
+
+/// Splits a number into `count` bytes of 7 bits, the most significant first
+fn seven_bit(value: u64, count: usize) -> impl Iterator<Item = u8> {
+    (0..count)
+        .rev()
+        .map(move |i| ((value >> (7 * i)) & 0x7F) as u8)
+}
+
+/// Joins bytes of 7 bits into a number
+fn from_seven_bit(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, b| (value << 7) | *b as u64)
+}
+
+/// Checks the bytes that come back against the test messages
+struct Checker {
+    start: Instant,
+    size: usize,
+    /// Test messages received intact, by sequence number
+    received: Vec<bool>,
+    /// Bytes of the message being received
+    message: Option<Vec<u8>>,
+    latencies: Vec<Duration>,
+    /// Messages with wrong bytes, cut short, or too long
+    corrupted: usize,
+    /// Messages received intact a second time
+    duplicated: usize,
+    /// Bytes outside of any message, besides System Real Time
+    stray: usize,
+}
+
+impl Checker {
+    fn new(start: Instant, count: u32, size: usize) -> Checker {
+        Checker {
+            start,
+            size,
+            received: vec![false; count as usize],
+            message: None,
+            latencies: vec![],
+            corrupted: 0,
+            duplicated: 0,
+            stray: 0,
+        }
+    }
+
+    /// Takes in a byte that arrived at `arrival`
+    fn observe(&mut self, arrival: Instant, byte: u8) {
+        match byte {
+            // Interfaces may add clocks or Active Sensing of their own
+            0xF8..=0xFF => {}
+            0xF0 => {
+                if self.message.replace(vec![byte]).is_some() {
+                    self.corrupted += 1;
+                }
+            }
+            0xF7 => match self.message.take() {
+                Some(mut bytes) => {
+                    bytes.push(byte);
+                    self.check(arrival, &bytes);
+                }
+                None => self.stray += 1,
+            },
+            0x80..=0xF6 => {
+                if self.message.take().is_some() {
+                    self.corrupted += 1;
+                }
+                self.stray += 1;
+            }
+            _ => match &mut self.message {
+                Some(bytes) => bytes.push(byte),
+                None => self.stray += 1,
+            },
+        }
+    }
+
+    /// Checks a complete message against the test message its header names
+    fn check(&mut self, arrival: Instant, bytes: &[u8]) {
+        if bytes.len() != HEADER + self.size + 1 || bytes[1..3] != [NON_COMMERCIAL, TAG] {
+            self.corrupted += 1;
+            return;
+        }
+        let sequence = from_seven_bit(&bytes[3..3 + SEQUENCE_BYTES]) as u32;
+        let sent = Duration::from_micros(from_seven_bit(&bytes[3 + SEQUENCE_BYTES..HEADER]));
+        let intact = message(sequence, sent, self.size) == bytes;
+        match self.received.get_mut(sequence as usize) {
+            Some(received) if intact && *received => self.duplicated += 1,
+            Some(received) if intact => {
+                *received = true;
+                let latency = arrival.saturating_duration_since(self.start + sent);
+                self.latencies.push(latency);
+            }
+            _ => self.corrupted += 1,
+        }
+    }
+
+    /// Returns `true` once every test message came back intact
+    fn done(&self) -> bool {
+        self.latencies.len() == self.received.len()
+    }
+
+    /// Returns the number of test messages that never came back intact
+    fn dropped(&self) -> usize {
+        self.received.len() - self.latencies.len()
+    }
+
+    /// Describes what came back and the distribution of the latency
+    fn report(&self) -> String {
+        let mut text = format!(
+            "Received  {} intact, {} corrupted, {} duplicated, {} dropped, {} stray bytes\n",
+            self.latencies.len(),
+            self.corrupted,
+            self.duplicated,
+            self.dropped(),
+            self.stray
+        );
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let ms = |d: &Duration| d.as_secs_f64() * 1e3;
+        let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else {
+            return text;
+        };
+        let at =
+            |fraction: f64| ms(&sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]);
+        let mean = sorted.iter().map(ms).sum::<f64>() / sorted.len() as f64;
+        text += &format!(
+            "Latency   min {:.3} ms, median {:.3} ms, 95% {:.3} ms, 99% {:.3} ms, max {:.3} ms, mean {:.3} ms\n",
+            ms(min),
+            at(0.5),
+            at(0.95),
+            at(0.99),
+            ms(max),
+            mean
+        );
+        let mut counts = [0; BUCKETS.len() + 1];
+        for latency in &sorted {
+            counts[BUCKETS.partition_point(|bound| ms(latency) >= *bound)] += 1;
+        }
+        let most = counts.iter().max().copied().unwrap_or(1).max(1);
+        for (i, count) in counts.iter().enumerate() {
+            let label = match BUCKETS.get(i) {
+                Some(bound) => format!("< {} ms", bound),
+                None => format!(">= {} ms", BUCKETS[BUCKETS.len() - 1]),
+            };
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(most));
+            text += &format!("  {:>10} {:>7} {}\n", label, count, bar);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_messages() {
+        let start = Instant::now();
+        let mut checker = Checker::new(start, 4, 8);
+        let ms = Duration::from_millis;
+        let feed = |checker: &mut Checker, bytes: &[u8], late: u64| {
+            for byte in bytes {
+                checker.observe(start + ms(late), *byte);
+            }
+        };
+        // Intact, with a clock inside
+        let mut first = message(0, ms(0), 8);
+        first.insert(5, 0xF8);
+        feed(&mut checker, &first, 2);
+        // A pattern byte flipped
+        let mut second = message(1, ms(10), 8);
+        second[HEADER + 2] ^= 1;
+        feed(&mut checker, &second, 12);
+        // Cut short by the next message, which is intact
+        let third = message(2, ms(20), 8);
+        feed(&mut checker, &third[..6], 23);
+        feed(&mut checker, &third, 24);
+        feed(&mut checker, &third, 25);
+        feed(&mut checker, &[0x40, 0xF7], 26);
+
+        assert_eq!(checker.latencies, [ms(2), ms(4)]);
+        assert_eq!(
+            (checker.corrupted, checker.duplicated, checker.stray),
+            (2, 1, 2)
+        );
+        assert_eq!(checker.dropped(), 2);
+        let report = checker.report();
+        assert!(report.starts_with("Received  2 intact, 2 corrupted, 1 duplicated, 2 dropped"));
+        assert!(report.contains("min 2.000 ms"));
+        assert!(report.contains("< 5 ms       2 #"));
+    }
+}
@@ -0,0 +1,1392 @@
+//! Command line interface
+//!
+//! Each subcommand has its own module with its arguments and the function that runs it.
+//! Options shared by several subcommands are flattened into their arguments
+
+mod agent;
+mod clock;
+mod control;
+mod convert;
+mod decode;
+mod diff;
+mod format;
+mod generate;
+mod identify;
+mod library;
+mod loopback;
+mod monitor;
+mod pack;
+mod panic;
+mod play;
+mod ports;
+mod print;
+mod query;
+mod raw;
+mod replay;
+mod send;
+mod tail;
+
+use crate::{
+    analysis::{
+        clock::ClockAnalyzer,
+        smoothness::SmoothnessAnalyzer,
+        stats::{Budgets, Limits, Statistics},
+        summary::Summary,
+        Settings, Strictness,
+    },
+    capture::{Capture, CaptureEvent, Filter, TimeFormat, Timeline, MESSAGE_STATUSES},
+    config::{Config, FilterConfig, RuleConfig, TriggerConfig},
+    midi::{self, notes::NoteNaming, MidiAnalysis},
+    script::Script,
+    sink::{
+        trigger::{Action, Arm, Trigger, When},
+        CaptureRecorder, CsvLogger, JsonlLogger, Librarian, LogFormat, MidicsvExporter, RawTee,
+        Router, Rule, Sink, SmfRecorder, StoreSink, SyxExporter, Thru, Triggers, UmpWriter,
+    },
+    smf,
+    source::{
+        pcap::{Direction, UsbFilter},
+        port, Source, SourceEvent,
+    },
+    state::{StateStore, UiState},
+    store::{self, Query},
+    ui::{self, Scrollback},
+};
+use anyhow::{bail, Context};
+use control::Control;
+use print::{ColorChoice, Printer, SummaryFormat};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::{OsStr, OsString},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// MIDI debugger and protocol analyzer
+#[derive(Debug, StructOpt)]
+#[structopt(name = "miditerm")]
+pub struct Cli {
+    /// Configuration file to use instead of `~/.config/miditerm/miditerm.toml`
+    #[structopt(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Profile of the configuration to use, such as `studio` or `live`, whose settings
+    /// replace those of the rest of the file. The TUI keeps its state for each profile, and
+    /// asks for one on its start screen when none is given and no source is
+    #[structopt(long, global = true)]
+    profile: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+impl Cli {
+    /// Loads the configuration and runs the subcommand
+    pub fn run(self) -> Result<(), anyhow::Error> {
+        let config = match &self.config {
+            Some(path) if !path.exists() => bail!("Configuration `{:?}` does not exist", path),
+            Some(path) => Config::load(path)?,
+            None => match Config::default_path() {
+                Some(path) => Config::load(&path)?,
+                None => Config::default(),
+            },
+        };
+        let config = match &self.profile {
+            Some(name) => config.with_profile(name)?,
+            None => config,
+        };
+        self.command.run(&config)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Watch a serial port or file in the terminal UI
+    Monitor(monitor::MonitorArgs),
+    /// Transmit bytes out a serial port
+    Send(send::SendArgs),
+    /// Silence stuck notes with Sustain off, All Sound Off, All Notes Off, and Reset All
+    /// Controllers on every channel
+    Panic(panic::PanicArgs),
+    /// Send a steady MIDI clock out a port, with Start, Stop, and tempo changes from the
+    /// keyboard
+    Clock(clock::ClockArgs),
+    /// Send or write test streams for validating other parsers and MIDI implementations
+    Generate(generate::GenerateArgs),
+    /// Ask the devices on a port who they are with a Universal Identity Request, and
+    /// print their manufacturer, family, model, and version
+    Identify(identify::IdentifyArgs),
+    /// Send test messages out a port and receive them back, checking every byte and
+    /// measuring the round-trip latency, to qualify MIDI interfaces
+    Loopback(loopback::LoopbackArgs),
+    /// Print the analysis of every byte of a file
+    Decode(decode::DecodeArgs),
+    /// Compare two SysEx dumps byte by byte, naming the parameters that differ in known
+    /// Roland and Yamaha formats
+    SysexDiff(diff::DiffArgs),
+    /// List the serial ports and MIDI devices of this machine,
+    /// marking the ones that look like MIDI interfaces
+    ListPorts,
+    /// Play back a capture recorded with `--record`, preserving its timing
+    Replay(replay::ReplayArgs),
+    /// Play a Standard MIDI File out a port with the timing of its tempo map, showing the
+    /// outgoing stream
+    Play(play::PlayArgs),
+    /// Convert a file into another format
+    Convert(convert::ConvertArgs),
+    /// Print the events of a session that match a filter expression
+    Query(query::QueryArgs),
+    /// Push the bytes of a serial port to a `miditerm monitor --listen` on another machine
+    Agent(agent::AgentArgs),
+    /// Print every byte of a serial port with its bits and any framing or parity error,
+    /// for links that corrupt bytes
+    Raw(raw::RawArgs),
+    /// Follow a session file as another miditerm writes it with `--session`
+    Tail(tail::TailArgs),
+    /// List, name, and send again the SysEx dumps cataloged with `--library`
+    Library(library::LibraryArgs),
+    /// Install or export packs of device support: patch maps, drum maps, a decoder script,
+    /// and the profile that uses them
+    Pack(pack::PackArgs),
+}
+
+impl Command {
+    /// Runs the subcommand with the defaults of the configuration
+    pub fn run(self, config: &Config) -> Result<(), anyhow::Error> {
+        match self {
+            Command::Monitor(args) => monitor::run(args, config),
+            Command::Send(args) => send::run(args),
+            Command::Panic(args) => panic::run(args, config),
+            Command::Clock(args) => clock::run(args),
+            Command::Generate(args) => generate::run(args),
+            Command::Identify(args) => identify::run(args, config),
+            Command::Loopback(args) => loopback::run(args, config),
+            Command::Decode(args) => decode::run(args, config),
+            Command::SysexDiff(args) => diff::run(args),
+            Command::ListPorts => ports::run(),
+            Command::Replay(args) => replay::run(args, config),
+            Command::Play(args) => play::run(args, config),
+            Command::Convert(args) => convert::run(args, config),
+            Command::Query(args) => query::run(args, config),
+            Command::Agent(args) => agent::run(args, config),
+            Command::Raw(args) => raw::run(args, config),
+            Command::Tail(args) => tail::run(args, config),
+            Command::Library(args) => library::run(args, config),
+            Command::Pack(args) => pack::run(args, config),
+        }
+    }
+}
+
+/// How the analysis is checked
+#[derive(Debug, StructOpt)]
+pub struct AnalysisArgs {
+    /// How harshly questionable MIDI is reported
+    #[structopt(long, default_value = "normal", possible_values = &["lenient", "normal", "strict"])]
+    strictness: Strictness,
+
+    /// Check messages against the General MIDI Level 1 specification
+    #[structopt(long)]
+    gm: bool,
+
+    /// How notes are named. Defaults to the `note_names` of the configuration file
+    #[structopt(long, possible_values = &["english", "german", "solfege"])]
+    note_names: Option<NoteNaming>,
+}
+
+impl AnalysisArgs {
+    /// Returns the analysis settings, taking what is not given on the command line from
+    /// the configuration
+    fn settings(&self, config: &Config) -> Settings {
+        Settings {
+            strictness: self.strictness,
+            gm: self.gm,
+            naming: self.note_names.unwrap_or(config.note_names),
+        }
+    }
+}
+
+/// Which traffic is decoded from pcap captures
+#[derive(Debug, StructOpt)]
+pub struct PcapArgs {
+    /// USB address of the MIDI device in a pcap capture. All devices are decoded if omitted
+    #[structopt(long)]
+    pcap_device: Option<u16>,
+
+    /// Direction of the USB traffic decoded from a pcap capture:
+    /// `in` from the device, `out` to it
+    #[structopt(long, default_value = "in", possible_values = &["in", "out"])]
+    pcap_direction: Direction,
+}
+
+impl PcapArgs {
+    /// Returns the source reading `path`, chosen by its extension:
+    /// `.syx` dumps, `.mtcap` captures, `.pcap` and `.pcapng` USB captures, or raw bytes
+    fn file_source(&self, path: PathBuf) -> Source {
+        match extension(&path).as_deref() {
+            Some("syx") => Source::Syx(path),
+            Some("mtcap") => Source::Replay {
+                path,
+                speed: 0.0,
+                output: None,
+            },
+            Some("pcap") | Some("pcapng") => Source::Pcap {
+                path,
+                filter: UsbFilter {
+                    direction: self.pcap_direction,
+                    device: self.pcap_device,
+                },
+            },
+            _ => Source::File(path),
+        }
+    }
+}
+
+/// Which events are displayed. Hidden events are still analyzed, counted, and written to
+/// the outputs. Each option given replaces the same setting of the `[filter]` configuration
+#[derive(Debug, StructOpt)]
+pub struct FilterArgs {
+    /// Only display the messages of these channels, e.g. `1,2,10`.
+    /// Messages without a channel are always displayed
+    #[structopt(long, use_delimiter = true)]
+    channels: Vec<u8>,
+
+    /// Hide these types of messages, e.g. `clock,activesense,aftertouch`.
+    /// Besides the names of single types, `notes` and `realtime` name groups of them
+    #[structopt(long, use_delimiter = true)]
+    hide: Vec<String>,
+
+    /// Only display these types of messages, e.g. `notes,cc`
+    #[structopt(long, use_delimiter = true)]
+    only: Vec<String>,
+}
+
+impl FilterArgs {
+    /// Returns `true` if any filter is given on the command line
+    fn given(&self) -> bool {
+        !(self.channels.is_empty() && self.hide.is_empty() && self.only.is_empty())
+    }
+
+    fn filter(&self, config: &FilterConfig) -> Result<Filter, anyhow::Error> {
+        let channels = given_or(&self.channels, &config.channels);
+        let hide = given_or(&self.hide, &config.hide);
+        let only = given_or(&self.only, &config.only);
+
+        let mut filter = Filter::default();
+        if let Some(channel) = channels.iter().find(|ch| !(1..=16).contains(*ch)) {
+            bail!("`{}` is not a channel from 1 to 16", channel);
+        }
+        if !channels.is_empty() {
+            let shown = channels.iter().fold(0_u16, |mask, ch| mask | 1 << (ch - 1));
+            filter.hidden_channels = !shown;
+        }
+        if !only.is_empty() {
+            let mut shown = BTreeSet::new();
+            for name in only {
+                shown.extend(store::parse_types(name)?);
+            }
+            filter.hidden_statuses = MESSAGE_STATUSES
+                .into_iter()
+                .filter(|s| !shown.contains(s))
+                .collect();
+        }
+        for name in hide {
+            filter.hidden_statuses.extend(store::parse_types(name)?);
+        }
+        Ok(filter)
+    }
+}
+
+/// Returns the values given on the command line, or the configured ones if there are none
+fn given_or<'a, T>(given: &'a [T], configured: &'a [T]) -> &'a [T] {
+    if given.is_empty() {
+        configured
+    } else {
+        given
+    }
+}
+
+/// Checks the thru rules of the configuration, numbering channels from 0
+fn rules(configured: &[RuleConfig]) -> Result<Vec<Rule>, anyhow::Error> {
+    let channel = |channel: Option<u8>| match channel {
+        Some(ch) if !(1..=16).contains(&ch) => bail!("`{}` is not a channel from 1 to 16", ch),
+        channel => Ok(channel.map(|ch| ch - 1)),
+    };
+    let control = |control: &str| {
+        control
+            .parse::<u8>()
+            .ok()
+            .filter(|c| *c < 120)
+            .context(format!("`{}` is not a controller from 0 to 119", control))
+    };
+    let rule = |rule: &RuleConfig| -> Result<Rule, anyhow::Error> {
+        let mut drop = BTreeSet::new();
+        for name in &rule.drop {
+            drop.extend(store::parse_types(name)?);
+        }
+        let mut controls = BTreeMap::new();
+        for (from, to) in &rule.cc {
+            controls.insert(control(from)?, control(&to.to_string())?);
+        }
+        if rule
+            .velocity
+            .is_some_and(|factor| factor.is_nan() || factor <= 0.0)
+        {
+            bail!("Velocities can only be scaled by a positive factor");
+        }
+        Ok(Rule {
+            channel: channel(rule.channel)?,
+            drop,
+            transpose: rule.transpose,
+            velocity: rule.velocity,
+            controls,
+            to_channel: channel(rule.to_channel)?,
+        })
+    };
+    configured
+        .iter()
+        .enumerate()
+        .map(|(i, configured)| rule(configured).context(format!("Invalid rule {}", i + 1)))
+        .collect()
+}
+
+/// Checks the triggers of the configuration and opens the ports they send to
+fn triggers(configured: &[TriggerConfig]) -> Result<Triggers, anyhow::Error> {
+    let mut ports: Vec<(OsString, Box<dyn Write>)> = vec![];
+    let mut trigger = |trigger: &TriggerConfig| -> Result<Trigger, anyhow::Error> {
+        let channel = match trigger.channel {
+            Some(ch) if !(1..=16).contains(&ch) => bail!("`{}` is not a channel from 1 to 16", ch),
+            channel => channel.map(|ch| ch - 1),
+        };
+        let when = match (trigger.message.as_deref(), trigger.control, trigger.crosses) {
+            (Some(name), None, None) => match condition(name)? {
+                When::Message { statuses, .. } => When::Message { statuses, channel },
+                When::Control { control, .. } => When::Control { channel, control },
+                when => when,
+            },
+            (None, None, None) => When::Message {
+                statuses: BTreeSet::new(),
+                channel,
+            },
+            (None | Some("cc"), Some(control), None) if control < 120 => {
+                When::Control { channel, control }
+            }
+            (None | Some("cc"), Some(control), Some(threshold))
+                if control < 120 && threshold < 128 =>
+            {
+                When::Crosses {
+                    channel,
+                    control,
+                    threshold,
+                }
+            }
+            _ => bail!(
+                "Wait for a `message` or a `control`, which may have to cross a value in \
+                 `crosses`, with a controller from 0 to 119"
+            ),
+        };
+        let mut actions = vec![];
+        if let Some(command) = &trigger.run {
+            actions.push(Action::Run(command.clone()));
+        }
+        if let Some(path) = &trigger.save {
+            actions.push(Action::Save(path.clone()));
+        }
+        if let Some(message) = &trigger.send {
+            let Some(name) = &trigger.out else {
+                bail!("`send` needs a port to send to in `out`");
+            };
+            let port = match ports.iter().position(|(opened, _)| opened == name) {
+                Some(port) => port,
+                None => {
+                    let port = port::open(name, midi::MIDI_BAUD_RATE).context(format!(
+                        "Unable to open serial port `{}`",
+                        name.to_string_lossy()
+                    ))?;
+                    ports.push((name.clone(), port));
+                    ports.len() - 1
+                }
+            };
+            actions.push(Action::Send {
+                message: message.clone(),
+                bytes: send::parse_message(message)?,
+                port,
+            });
+        }
+        if actions.is_empty() {
+            bail!("Nothing to do, give `run`, `save`, or `send`");
+        }
+        Ok(Trigger { when, actions })
+    };
+    let triggers = configured
+        .iter()
+        .enumerate()
+        .map(|(i, configured)| trigger(configured).context(format!("Invalid trigger {}", i + 1)))
+        .collect::<Result<_, _>>()?;
+    let ports = ports
+        .into_iter()
+        .map(|(name, port)| (name.to_string_lossy().into_owned(), port))
+        .collect();
+    Ok(Triggers::new(triggers, ports))
+}
+
+/// Settings of the files written by `convert` and `query`
+#[derive(Debug, StructOpt)]
+pub struct ExportArgs {
+    /// Resolution of Standard MIDI File and `midicsv` output in pulses per quarter note
+    #[structopt(long, default_value = "480")]
+    ppq: u16,
+
+    /// Tempo of Standard MIDI File and `midicsv` output when the input has no MIDI clock
+    #[structopt(long, default_value = "120")]
+    bpm: f64,
+
+    /// Controllers plotted by `cclanes` output, e.g. `1,7,74`. All are plotted if omitted
+    #[structopt(long, use_delimiter = true, parse(try_from_str = parse_control))]
+    controls: Vec<u8>,
+
+    /// Columns and rows of the screen recorded by `cast` output
+    #[structopt(long, default_value = "120x36", parse(try_from_str = parse_screen))]
+    cast_size: (u16, u16),
+
+    /// Frames per second of the capture recorded by `cast` output. Quiet stretches are
+    /// skipped over
+    #[structopt(long, default_value = "10")]
+    cast_fps: f64,
+}
+
+/// Where the capture is written as it is received
+#[derive(Debug, StructOpt)]
+pub struct OutputArgs {
+    /// Copy every received byte to this file as it arrives, as raw MIDI that can be read
+    /// again with `--file`
+    #[structopt(long, parse(from_os_str))]
+    tee: Option<PathBuf>,
+
+    /// Record every received byte with its timestamp to a capture file for later replay
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Record the capture to the given Standard MIDI File.
+    /// In the TUI this is also the file written when recording is started with `r`
+    #[structopt(long, parse(from_os_str))]
+    record_smf: Option<PathBuf>,
+
+    /// Resolution of the recorded Standard MIDI File in pulses per quarter note
+    #[structopt(long, default_value = "480")]
+    ppq: u16,
+
+    /// Tempo of the recorded Standard MIDI File when no MIDI clock is received
+    #[structopt(long, default_value = "120")]
+    bpm: f64,
+
+    /// Save every received System Exclusive message as a `.syx` file in this directory.
+    /// In the TUI this is also where `x` saves the selected SysEx message
+    #[structopt(long, parse(from_os_str))]
+    save_sysex: Option<PathBuf>,
+
+    /// Catalog every received SysEx dump in this library directory, skipping the ones
+    /// already in it, to name and send again with `miditerm library`
+    #[structopt(long, parse(from_os_str))]
+    library: Option<PathBuf>,
+
+    /// Translate received messages into Universal MIDI Packets and write them to
+    /// `tcp:HOST:PORT`, `udp:HOST:PORT`, or a file
+    #[structopt(long)]
+    ump_out: Option<String>,
+
+    /// UMP group the translated messages are sent on
+    #[structopt(long, default_value = "0")]
+    ump_group: u8,
+
+    /// Write a structured log of every parsed message to this file
+    #[structopt(long, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+
+    /// Format of the structured log. `midicsv` timestamps use `--ppq` and `--bpm`
+    #[structopt(long, default_value = "jsonl", possible_values = &["jsonl", "csv", "midicsv"])]
+    log_format: LogFormat,
+
+    /// Keep the capture in a session that survives restarts, appending to it if it exists.
+    /// `.db` and `.sqlite` files are sqlite databases, anything else is a capture file.
+    /// The TUI starts with the events already in the session
+    #[structopt(long, parse(from_os_str))]
+    session: Option<PathBuf>,
+
+    /// Forward every received message out a serial port as `PORT[,TRANSLATION..]`, e.g.
+    /// `/dev/ttyUSB1,pressure-to-cc=1,velocity=100`. Translations are `pressure-to-cc`,
+    /// `velocity`, `bend-to-cc`, `invert-cc` for pedals of the opposite polarity, and
+    /// `zone=LOW..HIGH:CHANNEL[:TRANSPOSE]` to split the keyboard, e.g. `zone=C-1..B3:2:+12`.
+    /// Notes outside every zone of a route are not sent. Each change is reported, and a port
+    /// of `-` only reports them. Can be given more than once
+    #[structopt(long, number_of_values = 1, allow_hyphen_values = true)]
+    route: Vec<String>,
+
+    /// Retransmit everything received out a serial port with running status, to run inline
+    /// in a MIDI chain, as `PORT[,OPTION..]`, e.g. `/dev/ttyUSB1,strip-active-sensing`.
+    /// Options are `strip-realtime`, `strip-active-sensing`, and `reencode-sysex` to send
+    /// SysEx once it is complete instead of as it arrives. Several inputs are merged into the
+    /// port at message boundaries, like a MIDI merge box
+    #[structopt(long)]
+    thru: Option<String>,
+
+    /// Rhai script whose `on_message(msg)` is called with every message, to annotate the
+    /// capture and to change, drop, or add to the messages sent out the `--thru` port.
+    /// Defaults to the `script` of the configuration
+    #[structopt(long, parse(from_os_str))]
+    script: Option<PathBuf>,
+
+    /// Write the statistics of the capture as JSON to this file when it ends, `-` for stdout
+    #[structopt(long, parse(from_os_str))]
+    stats_json: Option<PathBuf>,
+}
+
+/// When a capture ends on its own, and how it fails, for unattended use in test rigs and CI
+#[derive(Debug, StructOpt)]
+pub struct LimitArgs {
+    /// Stop capturing after this long, e.g. `30s`, `5m`, `1h`, or `500ms`.
+    /// A number without a unit is in seconds
+    #[structopt(long, parse(try_from_str = parse_duration))]
+    duration: Option<Duration>,
+
+    /// Stop capturing after this many bytes
+    #[structopt(long)]
+    max_bytes: Option<usize>,
+
+    /// Stop capturing after this many complete messages
+    #[structopt(long)]
+    max_messages: Option<usize>,
+
+    /// Exit with an error if any MIDI violation was observed
+    #[structopt(long)]
+    fail_on_violation: bool,
+
+    /// Raise an alarm when more than this much SysEx has been received, e.g. `64KB`.
+    /// The TUI then spills the capture to `--spill`
+    #[structopt(long, parse(try_from_str = parse_size))]
+    alarm_sysex: Option<usize>,
+
+    /// Raise an alarm when the capture grows beyond this size, e.g. `10MB`.
+    /// The TUI then spills the capture to `--spill`
+    #[structopt(long, parse(try_from_str = parse_size))]
+    alarm_total: Option<usize>,
+
+    /// Capture file the TUI writes the whole capture to once an alarm is raised, so it is
+    /// safe on disk
+    #[structopt(long, default_value = "miditerm-spill.mtcap", parse(from_os_str))]
+    spill: PathBuf,
+
+    /// Most events the TUI keeps, as a number of bytes received such as `100000`, or the
+    /// memory they take such as `50MB`. The oldest are dropped once it is full. Replaces
+    /// `scrollback` of the configuration, and is unlimited if neither is given
+    #[structopt(long)]
+    scrollback: Option<String>,
+
+    /// Capture file the events dropped from the scrollback of the TUI are written to
+    #[structopt(long, parse(from_os_str))]
+    scrollback_spill: Option<PathBuf>,
+
+    /// Hold the capture back until this is received, like the trigger of an oscilloscope:
+    /// a message type named like those of `--hide`, `ccN` for controller N,
+    /// `identity-reply`, or `violation`
+    #[structopt(long)]
+    arm_on: Option<String>,
+
+    /// Messages received before `--arm-on` is met that are kept in the capture
+    #[structopt(long, default_value = "100")]
+    pre_trigger: usize,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> Limits {
+        Limits {
+            duration: self.duration,
+            bytes: self.max_bytes,
+            messages: self.max_messages,
+            budgets: Budgets {
+                sysex: self.alarm_sysex,
+                total: self.alarm_total,
+            },
+        }
+    }
+
+    /// Returns the scrollback of the TUI given on the command line or in the configuration
+    fn scrollback(&self, config: &Config) -> Result<Option<Scrollback>, anyhow::Error> {
+        let Some(text) = self.scrollback.as_ref().or(config.scrollback.as_ref()) else {
+            return Ok(None);
+        };
+        let scrollback = text
+            .parse()
+            .context(format!("Invalid scrollback `{}`", text))?;
+        Ok(Some(scrollback))
+    }
+
+    fn arm(&self) -> Result<Option<Arm>, anyhow::Error> {
+        let Some(text) = &self.arm_on else {
+            return Ok(None);
+        };
+        let when = condition(text).context(format!("Invalid `--arm-on {}`", text))?;
+        Ok(Some(Arm::new(when, self.pre_trigger)))
+    }
+}
+
+/// Parses a condition written as a message type, `ccN`, `identity-reply`, or `violation`
+fn condition(text: &str) -> Result<When, anyhow::Error> {
+    let lower = text.to_lowercase();
+    if let Some(control) = lower.strip_prefix("cc").filter(|n| !n.is_empty()) {
+        let control = control
+            .parse::<u8>()
+            .ok()
+            .filter(|c| *c < 120)
+            .context(format!("`{}` is not a controller from 0 to 119", control))?;
+        return Ok(When::Control {
+            channel: None,
+            control,
+        });
+    }
+    Ok(match lower.as_str() {
+        "identity-reply" => When::IdentityReply,
+        "violation" => When::Violation,
+        _ => When::Message {
+            statuses: store::parse_types(text)?.into_iter().collect(),
+            channel: None,
+        },
+    })
+}
+
+/// Parses a size written as a number of bytes, optionally followed by `KB`, `MB`, or `GB`
+/// in units of 1024
+fn parse_size(text: &str) -> Result<usize, anyhow::Error> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let shift = match unit.to_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" => 10,
+        "M" | "MB" => 20,
+        "G" | "GB" => 30,
+        _ => bail!("`{}` is not a unit of size, use KB, MB, or GB", unit),
+    };
+    let number: usize = number
+        .parse()
+        .context(format!("`{}` is not a size", text))?;
+    match number.checked_mul(1 << shift) {
+        Some(size) => Ok(size),
+        None => bail!("`{}` is too large a size", text),
+    }
+}
+
+/// Parses a duration written as a number followed by `ms`, `s`, `m`, or `h`
+fn parse_duration(text: &str) -> Result<Duration, anyhow::Error> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let seconds = match unit {
+        "ms" => 1e-3,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => bail!("`{}` is not a unit of time, use ms, s, m, or h", unit),
+    };
+    let number: f64 = number
+        .parse()
+        .context(format!("`{}` is not a duration", text))?;
+    match Duration::try_from_secs_f64(number * seconds) {
+        Ok(duration) => Ok(duration),
+        Err(_) => bail!("`{}` is too long a duration", text),
+    }
+}
+
+/// Parses a controller number
+fn parse_control(text: &str) -> Result<u8, anyhow::Error> {
+    match text.parse() {
+        Ok(control) if control <= 127 => Ok(control),
+        _ => bail!("`{}` is not a controller from 0 to 127", text),
+    }
+}
+
+/// Parses the size of a screen written as columns and rows, such as `120x36`
+fn parse_screen(text: &str) -> Result<(u16, u16), anyhow::Error> {
+    let size = text
+        .split_once('x')
+        .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)));
+    match size {
+        Some((columns, rows)) if columns > 0 && rows > 0 => Ok((columns, rows)),
+        _ => bail!("`{}` is not a screen size, such as `120x36`", text),
+    }
+}
+
+/// How the analysis of headless captures is printed
+#[derive(Debug, StructOpt)]
+pub struct PrintArgs {
+    /// Color printed lines by severity: `auto` when printing to a terminal, `always`, or `never`
+    #[structopt(long, default_value = "auto", possible_values = &["auto", "always", "never"])]
+    color: ColorChoice,
+
+    /// Format of the summary printed when the session ends: `text`, or `json` for scripts
+    #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+    summary_format: SummaryFormat,
+}
+
+/// Where a capture is presented while it runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Display {
+    /// Interactive terminal UI
+    Tui,
+    /// Analysis of every byte printed to stdout
+    Print,
+    /// Nothing but errors
+    Quiet,
+}
+
+/// How a capture is presented while it runs
+struct View<'a> {
+    display: Display,
+    /// Events that are displayed
+    filter: Filter,
+    /// Provides the names, time format, theme, and layouts
+    config: &'a Config,
+    print: &'a PrintArgs,
+    /// Serial port the pads of the TUI and the `send` command send to
+    out: Option<&'a OsStr>,
+    /// Commands are read from stdin while printing
+    commands: bool,
+    /// Capture the TUI compares the live one with
+    reference: Option<ui::Reference>,
+    /// State of the TUI restored from the last session
+    state: UiState,
+    /// Where the state of the TUI is saved when it quits
+    state_store: Option<StateStore>,
+}
+
+/// Pads used when the configuration has none, a General MIDI drum kit on channel 10
+const DEFAULT_PADS: [(char, &str, &str); 8] = [
+    ('z', "noteon 10 36 100", "Kick"),
+    ('x', "noteon 10 38 100", "Snare"),
+    ('c', "noteon 10 42 100", "Closed Hi-Hat"),
+    ('v', "noteon 10 46 100", "Open Hi-Hat"),
+    ('b', "noteon 10 45 100", "Low Tom"),
+    ('n', "noteon 10 50 100", "High Tom"),
+    ('m', "noteon 10 49 100", "Crash"),
+    (',', "noteon 10 51 100", "Ride"),
+];
+
+/// Parses the messages of the pads of the configuration
+fn pads(config: &Config) -> Result<Vec<ui::Pad>, anyhow::Error> {
+    if config.pads.is_empty() {
+        return Ok(DEFAULT_PADS
+            .iter()
+            .map(|(key, message, label)| ui::Pad {
+                key: *key,
+                label: label.to_string(),
+                bytes: send::parse_message(message).expect("default pads are valid"),
+            })
+            .collect());
+    }
+    config
+        .pads
+        .iter()
+        .map(|pad| {
+            Ok(ui::Pad {
+                key: pad.key,
+                label: pad.label.clone().unwrap_or_else(|| pad.message.clone()),
+                bytes: send::parse_message(&pad.message)
+                    .context(format!("Invalid message of pad `{}`", pad.key))?,
+            })
+        })
+        .collect()
+}
+
+/// Runs the source through the analyzer into the outputs until it closes or the user quits
+fn run_capture(
+    source: Option<Source>,
+    outputs: &OutputArgs,
+    settings: Settings,
+    limits: &LimitArgs,
+    source_timestamps: bool,
+    view: View,
+) -> Result<(), anyhow::Error> {
+    let display = view.display;
+    // Recording can also be started from the TUI
+    smf::check_timing(outputs.ppq, outputs.bpm)?;
+    let mut sinks: Vec<Box<dyn Sink>> = vec![];
+    if let Some(path) = &outputs.tee {
+        sinks.push(Box::new(RawTee::create(path)?));
+    }
+    if let Some(path) = &outputs.record {
+        sinks.push(Box::new(CaptureRecorder::create(path)?));
+    }
+    if let Some(dir) = &outputs.save_sysex {
+        sinks.push(Box::new(SyxExporter::new(dir.clone())?));
+    }
+    if let Some(dir) = &outputs.library {
+        sinks.push(Box::new(Librarian::open(dir)?));
+    }
+    if let Some(path) = &outputs.log_file {
+        match outputs.log_format {
+            LogFormat::Jsonl => sinks.push(Box::new(JsonlLogger::create(path)?)),
+            LogFormat::Csv => sinks.push(Box::new(CsvLogger::create(path)?)),
+            LogFormat::Midicsv => sinks.push(Box::new(MidicsvExporter::new(
+                path.clone(),
+                outputs.ppq,
+                outputs.bpm,
+            ))),
+        }
+    }
+    if let Some(target) = &outputs.ump_out {
+        sinks.push(Box::new(UmpWriter::open(target, outputs.ump_group)?));
+    }
+    let mut zones = vec![];
+    for route in &outputs.route {
+        let router = Router::open(route)?;
+        zones.extend_from_slice(router.zones());
+        sinks.push(Box::new(router));
+    }
+    let script = outputs
+        .script
+        .as_deref()
+        .or(view.config.script.as_deref())
+        .map(Script::load)
+        .transpose()?;
+    match (&outputs.thru, script) {
+        (Some(spec), script) => {
+            let rules = rules(&view.config.rules)?;
+            sinks.push(Box::new(Thru::open(spec, rules, script)?));
+        }
+        // Without a thru port the script only annotates the capture
+        (None, Some(script)) => sinks.push(Box::new(script)),
+        (None, None) => {}
+    }
+    if !view.config.triggers.is_empty() {
+        sinks.push(Box::new(triggers(&view.config.triggers)?));
+    }
+    let mut history = vec![];
+    if let Some(path) = &outputs.session {
+        let store = store::open(path, settings)?;
+        history = store.query(&Query::default())?;
+        sinks.push(Box::new(StoreSink::new(store)));
+    }
+    // A resumed session continues from the time it ended
+    let start = history
+        .last()
+        .and_then(|e| Instant::now().checked_sub(e.time))
+        .unwrap_or_else(Instant::now);
+
+    if display == Display::Tui {
+        let options = ui::Options {
+            record: outputs.record_smf.is_some(),
+            smf_path: outputs
+                .record_smf
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("miditerm.mid")),
+            ppq: outputs.ppq,
+            bpm: outputs.bpm,
+            sysex_dir: outputs
+                .save_sysex
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(".")),
+            source_timestamps,
+            settings,
+            filter: view.filter,
+            limits: limits.limits(),
+            arm: limits.arm()?,
+            spill: limits.spill.clone(),
+            scrollback: limits.scrollback(view.config)?,
+            scrollback_spill: limits.scrollback_spill.clone(),
+            start,
+            history,
+            pads: pads(view.config)?,
+            keymap: ui::Keymap::new(&view.config.keys)?,
+            palette: ui::Palette::new(view.config.theme, &view.config.colors)?,
+            zones,
+            config: view.config.clone(),
+            reference: view.reference,
+            sources: source.as_ref().and_then(Source::input_names),
+            state: view.state,
+            state_store: view.state_store,
+        };
+        let summary = ui::run_application(options, source, sinks, midi_out(view.out)?)?;
+        summarize(&summary, outputs, view.print, display)?;
+        return check_violations(&summary.stats, limits);
+    }
+
+    if let Some(path) = &outputs.record_smf {
+        sinks.push(Box::new(SmfRecorder::new(
+            path.clone(),
+            outputs.ppq,
+            outputs.bpm,
+        )));
+    }
+    let source = source.context("No source to read from")?;
+    let timeline = Timeline::new(start, source_timestamps);
+    let sources = source.input_names();
+    let mut console = Console {
+        printer: Printer {
+            filter: view.filter,
+            names: &view.config.names,
+            time_format: view.config.timestamps.unwrap_or(TimeFormat::Seconds),
+            color: view.print.color.enabled(),
+            sources: sources.as_deref(),
+        },
+        commands: view.commands.then(control::spawn_stdin),
+        out: match view.commands {
+            true => midi_out(view.out)?,
+            false => None,
+        },
+        paused: false,
+        marks: 0,
+        history: vec![],
+    };
+    let summary = run_headless(
+        source,
+        timeline,
+        settings,
+        limits.limits(),
+        limits.arm()?,
+        &mut sinks,
+        (display == Display::Print).then_some(&mut console),
+    )
+    .context("Error parsing MIDI")?;
+    summarize(&summary, outputs, view.print, display)?;
+    check_violations(&summary.stats, limits)
+}
+
+/// Opens the serial port used as MIDI Out, if one is given
+fn midi_out(name: Option<&OsStr>) -> Result<Option<Box<dyn Write + Send>>, anyhow::Error> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let port = port::open(name, midi::MIDI_BAUD_RATE).context(format!(
+        "Unable to open serial port `{}`",
+        name.to_string_lossy()
+    ))?;
+    Ok(Some(Box::new(port)))
+}
+
+/// Reads a whole capture file to compare the live capture with, placing its events at the
+/// times they were recorded, or read if the file has none
+fn load_reference(source: Source, settings: Settings) -> Result<ui::Reference, anyhow::Error> {
+    let name = source.name();
+    let mut timeline = Timeline::new(Instant::now(), true);
+    let mut capture = Capture::with_settings(settings);
+    let mut events = vec![];
+    for event in source.spawn()? {
+        match event {
+            SourceEvent::Byte {
+                arrival,
+                timestamp,
+                byte,
+                source,
+            } => events.push(capture.process_from(
+                source,
+                timeline.time(arrival, source, timestamp),
+                byte,
+            )),
+            SourceEvent::Disconnected(_) | SourceEvent::Reconnected(_) | SourceEvent::Notice(_) => {
+            }
+            SourceEvent::Closed => break,
+            SourceEvent::Error(e) => {
+                bail!("Unable to read reference `{}`: {}", name, e)
+            }
+        }
+    }
+    Ok(ui::Reference::new(name, events))
+}
+
+/// Fails if violations were observed and `--fail-on-violation` was given
+fn check_violations(stats: &Statistics, limits: &LimitArgs) -> Result<(), anyhow::Error> {
+    if limits.fail_on_violation && stats.violations > 0 {
+        bail!("{} MIDI violations observed", stats.violations);
+    }
+    Ok(())
+}
+
+/// Prints the summary of a finished session and writes the statistics as JSON if requested.
+/// Statistics written to stdout replace the printed summary
+fn summarize(
+    summary: &Summary,
+    outputs: &OutputArgs,
+    print: &PrintArgs,
+    display: Display,
+) -> Result<(), anyhow::Error> {
+    if let Some(path) = &outputs.stats_json {
+        let json = serde_json::to_string_pretty(&summary.stats.to_json())?;
+        if path.as_os_str() == "-" {
+            println!("{}", json);
+            return Ok(());
+        }
+        fs::write(path, json + "\n").context(format!("Unable to write `{:?}`", path))?;
+    }
+    match print.summary_format {
+        _ if display == Display::Quiet => {}
+        SummaryFormat::Text => print!("{}", summary),
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&summary.to_json())?),
+    }
+    Ok(())
+}
+
+/// Where a headless capture prints its analysis, and takes commands from if asked to
+struct Console<'a> {
+    printer: Printer<'a>,
+    /// Lines read from stdin, when commands are taken
+    commands: Option<Receiver<String>>,
+    /// MIDI Out that `send` writes to
+    out: Option<Box<dyn Write + Send>>,
+    /// Events are neither printed nor written to the outputs
+    paused: bool,
+    /// Marks printed so far
+    marks: usize,
+    /// Events so far, kept for `save` when commands are taken
+    history: Vec<CaptureEvent>,
+}
+
+impl Console<'_> {
+    /// Prints an event unless paused
+    fn print(&mut self, event: &CaptureEvent) {
+        if self.commands.is_some() {
+            self.history.push(event.clone());
+        }
+        if !self.paused {
+            self.printer.print(event);
+        }
+    }
+
+    /// Carries out the commands received since the last call, returning `true` once one
+    /// of them ends the capture. `now` is the capture time that marks are placed at
+    fn run_commands(&mut self, now: Duration) -> bool {
+        let Some(commands) = &self.commands else {
+            return false;
+        };
+        let lines: Vec<String> = commands.try_iter().collect();
+        for line in lines {
+            let control = match line.parse::<Control>() {
+                Ok(control) => control,
+                Err(e) => {
+                    println!("Error: {:#}", e);
+                    continue;
+                }
+            };
+            match control {
+                Control::Pause => {
+                    self.paused = true;
+                    println!("Paused, the capture is still analyzed");
+                }
+                Control::Resume => {
+                    self.paused = false;
+                    println!("Resumed");
+                }
+                Control::Mark(text) => {
+                    self.marks += 1;
+                    let time = self.printer.time_format.format(now);
+                    match text.is_empty() {
+                        true => println!("--- Mark {} at {}", self.marks, time),
+                        false => println!("--- Mark {} at {}: {}", self.marks, time, text),
+                    }
+                }
+                Control::Send(bytes) => {
+                    let Some(out) = &mut self.out else {
+                        println!("No MIDI Out to send to, give one with `--out`");
+                        continue;
+                    };
+                    let hex = midi::hex(&bytes);
+                    match out.write_all(&bytes).and_then(|_| out.flush()) {
+                        Ok(()) => println!("Sent {}", hex),
+                        Err(e) => println!("MIDI Out failed: {}", e),
+                    }
+                }
+                Control::Filter {
+                    statuses,
+                    channels,
+                    shown,
+                } => {
+                    let filter = &mut self.printer.filter;
+                    if shown {
+                        filter.hidden_statuses.retain(|s| !statuses.contains(s));
+                        filter.hidden_channels &= !channels;
+                    } else {
+                        filter.hidden_statuses.extend(statuses);
+                        filter.hidden_channels |= channels;
+                    }
+                    println!(
+                        "Hiding {} message types and {} channels",
+                        filter.hidden_statuses.len(),
+                        filter.hidden_channels.count_ones()
+                    );
+                }
+                Control::Save(path) => match self.save(&path) {
+                    Ok(()) => println!("Saved {} bytes to `{:?}`", self.history.len(), path),
+                    Err(e) => println!("Error: {:#}", e),
+                },
+                Control::Help => println!("{}", control::HELP),
+                Control::Quit => return true,
+            }
+        }
+        false
+    }
+
+    /// Writes the events so far to a capture file
+    fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut recorder = CaptureRecorder::create(path)?;
+        for event in &self.history {
+            recorder.write(event)?;
+        }
+        recorder.finish()
+    }
+}
+
+/// Analyzes every byte received from the source until it closes, a limit is reached,
+/// Ctrl-C is pressed, or `quit` is given, printing the analysis and taking commands if a
+/// console is given
+fn run_headless(
+    source: Source,
+    mut timeline: Timeline,
+    settings: Settings,
+    limits: Limits,
+    mut arm: Option<Arm>,
+    sinks: &mut [Box<dyn Sink>],
+    mut console: Option<&mut Console>,
+) -> Result<Summary, anyhow::Error> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .context("Unable to install Ctrl-C handler")?;
+    }
+    let print = console.is_some();
+
+    let rx = source.spawn()?;
+    let mut capture = Capture::with_settings(settings);
+    let mut clock = ClockAnalyzer::new();
+    let mut smoothness = SmoothnessAnalyzer::new();
+    let mut stats = Statistics::new();
+    let started = Instant::now();
+    let mut budgets = limits.budgets;
+    let ended = loop {
+        if interrupted.load(Ordering::SeqCst) {
+            break "Interrupted".to_string();
+        }
+        if let Some(reason) = limits.reached(&stats, started.elapsed()) {
+            break reason;
+        }
+        if let Some(console) = console.as_deref_mut() {
+            if console.run_commands(timeline.now()) {
+                break "Quit".to_string();
+            }
+        }
+        let event = match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(SourceEvent::Byte {
+                arrival,
+                timestamp,
+                byte,
+                source,
+            }) => capture.process_from(source, timeline.time(arrival, source, timestamp), byte),
+            // Recorded among the bytes, and reported even without printing, as bytes were lost
+            Ok(SourceEvent::Disconnected(notice)) => {
+                capture.interrupt();
+                if console.is_none() {
+                    println!("{}", notice);
+                }
+                CaptureEvent::notice(0, timeline.now(), MidiAnalysis::Warning(notice))
+            }
+            Ok(SourceEvent::Reconnected(notice)) => {
+                if console.is_none() {
+                    println!("{}", notice);
+                }
+                CaptureEvent::notice(0, timeline.now(), MidiAnalysis::Info(notice))
+            }
+            Ok(SourceEvent::Notice(notice)) => {
+                println!("{}", notice);
+                continue;
+            }
+            Ok(SourceEvent::Closed) => break "End of input".to_string(),
+            // The source stopped without a word, so the capture may be cut short
+            Err(RecvTimeoutError::Disconnected) => {
+                for sink in sinks.iter_mut() {
+                    sink.finish()?;
+                }
+                bail!("The source stopped unexpectedly");
+            }
+            Ok(SourceEvent::Error(e)) => break e,
+            Err(RecvTimeoutError::Timeout) => continue,
+        };
+        let events = match &mut arm {
+            Some(arm) if arm.fired().is_some() => vec![event],
+            Some(arm) => {
+                let events = arm.pass(event);
+                if let Some(fired) = arm.fired() {
+                    println!("Triggered by {}", fired);
+                }
+                events
+            }
+            None => vec![event],
+        };
+        let paused = console.as_ref().is_some_and(|console| console.paused);
+        for event in events {
+            if let Some(console) = console.as_deref_mut() {
+                console.print(&event);
+            }
+            if !event.notice {
+                clock.observe(&event);
+                smoothness.observe(&event);
+                stats.observe(&event);
+            }
+            // Raised even without printing, for unattended captures
+            for alarm in budgets.check(&stats) {
+                println!("Alarm: {}", alarm);
+            }
+            if paused {
+                continue;
+            }
+            for sink in sinks.iter_mut() {
+                sink.write(&event)?;
+                for notice in sink.notices() {
+                    if print {
+                        println!("   > {}", notice);
+                    }
+                }
+            }
+        }
+    };
+
+    for sink in sinks.iter_mut() {
+        sink.finish()?;
+    }
+    Ok(Summary {
+        ended,
+        stats,
+        clock: clock.quality(),
+        smoothness: smoothness.reports(),
+        jitter: timeline.jitter(),
+    })
+}
+
+/// Returns the extension of a path in lowercase
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the filter options of a command line
+    fn filter_args(args: &[&str]) -> FilterArgs {
+        FilterArgs::from_iter_safe(std::iter::once("miditerm").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn plots_controls() {
+        let controls = |text: &str| {
+            ExportArgs::from_iter_safe(["convert", "--controls", text]).map(|args| args.controls)
+        };
+        assert_eq!(controls("1,7,127").unwrap(), [1, 7, 127]);
+        assert!(controls("1,200").is_err());
+        assert!(controls("-1").is_err());
+    }
+
+    #[test]
+    fn filters_channels() {
+        let configured = FilterConfig {
+            channels: vec![10],
+            ..FilterConfig::default()
+        };
+        let filter = filter_args(&["--channels", "1,2"])
+            .filter(&configured)
+            .unwrap();
+        assert_eq!(filter.hidden_channels, !0b11);
+        let mut capture = Capture::new();
+        let mut last = |bytes: &[u8]| {
+            let events: Vec<CaptureEvent> = bytes
+                .iter()
+                .map(|byte| capture.process(Duration::ZERO, *byte))
+                .collect();
+            events.last().unwrap().clone()
+        };
+        assert!(filter.matches(&last(&[0x91, 60, 100])));
+        assert!(!filter.matches(&last(&[0x92, 60, 100])));
+        // Messages without a channel are always shown
+        assert!(filter.matches(&last(&[0xF8])));
+
+        let filter = filter_args(&[]).filter(&configured).unwrap();
+        assert_eq!(filter.hidden_channels, !(1 << 9));
+        assert_eq!(
+            filter_args(&["--channels", "0"])
+                .filter(&configured)
+                .unwrap_err()
+                .to_string(),
+            "`0` is not a channel from 1 to 16"
+        );
+    }
+
+    #[test]
+    fn filters_message_types() {
+        let configured = FilterConfig {
+            hide: vec!["clock".to_string()],
+            ..FilterConfig::default()
+        };
+        let filter = filter_args(&[]).filter(&configured).unwrap();
+        assert_eq!(filter.hidden_statuses, BTreeSet::from([0xF8]));
+
+        let filter = filter_args(&["--only", "notes,cc", "--hide", "Note Off"])
+            .filter(&configured)
+            .unwrap();
+        let shown: Vec<u8> = MESSAGE_STATUSES
+            .into_iter()
+            .filter(|status| !filter.hidden_statuses.contains(status))
+            .collect();
+        assert_eq!(shown, [0x90, 0xB0]);
+
+        let filter = filter_args(&["--hide", "realtime,aftertouch"])
+            .filter(&configured)
+            .unwrap();
+        assert_eq!(filter.hidden_statuses.len(), 7);
+        assert!(filter.hidden_statuses.contains(&0xD0));
+        assert!(filter_args(&["--only", "bleep"])
+            .filter(&configured)
+            .is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert!(parse_duration("30 days").is_err());
+        assert!(parse_duration("s").is_err());
+        assert_eq!(
+            parse_duration("99999999999999999999h")
+                .unwrap_err()
+                .to_string(),
+            "`99999999999999999999h` is too long a duration"
+        );
+        assert!(parse_duration("1e400h").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("64KB").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("10mb").unwrap(), 10 << 20);
+        assert!(parse_size("1.5MB").is_err());
+        assert!(parse_size("KB").is_err());
+        let text = format!("{}GB", usize::MAX >> 29);
+        assert_eq!(
+            parse_size(&text).unwrap_err().to_string(),
+            format!("`{}` is too large a size", text)
+        );
+    }
+}
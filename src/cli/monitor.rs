@@ -0,0 +1,247 @@
+//! `miditerm monitor`
+
+use crate::cli::{
+    self, AnalysisArgs, Display, FilterArgs, LimitArgs, OutputArgs, PcapArgs, PrintArgs, View,
+};
+use crate::config::Config;
+use crate::midi;
+use crate::source::Source;
+use crate::state::{self, StateStore, UiState};
+use crate::ui;
+use anyhow::bail;
+use std::{ffi::OsString, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct MonitorArgs {
+    /// Name or path of the serial device to open, or `-` to read raw MIDI bytes piped into
+    /// stdin. Defaults to the port the terminal UI last monitored, or else the `port` of the
+    /// configuration file, when no `--file` is given.
+    /// Given several times, the ports are captured at once and each byte is tagged with
+    /// the port it came from
+    #[structopt(
+        long,
+        allow_hyphen_values = true,
+        number_of_values = 1,
+        parse(from_os_str)
+    )]
+    port: Vec<OsString>,
+
+    /// Read raw MIDI bytes piped into stdin, e.g. from `socat` or a custom capture tool,
+    /// like `--port -`
+    #[structopt(long)]
+    stdin: bool,
+
+    /// Read stdin as lines of hexadecimal bytes, such as the output of `amidi -d`,
+    /// instead of raw bytes
+    #[structopt(long)]
+    hex: bool,
+
+    /// Listen for remote capture agents started with `miditerm agent` on a TCP port
+    /// (`tcp:HOST:PORT`) or Unix socket (`unix:PATH`) instead of reading a local port
+    #[structopt(long)]
+    listen: Option<String>,
+
+    /// Join an RTP-MIDI (AppleMIDI) network session, waiting for invitations on a UDP port
+    /// such as `5004`, or inviting the device at `HOST:PORT`
+    #[structopt(long)]
+    rtp_midi: Option<String>,
+
+    /// Baud rate of the serial port, for adapters that do not run at the MIDI baud rate
+    /// of 31250. Defaults to the `baud` of the configuration file
+    #[structopt(long)]
+    baud: Option<u32>,
+
+    /// Path of a file to read instead of a serial port.
+    /// `.syx` files are checked to contain only complete SysEx messages,
+    /// `.pcap` and `.pcapng` files are decoded as USB-MIDI traffic,
+    /// `.mtcap` files are read with their recorded timing, anything else as raw MIDI bytes
+    #[structopt(long, parse(from_os_str))]
+    file: Option<PathBuf>,
+
+    /// Keep reading the raw MIDI `--file` as another process appends to it, like `tail -f`,
+    /// instead of stopping at its end
+    #[structopt(long)]
+    follow: bool,
+
+    /// Capture file, such as a known-good `.mtcap` recording, shown below the live capture
+    /// with `w` and scrolled along with it
+    #[structopt(long, parse(from_os_str))]
+    reference: Option<PathBuf>,
+
+    /// Serial port used as MIDI Out by the terminal UI, for the pads shown with `p` and
+    /// the programs stepped through with `[` and `]`, and by the `send` command of
+    /// `--commands`
+    #[structopt(long, parse(from_os_str))]
+    out: Option<OsString>,
+
+    /// Writes all received bytes to MIDI Out
+    #[allow(dead_code)]
+    #[structopt(short, long)]
+    echo: bool,
+
+    /// Print the analysis of every byte instead of opening the terminal UI
+    #[structopt(long)]
+    headless: bool,
+
+    /// Take commands on stdin while headless, one per line: `pause`, `resume`, `mark`,
+    /// `send` a message to `--out`, `filter` a message type or channel `on` or `off`,
+    /// `save` the capture so far, `help`, and `quit`
+    #[structopt(long)]
+    commands: bool,
+
+    /// Open the terminal UI with the port, panels, and filter of the configuration instead
+    /// of those it had when it last quit
+    #[structopt(long)]
+    fresh: bool,
+
+    /// Place bytes on the timeline using the timestamps sent by network sources
+    /// instead of their local arrival time, reducing network induced jitter
+    #[structopt(long)]
+    source_timestamps: bool,
+
+    #[structopt(flatten)]
+    pcap: PcapArgs,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+
+    #[structopt(flatten)]
+    filter: FilterArgs,
+
+    #[structopt(flatten)]
+    limits: LimitArgs,
+
+    #[structopt(flatten)]
+    print: PrintArgs,
+
+    #[structopt(flatten)]
+    outputs: OutputArgs,
+}
+
+pub fn run(args: MonitorArgs, config: &Config) -> Result<(), anyhow::Error> {
+    // Without a profile or a source, the TUI starts on a screen picking the profile
+    let picked;
+    let config = match config.profile {
+        None if !args.headless
+            && !config.profiles.is_empty()
+            && args.port.is_empty()
+            && args.file.is_none()
+            && !args.stdin
+            && args.listen.is_none()
+            && args.rtp_midi.is_none() =>
+        {
+            picked = match ui::pick_profile(config)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            &picked
+        }
+        _ => config,
+    };
+    let baud = args.baud.or(config.baud).unwrap_or(midi::MIDI_BAUD_RATE);
+    if args.stdin && (!args.port.is_empty() || args.file.is_some()) {
+        bail!("`--stdin` cannot be combined with `--port` or `--file`");
+    }
+    let network = match (args.listen, args.rtp_midi) {
+        (Some(_), Some(_)) => bail!("Only one of `--listen` and `--rtp-midi` can be given"),
+        (Some(address), None) => Some(Source::Listen(address)),
+        (None, Some(target)) => Some(Source::RtpMidi(target)),
+        (None, None) => None,
+    };
+    if network.is_some() && (args.stdin || !args.port.is_empty() || args.file.is_some()) {
+        bail!(
+            "`--listen` and `--rtp-midi` cannot be combined with `--stdin`, `--port` or `--file`"
+        );
+    }
+    // The state of the TUI is kept between sessions
+    let state_store = match args.headless {
+        true => None,
+        false => StateStore::new(config.profile.as_deref().unwrap_or(state::DEFAULT_PROFILE)),
+    };
+    let mut state = match &state_store {
+        Some(store) if !args.fresh => store.load()?,
+        _ => UiState::default(),
+    };
+    let ports = match (args.port.is_empty(), &args.file) {
+        _ if args.stdin => vec!["-".into()],
+        (true, None) => config
+            .port
+            .iter()
+            .chain(&state.port)
+            .take(1)
+            .cloned()
+            .collect(),
+        _ => args.port,
+    };
+    let stdin = ports.iter().filter(|port| *port == "-").count();
+    if stdin > 1 {
+        bail!("`-` can only be given once to `--port`");
+    }
+    if args.commands && !args.headless {
+        bail!("`--commands` needs `--headless`, the terminal UI takes keys instead");
+    }
+    if args.commands && stdin > 0 {
+        bail!("`--commands` reads stdin, so the capture cannot");
+    }
+    if args.hex && stdin == 0 {
+        bail!("`--hex` needs `--stdin` or `--port -`");
+    }
+    let port_source = |port: OsString| match port.to_str() {
+        Some("-") if args.hex => Source::StdinHex,
+        Some("-") => Source::Stdin,
+        _ => Source::Serial { port, baud },
+    };
+    let source = match (ports.len(), args.file) {
+        _ if network.is_some() => network,
+        (1.., Some(_)) => bail!("Only one of `--port` and `--file` can be given"),
+        (0, Some(path)) => Some(args.pcap.file_source(path)),
+        (0, None) if args.headless => bail!("`--port` or `--file` is required"),
+        // The TUI can still show the events of a resumed session
+        (0, None) => None,
+        (1, None) => ports.into_iter().next().map(port_source),
+        (_, None) => Some(Source::Inputs(ports.into_iter().map(port_source).collect())),
+    };
+    let source = match source {
+        Some(Source::File(path)) if args.follow => Some(Source::Follow(path)),
+        _ if args.follow => bail!("`--follow` needs a `--file` of raw MIDI bytes"),
+        source => source,
+    };
+    if let Some(Source::Serial { port, .. }) = &source {
+        state.port = Some(port.clone());
+    }
+    let filter = match state.filter.take() {
+        Some(filter) if !args.filter.given() => filter,
+        _ => args.filter.filter(&config.filter)?,
+    };
+    // Files are read all at once, so only their own timestamps are meaningful
+    let source_timestamps = args.source_timestamps || matches!(source, Some(Source::Pcap { .. }));
+    let display = if args.headless {
+        Display::Print
+    } else {
+        Display::Tui
+    };
+    let settings = args.analysis.settings(config);
+    let reference = match args.reference {
+        Some(path) => Some(cli::load_reference(args.pcap.file_source(path), settings)?),
+        None => None,
+    };
+    cli::run_capture(
+        source,
+        &args.outputs,
+        settings,
+        &args.limits,
+        source_timestamps,
+        View {
+            display,
+            filter,
+            config,
+            print: &args.print,
+            out: args.out.as_deref(),
+            commands: args.commands,
+            reference,
+            state,
+            state_store,
+        },
+    )
+}
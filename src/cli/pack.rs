@@ -0,0 +1,712 @@
+//! `miditerm pack`
+//!
+//! A pack bundles the support of a device in a tar file: `pack.toml` with its name and the
+//! profile to use it with, holding the patch map and drum map in its `names`, and the Rhai
+//! decoder the profile's `script` points to. Installing a pack unpacks it next to the
+//! configuration file and adds its profile there
+//!
+//! Packs for common gear are built in with the `builtin-packs` feature, from `packs/`
+//!
+//! `pack infer` drafts the decoder of a pack from dumps of an undocumented device
+
+use crate::{
+    analysis::sysex::{self, Field, Kind, Method, Structure},
+    config::{self, Config, Profile},
+    midi, syx,
+};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// Name of the manifest of a pack
+const MANIFEST: &str = "pack.toml";
+
+/// Files of a pack by their paths in the pack
+type Files = BTreeMap<PathBuf, Vec<u8>>;
+
+/// Files of the packs built into miditerm
+#[cfg(feature = "builtin-packs")]
+const BUILTIN: &[&[(&str, &[u8])]] = &[
+    &[
+        ("pack.toml", include_bytes!("../../packs/dx7/pack.toml")),
+        (
+            "scripts/dx7.rhai",
+            include_bytes!("../../packs/dx7/scripts/dx7.rhai"),
+        ),
+    ],
+    &[
+        (
+            "pack.toml",
+            include_bytes!("../../packs/roland-jv-xv/pack.toml"),
+        ),
+        (
+            "scripts/jv-xv.rhai",
+            include_bytes!("../../packs/roland-jv-xv/scripts/jv-xv.rhai"),
+        ),
+    ],
+    &[
+        ("pack.toml", include_bytes!("../../packs/gs-xg/pack.toml")),
+        (
+            "scripts/gs-xg.rhai",
+            include_bytes!("../../packs/gs-xg/scripts/gs-xg.rhai"),
+        ),
+    ],
+];
+#[cfg(not(feature = "builtin-packs"))]
+const BUILTIN: &[&[(&str, &[u8])]] = &[];
+
+#[derive(Debug, StructOpt)]
+pub enum PackArgs {
+    /// Install a pack from a tar file, a directory, or the name of a built-in pack, and
+    /// add its profile to the configuration. Zip files are not supported, unpack them and
+    /// install the directory
+    Install {
+        #[structopt(parse(from_os_str))]
+        bundle: PathBuf,
+
+        /// Replace a pack or profile of the same name
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Bundle a profile of the configuration and its script into a pack to share
+    Export {
+        /// Profile to bundle, which also names the pack
+        name: String,
+
+        /// Tar file to write
+        #[structopt(parse(from_os_str))]
+        bundle: PathBuf,
+
+        /// Short description shown when the pack is installed
+        #[structopt(long)]
+        description: Option<String>,
+    },
+    /// List the packs built into miditerm
+    List,
+    /// Infer the structure of the SysEx dumps of a device and write a draft pack whose
+    /// decoder names their fields, to refine and install (experimental)
+    Infer {
+        /// Name of the pack to write
+        name: String,
+
+        /// `.syx` files of similar dumps, the more the better
+        #[structopt(required = true, parse(from_os_str))]
+        dumps: Vec<PathBuf>,
+
+        /// Directory to write the pack to. Defaults to the name of the pack
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Contents of `pack.toml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Manifest {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Profile added to the configuration. Its `script` is relative to the pack
+    profile: Profile,
+}
+
+pub fn run(args: PackArgs, config: &Config) -> Result<(), anyhow::Error> {
+    match args {
+        PackArgs::List => {
+            for (manifest, _) in builtin()? {
+                let description = manifest.description.unwrap_or_default();
+                println!("{:<14}{}", manifest.name, description);
+            }
+            return Ok(());
+        }
+        PackArgs::Infer {
+            name,
+            dumps,
+            output,
+        } => return infer(&name, &dumps, output),
+        _ => {}
+    }
+    let Some(path) = config.path.clone() else {
+        bail!("No configuration file to install packs next to");
+    };
+    match args {
+        PackArgs::Install { bundle, force } => {
+            let name = bundle.to_string_lossy();
+            let files = match builtin()?.into_iter().find(|(m, _)| m.name == name) {
+                Some((_, files)) if !bundle.exists() => files,
+                _ => read_bundle(&bundle).context(format!("Unable to read `{:?}`", bundle))?,
+            };
+            let manifest = install(files, &path, force)
+                .context(format!("Unable to install `{:?}`", bundle))?;
+            if let Some(description) = &manifest.description {
+                println!("{}", description);
+            }
+            println!(
+                "Installed pack `{}`, use it with `--profile {}`",
+                manifest.name, manifest.name
+            );
+        }
+        PackArgs::Export {
+            name,
+            bundle,
+            description,
+        } => {
+            let files = export(&Config::load(&path)?, &name, description)?;
+            let file = File::create(&bundle).context(format!("Unable to create `{:?}`", bundle))?;
+            write_bundle(&files, file).context(format!("Unable to write `{:?}`", bundle))?;
+            println!("Wrote pack `{}` to {:?}", name, bundle);
+        }
+        PackArgs::List | PackArgs::Infer { .. } => {}
+    }
+    Ok(())
+}
+
+/// Returns the manifest and files of every built-in pack
+fn builtin() -> Result<Vec<(Manifest, Files)>, anyhow::Error> {
+    BUILTIN
+        .iter()
+        .map(|pack| {
+            let files: Files = pack
+                .iter()
+                .map(|(path, data)| (PathBuf::from(path), data.to_vec()))
+                .collect();
+            let manifest = toml::from_str(std::str::from_utf8(&files[Path::new(MANIFEST)])?)?;
+            Ok((manifest, files))
+        })
+        .collect()
+}
+
+/// Reads the files of a pack from a tar file or a directory, by their paths in the pack
+fn read_bundle(path: &Path) -> Result<Files, anyhow::Error> {
+    let mut files = BTreeMap::new();
+    if path.is_dir() {
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?.path();
+                if entry.is_dir() {
+                    dirs.push(entry);
+                } else {
+                    let name = entry.strip_prefix(path)?.to_path_buf();
+                    files.insert(name, fs::read(&entry)?);
+                }
+            }
+        }
+        return Ok(files);
+    }
+    let mut archive = tar::Archive::new(File::open(path)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // Archives made with `tar -cf pack.tar .` start every path with `./`
+        let name: PathBuf = entry
+            .path()?
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        files.insert(name, data);
+    }
+    Ok(files)
+}
+
+/// Writes the files of a pack to a tar file
+fn write_bundle(files: &Files, writer: impl Write) -> Result<(), anyhow::Error> {
+    let mut builder = tar::Builder::new(writer);
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+    builder.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Unpacks the files of a pack into `packs/NAME` next to the configuration file at
+/// `config`, and adds its profile to the file. The pack is checked whole and unpacked
+/// beside the installed one before it replaces it, so a bad pack leaves that one as it was
+fn install(files: Files, config: &Path, force: bool) -> Result<Manifest, anyhow::Error> {
+    let manifest = files
+        .get(Path::new(MANIFEST))
+        .context(format!("The pack has no `{}`", MANIFEST))?;
+    let mut manifest: Manifest = toml::from_str(std::str::from_utf8(manifest)?)
+        .context(format!("Invalid `{}`", MANIFEST))?;
+    let name = manifest.name.clone();
+    check_name(&name)?;
+
+    if let Some(script) = &manifest.profile.script {
+        if !files.contains_key(script) {
+            bail!("The pack has no script {:?}", script);
+        }
+    }
+    if let Some(path) = files.keys().find(|path| {
+        !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    }) {
+        bail!("The pack holds {:?}, outside of the pack", path);
+    }
+
+    if Config::load(config)?.profiles.contains_key(&name) && !force {
+        bail!(
+            "There is already a profile `{}`, use `--force` to replace it",
+            name
+        );
+    }
+    let packs = config.parent().unwrap_or(Path::new(".")).join("packs");
+    let dir = packs.join(&name);
+    if dir.exists() && !force {
+        bail!("Pack `{}` is already installed in {:?}", name, dir);
+    }
+
+    let unpacked = packs.join(format!(".{}.new", name));
+    if unpacked.exists() {
+        fs::remove_dir_all(&unpacked).context(format!("Unable to remove {:?}", unpacked))?;
+    }
+    let written = files.iter().try_for_each(|(path, data)| {
+        let target = unpacked.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).context(format!("Unable to create {:?}", parent))?;
+        }
+        fs::write(&target, data).context(format!("Unable to write {:?}", target))
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_dir_all(&unpacked);
+        return Err(e);
+    }
+    if dir.exists() {
+        fs::remove_dir_all(&dir).context(format!("Unable to remove {:?}", dir))?;
+    }
+    fs::rename(&unpacked, &dir).context(format!("Unable to move the pack to {:?}", dir))?;
+
+    if let Some(script) = &manifest.profile.script {
+        manifest.profile.script = Some(dir.join(script));
+    }
+    config::save_table(config, &["profiles", &name], &manifest.profile)?;
+    Ok(manifest)
+}
+
+/// Fails unless `name` is made of letters, digits, `-`, and `_`, as it names a directory
+fn check_name(name: &str) -> Result<(), anyhow::Error> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!(
+            "`{}` is not a pack name of letters, digits, `-`, and `_`",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Returns the files of a pack of the profile `name` of the configuration
+fn export(
+    config: &Config,
+    name: &str,
+    description: Option<String>,
+) -> Result<Files, anyhow::Error> {
+    let Some(profile) = config.profiles.get(name) else {
+        bail!("There is no profile `{}` in the configuration", name);
+    };
+    let mut manifest = Manifest {
+        name: name.to_string(),
+        description,
+        profile: profile.clone(),
+    };
+    let mut files = BTreeMap::new();
+    if let Some(script) = &profile.script {
+        let data = fs::read(script).context(format!("Unable to read script {:?}", script))?;
+        let file_name = script.file_name().context("The script has no file name")?;
+        let path = Path::new("scripts").join(file_name);
+        files.insert(path.clone(), data);
+        manifest.profile.script = Some(path);
+    }
+    files.insert(
+        PathBuf::from(MANIFEST),
+        toml::to_string_pretty(&manifest)?.into_bytes(),
+    );
+    Ok(files)
+}
+
+/// Infers the structure of the dumps, prints it, and writes a draft pack decoding it
+fn infer(name: &str, paths: &[PathBuf], output: Option<PathBuf>) -> Result<(), anyhow::Error> {
+    check_name(name)?;
+    let mut dumps = vec![];
+    for path in paths {
+        dumps.extend(syx::load(path)?);
+    }
+    let structures = sysex::infer(&dumps);
+    if structures.is_empty() {
+        bail!("The files hold no SysEx with data");
+    }
+    for (i, structure) in structures.iter().enumerate() {
+        println!(
+            "Message {}: {} bytes, {} dumps",
+            i + 1,
+            structure.len + 2,
+            structure.count
+        );
+        for field in &structure.fields {
+            println!("  {}", describe(field));
+        }
+    }
+
+    let dir = output.unwrap_or_else(|| PathBuf::from(name));
+    if dir.join(MANIFEST).exists() {
+        bail!("There is already a pack in {:?}", dir);
+    }
+    let script = Path::new("scripts").join(format!("{}.rhai", name));
+    let manifest = Manifest {
+        name: name.to_string(),
+        description: Some(format!("Draft decoder inferred from {} dumps", dumps.len())),
+        profile: Profile {
+            script: Some(script.clone()),
+            ..Profile::default()
+        },
+    };
+    fs::create_dir_all(dir.join("scripts")).context(format!("Unable to create {:?}", dir))?;
+    fs::write(dir.join(MANIFEST), toml::to_string_pretty(&manifest)?)
+        .context(format!("Unable to write {:?}", dir.join(MANIFEST)))?;
+    fs::write(dir.join(&script), draft(name, &dir, &structures))
+        .context(format!("Unable to write {:?}", dir.join(&script)))?;
+    println!(
+        "Wrote a draft pack to {:?}, refine {:?} and install it with `miditerm pack install`",
+        dir,
+        dir.join(&script)
+    );
+    Ok(())
+}
+
+/// Describes a field of an inferred structure by its offsets from `F0`
+fn describe(field: &Field) -> String {
+    let offsets = match field.len {
+        1 => format!("{}", field.start + 1),
+        len => format!("{}-{}", field.start + 1, field.start + len),
+    };
+    let what = match &field.kind {
+        Kind::Constant(bytes) => format!("constant {}", midi::hex(bytes)),
+        Kind::Text => "text".to_string(),
+        Kind::Value { min, max } => format!("value from {} to {}", min, max),
+        Kind::Bytes => "changing bytes".to_string(),
+        Kind::Checksum { start, method } => format!(
+            "checksum, {} of offsets {}-{}",
+            method.name(),
+            start + 1,
+            field.start
+        ),
+    };
+    format!("{:<11}{}", offsets, what)
+}
+
+/// Returns a Rhai decoder annotating the fields of the inferred structures
+fn draft(name: &str, dir: &Path, structures: &[Structure]) -> String {
+    let mut script = format!(
+        "// Draft decoder of {} inferred by `miditerm pack infer`. Offsets count the bytes of\n\
+         // the message from its F0. Rename the fields after the documentation of the device,\n\
+         // then install it with `miditerm pack install {}`\n",
+        name,
+        dir.display()
+    );
+    script.push_str(
+        r#"
+fn on_message(msg) {
+    if msg.name == "System Exclusive" {
+        let text = decode(msg.bytes);
+        if type_of(text) == "string" {
+            annotate(text);
+        }
+    }
+}
+
+fn decode(b) {
+"#,
+    );
+    for (i, structure) in structures.iter().enumerate() {
+        let header: Vec<String> = [0xF0]
+            .iter()
+            .chain(structure.header())
+            .map(|b| format!("0x{:02X}", b))
+            .collect();
+        script.push_str(&format!(
+            "    if b.len() == {} && starts(b, [{}]) {{\n        return message_{}(b);\n    }}\n",
+            structure.len + 2,
+            header.join(", "),
+            i + 1
+        ));
+    }
+    script.push_str("    ()\n}\n");
+
+    let mut methods = BTreeSet::new();
+    for (i, structure) in structures.iter().enumerate() {
+        script.push_str(&format!(
+            "\n/// {} bytes, seen {} times\nfn message_{}(b) {{\n    let text = \"Message {}\";\n",
+            structure.len + 2,
+            structure.count,
+            i + 1,
+            i + 1
+        ));
+        for field in &structure.fields {
+            let at = field.start + 1;
+            let line = match &field.kind {
+                Kind::Constant(_) => continue,
+                Kind::Text => format!(
+                    "text += ` text_{}=\"${{printable(b.extract({}, {}))}}\"`;",
+                    at, at, field.len
+                ),
+                Kind::Value { min, max } => format!(
+                    "text += ` value_{}=${{b[{}]}}`; // seen from {} to {}",
+                    at, at, min, max
+                ),
+                Kind::Bytes => format!("text += \" bytes_{}..{}\";", at, field.start + field.len),
+                Kind::Checksum { start, method } => {
+                    let function = match method {
+                        Method::Complement => "complement_ok",
+                        Method::Sum => "sum_ok",
+                        Method::Xor => "xor_ok",
+                    };
+                    methods.insert(function);
+                    format!(
+                        "text += if {}(b, {}, {}) {{ \" checksum OK\" }} else {{ \" checksum mismatch\" }};",
+                        function,
+                        start + 1,
+                        at
+                    )
+                }
+            };
+            script.push_str(&format!("    {}\n", line));
+        }
+        script.push_str("    text\n}\n");
+    }
+
+    script.push_str(
+        r#"
+/// Whether the message starts with the bytes of `header`
+fn starts(b, header) {
+    for i in 0..header.len() {
+        if b[i] != header[i] {
+            return false;
+        }
+    }
+    true
+}
+
+/// The bytes as text, with spaces in place of the ones that are not printable
+fn printable(data) {
+    let text = blob();
+    for x in data {
+        text.push(if x >= 0x20 && x < 0x7F { x } else { 0x20 });
+    }
+    text.as_string()
+}
+"#,
+    );
+    for function in methods {
+        let (what, check) = match function {
+            "complement_ok" => (
+                "the 7 bit complement of the sum",
+                "let sum = b[at];\n    for i in start..at {\n        sum += b[i];\n    }\n    sum % 128 == 0",
+            ),
+            "sum_ok" => (
+                "the 7 bit sum",
+                "let sum = 0;\n    for i in start..at {\n        sum += b[i];\n    }\n    sum % 128 == b[at]",
+            ),
+            _ => (
+                "the XOR",
+                "let xor = 0;\n    for i in start..at {\n        xor = xor ^ b[i];\n    }\n    (xor & 0x7F) == b[at]",
+            ),
+        };
+        script.push_str(&format!(
+            "\n/// Whether the byte at `at` is {} of the bytes from `start`\nfn {}(b, start, at) {{\n    {}\n}}\n",
+            what, function, check
+        ));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn round_trip() {
+        let dir = env::temp_dir().join(format!("miditerm-pack-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("td17.rhai");
+        fs::write(&script, "fn on_message(msg) {}").unwrap();
+        let mut config = Config::default();
+        let mut profile = Profile {
+            script: Some(script),
+            ..Profile::default()
+        };
+        let names = profile.names.get_or_insert_with(Default::default);
+        names.drums.insert("36".to_string(), "Kick".to_string());
+        names
+            .programs
+            .insert("0".to_string(), "Studio Kit".to_string());
+        config.profiles.insert("td17".to_string(), profile.clone());
+
+        let files = export(&config, "td17", Some("Roland TD-17".to_string())).unwrap();
+        let mut bundle = vec![];
+        write_bundle(&files, &mut bundle).unwrap();
+        let path = dir.join("td17.tar");
+        fs::write(&path, bundle).unwrap();
+        let files = read_bundle(&path).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [Path::new("pack.toml"), Path::new("scripts/td17.rhai")]
+        );
+
+        let config_path = dir.join("config").join("miditerm.toml");
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        let text = "# Studio setup\nport = \"td17\" # the kit\n";
+        fs::write(&config_path, text).unwrap();
+        let manifest = install(files.clone(), &config_path, false).unwrap();
+        assert_eq!(manifest.description.as_deref(), Some("Roland TD-17"));
+        let installed = Config::load(&config_path).unwrap();
+        let script = dir.join("config/packs/td17/scripts/td17.rhai");
+        assert_eq!(
+            installed.profiles["td17"],
+            Profile {
+                script: Some(script.clone()),
+                ..profile
+            }
+        );
+        assert!(script.exists());
+        let saved = fs::read_to_string(&config_path).unwrap();
+        assert!(saved.starts_with(text), "{}", saved);
+        assert!(saved.contains("[profiles.td17]"), "{}", saved);
+        assert!(install(files.clone(), &config_path, false).is_err());
+        install(files.clone(), &config_path, true).unwrap();
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), saved);
+
+        // A pack reaching outside of its directory is refused before anything is removed
+        let mut bad = files.clone();
+        bad.insert(PathBuf::from("../escape.rhai"), vec![]);
+        assert!(install(bad, &config_path, true).is_err());
+        assert!(script.exists());
+        assert!(!dir.join("config/packs/escape.rhai").exists());
+        assert!(!dir.join("config/packs/.td17.new").exists());
+
+        // Archives of a directory name their files `./pack.toml`
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, data) in &files {
+            let name = format!("./{}", name.display());
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, data.as_slice()).unwrap();
+        }
+        fs::write(&path, builder.into_inner().unwrap()).unwrap();
+        let read = read_bundle(&path).unwrap();
+        assert_eq!(read, files);
+        install(read, &config_path, true).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "builtin-packs")]
+    #[test]
+    fn builtin_packs() {
+        use crate::{capture::Capture, script::Script};
+        use std::time::Duration;
+
+        let dir = env::temp_dir().join(format!("miditerm-builtin-{}", std::process::id()));
+        let config_path = dir.join("miditerm.toml");
+        // A message of each pack, and its annotation
+        let messages: [(&str, &[u8], &str); 3] = [
+            (
+                "dx7",
+                &[0xF0, 0x43, 0x10, 0x01, 0x06, 0x05, 0xF7],
+                "DX7 Algorithm = 5 on channel 1",
+            ),
+            (
+                "roland-jv-xv",
+                &[
+                    0xF0, 0x41, 0x10, 0x6A, 0x12, 0x11, 0x04, 0x12, 0x05, 0x40, 0x14, 0xF7,
+                ],
+                "JV-1080 DT1 of 1 byte to User Patch 5 Tone 2 11 04 12 05, checksum OK",
+            ),
+            (
+                "gs-xg",
+                &[
+                    0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+                ],
+                "GS Reset = 0 at 40 00 7F, checksum OK",
+            ),
+        ];
+        let packs = builtin().unwrap();
+        assert_eq!(packs.len(), messages.len());
+        for ((manifest, files), (name, bytes, annotation)) in packs.into_iter().zip(messages) {
+            assert_eq!(manifest.name, name);
+            let installed = install(files, &config_path, false).unwrap();
+            let mut script = Script::load(installed.profile.script.as_ref().unwrap()).unwrap();
+            let mut capture = Capture::new();
+            for byte in bytes {
+                let event = capture.process(Duration::ZERO, *byte);
+                if let Some(message) = &event.message {
+                    script.on_message(&event, message);
+                }
+            }
+            let annotations = script.annotations();
+            assert_eq!(annotations.len(), 1);
+            assert!(annotations[0].ends_with(annotation), "{}", annotations[0]);
+        }
+        assert!(Config::load(&config_path).unwrap().profiles["gs-xg"]
+            .names
+            .as_ref()
+            .is_some_and(|names| names.drums["36"] == "Bass Drum 1"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn draft_decoder() {
+        use crate::{capture::Capture, script::Script};
+        use std::time::Duration;
+
+        // Dumps of a program number, a name, and a value, with an XOR checksum
+        let dumps: Vec<Vec<u8>> = [(1, b"Lead", 7), (2, b"Bass", 90), (3, b"Pads", 33)]
+            .into_iter()
+            .map(|(program, name, value)| {
+                let mut data = vec![0x7D, 0x01, program];
+                data.extend(name);
+                data.push(value);
+                data.push(data[1..].iter().fold(0, |xor, b| xor ^ b) & 0x7F);
+                data
+            })
+            .collect();
+        let structures = sysex::infer(&dumps);
+        let dir = env::temp_dir().join(format!("miditerm-draft-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("draft.rhai");
+        fs::write(&path, draft("draft", &dir, &structures)).unwrap();
+        let mut script = Script::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut capture = Capture::new();
+        for byte in [&[0xF0][..], &dumps[1], &[0xF7]].concat() {
+            let event = capture.process(Duration::ZERO, byte);
+            if let Some(message) = &event.message {
+                script.on_message(&event, message);
+            }
+        }
+        assert_eq!(
+            script.annotations(),
+            [r#"Script draft.rhai: Message 1 value_3=2 text_4="Bass" value_8=90 checksum OK"#]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! `miditerm panic`
+//!
+//! Silences stuck notes by sending Sustain off, All Sound Off, All Notes Off, and Reset All
+//! Controllers on all 16 channels. The same messages are sent by `P` in the TUI and by
+//! `miditerm send panic`
+
+use crate::{config::Config, midi, source};
+use anyhow::{bail, Context};
+use std::{ffi::OsString, io::Write};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct PanicArgs {
+    /// Name or path of the serial device or raw MIDI device to send the panic to. Defaults
+    /// to the `port` of the configuration file
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+}
+
+pub fn run(args: PanicArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let Some(port) = args.port.or_else(|| config.port.clone()) else {
+        bail!("`--port` is required");
+    };
+    let mut out = source::open_output(&port)
+        .context(format!("Unable to open `{}`", port.to_string_lossy()))?;
+    let bytes = midi::panic_bytes();
+    out.write_all(&bytes)
+        .and_then(|_| out.flush())
+        .context(format!("Unable to write to `{}`", port.to_string_lossy()))?;
+    println!(
+        "Sent Sustain off, All Sound Off, All Notes Off, and Reset All Controllers on all 16 channels of `{}`, {} bytes",
+        port.to_string_lossy(),
+        bytes.len()
+    );
+    Ok(())
+}
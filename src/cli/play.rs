@@ -0,0 +1,76 @@
+//! `miditerm play`
+
+use crate::cli::{self, AnalysisArgs, Display, FilterArgs, LimitArgs, OutputArgs, PrintArgs, View};
+use crate::config::Config;
+use crate::source::Source;
+use crate::state::UiState;
+use std::{ffi::OsString, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct PlayArgs {
+    /// Standard MIDI File to play
+    #[structopt(parse(from_os_str))]
+    song: PathBuf,
+
+    /// Name or path of the serial device or raw MIDI device to play the song out of.
+    /// The song is only shown if omitted
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Also send MIDI clock following the tempo map, with Start before the song and Stop
+    /// after it, so drum machines and sequencers play along
+    #[structopt(long)]
+    clock: bool,
+
+    /// Print the analysis of every byte instead of opening the terminal UI
+    #[structopt(long)]
+    headless: bool,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+
+    #[structopt(flatten)]
+    filter: FilterArgs,
+
+    #[structopt(flatten)]
+    limits: LimitArgs,
+
+    #[structopt(flatten)]
+    print: PrintArgs,
+
+    #[structopt(flatten)]
+    outputs: OutputArgs,
+}
+
+pub fn run(args: PlayArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let source = Source::Smf {
+        path: args.song,
+        output: args.port,
+        clock: args.clock,
+    };
+    let display = if args.headless {
+        Display::Print
+    } else {
+        Display::Tui
+    };
+    // The outgoing bytes carry their times in the song as source timestamps
+    cli::run_capture(
+        Some(source),
+        &args.outputs,
+        args.analysis.settings(config),
+        &args.limits,
+        true,
+        View {
+            display,
+            filter: args.filter.filter(&config.filter)?,
+            config,
+            print: &args.print,
+            out: None,
+            commands: false,
+            reference: None,
+            state: UiState::default(),
+            state_store: None,
+        },
+    )
+}
@@ -0,0 +1,73 @@
+//! `miditerm list-ports`
+//!
+//! USB ports are listed with their identity, `usb:VID:PID:SERIAL`, which names them for
+//! `--port` and the configuration whatever socket they are plugged into, and tells apart
+//! interfaces that describe themselves the same way
+
+use crate::source::port;
+use anyhow::Context;
+
+pub fn run() -> Result<(), anyhow::Error> {
+    let ports = serialport::available_ports().context("Unable to list serial ports")?;
+    println!("Serial ports:");
+    if ports.is_empty() {
+        println!("  None found");
+    }
+    let descriptions: Vec<(String, bool)> = ports.iter().map(port::describe).collect();
+    let shared = shared(
+        descriptions
+            .iter()
+            .map(|(description, _)| description.as_str()),
+    );
+    for ((info, (description, midi)), shared) in ports.iter().zip(&descriptions).zip(shared) {
+        let mut line = format!("  {:<24} {}", info.port_name, description);
+        if *midi {
+            line.push_str("  [MIDI]");
+        }
+        if let Some(identity) = port::identity(info) {
+            line.push_str(&format!("  {}", identity));
+        }
+        if shared {
+            line.push_str("  [same description as another port]");
+        }
+        println!("{}", line.trim_end());
+    }
+
+    println!("MIDI devices:");
+    let devices = port::midi_devices();
+    if devices.is_empty() {
+        println!("  None found");
+    }
+    for (path, name) in devices {
+        println!("  {:<24} {}  [MIDI]", path, name);
+    }
+    Ok(())
+}
+
+/// Tells for each description if another port has the same one, so that only its path or
+/// identity tells them apart
+fn shared<'a>(descriptions: impl Iterator<Item = &'a str> + Clone) -> Vec<bool> {
+    descriptions
+        .clone()
+        .map(|description| {
+            !description.is_empty()
+                && descriptions.clone().filter(|d| *d == description).count() > 1
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_descriptions() {
+        let descriptions = [
+            "USB 0582:012A Roland UM-ONE",
+            "",
+            "",
+            "USB 0582:012A Roland UM-ONE",
+        ];
+        assert_eq!(shared(descriptions.into_iter()), [true, false, false, true]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Line formatting of headless captures, optionally colored with ANSI escape codes
+
+use crate::capture::{CaptureEvent, Filter, TimeFormat};
+use crate::config::Names;
+use crate::midi::{self, sysex, MidiAnalysis, MidiMessage};
+use crate::source::input_name;
+use anyhow::bail;
+use std::{env, io::IsTerminal, str::FromStr};
+
+/// Width the time column is padded to
+const TIME_WIDTH: usize = 15;
+/// Narrowest the source column is padded to, room for the number of an unnamed input
+const SOURCE_WIDTH: usize = 3;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[93m";
+const RED: &str = "\x1b[91m";
+
+/// When printed lines are colored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns `true` if lines printed to stdout should be colored
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => bail!("Unknown color choice `{}`", s),
+        }
+    }
+}
+
+/// How the summary of a finished session is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryFormat {
+    Text,
+    /// One JSON object, for scripts
+    Json,
+}
+
+impl FromStr for SummaryFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SummaryFormat::Text),
+            "json" => Ok(SummaryFormat::Json),
+            _ => bail!("Unknown summary format `{}`", s),
+        }
+    }
+}
+
+/// Prints the analysis of the events that pass the display filter, one line per byte in
+/// columns of time, byte, type, channel, and analysis, followed by a summary of each message.
+/// All events are still counted and written to the outputs
+pub struct Printer<'a> {
+    pub filter: Filter,
+    pub names: &'a Names,
+    pub time_format: TimeFormat,
+    pub color: bool,
+    /// Names of the inputs, when bytes come from several and each line is tagged with its
+    /// input after the time
+    pub sources: Option<&'a [String]>,
+}
+
+impl Printer<'_> {
+    pub fn print(&self, event: &CaptureEvent) {
+        if !self.filter.matches(event) {
+            return;
+        }
+        println!("{}", self.byte_line(event));
+        if let Some(summary) = self.message_line(event) {
+            println!("{}", summary);
+        }
+    }
+
+    /// Returns the width of the source column with its separator, zero if there is none
+    fn source_width(&self) -> usize {
+        match self.sources {
+            Some(names) => names.iter().map(String::len).fold(SOURCE_WIDTH, usize::max) + 2,
+            None => 0,
+        }
+    }
+
+    /// Formats the analysis of a byte
+    fn byte_line(&self, event: &CaptureEvent) -> String {
+        let source = match self.sources {
+            Some(names) => format!(
+                "{:<width$}",
+                input_name(names, event.source),
+                width = self.source_width()
+            ),
+            None => String::new(),
+        };
+        let (byte, kind) = match (event.notice, event.is_status()) {
+            (true, _) => ("--".to_string(), "NOTICE"),
+            (false, true) => (format!("{:02X}", event.byte), "STATUS"),
+            (false, false) => (format!("{:02X}", event.byte), "DATA"),
+        };
+        let channel = match event.channel {
+            Some(ch) => format!("{:>2}", ch + 1),
+            None => " -".to_string(),
+        };
+        let text = match &event.analysis {
+            MidiAnalysis::Comment(text) => text.clone(),
+            // Keep the severity readable without colors
+            analysis => format!("{}: {}", capitalize(analysis.severity()), analysis.text()),
+        };
+        let style = match event.analysis {
+            MidiAnalysis::Comment(_) => "",
+            MidiAnalysis::Info(_) => CYAN,
+            MidiAnalysis::Warning(_) => YELLOW,
+            MidiAnalysis::Violation(_) => RED,
+        };
+        format!(
+            "{}  {}{}  {:<6}  {}  {}",
+            self.paint(
+                DIM,
+                &format!(
+                    "{:>width$}",
+                    self.time_format.format(event.time),
+                    width = TIME_WIDTH
+                )
+            ),
+            source,
+            byte,
+            kind,
+            channel,
+            self.paint(style, &text)
+        )
+    }
+
+    /// Summarizes the message completed by the byte, if any
+    fn message_line(&self, event: &CaptureEvent) -> Option<String> {
+        let message = event.message.as_ref()?;
+        let hex = midi::hex(&event.raw);
+        let name = match message {
+            MidiMessage::ControlChange { control, .. } => self
+                .names
+                .control(format!("{} {}", message.name(), control), *control),
+            MidiMessage::NoteOff { channel, note, .. }
+            | MidiMessage::NoteOn { channel, note, .. }
+            | MidiMessage::PolyPressure { channel, note, .. } => {
+                self.names.note(message.name().to_string(), *channel, *note)
+            }
+            _ => message.name().to_string(),
+        };
+        let mut summary = match message.channel() {
+            Some(channel) => format!(
+                "= {}, {}: {}",
+                name,
+                self.names
+                    .channel(format!("channel {}", channel + 1), channel),
+                hex
+            ),
+            None => format!("= {}: {}", name, hex),
+        };
+        if let MidiMessage::SystemExclusive(data) = message {
+            if let Some(text) = sysex::text(data) {
+                summary = format!("{} {}", summary, text);
+            }
+        }
+        Some(format!(
+            "{:width$}  {}",
+            "",
+            self.paint(BOLD, &summary),
+            width = TIME_WIDTH + self.source_width()
+        ))
+    }
+
+    /// Wraps text in an ANSI style if colors are enabled
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color && !style.is_empty() {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Returns the word with its first letter in uppercase
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn aligned_lines() {
+        let mut names = Names::default();
+        names.channels.insert("10".to_string(), "Drums".to_string());
+        let mut printer = Printer {
+            filter: Filter::default(),
+            names: &names,
+            time_format: TimeFormat::Seconds,
+            color: false,
+            sources: None,
+        };
+        let mut capture = Capture::new();
+        let time = Duration::from_millis(1500);
+        let status = capture.process(time, 0x99);
+        capture.process(time, 36);
+        let last = capture.process(time, 100);
+        assert_eq!(
+            printer.byte_line(&status),
+            format!(
+                "     1.500000 s  99  STATUS  10  {}",
+                status.analysis.text()
+            )
+        );
+        assert_eq!(
+            printer.message_line(&last).unwrap(),
+            "                 = Note On, channel 10 (Drums): 99 24 64"
+        );
+        assert_eq!(printer.message_line(&status), None);
+
+        let orphan = capture.process(time, 0xF7);
+        assert!(printer.byte_line(&orphan).contains("  Warning: "));
+
+        printer.color = true;
+        assert!(printer.byte_line(&orphan).contains(YELLOW));
+        assert!(!printer.byte_line(&status).contains(YELLOW));
+
+        printer.color = false;
+        let sources = ["keys".to_string(), "sequencer".to_string()];
+        printer.sources = Some(&sources);
+        let clock = capture.process_from(1, time, 0xF8);
+        assert_eq!(
+            printer.byte_line(&clock),
+            "     1.500000 s  sequencer  F8  STATUS   -  Timing Clock"
+        );
+        assert_eq!(
+            printer.message_line(&clock).unwrap(),
+            "                            = Timing Clock: F8"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! `miditerm query`
+
+use crate::capture::TimeFormat;
+use crate::cli::{
+    format::{Format, FORMAT_NAMES},
+    AnalysisArgs, ExportArgs,
+};
+use crate::config::Config;
+use crate::export::csv;
+use crate::midi;
+use crate::sink::LogRecord;
+use crate::store::{self, Query};
+use anyhow::{bail, Context};
+use std::{
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct QueryArgs {
+    /// Session written with `--session`, either a sqlite database or a capture file
+    #[structopt(parse(from_os_str))]
+    session: PathBuf,
+
+    /// Filter expression such as `"type=NoteOn channel=10 time>00:12:00"`.
+    /// Terms are `type=NAME[,NAME..]`, `channel=N[,N..]`, and `time` compared
+    /// with `<`, `<=`, `>`, or `>=` to `[[HH:]MM:]SS[.fraction]`. Matches everything if omitted
+    #[structopt(default_value = "")]
+    expression: Query,
+
+    /// Format of the matching events. Only `jsonl` and `csv` can be printed,
+    /// the other formats need `--output`. Printed as text if omitted
+    #[structopt(long, possible_values = FORMAT_NAMES)]
+    format: Option<Format>,
+
+    /// Write the matching events to this file instead of printing them.
+    /// The format is guessed from the extension if `--format` is omitted
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    export: ExportArgs,
+
+    /// Analysis of capture file sessions, which are analyzed again as they are loaded
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+}
+
+pub fn run(args: QueryArgs, config: &Config) -> Result<(), anyhow::Error> {
+    if !args.session.exists() {
+        bail!("Session `{:?}` does not exist", args.session);
+    }
+    let settings = args.analysis.settings(config);
+    let store = store::open(&args.session, settings)?;
+    let events = store.query(&args.expression)?;
+
+    if let Some(path) = args.output {
+        let format = match args.format {
+            Some(format) => format,
+            None => Format::from_path(&path)
+                .context("Unable to tell the output format from its extension, use `--format`")?,
+        };
+        let mut sink = format.open(path, &args.export, config, settings)?;
+        for event in &events {
+            sink.write(event)?;
+        }
+        return sink.finish();
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    match args.format {
+        None => {
+            let time_format = config.timestamps.unwrap_or(TimeFormat::Clock);
+            for record in events.iter().filter_map(LogRecord::from_event) {
+                let hex = midi::hex(record.bytes);
+                writeln!(
+                    out,
+                    "{}  {:<12} {}",
+                    time_format.format(Duration::from_secs_f64(record.time)),
+                    hex,
+                    record.analysis
+                )?;
+            }
+        }
+        Some(Format::Jsonl) => {
+            for record in events.iter().filter_map(LogRecord::from_event) {
+                serde_json::to_writer(&mut out, &record)?;
+                writeln!(out)?;
+            }
+        }
+        Some(Format::Csv) => {
+            writeln!(out, "{}", csv::PLAIN_HEADER)?;
+            for row in events.iter().filter_map(csv::plain_row) {
+                writeln!(out, "{}", row)?;
+            }
+        }
+        Some(format) => bail!("`{:?}` output needs `--output`", format),
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//! `miditerm raw`
+
+use crate::capture::Capture;
+use crate::cli::parse_duration;
+use crate::config::Config;
+use crate::midi;
+use crate::source::{
+    linestatus::{LineError, Unmarker},
+    port,
+};
+use anyhow::{bail, Context};
+use serialport::{Parity, SerialPort};
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Baud rates tried by `--sweep`, MIDI first
+const SWEEP_BAUDS: [u32; 7] = [31250, 38400, 9600, 19200, 57600, 115200, 4800];
+
+#[derive(Debug, StructOpt)]
+pub struct RawArgs {
+    /// Name or path of the serial device to open. Defaults to the `port` of the
+    /// configuration file
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Baud rate the line is read at, to look at a link that may not run at the MIDI baud
+    /// rate of 31250. Defaults to the `baud` of the configuration file
+    #[structopt(long)]
+    baud: Option<u32>,
+
+    /// Parity bit expected after each byte. MIDI has none, so a link that does carry one
+    /// shows as framing errors unless it is given
+    #[structopt(long, default_value = "none", possible_values = &["none", "even", "odd"])]
+    parity: String,
+
+    /// Read the line at each common baud rate in turn and count the errors at each,
+    /// instead of printing the bytes
+    #[structopt(long)]
+    sweep: bool,
+
+    /// Time spent at each baud rate of `--sweep`, such as `2s` or `500ms`
+    #[structopt(long, default_value = "2s", parse(try_from_str = parse_duration))]
+    dwell: Duration,
+}
+
+/// Bytes and errors received at one baud rate
+#[derive(Debug, Default)]
+struct Counts {
+    bytes: usize,
+    framing: usize,
+    breaks: usize,
+}
+
+impl Counts {
+    fn observe(&mut self, error: Option<LineError>) {
+        self.bytes += 1;
+        match error {
+            Some(LineError::Framing) => self.framing += 1,
+            Some(LineError::Break) => self.breaks += 1,
+            None => {}
+        }
+    }
+}
+
+pub fn run(args: RawArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let Some(name) = args.port.or_else(|| config.port.clone()) else {
+        bail!("`--port` is required");
+    };
+    let baud = args.baud.or(config.baud).unwrap_or(midi::MIDI_BAUD_RATE);
+    let parity = match args.parity.as_str() {
+        "even" => Parity::Even,
+        "odd" => Parity::Odd,
+        _ => Parity::None,
+    };
+    let mut port = open(&name, baud, parity)
+        .context(format!("Unable to open `{}`", name.to_string_lossy()))?;
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .context("Unable to install Ctrl-C handler")?;
+    }
+
+    if args.sweep {
+        let mut best: Option<(u32, usize)> = None;
+        for baud in SWEEP_BAUDS {
+            port.set_baud_rate(baud).context(format!(
+                "Unable to read `{}` at {} baud",
+                name.to_string_lossy(),
+                baud
+            ))?;
+            let mut counts = Counts::default();
+            read(&mut port, &interrupted, Some(args.dwell), |_, _, error| {
+                counts.observe(error)
+            })?;
+            let errors = counts.framing + counts.breaks;
+            println!(
+                "{:>7} baud  {:>6} bytes  {:>6} framing/parity errors  {:>4} breaks",
+                baud, counts.bytes, counts.framing, counts.breaks
+            );
+            if counts.bytes > 0 && best.is_none_or(|(_, fewest)| errors < fewest) {
+                best = Some((baud, errors));
+            }
+            if interrupted.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        match best {
+            Some((baud, _)) => println!("Fewest errors at {} baud", baud),
+            None => println!("Nothing received at any baud rate"),
+        }
+        return Ok(());
+    }
+
+    let parity = match parity {
+        Parity::None => "no".to_string(),
+        _ => args.parity,
+    };
+    println!(
+        "Reading `{}` at {} baud with {} parity",
+        name.to_string_lossy(),
+        baud,
+        parity
+    );
+    let mut capture = Capture::new();
+    let mut counts = Counts::default();
+    read(&mut port, &interrupted, None, |time, byte, error| {
+        counts.observe(error);
+        let event = capture.process(time, byte);
+        let kind = if event.is_status() { "STATUS" } else { "DATA" };
+        let message = event.message.as_ref().map_or("", |message| message.name());
+        let error = match error {
+            Some(error) => format!("  ! {}", error),
+            None => String::new(),
+        };
+        let line = format!(
+            "{:>12.6} s  {:02X}  {:08b}  {:<6}  {:<18}{}",
+            time.as_secs_f64(),
+            byte,
+            byte,
+            kind,
+            message,
+            error
+        );
+        println!("{}", line.trim_end());
+    })?;
+    println!(
+        "{} bytes, {} framing/parity errors, {} breaks",
+        counts.bytes, counts.framing, counts.breaks
+    );
+    Ok(())
+}
+
+/// Opens the port with the terminal driver marking the bytes received with errors
+#[cfg(unix)]
+fn open(name: &OsStr, baud: u32, parity: Parity) -> Result<Box<dyn SerialPort>, anyhow::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut port = port::open_native(name, baud)?;
+    port.set_parity(parity)?;
+    port.set_timeout(Duration::from_millis(100))?;
+    crate::source::linestatus::mark_errors(port.as_raw_fd())?;
+    Ok(Box::new(port))
+}
+
+#[cfg(not(unix))]
+fn open(_name: &OsStr, _baud: u32, _parity: Parity) -> Result<Box<dyn SerialPort>, anyhow::Error> {
+    bail!("The line status of serial ports is only available on Unix")
+}
+
+/// Hands every received byte to `f` with its time and error until Ctrl-C is pressed, or
+/// until `duration` has passed if one is given
+fn read(
+    port: &mut Box<dyn SerialPort>,
+    interrupted: &AtomicBool,
+    duration: Option<Duration>,
+    mut f: impl FnMut(Duration, u8, Option<LineError>),
+) -> Result<(), anyhow::Error> {
+    // Bytes already waiting were received at another baud rate
+    port.clear(serialport::ClearBuffer::Input)?;
+    let start = Instant::now();
+    let mut unmarker = Unmarker::new();
+    let mut buffer = [0; 256];
+    while !interrupted.load(Ordering::SeqCst) && duration.is_none_or(|d| start.elapsed() < d) {
+        let count = match port.read(&mut buffer) {
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("Unable to read the port"),
+        };
+        let time = start.elapsed();
+        for byte in &buffer[..count] {
+            if let Some((byte, error)) = unmarker.unmark(*byte) {
+                f(time, byte, error);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dwells() {
+        let dwell = |text: &str| RawArgs::from_iter_safe(["raw", "--sweep", "--dwell", text]);
+        assert_eq!(dwell("500ms").unwrap().dwell, Duration::from_millis(500));
+        assert_eq!(dwell("3").unwrap().dwell, Duration::from_secs(3));
+        for text in ["inf", "-1", "1e300"] {
+            assert!(dwell(text).is_err(), "{}", text);
+        }
+    }
+}
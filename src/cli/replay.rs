@@ -0,0 +1,97 @@
+//! `miditerm replay`
+
+use crate::cli::{self, AnalysisArgs, Display, FilterArgs, LimitArgs, OutputArgs, PrintArgs, View};
+use crate::config::Config;
+use crate::source::Source;
+use crate::state::UiState;
+use anyhow::bail;
+use std::{ffi::OsString, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct ReplayArgs {
+    /// Capture file recorded with `--record`
+    #[structopt(parse(from_os_str))]
+    capture: PathBuf,
+
+    /// Playback speed multiplier. Zero plays back as fast as possible
+    #[structopt(long, default_value = "1.0", parse(try_from_str = parse_speed))]
+    speed: f64,
+
+    /// Also write replayed bytes out this serial port
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Print the analysis of every byte instead of opening the terminal UI
+    #[structopt(long)]
+    headless: bool,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+
+    #[structopt(flatten)]
+    filter: FilterArgs,
+
+    #[structopt(flatten)]
+    limits: LimitArgs,
+
+    #[structopt(flatten)]
+    print: PrintArgs,
+
+    #[structopt(flatten)]
+    outputs: OutputArgs,
+}
+
+/// Parses a playback speed, which cannot be negative
+fn parse_speed(text: &str) -> Result<f64, anyhow::Error> {
+    match text.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed >= 0.0 => Ok(speed),
+        _ => bail!("`{}` is not a speed of zero or more", text),
+    }
+}
+
+pub fn run(args: ReplayArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let source = Source::Replay {
+        path: args.capture,
+        speed: args.speed,
+        output: args.port,
+    };
+    let display = if args.headless {
+        Display::Print
+    } else {
+        Display::Tui
+    };
+    // Replayed bytes carry their original times as source timestamps
+    cli::run_capture(
+        Some(source),
+        &args.outputs,
+        args.analysis.settings(config),
+        &args.limits,
+        true,
+        View {
+            display,
+            filter: args.filter.filter(&config.filter)?,
+            config,
+            print: &args.print,
+            out: None,
+            commands: false,
+            reference: None,
+            state: UiState::default(),
+            state_store: None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_speeds() {
+        assert_eq!(parse_speed("0").unwrap(), 0.0);
+        assert_eq!(parse_speed("2.5").unwrap(), 2.5);
+        for text in ["-1", "inf", "NaN", "fast"] {
+            assert!(parse_speed(text).is_err(), "{}", text);
+        }
+    }
+}
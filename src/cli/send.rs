@@ -0,0 +1,360 @@
+//! `miditerm send`
+//!
+//! Messages are written as a name followed by their values, such as `noteon 1 60 100`.
+//! Channels are numbered from 1 to 16. Supported messages are
+//!
+//! - `noteon CH NOTE [VELOCITY]`, `noteoff CH NOTE [VELOCITY]`, `polypressure CH NOTE VALUE`
+//! - `cc CH CONTROL VALUE`, `pc CH PROGRAM`, `aftertouch CH VALUE`
+//! - `pitchbend CH VALUE` from 0 to 16383, or from -8192 to +8191 when signed
+//! - `mtc VALUE`, `songpos BEATS`, `songselect SONG`, `tunerequest`
+//! - `clock`, `start`, `continue`, `stop`, `activesensing`, `reset`
+//! - `sysex BYTES..` with the data bytes in hexadecimal, without `F0` and `F7`
+//! - `hex BYTES..` or bare hexadecimal bytes, sent as they are
+//! - `panic`, Sustain off, All Sound Off, All Notes Off, and Reset All Controllers on every
+//!   channel
+
+use crate::{midi, midi::MidiMessage, source::port, syx};
+use anyhow::{anyhow, bail, Context};
+use std::{ffi::OsString, io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct SendArgs {
+    /// Name or path of the serial device to write to.
+    /// The bytes are printed in hexadecimal instead if omitted
+    #[structopt(long, parse(from_os_str))]
+    port: Option<OsString>,
+
+    /// Messages to send, e.g. `"noteon 1 60 100"`, or bytes in hexadecimal, e.g. `90 3C 7F`
+    messages: Vec<String>,
+
+    /// Control Change to send as `CH CONTROL VALUE`
+    #[structopt(long, number_of_values = 1)]
+    cc: Vec<String>,
+
+    /// Program Change to send as `CH PROGRAM`
+    #[structopt(long, number_of_values = 1)]
+    pc: Vec<String>,
+
+    /// Pitch Bend to send as `CH VALUE`
+    #[structopt(long, number_of_values = 1)]
+    pitchbend: Vec<String>,
+
+    /// Send every System Exclusive message of a `.syx` file
+    #[structopt(long, number_of_values = 1, parse(from_os_str))]
+    sysex_file: Vec<PathBuf>,
+}
+
+pub fn run(args: SendArgs) -> Result<(), anyhow::Error> {
+    let mut messages = parse_messages(&args.messages)?;
+    let flags = [
+        ("cc", &args.cc),
+        ("pc", &args.pc),
+        ("pitchbend", &args.pitchbend),
+    ];
+    for (name, values) in flags {
+        for value in values {
+            messages.push(parse_message(&format!("{} {}", name, value))?);
+        }
+    }
+    for path in &args.sysex_file {
+        messages.extend(
+            syx::load(path)?
+                .into_iter()
+                .map(|data| MidiMessage::SystemExclusive(data).to_bytes()),
+        );
+    }
+    if messages.is_empty() {
+        bail!("Nothing to send");
+    }
+
+    let Some(name) = args.port else {
+        for bytes in &messages {
+            println!("{}", midi::hex(bytes));
+        }
+        return Ok(());
+    };
+    let mut port = port::open(&name, midi::MIDI_BAUD_RATE).context(format!(
+        "Unable to open serial port `{}`",
+        name.to_string_lossy()
+    ))?;
+    let bytes = messages.concat();
+    port.write_all(&bytes)
+        .context(format!("Unable to write to `{}`", name.to_string_lossy()))?;
+    port.flush()?;
+    println!("Sent {} messages, {} bytes", messages.len(), bytes.len());
+    Ok(())
+}
+
+/// Names of the messages understood by `parse_message`
+const MESSAGE_NAMES: [&str; 30] = [
+    "hex",
+    "sysex",
+    "panic",
+    "noteon",
+    "on",
+    "noteoff",
+    "off",
+    "polypressure",
+    "polyat",
+    "cc",
+    "controlchange",
+    "pc",
+    "programchange",
+    "program",
+    "aftertouch",
+    "channelpressure",
+    "pressure",
+    "pitchbend",
+    "pb",
+    "mtc",
+    "songpos",
+    "songposition",
+    "songselect",
+    "tunerequest",
+    "clock",
+    "start",
+    "continue",
+    "stop",
+    "activesensing",
+    "reset",
+];
+
+/// Parses the positional arguments. Consecutive bare hexadecimal words such as `90 3C 7F`
+/// are sent as one message, so bytes do not need to be quoted
+fn parse_messages(args: &[String]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+    let mut messages = vec![];
+    let mut raw: Vec<&str> = vec![];
+    for arg in args {
+        let first = message_name(arg.split_whitespace().next().unwrap_or(""));
+        if !MESSAGE_NAMES.contains(&first.as_str()) {
+            raw.push(arg);
+            continue;
+        }
+        if !raw.is_empty() {
+            messages.push(parse_hex(&raw.join(" "))?);
+            raw.clear();
+        }
+        messages.push(parse_message(arg)?);
+    }
+    if !raw.is_empty() {
+        messages.push(parse_hex(&raw.join(" "))?);
+    }
+    Ok(messages)
+}
+
+/// Returns the name of a message as listed in `MESSAGE_NAMES`, so `Note-On` and
+/// `note_on` are understood as `noteon`
+fn message_name(word: &str) -> String {
+    word.to_lowercase().replace(['-', '_'], "")
+}
+
+/// Parses a message written as its name and values into the bytes to send
+pub(super) fn parse_message(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut words = text.split_whitespace();
+    let name = message_name(words.next().context("Empty message")?);
+    let values: Vec<&str> = words.collect();
+    if name == "hex" {
+        return parse_hex(&values.join(" "));
+    }
+    if name == "sysex" {
+        let data = parse_hex(&values.join(" "))?;
+        if data.iter().any(|b| b & 0x80 != 0) {
+            bail!("System Exclusive data bytes must be below 80");
+        }
+        return Ok(MidiMessage::SystemExclusive(data).to_bytes());
+    }
+    if name == "panic" {
+        if !values.is_empty() {
+            bail!("`panic` takes no values");
+        }
+        return Ok(midi::panic_bytes());
+    }
+
+    let arity = |min: usize, max: usize| {
+        let takes = match (min, max) {
+            (1, 1) => "1 value".to_string(),
+            (min, max) if min == max => format!("{} values", max),
+            (min, max) => format!("{} to {} values", min, max),
+        };
+        if values.len() < min || values.len() > max {
+            Err(anyhow!("`{}` takes {}, not {}", name, takes, values.len()))
+        } else {
+            Ok(())
+        }
+    };
+    let channel = || parse_value(values[0], 1, 16).map(|ch| ch as u8 - 1);
+    let data = |i: usize| parse_value(values[i], 0, 127).map(|v| v as u8);
+    let message = match name.as_str() {
+        "noteon" | "on" => {
+            arity(2, 3)?;
+            MidiMessage::NoteOn {
+                channel: channel()?,
+                note: data(1)?,
+                velocity: if values.len() > 2 { data(2)? } else { 100 },
+            }
+        }
+        "noteoff" | "off" => {
+            arity(2, 3)?;
+            MidiMessage::NoteOff {
+                channel: channel()?,
+                note: data(1)?,
+                velocity: if values.len() > 2 { data(2)? } else { 0 },
+            }
+        }
+        "polypressure" | "polyat" => {
+            arity(3, 3)?;
+            MidiMessage::PolyPressure {
+                channel: channel()?,
+                note: data(1)?,
+                pressure: data(2)?,
+            }
+        }
+        "cc" | "controlchange" => {
+            arity(3, 3)?;
+            MidiMessage::ControlChange {
+                channel: channel()?,
+                control: data(1)?,
+                value: data(2)?,
+            }
+        }
+        "pc" | "programchange" | "program" => {
+            arity(2, 2)?;
+            MidiMessage::ProgramChange {
+                channel: channel()?,
+                program: data(1)?,
+            }
+        }
+        "aftertouch" | "channelpressure" | "pressure" => {
+            arity(2, 2)?;
+            MidiMessage::ChannelPressure {
+                channel: channel()?,
+                pressure: data(1)?,
+            }
+        }
+        "pitchbend" | "pb" => {
+            arity(2, 2)?;
+            // Signed values are relative to the center of the wheel
+            let value = if values[1].starts_with(['+', '-']) {
+                parse_value(values[1], -8192, 8191)? + 8192
+            } else {
+                parse_value(values[1], 0, 16383)?
+            };
+            MidiMessage::PitchBend {
+                channel: channel()?,
+                value: value as u16,
+            }
+        }
+        "mtc" => {
+            arity(1, 1)?;
+            MidiMessage::MtcQuarterFrame(data(0)?)
+        }
+        "songpos" | "songposition" => {
+            arity(1, 1)?;
+            MidiMessage::SongPosition(parse_value(values[0], 0, 16383)? as u16)
+        }
+        "songselect" => {
+            arity(1, 1)?;
+            MidiMessage::SongSelect(data(0)?)
+        }
+        _ => {
+            arity(0, 0)?;
+            match name.as_str() {
+                "tunerequest" => MidiMessage::TuneRequest,
+                "clock" => MidiMessage::TimingClock,
+                "start" => MidiMessage::Start,
+                "continue" => MidiMessage::Continue,
+                "stop" => MidiMessage::Stop,
+                "activesensing" => MidiMessage::ActiveSensing,
+                "reset" => MidiMessage::SystemReset,
+                _ => bail!("Unknown message `{}`", name),
+            }
+        }
+    };
+    Ok(message.to_bytes())
+}
+
+/// Parses a decimal value within `min..=max`
+fn parse_value(text: &str, min: i32, max: i32) -> Result<i32, anyhow::Error> {
+    text.parse()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .context(format!("`{}` is not a value from {} to {}", text, min, max))
+}
+
+/// Parses whitespace separated hexadecimal bytes
+fn parse_hex(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    text.split_whitespace()
+        .map(|word| {
+            let digits = word.trim_start_matches("0x");
+            if digits.len() > 2 {
+                bail!("`{}` is not a single byte", word);
+            }
+            u8::from_str_radix(digits, 16).context(format!("`{}` is not a hexadecimal byte", word))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_bytes() {
+        assert_eq!(
+            parse_hex("90 3C 7f 0xF8").unwrap(),
+            vec![0x90, 0x3C, 0x7F, 0xF8]
+        );
+        assert!(parse_hex("903C").is_err());
+        assert!(parse_hex("G0").is_err());
+    }
+
+    #[test]
+    fn text_messages() {
+        assert_eq!(
+            parse_message("noteon 1 60 100").unwrap(),
+            vec![0x90, 60, 100]
+        );
+        assert_eq!(parse_message("NoteOff 16 60").unwrap(), vec![0x8F, 60, 0]);
+        assert_eq!(parse_message("note-off 16 60").unwrap(), vec![0x8F, 60, 0]);
+        assert_eq!(parse_message("cc 2 7 127").unwrap(), vec![0xB1, 7, 127]);
+        assert_eq!(
+            parse_message("pitchbend 1 -8192").unwrap(),
+            vec![0xE0, 0, 0]
+        );
+        assert_eq!(parse_message("pb 1 8192").unwrap(), vec![0xE0, 0, 0x40]);
+        assert_eq!(
+            parse_message("sysex 7E 7F 06 01").unwrap(),
+            vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]
+        );
+        assert_eq!(parse_message("clock").unwrap(), vec![0xF8]);
+        assert_eq!(
+            parse_message("panic").unwrap()[180..],
+            [0xBF, 64, 0, 0xBF, 120, 0, 0xBF, 123, 0, 0xBF, 121, 0]
+        );
+        assert!(parse_message("noteon 0 60").is_err());
+        assert_eq!(
+            parse_message("cc 1 7").unwrap_err().to_string(),
+            "`cc` takes 3 values, not 2"
+        );
+        assert_eq!(
+            parse_message("noteon 1").unwrap_err().to_string(),
+            "`noteon` takes 2 to 3 values, not 1"
+        );
+        assert!(parse_message("clock 1").is_err());
+        assert!(parse_message("bogus").is_err());
+
+        let args: Vec<String> = ["90", "3C", "7F", "start", "cc 1 7 12", "F8"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            parse_messages(&args).unwrap(),
+            vec![
+                vec![0x90, 0x3C, 0x7F],
+                vec![0xFA],
+                vec![0xB0, 7, 12],
+                vec![0xF8]
+            ]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! `miditerm tail`
+
+use crate::cli::{
+    self, extension, AnalysisArgs, Display, FilterArgs, LimitArgs, OutputArgs, PrintArgs, View,
+};
+use crate::config::Config;
+use crate::source::Source;
+use crate::state::UiState;
+use anyhow::bail;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct TailArgs {
+    /// Session file written by another miditerm with `--session`
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Print the analysis of every byte instead of opening the terminal UI
+    #[structopt(long)]
+    headless: bool,
+
+    #[structopt(flatten)]
+    analysis: AnalysisArgs,
+
+    #[structopt(flatten)]
+    filter: FilterArgs,
+
+    #[structopt(flatten)]
+    limits: LimitArgs,
+
+    #[structopt(flatten)]
+    print: PrintArgs,
+
+    #[structopt(flatten)]
+    outputs: OutputArgs,
+}
+
+pub fn run(args: TailArgs, config: &Config) -> Result<(), anyhow::Error> {
+    if let Some("db" | "sqlite") = extension(&args.path).as_deref() {
+        bail!("Sessions kept in sqlite databases can only be read with `miditerm query`");
+    }
+    let display = if args.headless {
+        Display::Print
+    } else {
+        Display::Tui
+    };
+    // The session keeps the times its bytes were recorded at
+    cli::run_capture(
+        Some(Source::Tail(args.path)),
+        &args.outputs,
+        args.analysis.settings(config),
+        &args.limits,
+        true,
+        View {
+            display,
+            filter: args.filter.filter(&config.filter)?,
+            config,
+            print: &args.print,
+            out: None,
+            commands: false,
+            reference: None,
+            state: UiState::default(),
+            state_store: None,
+        },
+    )
+}
@@ -0,0 +1,723 @@
+//! User configuration stored in `miditerm.toml`
+//!
+//! The file lives in `$XDG_CONFIG_HOME/miditerm/`, or `~/.config/miditerm/` if that is not set,
+//! unless another one is given with `--config`. Missing files and settings fall back to their
+//! defaults, and options given on the command line take precedence over the file. The
+//! profiles of the file replace some of its settings with those of a rig when chosen with
+//! `--profile`
+
+use crate::{
+    capture::TimeFormat,
+    midi::notes::NoteNaming,
+    source::port,
+    ui::{Alert, KeyPreset, Layout, Theme},
+};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use toml_edit::{DocumentMut, Item, Table};
+
+/// Name of the configuration file
+const FILE_NAME: &str = "miditerm.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Serial port monitored when neither `--port` nor `--file` is given
+    #[serde(
+        default,
+        with = "port::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub port: Option<OsString>,
+    /// Baud rate of serial ports, for adapters that do not run at the MIDI baud rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baud: Option<u32>,
+    /// How notes are named in the analysis and exports
+    pub note_names: NoteNaming,
+    /// How times are shown. Each display has its own default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<TimeFormat>,
+    /// Colors of the TUI
+    pub theme: Theme,
+    /// Colors of the TUI changed from those of the theme
+    pub colors: ColorsConfig,
+    /// Rows of the TUI fade to gray as they age while it follows the newest events, so the
+    /// last second of a fast stream stands out
+    pub dim: bool,
+    /// Most events the TUI keeps, as a number such as `100000` or the memory they take such
+    /// as `50MB`, the oldest being dropped once it is full. Unlimited if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrollback: Option<String>,
+    /// What the TUI does when a violation is received, besides coloring its row
+    pub alert: Alert,
+    /// Keys of the TUI
+    pub keys: KeysConfig,
+    /// Events displayed unless filters are given on the command line
+    pub filter: FilterConfig,
+    /// Names shown next to channel and controller numbers
+    pub names: Names,
+    /// Where the Program Changes stepped through in the TUI are sent
+    pub stepper: StepperConfig,
+    /// Keys of the TUI that send messages to MIDI Out. A drum kit on channel 10 if empty
+    pub pads: Vec<PadConfig>,
+    /// Panels shown when the TUI starts
+    pub layout: Layout,
+    /// Named layouts that can be switched to in the TUI
+    pub layouts: BTreeMap<String, Layout>,
+    /// Changes made to the messages sent out the thru port, in order
+    pub rules: Vec<RuleConfig>,
+    /// Actions taken when something is received, such as running a command
+    pub triggers: Vec<TriggerConfig>,
+    /// Rhai script used when `--script` is not given, such as the decoder of a device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    /// Settings of each rig, such as the studio, the live rig, or the bench
+    pub profiles: BTreeMap<String, Profile>,
+    /// Where the configuration was loaded from and where it is saved to
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Profile whose settings are in use, if any
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+/// Settings of a rig that replace those of the rest of the file while its profile is in use.
+/// Its layouts are added to those of the file, and its names to those of the file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    #[serde(
+        default,
+        with = "port::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub port: Option<OsString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baud: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<FilterConfig>,
+    /// Names of the channels, controllers, and programs of the rig, such as its patch map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub names: Option<Names>,
+    /// Profiles of the devices of the rig, such as those installed by packs, whose
+    /// settings are used in order before those of this profile. The devices of a device are
+    /// not followed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stepper: Option<StepperConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pads: Option<Vec<PadConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    pub layouts: BTreeMap<String, Layout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<RuleConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub triggers: Option<Vec<TriggerConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+}
+
+/// A rule applied to the messages sent out the thru port, such as moving channel 1 to
+/// channel 5 an octave up without its aftertouch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    /// Channel from 1 to 16 of the messages the rule applies to. Applies to every message
+    /// if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    /// Message types not sent, named like those of `--hide`
+    pub drop: Vec<String>,
+    /// Semitones added to the notes
+    pub transpose: i8,
+    /// Factor the velocity of Note Ons is multiplied by
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<f64>,
+    /// Control Change numbers replaced by others, such as `1 = 11`
+    pub cc: BTreeMap<String, u8>,
+    /// Channel from 1 to 16 the messages are moved to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_channel: Option<u8>,
+}
+
+/// Actions taken each time a message, a controller crossing a value, or a violation is
+/// received, such as `message = "cc"`, `control = 64`, `crosses = 64`, `run = "..."`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerConfig {
+    /// Message type waited for, named like those of `--hide`, or `identity-reply` or
+    /// `violation`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Channel from 1 to 16 of the messages waited for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    /// Controller whose value is waited for to cross `crosses`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crosses: Option<u8>,
+    /// Shell command run with the message in `MIDITERM_*` environment variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+    /// File the message is written to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save: Option<PathBuf>,
+    /// Message sent to `out`, written like those of `miditerm send`, such as `panic`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send: Option<String>,
+    /// Serial port `send` is sent to
+    #[serde(
+        default,
+        with = "port::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub out: Option<OsString>,
+}
+
+/// The display filter, written like the `--channels`, `--hide`, and `--only` options
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub channels: Vec<u8>,
+    pub hide: Vec<String>,
+    pub only: Vec<String>,
+}
+
+/// Styles of the TUI changed from those of the theme, written like `"white on red bold"`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorsConfig {
+    /// Rows of each severity: comment, info, warning, or violation
+    pub severity: BTreeMap<String, String>,
+    /// Rows of each type of message named like `--hide`, such as `clock` or `sysex`, unless
+    /// their severity is above a comment
+    pub messages: BTreeMap<String, String>,
+    /// Other parts of the TUI: header, sent, match, bookmark, or range
+    pub parts: BTreeMap<String, String>,
+}
+
+/// Keys of the TUI, those of a preset with some bound again
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeysConfig {
+    /// Bindings the others are changed from
+    pub preset: KeyPreset,
+    /// Actions bound to keys, such as `"ctrl-r" = "record"`, or `"none"` to free a key
+    pub bind: BTreeMap<String, String>,
+}
+
+/// Channel and bank of the programs stepped through with `[` and `]` in the TUI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepperConfig {
+    /// Channel from 1 to 16
+    pub channel: u8,
+    /// Bank sent as Bank Select MSB and LSB before each program, from 0 to 16383
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank: Option<u16>,
+}
+
+impl Default for StepperConfig {
+    fn default() -> StepperConfig {
+        StepperConfig {
+            channel: 1,
+            bank: None,
+        }
+    }
+}
+
+/// A key of the TUI bound to a message written like the messages of `miditerm send`,
+/// such as `noteon 10 36 100` or `pc 1 5`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PadConfig {
+    pub key: char,
+    pub message: String,
+    /// Shown instead of the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Names of the channels and controllers of a rig, such as `10 = "Drums"` or `74 = "Cutoff"`.
+/// TOML keys are strings, so the numbers are kept as they are written
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Names {
+    /// Names by channel from 1 to 16
+    pub channels: BTreeMap<String, String>,
+    /// Names by Control Change number
+    pub controls: BTreeMap<String, String>,
+    /// Patch map of the sound module, with names by program number from 0 to 127
+    pub programs: BTreeMap<String, String>,
+    /// Drum map of the drum channel, with the names of the sounds by note number
+    pub drums: BTreeMap<String, String>,
+    /// Channel from 1 to 16 the drum map applies to, 10 if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drum_channel: Option<u8>,
+}
+
+impl Names {
+    /// Adds the names of `other`, in place of those it names again, so that the patch map
+    /// of one device and the drum map of another make up the names of a rig
+    pub fn merge(&mut self, other: Names) {
+        self.channels.extend(other.channels);
+        self.controls.extend(other.controls);
+        self.programs.extend(other.programs);
+        self.drums.extend(other.drums);
+        self.drum_channel = other.drum_channel.or(self.drum_channel);
+    }
+
+    /// Returns `label` followed by the name of `channel` (0 to 15) in parentheses, if it has one
+    pub fn channel(&self, label: String, channel: u8) -> String {
+        match self.channels.get(&(channel + 1).to_string()) {
+            Some(name) => format!("{} ({})", label, name),
+            None => label,
+        }
+    }
+
+    /// Returns `label` followed by the name of `control` in parentheses, if it has one
+    pub fn control(&self, label: String, control: u8) -> String {
+        match self.controls.get(&control.to_string()) {
+            Some(name) => format!("{} ({})", label, name),
+            None => label,
+        }
+    }
+
+    /// Returns `label` followed by the name of the drum sound of `note` in parentheses, if
+    /// `channel` (0 to 15) is the drum channel and the drum map has one
+    pub fn note(&self, label: String, channel: u8, note: u8) -> String {
+        if channel + 1 != self.drum_channel.unwrap_or(10) {
+            return label;
+        }
+        match self.drums.get(&note.to_string()) {
+            Some(name) => format!("{} ({})", label, name),
+            None => label,
+        }
+    }
+
+    /// Returns `label` followed by the name of `program` in parentheses, if it has one
+    pub fn program(&self, label: String, program: u8) -> String {
+        match self.programs.get(&program.to_string()) {
+            Some(name) => format!("{} ({})", label, name),
+            None => label,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the configuration file of the user, if a home directory is known
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("miditerm").join(FILE_NAME))
+    }
+
+    /// Reads the configuration at `path`, or the defaults if it does not exist
+    pub fn load(path: &Path) -> Result<Config, anyhow::Error> {
+        let mut config = if path.exists() {
+            let text = fs::read_to_string(path).context(format!("Unable to read `{:?}`", path))?;
+            toml::from_str(&text).context(format!("Invalid configuration in `{:?}`", path))?
+        } else {
+            Config::default()
+        };
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Returns the configuration with the settings of the devices of the profile `name`, then
+    /// those of the profile
+    pub fn with_profile(&self, name: &str) -> Result<Config, anyhow::Error> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            match names.is_empty() {
+                true => bail!("There is no profile `{}` in the configuration", name),
+                false => bail!("Unknown profile `{}`, use {}", name, names.join(", ")),
+            }
+        };
+        let mut config = self.clone();
+        for device in &profile.devices {
+            let Some(settings) = self.profiles.get(device) else {
+                bail!("Unknown device `{}` in profile `{}`", device, name);
+            };
+            config.apply(settings.clone());
+        }
+        config.apply(profile);
+        config.profile = Some(name.to_string());
+        Ok(config)
+    }
+
+    /// Replaces the settings given by a profile
+    fn apply(&mut self, profile: Profile) {
+        self.port = profile.port.or(self.port.take());
+        self.baud = profile.baud.or(self.baud);
+        if let Some(filter) = profile.filter {
+            self.filter = filter;
+        }
+        if let Some(names) = profile.names {
+            self.names.merge(names);
+        }
+        if let Some(stepper) = profile.stepper {
+            self.stepper = stepper;
+        }
+        if let Some(pads) = profile.pads {
+            self.pads = pads;
+        }
+        if let Some(layout) = profile.layout {
+            self.layout = layout;
+        }
+        self.layouts.extend(profile.layouts);
+        if let Some(rules) = profile.rules {
+            self.rules = rules;
+        }
+        if let Some(triggers) = profile.triggers {
+            self.triggers = triggers;
+        }
+        self.script = profile.script.or(self.script.take());
+    }
+
+    /// Adds a named layout, to the profile in use if there is one, and writes it to the file
+    /// the configuration was loaded from, leaving the rest of the file as it is. Returns the
+    /// path of the file
+    pub fn save_layout(&mut self, name: &str, layout: &Layout) -> Result<PathBuf, anyhow::Error> {
+        let Some(path) = self.path.clone() else {
+            bail!("No configuration file to save layouts to");
+        };
+        match &self.profile {
+            Some(profile) => save_table(&path, &["profiles", profile, "layouts", name], layout)?,
+            None => save_table(&path, &["layouts", name], layout)?,
+        }
+        self.layouts.insert(name.to_string(), layout.clone());
+        Ok(path)
+    }
+}
+
+/// Writes `value` as the table at `keys` of the configuration file at `path`, in place of
+/// the one there, creating the file and its directory if needed. The rest of the file is
+/// kept as it was written, with its comments and order
+pub fn save_table(path: &Path, keys: &[&str], value: &impl Serialize) -> Result<(), anyhow::Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context(format!("Unable to read `{:?}`", path)),
+    };
+    let mut document: DocumentMut = text
+        .parse()
+        .context(format!("Invalid configuration in `{:?}`", path))?;
+    let end = last_position(document.as_table()) + 1;
+    let (last, parents) = keys.split_last().context("No table to save")?;
+    let mut table = document.as_table_mut();
+    for key in parents {
+        let item = table.entry(key).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        if item.is_inline_table() {
+            // `layouts = { live = { ... } }` is written out as a table to add one to it
+            *item = std::mem::take(item)
+                .into_table()
+                .map(Item::Table)
+                .unwrap_or_default();
+        }
+        table = item
+            .as_table_mut()
+            .context(format!("`{}` is not a table in `{:?}`", key, path))?;
+    }
+    let written: DocumentMut = toml::to_string_pretty(value)?.parse()?;
+    let mut new = written.as_table().clone();
+    // The table takes the place of the one it replaces, comments included, or goes last
+    let position = match table.get(last).and_then(Item::as_table) {
+        Some(old) => {
+            *new.decor_mut() = old.decor().clone();
+            old.position().unwrap_or(end)
+        }
+        None => end,
+    };
+    place(&mut new, position);
+    table.insert(last, Item::Table(new));
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(format!("Unable to create `{:?}`", dir))?;
+    }
+    fs::write(path, document.to_string()).context(format!("Unable to write `{:?}`", path))
+}
+
+/// Returns the highest position in the file of a table and the tables in it
+fn last_position(table: &Table) -> usize {
+    let nested = table.iter().map(|(_, item)| match item {
+        Item::Table(table) => last_position(table),
+        Item::ArrayOfTables(array) => array.iter().map(last_position).max().unwrap_or(0),
+        _ => 0,
+    });
+    nested.fold(table.position().unwrap_or(0), usize::max)
+}
+
+/// Moves a table and the tables in it to a position in the file, keeping their order
+fn place(table: &mut Table, position: usize) {
+    table.set_position(position);
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(table) => place(table, position),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(|t| place(t, position)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::Panel;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn parse_layouts() {
+        let config: Config = toml::from_str(
+            r#"
+            note_names = "german"
+            layout = { panels = ["detail"] }
+
+            [layouts.sync]
+            panels = ["stats", "detail"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.note_names, NoteNaming::German);
+        assert_eq!(config.layout.panels, vec![Panel::Detail]);
+        assert_eq!(
+            config.layouts["sync"].panels,
+            vec![Panel::Stats, Panel::Detail]
+        );
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            port = "/dev/ttyUSB0"
+            layout = { panels = ["detail"] }
+
+            [filter]
+            hide = ["clock"]
+
+            [layouts.sync]
+            panels = ["stats"]
+
+            [[rules]]
+            channel = 1
+            to_channel = 5
+            transpose = 12
+            drop = ["aftertouch"]
+
+            [profiles.live]
+            port = "/dev/ttyACM0"
+            filter = { only = ["notes"] }
+            names = { programs = { 5 = "Strings" } }
+            layouts.mpe = { panels = ["mpe"] }
+
+            [profiles.bench]
+            "#,
+        )
+        .unwrap();
+        let live = config.with_profile("live").unwrap();
+        assert_eq!(live.port.as_deref(), Some(OsStr::new("/dev/ttyACM0")));
+        assert_eq!(live.filter.only, vec!["notes"]);
+        assert!(live.filter.hide.is_empty());
+        assert_eq!(
+            live.names.program("Program 5".to_string(), 5),
+            "Program 5 (Strings)"
+        );
+        assert_eq!(live.layout, config.layout);
+        assert_eq!(live.layouts.len(), 2);
+        assert_eq!(live.profile.as_deref(), Some("live"));
+        assert_eq!(live.rules[0].to_channel, Some(5));
+        assert_eq!(live.rules[0].drop, vec!["aftertouch"]);
+
+        let bench = config.with_profile("bench").unwrap();
+        assert_eq!(bench.port, config.port);
+        assert_eq!(
+            config.with_profile("studio").unwrap_err().to_string(),
+            "Unknown profile `studio`, use bench, live"
+        );
+    }
+
+    #[test]
+    fn device_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            names = { channels = { 1 = "Lead" }, programs = { 0 = "Init" } }
+
+            [profiles.td17]
+            script = "td17.rhai"
+            names = { drums = { 36 = "Kick" } }
+
+            [profiles.dx7]
+            script = "dx7.rhai"
+            names = { programs = { 0 = "E.Piano 1" } }
+
+            [profiles.studio]
+            port = "/dev/ttyACM0"
+            devices = ["td17", "dx7"]
+            names = { channels = { 10 = "Kit" } }
+
+            [profiles.broken]
+            devices = ["td17", "jv1080"]
+            "#,
+        )
+        .unwrap();
+        let studio = config.with_profile("studio").unwrap();
+        assert_eq!(studio.script.as_deref(), Some(Path::new("dx7.rhai")));
+        assert_eq!(studio.port.as_deref(), Some(OsStr::new("/dev/ttyACM0")));
+        let names = &studio.names;
+        assert_eq!(
+            names.program("Program 0".to_string(), 0),
+            "Program 0 (E.Piano 1)"
+        );
+        assert_eq!(names.drums["36"], "Kick");
+        assert_eq!(names.channel("Ch 1".to_string(), 0), "Ch 1 (Lead)");
+        assert_eq!(names.channel("Ch 10".to_string(), 9), "Ch 10 (Kit)");
+        assert_eq!(
+            config.with_profile("broken").unwrap_err().to_string(),
+            "Unknown device `jv1080` in profile `broken`"
+        );
+    }
+
+    #[test]
+    fn save_layouts() {
+        let dir = env::temp_dir().join(format!("miditerm-config-{}", std::process::id()));
+        let path = dir.join(FILE_NAME);
+        fs::create_dir_all(&dir).unwrap();
+        let text = r#"# My rig
+port = "/dev/ttyUSB0" # the interface
+
+[layouts.sync] # for the drum machine
+panels = ["stats"]
+
+# Live sets
+[profiles.live]
+port = "/dev/ttyACM0"
+layouts.mpe = { panels = ["mpe"] }
+"#;
+        fs::write(&path, text).unwrap();
+        let detail = Layout {
+            panels: vec![Panel::Detail],
+        };
+
+        let mut config = Config::load(&path).unwrap();
+        config.save_layout("sync", &detail).unwrap();
+        config.save_layout("bench", &detail).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# My rig\nport = \"/dev/ttyUSB0\" # the interface\n"));
+        assert!(saved.contains("[layouts.sync] # for the drum machine\npanels = [\"detail\"]"));
+        assert!(saved.contains("# Live sets\n[profiles.live]\n"));
+        assert_eq!(config.layouts["bench"], detail);
+
+        let mut live = Config::load(&path).unwrap().with_profile("live").unwrap();
+        live.save_layout("solo", &detail).unwrap();
+        let saved = Config::load(&path).unwrap();
+        assert_eq!(saved.layouts["bench"], detail);
+        assert_eq!(saved.layouts["sync"], detail);
+        let layouts = &saved.profiles["live"].layouts;
+        assert_eq!(layouts.keys().collect::<Vec<_>>(), ["mpe", "solo"]);
+        assert_eq!(layouts["solo"], detail);
+        assert!(fs::read_to_string(&path).unwrap().contains("# Live sets"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            port = "/dev/ttyUSB0"
+            baud = 38400
+            timestamps = "clock"
+            theme = "mono"
+            alert = "bell"
+
+            [colors.messages]
+            clock = "dark-gray"
+
+            [keys]
+            preset = "vim"
+            bind = { "ctrl-r" = "record", x = "none" }
+
+            [filter]
+            hide = ["clock", "activesense"]
+
+            [names.channels]
+            10 = "Drums"
+
+            [names.controls]
+            74 = "Cutoff"
+
+            [names.programs]
+            0 = "Grand Piano"
+
+            [names.drums]
+            36 = "Kick"
+
+            [stepper]
+            channel = 2
+            bank = 1
+
+            [[pads]]
+            key = "z"
+            message = "noteon 10 36 100"
+            label = "Kick"
+
+            [[pads]]
+            key = "1"
+            message = "pc 1 5"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.port.as_deref(), Some(OsStr::new("/dev/ttyUSB0")));
+        assert_eq!(config.baud, Some(38400));
+        assert_eq!(config.timestamps, Some(TimeFormat::Clock));
+        assert_eq!(config.theme, Theme::Mono);
+        assert_eq!(config.alert, Alert::Bell);
+        assert_eq!(config.colors.messages["clock"], "dark-gray");
+        assert_eq!(config.keys.preset, KeyPreset::Vim);
+        assert_eq!(config.keys.bind["x"], "none");
+        assert_eq!(config.filter.hide, vec!["clock", "activesense"]);
+        assert_eq!(
+            config.names.channel("Note On".to_string(), 9),
+            "Note On (Drums)"
+        );
+        assert_eq!(
+            config.names.control("CC 74".to_string(), 74),
+            "CC 74 (Cutoff)"
+        );
+        assert_eq!(config.names.control("CC 1".to_string(), 1), "CC 1");
+        assert_eq!(
+            config.names.program("Program 0".to_string(), 0),
+            "Program 0 (Grand Piano)"
+        );
+        assert_eq!(
+            config.names.note("Note On".to_string(), 9, 36),
+            "Note On (Kick)"
+        );
+        assert_eq!(config.names.note("Note On".to_string(), 0, 36), "Note On");
+        assert_eq!(config.stepper.channel, 2);
+        assert_eq!(config.stepper.bank, Some(1));
+        assert_eq!(config.pads[0].key, 'z');
+        assert_eq!(config.pads[0].label.as_deref(), Some("Kick"));
+        assert_eq!(config.pads[1].message, "pc 1 5");
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+    }
+}
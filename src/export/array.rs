@@ -0,0 +1,164 @@
+//! Byte array literals of a capture, for pasting captured sequences into firmware tests
+
+use crate::capture::CaptureEvent;
+use std::fmt::Write;
+
+/// Bytes written per line of the array
+const BYTES_PER_LINE: usize = 12;
+
+/// Languages the array can be written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    C,
+    Rust,
+}
+
+/// Renders the bytes of the events as an array constant named after `name`, one message per
+/// line with a comment naming it
+pub fn array(events: &[CaptureEvent], language: Language, name: &str) -> String {
+    let mut lines = vec![];
+    for (bytes, comment) in messages(events) {
+        for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("0x{:02X},", b)).collect();
+            lines.push((hex.join(" "), (i == 0).then(|| comment.clone())));
+        }
+    }
+    let width = lines.iter().map(|(hex, _)| hex.len()).max().unwrap_or(0);
+    let name = identifier(name);
+
+    let mut out = String::new();
+    let _ = match language {
+        Language::C => writeln!(out, "const unsigned char {}[{}] = {{", name, events.len()),
+        Language::Rust => writeln!(
+            out,
+            "const {}: [u8; {}] = [",
+            name.to_uppercase(),
+            events.len()
+        ),
+    };
+    for (hex, comment) in lines {
+        let _ = match (comment, language) {
+            (None, _) => writeln!(out, "    {}", hex),
+            (Some(comment), Language::C) => {
+                writeln!(out, "    {:width$} /* {} */", hex, comment, width = width)
+            }
+            (Some(comment), Language::Rust) => {
+                writeln!(out, "    {:width$} // {}", hex, comment, width = width)
+            }
+        };
+    }
+    out.push_str(match language {
+        Language::C => "};\n",
+        Language::Rust => "];\n",
+    });
+    out
+}
+
+/// Groups the bytes into the messages they belong to in the order they were received, each
+/// with a description. Bytes that do not complete a message are grouped until the next
+/// status byte
+fn messages(events: &[CaptureEvent]) -> Vec<(Vec<u8>, String)> {
+    let mut messages = vec![];
+    let mut group = Group::default();
+    // Notices carry no bytes
+    for event in events.iter().filter(|event| !event.notice) {
+        if event.byte >= 0xF8 {
+            // Real Time bytes are messages of their own, unless they interrupt another message
+            if group.bytes.is_empty() {
+                messages.push((vec![event.byte], describe(event)));
+            } else {
+                group.bytes.push(event.byte);
+                group.inside.push(describe(event));
+            }
+            continue;
+        }
+        if event.is_status() && event.byte != 0xF7 {
+            messages.extend(group.take(None));
+        }
+        group.bytes.push(event.byte);
+        group.text = event.analysis.text().to_string();
+        if event.message.is_some() {
+            messages.extend(group.take(Some(describe(event))));
+        }
+    }
+    messages.extend(group.take(None));
+    messages
+}
+
+/// Bytes of a message that is not complete yet
+#[derive(Default)]
+struct Group {
+    bytes: Vec<u8>,
+    /// Analysis of the last byte that is not Real Time
+    text: String,
+    /// Real Time messages received in the middle of the message
+    inside: Vec<String>,
+}
+
+impl Group {
+    /// Takes the bytes with the description of the message they complete, or the analysis
+    /// of their last byte if they are incomplete
+    fn take(&mut self, message: Option<String>) -> Option<(Vec<u8>, String)> {
+        let group = std::mem::take(self);
+        if group.bytes.is_empty() {
+            return None;
+        }
+        let mut text = message.unwrap_or_else(|| format!("{} (incomplete)", group.text));
+        if !group.inside.is_empty() {
+            text = format!("{}, with {} inside", text, group.inside.join(", "));
+        }
+        Some((group.bytes, text))
+    }
+}
+
+/// Names the message completed by the event and its channel
+fn describe(event: &CaptureEvent) -> String {
+    match &event.message {
+        Some(message) => match message.channel() {
+            Some(channel) => format!("{}, channel {}", message.name(), channel + 1),
+            None => message.name().to_string(),
+        },
+        None => event.analysis.text().to_string(),
+    }
+}
+
+/// Turns a file name into a valid identifier
+fn identifier(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn one_message_per_line() {
+        let mut capture = Capture::new();
+        let events: Vec<CaptureEvent> = [0xFE, 0x90, 0x3C, 0xF8, 0x7F, 0x3C, 0x00, 0x40, 0xB0]
+            .into_iter()
+            .map(|byte| capture.process(Duration::ZERO, byte))
+            .collect();
+        assert_eq!(
+            array(&events, Language::C, "note-on"),
+            "const unsigned char note_on[9] = {\n\
+             \x20   0xFE,                   /* Active Sensing */\n\
+             \x20   0x90, 0x3C, 0xF8, 0x7F, /* Note On, channel 1, with Timing Clock inside */\n\
+             \x20   0x3C, 0x00,             /* Note On, channel 1 */\n\
+             \x20   0x40,                   /* Note On (Channel 0): Note 64 (E4) (incomplete) */\n\
+             \x20   0xB0,                   /* Control Change (Channel 0) (incomplete) */\n\
+             };\n"
+        );
+        assert!(array(&events, Language::Rust, "1st").starts_with(
+            "const _1ST: [u8; 9] = [\n    0xFE,                   // Active Sensing\n"
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+//! CSV renderings of a capture
+//!
+//! The midicsv format is the text representation of a Standard MIDI File used by the
+//! `midicsv`/`csvmidi` tools, so captures can be diffed and converted with existing tooling
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{self, MidiAnalysis},
+    smf::{self, SmfEvent, SmfTrack},
+};
+use std::fmt::Write;
+
+/// Header row of the plain CSV format
+pub const PLAIN_HEADER: &str = "time,bytes,channel,message,severity,analysis";
+
+/// Renders a track in the midicsv format
+pub fn midicsv(track: &SmfTrack) -> String {
+    let events = track.sorted_events();
+    let mut out = String::new();
+    let _ = writeln!(out, "0, 0, Header, 0, 1, {}", track.ppq());
+    let _ = writeln!(out, "1, 0, Start_track");
+    for event in &events {
+        if let Some(record) = midicsv_record(event) {
+            let _ = writeln!(out, "1, {}, {}", event.tick, record);
+        }
+    }
+    let end = events.last().map_or(0, |e| e.tick);
+    let _ = writeln!(out, "1, {}, End_track", end);
+    let _ = writeln!(out, "0, 0, End_of_file");
+    out
+}
+
+/// Formats the record type and fields of a single track event
+fn midicsv_record(event: &SmfEvent) -> Option<String> {
+    let data = &event.data;
+    let status = *data.first()?;
+    let channel = status & 0x0F;
+    let d0 = data.get(1).copied().unwrap_or(0);
+    let d1 = data.get(2).copied().unwrap_or(0);
+    let record = match status {
+        0xFF if d0 == 0x51 => {
+            let tempo = u32::from_be_bytes([0, data[3], data[4], data[5]]);
+            format!("Tempo, {}", tempo)
+        }
+        0xF0 => {
+            let (length, n) = smf::read_var_len(&data[1..])?;
+            let bytes: Vec<String> = data[1 + n..].iter().map(|b| b.to_string()).collect();
+            format!("System_exclusive, {}, {}", length, bytes.join(", "))
+        }
+        0x80..=0x8F => format!("Note_off_c, {}, {}, {}", channel, d0, d1),
+        0x90..=0x9F => format!("Note_on_c, {}, {}, {}", channel, d0, d1),
+        0xA0..=0xAF => format!("Poly_aftertouch_c, {}, {}, {}", channel, d0, d1),
+        0xB0..=0xBF => format!("Control_c, {}, {}, {}", channel, d0, d1),
+        0xC0..=0xCF => format!("Program_c, {}, {}", channel, d0),
+        0xD0..=0xDF => format!("Channel_aftertouch_c, {}, {}", channel, d0),
+        0xE0..=0xEF => {
+            let value = ((d1 as u16) << 7) | d0 as u16;
+            format!("Pitch_bend_c, {}, {}", channel, value)
+        }
+        _ => return None,
+    };
+    Some(record)
+}
+
+/// Renders one row of the plain CSV format, or `None` for events that neither complete a
+/// message, raise an issue, nor tell what happened to the source
+pub fn plain_row(event: &CaptureEvent) -> Option<String> {
+    let bytes = if event.message.is_some() {
+        event.raw.as_slice()
+    } else if event.notice
+        || matches!(
+            event.analysis,
+            MidiAnalysis::Warning(_) | MidiAnalysis::Violation(_)
+        )
+    {
+        event.received()
+    } else {
+        return None;
+    };
+    let hex = midi::hex(bytes);
+    let channel = event
+        .message
+        .as_ref()
+        .and_then(|m| m.channel())
+        .map_or(String::new(), |ch| (ch + 1).to_string());
+    let name = event.message.as_ref().map_or("", |m| m.name());
+    Some(format!(
+        "{:.6},{},{},{},{},{}",
+        event.time.as_secs_f64(),
+        hex,
+        channel,
+        name,
+        event.analysis.severity(),
+        quote(event.analysis.text())
+    ))
+}
+
+/// Quotes a CSV field if it contains separators or quotes
+fn quote(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use crate::smf::TrackBuilder;
+    use std::time::Duration;
+
+    #[test]
+    fn midicsv_track() {
+        let mut capture = Capture::new();
+        let mut builder = TrackBuilder::new(480, 120.0);
+        let bytes = [
+            (0, 0x90),
+            (0, 60),
+            (0, 100),
+            (500, 0xE1),
+            (500, 0x00),
+            (500, 0x40),
+            (1000, 0xF0),
+            (1000, 0x43),
+            (1000, 0xF7),
+        ];
+        for (ms, byte) in bytes {
+            builder.push(&capture.process(Duration::from_millis(ms), byte));
+        }
+        assert_eq!(
+            midicsv(builder.track()),
+            "0, 0, Header, 0, 1, 480\n\
+             1, 0, Start_track\n\
+             1, 0, Tempo, 500000\n\
+             1, 0, Note_on_c, 0, 60, 100\n\
+             1, 480, Pitch_bend_c, 1, 8192\n\
+             1, 960, System_exclusive, 2, 67, 247\n\
+             1, 960, End_track\n\
+             0, 0, End_of_file\n"
+        );
+    }
+
+    #[test]
+    fn plain() {
+        let mut capture = Capture::new();
+        let time = Duration::from_millis(1500);
+        assert_eq!(plain_row(&capture.process(time, 0xC2)), None);
+        assert_eq!(
+            plain_row(&capture.process(time, 5)).unwrap(),
+            "1.500000,C2 05,3,Program Change,comment,Program Change (Channel 2): Program 5"
+        );
+        assert_eq!(quote("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
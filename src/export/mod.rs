@@ -0,0 +1,5 @@
+//! Conversion of complete captures into other file formats
+
+pub mod array;
+pub mod csv;
+pub mod svg;
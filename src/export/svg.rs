@@ -0,0 +1,383 @@
+//! SVG charts of a capture for inclusion in reports
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{controls::get_controller_name, notes::NoteNaming, MidiMessage},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    time::Duration,
+};
+
+/// Fill colors of channels 1 to 16
+const CHANNEL_COLORS: [&str; 16] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#bfef45",
+    "#469990", "#9a6324", "#800000", "#808000", "#000075", "#a9a9a9", "#dcbeff", "#000000",
+];
+
+/// Horizontal scale of short charts
+const PIXELS_PER_SECOND: f64 = 100.0;
+/// Width of the plot beyond which longer captures are scaled down to fit
+const MAX_PLOT_WIDTH: f64 = 4000.0;
+/// Height of a key on the piano roll
+const KEY_HEIGHT: f64 = 6.0;
+/// Space left of and below the plot for the axis labels
+const MARGIN: f64 = 40.0;
+/// Height of a controller lane, a pixel for every other value
+const LANE_HEIGHT: f64 = 64.0;
+/// Space above a controller lane for its label
+const LANE_LABEL: f64 = 16.0;
+
+/// A note from its Note On to its Note Off
+#[derive(Debug, Clone, PartialEq)]
+struct Note {
+    channel: u8,
+    key: u8,
+    velocity: u8,
+    start: Duration,
+    end: Duration,
+}
+
+/// Collects the notes of a capture and renders them as a piano roll:
+/// time against pitch, with velocity as opacity and channels as colors
+#[derive(Debug, Default)]
+pub struct PianoRoll {
+    notes: Vec<Note>,
+    /// Start time and velocity of the notes currently held, by channel and key
+    held: HashMap<(u8, u8), (Duration, u8)>,
+    start: Option<Duration>,
+    end: Duration,
+    naming: NoteNaming,
+}
+
+impl PianoRoll {
+    /// Creates an empty piano roll that names notes with `naming`
+    pub fn new(naming: NoteNaming) -> PianoRoll {
+        PianoRoll {
+            naming,
+            ..PianoRoll::default()
+        }
+    }
+
+    /// Adds the note completed or started by the event, if any
+    pub fn push(&mut self, event: &CaptureEvent) {
+        self.start.get_or_insert(event.time);
+        self.end = self.end.max(event.time);
+        match event.message {
+            Some(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }) if velocity > 0 => {
+                // A retriggered note ends the one already sounding
+                self.release(channel, note, event.time);
+                self.held.insert((channel, note), (event.time, velocity));
+            }
+            Some(MidiMessage::NoteOn { channel, note, .. })
+            | Some(MidiMessage::NoteOff { channel, note, .. }) => {
+                self.release(channel, note, event.time)
+            }
+            _ => {}
+        }
+    }
+
+    fn release(&mut self, channel: u8, key: u8, time: Duration) {
+        if let Some((start, velocity)) = self.held.remove(&(channel, key)) {
+            self.notes.push(Note {
+                channel,
+                key,
+                velocity,
+                start,
+                end: time,
+            });
+        }
+    }
+
+    /// Renders the piano roll. Notes still held at the end of the capture extend to its end
+    pub fn render(&self) -> String {
+        let mut notes = self.notes.clone();
+        notes.extend(
+            self.held
+                .iter()
+                .map(|(&(channel, key), &(start, velocity))| Note {
+                    channel,
+                    key,
+                    velocity,
+                    start,
+                    end: self.end,
+                }),
+        );
+        notes.sort_by_key(|n| (n.start, n.key));
+
+        let start = self.start.unwrap_or_default();
+        let span = self.end.saturating_sub(start).as_secs_f64();
+        let low = notes
+            .iter()
+            .map(|n| n.key)
+            .min()
+            .unwrap_or(60)
+            .saturating_sub(1);
+        let high = notes
+            .iter()
+            .map(|n| n.key)
+            .max()
+            .unwrap_or(72)
+            .saturating_add(1)
+            .min(127);
+        let plot_height = (high - low + 1) as f64 * KEY_HEIGHT;
+        let y = |key: u8| (high - key) as f64 * KEY_HEIGHT;
+        let scale = time_scale(span);
+        let x = |time: Duration| MARGIN + time.saturating_sub(start).as_secs_f64() * scale;
+
+        let mut svg = String::new();
+        let width = MARGIN + span * scale + 10.0;
+        header(&mut svg, width, plot_height + MARGIN);
+
+        // Shade the black keys and label every C
+        for key in low..=high {
+            if matches!(key % 12, 1 | 3 | 6 | 8 | 10) {
+                let _ = writeln!(
+                    svg,
+                    r##"<rect x="{MARGIN}" y="{:.1}" width="{:.1}" height="{KEY_HEIGHT}" fill="#f0f0f0"/>"##,
+                    y(key),
+                    width - MARGIN
+                );
+            }
+            if key % 12 == 0 {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="2" y="{:.1}" font-size="8">{}</text>"#,
+                    y(key) + KEY_HEIGHT,
+                    self.naming.name(key)
+                );
+            }
+        }
+        time_grid(&mut svg, span, scale, plot_height);
+
+        for note in &notes {
+            let _ = writeln!(
+                svg,
+                r#"<rect class="note" x="{:.1}" y="{:.1}" width="{:.1}" height="{KEY_HEIGHT}" fill="{}" fill-opacity="{:.2}"><title>Ch {} {} vel {}</title></rect>"#,
+                x(note.start),
+                y(note.key),
+                (x(note.end) - x(note.start)).max(1.0),
+                CHANNEL_COLORS[note.channel as usize & 0x0F],
+                0.2 + 0.8 * note.velocity as f64 / 127.0,
+                note.channel + 1,
+                self.naming.name(note.key),
+                note.velocity
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Collects the values of Control Change messages and renders them as one lane per
+/// controller and channel, with value against time
+#[derive(Debug, Default)]
+pub struct CcLanes {
+    /// Controllers to plot. Every controller is plotted if empty
+    controls: BTreeSet<u8>,
+    /// Times and values of each controller, by channel and controller
+    lanes: BTreeMap<(u8, u8), Vec<(Duration, u8)>>,
+    start: Option<Duration>,
+    end: Duration,
+}
+
+impl CcLanes {
+    /// Creates lanes for the given controllers, or for every controller if empty
+    pub fn new(controls: BTreeSet<u8>) -> CcLanes {
+        CcLanes {
+            controls,
+            ..CcLanes::default()
+        }
+    }
+
+    /// Adds the value set by the event, if any
+    pub fn push(&mut self, event: &CaptureEvent) {
+        self.start.get_or_insert(event.time);
+        self.end = self.end.max(event.time);
+        if let Some(MidiMessage::ControlChange {
+            channel,
+            control,
+            value,
+        }) = event.message
+        {
+            if self.controls.is_empty() || self.controls.contains(&control) {
+                self.lanes
+                    .entry((channel, control))
+                    .or_default()
+                    .push((event.time, value));
+            }
+        }
+    }
+
+    /// Renders the lanes. Each value holds until the next one or the end of the capture
+    pub fn render(&self) -> String {
+        let start = self.start.unwrap_or_default();
+        let span = self.end.saturating_sub(start).as_secs_f64();
+        let scale = time_scale(span);
+        let x = |time: Duration| MARGIN + time.saturating_sub(start).as_secs_f64() * scale;
+        let lane_span = LANE_LABEL + LANE_HEIGHT;
+        let plot_height = (self.lanes.len().max(1)) as f64 * lane_span;
+
+        let mut svg = String::new();
+        let width = MARGIN + span * scale + 10.0;
+        header(&mut svg, width, plot_height + MARGIN);
+        time_grid(&mut svg, span, scale, plot_height);
+        if self.lanes.is_empty() {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{MARGIN}" y="{LANE_LABEL}" font-size="10">No Control Change messages</text>"#
+            );
+        }
+
+        for (i, (&(channel, control), points)) in self.lanes.iter().enumerate() {
+            let top = i as f64 * lane_span + LANE_LABEL;
+            let y = |value: u8| top + LANE_HEIGHT - value as f64 * LANE_HEIGHT / 127.0;
+            let _ = writeln!(
+                svg,
+                r#"<text x="{MARGIN}" y="{:.1}" font-size="10">Ch {} CC {} {}</text>"#,
+                top - 4.0,
+                channel + 1,
+                control,
+                get_controller_name(control)
+            );
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{MARGIN}" y="{top:.1}" width="{:.1}" height="{LANE_HEIGHT}" fill="none" stroke="#cccccc"/>"##,
+                width - MARGIN
+            );
+            for value in [0, 127] {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="2" y="{:.1}" font-size="8">{}</text>"#,
+                    y(value) + 3.0,
+                    value
+                );
+            }
+
+            // Hold each value until the next one as a step line
+            let mut line = String::new();
+            let mut previous: Option<u8> = None;
+            for &(time, value) in points {
+                if let Some(previous) = previous {
+                    let _ = write!(line, "{:.1},{:.1} ", x(time), y(previous));
+                }
+                let _ = write!(line, "{:.1},{:.1} ", x(time), y(value));
+                previous = Some(value);
+            }
+            if let Some(previous) = previous {
+                let _ = write!(line, "{:.1},{:.1}", x(self.end), y(previous));
+            }
+            let _ = writeln!(
+                svg,
+                r#"<polyline class="lane" points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+                line.trim_end(),
+                CHANNEL_COLORS[channel as usize & 0x0F]
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Returns the horizontal scale in pixels per second for a capture lasting `span` seconds
+fn time_scale(span: f64) -> f64 {
+    PIXELS_PER_SECOND.min(MAX_PLOT_WIDTH / span.max(f64::EPSILON))
+}
+
+/// Opens an SVG document with a white background
+fn header(svg: &mut String, width: f64, height: f64) {
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" font-family="sans-serif">"#,
+        width, height
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+}
+
+/// Draws a vertical line and a label for every step of time below the plot
+fn time_grid(svg: &mut String, span: f64, scale: f64, plot_height: f64) {
+    // Keep the number of lines readable for any length of capture
+    let step = [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 3600.0]
+        .into_iter()
+        .find(|step| span / step <= 20.0)
+        .unwrap_or(3600.0);
+    let mut time = 0.0;
+    while time <= span {
+        let x = MARGIN + time * scale;
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x:.1}" y1="0" x2="{x:.1}" y2="{plot_height:.1}" stroke="#cccccc"/>"##
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{x:.1}" y="{:.1}" font-size="8">{}s</text>"#,
+            plot_height + 12.0,
+            (time * 10.0).round() / 10.0
+        );
+        time += step;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn piano_roll_notes() {
+        let mut capture = Capture::new();
+        let mut roll = PianoRoll::new(NoteNaming::English);
+        let bytes = [
+            (0, 0x90),
+            (0, 60),
+            (0, 127),
+            (500, 0x91),
+            (500, 64),
+            (500, 64),
+            (1000, 0x90),
+            (1000, 60),
+            (1000, 0),
+        ];
+        for (ms, byte) in bytes {
+            roll.push(&capture.process(Duration::from_millis(ms), byte));
+        }
+        assert_eq!(roll.notes.len(), 1);
+        assert_eq!(roll.notes[0].end, Duration::from_secs(1));
+
+        let svg = roll.render();
+        assert_eq!(svg.matches(r#"class="note""#).count(), 2);
+        assert!(svg.contains(r##"width="100.0" height="6" fill="#e6194b" fill-opacity="1.00""##));
+        assert!(svg.contains("<title>Ch 2 E4 vel 64</title>"));
+    }
+
+    #[test]
+    fn cc_lanes_steps() {
+        let mut capture = Capture::new();
+        let mut lanes = CcLanes::new([7].into());
+        let bytes = [
+            (0, 0xB0),
+            (0, 7),
+            (0, 127),
+            (0, 10),
+            (0, 64),
+            (1000, 7),
+            (1000, 0),
+            (2000, 0xF8),
+        ];
+        for (ms, byte) in bytes {
+            lanes.push(&capture.process(Duration::from_millis(ms), byte));
+        }
+        assert_eq!(lanes.lanes.len(), 1);
+
+        let svg = lanes.render();
+        assert!(svg.contains("Ch 1 CC 7 Channel volume"));
+        assert!(svg.contains(
+            r##"points="40.0,16.0 140.0,16.0 140.0,80.0 240.0,80.0" fill="none" stroke="#e6194b""##
+        ));
+    }
+}
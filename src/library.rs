@@ -0,0 +1,295 @@
+//! A librarian of the SysEx dumps of a rig
+//!
+//! The library is a directory of `.syx` files with an index, `library.toml`, listing each
+//! dump with a number, a name given by the user, its manufacturer, size, CRC-32, and the
+//! time it was received. The directory lives in `$XDG_DATA_HOME/miditerm/library/`, or
+//! `~/.local/share/miditerm/library/` if that is not set, unless another one is given. A
+//! dump already in the library is not added again, so patches can be dumped from a synth
+//! as often as needed
+
+use crate::{
+    midi::{self, sysex},
+    syx,
+};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the index in the directory of the library
+const INDEX: &str = "library.toml";
+
+/// A dump of the library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dump {
+    pub number: usize,
+    pub name: String,
+    /// `.syx` file in the directory of the library
+    pub file: String,
+    /// Manufacturer ID in hex, such as `41` or `00 20 29`
+    pub manufacturer: String,
+    /// Bytes of the message, with its `F0` and `F7`
+    pub size: usize,
+    /// CRC-32 of the data bytes
+    pub crc: String,
+    /// Seconds since the Unix epoch
+    pub received: u64,
+}
+
+impl Dump {
+    /// Returns the name of the manufacturer, or its ID if it is not known
+    pub fn manufacturer_name(&self) -> String {
+        let id: Vec<u8> = self
+            .manufacturer
+            .split_whitespace()
+            .filter_map(|b| u8::from_str_radix(b, 16).ok())
+            .collect();
+        match sysex::manufacturer(&id) {
+            Some(m) => m.manufacturer.clone(),
+            None => self.manufacturer.clone(),
+        }
+    }
+}
+
+/// Contents of the index
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default, rename = "dump")]
+    dumps: Vec<Dump>,
+}
+
+/// What happened to a dump given to `Library::add`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Added {
+    /// Added with this number
+    New(usize),
+    /// Already in the library with this number
+    Known(usize),
+}
+
+/// The dumps of a library directory
+pub struct Library {
+    dir: PathBuf,
+    dumps: Vec<Dump>,
+}
+
+impl Library {
+    /// Returns the library directory of the user, if a home directory is known
+    pub fn default_dir() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+        };
+        Some(dir.join("miditerm").join("library"))
+    }
+
+    /// Opens the library in `dir`, which is created if it does not exist
+    pub fn open(dir: &Path) -> Result<Library, anyhow::Error> {
+        fs::create_dir_all(dir).context(format!("Unable to create directory `{:?}`", dir))?;
+        let path = dir.join(INDEX);
+        let index: Index = if path.exists() {
+            let text = fs::read_to_string(&path).context(format!("Unable to read `{:?}`", path))?;
+            toml::from_str(&text).context(format!("Invalid library index `{:?}`", path))?
+        } else {
+            Index::default()
+        };
+        Ok(Library {
+            dir: dir.to_path_buf(),
+            dumps: index.dumps,
+        })
+    }
+
+    /// Returns the dumps in the order they were added
+    pub fn dumps(&self) -> &[Dump] {
+        &self.dumps
+    }
+
+    /// Adds the data bytes of a SysEx message received at `received`, unless the same dump is
+    /// already in the library, and writes the index
+    pub fn add(&mut self, data: &[u8], received: SystemTime) -> Result<Added, anyhow::Error> {
+        let crc = format!("{:08X}", crc32(data));
+        let size = data.len() + 2;
+        if let Some(dump) = self.dumps.iter().find(|d| d.crc == crc && d.size == size) {
+            return Ok(Added::Known(dump.number));
+        }
+        let number = self.dumps.iter().map(|d| d.number).max().unwrap_or(0) + 1;
+        let id = match data {
+            [0x00, a, b, ..] => vec![0x00, *a, *b],
+            [a, ..] => vec![*a],
+            [] => vec![],
+        };
+        let name = match sysex::manufacturer(data).filter(|m| !m.reserved) {
+            Some(m) => format!("{} {}", m.manufacturer, number),
+            None => format!("Dump {}", number),
+        };
+        let file = syx::file_name(number, data);
+        syx::save(&self.dir.join(&file), data)?;
+        self.dumps.push(Dump {
+            number,
+            name,
+            file,
+            manufacturer: midi::hex(&id),
+            size,
+            crc,
+            received: received
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs()),
+        });
+        self.save()?;
+        Ok(Added::New(number))
+    }
+
+    /// Returns the dump with a number or a name, names being matched without regard to case
+    pub fn find(&self, key: &str) -> Result<&Dump, anyhow::Error> {
+        let found = match key.parse::<usize>() {
+            Ok(number) => self.dumps.iter().find(|d| d.number == number),
+            Err(_) => self.dumps.iter().find(|d| d.name.eq_ignore_ascii_case(key)),
+        };
+        found.context(format!("No dump `{}` in the library", key))
+    }
+
+    /// Gives a dump a new name, which no other dump may have, and writes the index
+    pub fn rename(&mut self, key: &str, name: &str) -> Result<(), anyhow::Error> {
+        let number = self.find(key)?.number;
+        if name.trim().is_empty() || name.parse::<usize>().is_ok() {
+            bail!("Dumps are named with text, numbers being taken by the dumps themselves");
+        }
+        if let Some(other) = self
+            .dumps
+            .iter()
+            .find(|d| d.number != number && d.name.eq_ignore_ascii_case(name))
+        {
+            bail!("Dump {} is already named `{}`", other.number, other.name);
+        }
+        if let Some(dump) = self.dumps.iter_mut().find(|d| d.number == number) {
+            dump.name = name.trim().to_string();
+        }
+        self.save()
+    }
+
+    /// Takes a dump and its file out of the library, and writes the index
+    pub fn remove(&mut self, key: &str) -> Result<Dump, anyhow::Error> {
+        let number = self.find(key)?.number;
+        let position = self.dumps.iter().position(|d| d.number == number);
+        let dump = self.dumps.remove(position.expect("Dump should be found"));
+        let path = self.dir.join(&dump.file);
+        if path.exists() {
+            fs::remove_file(&path).context(format!("Unable to remove `{:?}`", path))?;
+        }
+        self.save()?;
+        Ok(dump)
+    }
+
+    /// Reads the data bytes of a dump, checking them against the CRC of the index
+    pub fn load(&self, dump: &Dump) -> Result<Vec<u8>, anyhow::Error> {
+        let path = self.dir.join(&dump.file);
+        let data = match &syx::load(&path)?[..] {
+            [data] => data.clone(),
+            _ => bail!("`{:?}` should hold a single SysEx message", path),
+        };
+        if format!("{:08X}", crc32(&data)) != dump.crc {
+            bail!("`{:?}` changed since it was added to the library", path);
+        }
+        Ok(data)
+    }
+
+    /// Writes the index
+    fn save(&self) -> Result<(), anyhow::Error> {
+        let path = self.dir.join(INDEX);
+        let text = toml::to_string_pretty(&Index {
+            dumps: self.dumps.clone(),
+        })?;
+        fs::write(&path, text).context(format!("Unable to write `{:?}`", path))
+    }
+}
+
+/// Returns the CRC-32 of bytes, as used by zip and PNG
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Formats seconds since the Unix epoch as a UTC date and time, such as
+/// `2024-03-09 21:15:02`
+pub fn format_time(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+    // Days to a civil date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn catalogs_dumps() {
+        let dir = env::temp_dir().join(format!("miditerm-library-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let time = UNIX_EPOCH + Duration::from_secs(1_710_018_902);
+        let mut library = Library::open(&dir).unwrap();
+        assert_eq!(
+            library.add(&[0x41, 0x10, 0x42], time).unwrap(),
+            Added::New(1)
+        );
+        assert_eq!(library.add(&[0x43, 0x00], time).unwrap(), Added::New(2));
+        assert_eq!(
+            library.add(&[0x41, 0x10, 0x42], time).unwrap(),
+            Added::Known(1)
+        );
+        library.rename("1", "Fat Bass").unwrap();
+        assert!(library.rename("2", "fat bass").is_err());
+
+        let library = Library::open(&dir).unwrap();
+        let dump = library.find("FAT BASS").unwrap();
+        assert_eq!(
+            (dump.number, dump.size, dump.manufacturer.as_str()),
+            (1, 5, "41")
+        );
+        assert_eq!(dump.manufacturer_name(), "Roland Corporation");
+        assert_eq!(library.load(dump).unwrap(), [0x41, 0x10, 0x42]);
+        assert_eq!(library.find("2").unwrap().name, "Yamaha Corporation 2");
+        assert!(library.find("3").is_err());
+
+        let mut library = library;
+        library.remove("1").unwrap();
+        assert!(!dir.join("sysex_0001_41.syx").exists());
+        assert_eq!(library.add(&[0x7D], time).unwrap(), Added::New(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksums_and_times() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(1_710_018_902), "2024-03-09 21:15:02");
+    }
+}
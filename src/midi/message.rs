@@ -0,0 +1,93 @@
+//! Helpers for inspecting decoded MIDI messages
+
+use crate::midi::*;
+
+impl MidiMessage {
+    /// Returns the human readable name of the message type
+    pub fn name(&self) -> &'static str {
+        match self {
+            MidiMessage::NoteOff { .. } => "Note Off",
+            MidiMessage::NoteOn { .. } => "Note On",
+            MidiMessage::PolyPressure { .. } => "Poly Pressure",
+            MidiMessage::ControlChange { .. } => "Control Change",
+            MidiMessage::ChannelMode { mode, .. } => match mode {
+                MidiChannelMode::AllSoundOff => "All Sound Off",
+                MidiChannelMode::ResetAllControllers => "Reset All Controllers",
+                MidiChannelMode::LocalControl(_) => "Local Control",
+                MidiChannelMode::AllNotesOff => "All Notes Off",
+                MidiChannelMode::OmniModeOff => "Omni Mode Off",
+                MidiChannelMode::OmniModeOn => "Omni Mode On",
+                MidiChannelMode::MonoModeOn(_) => "Mono Mode On",
+                MidiChannelMode::PolyModeOn => "Poly Mode On",
+            },
+            MidiMessage::ProgramChange { .. } => "Program Change",
+            MidiMessage::ChannelPressure { .. } => "Channel Pressure",
+            MidiMessage::PitchBend { .. } => "Pitch Bend",
+            MidiMessage::MtcQuarterFrame(_) => "MTC Quarter Frame",
+            MidiMessage::SongPosition(_) => "Song Position",
+            MidiMessage::SongSelect(_) => "Song Select",
+            MidiMessage::TuneRequest => "Tune Request",
+            MidiMessage::TimingClock => "Timing Clock",
+            MidiMessage::Start => "Start",
+            MidiMessage::Continue => "Continue",
+            MidiMessage::Stop => "Stop",
+            MidiMessage::ActiveSensing => "Active Sensing",
+            MidiMessage::SystemReset => "System Reset",
+            MidiMessage::SystemExclusive(_) => "System Exclusive",
+        }
+    }
+
+    /// Returns the channel of a channel message
+    pub fn channel(&self) -> Option<u8> {
+        match self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ChannelMode { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => Some(*channel),
+            _ => None,
+        }
+    }
+
+    /// Returns the status byte of the message, without the channel of channel messages
+    pub fn status(&self) -> u8 {
+        match self {
+            MidiMessage::NoteOff { .. } => 0x80,
+            MidiMessage::NoteOn { .. } => 0x90,
+            MidiMessage::PolyPressure { .. } => 0xA0,
+            MidiMessage::ControlChange { .. } | MidiMessage::ChannelMode { .. } => 0xB0,
+            MidiMessage::ProgramChange { .. } => 0xC0,
+            MidiMessage::ChannelPressure { .. } => 0xD0,
+            MidiMessage::PitchBend { .. } => 0xE0,
+            MidiMessage::SystemExclusive(_) => 0xF0,
+            MidiMessage::MtcQuarterFrame(_) => 0xF1,
+            MidiMessage::SongPosition(_) => 0xF2,
+            MidiMessage::SongSelect(_) => 0xF3,
+            MidiMessage::TuneRequest => 0xF6,
+            MidiMessage::TimingClock => 0xF8,
+            MidiMessage::Start => 0xFA,
+            MidiMessage::Continue => 0xFB,
+            MidiMessage::Stop => 0xFC,
+            MidiMessage::ActiveSensing => 0xFE,
+            MidiMessage::SystemReset => 0xFF,
+        }
+    }
+
+    /// Moves a channel message to another channel. Other messages are left as they are
+    pub fn set_channel(&mut self, to: u8) {
+        match self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ChannelMode { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => *channel = to,
+            _ => {}
+        }
+    }
+}
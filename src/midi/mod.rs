@@ -1,10 +1,14 @@
 //! Low level MIDI parser
 
 pub mod controls;
+mod message;
+pub mod notes;
 mod parser;
 pub mod sysex;
 mod unparser;
 
+use serde::Serialize;
+
 // PUBLIC CONSTANTS
 pub const MIDI_BAUD_RATE: u32 = 31_250_u32;
 
@@ -23,6 +27,9 @@ const MIDI_MSG_PROGRAM_CHANGE: u8 = 0xC0_u8;
 const MIDI_MSG_CHANNEL_PRESSURE: u8 = 0xD0_u8;
 const MIDI_MSG_PITCH_BEND: u8 = 0xE0_u8;
 
+// Controllers
+const MIDI_CC_SUSTAIN: u8 = 64_u8;
+
 // Channel Mode Messages
 const MIDI_CMM_ALL_SOUNDS_OFF: u8 = 120_u8;
 const MIDI_CMM_RESET_ALL_CONTROLLERS: u8 = 121_u8;
@@ -52,7 +59,7 @@ const MIDI_SYSRT_ACTIVE_SENSE: u8 = 0xFE_u8;
 const MIDI_SYSRT_SYSTEM_RESET: u8 = 0xFF_u8;
 
 /// Enum representing MIDI Channel Mode messages
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum MidiChannelMode {
     AllSoundOff,
     ResetAllControllers,
@@ -67,7 +74,7 @@ pub enum MidiChannelMode {
 /// Enum representing all MIDI messages.
 /// Can be used to construct an outgoing MIDI message
 /// Return type of the `MidiParser`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum MidiMessage {
     // Channel Messages
     NoteOff { channel: u8, note: u8, velocity: u8 },
@@ -97,8 +104,33 @@ pub enum MidiMessage {
     SystemExclusive(Vec<u8>),
 }
 
+/// Formats bytes as space separated hexadecimal, such as `F0 7E 7F 06 01 F7`
+pub fn hex(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    hex.join(" ")
+}
+
+/// Returns the bytes that silence every channel after stuck notes: Sustain off, All Sound
+/// Off, All Notes Off, and Reset All Controllers on each of the 16 channels. Sustain is
+/// released on its own for devices that do not reset it with the other controllers
+pub fn panic_bytes() -> Vec<u8> {
+    (0..16)
+        .flat_map(|channel| {
+            let status = MIDI_MSG_CONTROL_CHANGE | channel;
+            [
+                MIDI_CC_SUSTAIN,
+                MIDI_CMM_ALL_SOUNDS_OFF,
+                MIDI_CMM_ALL_NOTES_OFF,
+                MIDI_CMM_RESET_ALL_CONTROLLERS,
+            ]
+            .into_iter()
+            .flat_map(move |control| [status, control, 0])
+        })
+        .collect()
+}
+
 /// Responses from the protocol analyzer
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MidiAnalysis {
     /// Lowest level of
     Comment(String),
@@ -142,3 +174,53 @@ pub struct MidiParser {
     channel: u8,
     sysex: Vec<u8>,
 }
+
+/// Encodes messages for a byte stream, leaving out the status byte of channel messages that
+/// repeat the running status, as MIDI senders do to save bandwidth
+#[derive(Debug, Default)]
+pub struct RunningStatusEncoder {
+    running: Option<u8>,
+}
+
+impl MidiAnalysis {
+    /// Returns the name of the severity of the analysis
+    pub fn severity(&self) -> &'static str {
+        match self {
+            MidiAnalysis::Comment(_) => "comment",
+            MidiAnalysis::Info(_) => "info",
+            MidiAnalysis::Warning(_) => "warning",
+            MidiAnalysis::Violation(_) => "violation",
+        }
+    }
+
+    /// Rebuilds an analysis from the name of its severity and its text
+    pub fn from_severity(severity: &str, text: String) -> Option<MidiAnalysis> {
+        match severity {
+            "comment" => Some(MidiAnalysis::Comment(text)),
+            "info" => Some(MidiAnalysis::Info(text)),
+            "warning" => Some(MidiAnalysis::Warning(text)),
+            "violation" => Some(MidiAnalysis::Violation(text)),
+            _ => None,
+        }
+    }
+
+    /// Replaces the text of the analysis, keeping its severity
+    pub fn map_text(self, f: impl FnOnce(String) -> String) -> MidiAnalysis {
+        match self {
+            MidiAnalysis::Comment(s) => MidiAnalysis::Comment(f(s)),
+            MidiAnalysis::Info(s) => MidiAnalysis::Info(f(s)),
+            MidiAnalysis::Warning(s) => MidiAnalysis::Warning(f(s)),
+            MidiAnalysis::Violation(s) => MidiAnalysis::Violation(f(s)),
+        }
+    }
+
+    /// Returns the text of the analysis regardless of its severity
+    pub fn text(&self) -> &str {
+        match self {
+            MidiAnalysis::Comment(s)
+            | MidiAnalysis::Info(s)
+            | MidiAnalysis::Warning(s)
+            | MidiAnalysis::Violation(s) => s,
+        }
+    }
+}
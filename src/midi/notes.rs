@@ -0,0 +1,79 @@
+//! Names of MIDI note numbers in several naming conventions
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+const ENGLISH: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const GERMAN: [&str; 12] = [
+    "C", "Cis", "D", "Dis", "E", "F", "Fis", "G", "Gis", "A", "B", "H",
+];
+const SOLFEGE: [&str; 12] = [
+    "Do", "Do#", "Ré", "Ré#", "Mi", "Fa", "Fa#", "Sol", "Sol#", "La", "La#", "Si",
+];
+
+/// Convention used to name notes. Octaves are numbered the same way in all of them,
+/// with note 60 in octave 4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteNaming {
+    /// Letters with sharps: C, C#, D
+    #[default]
+    English,
+    /// Letters with German accidentals, B being B flat and H being B natural
+    German,
+    /// Fixed do solfège: Do, Ré, Mi
+    Solfege,
+}
+
+impl NoteNaming {
+    /// Returns the name of a note number with its octave, e.g. `C4` for 60
+    pub fn name(self, note: u8) -> String {
+        let names = match self {
+            NoteNaming::English => &ENGLISH,
+            NoteNaming::German => &GERMAN,
+            NoteNaming::Solfege => &SOLFEGE,
+        };
+        format!("{}{}", names[note as usize % 12], note as i32 / 12 - 1)
+    }
+}
+
+impl fmt::Display for NoteNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NoteNaming::English => "english",
+            NoteNaming::German => "german",
+            NoteNaming::Solfege => "solfege",
+        })
+    }
+}
+
+impl FromStr for NoteNaming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "english" => Ok(NoteNaming::English),
+            "german" => Ok(NoteNaming::German),
+            "solfege" => Ok(NoteNaming::Solfege),
+            _ => bail!("Unknown note naming `{}`", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_names() {
+        assert_eq!(NoteNaming::English.name(60), "C4");
+        assert_eq!(NoteNaming::English.name(0), "C-1");
+        assert_eq!(NoteNaming::German.name(70), "B4");
+        assert_eq!(NoteNaming::German.name(71), "H4");
+        assert_eq!(NoteNaming::Solfege.name(64), "Mi4");
+        assert_eq!(NoteNaming::Solfege.name(62), "Ré4");
+    }
+}
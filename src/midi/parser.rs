@@ -36,6 +36,14 @@ impl MidiParser {
         self.status
     }
 
+    /// Returns the channel of the current running status if it is a channel message
+    pub fn get_channel(&self) -> Option<u8> {
+        match self.status {
+            Some(state) if state < 0xF0 => Some(self.channel),
+            _ => None,
+        }
+    }
+
     /// Returns the name of the current running status
     pub fn get_state_name(&mut self) -> String {
         if let Some(state) = self.status {
@@ -564,10 +572,10 @@ mod tests {
     #[test]
     fn note_on() {
         let mut parser = MidiParser::new();
-        assert_eq!(parser.parse_midi(0x95), None);
-        assert_eq!(parser.parse_midi(60), None);
+        assert_eq!(parser.parse_midi(0x95).0, None);
+        assert_eq!(parser.parse_midi(60).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOn {
                 channel: 5,
                 note: 60,
@@ -578,10 +586,10 @@ mod tests {
     #[test]
     fn note_off() {
         let mut parser = MidiParser::new();
-        assert_eq!(parser.parse_midi(0x83), None);
-        assert_eq!(parser.parse_midi(59), None);
+        assert_eq!(parser.parse_midi(0x83).0, None);
+        assert_eq!(parser.parse_midi(59).0, None);
         assert_eq!(
-            parser.parse_midi(66),
+            parser.parse_midi(66).0,
             Some(MidiMessage::NoteOff {
                 channel: 3,
                 note: 59,
@@ -592,28 +600,28 @@ mod tests {
     #[test]
     fn running_status_note_on() {
         let mut parser = MidiParser::new();
-        assert_eq!(parser.parse_midi(0x90), None);
-        assert_eq!(parser.parse_midi(60), None);
+        assert_eq!(parser.parse_midi(0x90).0, None);
+        assert_eq!(parser.parse_midi(60).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOn {
                 channel: 0,
                 note: 60,
                 velocity: 127,
             })
         );
-        assert_eq!(parser.parse_midi(61), None);
+        assert_eq!(parser.parse_midi(61).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOn {
                 channel: 0,
                 note: 61,
                 velocity: 127,
             })
         );
-        assert_eq!(parser.parse_midi(62), None);
+        assert_eq!(parser.parse_midi(62).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOn {
                 channel: 0,
                 note: 62,
@@ -624,28 +632,28 @@ mod tests {
     #[test]
     fn running_status_note_off() {
         let mut parser = MidiParser::new();
-        assert_eq!(parser.parse_midi(0x80), None);
-        assert_eq!(parser.parse_midi(60), None);
+        assert_eq!(parser.parse_midi(0x80).0, None);
+        assert_eq!(parser.parse_midi(60).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOff {
                 channel: 0,
                 note: 60,
                 velocity: 127,
             })
         );
-        assert_eq!(parser.parse_midi(61), None);
+        assert_eq!(parser.parse_midi(61).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOff {
                 channel: 0,
                 note: 61,
                 velocity: 127,
             })
         );
-        assert_eq!(parser.parse_midi(62), None);
+        assert_eq!(parser.parse_midi(62).0, None);
         assert_eq!(
-            parser.parse_midi(127),
+            parser.parse_midi(127).0,
             Some(MidiMessage::NoteOff {
                 channel: 0,
                 note: 62,
@@ -656,11 +664,11 @@ mod tests {
     #[test]
     fn pitch_bend() {
         let mut parser = MidiParser::new();
-        assert_eq!(parser.parse_midi(0xE5), None);
+        assert_eq!(parser.parse_midi(0xE5).0, None);
         for n in 0x02_F0_u16..0x03_0F_u16 {
-            assert_eq!(parser.parse_midi((n as u8) & 0x7F), None);
+            assert_eq!(parser.parse_midi((n as u8) & 0x7F).0, None);
             assert_eq!(
-                parser.parse_midi((n >> 7) as u8),
+                parser.parse_midi((n >> 7) as u8).0,
                 Some(MidiMessage::PitchBend {
                     channel: 5,
                     value: n,
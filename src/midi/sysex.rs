@@ -1,4 +1,9 @@
 use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// Manufacturer IDs assigned by the MIDI Association, generated by
+/// `tools/id_csv_to_json.py`
+const IDS: &str = include_str!("../../data/ids.json");
 
 #[derive(Debug, Deserialize)]
 /// Current MIDI Association membership status of this manufacturer
@@ -43,6 +48,18 @@ pub enum ManufacturerGroup {
     Special,
 }
 
+impl ManufacturerGroup {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ManufacturerGroup::NorthAmerica => "North America",
+            ManufacturerGroup::Europe => "Europe",
+            ManufacturerGroup::Japan => "Japan",
+            ManufacturerGroup::Other => "Other",
+            ManufacturerGroup::Special => "Special",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ManufacturerID {
     pub id: Vec<u8>,
@@ -51,3 +68,180 @@ pub struct ManufacturerID {
     pub status: Option<ManufacturerStatus>,
     pub reserved: bool,
 }
+
+/// Returns the manufacturer IDs of the MIDI Association, read the first time they are needed
+pub fn manufacturers() -> &'static [ManufacturerID] {
+    static MANUFACTURERS: OnceLock<Vec<ManufacturerID>> = OnceLock::new();
+    MANUFACTURERS.get_or_init(|| serde_json::from_str(IDS).unwrap_or_default())
+}
+
+/// Returns the manufacturer whose ID starts `data`, either a single byte or `00` followed by
+/// two more
+pub fn manufacturer(data: &[u8]) -> Option<&'static ManufacturerID> {
+    let id = match data {
+        [0x00, a, b, ..] => &[0x00, *a, *b][..],
+        [0x00, ..] | [] => return None,
+        [id, ..] => &[*id][..],
+    };
+    manufacturers().iter().find(|m| m.id == id)
+}
+
+/// What a device says about itself in an Identity Reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Device ID the reply came from
+    pub device: u8,
+    /// Manufacturer ID, one byte or three
+    pub manufacturer: Vec<u8>,
+    /// Device family code
+    pub family: u16,
+    /// Model within the family
+    pub model: u16,
+    /// Software revision, in a form of the manufacturer's choosing
+    pub version: [u8; 4],
+}
+
+/// Decodes the data of an Identity Reply, `7E device 06 02` followed by the manufacturer ID,
+/// the family and model as two 14 bit numbers least significant byte first, and four bytes
+/// of software revision
+pub fn identity(data: &[u8]) -> Option<Identity> {
+    let [0x7E, device, 0x06, 0x02, rest @ ..] = data else {
+        return None;
+    };
+    let id_len = if rest.first() == Some(&0x00) { 3 } else { 1 };
+    let rest = rest.get(id_len..)?;
+    let [f0, f1, m0, m1, v0, v1, v2, v3, ..] = *rest else {
+        return None;
+    };
+    let word = |lsb: u8, msb: u8| (msb as u16) << 7 | lsb as u16;
+    Some(Identity {
+        device: *device,
+        manufacturer: data[4..4 + id_len].to_vec(),
+        family: word(f0, f1),
+        model: word(m0, m1),
+        version: [v0, v1, v2, v3],
+    })
+}
+
+/// Shortest run of text characters reported inside a binary payload
+const MIN_TEXT_RUN: usize = 6;
+
+/// Returns the text carried by the data of a System Exclusive message, such as patch names,
+/// MIDI Show Control cue numbers, or file names, quoted and separated by commas.
+/// Runs of text are picked out of binary payloads, and payloads too short for that but
+/// mostly made of text are shown whole with the other bytes escaped as `\xNN`
+pub fn text(data: &[u8]) -> Option<String> {
+    let payload = &data[payload_start(data).min(data.len())..];
+    let runs: Vec<String> = payload
+        .split(|b| !is_text(*b))
+        .map(|run| run.trim_ascii())
+        .filter(|run| run.len() >= MIN_TEXT_RUN && run.iter().any(u8::is_ascii_alphabetic))
+        .map(quote)
+        .collect();
+    if !runs.is_empty() {
+        return Some(runs.join(", "));
+    }
+    let printable = payload.iter().filter(|b| is_printable(**b)).count();
+    let mostly_text = printable * 4 >= payload.len() * 3;
+    (mostly_text && payload.iter().any(u8::is_ascii_alphanumeric)).then(|| quote(payload))
+}
+
+/// Returns where the payload starts after the manufacturer ID or the Universal header
+fn payload_start(data: &[u8]) -> usize {
+    match data {
+        // MIDI Show Control: ID, device, sub-ID, command format, and command
+        [0x7F, _, 0x02, ..] => 5,
+        // Universal messages: ID, device, and two sub-IDs
+        [0x7E | 0x7F, ..] => 4,
+        // Extended manufacturer ID
+        [0x00, ..] => 3,
+        _ => 1,
+    }
+}
+
+fn is_printable(byte: u8) -> bool {
+    (0x20..=0x7E).contains(&byte)
+}
+
+/// Characters expected in names, as opposed to any byte that happens to be printable
+fn is_text(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b" .,-_/()'&!#+:".contains(&byte)
+}
+
+/// Quotes the bytes as text, escaping the ones that are not printable
+fn quote(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                text.push('\\');
+                text.push(*byte as char);
+            }
+            _ if is_printable(*byte) => text.push(*byte as char),
+            _ => text.push_str(&format!("\\x{:02X}", byte)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_payloads() {
+        // MIDI Show Control GO of cue 1.5 in list 2
+        let go = [0x7F, 0x01, 0x02, 0x01, 0x01, b'1', b'.', b'5', 0x00, b'2'];
+        assert_eq!(text(&go).as_deref(), Some(r#""1.5\x002""#));
+
+        // A patch dump with its name among the parameters
+        let mut dump = vec![0x41, 0x10, 0x42, 0x12, 0x05, 0x00];
+        dump.extend(b"Grand Piano  ");
+        dump.extend([0x00, 0x64, 0x7F, 0x01, 0x40, 0x02, 0x00, 0x7F, 0x7F, 0x03]);
+        assert_eq!(text(&dump).as_deref(), Some(r#""Grand Piano""#));
+
+        // Identity Reply
+        let reply = [
+            0x7E, 0x10, 0x06, 0x02, 0x41, 0x10, 0x42, 0x00, 0x00, 0x01, 0x00,
+        ];
+        assert_eq!(text(&reply), None);
+        assert_eq!(text(&[]), None);
+    }
+
+    #[test]
+    fn manufacturer_ids() {
+        assert!(manufacturers().len() > 500);
+        let roland = manufacturer(&[0x41, 0x10]).unwrap();
+        assert_eq!(roland.manufacturer, "Roland Corporation");
+        assert_eq!(roland.group.name(), "Japan");
+        assert!(manufacturer(&[0x00, 0x20, 0x29]).is_some());
+        assert!(manufacturer(&[0x00, 0x20]).is_none());
+    }
+
+    #[test]
+    fn identity_reply() {
+        let reply = [
+            0x7E, 0x10, 0x06, 0x02, 0x41, 0x42, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00,
+        ];
+        assert_eq!(
+            identity(&reply),
+            Some(Identity {
+                device: 0x10,
+                manufacturer: vec![0x41],
+                family: 0xC2,
+                model: 0x100,
+                version: [0x00, 0x01, 0x00, 0x00],
+            })
+        );
+        let extended = [
+            0x7E, 0x7F, 0x06, 0x02, 0x00, 0x20, 0x29, 0x01, 0x02, 0x03, 0x04, 1, 2, 3, 4,
+        ];
+        let novation = identity(&extended).unwrap();
+        assert_eq!(novation.manufacturer, [0x00, 0x20, 0x29]);
+        assert_eq!((novation.family, novation.version), (0x101, [1, 2, 3, 4]));
+        assert_eq!(identity(&reply[..12]), None);
+        // The request itself
+        assert_eq!(identity(&[0x7E, 0x7F, 0x06, 0x01]), None);
+    }
+}
@@ -175,3 +175,54 @@ impl MidiMessage {
         }
     }
 }
+
+impl RunningStatusEncoder {
+    /// Creates an encoder without a running status
+    pub fn new() -> RunningStatusEncoder {
+        RunningStatusEncoder::default()
+    }
+
+    /// Returns the bytes of the message, without its status if it is the running status.
+    /// System Real Time messages leave the running status alone, other system messages
+    /// cancel it
+    pub fn encode(&mut self, message: MidiMessage) -> Vec<u8> {
+        let mut bytes = message.to_bytes();
+        match bytes[0] {
+            status if status >= MIDI_SYSRT_TIMING_CLOCK => {}
+            status if status >= MIDI_SYSEX_SOX => self.running = None,
+            status if self.running == Some(status) => {
+                bytes.remove(0);
+            }
+            status => self.running = Some(status),
+        }
+        bytes
+    }
+
+    /// Forgets the running status, after bytes that cancel it were sent around the encoder
+    pub fn reset(&mut self) {
+        self.running = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_status() {
+        let mut encoder = RunningStatusEncoder::new();
+        let note_on = |note| MidiMessage::NoteOn {
+            channel: 0,
+            note,
+            velocity: 100,
+        };
+        assert_eq!(encoder.encode(note_on(60)), [0x90, 60, 100]);
+        assert_eq!(encoder.encode(note_on(62)), [62, 100]);
+        assert_eq!(encoder.encode(MidiMessage::TimingClock), [0xF8]);
+        assert_eq!(encoder.encode(note_on(64)), [64, 100]);
+        assert_eq!(encoder.encode(MidiMessage::TuneRequest), [0xF6]);
+        assert_eq!(encoder.encode(note_on(65)), [0x90, 65, 100]);
+        encoder.reset();
+        assert_eq!(encoder.encode(note_on(67)), [0x90, 67, 100]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Scripting hooks written in Rhai, for device specific decoding and automation
+//!
+//! A script defines `on_message(msg)`, which is called with every complete message as a
+//! map of its `time` in seconds, `source` input, `name`, `status` without the channel,
+//! `channel` from 1 to 16 (`()` for system messages), and `bytes`. It returns what is sent
+//! out the thru port in its place:
+//!
+//! - nothing or `true` to send the message as it is, `false` to drop it
+//! - the map with changed `bytes`, or an array of bytes, to send another message
+//! - an array of maps or byte arrays to send several messages, such as injected ones
+//!
+//! `annotate(text)` adds a line to the log of the message
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{MidiMessage, MidiParser},
+    sink::Sink,
+};
+use anyhow::{anyhow, bail, Context};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST, INT};
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+/// Name of the function called with every message
+const HOOK: &str = "on_message";
+
+/// Most operations a script runs per message, so that a script stuck in a loop fails
+/// instead of holding up the capture
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Deepest a script may nest its function calls, so that runaway recursion fails before
+/// it overflows the stack
+const MAX_CALL_LEVELS: usize = 32;
+
+/// A compiled script, ready to be called with every message
+pub struct Script {
+    /// File name of the script, shown with its annotations
+    name: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// Lines added with `annotate` since they were last taken
+    annotations: Rc<RefCell<Vec<String>>>,
+}
+
+impl Script {
+    /// Compiles the script and runs its top level statements once
+    pub fn load(path: &Path) -> Result<Script, anyhow::Error> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS);
+        let annotations = Rc::new(RefCell::new(vec![]));
+        {
+            let annotations = annotations.clone();
+            engine.register_fn("annotate", move |text: &str| {
+                annotations.borrow_mut().push(text.to_string())
+            });
+        }
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!("{}", e))
+            .context(format!("Unable to load script `{:?}`", path))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == HOOK && f.params.len() == 1)
+        {
+            bail!("Script `{:?}` does not define `{}(msg)`", path, HOOK);
+        }
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow!("{}", e))
+            .context(format!("Unable to run script `{:?}`", path))?;
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        Ok(Script {
+            name,
+            engine,
+            ast,
+            scope,
+            annotations,
+        })
+    }
+
+    /// Calls the hook with the message of the event and returns the messages sent in its
+    /// place. A failing hook is annotated and leaves the message as it is
+    pub fn on_message(&mut self, event: &CaptureEvent, message: &MidiMessage) -> Vec<MidiMessage> {
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut self.scope,
+                &self.ast,
+                HOOK,
+                (to_map(event, message),),
+            )
+            .map_err(|e| anyhow!("{}", e))
+            .and_then(|result| messages(result, message));
+        match result {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.annotations
+                    .borrow_mut()
+                    .push(format!("error: {:#}", e));
+                vec![message.clone()]
+            }
+        }
+    }
+
+    /// Takes the lines added by the script, ready for the log
+    pub fn annotations(&mut self) -> Vec<String> {
+        self.annotations
+            .take()
+            .into_iter()
+            .map(|text| format!("Script {}: {}", self.name, text))
+            .collect()
+    }
+}
+
+/// Returns the message as the map handed to the hook
+fn to_map(event: &CaptureEvent, message: &MidiMessage) -> Map {
+    let bytes: Array = message
+        .clone()
+        .to_bytes()
+        .into_iter()
+        .map(|b| Dynamic::from(b as INT))
+        .collect();
+    let mut map = Map::new();
+    map.insert("time".into(), Dynamic::from(event.time.as_secs_f64()));
+    map.insert("source".into(), Dynamic::from(event.source as INT));
+    map.insert("name".into(), Dynamic::from(message.name()));
+    map.insert("status".into(), Dynamic::from(message.status() as INT));
+    let channel = match message.channel() {
+        Some(channel) => Dynamic::from(channel as INT + 1),
+        None => Dynamic::UNIT,
+    };
+    map.insert("channel".into(), channel);
+    map.insert("bytes".into(), Dynamic::from(bytes));
+    map
+}
+
+/// Returns the messages a hook returned in place of `message`
+fn messages(result: Dynamic, message: &MidiMessage) -> Result<Vec<MidiMessage>, anyhow::Error> {
+    if result.is_unit() {
+        return Ok(vec![message.clone()]);
+    }
+    if let Some(send) = result.clone().try_cast::<bool>() {
+        return Ok(if send { vec![message.clone()] } else { vec![] });
+    }
+    if result.is_map() {
+        return Ok(vec![parse(result)?]);
+    }
+    let Some(array) = result.try_cast::<Array>() else {
+        bail!("`{}` returned neither a message nor an array of them", HOOK);
+    };
+    // An array of numbers is the bytes of one message
+    if array.iter().all(|item| item.is_int()) {
+        return Ok(vec![parse(Dynamic::from(array))?]);
+    }
+    array.into_iter().map(parse).collect()
+}
+
+/// Parses a map with `bytes`, or an array of bytes, into a message
+fn parse(value: Dynamic) -> Result<MidiMessage, anyhow::Error> {
+    let bytes = match value.clone().try_cast::<Map>() {
+        Some(mut map) => map.remove("bytes").unwrap_or_default(),
+        None => value,
+    };
+    let Some(bytes) = bytes.try_cast::<Array>() else {
+        bail!("`{}` returned a message without bytes", HOOK);
+    };
+    let mut parser = MidiParser::new();
+    let mut parsed = vec![];
+    let mut complete = false;
+    for byte in &bytes {
+        let byte = byte
+            .as_int()
+            .ok()
+            .and_then(|b| u8::try_from(b).ok())
+            .context(format!("`{}` is not a byte", byte))?;
+        let (message, _) = parser.parse_midi(byte);
+        complete = message.is_some();
+        parsed.extend(message);
+    }
+    match parsed.pop() {
+        Some(message) if complete && parsed.is_empty() => Ok(message),
+        _ => bail!("`{:?}` is not one complete message", bytes),
+    }
+}
+
+/// Runs the script on the capture when there is no thru port, only for its annotations
+impl Sink for Script {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if let Some(message) = &event.message {
+            self.on_message(event, message);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    fn notices(&mut self) -> Vec<String> {
+        self.annotations()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::{env, fs, time::Duration};
+
+    #[test]
+    fn hooks() {
+        let path = env::temp_dir().join(format!("miditerm-script-{}.rhai", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            fn on_message(msg) {
+                if msg.name == "Timing Clock" { return false; }
+                if msg.status == 0x90 && msg.channel == 1 {
+                    annotate("note " + msg.bytes[1]);
+                    msg.bytes[0] = 0x91;
+                    return [msg, [0xB1, 64, 127]];
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let mut script = Script::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut capture = Capture::new();
+        let mut sent = vec![];
+        for byte in [0xF8, 0x90, 60, 100, 0xC0, 5] {
+            let event = capture.process(Duration::ZERO, byte);
+            if let Some(message) = &event.message {
+                sent.extend(script.on_message(&event, message));
+            }
+        }
+        assert_eq!(
+            sent,
+            [
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    control: 64,
+                    value: 127
+                },
+                MidiMessage::ProgramChange {
+                    channel: 0,
+                    program: 5
+                },
+            ]
+        );
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert_eq!(script.annotations(), [format!("Script {}: note 60", name)]);
+    }
+
+    #[test]
+    fn runaway_hooks_fail() {
+        let path = env::temp_dir().join(format!("miditerm-runaway-{}.rhai", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            fn deeper(n) { deeper(n + 1) }
+            fn on_message(msg) {
+                if msg.name == "Timing Clock" { loop {} }
+                deeper(0)
+            }
+            "#,
+        )
+        .unwrap();
+        let mut script = Script::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut capture = Capture::new();
+        for byte in [0xF8, 0xFA] {
+            let event = capture.process(Duration::ZERO, byte);
+            let message = event.message.clone().unwrap();
+            assert_eq!(script.on_message(&event, &message), [message]);
+        }
+        let annotations = script.annotations();
+        assert_eq!(annotations.len(), 2);
+        assert!(
+            annotations.iter().all(|a| a.contains("error:")),
+            "{:?}",
+            annotations
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Byte array literals of the capture
+
+use crate::{
+    capture::CaptureEvent,
+    export::array::{self, Language},
+    sink::Sink,
+};
+use anyhow::Context;
+use std::{fs, path::PathBuf};
+
+/// Collects the capture and writes it as a C or Rust byte array named after the file when
+/// finished
+pub struct ArrayExporter {
+    path: PathBuf,
+    language: Language,
+    events: Vec<CaptureEvent>,
+}
+
+impl ArrayExporter {
+    /// Creates an exporter that writes to `path` when finished
+    pub fn new(path: PathBuf, language: Language) -> ArrayExporter {
+        ArrayExporter {
+            path,
+            language,
+            events: vec![],
+        }
+    }
+}
+
+impl Sink for ArrayExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        let name = self
+            .path
+            .file_stem()
+            .map_or("capture".into(), |stem| stem.to_string_lossy());
+        let text = array::array(&self.events, self.language, &name);
+        fs::write(&self.path, text).context(format!("Unable to write `{:?}`", self.path))
+    }
+}
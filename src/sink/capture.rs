@@ -0,0 +1,37 @@
+//! Records the raw bytes of the capture with their timestamps
+
+use crate::{
+    capture::{format::CaptureWriter, CaptureEvent},
+    sink::Sink,
+};
+use anyhow::Context;
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// Writes every received byte to a capture file that can be replayed later
+pub struct CaptureRecorder {
+    writer: CaptureWriter<BufWriter<File>>,
+}
+
+impl CaptureRecorder {
+    /// Creates the capture file, replacing any existing file
+    pub fn create(path: &Path) -> Result<CaptureRecorder, anyhow::Error> {
+        let file = File::create(path).context(format!("Unable to create `{:?}`", path))?;
+        Ok(CaptureRecorder {
+            writer: CaptureWriter::new(BufWriter::new(file))?,
+        })
+    }
+}
+
+impl Sink for CaptureRecorder {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        // Capture files hold received bytes only
+        for &byte in event.received() {
+            self.writer.write(event.time, byte)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()
+    }
+}
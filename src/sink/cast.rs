@@ -0,0 +1,50 @@
+//! Renders the capture as a recording of the TUI when it ends
+
+use crate::{analysis::Settings, capture::CaptureEvent, config::Config, sink::Sink, ui};
+use anyhow::Context;
+use std::{fs, path::PathBuf};
+
+/// Collects events and writes the TUI replaying them as an asciicast when finished
+pub struct CastExporter {
+    path: PathBuf,
+    config: Config,
+    settings: Settings,
+    /// Columns and rows of the recorded screen
+    size: (u16, u16),
+    /// Frames per second of the capture
+    fps: f64,
+    events: Vec<CaptureEvent>,
+}
+
+impl CastExporter {
+    /// Creates an exporter that writes to `path` when finished
+    pub fn new(
+        path: PathBuf,
+        config: Config,
+        settings: Settings,
+        size: (u16, u16),
+        fps: f64,
+    ) -> CastExporter {
+        CastExporter {
+            path,
+            config,
+            settings,
+            size,
+            fps,
+            events: vec![],
+        }
+    }
+}
+
+impl Sink for CastExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        let events = std::mem::take(&mut self.events);
+        let cast = ui::cast(&self.config, self.settings, events, self.size, self.fps)?;
+        fs::write(&self.path, cast).context(format!("Unable to write `{:?}`", self.path))
+    }
+}
@@ -0,0 +1,66 @@
+//! CSV logs of the capture
+
+use crate::{capture::CaptureEvent, export::csv, sink::Sink, smf::TrackBuilder};
+use anyhow::Context;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Writes one timestamped CSV row per message, and per byte that raised an issue
+pub struct CsvLogger {
+    writer: BufWriter<File>,
+}
+
+impl CsvLogger {
+    /// Creates the log file and writes the header row
+    pub fn create(path: &Path) -> Result<CsvLogger, anyhow::Error> {
+        let file = File::create(path).context(format!("Unable to create `{:?}`", path))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", csv::PLAIN_HEADER)?;
+        Ok(CsvLogger { writer })
+    }
+}
+
+impl Sink for CsvLogger {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if let Some(row) = csv::plain_row(event) {
+            writeln!(self.writer, "{}", row)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts the capture to a track and writes it in the midicsv format when finished
+pub struct MidicsvExporter {
+    path: PathBuf,
+    builder: TrackBuilder,
+}
+
+impl MidicsvExporter {
+    /// Creates an exporter that writes to `path` when finished
+    pub fn new(path: PathBuf, ppq: u16, bpm: f64) -> MidicsvExporter {
+        MidicsvExporter {
+            path,
+            builder: TrackBuilder::new(ppq, bpm),
+        }
+    }
+}
+
+impl Sink for MidicsvExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.builder.push(event);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        fs::write(&self.path, csv::midicsv(self.builder.track()))
+            .context(format!("Unable to write `{:?}`", self.path))
+    }
+}
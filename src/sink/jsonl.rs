@@ -0,0 +1,124 @@
+//! Structured JSON Lines log of the capture
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{MidiAnalysis, MidiMessage},
+    sink::Sink,
+};
+use anyhow::Context;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// One line of the log
+#[derive(Debug, Serialize)]
+pub struct LogRecord<'a> {
+    /// Seconds since the start of the capture
+    pub time: f64,
+    /// Bytes of the message as received
+    pub bytes: &'a [u8],
+    /// Decoded message, if the bytes completed one
+    pub message: Option<&'a MidiMessage>,
+    /// Severity of the analysis
+    pub severity: &'static str,
+    /// Text of the analysis
+    pub analysis: &'a str,
+}
+
+impl<'a> LogRecord<'a> {
+    /// Builds the log record of an event.
+    ///
+    /// Returns `None` for events that neither complete a message, raise an issue, nor tell
+    /// what happened to the source
+    pub fn from_event(event: &'a CaptureEvent) -> Option<LogRecord<'a>> {
+        let bytes = match (&event.message, &event.analysis) {
+            (Some(_), _) => event.raw.as_slice(),
+            (None, MidiAnalysis::Warning(_)) | (None, MidiAnalysis::Violation(_)) => {
+                event.received()
+            }
+            (None, _) if event.notice => &[],
+            (None, _) => return None,
+        };
+        Some(LogRecord {
+            time: event.time.as_secs_f64(),
+            bytes,
+            message: event.message.as_ref(),
+            severity: event.analysis.severity(),
+            analysis: event.analysis.text(),
+        })
+    }
+}
+
+/// Writes one JSON object per message, and per byte that raised a warning or violation
+pub struct JsonlLogger {
+    writer: BufWriter<File>,
+}
+
+impl JsonlLogger {
+    /// Creates the log file, replacing any existing file
+    pub fn create(path: &Path) -> Result<JsonlLogger, anyhow::Error> {
+        let file = File::create(path).context(format!("Unable to create `{:?}`", path))?;
+        Ok(JsonlLogger {
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+impl Sink for JsonlLogger {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if let Some(record) = LogRecord::from_event(event) {
+            serde_json::to_writer(&mut self.writer, &record)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::{env, fs, time::Duration};
+
+    #[test]
+    fn logs_messages_and_issues() {
+        let path = env::temp_dir().join(format!("miditerm-log-{}.jsonl", std::process::id()));
+        let mut logger = JsonlLogger::create(&path).unwrap();
+        let mut capture = Capture::new();
+        for (i, byte) in [0x40, 0x90, 60, 100, 0xF8].into_iter().enumerate() {
+            let event = capture.process(Duration::from_millis(500 * i as u64), byte);
+            logger.write(&event).unwrap();
+        }
+        let lost = MidiAnalysis::Warning("Port `/dev/ttyUSB0` disconnected".to_string());
+        logger
+            .write(&CaptureEvent::notice(0, Duration::from_secs(3), lost))
+            .unwrap();
+        logger.finish().unwrap();
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        // The bytes of the Note On are logged once it is complete
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["severity"], "warning");
+        assert_eq!(lines[0]["bytes"], serde_json::json!([0x40]));
+        assert!(lines[0]["message"].is_null());
+        assert_eq!(lines[1]["time"], 1.5);
+        assert_eq!(lines[1]["bytes"], serde_json::json!([0x90, 60, 100]));
+        assert_eq!(lines[1]["message"]["NoteOn"]["note"], 60);
+        assert_eq!(lines[2]["message"], "TimingClock");
+        assert_eq!(lines[3]["bytes"], serde_json::json!([]));
+        assert_eq!(lines[3]["analysis"], "Port `/dev/ttyUSB0` disconnected");
+    }
+}
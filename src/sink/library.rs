@@ -0,0 +1,60 @@
+//! Catalogs every received SysEx dump in a library
+
+use crate::{
+    capture::CaptureEvent,
+    library::{Added, Library},
+    midi::MidiMessage,
+    sink::Sink,
+};
+use std::{path::Path, time::SystemTime};
+
+/// Adds each captured SysEx message to a library, telling which are new
+pub struct Librarian {
+    library: Library,
+    notices: Vec<String>,
+}
+
+impl Librarian {
+    /// Opens the library in `dir`, creating it if it does not exist
+    pub fn open(dir: &Path) -> Result<Librarian, anyhow::Error> {
+        Ok(Librarian {
+            library: Library::open(dir)?,
+            notices: vec![],
+        })
+    }
+}
+
+impl Sink for Librarian {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        // Universal Real Time messages such as MTC and MMC are not dumps
+        let Some(MidiMessage::SystemExclusive(data)) = &event.message else {
+            return Ok(());
+        };
+        if data.first() == Some(&0x7F) {
+            return Ok(());
+        }
+        let notice = match self.library.add(data, SystemTime::now())? {
+            Added::New(number) => {
+                let dump = self.library.find(&number.to_string())?;
+                format!(
+                    "Library: added dump {} `{}`, {} bytes",
+                    number, dump.name, dump.size
+                )
+            }
+            Added::Known(number) => {
+                let dump = self.library.find(&number.to_string())?;
+                format!("Library: same as dump {} `{}`", number, dump.name)
+            }
+        };
+        self.notices.push(notice);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    fn notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
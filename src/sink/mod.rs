@@ -0,0 +1,77 @@
+//! Outputs that consume the analyzed capture as it is received
+
+mod array;
+mod capture;
+mod cast;
+mod csv;
+mod jsonl;
+mod library;
+mod raw;
+mod route;
+mod rules;
+mod smf;
+mod store;
+mod svg;
+mod syx;
+mod thru;
+pub mod trigger;
+mod ump;
+
+pub use self::array::ArrayExporter;
+pub use self::capture::CaptureRecorder;
+pub use self::cast::CastExporter;
+pub use self::csv::{CsvLogger, MidicsvExporter};
+pub use self::jsonl::{JsonlLogger, LogRecord};
+pub use self::library::Librarian;
+pub use self::raw::RawTee;
+pub use self::route::{Router, Zone};
+pub use self::rules::Rule;
+pub use self::smf::SmfRecorder;
+pub use self::store::StoreSink;
+pub use self::svg::{CcLaneExporter, PianoRollExporter};
+pub use self::syx::SyxExporter;
+pub use self::thru::Thru;
+pub use self::trigger::Triggers;
+pub use self::ump::UmpWriter;
+
+use crate::capture::CaptureEvent;
+use anyhow::bail;
+use std::str::FromStr;
+
+/// Consumer of live capture events
+pub trait Sink {
+    /// Handles the next event of the capture
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error>;
+
+    /// Flushes any buffered output. Called once when the capture ends
+    fn finish(&mut self) -> Result<(), anyhow::Error>;
+
+    /// Takes the descriptions of what the sink did to the events written since the last call
+    fn notices(&mut self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Formats of the structured log written with `--log-file`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// One CSV row per message with timestamps
+    Csv,
+    /// Standard MIDI File events in the text format of `midicsv`
+    Midicsv,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(LogFormat::Jsonl),
+            "csv" => Ok(LogFormat::Csv),
+            "midicsv" => Ok(LogFormat::Midicsv),
+            _ => bail!("Unknown log format `{}`", s),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Copies the raw bytes of the capture to a file
+
+use crate::{capture::CaptureEvent, sink::Sink};
+use anyhow::Context;
+use std::{fs::File, io::Write, path::Path};
+
+/// Writes every received byte to a file as it arrives, like `tee`. The file is not buffered,
+/// so the bytes received so far survive a crash of the analysis
+pub struct RawTee {
+    file: File,
+}
+
+impl RawTee {
+    /// Creates the file, replacing any existing file
+    pub fn create(path: &Path) -> Result<RawTee, anyhow::Error> {
+        let file = File::create(path).context(format!("Unable to create `{:?}`", path))?;
+        Ok(RawTee { file })
+    }
+}
+
+impl Sink for RawTee {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.file.write_all(event.received())?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::{env, fs, time::Duration};
+
+    #[test]
+    fn tees_bytes() {
+        let path = env::temp_dir().join(format!("miditerm-tee-{}.mid", std::process::id()));
+        let mut tee = RawTee::create(&path).unwrap();
+        let mut capture = Capture::new();
+        // Bytes that complete no message are copied too
+        let bytes = [0x40, 0x90, 60, 0xF8, 100];
+        for (i, byte) in bytes.into_iter().enumerate() {
+            tee.write(&capture.process(Duration::from_millis(i as u64), byte))
+                .unwrap();
+            // Each byte is in the file as soon as it is written
+            assert_eq!(fs::read(&path).unwrap(), bytes[..=i]);
+        }
+        tee.finish().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,461 @@
+//! Forwards received messages to a serial port, translating them on the way
+
+use crate::{capture::CaptureEvent, midi, midi::MidiMessage, sink::Sink, source::port};
+use anyhow::{bail, Context};
+use serialport::SerialPort;
+use std::{ffi::OsStr, str::FromStr};
+
+/// A ready-made change applied to routed messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Translation {
+    /// Channel Pressure sent as this controller
+    PressureToCc(u8),
+    /// Velocity of every sounding Note On replaced by this value
+    FixedVelocity(u8),
+    /// Pitch Bend sent as this controller, keeping the upper 7 bits of the bend
+    BendToCc(u8),
+    /// Values of this controller turned upside down, fixing pedals and switches of the
+    /// opposite polarity
+    InvertCc(u8),
+}
+
+impl Translation {
+    /// Returns the message with the translation applied
+    pub fn apply(self, message: MidiMessage) -> MidiMessage {
+        match (self, message) {
+            (
+                Translation::PressureToCc(control),
+                MidiMessage::ChannelPressure { channel, pressure },
+            ) => MidiMessage::ControlChange {
+                channel,
+                control,
+                value: pressure,
+            },
+            // Velocity 0 is a Note Off and stays one
+            (
+                Translation::FixedVelocity(fixed),
+                MidiMessage::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                },
+            ) if velocity > 0 => MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: fixed,
+            },
+            (Translation::BendToCc(control), MidiMessage::PitchBend { channel, value }) => {
+                MidiMessage::ControlChange {
+                    channel,
+                    control,
+                    value: (value >> 7) as u8,
+                }
+            }
+            (
+                Translation::InvertCc(inverted),
+                MidiMessage::ControlChange {
+                    channel,
+                    control,
+                    value,
+                },
+            ) if control == inverted => MidiMessage::ControlChange {
+                channel,
+                control,
+                value: 127 - value,
+            },
+            (_, message) => message,
+        }
+    }
+}
+
+impl FromStr for Translation {
+    type Err = anyhow::Error;
+
+    /// Parses `pressure-to-cc[=CONTROL]`, `velocity=VALUE`, `bend-to-cc=CONTROL`, or
+    /// `invert-cc[=CONTROL]`. Channel Pressure goes to the Modulation Wheel (CC 1) and the
+    /// Sustain Pedal (CC 64) is inverted unless a controller is given
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let value = |default: Option<u8>| -> Result<u8, anyhow::Error> {
+            match value {
+                Some(value) => value
+                    .parse()
+                    .ok()
+                    .filter(|v| *v < 128)
+                    .context(format!("`{}` is not a value from 0 to 127", value)),
+                None => default.context(format!("`{}` needs a value, e.g. `{}=1`", name, name)),
+            }
+        };
+        match name {
+            "pressure-to-cc" => Ok(Translation::PressureToCc(value(Some(1))?)),
+            "velocity" => Ok(Translation::FixedVelocity(value(None)?)),
+            "bend-to-cc" => Ok(Translation::BendToCc(value(None)?)),
+            "invert-cc" => Ok(Translation::InvertCc(value(Some(64))?)),
+            _ => bail!(
+                "Unknown translation `{}`, use pressure-to-cc, velocity, bend-to-cc, or invert-cc",
+                name
+            ),
+        }
+    }
+}
+
+/// A range of notes sent on a channel of its own, optionally transposed,
+/// for splitting a keyboard across channels or ports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zone {
+    /// Lowest and highest notes of the zone, inclusive
+    pub low: u8,
+    pub high: u8,
+    /// Channel from 0 to 15 the notes of the zone are sent on
+    pub channel: u8,
+    /// Semitones added to the notes of the zone
+    pub transpose: i8,
+}
+
+impl Zone {
+    /// Returns the note message moved into the zone, or `None` if its note is outside the
+    /// zone or is transposed out of the MIDI range
+    fn apply(&self, message: &MidiMessage) -> Option<MidiMessage> {
+        let note = match message {
+            MidiMessage::NoteOff { note, .. }
+            | MidiMessage::NoteOn { note, .. }
+            | MidiMessage::PolyPressure { note, .. } => *note,
+            _ => return None,
+        };
+        if !(self.low..=self.high).contains(&note) {
+            return None;
+        }
+        let moved = note
+            .checked_add_signed(self.transpose)
+            .filter(|n| *n < 128)?;
+        let channel = self.channel;
+        Some(match message.clone() {
+            MidiMessage::NoteOff { velocity, .. } => MidiMessage::NoteOff {
+                channel,
+                note: moved,
+                velocity,
+            },
+            MidiMessage::NoteOn { velocity, .. } => MidiMessage::NoteOn {
+                channel,
+                note: moved,
+                velocity,
+            },
+            MidiMessage::PolyPressure { pressure, .. } => MidiMessage::PolyPressure {
+                channel,
+                note: moved,
+                pressure,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Zone {
+    type Err = anyhow::Error;
+
+    /// Parses `LOW..HIGH:CHANNEL[:TRANSPOSE]` such as `C-1..B3:2:+12`, with notes given as
+    /// numbers or English names where C4 is middle C, and channels numbered from 1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let range = parts.next().unwrap_or_default();
+        let (low, high) = range
+            .split_once("..")
+            .context(format!("`{}` is not a range of notes like `C4..G9`", range))?;
+        let channel = parts
+            .next()
+            .context("Zones need a channel, e.g. `C4..G9:1`")?;
+        let transpose = parts.next().unwrap_or("0");
+        let zone = Zone {
+            low: parse_note(low)?,
+            high: parse_note(high)?,
+            channel: channel
+                .parse::<u8>()
+                .ok()
+                .filter(|ch| (1..=16).contains(ch))
+                .context(format!("`{}` is not a channel from 1 to 16", channel))?
+                - 1,
+            transpose: transpose
+                .parse()
+                .context(format!("`{}` is not a transposition", transpose))?,
+        };
+        if zone.low > zone.high {
+            bail!("Zone `{}` ends below where it starts", s);
+        }
+        Ok(zone)
+    }
+}
+
+/// Parses a note number, or an English note name such as `C4`, `F#3`, or `Bb-1`
+fn parse_note(text: &str) -> Result<u8, anyhow::Error> {
+    if let Ok(note) = text.parse::<u8>() {
+        if note < 128 {
+            return Ok(note);
+        }
+    }
+    let invalid = || anyhow::anyhow!("`{}` is not a note from 0 to 127 or from C-1 to G9", text);
+    let mut chars = text.chars();
+    let pitch = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => return Err(invalid()),
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.strip_prefix('#') {
+        Some(octave) => (1, octave),
+        None => match rest.strip_prefix('b') {
+            Some(octave) => (-1, octave),
+            None => (0, rest),
+        },
+    };
+    let octave: i32 = octave.parse().map_err(|_| invalid())?;
+    let note = (octave + 1) * 12 + pitch + accidental;
+    u8::try_from(note)
+        .ok()
+        .filter(|n| *n < 128)
+        .ok_or_else(invalid)
+}
+
+/// Sends every complete message out a serial port after applying its translations,
+/// describing each message it changes. A route with zones only sends the notes inside them
+pub struct Router {
+    /// Name of the port, or `-` for a route that only describes its changes
+    name: String,
+    port: Option<Box<dyn SerialPort>>,
+    translations: Vec<Translation>,
+    zones: Vec<Zone>,
+    notices: Vec<String>,
+}
+
+impl Router {
+    /// Opens a route described as `PORT[,TRANSLATION..][,zone=ZONE..]`, such as
+    /// `/dev/ttyUSB1,pressure-to-cc=1,velocity=100,zone=C4..G9:1`. A port of `-` sends nothing
+    pub fn open(spec: &str) -> Result<Router, anyhow::Error> {
+        let mut parts = spec.split(',');
+        let name = parts.next().unwrap_or_default().to_string();
+        let mut translations = vec![];
+        let mut zones = vec![];
+        for part in parts {
+            let parsed = match part.strip_prefix("zone=") {
+                Some(zone) => zone.parse().map(|zone| zones.push(zone)),
+                None => part
+                    .parse()
+                    .map(|translation| translations.push(translation)),
+            };
+            parsed.context(format!("Invalid route `{}`", spec))?;
+        }
+        let port = match name.as_str() {
+            "" => bail!("Route `{}` has no port", spec),
+            "-" => None,
+            _ => Some(
+                port::open(OsStr::new(&name), midi::MIDI_BAUD_RATE)
+                    .context(format!("Unable to open serial port `{}`", name))?,
+            ),
+        };
+        Ok(Router {
+            name,
+            port,
+            translations,
+            zones,
+            notices: vec![],
+        })
+    }
+
+    /// Returns the zones of the route, in the order they were given
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+}
+
+impl Sink for Router {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        let Some(message) = &event.message else {
+            return Ok(());
+        };
+        let mut translated = self
+            .translations
+            .iter()
+            .fold(message.clone(), |message, t| t.apply(message));
+        let is_note = matches!(
+            translated,
+            MidiMessage::NoteOff { .. }
+                | MidiMessage::NoteOn { .. }
+                | MidiMessage::PolyPressure { .. }
+        );
+        if is_note && !self.zones.is_empty() {
+            match self.zones.iter().find_map(|zone| zone.apply(&translated)) {
+                Some(zoned) => translated = zoned,
+                None => {
+                    self.notices.push(format!(
+                        "Route {}: {} {} not sent, outside its zones",
+                        self.name,
+                        message.name(),
+                        midi::hex(&message.clone().to_bytes())
+                    ));
+                    return Ok(());
+                }
+            }
+        }
+        if &translated != message {
+            self.notices.push(format!(
+                "Route {}: {} {} became {} {}",
+                self.name,
+                message.name(),
+                midi::hex(&message.clone().to_bytes()),
+                translated.name(),
+                midi::hex(&translated.clone().to_bytes())
+            ));
+        }
+        if let Some(port) = &mut self.port {
+            port.write_all(&translated.to_bytes())
+                .context(format!("Unable to write to `{}`", self.name))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        if let Some(port) = &mut self.port {
+            port.flush()?;
+        }
+        Ok(())
+    }
+
+    fn notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations() {
+        let pressure = MidiMessage::ChannelPressure {
+            channel: 2,
+            pressure: 64,
+        };
+        let to_cc: Translation = "pressure-to-cc".parse().unwrap();
+        assert_eq!(
+            to_cc.apply(pressure.clone()),
+            MidiMessage::ControlChange {
+                channel: 2,
+                control: 1,
+                value: 64
+            }
+        );
+
+        let velocity: Translation = "velocity=100".parse().unwrap();
+        let note_on = |velocity| MidiMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity,
+        };
+        assert_eq!(velocity.apply(note_on(20)), note_on(100));
+        assert_eq!(velocity.apply(note_on(0)), note_on(0));
+        assert_eq!(velocity.apply(pressure.clone()), pressure);
+
+        let bend: Translation = "bend-to-cc=74".parse().unwrap();
+        assert_eq!(
+            bend.apply(MidiMessage::PitchBend {
+                channel: 0,
+                value: 8192
+            }),
+            MidiMessage::ControlChange {
+                channel: 0,
+                control: 74,
+                value: 64
+            }
+        );
+
+        let invert: Translation = "invert-cc".parse().unwrap();
+        let sustain = |control, value| MidiMessage::ControlChange {
+            channel: 0,
+            control,
+            value,
+        };
+        assert_eq!(invert.apply(sustain(64, 0)), sustain(64, 127));
+        assert_eq!(invert.apply(sustain(64, 100)), sustain(64, 27));
+        assert_eq!(invert.apply(sustain(1, 0)), sustain(1, 0));
+
+        assert!("velocity".parse::<Translation>().is_err());
+        assert!("velocity=128".parse::<Translation>().is_err());
+        assert!("transpose=12".parse::<Translation>().is_err());
+    }
+
+    #[test]
+    fn zones() {
+        let lower: Zone = "C-1..B3:2:+12".parse().unwrap();
+        assert_eq!(
+            lower,
+            Zone {
+                low: 0,
+                high: 59,
+                channel: 1,
+                transpose: 12
+            }
+        );
+        let upper: Zone = "60..127:1".parse().unwrap();
+        assert_eq!((upper.low, upper.high, upper.transpose), (60, 127, 0));
+        assert_eq!(parse_note("F#3").unwrap(), 54);
+        assert_eq!(parse_note("Bb-1").unwrap(), 10);
+        assert!(parse_note("H4").is_err());
+        assert!("C4..C3:1".parse::<Zone>().is_err());
+        assert!("C4..G9".parse::<Zone>().is_err());
+        assert!("C4..G9:17".parse::<Zone>().is_err());
+
+        let note_on = |channel, note| MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity: 100,
+        };
+        assert_eq!(lower.apply(&note_on(0, 48)), Some(note_on(1, 60)));
+        assert_eq!(lower.apply(&note_on(0, 60)), None);
+        assert_eq!(upper.apply(&note_on(3, 60)), Some(note_on(0, 60)));
+        let high: Zone = "G9..G9:1:+1".parse().unwrap();
+        assert_eq!(high.apply(&note_on(0, 127)), None);
+    }
+
+    #[test]
+    fn describe_changes() {
+        let mut router = Router::open("-,pressure-to-cc=11").unwrap();
+        let mut capture = crate::capture::Capture::new();
+        for byte in [0xD0, 0x40, 0xF8] {
+            router
+                .write(&capture.process(std::time::Duration::ZERO, byte))
+                .unwrap();
+        }
+        assert_eq!(
+            router.notices(),
+            vec!["Route -: Channel Pressure D0 40 became Control Change B0 0B 40"]
+        );
+        assert!(router.notices().is_empty());
+    }
+
+    #[test]
+    fn inverts_pedals_on_routes() {
+        let mut router = Router::open("-,invert-cc=67,invert-cc").unwrap();
+        let mut capture = crate::capture::Capture::new();
+        for byte in [0xB0, 64, 0, 67, 127, 1, 10] {
+            router
+                .write(&capture.process(std::time::Duration::ZERO, byte))
+                .unwrap();
+        }
+        // The Modulation Wheel is left alone, running status or not
+        assert_eq!(
+            router.notices(),
+            [
+                "Route -: Control Change B0 40 00 became Control Change B0 40 7F",
+                "Route -: Control Change B0 43 7F became Control Change B0 43 00",
+            ]
+        );
+        assert!(Router::open("-,invert-cc=128").is_err());
+    }
+}
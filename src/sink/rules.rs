@@ -0,0 +1,173 @@
+//! Rules of the configuration that change the messages sent out the thru port, such as
+//! moving channel 1 to channel 5 an octave up without its aftertouch
+
+use crate::{midi::hex, midi::MidiMessage};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Changes made to the messages a rule applies to, in the order they are listed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rule {
+    /// Channel from 0 to 15 of the messages the rule applies to, or every message if `None`
+    pub channel: Option<u8>,
+    /// Statuses of the messages not sent, without the channel of channel messages
+    pub drop: BTreeSet<u8>,
+    /// Semitones added to the notes of note messages
+    pub transpose: i8,
+    /// Factor the velocity of sounding Note Ons is multiplied by
+    pub velocity: Option<f64>,
+    /// Control Change numbers replaced by others
+    pub controls: BTreeMap<u8, u8>,
+    /// Channel from 0 to 15 the messages are moved to
+    pub to_channel: Option<u8>,
+}
+
+impl Rule {
+    /// Returns the message with the rule applied, or `None` if the rule drops it. Each
+    /// change made is described in `changes`
+    fn apply(&self, mut message: MidiMessage, changes: &mut Vec<String>) -> Option<MidiMessage> {
+        if self
+            .channel
+            .is_some_and(|channel| message.channel() != Some(channel))
+        {
+            return Some(message);
+        }
+        if self.drop.contains(&message.status()) {
+            changes.push("dropped".to_string());
+            return None;
+        }
+        if self.transpose != 0 {
+            if let MidiMessage::NoteOff { note, .. }
+            | MidiMessage::NoteOn { note, .. }
+            | MidiMessage::PolyPressure { note, .. } = &mut message
+            {
+                let Some(moved) = note.checked_add_signed(self.transpose).filter(|n| *n < 128)
+                else {
+                    changes.push(format!(
+                        "dropped, transposed {:+} out of range",
+                        self.transpose
+                    ));
+                    return None;
+                };
+                *note = moved;
+                changes.push(format!("transposed {:+}", self.transpose));
+            }
+        }
+        // Velocity 0 is a Note Off and stays one
+        if let (Some(factor), MidiMessage::NoteOn { velocity, .. }) = (self.velocity, &mut message)
+        {
+            if *velocity > 0 {
+                *velocity = (*velocity as f64 * factor).round().clamp(1.0, 127.0) as u8;
+                changes.push(format!("velocity ×{}", factor));
+            }
+        }
+        if let MidiMessage::ControlChange { control, .. } = &mut message {
+            if let Some(to) = self.controls.get(control) {
+                changes.push(format!("CC {}→{}", control, to));
+                *control = *to;
+            }
+        }
+        if let (Some(to), Some(from)) = (self.to_channel, message.channel()) {
+            message.set_channel(to);
+            changes.push(format!("channel {}→{}", from + 1, to + 1));
+        }
+        Some(message)
+    }
+}
+
+/// Applies the rules in order, each to what the previous ones left of the message. Returns
+/// what is left, with a description of the changes if there were any
+pub fn apply(rules: &[Rule], message: &MidiMessage) -> (Option<MidiMessage>, Option<String>) {
+    let mut changes = vec![];
+    let mut changed = Some(message.clone());
+    for rule in rules {
+        changed = match changed {
+            Some(message) => rule.apply(message, &mut changes),
+            None => break,
+        };
+    }
+    if changes.is_empty() {
+        return (changed, None);
+    }
+    let before = format!("{} {}", message.name(), hex(&message.clone().to_bytes()));
+    let description = match &changed {
+        Some(after) => format!(
+            "{} became {} {} ({})",
+            before,
+            after.name(),
+            hex(&after.clone().to_bytes()),
+            changes.join(", ")
+        ),
+        None => format!("{} {}", before, changes.join(", ")),
+    };
+    (changed, Some(description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_in_order() {
+        // Channel 1 to channel 5 an octave up without aftertouch, then the Modulation Wheel
+        // of channel 5 as Expression
+        let rules = [
+            Rule {
+                channel: Some(0),
+                drop: [0xD0].into(),
+                transpose: 12,
+                velocity: Some(0.5),
+                to_channel: Some(4),
+                ..Rule::default()
+            },
+            Rule {
+                channel: Some(4),
+                controls: [(1, 11)].into(),
+                ..Rule::default()
+            },
+        ];
+        let note_on = |channel, note, velocity| MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        };
+        assert_eq!(
+            apply(&rules, &note_on(0, 60, 101)),
+            (
+                Some(note_on(4, 72, 51)),
+                Some(
+                    "Note On 90 3C 65 became Note On 94 48 33 (transposed +12, velocity ×0.5, \
+                     channel 1→5)"
+                        .to_string()
+                )
+            )
+        );
+        assert_eq!(apply(&rules, &note_on(0, 60, 0)).0, Some(note_on(4, 72, 0)));
+        assert_eq!(apply(&rules, &note_on(0, 120, 1)).0, None);
+        assert_eq!(
+            apply(&rules, &note_on(1, 60, 101)),
+            (Some(note_on(1, 60, 101)), None)
+        );
+
+        let pressure = MidiMessage::ChannelPressure {
+            channel: 0,
+            pressure: 64,
+        };
+        assert_eq!(
+            apply(&rules, &pressure),
+            (None, Some("Channel Pressure D0 40 dropped".to_string()))
+        );
+        let modulation = MidiMessage::ControlChange {
+            channel: 0,
+            control: 1,
+            value: 10,
+        };
+        assert_eq!(
+            apply(&rules, &modulation).0,
+            Some(MidiMessage::ControlChange {
+                channel: 4,
+                control: 11,
+                value: 10
+            })
+        );
+    }
+}
@@ -0,0 +1,31 @@
+//! Records a live capture into a type 0 Standard MIDI File
+
+use crate::{capture::CaptureEvent, sink::Sink, smf::TrackBuilder};
+use std::path::PathBuf;
+
+/// Builds a track from the received messages and saves it when finished
+pub struct SmfRecorder {
+    path: PathBuf,
+    builder: TrackBuilder,
+}
+
+impl SmfRecorder {
+    /// Creates a new recorder that writes to `path` when finished
+    pub fn new(path: PathBuf, ppq: u16, bpm: f64) -> SmfRecorder {
+        SmfRecorder {
+            path,
+            builder: TrackBuilder::new(ppq, bpm),
+        }
+    }
+}
+
+impl Sink for SmfRecorder {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.builder.push(event);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.builder.track().save(&self.path)
+    }
+}
@@ -0,0 +1,25 @@
+//! Keeps the capture in a store that outlives the session
+
+use crate::{capture::CaptureEvent, sink::Sink, store::CaptureStore};
+
+/// Appends every received event to a capture store
+pub struct StoreSink {
+    store: Box<dyn CaptureStore>,
+}
+
+impl StoreSink {
+    /// Creates a sink that appends to `store`
+    pub fn new(store: Box<dyn CaptureStore>) -> StoreSink {
+        StoreSink { store }
+    }
+}
+
+impl Sink for StoreSink {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.store.append(event)
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.store.flush()
+    }
+}
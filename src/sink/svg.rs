@@ -0,0 +1,67 @@
+//! Renders the capture as an SVG chart when it ends
+
+use crate::{
+    capture::CaptureEvent,
+    export::svg::{CcLanes, PianoRoll},
+    midi::notes::NoteNaming,
+    sink::Sink,
+};
+use anyhow::Context;
+use std::{collections::BTreeSet, fs, path::PathBuf};
+
+/// Collects notes and writes them as a piano roll when finished
+pub struct PianoRollExporter {
+    path: PathBuf,
+    roll: PianoRoll,
+}
+
+impl PianoRollExporter {
+    /// Creates an exporter that names notes with `naming` and writes to `path` when finished
+    pub fn new(path: PathBuf, naming: NoteNaming) -> PianoRollExporter {
+        PianoRollExporter {
+            path,
+            roll: PianoRoll::new(naming),
+        }
+    }
+}
+
+impl Sink for PianoRollExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.roll.push(event);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        fs::write(&self.path, self.roll.render())
+            .context(format!("Unable to write `{:?}`", self.path))
+    }
+}
+
+/// Collects controller values and writes them as lanes when finished
+pub struct CcLaneExporter {
+    path: PathBuf,
+    lanes: CcLanes,
+}
+
+impl CcLaneExporter {
+    /// Creates an exporter of the given controllers, or of all of them if empty,
+    /// that writes to `path` when finished
+    pub fn new(path: PathBuf, controls: BTreeSet<u8>) -> CcLaneExporter {
+        CcLaneExporter {
+            path,
+            lanes: CcLanes::new(controls),
+        }
+    }
+}
+
+impl Sink for CcLaneExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.lanes.push(event);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        fs::write(&self.path, self.lanes.render())
+            .context(format!("Unable to write `{:?}`", self.path))
+    }
+}
@@ -0,0 +1,33 @@
+//! Exports every received System Exclusive message to its own `.syx` file
+
+use crate::{capture::CaptureEvent, midi::MidiMessage, sink::Sink, syx};
+use anyhow::Context;
+use std::{fs, path::PathBuf};
+
+/// Writes each captured SysEx message to a numbered `.syx` file in a directory
+pub struct SyxExporter {
+    dir: PathBuf,
+    count: usize,
+}
+
+impl SyxExporter {
+    /// Creates the exporter, creating `dir` if it does not exist
+    pub fn new(dir: PathBuf) -> Result<SyxExporter, anyhow::Error> {
+        fs::create_dir_all(&dir).context(format!("Unable to create directory `{:?}`", dir))?;
+        Ok(SyxExporter { dir, count: 0 })
+    }
+}
+
+impl Sink for SyxExporter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if let Some(MidiMessage::SystemExclusive(data)) = &event.message {
+            self.count += 1;
+            syx::save(&self.dir.join(syx::file_name(self.count, data)), data)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
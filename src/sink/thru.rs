@@ -0,0 +1,311 @@
+//! Retransmits the capture out a serial port, so miditerm can sit inline in a MIDI chain
+//!
+//! When several inputs are captured at once the port merges them like a hardware MIDI merge
+//! box. Messages are only sent once complete, so they never end up inside each other, and
+//! running status is worked out for the merged stream rather than taken from any input.
+//! Real Time bytes go out as soon as they arrive, even in the middle of a SysEx.
+//! The rules of the configuration and then the script given with `--script` change the
+//! messages on the way, and each change is described in the log
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{self, MidiMessage, RunningStatusEncoder},
+    script::Script,
+    sink::{rules, Rule, Sink},
+    source::port,
+};
+use anyhow::{bail, Context};
+use serialport::SerialPort;
+use std::{collections::VecDeque, ffi::OsStr, io::Write, str::FromStr};
+
+/// Most events held back while an input sends SysEx. Past this the SysEx is taken as
+/// abandoned, such as when its input closed in the middle of it, and the other inputs go on
+const MAX_HELD: usize = 4096;
+
+/// What a thru port leaves out of the stream or changes in it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThruOption {
+    /// System Real Time messages are not sent
+    StripRealtime,
+    /// Active Sensing is not sent, for devices that stop at the first gap in it
+    StripActiveSensing,
+    /// SysEx is sent once it is complete, as the parser understood it, instead of byte by
+    /// byte as it arrives. Interrupted SysEx is dropped, and the messages of several inputs
+    /// cannot end up inside each other
+    ReencodeSysex,
+}
+
+impl FromStr for ThruOption {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strip-realtime" => Ok(ThruOption::StripRealtime),
+            "strip-active-sensing" => Ok(ThruOption::StripActiveSensing),
+            "reencode-sysex" => Ok(ThruOption::ReencodeSysex),
+            _ => bail!(
+                "Unknown thru option `{}`, use strip-realtime, strip-active-sensing, or reencode-sysex",
+                s
+            ),
+        }
+    }
+}
+
+/// Sends every complete message out a port with running status, like the MIDI Thru of a
+/// device. Bytes that are not part of a message, such as orphaned data bytes, are not sent
+pub struct Thru<W: Write> {
+    name: String,
+    port: W,
+    encoder: RunningStatusEncoder,
+    options: Vec<ThruOption>,
+    rules: Vec<Rule>,
+    script: Option<Script>,
+    /// Descriptions of the messages changed by the rules since they were last taken
+    notices: Vec<String>,
+    /// Input whose SysEx is being passed through as it arrives
+    streaming: Option<u8>,
+    /// Events of the other inputs held back until that SysEx ends
+    held: VecDeque<CaptureEvent>,
+}
+
+impl Thru<Box<dyn SerialPort>> {
+    /// Opens a thru port described as `PORT[,OPTION..]`, such as
+    /// `/dev/ttyUSB1,strip-active-sensing`, applying the rules and the script to what it sends
+    pub fn open(
+        spec: &str,
+        rules: Vec<Rule>,
+        script: Option<Script>,
+    ) -> Result<Thru<Box<dyn SerialPort>>, anyhow::Error> {
+        let mut parts = spec.split(',');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() {
+            bail!("Thru `{}` has no port", spec);
+        }
+        let options = parts
+            .map(str::parse)
+            .collect::<Result<Vec<ThruOption>, _>>()
+            .context(format!("Invalid thru `{}`", spec))?;
+        let port = port::open(OsStr::new(name), midi::MIDI_BAUD_RATE)
+            .context(format!("Unable to open serial port `{}`", name))?;
+        Ok(Thru::new(name.to_string(), port, options, rules, script))
+    }
+}
+
+impl<W: Write> Thru<W> {
+    pub fn new(
+        name: String,
+        port: W,
+        options: Vec<ThruOption>,
+        rules: Vec<Rule>,
+        script: Option<Script>,
+    ) -> Thru<W> {
+        Thru {
+            name,
+            port,
+            encoder: RunningStatusEncoder::new(),
+            options,
+            rules,
+            script,
+            notices: vec![],
+            streaming: None,
+            held: VecDeque::new(),
+        }
+    }
+
+    /// Returns `true` if the event has to wait for the SysEx of another input to end
+    fn waits(&self, event: &CaptureEvent) -> bool {
+        let realtime = event.byte >= 0xF8;
+        self.streaming
+            .is_some_and(|input| input != event.source && !realtime)
+    }
+
+    /// Returns the bytes sent for the event, along with those of the events it releases
+    fn bytes(&mut self, event: &CaptureEvent) -> Vec<u8> {
+        let mut bytes = vec![];
+        if !self.waits(event) {
+            bytes = self.pass(event);
+        } else {
+            self.held.push_back(event.clone());
+            if self.held.len() <= MAX_HELD {
+                return bytes;
+            }
+            self.streaming = None;
+        }
+        // The held events of each input go out in order, and those of an input that starts
+        // a SysEx of its own hold back the others again
+        let mut i = 0;
+        while i < self.held.len() {
+            if self.waits(&self.held[i]) {
+                i += 1;
+                continue;
+            }
+            let streaming = self.streaming;
+            if let Some(event) = self.held.remove(i) {
+                bytes.extend(self.pass(&event));
+            }
+            if streaming.is_some() && self.streaming.is_none() {
+                i = 0;
+            }
+        }
+        bytes
+    }
+
+    /// Returns the bytes sent for an event that does not have to wait
+    fn pass(&mut self, event: &CaptureEvent) -> Vec<u8> {
+        // SysEx passes through as it arrives, so long dumps are not held back, unless a rule
+        // drops all of it
+        if event.status == Some(0xF0) && !self.options.contains(&ThruOption::ReencodeSysex) {
+            let dropped = self
+                .rules
+                .iter()
+                .any(|rule| rule.channel.is_none() && rule.drop.contains(&0xF0));
+            if dropped {
+                return vec![];
+            }
+            self.encoder.reset();
+            self.streaming = match event.message {
+                Some(_) => None,
+                None => Some(event.source),
+            };
+            return vec![event.byte];
+        }
+        // Any status other than Real Time ends a SysEx
+        if event.byte < 0xF8 && self.streaming == Some(event.source) {
+            self.streaming = None;
+        }
+        match &event.message {
+            Some(MidiMessage::ActiveSensing)
+                if self.options.contains(&ThruOption::StripActiveSensing) =>
+            {
+                vec![]
+            }
+            Some(_) if event.byte >= 0xF8 && self.options.contains(&ThruOption::StripRealtime) => {
+                vec![]
+            }
+            Some(message) => {
+                let (message, changes) = rules::apply(&self.rules, message);
+                if let Some(changes) = changes {
+                    self.notices
+                        .push(format!("Thru {}: {}", self.name, changes));
+                }
+                let messages = match (message, &mut self.script) {
+                    (Some(message), Some(script)) => {
+                        let messages = script.on_message(event, &message);
+                        self.notices.extend(script.annotations());
+                        messages
+                    }
+                    (message, _) => message.into_iter().collect(),
+                };
+                let mut bytes = vec![];
+                for message in messages {
+                    bytes.extend(self.encoder.encode(message));
+                }
+                bytes
+            }
+            None => vec![],
+        }
+    }
+}
+
+impl<W: Write> Sink for Thru<W> {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if event.notice {
+            return Ok(());
+        }
+        let bytes = self.bytes(event);
+        if !bytes.is_empty() {
+            self.port
+                .write_all(&bytes)
+                .context(format!("Unable to write to `{}`", self.name))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.port.flush()?;
+        Ok(())
+    }
+
+    fn notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    /// Returns what a thru with the options sends for the bytes
+    fn thru(options: Vec<ThruOption>, bytes: &[u8]) -> Vec<u8> {
+        let mut capture = Capture::new();
+        let mut thru = Thru::new("test".to_string(), vec![], options, vec![], None);
+        for byte in bytes {
+            thru.write(&capture.process(Duration::ZERO, *byte)).unwrap();
+        }
+        thru.port
+    }
+
+    #[test]
+    fn retransmits() {
+        let bytes = [
+            0x90, 60, 100, 0x90, 62, 0xFE, 100, 0xF0, 0x7E, 0xF8, 0x01, 0xF7, 0x3C, 0x90, 64, 0,
+        ];
+        // Active Sensing goes out before the message it interrupted, and the status is sent
+        // again after the SysEx cancelled the running status. The orphaned 3C is dropped
+        assert_eq!(
+            thru(vec![], &bytes),
+            [0x90, 60, 100, 0xFE, 62, 100, 0xF0, 0x7E, 0xF8, 0x01, 0xF7, 0x90, 64, 0]
+        );
+        assert_eq!(
+            thru(vec![ThruOption::StripActiveSensing], &bytes)[..6],
+            [0x90, 60, 100, 62, 100, 0xF0]
+        );
+        assert_eq!(
+            thru(
+                vec![ThruOption::StripRealtime, ThruOption::ReencodeSysex],
+                &bytes
+            ),
+            [0x90, 60, 100, 62, 100, 0xF0, 0x7E, 0x01, 0xF7, 0x90, 64, 0]
+        );
+        // Interrupted SysEx is only dropped when re-encoded
+        let interrupted = [0xF0, 0x7E, 0x90, 60, 100];
+        assert_eq!(
+            thru(vec![ThruOption::ReencodeSysex], &interrupted),
+            [0x90, 60, 100]
+        );
+        assert!("strip-clock".parse::<ThruOption>().is_err());
+    }
+
+    #[test]
+    fn merges_inputs() {
+        let bytes = [
+            (0, 0xF0),
+            (0, 0x7E),
+            (1, 0x90),
+            (1, 0x3C),
+            (1, 0xF8),
+            (1, 0x64),
+            (0, 0x01),
+            (0, 0xF7),
+            (1, 0x3E),
+            (1, 0x64),
+            (0, 0x90),
+            (0, 0x40),
+            (0, 0x64),
+        ];
+        let mut capture = Capture::new();
+        let mut thru = Thru::new("test".to_string(), vec![], vec![], vec![], None);
+        for (source, byte) in bytes {
+            let event = capture.process_from(source, Duration::ZERO, byte);
+            thru.write(&event).unwrap();
+        }
+        // The clock of the second input goes out at once, its note once the SysEx of the
+        // first ended, and the note of the first shares the running status of the second
+        assert_eq!(
+            thru.port,
+            [0xF0, 0x7E, 0xF8, 0x01, 0xF7, 0x90, 0x3C, 0x64, 0x3E, 0x64, 0x40, 0x64]
+        );
+    }
+}
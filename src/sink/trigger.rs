@@ -0,0 +1,431 @@
+//! Triggers of the configuration that act on what is received, for automating hardware
+//! tests: running a command when the Sustain Pedal is pressed, saving the Identity Reply of
+//! a device, or sending a panic when a violation occurs
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{self, MidiAnalysis, MidiMessage},
+    sink::Sink,
+};
+use anyhow::Context;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Child, Command},
+};
+
+/// What a trigger waits for
+#[derive(Debug, Clone, PartialEq)]
+pub enum When {
+    /// A message of one of these statuses, without the channel of channel messages, or any
+    /// message if there are none. On this channel from 0 to 15 if one is given
+    Message {
+        statuses: BTreeSet<u8>,
+        channel: Option<u8>,
+    },
+    /// A Control Change of a controller, on this channel from 0 to 15 if one is given
+    Control { channel: Option<u8>, control: u8 },
+    /// A controller passing a value in either direction, such as a pedal being pressed or
+    /// released. A controller not seen yet starts at 0
+    Crosses {
+        channel: Option<u8>,
+        control: u8,
+        threshold: u8,
+    },
+    /// The reply of a device to an Identity Request
+    IdentityReply,
+    /// A byte analyzed as a violation of the MIDI specification
+    Violation,
+}
+
+/// What a trigger does once it fires
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Runs a shell command without waiting for it. The message is passed to it in the
+    /// `MIDITERM_*` environment variables
+    Run(String),
+    /// Writes the message to a file, replacing what it held
+    Save(PathBuf),
+    /// Sends the bytes of a message, written as it was configured, out one of the ports of
+    /// the triggers
+    Send {
+        message: String,
+        bytes: Vec<u8>,
+        port: usize,
+    },
+}
+
+/// A condition and the actions taken each time it is met
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub when: When,
+    pub actions: Vec<Action>,
+}
+
+/// Runs the triggers on the capture
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    /// Ports the triggers send to, with their names
+    ports: Vec<(String, Box<dyn Write>)>,
+    watcher: Watcher,
+    /// Commands still running, reaped once they exit
+    children: Vec<Child>,
+    notices: Vec<String>,
+}
+
+/// Follows the controllers of the capture to tell which conditions its events meet
+#[derive(Debug, Clone, Default)]
+pub struct Watcher {
+    /// Last value of each controller, by channel and controller
+    controls: BTreeMap<(u8, u8), u8>,
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher::default()
+    }
+
+    /// Returns the conditions met by the next event of the capture, numbered from 0
+    pub fn met<'a>(
+        &mut self,
+        event: &CaptureEvent,
+        conditions: impl Iterator<Item = &'a When>,
+    ) -> Vec<usize> {
+        let mut previous = None;
+        if let Some(MidiMessage::ControlChange {
+            channel,
+            control,
+            value,
+        }) = event.message
+        {
+            previous = Some(self.controls.insert((channel, control), value).unwrap_or(0));
+        }
+        let message = event.message.as_ref();
+        let meets = |when: &When| match when {
+            When::Message { statuses, channel } => message.is_some_and(|message| {
+                (statuses.is_empty() || statuses.contains(&message.status()))
+                    && channel.is_none_or(|channel| message.channel() == Some(channel))
+            }),
+            When::Control {
+                channel: wanted,
+                control: wanted_control,
+            } => matches!(
+                message,
+                Some(MidiMessage::ControlChange { channel, control, .. })
+                    if control == wanted_control && wanted.is_none_or(|wanted| *channel == wanted)
+            ),
+            When::Crosses {
+                channel: wanted,
+                control: wanted_control,
+                threshold,
+            } => match (message, previous) {
+                (
+                    Some(MidiMessage::ControlChange {
+                        channel,
+                        control,
+                        value,
+                    }),
+                    Some(previous),
+                ) => {
+                    control == wanted_control
+                        && wanted.is_none_or(|wanted| *channel == wanted)
+                        && (previous < *threshold) != (*value < *threshold)
+                }
+                _ => false,
+            },
+            When::IdentityReply => matches!(
+                message,
+                Some(MidiMessage::SystemExclusive(data))
+                    if data.len() > 3 && data[0] == 0x7E && data[2..4] == [0x06, 0x02]
+            ),
+            When::Violation => matches!(event.analysis, MidiAnalysis::Violation(_)),
+        };
+        conditions
+            .enumerate()
+            .filter(|(_, when)| meets(when))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl Triggers {
+    pub fn new(triggers: Vec<Trigger>, ports: Vec<(String, Box<dyn Write>)>) -> Triggers {
+        Triggers {
+            triggers,
+            ports,
+            watcher: Watcher::new(),
+            children: vec![],
+            notices: vec![],
+        }
+    }
+
+    /// Returns the triggers that fire on the event, numbered from 0
+    fn fired(&mut self, event: &CaptureEvent) -> Vec<usize> {
+        self.watcher
+            .met(event, self.triggers.iter().map(|trigger| &trigger.when))
+    }
+
+    /// Takes the actions of a trigger that fired on the event
+    fn act(&mut self, number: usize, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        let bytes = event
+            .message
+            .clone()
+            .map_or_else(|| event.received().to_vec(), MidiMessage::to_bytes);
+        let what = describe(event);
+        for action in self.triggers[number].actions.clone() {
+            let done = match action {
+                Action::Run(command) => {
+                    let mut shell = Command::new("sh");
+                    shell
+                        .arg("-c")
+                        .arg(&command)
+                        .env("MIDITERM_TIME", event.time.as_secs_f64().to_string())
+                        .env("MIDITERM_BYTES", midi::hex(&bytes))
+                        .env("MIDITERM_SOURCE", event.source.to_string());
+                    if let Some(message) = &event.message {
+                        shell.env("MIDITERM_MESSAGE", message.name());
+                    }
+                    if let Some(channel) = event.message.as_ref().and_then(MidiMessage::channel) {
+                        shell.env("MIDITERM_CHANNEL", (channel + 1).to_string());
+                    }
+                    if let Some(MidiMessage::ControlChange { value, .. }) = &event.message {
+                        shell.env("MIDITERM_VALUE", value.to_string());
+                    }
+                    let child = shell
+                        .spawn()
+                        .context(format!("Unable to run `{}`", command))?;
+                    self.children.push(child);
+                    format!("ran `{}`", command)
+                }
+                Action::Save(path) => {
+                    fs::write(&path, &bytes).context(format!("Unable to write `{:?}`", path))?;
+                    format!("saved to {:?}", path)
+                }
+                Action::Send {
+                    message,
+                    bytes,
+                    port,
+                } => {
+                    let (name, port) = &mut self.ports[port];
+                    port.write_all(&bytes)
+                        .and_then(|_| port.flush())
+                        .context(format!("Unable to write to `{}`", name))?;
+                    format!("sent `{}` to {}", message, name)
+                }
+            };
+            self.notices
+                .push(format!("Trigger {}: {}, {}", number + 1, what, done));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the violation of an event, or else the message it completed, its byte, or what
+/// happened to the source
+fn describe(event: &CaptureEvent) -> String {
+    match (&event.message, &event.analysis) {
+        (_, MidiAnalysis::Violation(violation)) => violation.clone(),
+        (None, analysis) if event.notice => analysis.text().to_string(),
+        (Some(message), _) => format!(
+            "{} {}",
+            message.name(),
+            midi::hex(&message.clone().to_bytes())
+        ),
+        (None, _) => midi::hex(&[event.byte]),
+    }
+}
+
+impl Sink for Triggers {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.children
+            .retain_mut(|child| child.try_wait().is_ok_and(|status| status.is_none()));
+        for number in self.fired(event) {
+            self.act(number, event)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        for (name, port) in &mut self.ports {
+            port.flush()
+                .context(format!("Unable to write to `{}`", name))?;
+        }
+        Ok(())
+    }
+
+    fn notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
+
+/// Holds the capture back until an event meets a condition, like the trigger of an
+/// oscilloscope, keeping the last messages before it to show what led up to it
+#[derive(Debug, Clone)]
+pub struct Arm {
+    when: When,
+    watcher: Watcher,
+    /// Number of messages kept from before the condition is met
+    pre: usize,
+    held: VecDeque<CaptureEvent>,
+    /// Complete messages in `held`
+    messages: usize,
+    /// Description of the event that met the condition, once one did
+    fired: Option<String>,
+}
+
+impl Arm {
+    pub fn new(when: When, pre: usize) -> Arm {
+        Arm {
+            when,
+            watcher: Watcher::new(),
+            pre,
+            held: VecDeque::new(),
+            messages: 0,
+            fired: None,
+        }
+    }
+
+    /// Returns a description of the event that met the condition, once one has
+    pub fn fired(&self) -> Option<&str> {
+        self.fired.as_deref()
+    }
+
+    /// Returns the events let into the capture by the next one: nothing until the condition
+    /// is met, then the events kept from before it followed by the event itself, then every
+    /// event
+    pub fn pass(&mut self, event: CaptureEvent) -> Vec<CaptureEvent> {
+        if self.fired.is_some() {
+            return vec![event];
+        }
+        if !self
+            .watcher
+            .met(&event, [&self.when].into_iter())
+            .is_empty()
+        {
+            self.fired = Some(format!(
+                "{} at {:.6} s",
+                describe(&event),
+                event.time.as_secs_f64()
+            ));
+            let mut events: Vec<CaptureEvent> = self.held.drain(..).collect();
+            events.push(event);
+            return events;
+        }
+        if event.message.is_some() {
+            self.messages += 1;
+        }
+        self.held.push_back(event);
+        // The oldest message goes with any stray bytes before it
+        while self.messages > self.pre {
+            while let Some(dropped) = self.held.pop_front() {
+                if dropped.message.is_some() {
+                    break;
+                }
+            }
+            self.messages -= 1;
+        }
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::{cell::RefCell, env, io, rc::Rc, time::Duration};
+
+    /// Port that keeps what is sent to it
+    struct Sent(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fires() {
+        let path = env::temp_dir().join(format!("miditerm-trigger-{}.syx", std::process::id()));
+        let sent = Rc::new(RefCell::new(vec![]));
+        let mut triggers = Triggers::new(
+            vec![
+                Trigger {
+                    when: When::Crosses {
+                        channel: Some(0),
+                        control: 64,
+                        threshold: 64,
+                    },
+                    actions: vec![Action::Send {
+                        message: "pc 1 1".to_string(),
+                        bytes: vec![0xC0, 1],
+                        port: 0,
+                    }],
+                },
+                Trigger {
+                    when: When::IdentityReply,
+                    actions: vec![Action::Save(path.clone())],
+                },
+                Trigger {
+                    when: When::Message {
+                        statuses: [0xF8].into(),
+                        channel: None,
+                    },
+                    actions: vec![],
+                },
+            ],
+            vec![("out".to_string(), Box::new(Sent(sent.clone())))],
+        );
+
+        let reply = [0xF0, 0x7E, 0x10, 0x06, 0x02, 0x41, 0x10, 0x42, 0xF7];
+        let pedal = [0xB0, 64, 127, 64, 100, 64, 0, 0xB1, 64, 127];
+        let mut capture = Capture::new();
+        for byte in reply.into_iter().chain(pedal) {
+            let event = capture.process(Duration::ZERO, byte);
+            triggers.write(&event).unwrap();
+        }
+        // Pressed and released on channel 1, ignored on channel 2
+        assert_eq!(*sent.borrow(), [0xC0, 1, 0xC0, 1]);
+        assert_eq!(fs::read(&path).unwrap(), reply);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            triggers.notices(),
+            [
+                format!(
+                    "Trigger 2: System Exclusive F0 7E 10 06 02 41 10 42 F7, saved to {:?}",
+                    path
+                ),
+                "Trigger 1: Control Change B0 40 7F, sent `pc 1 1` to out".to_string(),
+                "Trigger 1: Control Change B0 40 00, sent `pc 1 1` to out".to_string(),
+            ]
+        );
+        assert_eq!(triggers.fired(&capture.process(Duration::ZERO, 0xF8)), [2]);
+    }
+
+    #[test]
+    fn arms() {
+        let mut arm = Arm::new(
+            When::Control {
+                channel: None,
+                control: 64,
+            },
+            2,
+        );
+        let mut capture = Capture::new();
+        let mut passed = vec![];
+        for byte in [0xF8, 0x90, 60, 100, 0xFE, 0xB0, 1, 5, 0xB0, 64, 127, 0xF8] {
+            passed.extend(arm.pass(capture.process(Duration::ZERO, byte)));
+            assert_eq!(arm.fired().is_some(), !passed.is_empty());
+        }
+        // The two messages before the pedal, the pedal, and everything after it
+        let passed: Vec<u8> = passed.iter().map(|event| event.byte).collect();
+        assert_eq!(passed, [0xFE, 0xB0, 1, 5, 0xB0, 64, 127, 0xF8]);
+        assert_eq!(arm.fired(), Some("Control Change B0 40 7F at 0.000000 s"));
+    }
+}
@@ -0,0 +1,71 @@
+//! Writes the capture as Universal MIDI Packets to a file or network socket
+
+use crate::{capture::CaptureEvent, sink::Sink, ump};
+use anyhow::Context;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::{TcpStream, UdpSocket},
+};
+
+/// Destination of the translated packets
+enum Target {
+    /// File or TCP stream receiving a continuous stream of big endian words
+    Stream(Box<dyn Write + Send>),
+    /// UDP socket receiving one datagram per MIDI message
+    Datagram(UdpSocket),
+}
+
+/// Translates every received MIDI 1.0 message into UMP
+pub struct UmpWriter {
+    target: Target,
+    group: u8,
+}
+
+impl UmpWriter {
+    /// Opens the destination described by `target`:
+    /// `tcp:HOST:PORT`, `udp:HOST:PORT`, or otherwise the path of a file
+    pub fn open(target: &str, group: u8) -> Result<UmpWriter, anyhow::Error> {
+        let target = if let Some(address) = target.strip_prefix("tcp:") {
+            let stream = TcpStream::connect(address)
+                .context(format!("Unable to connect to `{}`", address))?;
+            Target::Stream(Box::new(stream))
+        } else if let Some(address) = target.strip_prefix("udp:") {
+            let socket = UdpSocket::bind("0.0.0.0:0").context("Unable to open UDP socket")?;
+            socket
+                .connect(address)
+                .context(format!("Unable to connect to `{}`", address))?;
+            Target::Datagram(socket)
+        } else {
+            let file = File::create(target).context(format!("Unable to create `{}`", target))?;
+            Target::Stream(Box::new(BufWriter::new(file)))
+        };
+        Ok(UmpWriter { target, group })
+    }
+}
+
+impl Sink for UmpWriter {
+    fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        let Some(message) = &event.message else {
+            return Ok(());
+        };
+        let bytes: Vec<u8> = ump::translate(message, self.group)
+            .into_iter()
+            .flat_map(u32::to_be_bytes)
+            .collect();
+        match &mut self.target {
+            Target::Stream(stream) => stream.write_all(&bytes)?,
+            Target::Datagram(socket) => {
+                socket.send(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        if let Target::Stream(stream) = &mut self.target {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
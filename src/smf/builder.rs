@@ -0,0 +1,139 @@
+//! Conversion of a timestamped capture into a Standard MIDI File track
+
+use crate::{
+    capture::CaptureEvent,
+    midi::MidiMessage,
+    smf::{self, SmfTrack},
+};
+use std::time::Duration;
+
+/// Minimum relative change in clock derived tempo before a new Set Tempo event is written
+const TEMPO_CHANGE_THRESHOLD: f64 = 0.005;
+
+/// Converts received messages into ticks on a type 0 track.
+///
+/// When MIDI Timing Clock is present the tempo is measured once per quarter note
+/// and written to the track as Set Tempo events. Otherwise the fixed tempo is used
+#[derive(Debug)]
+pub struct TrackBuilder {
+    ppq: u16,
+    track: SmfTrack,
+    /// Capture time of the first event
+    start: Option<Duration>,
+    /// Current tempo in microseconds per quarter note
+    tempo: u32,
+    /// Time and tick of the most recent tempo change
+    anchor: (Duration, u32),
+    /// Time of the first clock of the quarter note being measured and number of clocks since
+    beat: Option<(Duration, u32)>,
+}
+
+impl TrackBuilder {
+    /// Creates a builder for a track with the given resolution and initial tempo
+    pub fn new(ppq: u16, bpm: f64) -> TrackBuilder {
+        let tempo = smf::bpm_to_tempo(bpm);
+        let mut track = SmfTrack::new(ppq);
+        track.push_tempo(0, tempo);
+        TrackBuilder {
+            ppq,
+            track,
+            start: None,
+            tempo,
+            anchor: (Duration::ZERO, 0),
+            beat: None,
+        }
+    }
+
+    /// Returns the track built so far
+    pub fn track(&self) -> &SmfTrack {
+        &self.track
+    }
+
+    /// Adds the message completed by the event, if any, to the track
+    pub fn push(&mut self, event: &CaptureEvent) {
+        let start = *self.start.get_or_insert(event.time);
+        let time = event.time.saturating_sub(start);
+        match &event.message {
+            Some(MidiMessage::TimingClock) => self.clock(time),
+            // A Start restarts the tempo measurement on the next clock
+            Some(MidiMessage::Start) | Some(MidiMessage::Continue) => self.beat = None,
+            Some(message) => {
+                let tick = self.tick(time);
+                self.track.push_message(tick, message);
+            }
+            None => {}
+        }
+    }
+
+    /// Converts a time since the first event to a tick using the current tempo
+    fn tick(&self, time: Duration) -> u32 {
+        let (anchor_time, anchor_tick) = self.anchor;
+        let elapsed = time.saturating_sub(anchor_time).as_micros();
+        anchor_tick + (elapsed * self.ppq as u128 / self.tempo as u128) as u32
+    }
+
+    /// Measures the tempo from incoming Timing Clock messages
+    fn clock(&mut self, time: Duration) {
+        let (beat_start, count) = match self.beat {
+            Some((beat_start, count)) => (beat_start, count + 1),
+            None => {
+                self.beat = Some((time, 0));
+                return;
+            }
+        };
+        if count < smf::CLOCKS_PER_QUARTER {
+            self.beat = Some((beat_start, count));
+            return;
+        }
+
+        let tempo = time.saturating_sub(beat_start).as_micros() as u32;
+        self.beat = Some((time, 0));
+        let change = (tempo as f64 - self.tempo as f64).abs() / self.tempo as f64;
+        // Beats too short or too long for a Set Tempo event keep the tempo
+        if (1..=smf::MAX_TEMPO).contains(&tempo) && change > TEMPO_CHANGE_THRESHOLD {
+            let tick = self.tick(time);
+            self.track.push_tempo(tick, tempo);
+            self.anchor = (time, tick);
+            self.tempo = tempo;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    fn feed(builder: &mut TrackBuilder, capture: &mut Capture, micros: u64, bytes: &[u8]) {
+        for byte in bytes {
+            builder.push(&capture.process(Duration::from_micros(micros), *byte));
+        }
+    }
+
+    #[test]
+    fn fixed_tempo() {
+        let mut capture = Capture::new();
+        let mut builder = TrackBuilder::new(480, 120.0);
+        feed(&mut builder, &mut capture, 1_000, &[0x90, 60, 100]);
+        feed(&mut builder, &mut capture, 501_000, &[60, 0]);
+        assert_eq!(builder.tick(Duration::from_micros(500_000)), 480);
+        let bytes = builder.track().to_bytes();
+        assert_eq!(
+            &bytes[22 + 7..],
+            &[0x00, 0x90, 60, 100, 0x83, 0x60, 0x90, 60, 0, 0x00, 0xFF, 0x2F, 0x00]
+        );
+    }
+
+    #[test]
+    fn clock_tempo() {
+        let mut capture = Capture::new();
+        let mut builder = TrackBuilder::new(480, 120.0);
+        // 24 clocks per quarter at 100 BPM = 25 ms per clock
+        for n in 0..=24 {
+            feed(&mut builder, &mut capture, n * 25_000, &[0xF8]);
+        }
+        assert_eq!(builder.tempo, 600_000);
+        assert_eq!(builder.anchor, (Duration::from_millis(600), 576));
+        assert_eq!(builder.tick(Duration::from_millis(1200)), 1056);
+    }
+}
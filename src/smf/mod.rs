@@ -0,0 +1,73 @@
+//! Standard MIDI File support
+
+mod builder;
+mod reader;
+mod writer;
+
+pub use builder::TrackBuilder;
+pub use reader::SmfFile;
+pub use writer::*;
+
+use anyhow::bail;
+
+/// Number of MIDI Timing Clock messages per quarter note
+pub const CLOCKS_PER_QUARTER: u32 = 24;
+
+/// Longest tempo a Set Tempo event can hold, in microseconds per quarter note
+pub const MAX_TEMPO: u32 = 0xFF_FFFF;
+
+/// Slowest tempo in beats per minute a Set Tempo event can hold
+const MIN_BPM: f64 = 60_000_000.0 / MAX_TEMPO as f64;
+/// Fastest tempo in beats per minute, a quarter note of one microsecond
+const MAX_BPM: f64 = 60_000_000.0;
+
+/// Converts beats per minute into microseconds per quarter note
+pub fn bpm_to_tempo(bpm: f64) -> u32 {
+    (60_000_000.0 / bpm).round() as u32
+}
+
+/// Checks that a file can be written with the resolution `ppq` and the tempo `bpm`
+pub fn check_timing(ppq: u16, bpm: f64) -> Result<(), anyhow::Error> {
+    if ppq == 0 {
+        bail!("`--ppq` must be at least 1");
+    }
+    if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+        bail!(
+            "`--bpm` must be from {:.2} to {}, not {}",
+            MIN_BPM,
+            MAX_BPM,
+            bpm
+        );
+    }
+    Ok(())
+}
+
+/// Reads a variable length quantity from the start of `bytes`.
+///
+/// Returns the value and the number of bytes it occupied
+pub fn read_var_len(bytes: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0_u32;
+    for (i, byte) in bytes.iter().take(4).enumerate() {
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_timing() {
+        assert!(check_timing(480, 120.0).is_ok());
+        assert_eq!(bpm_to_tempo(MIN_BPM), MAX_TEMPO);
+        assert_eq!(bpm_to_tempo(MAX_BPM), 1);
+        assert!(check_timing(0, 120.0).is_err());
+        for bpm in [0.0, 3.5, 1e9, f64::NAN, f64::INFINITY] {
+            assert!(check_timing(480, bpm).is_err(), "{}", bpm);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Reader for Standard MIDI Files of any type
+
+use super::{read_var_len, SmfEvent, CLOCKS_PER_QUARTER};
+use anyhow::{anyhow, bail, Context};
+use std::{fs, path::Path, time::Duration};
+
+/// Tempo of a file until its first Set Tempo, 120 beats per minute
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// What a tick of a file stands for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Division {
+    /// Pulses per quarter note, whose length follows the tempo map
+    Ppq(u16),
+    /// A fixed number of ticks per second, from SMPTE frames and their subdivisions
+    Smpte(f64),
+}
+
+/// A Standard MIDI File with the events of all its tracks merged in tick order
+#[derive(Debug)]
+pub struct SmfFile {
+    division: Division,
+    /// Events as stored in the file: channel messages with their status, SysEx as `F0` or
+    /// `F7` with the length and data, and meta events as `FF` with their type and length
+    events: Vec<SmfEvent>,
+    /// Tick of the last End of Track
+    end: u32,
+    /// Set Tempo events in tick order
+    tempos: Vec<Tempo>,
+}
+
+/// A change of tempo, with the time it happens at
+#[derive(Debug)]
+struct Tempo {
+    tick: u32,
+    micros: f64,
+    /// Microseconds per quarter note from here on
+    tempo: u32,
+}
+
+impl SmfFile {
+    /// Reads the file at `path`
+    pub fn load(path: &Path) -> Result<SmfFile, anyhow::Error> {
+        let bytes = fs::read(path).context(format!("Unable to read file `{:?}`", path))?;
+        SmfFile::parse(&bytes).context(format!("Unable to read MIDI file `{:?}`", path))
+    }
+
+    /// Parses a complete file. The tracks of a type 2 file are independent sequences,
+    /// and are placed one after the other
+    pub fn parse(bytes: &[u8]) -> Result<SmfFile, anyhow::Error> {
+        let mut chunks = Chunks(bytes);
+        let header = match chunks.next() {
+            Some((b"MThd", data)) if data.len() >= 6 => data,
+            _ => bail!("The file does not start with an `MThd` header"),
+        };
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let division = match (header[4], header[5]) {
+            (frames, ticks) if frames & 0x80 != 0 => {
+                // The negative number of frames per second, where 29 stands for 29.97
+                let fps = match (frames as i8).unsigned_abs() {
+                    29 => 29.97,
+                    fps => fps as f64,
+                };
+                Division::Smpte(fps * ticks as f64)
+            }
+            (high, low) => Division::Ppq(u16::from_be_bytes([high, low])),
+        };
+        if division == Division::Ppq(0) || division == Division::Smpte(0.0) {
+            bail!("The header gives a division of zero");
+        }
+
+        let mut events = vec![];
+        let mut end = 0;
+        let mut track = 0;
+        for (id, data) in chunks {
+            // Chunks of other types are skipped, as the specification asks
+            if id != b"MTrk" {
+                continue;
+            }
+            track += 1;
+            let offset = if format == 2 { end } else { 0 };
+            let track_end = read_track(data, offset, &mut events)
+                .context(format!("Unable to read track {}", track))?;
+            end = end.max(track_end);
+        }
+        if track == 0 {
+            bail!("The file has no tracks");
+        }
+        // Stable sort keeps simultaneous events in track order
+        events.sort_by_key(|e| e.tick);
+        let mut tempos: Vec<Tempo> = vec![];
+        if let Division::Ppq(ppq) = division {
+            for event in &events {
+                let Some(tempo) = tempo_of(&event.data) else {
+                    continue;
+                };
+                let (last, micros, last_tempo) = tempos
+                    .last()
+                    .map_or((0, 0.0, DEFAULT_TEMPO), |t| (t.tick, t.micros, t.tempo));
+                tempos.push(Tempo {
+                    tick: event.tick,
+                    micros: micros + (event.tick - last) as f64 * last_tempo as f64 / ppq as f64,
+                    tempo,
+                });
+            }
+        }
+        Ok(SmfFile {
+            division,
+            events,
+            end,
+            tempos,
+        })
+    }
+
+    /// Returns the resolution in pulses per quarter note, or `None` for SMPTE time
+    pub fn ppq(&self) -> Option<u16> {
+        match self.division {
+            Division::Ppq(ppq) => Some(ppq),
+            Division::Smpte(_) => None,
+        }
+    }
+
+    /// Returns the time of a tick, following the Set Tempo events before it
+    pub fn time(&self, tick: f64) -> Duration {
+        let ppq = match self.division {
+            Division::Ppq(ppq) => ppq as f64,
+            Division::Smpte(rate) => return Duration::from_secs_f64(tick / rate),
+        };
+        let i = self.tempos.partition_point(|t| (t.tick as f64) < tick);
+        let micros = match i.checked_sub(1).map(|i| &self.tempos[i]) {
+            Some(t) => t.micros + (tick - t.tick as f64) * t.tempo as f64 / ppq,
+            None => tick * DEFAULT_TEMPO as f64 / ppq,
+        };
+        Duration::from_secs_f64(micros / 1e6)
+    }
+
+    /// Returns the length of the song, up to its last End of Track
+    pub fn length(&self) -> Duration {
+        self.time(self.end as f64)
+    }
+
+    /// Returns the bytes to send for each event in the order of the file, with their time.
+    /// Meta events are left out, as they are never sent on the wire
+    pub fn messages(&self) -> Vec<(Duration, Vec<u8>)> {
+        let mut messages = vec![];
+        for event in &self.events {
+            let bytes = match event.data[0] {
+                0xFF => continue,
+                // The length follows the status of SysEx and of escaped bytes
+                status @ (0xF0 | 0xF7) => {
+                    let (_, n) = read_var_len(&event.data[1..]).unwrap_or((0, 0));
+                    let data = &event.data[1 + n..];
+                    if status == 0xF0 {
+                        [&[0xF0], data].concat()
+                    } else {
+                        data.to_vec()
+                    }
+                }
+                _ => event.data.clone(),
+            };
+            if !bytes.is_empty() {
+                messages.push((self.time(event.tick as f64), bytes));
+            }
+        }
+        messages
+    }
+
+    /// Returns the times of the MIDI Timing Clock messages of the song, 24 per quarter note
+    /// from the start up to its end, or `None` for SMPTE time that has no quarter notes
+    pub fn clocks(&self) -> Option<Vec<Duration>> {
+        let step = self.ppq()? as f64 / CLOCKS_PER_QUARTER as f64;
+        let count = (self.end as f64 / step).floor() as u64;
+        Some((0..=count).map(|i| self.time(i as f64 * step)).collect())
+    }
+}
+
+/// Returns the tempo set by a Set Tempo meta event
+fn tempo_of(data: &[u8]) -> Option<u32> {
+    match data {
+        [0xFF, 0x51, 0x03, a, b, c] => Some(u32::from_be_bytes([0, *a, *b, *c])),
+        _ => None,
+    }
+}
+
+/// The chunks of a file as their type and data. A chunk cut short ends the file
+struct Chunks<'a>(&'a [u8]);
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.0;
+        if bytes.len() < 8 {
+            return None;
+        }
+        let len = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        // Files cut short still play up to where they end
+        let end = bytes.len().min(8 + len);
+        self.0 = &bytes[end..];
+        Some((&bytes[..4], &bytes[8..end]))
+    }
+}
+
+/// Adds the events of a track with their ticks moved by `offset`, returning the tick of
+/// its end
+fn read_track(data: &[u8], offset: u32, events: &mut Vec<SmfEvent>) -> Result<u32, anyhow::Error> {
+    let short = || anyhow!("The track is cut short");
+    let mut tick = offset;
+    let mut running = None;
+    let mut i = 0;
+    while i < data.len() {
+        let (delta, n) = read_var_len(&data[i..]).ok_or_else(short)?;
+        tick = tick.saturating_add(delta);
+        i += n;
+        let status = *data.get(i).ok_or_else(short)?;
+        let start = i;
+        // Status of a channel message, whose data bytes are read below
+        let channel = match status {
+            0xFF | 0xF0 | 0xF7 => {
+                // Meta events and SysEx cancel running status
+                running = None;
+                let header = if status == 0xFF { 2 } else { 1 };
+                let (len, n) = data
+                    .get(i + header..)
+                    .and_then(read_var_len)
+                    .ok_or_else(short)?;
+                i += header + n + len as usize;
+                if status == 0xFF && data.get(start + 1) == Some(&0x2F) {
+                    return Ok(tick);
+                }
+                None
+            }
+            0x80..=0xEF => {
+                running = Some(status);
+                i += 1;
+                Some(status)
+            }
+            0xF1..=0xFE => bail!(
+                "Unexpected status {:02X} at byte {} of the track",
+                status,
+                start
+            ),
+            _ => Some(running.ok_or_else(|| {
+                anyhow!(
+                    "Data byte without a running status at byte {} of the track",
+                    start
+                )
+            })?),
+        };
+        let event = match channel {
+            None => data.get(start..i).ok_or_else(short)?.to_vec(),
+            Some(status) => {
+                let count = if matches!(status & 0xF0, 0xC0 | 0xD0) {
+                    1
+                } else {
+                    2
+                };
+                let bytes = data.get(i..i + count).ok_or_else(short)?;
+                i += count;
+                [&[status], bytes].concat()
+            }
+        };
+        events.push(SmfEvent { tick, data: event });
+    }
+    // Tracks without an End of Track end after their last event
+    Ok(tick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smf::{write_var_len, SmfTrack};
+
+    /// Wraps track data into a type 1 file with 96 ticks per quarter note
+    fn file(tracks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(6_u32.to_be_bytes());
+        bytes.extend([0, 1, 0, tracks.len() as u8, 0, 96]);
+        for track in tracks {
+            bytes.extend(b"MTrk");
+            bytes.extend((track.len() as u32).to_be_bytes());
+            bytes.extend(track);
+        }
+        bytes
+    }
+
+    #[test]
+    fn tempo_map() {
+        // A tempo of 60 after the first quarter note, and two notes under running status
+        let conductor = vec![
+            0x00, 0xFF, 0x03, 0x01, b'x', // track name
+            0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 1000000 us per quarter
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut notes = vec![0x00, 0x90, 60, 100, 0x60, 62, 100];
+        let mut delta = vec![];
+        write_var_len(&mut delta, 96);
+        notes.extend(&delta);
+        notes.extend([0xF0, 0x03, 0x7D, 0x01, 0xF7]);
+        notes.extend([0x00, 0xC1, 5, 0x30, 0xFF, 0x2F, 0x00]);
+        let song = SmfFile::parse(&file(&[conductor, notes])).unwrap();
+
+        assert_eq!(song.ppq(), Some(96));
+        let ms = |ms: u64| Duration::from_millis(ms);
+        assert_eq!(
+            song.messages(),
+            [
+                (ms(0), vec![0x90, 60, 100]),
+                (ms(500), vec![0x90, 62, 100]),
+                (ms(1500), vec![0xF0, 0x7D, 0x01, 0xF7]),
+                (ms(1500), vec![0xC1, 5]),
+            ]
+        );
+        assert_eq!(song.length(), ms(2000));
+        let clocks = song.clocks().unwrap();
+        assert_eq!(clocks.len(), 5 * 12 + 1);
+        assert_eq!(clocks[24], ms(500));
+        assert_eq!(clocks[36], ms(1000));
+        assert_eq!(clocks[60], ms(2000));
+
+        assert!(SmfFile::parse(&file(&[vec![0x00, 60, 100]])).is_err());
+        assert!(SmfFile::parse(&file(&[vec![0x00, 0x90, 60]])).is_err());
+    }
+
+    #[test]
+    fn written_files() {
+        let mut track = SmfTrack::new(480);
+        track.push_tempo(0, 250_000);
+        track.push_raw(480, vec![0xB0, 7, 100]);
+        let song = SmfFile::parse(&track.to_bytes()).unwrap();
+        assert_eq!(
+            song.messages(),
+            [(Duration::from_millis(250), vec![0xB0, 7, 100])]
+        );
+    }
+}
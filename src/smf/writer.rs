@@ -0,0 +1,179 @@
+//! Writer for type 0 Standard MIDI Files
+
+use crate::midi::MidiMessage;
+use anyhow::Context;
+use std::{fs::File, io::Write, path::Path};
+
+/// An event on the single track of a type 0 file, stored with its absolute tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmfEvent {
+    pub tick: u32,
+    pub data: Vec<u8>,
+}
+
+/// Builds the track of a type 0 Standard MIDI File
+#[derive(Debug)]
+pub struct SmfTrack {
+    ppq: u16,
+    events: Vec<SmfEvent>,
+}
+
+impl SmfTrack {
+    /// Creates an empty track with the given resolution in pulses per quarter note
+    pub fn new(ppq: u16) -> SmfTrack {
+        SmfTrack {
+            ppq,
+            events: vec![],
+        }
+    }
+
+    /// Adds a Set Tempo meta event
+    pub fn push_tempo(&mut self, tick: u32, tempo: u32) {
+        let t = tempo.to_be_bytes();
+        self.push_raw(tick, vec![0xFF, 0x51, 0x03, t[1], t[2], t[3]]);
+    }
+
+    /// Adds a MIDI message to the track.
+    ///
+    /// Returns `false` if the message cannot be stored in a Standard MIDI File
+    /// (System Real Time and System Common messages)
+    pub fn push_message(&mut self, tick: u32, message: &MidiMessage) -> bool {
+        match message {
+            MidiMessage::SystemExclusive(data) => {
+                let mut bytes = vec![0xF0];
+                write_var_len(&mut bytes, data.len() as u32 + 1);
+                bytes.extend_from_slice(data);
+                bytes.push(0xF7);
+                self.push_raw(tick, bytes);
+                true
+            }
+            MidiMessage::NoteOff { .. }
+            | MidiMessage::NoteOn { .. }
+            | MidiMessage::PolyPressure { .. }
+            | MidiMessage::ControlChange { .. }
+            | MidiMessage::ChannelMode { .. }
+            | MidiMessage::ProgramChange { .. }
+            | MidiMessage::ChannelPressure { .. }
+            | MidiMessage::PitchBend { .. } => {
+                self.push_raw(tick, message.clone().to_bytes());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds raw event bytes to the track
+    pub fn push_raw(&mut self, tick: u32, data: Vec<u8>) {
+        self.events.push(SmfEvent { tick, data });
+    }
+
+    /// Returns the resolution in pulses per quarter note
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    /// Returns the events of the track in tick order
+    pub fn sorted_events(&self) -> Vec<SmfEvent> {
+        let mut events = self.events.clone();
+        // Stable sort keeps the arrival order of simultaneous events
+        events.sort_by_key(|e| e.tick);
+        events
+    }
+
+    /// Serializes the track into a complete type 0 file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut track = vec![];
+        let mut last_tick = 0;
+        for event in self.sorted_events() {
+            write_var_len(&mut track, event.tick - last_tick);
+            track.extend_from_slice(&event.data);
+            last_tick = event.tick;
+        }
+        // End of Track
+        track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+        let mut file = vec![];
+        file.extend_from_slice(b"MThd");
+        file.extend_from_slice(&6_u32.to_be_bytes());
+        file.extend_from_slice(&0_u16.to_be_bytes());
+        file.extend_from_slice(&1_u16.to_be_bytes());
+        file.extend_from_slice(&self.ppq.to_be_bytes());
+        file.extend_from_slice(b"MTrk");
+        file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        file.extend_from_slice(&track);
+        file
+    }
+
+    /// Writes the track to a type 0 file at the given path
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut file = File::create(path).context(format!("Unable to create file `{:?}`", path))?;
+        file.write_all(&self.to_bytes())
+            .context(format!("Unable to write file `{:?}`", path))?;
+        Ok(())
+    }
+}
+
+/// Appends a variable length quantity
+pub fn write_var_len(out: &mut Vec<u8>, value: u32) {
+    let mut buffer = [0_u8; 5];
+    let mut n = 0;
+    let mut v = value;
+    loop {
+        buffer[n] = (v & 0x7F) as u8;
+        v >>= 7;
+        n += 1;
+        if v == 0 {
+            break;
+        }
+    }
+    for i in (0..n).rev() {
+        out.push(if i > 0 { buffer[i] | 0x80 } else { buffer[i] });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn var_len() {
+        for (value, expected) in [
+            (0x00_u32, vec![0x00_u8]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x2000, vec![0xC0, 0x00]),
+            (0x3FFF, vec![0xFF, 0x7F]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut out = vec![];
+            write_var_len(&mut out, value);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn track_layout() {
+        let mut track = SmfTrack::new(96);
+        track.push_tempo(0, 500_000);
+        track.push_message(
+            96,
+            &MidiMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 100,
+            },
+        );
+        assert!(!track.push_message(100, &MidiMessage::TimingClock));
+        let bytes = track.to_bytes();
+        assert_eq!(&bytes[0..4], b"MThd");
+        assert_eq!(&bytes[12..14], &[0, 96]);
+        assert_eq!(
+            &bytes[22..],
+            &[
+                0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // tempo
+                0x60, 0x90, 60, 100, // note on
+                0x00, 0xFF, 0x2F, 0x00, // end of track
+            ]
+        );
+    }
+}
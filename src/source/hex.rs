@@ -0,0 +1,96 @@
+//! Bytes written as hexadecimal text, as pasted from bug reports or printed by `amidi -d`
+
+use anyhow::bail;
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, ErrorKind, Read},
+};
+
+/// Parses hexadecimal bytes as pasted from logic analyzers and bug reports.
+/// Bytes may be separated by spaces, commas, or colons, carry a `0x` prefix,
+/// or be run together like `903C7F`
+pub fn parse(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut bytes = vec![];
+    for word in text.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("`{}` is not a hexadecimal byte", word);
+        }
+        for i in (0..digits.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&digits[i..i + 2], 16)?);
+        }
+    }
+    if bytes.is_empty() {
+        bail!("No bytes given");
+    }
+    Ok(bytes)
+}
+
+/// Reads lines of hexadecimal text as the bytes they spell. Each line is delivered as soon
+/// as it is complete, so a live dump keeps its timing. Blank lines are skipped
+pub struct HexLines<R> {
+    reader: R,
+    bytes: VecDeque<u8>,
+}
+
+impl<R: BufRead> HexLines<R> {
+    pub fn new(reader: R) -> HexLines<R> {
+        HexLines {
+            reader,
+            bytes: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Read for HexLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.bytes.is_empty() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(0);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bytes = parse(line.trim())
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("{:#}", e)))?;
+            self.bytes.extend(bytes);
+        }
+        let n = buf.len().min(self.bytes.len());
+        for (slot, byte) in buf.iter_mut().zip(self.bytes.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_dumps() {
+        let expected = vec![0x90, 0x3C, 0x7F, 0xF8];
+        assert_eq!(parse("90 3C 7F F8").unwrap(), expected);
+        assert_eq!(parse("0x90, 0x3c, 0x7F, 0xF8").unwrap(), expected);
+        assert_eq!(parse("90:3C:7F:F8").unwrap(), expected);
+        assert_eq!(parse("903C7FF8").unwrap(), expected);
+        assert!(parse("903").is_err());
+        assert!(parse("dump.bin").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn hex_lines() {
+        let mut bytes = vec![];
+        HexLines::new("90 3C 7F\n\nF8\n".as_bytes())
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, [0x90, 0x3C, 0x7F, 0xF8]);
+        let mut invalid = HexLines::new("90 3C 7F\nhello\n".as_bytes());
+        assert!(invalid.read_to_end(&mut bytes).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Line status of serial ports: bytes received with a framing or parity error, and breaks
+//!
+//! The terminal driver reports these in the byte stream itself once asked to mark them.
+//! A byte with an error is read as `FF 00 BYTE`, a break as `FF 00 00`, and an `FF` that
+//! arrived intact as `FF FF`
+
+use anyhow::Context;
+use std::fmt;
+
+/// What went wrong on the line while a byte was received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineError {
+    /// The stop bit or the parity bit was wrong, often because the baud rate does not match
+    Framing,
+    /// The line was held low for longer than a byte, such as a cable being unplugged. A
+    /// framing error on a `00` byte is reported the same way
+    Break,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::Framing => write!(f, "framing/parity error"),
+            LineError::Break => write!(f, "break"),
+        }
+    }
+}
+
+/// Splits a marked byte stream back into the received bytes and their errors
+#[derive(Debug, Default)]
+pub struct Unmarker {
+    /// Marker bytes read so far, `FF` or `FF 00`
+    marker: usize,
+}
+
+impl Unmarker {
+    pub fn new() -> Unmarker {
+        Unmarker::default()
+    }
+
+    /// Returns the received byte and its error once the byte read completes one
+    pub fn unmark(&mut self, byte: u8) -> Option<(u8, Option<LineError>)> {
+        match (self.marker, byte) {
+            (0, 0xFF) => {
+                self.marker = 1;
+                None
+            }
+            (0, byte) => Some((byte, None)),
+            (1, 0x00) => {
+                self.marker = 2;
+                None
+            }
+            // Only `FF` is escaped. Anything else after it is not a marker
+            (1, byte) => {
+                self.marker = 0;
+                Some((byte, None))
+            }
+            (_, 0x00) => {
+                self.marker = 0;
+                Some((0x00, Some(LineError::Break)))
+            }
+            (_, byte) => {
+                self.marker = 0;
+                Some((byte, Some(LineError::Framing)))
+            }
+        }
+    }
+}
+
+/// Asks the terminal driver of the port to mark the bytes received with errors instead of
+/// passing them on silently
+#[cfg(unix)]
+pub fn mark_errors(fd: std::os::unix::io::RawFd) -> Result<(), anyhow::Error> {
+    // SAFETY: the termios structure is filled by `tcgetattr` before it is read
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("Unable to read the line settings");
+        }
+        termios.c_iflag |= libc::INPCK | libc::PARMRK;
+        termios.c_iflag &= !(libc::IGNPAR | libc::IGNBRK | libc::BRKINT | libc::ISTRIP);
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("Unable to change the line settings");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmarks() {
+        let mut unmarker = Unmarker::new();
+        let marked = [0x90, 0xFF, 0xFF, 0xFF, 0x00, 0x3C, 0xFF, 0x00, 0x00, 0x64];
+        let bytes: Vec<(u8, Option<LineError>)> =
+            marked.iter().filter_map(|b| unmarker.unmark(*b)).collect();
+        assert_eq!(
+            bytes,
+            [
+                (0x90, None),
+                (0xFF, None),
+                (0x3C, Some(LineError::Framing)),
+                (0x00, Some(LineError::Break)),
+                (0x64, None),
+            ]
+        );
+    }
+}
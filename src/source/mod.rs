@@ -0,0 +1,863 @@
+//! Byte sources that feed the analyzer
+//!
+//! Each source runs on its own thread and delivers received bytes over a channel
+
+pub mod hex;
+pub mod linestatus;
+pub mod pcap;
+pub mod port;
+mod rtpmidi;
+pub mod server;
+
+use crate::{
+    capture::{format::CaptureReader, SENT},
+    midi,
+    smf::SmfFile,
+    syx,
+};
+use anyhow::{bail, Context};
+use serialport::SerialPort;
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Events delivered by a running source
+#[derive(Debug)]
+pub enum SourceEvent {
+    /// A byte was received
+    Byte {
+        /// Local time the byte arrived
+        arrival: Instant,
+        /// Time the byte was sent according to the source's own clock,
+        /// for protocols that carry timestamps
+        timestamp: Option<Duration>,
+        byte: u8,
+        /// Input the byte came from, when a source has several such as the agents of a
+        /// listening server
+        source: u8,
+    },
+    /// The device of the source went away, and the source keeps trying to open it again.
+    /// Bytes sent in the meantime are lost
+    Disconnected(String),
+    /// The device of the source is back after it was disconnected
+    Reconnected(String),
+    /// Something happened to the source that is worth telling, such as a remote agent that
+    /// connected
+    Notice(String),
+    /// The source reached its end and will not produce any more bytes
+    Closed,
+    /// The source failed and will not produce any more bytes
+    Error(String),
+}
+
+/// Where to read MIDI bytes from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// Raw MIDI bytes stored in a file
+    File(PathBuf),
+    /// Raw MIDI bytes appended to a file by another process, read as they are written
+    Follow(PathBuf),
+    /// A session file written by another miditerm with `--session`, read from its start and
+    /// then as the other miditerm appends to it, with the times it recorded
+    Tail(PathBuf),
+    /// Raw MIDI bytes piped into standard input
+    Stdin,
+    /// Lines of hexadecimal bytes piped into standard input, as printed by `amidi -d`
+    StdinHex,
+    /// Raw MIDI bytes given directly, such as from the command line
+    Bytes(Vec<u8>),
+    /// A `.syx` file of System Exclusive messages, validated before it is read
+    Syx(PathBuf),
+    /// A serial port, normally running at the MIDI baud rate
+    Serial { port: OsString, baud: u32 },
+    /// A recorded capture file played back with its original timing
+    Replay {
+        path: PathBuf,
+        /// Playback speed multiplier. Zero plays back as fast as possible
+        speed: f64,
+        /// Serial port that replayed bytes are also written to
+        output: Option<OsString>,
+    },
+    /// A Standard MIDI File played with the timing of its tempo map
+    Smf {
+        path: PathBuf,
+        /// Serial port or raw MIDI device the song is also written to
+        output: Option<OsString>,
+        /// Also send MIDI Timing Clock following the tempo, framed by Start and Stop
+        clock: bool,
+    },
+    /// USB-MIDI traffic extracted from a pcap or pcapng capture, delivered at once
+    /// with the capture times as source timestamps
+    Pcap {
+        path: PathBuf,
+        filter: pcap::UsbFilter,
+    },
+    /// Bytes pushed by remote capture agents to a TCP port (`tcp:HOST:PORT`) or Unix socket
+    /// (`unix:PATH`), with the timestamps of the agents as source timestamps
+    Listen(String),
+    /// An RTP-MIDI session, waiting for invitations on a UDP port (`5004`) or inviting the
+    /// device at `HOST:PORT`, with the RTP timestamps as source timestamps
+    RtpMidi(String),
+    /// Several sources read at once, such as a controller and a sequencer, the bytes of each
+    /// tagged with its position in the list
+    Inputs(Vec<Source>),
+}
+
+/// A running source that can be closed, such as a port picked in the TUI
+#[derive(Debug)]
+pub struct Connection {
+    /// Short name of the source
+    pub name: String,
+    pub events: Receiver<SourceEvent>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Drop for Connection {
+    /// Closes the source, so a serial port is free to be opened again at once rather than
+    /// once its next byte finds that nobody is receiving
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Source {
+    /// Opens the source and starts reading from it on a new thread
+    pub fn spawn(self) -> Result<Receiver<SourceEvent>, anyhow::Error> {
+        self.spawn_until(Arc::default())
+    }
+
+    /// Opens the source and starts reading from it on a new thread, until the connection
+    /// is dropped
+    pub fn connect(self) -> Result<Connection, anyhow::Error> {
+        let name = self.name();
+        let closed = Arc::new(AtomicBool::new(false));
+        Ok(Connection {
+            name,
+            events: self.spawn_until(closed.clone())?,
+            closed,
+        })
+    }
+
+    /// Opens the source and starts reading from it on a new thread. Serial ports are closed
+    /// once `closed` is set
+    fn spawn_until(self, closed: Arc<AtomicBool>) -> Result<Receiver<SourceEvent>, anyhow::Error> {
+        let (tx, rx) = mpsc::channel();
+        match self {
+            Source::File(path) => {
+                let file =
+                    File::open(&path).context(format!("Unable to open file `{:?}`", path))?;
+                thread::spawn(move || read_bytes(BufReader::new(file), tx));
+            }
+            Source::Follow(path) => {
+                let file =
+                    File::open(&path).context(format!("Unable to open file `{:?}`", path))?;
+                thread::spawn(move || read_bytes(Follow { file, position: 0 }, tx));
+            }
+            Source::Tail(path) => {
+                let file =
+                    File::open(&path).context(format!("Unable to open session `{:?}`", path))?;
+                thread::spawn(move || tail(Growing { file, position: 0 }, tx));
+            }
+            Source::Stdin => {
+                thread::spawn(move || read_bytes(io::stdin().lock(), tx));
+            }
+            Source::StdinHex => {
+                thread::spawn(move || read_bytes(hex::HexLines::new(io::stdin().lock()), tx));
+            }
+            Source::Bytes(bytes) => {
+                thread::spawn(move || read_bytes(bytes.as_slice(), tx));
+            }
+            Source::Syx(path) => {
+                let bytes: Vec<u8> = syx::load(&path)?
+                    .into_iter()
+                    .flat_map(|data| [vec![0xF0], data, vec![0xF7]].concat())
+                    .collect();
+                thread::spawn(move || read_bytes(bytes.as_slice(), tx));
+            }
+            Source::Serial { port, baud } => {
+                let serial = open_serial(&port, baud).context(format!(
+                    "Unable to open serial port `{}`",
+                    port.to_string_lossy()
+                ))?;
+                thread::spawn(move || read_serial(serial, &port, baud, tx, &closed));
+            }
+            Source::Replay {
+                path,
+                speed,
+                output,
+            } => {
+                let file =
+                    File::open(&path).context(format!("Unable to open file `{:?}`", path))?;
+                let reader = CaptureReader::new(BufReader::new(file))
+                    .context(format!("Unable to read capture `{:?}`", path))?;
+                let output = match output {
+                    Some(name) => Some(port::open(&name, midi::MIDI_BAUD_RATE).context(
+                        format!("Unable to open serial port `{}`", name.to_string_lossy()),
+                    )?),
+                    None => None,
+                };
+                thread::spawn(move || replay(reader, speed, output, tx));
+            }
+            Source::Smf {
+                path,
+                output,
+                clock,
+            } => {
+                let song = SmfFile::load(&path)?;
+                let schedule = schedule(&song, clock)
+                    .context(format!("Unable to send clock for `{:?}`", path))?;
+                let output = match output {
+                    Some(name) => Some(
+                        open_output(&name)
+                            .context(format!("Unable to open `{}`", name.to_string_lossy()))?,
+                    ),
+                    None => None,
+                };
+                thread::spawn(move || play(schedule, clock, output, tx));
+            }
+            Source::Pcap { path, filter } => {
+                let bytes = pcap::load(&path, filter)?;
+                thread::spawn(move || {
+                    let arrival = Instant::now();
+                    for (time, byte) in bytes {
+                        let event = SourceEvent::Byte {
+                            arrival,
+                            timestamp: Some(time),
+                            byte,
+                            source: 0,
+                        };
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    let _ = tx.send(SourceEvent::Closed);
+                });
+            }
+            Source::Listen(address) => server::listen(&address, tx)?,
+            Source::RtpMidi(target) => rtpmidi::spawn(&target, tx)?,
+            Source::Inputs(sources) => {
+                let open = Arc::new(AtomicUsize::new(sources.len()));
+                for (input, source) in sources.into_iter().enumerate() {
+                    let name = source.name();
+                    let rx = source.spawn_until(closed.clone())?;
+                    let (tx, open) = (tx.clone(), open.clone());
+                    thread::spawn(move || merge(input as u8, &name, rx, tx, &open));
+                }
+            }
+        }
+        Ok(rx)
+    }
+
+    /// Returns a short name of the source, such as the name of its file or device
+    pub fn name(&self) -> String {
+        let file_name = |path: &Path| {
+            path.file_name().map_or_else(
+                || path.display().to_string(),
+                |n| n.to_string_lossy().into(),
+            )
+        };
+        match self {
+            Source::File(path)
+            | Source::Follow(path)
+            | Source::Tail(path)
+            | Source::Syx(path)
+            | Source::Replay { path, .. }
+            | Source::Smf { path, .. }
+            | Source::Pcap { path, .. } => file_name(path),
+            Source::Stdin | Source::StdinHex => "stdin".to_string(),
+            Source::Bytes(_) => "bytes".to_string(),
+            Source::Serial { port, .. } => {
+                let name = port.to_string_lossy();
+                name.strip_prefix("/dev/").unwrap_or(&name).to_string()
+            }
+            Source::Listen(address) | Source::RtpMidi(address) => address.clone(),
+            Source::Inputs(sources) => {
+                let names: Vec<String> = sources.iter().map(Source::name).collect();
+                names.join(" + ")
+            }
+        }
+    }
+
+    /// Returns the names of the inputs the bytes of the source are tagged with, or `None`
+    /// if all of its bytes come from input 0
+    pub fn input_names(&self) -> Option<Vec<String>> {
+        match self {
+            Source::Inputs(sources) => Some(sources.iter().map(Source::name).collect()),
+            // Agents give their own source IDs
+            Source::Listen(_) => Some(vec![]),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a whole file at once, chosen by its extension: `.syx` dumps, `.mtcap` captures,
+/// `.mid` songs, or raw bytes. Each byte comes with the time it was recorded or is due in
+/// the song, and dumps and raw bytes all come at time zero
+pub fn load_file(path: &Path) -> Result<Vec<(Duration, u8)>, anyhow::Error> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let at_zero = |bytes: Vec<u8>| bytes.into_iter().map(|b| (Duration::ZERO, b)).collect();
+    match extension.as_deref() {
+        Some("syx") => Ok(at_zero(
+            syx::load(path)?
+                .into_iter()
+                .flat_map(|data| [vec![0xF0], data, vec![0xF7]].concat())
+                .collect(),
+        )),
+        Some("mtcap") => {
+            let file = File::open(path).context(format!("Unable to open file `{:?}`", path))?;
+            CaptureReader::new(BufReader::new(file))
+                .and_then(|reader| reader.collect())
+                .context(format!("Unable to read capture `{:?}`", path))
+        }
+        Some("mid" | "midi") => Ok(SmfFile::load(path)?
+            .messages()
+            .into_iter()
+            .flat_map(|(time, bytes)| bytes.into_iter().map(move |b| (time, b)))
+            .collect()),
+        _ => Ok(at_zero(
+            fs::read(path).context(format!("Unable to read file `{:?}`", path))?,
+        )),
+    }
+}
+
+/// Opens a raw MIDI device node (`/dev/snd/midiC1D0`) as a file, and anything else as a
+/// serial port, to write to
+pub fn open_output(name: &OsStr) -> Result<Box<dyn Write + Send>, anyhow::Error> {
+    if Path::new(name).starts_with("/dev/snd") {
+        return Ok(Box::new(OpenOptions::new().write(true).open(name)?));
+    }
+    Ok(port::open(name, midi::MIDI_BAUD_RATE)?)
+}
+
+/// The reading and the writing side of a port
+pub type Duplex = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+/// Opens a port to both read and write, such as to send a request and wait for the reply. A
+/// raw MIDI device node is opened as a file, and anything else as a serial port at `baud`
+pub fn open_duplex(name: &OsStr, baud: u32) -> Result<Duplex, anyhow::Error> {
+    if Path::new(name).starts_with("/dev/snd") {
+        let file = OpenOptions::new().read(true).write(true).open(name)?;
+        return Ok((Box::new(file.try_clone()?), Box::new(file)));
+    }
+    let mut port = port::open(name, baud)?;
+    port.set_timeout(Duration::from_millis(100))?;
+    Ok((port.try_clone()?, port))
+}
+
+/// Reads bytes from the reading side of a port on a new thread
+pub fn spawn_reader(reader: Box<dyn Read + Send>) -> Receiver<SourceEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || read_bytes(reader, tx));
+    rx
+}
+
+/// Returns the name of an input, or its number for inputs without a name. Messages sent by
+/// miditerm itself are named `Sent`
+pub fn input_name(names: &[String], source: u8) -> String {
+    if source == SENT {
+        return "Sent".to_string();
+    }
+    match names.get(source as usize) {
+        Some(name) => name.clone(),
+        None => format!("#{}", source),
+    }
+}
+
+/// Forwards the events of one of several inputs tagged with its number. The source only
+/// closes once every input has closed, and the end of the others is reported as a notice
+fn merge(
+    input: u8,
+    name: &str,
+    rx: Receiver<SourceEvent>,
+    tx: Sender<SourceEvent>,
+    open: &AtomicUsize,
+) {
+    let end = loop {
+        let event = match rx.recv() {
+            Ok(SourceEvent::Byte {
+                arrival,
+                timestamp,
+                byte,
+                ..
+            }) => SourceEvent::Byte {
+                arrival,
+                timestamp,
+                byte,
+                source: input,
+            },
+            Ok(SourceEvent::Closed) | Err(_) => break None,
+            Ok(SourceEvent::Error(e)) => break Some(e),
+            Ok(event) => event,
+        };
+        if tx.send(event).is_err() {
+            return;
+        }
+    };
+    let last = open.fetch_sub(1, Ordering::SeqCst) == 1;
+    let event = match end {
+        Some(e) if last => SourceEvent::Error(format!("{}: {}", name, e)),
+        None if last => SourceEvent::Closed,
+        Some(e) => SourceEvent::Notice(format!("{}: {}", name, e)),
+        None => SourceEvent::Notice(format!("{} closed", name)),
+    };
+    let _ = tx.send(event);
+}
+
+/// How reading from a source stopped
+enum End {
+    /// The reader has no more bytes
+    Exhausted,
+    Failed(io::Error),
+    /// The receiver of the events hung up
+    HungUp,
+}
+
+/// Forwards bytes until the reader is exhausted or fails, or the receiver hangs up
+fn forward<R: Read>(reader: &mut R, tx: &Sender<SourceEvent>) -> End {
+    let mut buffer = [0_u8; 256];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return End::Exhausted,
+            Ok(n) => {
+                let now = Instant::now();
+                for byte in &buffer[..n] {
+                    let event = SourceEvent::Byte {
+                        arrival: now,
+                        timestamp: None,
+                        byte: *byte,
+                        source: 0,
+                    };
+                    if tx.send(event).is_err() {
+                        return End::HungUp;
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return End::Failed(e),
+        }
+    }
+}
+
+/// Reads bytes until the reader is exhausted or the receiver hangs up
+fn read_bytes<R: Read>(mut reader: R, tx: Sender<SourceEvent>) {
+    let event = match forward(&mut reader, &tx) {
+        End::Exhausted => SourceEvent::Closed,
+        End::Failed(e) => SourceEvent::Error(format!("IO Error while reading: {:?}", e)),
+        End::HungUp => return,
+    };
+    let _ = tx.send(event);
+}
+
+/// First wait before a disconnected serial port is opened again
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+/// Longest wait between attempts to open a disconnected serial port
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How often a serial port waiting for bytes checks whether it was closed
+const CLOSE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Opens a serial port for reading
+fn open_serial(name: &OsStr, baud: u32) -> Result<Box<dyn SerialPort>, anyhow::Error> {
+    let mut serial = port::open(name, baud)?;
+    serial.set_timeout(CLOSE_INTERVAL)?;
+    Ok(serial)
+}
+
+/// A serial port that reads as exhausted once it is closed
+struct Closable<'a> {
+    serial: &'a mut Box<dyn SerialPort>,
+    closed: &'a AtomicBool,
+}
+
+impl Read for Closable<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.closed.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+            match self.serial.read(buf) {
+                Err(e) if e.kind() == ErrorKind::TimedOut => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Reads bytes from a serial port until the receiver hangs up or the port is closed. A port
+/// that goes away, such as an unplugged USB adapter, is opened again as soon as it is back
+fn read_serial(
+    mut serial: Box<dyn SerialPort>,
+    port: &OsStr,
+    baud: u32,
+    tx: Sender<SourceEvent>,
+    closed: &AtomicBool,
+) {
+    let name = port.to_string_lossy();
+    loop {
+        let mut reader = Closable {
+            serial: &mut serial,
+            closed,
+        };
+        let reason = match forward(&mut reader, &tx) {
+            _ if closed.load(Ordering::Relaxed) => return,
+            End::Exhausted => "end of stream".to_string(),
+            End::Failed(e) => e.to_string(),
+            End::HungUp => return,
+        };
+        let event = SourceEvent::Disconnected(format!(
+            "Port `{}` disconnected ({}), reconnecting",
+            name, reason
+        ));
+        if tx.send(event).is_err() {
+            return;
+        }
+        // Closing the old port lets the device come back under the same name
+        drop(serial);
+        // Back off so a missing device does not keep the thread busy
+        let mut delay = RECONNECT_DELAY;
+        serial = loop {
+            thread::sleep(delay);
+            if closed.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Ok(serial) = open_serial(port, baud) {
+                break serial;
+            }
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        };
+        let event = SourceEvent::Reconnected(format!("Port `{}` reconnected", name));
+        if tx.send(event).is_err() {
+            return;
+        }
+    }
+}
+
+/// How long a followed file is left alone after its end was reached
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A file that is still being written, like `tail -f`. Reading at its end waits for more
+/// bytes instead of ending, and a file truncated by its writer is read again from the start
+struct Follow {
+    file: File,
+    position: u64,
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read(buf)?;
+        if n > 0 {
+            self.position += n as u64;
+            return Ok(n);
+        }
+        if self.file.metadata()?.len() < self.position {
+            self.position = self.file.seek(SeekFrom::Start(0))?;
+        }
+        thread::sleep(FOLLOW_INTERVAL);
+        // Lets `read_bytes` try again
+        Err(ErrorKind::TimedOut.into())
+    }
+}
+
+/// A capture file that is still being written. Reading at its end waits for more bytes, so
+/// records are never cut short by the writer being in the middle of one
+struct Growing {
+    file: File,
+    position: u64,
+}
+
+impl Read for Growing {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 {
+                self.position += n as u64;
+                return Ok(n);
+            }
+            if self.file.metadata()?.len() < self.position {
+                return Err(io::Error::other("the file was truncated by its writer"));
+            }
+            thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+/// Sends the bytes of a session file as they are appended to it, with the times they were
+/// recorded at as source timestamps. They are taken to have arrived at those times, so the
+/// bytes already in the file keep their spacing
+fn tail<R: Read>(reader: R, tx: Sender<SourceEvent>) {
+    let reader = match CaptureReader::new(reader) {
+        Ok(reader) => reader,
+        Err(e) => {
+            let _ = tx.send(SourceEvent::Error(format!("{:#}", e)));
+            return;
+        }
+    };
+    let start = Instant::now();
+    for record in reader {
+        let event = match record {
+            Ok((time, byte)) => SourceEvent::Byte {
+                arrival: start + time,
+                timestamp: Some(time),
+                byte,
+                source: 0,
+            },
+            Err(e) => SourceEvent::Error(format!("{:#}", e)),
+        };
+        let failed = matches!(event, SourceEvent::Error(_));
+        if tx.send(event).is_err() || failed {
+            return;
+        }
+    }
+    let _ = tx.send(SourceEvent::Closed);
+}
+
+/// Sends the bytes of a capture file at their original times, scaled by `speed`.
+/// The original times are delivered as source timestamps
+fn replay<R: Read, W: Write>(
+    reader: CaptureReader<R>,
+    speed: f64,
+    mut output: Option<W>,
+    tx: Sender<SourceEvent>,
+) {
+    let start = Instant::now();
+    for record in reader {
+        let (time, byte) = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let _ = tx.send(SourceEvent::Error(format!("{:#}", e)));
+                return;
+            }
+        };
+        if speed > 0.0 {
+            let due = Duration::try_from_secs_f64(time.as_secs_f64() / speed)
+                .ok()
+                .and_then(|time| start.checked_add(time));
+            let Some(due) = due else {
+                let _ = tx.send(SourceEvent::Error(format!(
+                    "The speed is too slow to replay {:?}",
+                    time
+                )));
+                return;
+            };
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        if let Some(port) = &mut output {
+            if let Err(e) = port.write_all(&[byte]) {
+                let _ = tx.send(SourceEvent::Error(format!(
+                    "IO Error while writing: {:?}",
+                    e
+                )));
+                return;
+            }
+        }
+        let event = SourceEvent::Byte {
+            arrival: Instant::now(),
+            timestamp: Some(time),
+            byte,
+            source: 0,
+        };
+        if tx.send(event).is_err() {
+            return;
+        }
+    }
+    let _ = tx.send(SourceEvent::Closed);
+}
+
+/// Returns the bytes of a song to send at each time, with clocks before the messages at the
+/// same time
+fn schedule(song: &SmfFile, clock: bool) -> Result<Vec<(Duration, Vec<u8>)>, anyhow::Error> {
+    let mut schedule = vec![];
+    if clock {
+        let Some(clocks) = song.clocks() else {
+            bail!("The song is timed in SMPTE frames, which have no beats to clock");
+        };
+        schedule.push((Duration::ZERO, vec![0xFA]));
+        schedule.extend(clocks.into_iter().map(|time| (time, vec![0xF8])));
+    }
+    schedule.extend(song.messages());
+    // Stable sort keeps the clocks first
+    schedule.sort_by_key(|(time, _)| *time);
+    if clock {
+        schedule.push((song.length(), vec![0xFC]));
+    }
+    Ok(schedule)
+}
+
+/// Sends the bytes of a song at their times, with the times as source timestamps. A song
+/// stopped before its end silences every channel, and stops the receiver of the clock
+fn play<W: Write>(
+    schedule: Vec<(Duration, Vec<u8>)>,
+    clock: bool,
+    mut output: Option<W>,
+    tx: Sender<SourceEvent>,
+) {
+    let start = Instant::now();
+    for (time, bytes) in schedule {
+        let due = start + time;
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+        if let Some(port) = &mut output {
+            if let Err(e) = port.write_all(&bytes).and_then(|_| port.flush()) {
+                let _ = tx.send(SourceEvent::Error(format!(
+                    "IO Error while writing: {:?}",
+                    e
+                )));
+                return;
+            }
+        }
+        let arrival = Instant::now();
+        for byte in bytes {
+            let event = SourceEvent::Byte {
+                arrival,
+                timestamp: Some(time),
+                byte,
+                source: 0,
+            };
+            if tx.send(event).is_err() {
+                if let Some(port) = &mut output {
+                    // All Notes Off on every channel
+                    let mut silence: Vec<u8> = (0..16).flat_map(|c| [0xB0 | c, 123, 0]).collect();
+                    if clock {
+                        silence.push(0xFC);
+                    }
+                    let _ = port.write_all(&silence).and_then(|_| port.flush());
+                }
+                return;
+            }
+        }
+    }
+    let _ = tx.send(SourceEvent::Closed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::format::CaptureWriter;
+    use std::env;
+
+    /// Waits for the next byte of a source, with its timestamp
+    fn next_byte(events: &Receiver<SourceEvent>) -> (Option<Duration>, u8) {
+        match events.recv_timeout(Duration::from_secs(5)) {
+            Ok(SourceEvent::Byte {
+                timestamp, byte, ..
+            }) => (timestamp, byte),
+            event => panic!("{:?}", event),
+        }
+    }
+
+    /// Reads the given results one call at a time
+    struct Script(Vec<io::Result<u8>>);
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.is_empty() {
+                true => Ok(0),
+                false => self.0.remove(0).map(|byte| {
+                    buf[0] = byte;
+                    1
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_disconnections() {
+        let (tx, events) = mpsc::channel();
+        // Timeouts of a port waiting for bytes are not disconnections
+        let mut port = Script(vec![
+            Ok(0x90),
+            Err(ErrorKind::TimedOut.into()),
+            Ok(60),
+            Err(ErrorKind::BrokenPipe.into()),
+            Ok(100),
+        ]);
+        assert!(
+            matches!(forward(&mut port, &tx), End::Failed(e) if e.kind() == ErrorKind::BrokenPipe)
+        );
+        assert_eq!(next_byte(&events).1, 0x90);
+        assert_eq!(next_byte(&events).1, 60);
+        assert!(events.try_recv().is_err());
+        // The rest is read once the port is back
+        assert!(matches!(forward(&mut port, &tx), End::Exhausted));
+        assert_eq!(next_byte(&events).1, 100);
+        drop(events);
+        let mut port = Script(vec![Ok(0xF8)]);
+        assert!(matches!(forward(&mut port, &tx), End::HungUp));
+    }
+
+    #[test]
+    fn replays_at_any_speed() {
+        let mut capture = vec![];
+        let mut writer = CaptureWriter::new(&mut capture).unwrap();
+        writer.write(Duration::ZERO, 0xFA).unwrap();
+        writer.write(Duration::from_secs(1), 0xF8).unwrap();
+        // Too slow for the second byte to ever be due
+        let (tx, events) = mpsc::channel();
+        let reader = CaptureReader::new(&capture[..]).unwrap();
+        replay(reader, 1e-300, None::<File>, tx);
+        assert_eq!(next_byte(&events).1, 0xFA);
+        assert!(matches!(events.recv(), Ok(SourceEvent::Error(_))));
+
+        let (tx, events) = mpsc::channel();
+        let reader = CaptureReader::new(&capture[..]).unwrap();
+        replay(reader, 0.0, None::<File>, tx);
+        assert_eq!(next_byte(&events).1, 0xFA);
+        assert_eq!(next_byte(&events).1, 0xF8);
+        assert!(matches!(events.recv(), Ok(SourceEvent::Closed)));
+    }
+
+    #[test]
+    fn tails_sessions() {
+        let path = env::temp_dir().join(format!("miditerm-tail-{}.mtcap", std::process::id()));
+        let mut session = CaptureWriter::new(File::create(&path).unwrap()).unwrap();
+        session.write(Duration::from_millis(10), 0xF8).unwrap();
+        session.flush().unwrap();
+        let events = Source::Tail(path.clone()).spawn().unwrap();
+        assert_eq!(next_byte(&events), (Some(Duration::from_millis(10)), 0xF8));
+
+        // A record written in two goes is read whole
+        let mut record = vec![];
+        CaptureWriter::resume(&mut record, Duration::from_millis(10))
+            .write(Duration::from_millis(30), 0xFA)
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record[..1]).unwrap();
+        thread::sleep(FOLLOW_INTERVAL * 2);
+        assert!(events.try_recv().is_err());
+        file.write_all(&record[1..]).unwrap();
+        assert_eq!(next_byte(&events), (Some(Duration::from_millis(30)), 0xFA));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn follows_files() {
+        let path = env::temp_dir().join(format!("miditerm-follow-{}.mid", std::process::id()));
+        fs::write(&path, [0x90, 60]).unwrap();
+        let events = Source::Follow(path.clone()).spawn().unwrap();
+        assert_eq!(next_byte(&events).1, 0x90);
+        assert_eq!(next_byte(&events).1, 60);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[100]).unwrap();
+        assert_eq!(next_byte(&events).1, 100);
+        // Started again by its writer, the file is read from its start
+        fs::write(&path, [0xF8]).unwrap();
+        assert_eq!(next_byte(&events).1, 0xF8);
+        fs::remove_file(&path).unwrap();
+    }
+}
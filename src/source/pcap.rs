@@ -0,0 +1,416 @@
+//! Extraction of USB-MIDI traffic from Wireshark pcap and pcapng captures
+//!
+//! Supports captures made with Linux usbmon and Windows USBPcap. Bulk and interrupt
+//! transfers are decoded as USB-MIDI event packets, so captures should be filtered to the
+//! MIDI device with `device` when other USB devices share the bus
+
+use anyhow::{anyhow, bail, Context};
+use std::{fs, path::Path, str::FromStr, time::Duration};
+
+// Link types
+const LINKTYPE_USB_LINUX: u16 = 189;
+const LINKTYPE_USB_LINUX_MMAPPED: u16 = 220;
+const LINKTYPE_USBPCAP: u16 = 249;
+
+// pcapng block types
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE: u32 = 0x0000_0001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPTION_TSRESOL: u16 = 9;
+
+// USB transfer types
+const TRANSFER_INTERRUPT: u8 = 1;
+const TRANSFER_BULK: u8 = 3;
+
+/// Number of MIDI bytes in a USB-MIDI event packet for each Code Index Number
+const CIN_LENGTHS: [usize; 16] = [0, 0, 2, 3, 3, 1, 2, 3, 3, 3, 3, 3, 2, 2, 3, 1];
+
+/// Which direction of USB traffic to extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the device to the host (MIDI Out of the device)
+    In,
+    /// From the host to the device (MIDI In of the device)
+    Out,
+}
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in" => Ok(Direction::In),
+            "out" => Ok(Direction::Out),
+            _ => Err(anyhow!("Unknown USB direction `{}`", s)),
+        }
+    }
+}
+
+/// Selects the USB traffic to decode as MIDI
+#[derive(Debug, Clone, Copy)]
+pub struct UsbFilter {
+    pub direction: Direction,
+    /// Device address on the bus, or any device if `None`
+    pub device: Option<u16>,
+}
+
+/// A captured USB packet
+#[derive(Debug)]
+struct Packet<'a> {
+    link_type: u16,
+    time: Duration,
+    data: &'a [u8],
+}
+
+/// Reads a capture file and returns the MIDI bytes it contains with their capture times,
+/// relative to the first packet of the file
+pub fn load(path: &Path, filter: UsbFilter) -> Result<Vec<(Duration, u8)>, anyhow::Error> {
+    let bytes = fs::read(path).context(format!("Unable to read file `{:?}`", path))?;
+    extract(&bytes, filter).context(format!("Unable to decode capture `{:?}`", path))
+}
+
+/// Extracts MIDI bytes from the contents of a pcap or pcapng file
+pub fn extract(bytes: &[u8], filter: UsbFilter) -> Result<Vec<(Duration, u8)>, anyhow::Error> {
+    let packets = match bytes.get(0..4) {
+        Some([0x0A, 0x0D, 0x0D, 0x0A]) => pcapng_packets(bytes)?,
+        Some(_) => pcap_packets(bytes)?,
+        None => bail!("File is too short to be a capture"),
+    };
+    let start = packets.first().map_or(Duration::ZERO, |p| p.time);
+    let mut midi = vec![];
+    for packet in packets {
+        let Some(payload) = usb_payload(&packet, filter) else {
+            continue;
+        };
+        let time = packet.time.saturating_sub(start);
+        midi.extend(usb_midi_bytes(payload).into_iter().map(|b| (time, b)));
+    }
+    Ok(midi)
+}
+
+/// Reads integers in the byte order of the capture
+#[derive(Clone, Copy)]
+struct Endian(bool);
+
+impl Endian {
+    fn u16(self, b: &[u8], at: usize) -> Option<u16> {
+        let v = b.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.0 {
+            u16::from_be_bytes(v)
+        } else {
+            u16::from_le_bytes(v)
+        })
+    }
+
+    fn u32(self, b: &[u8], at: usize) -> Option<u32> {
+        let v = b.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.0 {
+            u32::from_be_bytes(v)
+        } else {
+            u32::from_le_bytes(v)
+        })
+    }
+}
+
+/// Splits a classic pcap file into packets
+fn pcap_packets(bytes: &[u8]) -> Result<Vec<Packet<'_>>, anyhow::Error> {
+    let (endian, nanos) = match bytes.get(0..4) {
+        Some([0xD4, 0xC3, 0xB2, 0xA1]) => (Endian(false), false),
+        Some([0xA1, 0xB2, 0xC3, 0xD4]) => (Endian(true), false),
+        Some([0x4D, 0x3C, 0xB2, 0xA1]) => (Endian(false), true),
+        Some([0xA1, 0xB2, 0x3C, 0x4D]) => (Endian(true), true),
+        _ => bail!("Not a pcap or pcapng file"),
+    };
+    let link_type = endian.u32(bytes, 20).context("Truncated pcap header")? as u16;
+    let mut packets = vec![];
+    let mut offset = 24;
+    while offset + 16 <= bytes.len() {
+        let seconds = endian.u32(bytes, offset).unwrap_or(0) as u64;
+        let fraction = endian.u32(bytes, offset + 4).unwrap_or(0) as u64;
+        let length = endian.u32(bytes, offset + 8).unwrap_or(0) as usize;
+        let data = bytes
+            .get(offset + 16..offset + 16 + length)
+            .context("Truncated pcap packet")?;
+        let time = Duration::from_secs(seconds)
+            + if nanos {
+                Duration::from_nanos(fraction)
+            } else {
+                Duration::from_micros(fraction)
+            };
+        packets.push(Packet {
+            link_type,
+            time,
+            data,
+        });
+        offset += 16 + length;
+    }
+    Ok(packets)
+}
+
+/// Splits a pcapng file into packets
+fn pcapng_packets(bytes: &[u8]) -> Result<Vec<Packet<'_>>, anyhow::Error> {
+    let mut endian = Endian(false);
+    // Link type and timestamp resolution of each interface in the current section
+    let mut interfaces: Vec<(u16, Resolution)> = vec![];
+    let mut packets = vec![];
+    let mut offset = 0;
+    while offset + 12 <= bytes.len() {
+        let block_type = endian.u32(bytes, offset).unwrap_or(0);
+        if block_type == BLOCK_SECTION_HEADER {
+            endian = match Endian(false).u32(bytes, offset + 8) {
+                Some(BYTE_ORDER_MAGIC) => Endian(false),
+                Some(magic) if magic.swap_bytes() == BYTE_ORDER_MAGIC => Endian(true),
+                _ => bail!("Invalid pcapng section header at offset {}", offset),
+            };
+            interfaces.clear();
+        }
+        let length = endian.u32(bytes, offset + 4).unwrap_or(0) as usize;
+        if length < 12 || offset + length > bytes.len() {
+            bail!("Invalid pcapng block length at offset {}", offset);
+        }
+        let body = &bytes[offset + 8..offset + length - 4];
+        match block_type {
+            BLOCK_INTERFACE => {
+                let link_type = endian.u16(body, 0).unwrap_or(0);
+                let options = body
+                    .get(8..)
+                    .context("Truncated pcapng interface description")?;
+                interfaces.push((link_type, Resolution::from_options(endian, options)));
+            }
+            BLOCK_ENHANCED_PACKET => {
+                let interface = endian.u32(body, 0).unwrap_or(0) as usize;
+                let (link_type, resolution) = *interfaces
+                    .get(interface)
+                    .context("Packet refers to an unknown interface")?;
+                let high = endian.u32(body, 4).unwrap_or(0) as u64;
+                let low = endian.u32(body, 8).unwrap_or(0) as u64;
+                let captured = endian.u32(body, 12).unwrap_or(0) as usize;
+                let data = body
+                    .get(20..20 + captured)
+                    .context("Truncated pcapng packet")?;
+                packets.push(Packet {
+                    link_type,
+                    time: resolution.to_duration((high << 32) | low),
+                    data,
+                });
+            }
+            _ => {}
+        }
+        offset += length;
+    }
+    Ok(packets)
+}
+
+/// Timestamp resolution of a pcapng interface
+#[derive(Debug, Clone, Copy)]
+enum Resolution {
+    /// Units of 10^-n seconds
+    Decimal(u32),
+    /// Units of 2^-n seconds
+    Binary(u32),
+}
+
+impl Resolution {
+    /// Reads the `if_tsresol` option, defaulting to microseconds
+    fn from_options(endian: Endian, mut options: &[u8]) -> Resolution {
+        while let (Some(code), Some(length)) = (endian.u16(options, 0), endian.u16(options, 2)) {
+            let length = length as usize;
+            if code == OPTION_TSRESOL && length == 1 {
+                let Some(&value) = options.get(4) else {
+                    break;
+                };
+                return if value & 0x80 == 0 {
+                    Resolution::Decimal(value as u32)
+                } else {
+                    Resolution::Binary((value & 0x7F) as u32)
+                };
+            }
+            let padded = 4 + length.div_ceil(4) * 4;
+            if code == 0 || padded > options.len() {
+                break;
+            }
+            options = &options[padded..];
+        }
+        Resolution::Decimal(6)
+    }
+
+    fn to_duration(self, ticks: u64) -> Duration {
+        let nanos = match self {
+            Resolution::Decimal(n) if n <= 9 => ticks as u128 * 10_u128.pow(9 - n),
+            Resolution::Decimal(n) => ticks as u128 / 10_u128.pow(n.min(38) - 9),
+            Resolution::Binary(n) => (ticks as u128 * 1_000_000_000) >> n.min(64),
+        };
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+/// Returns the data of a bulk or interrupt transfer matching the filter
+fn usb_payload<'a>(packet: &Packet<'a>, filter: UsbFilter) -> Option<&'a [u8]> {
+    let data = packet.data;
+    let (header, transfer, endpoint, device, completion) = match packet.link_type {
+        LINKTYPE_USB_LINUX | LINKTYPE_USB_LINUX_MMAPPED => {
+            let header = if packet.link_type == LINKTYPE_USB_LINUX {
+                48
+            } else {
+                64
+            };
+            let completion = *data.get(8)? == b'C';
+            let [transfer, endpoint, device] = *data.get(9..12)? else {
+                return None;
+            };
+            (header, transfer, endpoint, device as u16, completion)
+        }
+        LINKTYPE_USBPCAP => {
+            let header = u16::from_le_bytes(data.get(0..2)?.try_into().ok()?) as usize;
+            let device = u16::from_le_bytes(data.get(19..21)?.try_into().ok()?);
+            let completion = data.get(16)? & 0x01 != 0;
+            (header, *data.get(22)?, *data.get(21)?, device, completion)
+        }
+        _ => return None,
+    };
+    if transfer != TRANSFER_BULK && transfer != TRANSFER_INTERRUPT {
+        return None;
+    }
+    if filter.device.is_some_and(|d| d != device) {
+        return None;
+    }
+    // Data travels with the completion for IN transfers and with the submission for OUT
+    let direction = if endpoint & 0x80 != 0 {
+        Direction::In
+    } else {
+        Direction::Out
+    };
+    if direction != filter.direction || completion != (direction == Direction::In) {
+        return None;
+    }
+    data.get(header..).filter(|d| !d.is_empty())
+}
+
+/// Decodes USB-MIDI event packets into a MIDI byte stream
+pub fn usb_midi_bytes(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|packet| {
+            let length = CIN_LENGTHS[(packet[0] & 0x0F) as usize];
+            packet[1..1 + length].to_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usb_midi_packets() {
+        let data = [
+            0x09, 0x90, 0x3C, 0x7F, // Note On
+            0x0F, 0xF8, 0x00, 0x00, // Timing Clock
+            0x04, 0xF0, 0x7E, 0x7F, // SysEx start
+            0x06, 0x01, 0xF7, 0x00, // SysEx end with 2 bytes
+            0x00, 0x00, 0x00, 0x00, // Padding
+        ];
+        assert_eq!(
+            usb_midi_bytes(&data),
+            vec![0x90, 0x3C, 0x7F, 0xF8, 0xF0, 0x7E, 0x7F, 0x01, 0xF7]
+        );
+    }
+
+    /// Builds a usbmon mmapped packet for a bulk transfer completion on endpoint 0x81
+    fn usbmon_packet(device: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0_u8; 64];
+        packet[8] = b'C';
+        packet[9] = TRANSFER_BULK;
+        packet[10] = 0x81;
+        packet[11] = device;
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let length = (12 + body.len()) as u32;
+        [
+            &block_type.to_le_bytes()[..],
+            &length.to_le_bytes(),
+            body,
+            &length.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn pcapng_usbmon() {
+        let mut file = block(
+            BLOCK_SECTION_HEADER,
+            &[
+                0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+        );
+        let mut interface = vec![];
+        interface.extend_from_slice(&LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+        interface.extend_from_slice(&[0, 0, 0, 0, 4, 0]);
+        file.extend(block(BLOCK_INTERFACE, &interface));
+        for (micros, device) in [(1_000_000_u64, 5_u8), (1_002_500, 5), (1_003_000, 6)] {
+            let packet = usbmon_packet(device, &[0x09, 0x90, 0x3C, 0x7F]);
+            let mut body = vec![];
+            body.extend_from_slice(&0_u32.to_le_bytes());
+            body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(micros as u32).to_le_bytes());
+            body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            body.extend_from_slice(&packet);
+            file.extend(block(BLOCK_ENHANCED_PACKET, &body));
+        }
+
+        let filter = UsbFilter {
+            direction: Direction::In,
+            device: Some(5),
+        };
+        let midi = extract(&file, filter).unwrap();
+        assert_eq!(midi.len(), 6);
+        assert_eq!(midi[3], (Duration::from_micros(2_500), 0x90));
+
+        let filter = UsbFilter {
+            direction: Direction::Out,
+            device: None,
+        };
+        assert!(extract(&file, filter).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated_captures() {
+        let filter = UsbFilter {
+            direction: Direction::In,
+            device: None,
+        };
+        // A usbmon packet cut off after the event type
+        let mut pcap = vec![0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0];
+        pcap.extend([0; 12]);
+        pcap.extend((LINKTYPE_USB_LINUX as u32).to_le_bytes());
+        pcap.extend([0; 8]);
+        pcap.extend(10_u32.to_le_bytes());
+        pcap.extend(10_u32.to_le_bytes());
+        pcap.extend(&usbmon_packet(5, &[])[..10]);
+        assert!(extract(&pcap, filter).unwrap().is_empty());
+        // The packet runs past the end of the file
+        assert!(extract(&pcap[..pcap.len() - 1], filter).is_err());
+
+        let section = block(
+            BLOCK_SECTION_HEADER,
+            &[
+                0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+        );
+        // An interface description without its snap length
+        let mut file = section.clone();
+        file.extend(block(BLOCK_INTERFACE, &[220, 0, 0, 0]));
+        assert!(extract(&file, filter).is_err());
+        // A timestamp resolution option without its value
+        let mut file = section;
+        let mut interface = vec![220, 0, 0, 0, 0, 0, 0, 0];
+        interface.extend(OPTION_TSRESOL.to_le_bytes());
+        interface.extend(1_u16.to_le_bytes());
+        file.extend(block(BLOCK_INTERFACE, &interface));
+        assert!(extract(&file, filter).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Names of serial ports and raw MIDI devices
+//!
+//! A port is named by its path, kept as an `OsString` so device nodes whose names are not
+//! UTF-8 still open, or by the USB identity of its interface, `usb:VID:PID` followed by
+//! `:SERIAL` when the interface has a serial number. The identity stays the same when the
+//! interface is plugged into another socket, and its serial number tells apart interfaces
+//! that share a product name. Names are saved in the configuration and the state as
+//! strings, or as lists of bytes when they are not UTF-8
+
+use anyhow::{anyhow, bail, Context};
+use serialport::{SerialPort, SerialPortInfo, SerialPortType};
+use std::ffi::{OsStr, OsString};
+
+/// Start of the names that are USB identities
+const USB_PREFIX: &str = "usb:";
+
+/// Returns the USB identity of a serial port, or `None` for ports that are not USB
+pub fn identity(info: &SerialPortInfo) -> Option<String> {
+    let SerialPortType::UsbPort(usb) = &info.port_type else {
+        return None;
+    };
+    let mut identity = format!("{}{:04X}:{:04X}", USB_PREFIX, usb.vid, usb.pid);
+    if let Some(serial) = usb.serial_number.as_deref().filter(|s| !s.is_empty()) {
+        identity.push(':');
+        identity.push_str(serial);
+    }
+    Some(identity)
+}
+
+/// Describes a serial port and tells if it looks like a MIDI interface
+pub fn describe(port: &SerialPortInfo) -> (String, bool) {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let names: Vec<&str> = [&usb.manufacturer, &usb.product]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            let description = format!("USB {:04X}:{:04X} {}", usb.vid, usb.pid, names.join(" "));
+            let midi = names.iter().any(|name| looks_like_midi(name));
+            (description.trim_end().to_string(), midi)
+        }
+        SerialPortType::BluetoothPort => ("Bluetooth".to_string(), false),
+        SerialPortType::PciPort => ("PCI".to_string(), false),
+        SerialPortType::Unknown => (String::new(), false),
+    }
+}
+
+/// Returns `true` if a device name suggests a MIDI interface
+fn looks_like_midi(name: &str) -> bool {
+    name.to_lowercase().contains("midi")
+}
+
+/// Lists the ALSA raw MIDI devices, which can be read with `monitor --file`
+#[cfg(target_os = "linux")]
+pub fn midi_devices() -> Vec<(String, String)> {
+    let Ok(entries) = std::fs::read_dir("/dev/snd") else {
+        return vec![];
+    };
+    let mut devices: Vec<(String, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let (card, device) = parse_rawmidi_name(&file_name)?;
+            // The first line of the proc entry is the name of the device
+            let name = std::fs::read_to_string(format!("/proc/asound/card{}/midi{}", card, device))
+                .ok()
+                .and_then(|info| info.lines().next().map(str::to_string))
+                .unwrap_or_default();
+            Some((format!("/dev/snd/{}", file_name), name))
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn midi_devices() -> Vec<(String, String)> {
+    vec![]
+}
+
+/// Returns the card and device numbers of a raw MIDI device node named like `midiC1D0`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_rawmidi_name(name: &str) -> Option<(u32, u32)> {
+    let (card, device) = name.strip_prefix("midiC")?.split_once('D')?;
+    Some((card.parse().ok()?, device.parse().ok()?))
+}
+
+/// Returns the path of the port a name stands for, finding the port of a USB identity
+/// among the ports of this machine
+pub fn resolve(name: &OsStr) -> Result<OsString, anyhow::Error> {
+    let Some(wanted) = name.to_str().and_then(|n| n.strip_prefix(USB_PREFIX)) else {
+        return Ok(name.to_os_string());
+    };
+    let invalid = || {
+        anyhow!(
+            "`{}` is not a USB identity such as `usb:0582:012A` or `usb:0582:012A:SERIAL`",
+            name.to_string_lossy()
+        )
+    };
+    let mut parts = wanted.splitn(3, ':');
+    let mut id = || u16::from_str_radix(parts.next()?, 16).ok();
+    let (vid, pid) = (id().ok_or_else(invalid)?, id().ok_or_else(invalid)?);
+    let serial = wanted.splitn(3, ':').nth(2);
+    let ports = serialport::available_ports().context("Unable to list serial ports")?;
+    let found: Vec<&SerialPortInfo> = ports
+        .iter()
+        .filter(|info| match &info.port_type {
+            SerialPortType::UsbPort(usb) => {
+                usb.vid == vid
+                    && usb.pid == pid
+                    && serial.is_none_or(|s| usb.serial_number.as_deref() == Some(s))
+            }
+            _ => false,
+        })
+        .collect();
+    match found[..] {
+        [info] => Ok(info.port_name.clone().into()),
+        [] => bail!(
+            "No serial port of `{}` is connected",
+            name.to_string_lossy()
+        ),
+        _ => {
+            let names: Vec<String> = found
+                .iter()
+                .map(|info| identity(info).unwrap_or_else(|| info.port_name.clone()))
+                .collect();
+            bail!(
+                "{} serial ports match `{}`, give one of {}",
+                found.len(),
+                name.to_string_lossy(),
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Opens a serial port by its name at `baud`, with 8 data bits, no parity, and a
+/// timeout of zero
+pub fn open(name: &OsStr, baud: u32) -> Result<Box<dyn SerialPort>, anyhow::Error> {
+    #[cfg(unix)]
+    return Ok(Box::new(open_native(name, baud)?));
+    #[cfg(not(unix))]
+    {
+        let path = resolve(name)?;
+        let path = path
+            .to_str()
+            .context("The serial library only opens ports whose names are UTF-8")?;
+        Ok(serialport::new(path, baud).open()?)
+    }
+}
+
+/// Opens a serial port by its name as a terminal device, for access to its descriptor
+#[cfg(unix)]
+pub fn open_native(name: &OsStr, baud: u32) -> Result<serialport::TTYPort, anyhow::Error> {
+    use std::{
+        fs::OpenOptions,
+        os::unix::{
+            fs::OpenOptionsExt,
+            io::{AsRawFd, FromRawFd, IntoRawFd},
+        },
+    };
+
+    let path = resolve(name)?;
+    if let Some(path) = path.to_str() {
+        return Ok(serialport::new(path, baud).open_native()?);
+    }
+    // The serial library only takes UTF-8 paths, so the device is opened and set up as it
+    // would, and handed over
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(&path)?;
+    let fd = file.as_raw_fd();
+    // SAFETY: the termios structure is filled by `tcgetattr` before it is read
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("Unable to read the line settings");
+        }
+        termios.c_cflag |= libc::CREAD | libc::CLOCAL;
+        libc::cfmakeraw(&mut termios);
+        // Reads block again once the settings are made
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0
+            || libc::fcntl(fd, libc::F_SETFL, 0) != 0
+        {
+            return Err(std::io::Error::last_os_error())
+                .context("Unable to change the line settings");
+        }
+    }
+    // SAFETY: the descriptor is open and owned by the port from here on
+    let mut port = unsafe { serialport::TTYPort::from_raw_fd(file.into_raw_fd()) };
+    port.set_baud_rate(baud)?;
+    port.set_data_bits(serialport::DataBits::Eight)?;
+    port.set_parity(serialport::Parity::None)?;
+    port.set_stop_bits(serialport::StopBits::One)?;
+    port.set_flow_control(serialport::FlowControl::None)?;
+    port.set_timeout(std::time::Duration::ZERO)?;
+    Ok(port)
+}
+
+/// Returns the bytes of a name, as saved when it is not UTF-8
+fn to_bytes(name: &OsStr) -> Vec<u8> {
+    #[cfg(unix)]
+    return std::os::unix::ffi::OsStrExt::as_bytes(name).to_vec();
+    #[cfg(not(unix))]
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Returns the name saved as bytes
+fn from_bytes(bytes: Vec<u8>) -> OsString {
+    #[cfg(unix)]
+    return std::os::unix::ffi::OsStringExt::from_vec(bytes);
+    #[cfg(not(unix))]
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+/// Saves an optional port name as a string, or as a list of bytes when it is not UTF-8,
+/// for `#[serde(with = "port::optional")]`
+pub mod optional {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+
+    /// A name as written in a file
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(
+        name: &Option<OsString>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match name {
+            None => serializer.serialize_none(),
+            Some(name) => match name.to_str() {
+                Some(text) => serializer.serialize_some(text),
+                None => serializer.serialize_some(&super::to_bytes(name)),
+            },
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OsString>, D::Error> {
+        Ok(match Option::<Saved>::deserialize(deserializer)? {
+            None => None,
+            Some(Saved::Text(text)) => Some(text.into()),
+            Some(Saved::Bytes(bytes)) => Some(super::from_bytes(bytes)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use serialport::UsbPortInfo;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Saved {
+        #[serde(default, with = "optional", skip_serializing_if = "Option::is_none")]
+        port: Option<OsString>,
+    }
+
+    #[test]
+    fn saved_names() {
+        let round_trip = |port: OsString| {
+            let text = toml::to_string(&Saved {
+                port: Some(port.clone()),
+            })
+            .unwrap();
+            assert_eq!(toml::from_str::<Saved>(&text).unwrap().port, Some(port));
+            text
+        };
+        assert_eq!(
+            round_trip("/dev/ttyUSB0".into()),
+            "port = \"/dev/ttyUSB0\"\n"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            round_trip(from_bytes(b"/dev/tty\xFF".to_vec())),
+            "port = [47, 100, 101, 118, 47, 116, 116, 121, 255]\n"
+        );
+        assert_eq!(toml::from_str::<Saved>("").unwrap().port, None);
+    }
+
+    #[test]
+    fn recognize_midi_devices() {
+        assert!(looks_like_midi("USB MIDI Interface"));
+        assert!(!looks_like_midi("FT232R USB UART"));
+        assert_eq!(parse_rawmidi_name("midiC1D0"), Some((1, 0)));
+        assert_eq!(parse_rawmidi_name("pcmC0D0p"), None);
+    }
+
+    #[test]
+    fn usb_identities() {
+        let mut info = SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0582,
+                pid: 0x012A,
+                serial_number: Some("A1:B2".to_string()),
+                manufacturer: None,
+                product: Some("UM-ONE".to_string()),
+            }),
+        };
+        assert_eq!(identity(&info).as_deref(), Some("usb:0582:012A:A1:B2"));
+        info.port_type = SerialPortType::PciPort;
+        assert_eq!(identity(&info), None);
+
+        let path = OsStr::new("/dev/ttyUSB0");
+        assert_eq!(resolve(path).unwrap(), path);
+        assert!(resolve(OsStr::new("usb:zz:012A")).is_err());
+    }
+}
@@ -0,0 +1,534 @@
+//! RTP-MIDI (AppleMIDI) sessions, for analyzing network MIDI from iOS apps and hardware
+//! without bridging software
+//!
+//! A session uses two UDP ports: session initiation on the control port, and clock
+//! synchronization and MIDI on the data port right above it. The recovery journal that
+//! senders attach to their packets is not read, so lost packets are only reported
+
+use super::SourceEvent;
+use anyhow::{bail, Context};
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Name miditerm shows up with in the session
+const NAME: &str = "miditerm";
+/// Version of the AppleMIDI session protocol
+const VERSION: u32 = 2;
+/// RTP payload type of RTP-MIDI packets
+const PAYLOAD_TYPE: u8 = 0x61;
+/// Length of one tick of the session clock, which runs at 10 kHz
+const TICK: Duration = Duration::from_micros(100);
+/// How long to wait for an answer before an invitation is sent again
+const INVITATION_INTERVAL: Duration = Duration::from_secs(1);
+/// How often an invited device is synchronized with, so it keeps the session open
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Session initiation and synchronization commands
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    /// `IN`: asks to join the session
+    Invitation { token: u32, ssrc: u32, name: String },
+    /// `OK`: lets the invited join
+    Accepted { token: u32, ssrc: u32, name: String },
+    /// `NO`
+    Rejected { token: u32, ssrc: u32 },
+    /// `BY`: leaves the session
+    Bye { token: u32, ssrc: u32 },
+    /// `CK`: the three steps of a clock synchronization, in ticks of the session clock
+    Sync {
+        ssrc: u32,
+        count: u8,
+        timestamps: [u64; 3],
+    },
+}
+
+impl Command {
+    fn parse(packet: &[u8]) -> Option<Command> {
+        let [0xFF, 0xFF, a, b, ref body @ ..] = *packet else {
+            return None;
+        };
+        let u32_at = |i: usize| Some(u32::from_be_bytes(body.get(i..i + 4)?.try_into().ok()?));
+        let u64_at = |i: usize| Some(u64::from_be_bytes(body.get(i..i + 8)?.try_into().ok()?));
+        let name = || {
+            let name = body.get(12..).unwrap_or_default();
+            let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..end]).into_owned()
+        };
+        let (token, ssrc) = (u32_at(4), u32_at(8));
+        match &[a, b] {
+            b"IN" => Some(Command::Invitation {
+                token: token?,
+                ssrc: ssrc?,
+                name: name(),
+            }),
+            b"OK" => Some(Command::Accepted {
+                token: token?,
+                ssrc: ssrc?,
+                name: name(),
+            }),
+            b"NO" => Some(Command::Rejected {
+                token: token?,
+                ssrc: ssrc?,
+            }),
+            b"BY" => Some(Command::Bye {
+                token: token?,
+                ssrc: ssrc?,
+            }),
+            b"CK" => Some(Command::Sync {
+                ssrc: u32_at(0)?,
+                count: *body.get(4)?,
+                timestamps: [u64_at(8)?, u64_at(16)?, u64_at(24)?],
+            }),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut packet = vec![0xFF, 0xFF];
+        let mut session = |command: &[u8], token: u32, ssrc: u32| {
+            packet.extend(command);
+            packet.extend(VERSION.to_be_bytes());
+            packet.extend(token.to_be_bytes());
+            packet.extend(ssrc.to_be_bytes());
+        };
+        match self {
+            Command::Invitation { token, ssrc, name } | Command::Accepted { token, ssrc, name } => {
+                let command = match self {
+                    Command::Invitation { .. } => b"IN",
+                    _ => b"OK",
+                };
+                session(command, *token, *ssrc);
+                packet.extend(name.as_bytes());
+                packet.push(0);
+            }
+            Command::Rejected { token, ssrc } => session(b"NO", *token, *ssrc),
+            Command::Bye { token, ssrc } => session(b"BY", *token, *ssrc),
+            Command::Sync {
+                ssrc,
+                count,
+                timestamps,
+            } => {
+                packet.extend(b"CK");
+                packet.extend(ssrc.to_be_bytes());
+                packet.extend([*count, 0, 0, 0]);
+                for timestamp in timestamps {
+                    packet.extend(timestamp.to_be_bytes());
+                }
+            }
+        }
+        packet
+    }
+}
+
+/// Turns the command lists of RTP-MIDI packets back into a MIDI byte stream
+#[derive(Debug, Default)]
+struct Decoder {
+    /// Status of the last Channel Voice message, for commands sent with running status
+    running: Option<u8>,
+    /// A SysEx message was split across commands and has not ended yet
+    sysex: bool,
+    /// Sequence number of the last packet
+    sequence: Option<u16>,
+    /// RTP timestamp of the first packet, which the timestamps of the bytes count from
+    start: Option<u32>,
+}
+
+impl Decoder {
+    /// Decodes an RTP packet into the number of packets lost before it and its bytes with
+    /// their timestamps, or `None` if it is not RTP-MIDI
+    fn packet(&mut self, packet: &[u8]) -> Option<(u16, Vec<(Duration, u8)>)> {
+        if packet.len() < 12 || packet[0] & 0xC0 != 0x80 || packet[1] & 0x7F != PAYLOAD_TYPE {
+            return None;
+        }
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let timestamp = u32::from_be_bytes(packet[4..8].try_into().ok()?);
+        let lost = match self.sequence {
+            Some(last) => sequence.wrapping_sub(last).wrapping_sub(1),
+            None => 0,
+        };
+        self.sequence = Some(sequence);
+        let start = *self.start.get_or_insert(timestamp);
+        let header = 12 + 4 * (packet[0] & 0x0F) as usize;
+        let bytes = self
+            .commands(packet.get(header..)?)
+            .into_iter()
+            .map(|(delta, byte)| {
+                let ticks = timestamp.wrapping_sub(start).wrapping_add(delta);
+                (TICK * ticks, byte)
+            })
+            .collect();
+        Some((lost, bytes))
+    }
+
+    /// Decodes the MIDI command section of a packet into its bytes, with the delta time of
+    /// each in ticks. Decoding stops at the first malformed command
+    fn commands(&mut self, section: &[u8]) -> Vec<(u32, u8)> {
+        let Some(&flags) = section.first() else {
+            return vec![];
+        };
+        // B: the length takes 12 bits, Z: the first command has a delta time
+        let (length, start) = if flags & 0x80 != 0 {
+            let low = *section.get(1).unwrap_or(&0) as usize;
+            (((flags & 0x0F) as usize) << 8 | low, 2)
+        } else {
+            ((flags & 0x0F) as usize, 1)
+        };
+        let list = section.get(start..).unwrap_or_default();
+        let list = &list[..length.min(list.len())];
+        let mut has_delta = flags & 0x20 != 0;
+        let mut delta = 0_u32;
+        let mut bytes = vec![];
+        let mut i = 0;
+        while i < list.len() {
+            if has_delta {
+                // Delta times count from the previous command, 7 bits per byte
+                let mut value = 0_u32;
+                for _ in 0..4 {
+                    let Some(&b) = list.get(i) else {
+                        return bytes;
+                    };
+                    i += 1;
+                    value = (value << 7) | (b & 0x7F) as u32;
+                    if b & 0x80 == 0 {
+                        break;
+                    }
+                }
+                delta = delta.wrapping_add(value);
+            }
+            has_delta = true;
+            match self.command(&list[i..]) {
+                Some((length, command)) => {
+                    bytes.extend(command.into_iter().map(|byte| (delta, byte)));
+                    i += length;
+                }
+                None => return bytes,
+            }
+        }
+        bytes
+    }
+
+    /// Reads the command at the start of the list, returning the length it takes in the
+    /// list and the bytes it stands for in a MIDI stream
+    fn command(&mut self, list: &[u8]) -> Option<(usize, Vec<u8>)> {
+        let first = *list.first()?;
+        if self.sysex || first == 0xF0 {
+            // SysEx segments end with F0, and carry on after an F7 in the next command
+            let mut bytes = if self.sysex { vec![] } else { vec![0xF0] };
+            let skip = usize::from(matches!(first, 0xF0 | 0xF7));
+            let end = skip
+                + list[skip..]
+                    .iter()
+                    .position(|b| matches!(b, 0xF0 | 0xF4 | 0xF7))?;
+            bytes.extend(&list[skip..end]);
+            match list[end] {
+                0xF0 => self.sysex = true,
+                // Cancelled, the SysEx is left unterminated
+                0xF4 => self.sysex = false,
+                _ => {
+                    self.sysex = false;
+                    bytes.push(0xF7);
+                }
+            }
+            return Some((end + 1, bytes));
+        }
+        let (status, data) = if first & 0x80 != 0 {
+            (first, &list[1..])
+        } else {
+            (self.running?, list)
+        };
+        let length = match status {
+            0x80..=0xBF | 0xE0..=0xEF | 0xF2 => 2,
+            0xC0..=0xDF | 0xF1 | 0xF3 => 1,
+            _ => 0,
+        };
+        match status {
+            0x80..=0xEF => self.running = Some(status),
+            0xF0..=0xF7 => self.running = None,
+            _ => {}
+        }
+        let data = data.get(..length)?;
+        let mut bytes = if first & 0x80 != 0 {
+            vec![first]
+        } else {
+            vec![]
+        };
+        bytes.extend(data);
+        Some((bytes.len(), bytes))
+    }
+}
+
+/// Joins an RTP-MIDI session and forwards the MIDI of its members. `target` is the port to
+/// wait for invitations on, such as `5004`, or the `HOST:PORT` of a device to invite
+pub fn spawn(target: &str, tx: Sender<SourceEvent>) -> Result<(), anyhow::Error> {
+    let (control, data, peer) = match target.parse::<u16>() {
+        Ok(port) => {
+            let control = UdpSocket::bind(("0.0.0.0", port))
+                .context(format!("Unable to listen on UDP port {}", port))?;
+            let Some(data_port) = port.checked_add(1) else {
+                bail!("RTP-MIDI needs the UDP port above {} as well", port);
+            };
+            let data = UdpSocket::bind(("0.0.0.0", data_port))
+                .context(format!("Unable to listen on UDP port {}", data_port))?;
+            (control, data, None)
+        }
+        Err(_) => {
+            let Some(peer) = target.to_socket_addrs().ok().and_then(|mut a| a.next()) else {
+                bail!(
+                    "Unknown RTP-MIDI address `{}`, expected PORT or HOST:PORT",
+                    target
+                );
+            };
+            let control = UdpSocket::bind("0.0.0.0:0").context("Unable to open UDP socket")?;
+            let data = UdpSocket::bind("0.0.0.0:0").context("Unable to open UDP socket")?;
+            (control, data, Some(peer))
+        }
+    };
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let session = Session {
+        ssrc: seed ^ std::process::id().rotate_left(16),
+        start: Instant::now(),
+    };
+    let notice = match peer {
+        Some(peer) => format!("Inviting `{}` to an RTP-MIDI session", peer),
+        None => format!("Waiting for RTP-MIDI invitations on UDP port {}", target),
+    };
+    let _ = tx.send(SourceEvent::Notice(notice));
+
+    let sender = data.try_clone()?;
+    let control_tx = tx.clone();
+    thread::spawn(move || session.control(control, sender, peer, control_tx));
+    thread::spawn(move || session.data(data, peer.is_some(), tx));
+    Ok(())
+}
+
+/// Identity and clock of miditerm in the session
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    ssrc: u32,
+    start: Instant,
+}
+
+impl Session {
+    /// Time on the session clock in ticks
+    fn now(&self) -> u64 {
+        (self.start.elapsed().as_micros() / TICK.as_micros()) as u64
+    }
+
+    /// Answers invitations to the control port, or invites the device at `peer` until it
+    /// accepts. A device that accepts on the control port is invited on the data port next
+    fn control(
+        self,
+        socket: UdpSocket,
+        data: UdpSocket,
+        peer: Option<SocketAddr>,
+        tx: Sender<SourceEvent>,
+    ) {
+        let token = self.ssrc.rotate_left(8);
+        let invitation = Command::Invitation {
+            token,
+            ssrc: self.ssrc,
+            name: NAME.to_string(),
+        };
+        let _ = socket.set_read_timeout(Some(INVITATION_INTERVAL));
+        let mut pending = peer;
+        let mut buffer = [0_u8; 1500];
+        loop {
+            if let Some(peer) = pending {
+                let _ = socket.send_to(&invitation.encode(), peer);
+            }
+            let Ok((n, from)) = socket.recv_from(&mut buffer) else {
+                continue;
+            };
+            let reply = match Command::parse(&buffer[..n]) {
+                Some(Command::Invitation { token, name, .. }) => {
+                    let notice = format!("`{}` joins the RTP-MIDI session from {}", name, from);
+                    if tx.send(SourceEvent::Notice(notice)).is_err() {
+                        return;
+                    }
+                    Some(Command::Accepted {
+                        token,
+                        ssrc: self.ssrc,
+                        name: NAME.to_string(),
+                    })
+                }
+                Some(Command::Accepted { name, .. }) if pending.is_some() => {
+                    pending = None;
+                    let data_port = SocketAddr::new(from.ip(), from.port().wrapping_add(1));
+                    let _ = data.send_to(&invitation.encode(), data_port);
+                    let notice = format!("Joined the RTP-MIDI session of `{}`", name);
+                    if tx.send(SourceEvent::Notice(notice)).is_err() {
+                        return;
+                    }
+                    None
+                }
+                Some(Command::Rejected { .. }) if pending.is_some() => {
+                    let _ = tx.send(SourceEvent::Error(format!(
+                        "`{}` declined the RTP-MIDI invitation",
+                        from
+                    )));
+                    return;
+                }
+                Some(Command::Bye { .. }) => {
+                    let notice = format!("{} left the RTP-MIDI session", from);
+                    if tx.send(SourceEvent::Notice(notice)).is_err() {
+                        return;
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(reply) = reply {
+                let _ = socket.send_to(&reply.encode(), from);
+            }
+        }
+    }
+
+    /// Receives MIDI on the data port, answering invitations and clock synchronizations.
+    /// An `initiator` also starts a synchronization every now and then
+    fn data(self, socket: UdpSocket, initiator: bool, tx: Sender<SourceEvent>) {
+        let _ = socket.set_read_timeout(Some(SYNC_INTERVAL));
+        let mut decoder = Decoder::default();
+        let mut peer = None;
+        let mut last_sync: Option<Instant> = None;
+        let mut buffer = [0_u8; 1500];
+        loop {
+            let due = last_sync.is_none_or(|time| time.elapsed() >= SYNC_INTERVAL);
+            if let Some(peer) = peer.filter(|_| initiator && due) {
+                let sync = Command::Sync {
+                    ssrc: self.ssrc,
+                    count: 0,
+                    timestamps: [self.now(), 0, 0],
+                };
+                let _ = socket.send_to(&sync.encode(), peer);
+                last_sync = Some(Instant::now());
+            }
+            let Ok((n, from)) = socket.recv_from(&mut buffer) else {
+                continue;
+            };
+            let packet = &buffer[..n];
+            let reply = match Command::parse(packet) {
+                Some(Command::Invitation { token, .. }) => Some(Command::Accepted {
+                    token,
+                    ssrc: self.ssrc,
+                    name: NAME.to_string(),
+                }),
+                Some(Command::Accepted { .. }) => {
+                    peer = Some(from);
+                    last_sync = None;
+                    None
+                }
+                Some(Command::Sync {
+                    count, timestamps, ..
+                }) if count < 2 => {
+                    let mut timestamps = timestamps;
+                    timestamps[count as usize + 1] = self.now();
+                    Some(Command::Sync {
+                        ssrc: self.ssrc,
+                        count: count + 1,
+                        timestamps,
+                    })
+                }
+                Some(_) => None,
+                None => {
+                    let Some((lost, bytes)) = decoder.packet(packet) else {
+                        continue;
+                    };
+                    if lost > 0 {
+                        let notice = format!("{} RTP-MIDI packets lost", lost);
+                        if tx.send(SourceEvent::Notice(notice)).is_err() {
+                            return;
+                        }
+                    }
+                    let arrival = Instant::now();
+                    for (timestamp, byte) in bytes {
+                        let event = SourceEvent::Byte {
+                            arrival,
+                            timestamp: Some(timestamp),
+                            byte,
+                            source: 0,
+                        };
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    None
+                }
+            };
+            if let Some(reply) = reply {
+                let _ = socket.send_to(&reply.encode(), from);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_commands() {
+        let invitation = Command::Invitation {
+            token: 0x01020304,
+            ssrc: 0xAABBCCDD,
+            name: "iPad".to_string(),
+        };
+        let packet = invitation.encode();
+        assert_eq!(&packet[..8], [0xFF, 0xFF, b'I', b'N', 0, 0, 0, 2]);
+        assert_eq!(Command::parse(&packet), Some(invitation));
+        let sync = Command::Sync {
+            ssrc: 7,
+            count: 1,
+            timestamps: [100, 200, 0],
+        };
+        assert_eq!(sync.encode().len(), 36);
+        assert_eq!(Command::parse(&sync.encode()), Some(sync));
+        assert_eq!(Command::parse(&[0x80, 0x61]), None);
+    }
+
+    #[test]
+    fn command_lists() {
+        let mut decoder = Decoder::default();
+        let rtp = |sequence: u8, timestamp: u8, section: &[u8]| {
+            [
+                &[0x80, 0x61, 0, sequence, 0, 0, 0, timestamp, 0, 0, 0, 1][..],
+                section,
+            ]
+            .concat()
+        };
+        // Note On, then a running status Note On 3 ticks later and a Timing Clock
+        let packet = rtp(
+            1,
+            10,
+            &[0x08, 0x90, 0x3C, 0x7F, 0x03, 0x3E, 0x7F, 0x00, 0xF8],
+        );
+        let (lost, bytes) = decoder.packet(&packet).unwrap();
+        assert_eq!(lost, 0);
+        assert_eq!(
+            bytes,
+            [
+                (Duration::ZERO, 0x90),
+                (Duration::ZERO, 0x3C),
+                (Duration::ZERO, 0x7F),
+                (TICK * 3, 0x3E),
+                (TICK * 3, 0x7F),
+                (TICK * 3, 0xF8),
+            ]
+        );
+        // A SysEx split across two packets, after one that was lost
+        let first = rtp(3, 20, &[0x24, 0x00, 0xF0, 0x41, 0xF0]);
+        let (lost, bytes) = decoder.packet(&first).unwrap();
+        assert_eq!(lost, 1);
+        assert_eq!(bytes, [(TICK * 10, 0xF0), (TICK * 10, 0x41)]);
+        let last = rtp(4, 21, &[0x03, 0xF7, 0x10, 0xF7]);
+        let (_, bytes) = decoder.packet(&last).unwrap();
+        assert_eq!(bytes, [(TICK * 11, 0x10), (TICK * 11, 0xF7)]);
+        assert_eq!(decoder.packet(&[0xFF, 0xFF, b'C', b'K']), None);
+    }
+}
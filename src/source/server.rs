@@ -0,0 +1,253 @@
+//! Capture server that remote capture agents push their bytes to, such as a Raspberry Pi
+//! next to the synth rack running `miditerm agent`
+//!
+//! Agents connect over TCP or a Unix socket and send frames of:
+//!
+//! | Field     | Size    | Content                                              |
+//! |-----------|---------|------------------------------------------------------|
+//! | Source    | 1       | Number the agent identifies its bytes with           |
+//! | Timestamp | 8       | Microseconds on the clock of the agent, big-endian   |
+//! | Length    | 2       | Number of MIDI bytes that follow, big-endian         |
+//! | Bytes     | Length  | MIDI bytes as received by the agent                  |
+
+use super::SourceEvent;
+use anyhow::{bail, Context};
+use std::{
+    collections::BTreeSet,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Size of the header of a frame
+const HEADER_SIZE: usize = 11;
+
+/// Where the server listens and agents connect to
+enum Address<'a> {
+    Tcp(&'a str),
+    Unix(&'a str),
+}
+
+impl Address<'_> {
+    fn parse(address: &str) -> Result<Address<'_>, anyhow::Error> {
+        if let Some(address) = address.strip_prefix("tcp:") {
+            Ok(Address::Tcp(address))
+        } else if let Some(path) = address.strip_prefix("unix:") {
+            Ok(Address::Unix(path))
+        } else {
+            bail!(
+                "Unknown address `{}`, expected `tcp:HOST:PORT` or `unix:PATH`",
+                address
+            )
+        }
+    }
+}
+
+/// Bytes received by an agent
+#[derive(Debug, PartialEq)]
+struct Frame {
+    source: u8,
+    timestamp: Duration,
+    bytes: Vec<u8>,
+}
+
+/// Encodes bytes received by an agent at `timestamp`, in as many frames as they need
+pub fn encode(source: u8, timestamp: Duration, bytes: &[u8]) -> Vec<u8> {
+    let mut frames = vec![];
+    for chunk in bytes.chunks(u16::MAX as usize) {
+        frames.push(source);
+        frames.extend((timestamp.as_micros() as u64).to_be_bytes());
+        frames.extend((chunk.len() as u16).to_be_bytes());
+        frames.extend(chunk);
+    }
+    frames
+}
+
+/// Reads the next frame, or `None` if the agent closed the connection between frames
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Frame>> {
+    let mut header = [0_u8; HEADER_SIZE];
+    match reader.read_exact(&mut header[..1]) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    reader.read_exact(&mut header[1..])?;
+    let timestamp = u64::from_be_bytes(header[1..9].try_into().unwrap());
+    // No clock runs for the 292 000 years it takes to get past this
+    if timestamp > i64::MAX as u64 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("timestamp {} out of range", timestamp),
+        ));
+    }
+    let length = u16::from_be_bytes([header[9], header[10]]);
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(Frame {
+        source: header[0],
+        timestamp: Duration::from_micros(timestamp),
+        bytes,
+    }))
+}
+
+/// Starts listening at `address`, serving every agent that connects on its own thread
+pub fn listen(address: &str, tx: Sender<SourceEvent>) -> Result<(), anyhow::Error> {
+    let notice = format!("Listening for agents on `{}`", address);
+    match Address::parse(address)? {
+        Address::Tcp(address) => {
+            let listener =
+                TcpListener::bind(address).context(format!("Unable to listen on `{}`", address))?;
+            let _ = tx.send(SourceEvent::Notice(notice));
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let peer = match stream.peer_addr() {
+                        Ok(peer) => peer.to_string(),
+                        Err(_) => "unknown address".to_string(),
+                    };
+                    let tx = tx.clone();
+                    thread::spawn(move || serve(stream, &peer, tx));
+                }
+            });
+        }
+        #[cfg(unix)]
+        Address::Unix(path) => {
+            use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+            // A socket left behind by an earlier run would keep the path taken
+            if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                let _ = std::fs::remove_file(path);
+            }
+            let listener =
+                UnixListener::bind(path).context(format!("Unable to listen on `{}`", path))?;
+            let _ = tx.send(SourceEvent::Notice(notice));
+            let path = path.to_string();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let tx = tx.clone();
+                    let peer = format!("`{}`", path);
+                    thread::spawn(move || serve(stream, &peer, tx));
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        Address::Unix(_) => bail!("Unix sockets are not supported on this system"),
+    }
+    Ok(())
+}
+
+/// Opens a connection to the server at `address` for an agent to send its frames
+pub fn connect(address: &str) -> Result<Box<dyn Write + Send>, anyhow::Error> {
+    match Address::parse(address)? {
+        Address::Tcp(address) => {
+            let stream = TcpStream::connect(address)
+                .context(format!("Unable to connect to `{}`", address))?;
+            // Frames are small and should not wait for more to fill a packet
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(unix)]
+        Address::Unix(path) => {
+            let stream = std::os::unix::net::UnixStream::connect(path)
+                .context(format!("Unable to connect to `{}`", path))?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(not(unix))]
+        Address::Unix(_) => bail!("Unix sockets are not supported on this system"),
+    }
+}
+
+/// Forwards the bytes of an agent until it disconnects or the receiver hangs up.
+/// Bytes of all sources are delivered as one stream, with their timestamps
+fn serve<R: Read>(mut stream: R, peer: &str, tx: Sender<SourceEvent>) {
+    let mut sources = BTreeSet::new();
+    let reason = loop {
+        let frame = match read_frame(&mut stream) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break "connection closed".to_string(),
+            Err(e) => break e.to_string(),
+        };
+        if sources.insert(frame.source) {
+            let notice = format!("Source {} connected from {}", frame.source, peer);
+            if tx.send(SourceEvent::Notice(notice)).is_err() {
+                return;
+            }
+        }
+        let arrival = Instant::now();
+        for byte in frame.bytes {
+            let event = SourceEvent::Byte {
+                arrival,
+                timestamp: Some(frame.timestamp),
+                byte,
+                source: frame.source,
+            };
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    };
+    let _ = tx.send(SourceEvent::Notice(format!(
+        "Agent at {} disconnected ({})",
+        peer, reason
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn frames() {
+        let time = Duration::from_micros(1_500_000);
+        let mut stream = encode(1, time, &[0x90, 0x3C, 0x7F]);
+        stream.extend(encode(2, time * 2, &[0xF8]));
+        assert_eq!(
+            stream[..HEADER_SIZE],
+            [1, 0, 0, 0, 0, 0, 0x16, 0xE3, 0x60, 0, 3]
+        );
+        assert_eq!(
+            read_frame(&mut &stream[..]).unwrap(),
+            Some(Frame {
+                source: 1,
+                timestamp: time,
+                bytes: vec![0x90, 0x3C, 0x7F],
+            })
+        );
+        // A timestamp no clock can reach is refused
+        let mut frame = encode(1, time, &[0xF8]);
+        frame[1] = 0x80;
+        assert_eq!(
+            read_frame(&mut &frame[..]).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            encode(0, time, &vec![0; 70_000]).len(),
+            70_000 + 2 * HEADER_SIZE
+        );
+
+        let (tx, rx) = mpsc::channel();
+        // The last frame is cut short
+        serve(&stream[..stream.len() - 1], "test", tx);
+        let events: Vec<String> = rx
+            .iter()
+            .map(|event| match event {
+                SourceEvent::Byte {
+                    timestamp, byte, ..
+                } => format!("{:02X} at {:?}", byte, timestamp.unwrap()),
+                SourceEvent::Notice(notice) => notice,
+                event => format!("{:?}", event),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "Source 1 connected from test",
+                "90 at 1.5s",
+                "3C at 1.5s",
+                "7F at 1.5s",
+                "Agent at test disconnected (failed to fill whole buffer)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! State of the TUI kept between sessions in `state.toml`
+//!
+//! The file lives in `$XDG_STATE_HOME/miditerm/`, or `~/.local/state/miditerm/` if that is
+//! not set. It is written when the TUI quits and read when it starts, so restarting the tool
+//! in the middle of debugging keeps the panels, filter, and port that were in use. Each
+//! profile has a state of its own
+
+use crate::{capture::Filter, source::port, ui::Layout};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the state file
+const FILE_NAME: &str = "state.toml";
+/// Profile the state is kept under when none is chosen
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// What the TUI was showing when it quit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// Serial port that was monitored
+    #[serde(
+        default,
+        with = "port::optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub port: Option<OsString>,
+    /// Panels that were shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    /// Events that were shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Filter>,
+    /// The table scrolled to the newest event as it arrived
+    pub follow: bool,
+    /// The table had a row per message rather than per byte
+    pub messages: bool,
+    /// The reference capture was shown below the live one
+    pub show_reference: bool,
+}
+
+impl Default for UiState {
+    fn default() -> UiState {
+        UiState {
+            port: None,
+            layout: None,
+            filter: None,
+            follow: true,
+            messages: false,
+            show_reference: true,
+        }
+    }
+}
+
+/// Where the state of a profile is read from and written to
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateStore {
+    pub path: PathBuf,
+    pub profile: String,
+}
+
+impl StateStore {
+    /// Returns the store of the profile in the state file of the user, if a home directory
+    /// is known
+    pub fn new(profile: &str) -> Option<StateStore> {
+        let dir = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+        };
+        Some(StateStore {
+            path: dir.join("miditerm").join(FILE_NAME),
+            profile: profile.to_string(),
+        })
+    }
+
+    /// Reads the states of every profile, none if the file does not exist
+    fn read(path: &Path) -> Result<BTreeMap<String, UiState>, anyhow::Error> {
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let text = fs::read_to_string(path).context(format!("Unable to read `{:?}`", path))?;
+        toml::from_str(&text).context(format!("Invalid state in `{:?}`", path))
+    }
+
+    /// Returns the state saved for the profile, or the defaults if there is none
+    pub fn load(&self) -> Result<UiState, anyhow::Error> {
+        let mut states = StateStore::read(&self.path)?;
+        Ok(states.remove(&self.profile).unwrap_or_default())
+    }
+
+    /// Saves the state of the profile, keeping those of the other profiles, and creating the
+    /// directory of the file if needed
+    pub fn save(&self, state: &UiState) -> Result<(), anyhow::Error> {
+        let mut states = StateStore::read(&self.path)?;
+        states.insert(self.profile.clone(), state.clone());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context(format!("Unable to create `{:?}`", dir))?;
+        }
+        let text = toml::to_string_pretty(&states)?;
+        fs::write(&self.path, text).context(format!("Unable to write `{:?}`", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::Panel;
+
+    #[test]
+    fn profiles_are_kept_apart() {
+        let dir = env::temp_dir().join(format!("miditerm-state-{}", std::process::id()));
+        let store = |profile: &str| StateStore {
+            path: dir.join(FILE_NAME),
+            profile: profile.to_string(),
+        };
+        assert_eq!(store("studio").load().unwrap(), UiState::default());
+
+        let filter = Filter {
+            hidden_channels: 0b101,
+            hidden_statuses: [0xF8].into(),
+            hidden_sources: [1].into(),
+            hidden_severities: 0b0011,
+            pattern: "clock".to_string(),
+        };
+        let state = UiState {
+            port: Some("/dev/ttyUSB0".into()),
+            layout: Some(Layout {
+                panels: vec![Panel::Stats],
+            }),
+            filter: Some(filter),
+            follow: false,
+            messages: true,
+            show_reference: false,
+        };
+        store("studio").save(&state).unwrap();
+        store(DEFAULT_PROFILE).save(&UiState::default()).unwrap();
+
+        let loaded = store("studio").load().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // Inputs differ from session to session, so hidden ones are not restored
+        let filter = loaded.filter.as_ref().unwrap();
+        assert!(filter.hidden_sources.is_empty());
+        assert_eq!(filter.hidden_statuses.len(), 1);
+        assert_eq!(
+            loaded,
+            UiState {
+                filter: Some(filter.clone()),
+                ..state
+            }
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Filter expression language for queries
+//!
+//! An expression is a list of whitespace separated terms that must all hold, such as
+//! `type=NoteOn,NoteOff channel=10 time>00:12:00`. Supported terms are
+//!
+//! - `type=NAME[,NAME..]` message types, e.g. `NoteOn`, `CC`, `SysEx`, `Clock`, or a status in hex
+//! - `channel=N[,N..]` channels from 1 to 16
+//! - `time>T`, `time>=T`, `time<T`, `time<=T` with `T` as `[[HH:]MM:]SS[.fraction]`
+
+use crate::store::Query;
+use anyhow::{anyhow, bail, Context};
+use std::{str::FromStr, time::Duration};
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Query::default();
+        for term in s.split_whitespace() {
+            let split = term
+                .find(['=', '<', '>'])
+                .context(format!("`{}` is not a comparison", term))?;
+            let (key, rest) = term.split_at(split);
+            let (operator, value) = match rest.get(..2) {
+                Some(">=") | Some("<=") => rest.split_at(2),
+                _ => rest.split_at(1),
+            };
+            match (key.to_lowercase().as_str(), operator) {
+                ("type", "=") => {
+                    for name in value.split(',') {
+                        query.statuses.insert(parse_type(name)?);
+                    }
+                }
+                ("channel", "=") => {
+                    for channel in value.split(',') {
+                        let channel: u8 =
+                            channel
+                                .parse()
+                                .ok()
+                                .filter(|ch| (1..=16).contains(ch))
+                                .context(format!("`{}` is not a channel from 1 to 16", channel))?;
+                        query.channels.insert(channel - 1);
+                    }
+                }
+                ("time", operator) => {
+                    let time = parse_time(value)?;
+                    match operator {
+                        ">" => {
+                            let from = time.checked_add(Duration::from_micros(1));
+                            query.from = Some(from.context(format!("`{}` is too late", value))?);
+                        }
+                        ">=" => query.from = Some(time),
+                        "<" => query.to = Some(time.saturating_sub(Duration::from_micros(1))),
+                        "<=" => query.to = Some(time),
+                        _ => bail!("Times can only be compared with <, <=, >, and >="),
+                    }
+                }
+                ("type", _) | ("channel", _) => bail!("`{}` can only be compared with =", key),
+                _ => bail!("Unknown field `{}`", key),
+            }
+        }
+        Ok(query)
+    }
+}
+
+/// Returns the status of a message type given by name or as a hexadecimal status
+pub fn parse_type(name: &str) -> Result<u8, anyhow::Error> {
+    let normalized: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    let status = match normalized.as_str() {
+        "noteoff" => 0x80,
+        "noteon" => 0x90,
+        "polypressure" | "polyaftertouch" => 0xA0,
+        "cc" | "controlchange" => 0xB0,
+        "pc" | "programchange" => 0xC0,
+        "channelpressure" | "aftertouch" => 0xD0,
+        "pitchbend" | "pb" => 0xE0,
+        "sysex" | "systemexclusive" => 0xF0,
+        "mtc" | "mtcquarterframe" => 0xF1,
+        "songposition" => 0xF2,
+        "songselect" => 0xF3,
+        "tunerequest" => 0xF6,
+        "clock" | "timingclock" => 0xF8,
+        "start" => 0xFA,
+        "continue" => 0xFB,
+        "stop" => 0xFC,
+        "activesensing" | "activesense" => 0xFE,
+        "reset" | "systemreset" => 0xFF,
+        _ => {
+            let status = u8::from_str_radix(normalized.trim_start_matches("0x"), 16)
+                .ok()
+                .filter(|s| s & 0x80 != 0)
+                .ok_or_else(|| anyhow!("Unknown message type `{}`", name))?;
+            if status < 0xF0 {
+                status & 0xF0
+            } else {
+                status
+            }
+        }
+    };
+    Ok(status)
+}
+
+/// Returns the statuses of a message type, or of a group of them: `notes` or `realtime`
+pub fn parse_types(name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    match name.to_lowercase().as_str() {
+        "notes" => Ok(vec![0x80, 0x90]),
+        "realtime" => Ok(vec![0xF8, 0xFA, 0xFB, 0xFC, 0xFE, 0xFF]),
+        _ => Ok(vec![parse_type(name)?]),
+    }
+}
+
+/// Parses a time given as `[[HH:]MM:]SS[.fraction]`
+fn parse_time(text: &str) -> Result<Duration, anyhow::Error> {
+    let invalid = || anyhow!("`{}` is not a time like 00:12:00", text);
+    if text.split(':').count() > 3 {
+        return Err(invalid());
+    }
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| anyhow!("`{}` is too late", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expression() {
+        let query: Query = "type=NoteOn,cc channel=10 time>00:12:00 time<=1:00:00.5"
+            .parse()
+            .unwrap();
+        assert_eq!(query.statuses, [0x90, 0xB0].into());
+        assert_eq!(query.channels, [9].into());
+        assert_eq!(query.from, Some(Duration::from_micros(720_000_001)));
+        assert_eq!(query.to, Some(Duration::from_millis(3_600_500)));
+        assert_eq!("".parse::<Query>().unwrap(), Query::default());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!("type=Bogus".parse::<Query>().is_err());
+        assert!("channel=17".parse::<Query>().is_err());
+        assert!("channel>3".parse::<Query>().is_err());
+        assert!("velocity=3".parse::<Query>().is_err());
+        assert!("time>soon".parse::<Query>().is_err());
+        assert!("time>1e30".parse::<Query>().is_err());
+        assert!("time>18446744073709551615".parse::<Query>().is_err());
+        assert!("time<=18446744073709551615".parse::<Query>().is_err());
+    }
+}
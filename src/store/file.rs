@@ -0,0 +1,82 @@
+//! Store backed by a capture file
+//!
+//! Only the timestamped bytes are written to disk, as soon as they complete a message so
+//! that other processes can follow the session. The file is analyzed again when it is
+//! opened and queries are answered from memory
+
+use crate::{
+    analysis::Settings,
+    capture::{
+        format::{CaptureReader, CaptureWriter},
+        Capture, CaptureEvent,
+    },
+    store::{CaptureStore, MemoryStore, Query},
+};
+use anyhow::Context;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+/// Appends events to a capture file while keeping them in memory for queries
+pub struct FileStore {
+    writer: CaptureWriter<BufWriter<File>>,
+    memory: MemoryStore,
+}
+
+impl FileStore {
+    /// Opens the capture file at `path`, analyzing any bytes it already contains with
+    /// `settings`, or creates it if it does not exist
+    pub fn open(path: &Path, settings: Settings) -> Result<FileStore, anyhow::Error> {
+        let mut memory = MemoryStore::new();
+        if !path.exists() {
+            let file = File::create(path).context(format!("Unable to create `{:?}`", path))?;
+            let writer = CaptureWriter::new(BufWriter::new(file))?;
+            return Ok(FileStore { writer, memory });
+        }
+
+        let file = File::open(path).context(format!("Unable to open `{:?}`", path))?;
+        let reader = CaptureReader::new(BufReader::new(file))
+            .context(format!("Unable to read capture `{:?}`", path))?;
+        let mut capture = Capture::with_settings(settings);
+        for record in reader {
+            let (time, byte) = record.context(format!("Unable to read capture `{:?}`", path))?;
+            memory.append(&capture.process(time, byte))?;
+        }
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .context(format!("Unable to open `{:?}` for writing", path))?;
+        let last = memory.end()?.unwrap_or(Duration::ZERO);
+        let writer = CaptureWriter::resume(BufWriter::new(file), last);
+        Ok(FileStore { writer, memory })
+    }
+}
+
+impl CaptureStore for FileStore {
+    fn append(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        // Notices are kept for as long as the session is open, capture files hold bytes only
+        for &byte in event.received() {
+            self.writer.write(event.time, byte)?;
+        }
+        // Whole messages reach the file at once for `miditerm tail` to show
+        if event.message.is_some() {
+            self.writer.flush()?;
+        }
+        self.memory.append(event)
+    }
+
+    fn query(&self, query: &Query) -> Result<Vec<CaptureEvent>, anyhow::Error> {
+        self.memory.query(query)
+    }
+
+    fn end(&self) -> Result<Option<Duration>, anyhow::Error> {
+        self.memory.end()
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,52 @@
+//! Store that keeps events in memory for the duration of the session
+
+use crate::{
+    capture::CaptureEvent,
+    store::{CaptureStore, Query},
+};
+use std::time::Duration;
+
+/// Keeps every event in memory
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    events: Vec<CaptureEvent>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl CaptureStore for MemoryStore {
+    fn append(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn query(&self, query: &Query) -> Result<Vec<CaptureEvent>, anyhow::Error> {
+        // Events are stored in time order, so the time range is found by binary search
+        let start = match query.from {
+            Some(from) => self.events.partition_point(|e| e.time < from),
+            None => 0,
+        };
+        let end = match query.to {
+            Some(to) => self.events.partition_point(|e| e.time <= to),
+            None => self.events.len(),
+        };
+        Ok(self.events[start..end.max(start)]
+            .iter()
+            .filter(|e| query.matches(e))
+            .cloned()
+            .collect())
+    }
+
+    fn end(&self) -> Result<Option<Duration>, anyhow::Error> {
+        Ok(self.events.last().map(|e| e.time))
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Storage of analyzed captures
+//!
+//! Long sessions can be kept in memory, in a capture file, or in a sqlite database.
+//! File and sqlite stores survive restarts and can be queried by time, status, and channel
+
+mod expr;
+mod file;
+mod memory;
+mod sqlite;
+
+pub use expr::parse_types;
+pub use file::FileStore;
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+use crate::{
+    analysis::Settings,
+    capture::CaptureEvent,
+    midi::{MidiMessage, MidiParser},
+};
+use std::{collections::BTreeSet, path::Path, time::Duration};
+
+/// Selects events from a store. Empty criteria match every event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Earliest time of the events to return
+    pub from: Option<Duration>,
+    /// Latest time of the events to return
+    pub to: Option<Duration>,
+    /// Statuses of the events to return, without the channel of channel messages
+    pub statuses: BTreeSet<u8>,
+    /// Channels of the events to return. When set, events without a channel are not returned
+    pub channels: BTreeSet<u8>,
+}
+
+impl Query {
+    /// Returns `true` if the event meets all criteria
+    pub fn matches(&self, event: &CaptureEvent) -> bool {
+        self.from.is_none_or(|from| event.time >= from)
+            && self.to.is_none_or(|to| event.time <= to)
+            && (self.statuses.is_empty()
+                || event.status.is_some_and(|s| self.statuses.contains(&s)))
+            && (self.channels.is_empty()
+                || event.channel.is_some_and(|ch| self.channels.contains(&ch)))
+    }
+}
+
+/// A place to keep the events of a capture
+pub trait CaptureStore: Send {
+    /// Adds an event after all events already stored
+    fn append(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error>;
+
+    /// Returns the stored events that match the query, in capture order
+    fn query(&self, query: &Query) -> Result<Vec<CaptureEvent>, anyhow::Error>;
+
+    /// Returns the time of the last stored event
+    fn end(&self) -> Result<Option<Duration>, anyhow::Error>;
+
+    /// Makes sure all appended events are persisted
+    fn flush(&mut self) -> Result<(), anyhow::Error>;
+}
+
+/// Opens the store at `path`, creating it if needed.
+/// `.db` and `.sqlite` files are opened as sqlite databases, anything else as a capture file.
+/// Capture files are analyzed with `settings` as they are loaded
+pub fn open(path: &Path, settings: Settings) -> Result<Box<dyn CaptureStore>, anyhow::Error> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("db") | Some("sqlite") => Ok(Box::new(SqliteStore::open(path)?)),
+        _ => Ok(Box::new(FileStore::open(path, settings)?)),
+    }
+}
+
+/// Decodes the message stored as the raw bytes of an event.
+/// Messages sent with running status are decoded using the stored status and channel
+fn decode_raw(status: Option<u8>, channel: Option<u8>, raw: &[u8]) -> Option<MidiMessage> {
+    let first = raw.first()?;
+    let mut parser = MidiParser::new();
+    if first & 0x80 == 0 {
+        parser.parse_midi(status? | channel.unwrap_or(0));
+    }
+    raw.iter().fold(None, |_, byte| parser.parse_midi(*byte).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    /// Runs bytes through a capture, one millisecond apart
+    pub(super) fn events(bytes: &[u8]) -> Vec<CaptureEvent> {
+        let mut capture = Capture::new();
+        bytes
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| capture.process(Duration::from_millis(i as u64), *byte))
+            .collect()
+    }
+
+    #[test]
+    fn running_status_raw() {
+        let events = events(&[0x93, 60, 100, 62, 100]);
+        let last = &events[4];
+        assert_eq!(last.raw, vec![62, 100]);
+        assert_eq!(
+            decode_raw(last.status, last.channel, &last.raw),
+            last.message
+        );
+    }
+
+    #[test]
+    fn query_criteria() {
+        let events = events(&[0x90, 60, 100, 0xF8, 0x99, 36, 90]);
+        let query = Query {
+            from: Some(Duration::from_millis(2)),
+            channels: [9].into(),
+            ..Query::default()
+        };
+        let matched: Vec<u8> = events
+            .iter()
+            .filter(|e| query.matches(e))
+            .map(|e| e.byte)
+            .collect();
+        assert_eq!(matched, vec![0x99, 36, 90]);
+    }
+}
@@ -0,0 +1,219 @@
+//! Store backed by a sqlite database
+//!
+//! Every event is a row, indexed by time and by status and channel, so queries over very
+//! long sessions do not need to load the whole capture
+
+use crate::{
+    capture::CaptureEvent,
+    midi::MidiAnalysis,
+    store::{decode_raw, CaptureStore, Query},
+};
+use anyhow::Context;
+use rusqlite::{params, Connection, Row};
+use std::{collections::BTreeSet, path::Path, time::Duration};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        time_us INTEGER NOT NULL,
+        byte INTEGER NOT NULL,
+        status INTEGER,
+        channel INTEGER,
+        raw BLOB NOT NULL,
+        severity TEXT NOT NULL,
+        analysis TEXT NOT NULL,
+        notice INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS events_time ON events (time_us);
+    CREATE INDEX IF NOT EXISTS events_kind ON events (status, channel);
+";
+
+/// Number of appended events committed together
+const BATCH_SIZE: usize = 4096;
+
+/// Keeps events in a sqlite database
+pub struct SqliteStore {
+    connection: Connection,
+    /// Number of appended events not yet committed
+    pending: usize,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if it does not exist
+    pub fn open(path: &Path) -> Result<SqliteStore, anyhow::Error> {
+        let connection =
+            Connection::open(path).context(format!("Unable to open database `{:?}`", path))?;
+        connection
+            .execute_batch(SCHEMA)
+            .context(format!("Unable to create tables in `{:?}`", path))?;
+        // Sessions stored before notices were have no column for them
+        let noticed: bool = connection.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('events') WHERE name = 'notice'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !noticed {
+            connection
+                .execute_batch("ALTER TABLE events ADD COLUMN notice INTEGER NOT NULL DEFAULT 0")
+                .context(format!("Unable to update the tables in `{:?}`", path))?;
+        }
+        Ok(SqliteStore {
+            connection,
+            pending: 0,
+        })
+    }
+
+    /// Rebuilds an event from a row of the events table
+    fn event(row: &Row) -> rusqlite::Result<CaptureEvent> {
+        let time: i64 = row.get(0)?;
+        let status: Option<u8> = row.get(2)?;
+        let channel: Option<u8> = row.get(3)?;
+        let raw: Vec<u8> = row.get(4)?;
+        let severity: String = row.get(5)?;
+        let text: String = row.get(6)?;
+        Ok(CaptureEvent {
+            time: Duration::from_micros(time as u64),
+            byte: row.get(1)?,
+            status,
+            channel,
+            message: decode_raw(status, channel, &raw),
+            raw,
+            analysis: MidiAnalysis::from_severity(&severity, text.clone())
+                .unwrap_or(MidiAnalysis::Comment(text)),
+            // Not stored, the inputs of a session are told apart while it runs
+            source: 0,
+            notice: row.get(7)?,
+        })
+    }
+}
+
+/// Formats a set of small integers as a SQL list
+fn sql_list(values: &BTreeSet<u8>) -> String {
+    let values: Vec<String> = values.iter().map(u8::to_string).collect();
+    values.join(", ")
+}
+
+impl CaptureStore for SqliteStore {
+    fn append(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        if self.pending == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection
+            .prepare_cached(
+                "INSERT INTO events
+                 (time_us, byte, status, channel, raw, severity, analysis, notice)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?
+            .execute(params![
+                event.time.as_micros() as i64,
+                event.byte,
+                event.status,
+                event.channel,
+                event.raw,
+                event.analysis.severity(),
+                event.analysis.text(),
+                event.notice,
+            ])?;
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn query(&self, query: &Query) -> Result<Vec<CaptureEvent>, anyhow::Error> {
+        let mut sql = "SELECT time_us, byte, status, channel, raw, severity, analysis, notice
+                       FROM events WHERE 1"
+            .to_string();
+        if let Some(from) = query.from {
+            sql += &format!(" AND time_us >= {}", from.as_micros());
+        }
+        if let Some(to) = query.to {
+            sql += &format!(" AND time_us <= {}", to.as_micros());
+        }
+        if !query.statuses.is_empty() {
+            sql += &format!(" AND status IN ({})", sql_list(&query.statuses));
+        }
+        if !query.channels.is_empty() {
+            sql += &format!(" AND channel IN ({})", sql_list(&query.channels));
+        }
+        sql += " ORDER BY id";
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let events = statement
+            .query_map([], SqliteStore::event)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Unable to query events")?;
+        Ok(events)
+    }
+
+    fn end(&self) -> Result<Option<Duration>, anyhow::Error> {
+        let time: Option<i64> =
+            self.connection
+                .query_row("SELECT MAX(time_us) FROM events", [], |row| row.get(0))?;
+        Ok(time.map(|t| Duration::from_micros(t as u64)))
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        if self.pending > 0 {
+            self.connection.execute_batch("COMMIT")?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::events;
+
+    #[test]
+    fn round_trip() {
+        let mut store = SqliteStore::open(Path::new(":memory:")).unwrap();
+        let mut events = events(&[0x90, 60, 100, 0xF8, 62, 0, 0xF0, 0x43, 0x01, 0xF7, 0x45]);
+        let lost = MidiAnalysis::Warning("Port `/dev/ttyUSB0` disconnected".to_string());
+        events.push(CaptureEvent::notice(0, Duration::from_millis(10), lost));
+        for event in &events {
+            store.append(event).unwrap();
+        }
+        store.flush().unwrap();
+        assert_eq!(store.query(&Query::default()).unwrap(), events);
+        assert_eq!(store.end().unwrap(), Some(Duration::from_millis(10)));
+
+        let query = Query {
+            to: Some(Duration::from_millis(5)),
+            statuses: [0x90].into(),
+            ..Query::default()
+        };
+        let bytes: Vec<u8> = store
+            .query(&query)
+            .unwrap()
+            .iter()
+            .map(|e| e.byte)
+            .collect();
+        assert_eq!(bytes, vec![0x90, 60, 100, 62, 0]);
+    }
+
+    #[test]
+    fn adds_the_notice_column() {
+        let path = std::env::temp_dir().join(format!("miditerm-old-{}.db", std::process::id()));
+        let schema = SCHEMA.replace(",\n        notice INTEGER NOT NULL DEFAULT 0", "");
+        assert!(!schema.contains("notice"));
+        let connection = Connection::open(&path).unwrap();
+        connection.execute_batch(&schema).unwrap();
+        connection
+            .execute(
+                "INSERT INTO events (time_us, byte, raw, severity, analysis)
+                 VALUES (0, 248, X'F8', 'comment', 'Timing Clock')",
+                [],
+            )
+            .unwrap();
+        drop(connection);
+        let store = SqliteStore::open(&path).unwrap();
+        let events = store.query(&Query::default()).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].notice);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! Reading and writing `.syx` raw SysEx dump files
+//!
+//! A `.syx` file is simply one or more complete System Exclusive messages,
+//! including their `F0` and `F7` framing bytes, stored back to back
+
+use anyhow::{bail, Context};
+use std::{fs, path::Path};
+
+/// Splits the contents of a `.syx` file into the data bytes of each message,
+/// without the `F0`/`F7` framing
+pub fn split(bytes: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+    let mut messages = vec![];
+    let mut current: Option<Vec<u8>> = None;
+    for (offset, byte) in bytes.iter().enumerate() {
+        match (*byte, &mut current) {
+            (0xF0, None) => current = Some(vec![]),
+            (0xF7, Some(_)) => messages.push(current.take().expect("Message should be open")),
+            (b, Some(data)) if b < 0x80 => data.push(b),
+            (b, _) => bail!("Unexpected byte {:02X} at offset {}", b, offset),
+        }
+    }
+    if current.is_some() {
+        bail!("File ends within a System Exclusive message");
+    }
+    Ok(messages)
+}
+
+/// Reads every message in a `.syx` file
+pub fn load(path: &Path) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+    let bytes = fs::read(path).context(format!("Unable to read file `{:?}`", path))?;
+    split(&bytes).context(format!("`{:?}` is not a valid .syx file", path))
+}
+
+/// Writes a single System Exclusive message to a `.syx` file
+pub fn save(path: &Path, data: &[u8]) -> Result<(), anyhow::Error> {
+    let bytes = [&[0xF0], data, &[0xF7]].concat();
+    fs::write(path, bytes).context(format!("Unable to write file `{:?}`", path))
+}
+
+/// Builds the file name of the `index`th exported message.
+/// The manufacturer ID is included to make dumps easier to tell apart
+pub fn file_name(index: usize, data: &[u8]) -> String {
+    let id = match data {
+        [0x00, a, b, ..] => format!("00{:02X}{:02X}", a, b),
+        [a, ..] => format!("{:02X}", a),
+        [] => "empty".to_string(),
+    };
+    format!("sysex_{:04}_{}.syx", index, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_messages() {
+        let bytes = [0xF0, 0x43, 0x10, 0xF7, 0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+        assert_eq!(
+            split(&bytes).unwrap(),
+            vec![vec![0x43, 0x10], vec![0x7E, 0x7F, 0x06, 0x01]]
+        );
+        assert!(split(&[0xF0, 0x43]).is_err());
+        assert!(split(&[0x43, 0xF7]).is_err());
+        assert!(split(&[0xF0, 0x90, 0xF7]).is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(file_name(1, &[0x43, 0x10]), "sysex_0001_43.syx");
+        assert_eq!(file_name(12, &[0x00, 0x20, 0x29]), "sysex_0012_002029.syx");
+    }
+}
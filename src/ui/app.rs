@@ -1,14 +1,68 @@
-use crossterm::event::KeyCode::Tab;
-use crossterm::event::{self, Event, KeyCode, MouseEventKind};
-use std::ops::Add;
+use crate::analysis::{
+    self, align,
+    channels::ChannelTracker,
+    clock::ClockAnalyzer,
+    diff,
+    intervals::IntervalAnalyzer,
+    mpe::MpeTracker,
+    rate::RateMeter,
+    smoothness::SmoothnessAnalyzer,
+    snapshot::{self, Snapshot},
+    stats::{Budgets, Statistics},
+    summary::Summary,
+    watch::Watch,
+    Reanalysis,
+};
+use crate::capture::{
+    Capture, CaptureEvent, EventIndex, Filter, TimeFormat, Timeline, MESSAGE_STATUSES, SENT,
+    SEVERITIES,
+};
+use crate::config::Config;
+use crate::export::array::{self, Language};
+use crate::midi::{
+    self, controls, notes::NoteNaming, sysex, MidiAnalysis, MidiChannelMode, MidiMessage,
+};
+use crate::sink::{trigger::Arm, CaptureRecorder, Sink};
+use crate::source::{self, input_name, Connection, Source, SourceEvent};
+use crate::state::UiState;
+use crate::syx;
+use crate::ui::{
+    composer::Composer,
+    files::{self, FileDialog, Purpose, Recording},
+    help, keyboard,
+    keys::Action,
+    layout,
+    pads::Pads,
+    panels,
+    ports::{Choice, PortDialog},
+    scrollback, scrollbar,
+    search::Search,
+    stepper::Stepper,
+    strip::Strip,
+    theme::{Monochrome, Palette, Theme},
+    workspace::Reference,
+    Alert, Keymap, Options, Panel,
+};
+use anyhow::Context;
+use arboard::Clipboard;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 use tui::layout::Direction;
 use tui::text::{Span, Spans};
-use tui::widgets::Paragraph;
 use tui::{
-    backend::Backend,
-    layout::{Constraint, Layout},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    backend::{Backend, TestBackend},
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
     Frame, Terminal,
 };
 
@@ -18,157 +72,2437 @@ const STYLE_DEFAULT: Style = Style {
     add_modifier: Modifier::empty(),
     sub_modifier: Modifier::empty(),
 };
-const STYLE_HEADER: Style = Style {
-    fg: Some(Color::Blue),
-    bg: Some(Color::Gray),
-    add_modifier: Modifier::BOLD,
-    sub_modifier: Modifier::empty(),
-};
-const STYLE_INFO: Style = Style {
-    fg: Some(Color::Cyan),
-    bg: None,
-    add_modifier: Modifier::empty(),
-    sub_modifier: Modifier::empty(),
-};
-const STYLE_WARNING: Style = Style {
-    fg: Some(Color::LightYellow),
-    bg: None,
-    add_modifier: Modifier::empty(),
-    sub_modifier: Modifier::empty(),
-};
-const STYLE_VIOLATION: Style = Style {
-    fg: Some(Color::LightRed),
-    bg: None,
-    add_modifier: Modifier::empty(),
-    sub_modifier: Modifier::empty(),
-};
 
-const HEADERS: [&str; 5] = ["BYTE", "TYPE", "CH", "MESSAGE", "DATA"];
+const HEADERS: [&str; 6] = ["SOURCE", "BYTE", "TYPE", "CH", "MESSAGE", "DATA"];
+/// Headers of the message view, with the bytes of each message in the second column
+const MESSAGE_HEADERS: [&str; 6] = ["SOURCE", "BYTES", "TYPE", "CH", "MESSAGE", "DATA"];
+/// Columns of `HEADERS` shown in the compact layout
+const COMPACT_COLUMNS: [usize; 3] = [1, 3, 4];
+/// What the items of the menu bar do when clicked, from left to right
+const MENU: [Action; 6] = [
+    Action::Filter,
+    Action::Load,
+    Action::Save,
+    Action::Ports,
+    Action::RecordTo,
+    Action::Quit,
+];
+/// Columns of `MESSAGE_HEADERS` shown in the compact layout
+const COMPACT_MESSAGE_COLUMNS: [usize; 4] = [1, 2, 3, 4];
+/// Bytes of a message shown before the rest are left out
+const MESSAGE_BYTES: usize = 3;
+/// Width of the source column, shown when bytes come from several inputs
+const SOURCE_WIDTH: u16 = 10;
+
+/// Terminals narrower than this use the compact layout
+const COMPACT_WIDTH: u16 = 80;
+/// Smallest terminal the UI can be drawn in
+const MIN_WIDTH: u16 = 30;
+const MIN_HEIGHT: u16 = 5;
+
+/// System Real Time statuses hidden by the real time filter
+const REALTIME_STATUSES: [u8; 6] = [0xF8, 0xFA, 0xFB, 0xFC, 0xFE, 0xFF];
+
+/// Number of bytes searched for the end of the selected message
+const MESSAGE_SEARCH_LIMIT: usize = 65_536;
+
+/// Panels are drawn beside the table on terminals at least this wide, and below it otherwise
+const PANEL_SIDE_WIDTH: u16 = 110;
+/// Width of the panels beside the table
+const PANEL_WIDTH: u16 = 40;
+/// Height of the panels below the table
+const PANEL_HEIGHT: u16 = 10;
+
+/// Items per row of the filter dialog
+const DIALOG_COLUMNS: usize = 4;
 
-struct App<'a> {
-    table_state: TableState,
-    analysis: Vec<Vec<&'a str>>,
-    messages: Vec<Vec<&'a str>>,
+/// How often the UI checks the source for new bytes while waiting for input
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Shortest time between two alerts of violations, so a flood of them does not ring on
+const ALERT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the status line flashes for
+const FLASH: Duration = Duration::from_millis(300);
+
+struct App {
+    /// Selected row of the filtered view
+    selected: Option<usize>,
+    /// First row of the filtered view shown in the table
+    offset: usize,
+    events: Vec<CaptureEvent>,
+    /// Events dropped from the start of the capture as the scrollback filled up
+    dropped: usize,
+    /// About how much memory the events take
+    memory: usize,
+    /// Where the dropped events are written, once the first are dropped
+    dropped_spill: Option<CaptureRecorder>,
+    /// Events grouped by status and channel for filtering
+    index: EventIndex,
+    filter: Filter,
+    /// Positions of the events that pass the filter, or `None` when nothing is filtered
+    view: Option<Vec<usize>>,
+    /// Rows hidden by the filter
+    hidden: usize,
     viewport: u16,
     /// When `true` the table should automatically scroll to the bottom as
     /// new entries are added
     follow: bool,
+    /// Rows fade as they age while following
+    dim: bool,
+    /// The table has a row per message rather than per byte
+    messages: bool,
+    options: Options,
+    /// Source the bytes are read from, while it is open
+    source: Option<Connection>,
+    capture: Capture,
+    timeline: Timeline,
+    clock: ClockAnalyzer,
+    /// Background re-analysis of the capture after the settings changed, with the number
+    /// of events dropped when it started
+    reanalysis: Option<(Receiver<Reanalysis>, usize)>,
+    /// Outputs fed with every received event
+    sinks: Vec<Box<dyn Sink>>,
+    /// Active recording, if any
+    recorder: Option<Recording>,
+    /// Number of SysEx messages saved with `x`
+    saved_sysex: usize,
+    /// SysEx message marked with `X` to compare with the next one
+    marked_sysex: Option<Vec<u8>>,
+    /// Differences of the SysEx messages compared with `X`, shown in a popup until it is
+    /// dismissed with Esc
+    sysex_diff: Vec<String>,
+    /// Bytes of the SysEx message viewed with `V`, shown in a popup until it is dismissed
+    /// with Esc
+    sysex_dump: Vec<String>,
+    /// State of the channels kept with `z`, to be compared with the current one
+    snapshot: Option<Snapshot>,
+    /// Changes of the channels since the snapshot, shown in a popup until it is dismissed
+    /// with Esc
+    state_diff: Vec<String>,
+    /// Message shown in the status line
+    status: String,
+    /// Item under the cursor of the filter dialog, when it is open
+    filter_dialog: Option<usize>,
+    /// Panels shown beside the event table
+    layout: layout::Layout,
+    stats: Statistics,
+    /// Does not depend on the settings, so it survives re-analysis
+    smoothness: SmoothnessAnalyzer,
+    /// Times between messages and between clocks
+    intervals: IntervalAnalyzer,
+    /// Controller charted by the watch panel
+    watch: Option<Watch>,
+    /// Gestures of the most recent notes, for the MPE panel
+    mpe: MpeTracker,
+    /// State of each channel, for the keyboard, controller, and level panels
+    channels: ChannelTracker,
+    /// Messages and bytes of the last minute, for the rate panel
+    rate: RateMeter,
+    /// The bus was close to saturation when last checked
+    saturated: bool,
+    /// File picked to load a capture from or save it to, while the dialog is open
+    file_dialog: Option<FileDialog>,
+    /// Ports to connect the capture to, while the dialog is open
+    port_dialog: Option<PortDialog>,
+    /// The keys are listed over the table
+    show_help: bool,
+    /// Name typed so far when saving the layout
+    layout_prompt: Option<String>,
+    /// Text typed so far when searching, and the row the search started from
+    search_prompt: Option<(String, Option<usize>)>,
+    /// Rows matching it are highlighted, and `n` and `N` move between them
+    search: Option<Search>,
+    /// When the source was opened, for the duration limit
+    started: Instant,
+    /// Where pads and stepped programs are sent
+    out: Option<Box<dyn Write + Send>>,
+    pads: Pads,
+    stepper: Stepper,
+    /// Message of the send panel
+    composer: Composer,
+    /// Position in the capture where the range being selected starts
+    anchor: Option<usize>,
+    /// Positions in the capture of the rows marked with `B`
+    bookmarks: BTreeSet<usize>,
+    /// Opened the first time something is copied
+    clipboard: Option<Clipboard>,
+    /// Budgets that have not raised an alarm yet
+    budgets: Budgets,
+    /// Alarms shown in a popup until it is dismissed with Esc
+    alarms: Vec<String>,
+    /// A violation was received since the last alert
+    violated: bool,
+    /// When the last alert of a violation was given
+    alerted: Option<Instant>,
+    /// `true` once the capture is being written to the spill file
+    spilling: bool,
+    /// Inputs bytes were received from, or are expected from
+    sources: BTreeSet<u8>,
+    /// Capture compared with the live one
+    reference: Option<Reference>,
+    /// The reference is shown beside the event table
+    show_reference: bool,
+    /// Byte density over the whole capture
+    strip: Strip,
+    /// Where the strip was last drawn, for clicks on it
+    strip_area: Rect,
+    /// Where the event table, its scrollbar, and the menu bar were last drawn, for clicks
+    /// on them
+    table_area: Rect,
+    scrollbar_area: Rect,
+    menu_area: Rect,
+    /// The thumb of the scrollbar is being dragged
+    dragging: bool,
+    /// Holds the capture back until its condition is met, if one was given
+    arm: Option<Arm>,
 }
 
-impl<'a> App<'a> {
-    pub(crate) fn new() -> App<'a> {
+impl App {
+    pub(crate) fn new(
+        mut options: Options,
+        source: Option<Connection>,
+        sinks: Vec<Box<dyn Sink>>,
+        out: Option<Box<dyn Write + Send>>,
+    ) -> App {
+        let reference = options.reference.take();
+        let arm = options.arm.take();
         App {
-            table_state: TableState::default(),
-            analysis: vec![
-                vec![" 90", "STATUS", " 1", "Note On", "-"],
-                vec![" 3C", "DATA  ", " 1", "Note On (Note)", "60"],
-                vec![" F8", "STATUS", " -", "Timing Clock", "-"],
-                vec![" 7F", "DATA  ", " 1", "Note On (Velocity)", "127"],
-                vec![" 9F", "STATUS", "16", "Note On", "-"],
-                vec![" 3C", "DATA  ", "16", "Note On (Note)", "60"],
-                vec![" F8", "STATUS", " -", "Timing Clock", "-"],
-                vec![" 7F", "DATA  ", "16", "Note On (Velocity)", "127"],
-                vec![" 3E", "DATA  ", "16", "Note On (Note)", "62"],
-                vec![" 7F", "DATA  ", "16", "Note On (Velocity)", "127"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" FE", "STATUS", " -", "Active Sense", "-"],
-                vec![" 90", "STATUS", " 1", "Note On", "-"],
-            ],
-            messages: vec![],
+            out,
+            pads: Pads::new(options.pads.clone()),
+            stepper: Stepper::new(&options.config),
+            composer: Composer::new(),
+            anchor: None,
+            bookmarks: BTreeSet::new(),
+            clipboard: None,
+            budgets: options.limits.budgets,
+            alarms: vec![],
+            violated: false,
+            alerted: None,
+            spilling: false,
+            sources: match &options.sources {
+                Some(names) => (0..names.len() as u8).collect(),
+                None => BTreeSet::new(),
+            },
+            show_reference: reference.is_some() && options.state.show_reference,
+            strip: Strip::new(),
+            strip_area: Rect::default(),
+            table_area: Rect::default(),
+            scrollbar_area: Rect::default(),
+            menu_area: Rect::default(),
+            dragging: false,
+            reference,
+            selected: None,
+            offset: 0,
+            events: vec![],
+            dropped: 0,
+            memory: 0,
+            dropped_spill: None,
+            index: EventIndex::new(),
+            filter: Filter::default(),
+            view: None,
+            hidden: 0,
             viewport: 0,
-            follow: true,
+            follow: options.state.follow,
+            dim: options.config.dim,
+            messages: options.state.messages,
+            source,
+            capture: Capture::with_settings(options.settings),
+            timeline: Timeline::new(options.start, options.source_timestamps),
+            clock: ClockAnalyzer::new(),
+            reanalysis: None,
+            layout: match &options.state.layout {
+                Some(layout) => layout.clone(),
+                None => options.config.layout.clone(),
+            },
+            options,
+            sinks,
+            recorder: None,
+            saved_sysex: 0,
+            marked_sysex: None,
+            sysex_diff: vec![],
+            sysex_dump: vec![],
+            snapshot: None,
+            state_diff: vec![],
+            status: match &arm {
+                Some(_) => "Armed, waiting for the `--arm-on` condition".to_string(),
+                None => String::new(),
+            },
+            arm,
+            filter_dialog: None,
+            stats: Statistics::new(),
+            smoothness: SmoothnessAnalyzer::new(),
+            intervals: IntervalAnalyzer::new(),
+            watch: None,
+            mpe: MpeTracker::new(),
+            channels: ChannelTracker::new(),
+            rate: RateMeter::new(),
+            saturated: false,
+            file_dialog: None,
+            port_dialog: None,
+            show_help: false,
+            layout_prompt: None,
+            search_prompt: None,
+            search: None,
+            started: Instant::now(),
         }
     }
 
     pub fn previous(&mut self) {
         self.follow = false;
-        self.table_state.select(
-            self.table_state
-                .selected()
-                .unwrap_or(0)
-                .checked_sub(self.viewport as usize),
-        );
+        self.selected = self
+            .selected
+            .unwrap_or(0)
+            .checked_sub(self.viewport as usize);
     }
     pub fn next(&mut self) {
         self.follow = false;
-        self.table_state.select(
-            self.table_state
-                .selected()
-                .unwrap_or(self.analysis.len())
-                .checked_add(self.viewport as usize),
+        let last = self.rows().saturating_sub(1);
+        self.selected = Some(
+            self.selected
+                .unwrap_or(last)
+                .saturating_add(self.viewport as usize)
+                .min(last),
         );
     }
-    pub fn last(&mut self) {
-        self.follow = true;
-        self.table_state.select(Some(self.analysis.len() as usize));
+
+    /// Moves the selection by a number of rows, back if negative, stopping at the first and
+    /// last rows
+    fn move_rows(&mut self, rows: isize) {
+        let Some(last) = self.rows().checked_sub(1) else {
+            return;
+        };
+        self.follow = false;
+        let from = self.selected.unwrap_or(last);
+        self.selected = Some(from.saturating_add_signed(rows).min(last));
+    }
+
+    /// Returns the number of rows in the filtered view
+    fn rows(&self) -> usize {
+        self.view.as_ref().map_or(self.events.len(), Vec::len)
+    }
+
+    /// Returns the position in the capture of the event shown at `row`
+    fn position(&self, row: usize) -> Option<usize> {
+        match &self.view {
+            Some(view) => view.get(row).copied(),
+            None => (row < self.events.len()).then_some(row),
+        }
+    }
+
+    /// Selects the first row at or after the stretch of the capture drawn in a column of the
+    /// strip
+    fn jump_to(&mut self, column: u16) {
+        let columns = self.strip_area.width as usize;
+        let column = column.saturating_sub(self.strip_area.x) as usize;
+        if column >= columns || self.rows() == 0 {
+            return;
+        }
+        let time = self.strip.span(column, columns).start;
+        let rows: Vec<usize> = (0..self.rows()).collect();
+        let row = rows.partition_point(|row| {
+            self.position(*row)
+                .is_some_and(|position| self.events[position].time < time)
+        });
+        self.follow = false;
+        self.selected = Some(row.min(self.rows() - 1));
+    }
+
+    /// Handles the mouse: the wheel scrolls by pages, clicks select rows and jump along the
+    /// strip, and the thumb of the scrollbar is dragged. Returns what the item of the menu
+    /// bar clicked does
+    fn mouse(&mut self, mouse: MouseEvent) -> Option<Action> {
+        let within = |area: Rect| {
+            (area.left()..area.right()).contains(&mouse.column)
+                && (area.top()..area.bottom()).contains(&mouse.row)
+        };
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.previous(),
+            MouseEventKind::ScrollDown => self.next(),
+            // A click closes the help like any key, and is left alone by dialogs that only
+            // take keys
+            MouseEventKind::Down(_) if self.show_help => self.show_help = false,
+            _ if self.modal() => {}
+            MouseEventKind::Down(MouseButton::Left) if within(self.strip_area) => {
+                self.jump_to(mouse.column)
+            }
+            MouseEventKind::Down(MouseButton::Left) if within(self.scrollbar_area) => {
+                self.dragging = true;
+                self.scroll_to(mouse.row);
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => self.scroll_to(mouse.row),
+            MouseEventKind::Up(MouseButton::Left) => self.dragging = false,
+            MouseEventKind::Down(MouseButton::Left) if within(self.menu_area) => {
+                let column = (mouse.column - self.menu_area.x) as usize;
+                let item = column * MENU.len() / self.menu_area.width as usize;
+                return MENU.get(item).copied();
+            }
+            // Below the header of the table
+            MouseEventKind::Down(MouseButton::Left)
+                if within(self.table_area) && mouse.row > self.table_area.y =>
+            {
+                let row = self.offset + (mouse.row - self.table_area.y - 1) as usize;
+                if row < self.rows() {
+                    self.follow = false;
+                    self.selected = Some(row);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns `true` while a dialog or prompt takes the keys
+    fn modal(&self) -> bool {
+        self.filter_dialog.is_some()
+            || self.file_dialog.is_some()
+            || self.port_dialog.is_some()
+            || self.layout_prompt.is_some()
+            || self.search_prompt.is_some()
+    }
+
+    /// Scrolls the table to where the cell `y` of the scrollbar stands for, keeping the
+    /// selection in view
+    fn scroll_to(&mut self, y: u16) {
+        let rows = self.rows();
+        if rows == 0 {
+            return;
+        }
+        let shown = (self.viewport as usize).clamp(1, rows);
+        let track = self.scrollbar_area;
+        let first = scrollbar::row_at(y.saturating_sub(track.y), track.height, rows - shown + 1);
+        self.follow = false;
+        self.offset = first;
+        self.selected = Some(
+            self.selected
+                .unwrap_or(first)
+                .clamp(first, first + shown - 1),
+        );
+    }
+
+    /// Adds a newly received event to the capture, index, and view. Sent events are not
+    /// counted with the received ones
+    fn push_event(&mut self, event: CaptureEvent) {
+        self.strip.observe(event.time);
+        // Neither sent bytes nor notices were received
+        if event.source != SENT && !event.notice {
+            self.stats.observe(&event);
+            self.smoothness.observe(&event);
+            self.intervals.observe(&event);
+            if let Some(watch) = &mut self.watch {
+                watch.observe(&event);
+            }
+            self.mpe.observe(&event);
+            self.channels.observe(&event);
+            self.rate.observe(&event);
+            if matches!(event.analysis, MidiAnalysis::Violation(_)) {
+                self.violated = true;
+            }
+        }
+        self.sources.insert(event.source);
+        self.memory += scrollback::footprint(&event);
+        self.index.push(&event);
+        if self.shows(&event) {
+            if !self.filter.matches(&event) {
+                self.hidden += 1;
+            } else if let Some(view) = &mut self.view {
+                view.push(self.events.len());
+            }
+        }
+        self.events.push(event);
+        if let Some(scrollback) = self.options.scrollback {
+            let excess = scrollback.excess(&self.events, self.memory);
+            if excess > 0 {
+                self.drop_oldest(excess);
+            }
+        }
+    }
+
+    /// Drops the oldest events once the scrollback is full, writing them to the spill file if
+    /// there is one, and rebuilds the index and view keeping the selection where it was
+    fn drop_oldest(&mut self, count: usize) {
+        if self.dropped == 0 {
+            self.status = "Scrollback full, dropping the oldest events".to_string();
+            if let Some(path) = &self.options.scrollback_spill {
+                match CaptureRecorder::create(path) {
+                    Ok(recorder) => self.dropped_spill = Some(recorder),
+                    Err(e) => self.status = format!("{:#}", e),
+                }
+            }
+        }
+        if let Some(recorder) = &mut self.dropped_spill {
+            let written = self.events[..count]
+                .iter()
+                .try_for_each(|event| recorder.write(event));
+            if let Err(e) = written {
+                self.status = format!("Unable to spill the dropped events: {:#}", e);
+                self.dropped_spill = None;
+            }
+        }
+        let position = self.selected.and_then(|row| self.position(row));
+        self.memory -= self.events[..count]
+            .iter()
+            .map(scrollback::footprint)
+            .sum::<usize>();
+        self.events.drain(..count);
+        self.dropped += count;
+        self.index = EventIndex::new();
+        for event in &self.events {
+            self.index.push(event);
+        }
+        self.anchor = self.anchor.and_then(|anchor| anchor.checked_sub(count));
+        self.bookmarks = self
+            .bookmarks
+            .iter()
+            .filter_map(|position| position.checked_sub(count))
+            .collect();
+        // With no view rows are positions, which `set_filter` turns back into rows
+        self.view = None;
+        self.selected = position.map(|position| position.saturating_sub(count));
+        self.set_filter(self.filter.clone());
+    }
+
+    /// Returns `true` if the event has a row of its own: every byte does, and in the message
+    /// view the bytes that complete a message and those with something wrong with them
+    fn shows(&self, event: &CaptureEvent) -> bool {
+        !self.messages
+            || event.message.is_some()
+            || matches!(
+                event.analysis,
+                MidiAnalysis::Warning(_) | MidiAnalysis::Violation(_)
+            )
+    }
+
+    /// Rebuilds the view from the index, keeping the selection on the same part of the capture
+    fn set_filter(&mut self, filter: Filter) {
+        if let Some(reference) = &mut self.reference {
+            reference.set_filter(&filter);
+        }
+        let position = self.selected.and_then(|row| self.position(row));
+        self.view = (!filter.is_empty() || self.messages).then(|| {
+            let mut view = self.index.select(&filter, &self.events);
+            view.retain(|p| self.shows(&self.events[*p]));
+            view
+        });
+        let rows = match self.messages {
+            true => self.events.iter().filter(|e| self.shows(e)).count(),
+            false => self.events.len(),
+        };
+        self.hidden = rows - self.rows();
+        self.filter = filter;
+        self.selected = position.map(|position| self.row_of(position));
+    }
+
+    /// Returns the row of the event at `position`, or of the next one shown if it is hidden
+    fn row_of(&self, position: usize) -> usize {
+        match &self.view {
+            Some(view) => view.partition_point(|p| *p < position),
+            None => position,
+        }
+    }
+
+    /// Switches the table between a row per byte and a row per message, keeping the
+    /// selection on the same part of the capture
+    pub fn toggle_message_view(&mut self) {
+        self.messages = !self.messages;
+        self.set_filter(self.filter.clone());
+        self.status = if self.messages {
+            "A row per message".to_string()
+        } else {
+            "A row per byte".to_string()
+        };
+    }
+
+    /// Shows or hides System Real Time messages
+    pub fn toggle_realtime_filter(&mut self) {
+        let mut filter = self.filter.clone();
+        let hidden = REALTIME_STATUSES
+            .iter()
+            .all(|s| filter.hidden_statuses.contains(s));
+        for status in REALTIME_STATUSES {
+            if hidden {
+                filter.hidden_statuses.remove(&status);
+            } else {
+                filter.hidden_statuses.insert(status);
+            }
+        }
+        self.set_filter(filter);
+        self.status = if hidden {
+            "Showing System Real Time messages".to_string()
+        } else {
+            "Hiding System Real Time messages".to_string()
+        };
+    }
+
+    /// Opens or closes the filter dialog
+    pub fn toggle_filter_dialog(&mut self) {
+        self.filter_dialog = match self.filter_dialog {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    /// Returns the items of the filter dialog: the 16 channels, the message types, the
+    /// severities of the analysis, the inputs when bytes come from several, and the text the
+    /// analysis must contain
+    fn filter_items(&self) -> Vec<FilterItem> {
+        let channels = (0..16).map(FilterItem::Channel);
+        let statuses = MESSAGE_STATUSES.into_iter().map(FilterItem::Status);
+        let severities = (0..SEVERITIES.len() as u8).map(FilterItem::Severity);
+        let sources = self
+            .sources
+            .iter()
+            .filter(|_| self.options.sources.is_some())
+            .map(|source| FilterItem::Source(*source));
+        channels
+            .chain(statuses)
+            .chain(severities)
+            .chain(sources)
+            .chain([FilterItem::Pattern])
+            .collect()
+    }
+
+    /// Handles a key pressed while the filter dialog is open
+    fn filter_dialog_key(&mut self, code: KeyCode) {
+        let Some(cursor) = self.filter_dialog else {
+            return;
+        };
+        let items = self.filter_items();
+        let grid = dialog_grid(&items);
+        // The item in the same column of the row above or below, or the last of a shorter row
+        let vertical = |row: Option<usize>| {
+            let row = row?;
+            let column = grid[cursor].1;
+            grid.iter().rposition(|(r, c)| *r == row && *c <= column)
+        };
+        let mut filter = self.filter.clone();
+        match (code, items[cursor]) {
+            (KeyCode::Left, _) => self.filter_dialog = Some(cursor.saturating_sub(1)),
+            (KeyCode::Right, _) => self.filter_dialog = Some((cursor + 1).min(items.len() - 1)),
+            (KeyCode::Up, _) => {
+                self.filter_dialog = vertical(grid[cursor].0.checked_sub(1)).or(Some(cursor))
+            }
+            (KeyCode::Down, _) => {
+                self.filter_dialog = vertical(Some(grid[cursor].0 + 1)).or(Some(cursor))
+            }
+            (KeyCode::Char(c), FilterItem::Pattern) => filter.pattern.push(c),
+            (KeyCode::Backspace, FilterItem::Pattern) => {
+                filter.pattern.pop();
+            }
+            (KeyCode::Char(' ') | KeyCode::Enter, FilterItem::Channel(ch)) => {
+                filter.hidden_channels ^= 1 << ch
+            }
+            (KeyCode::Char(' ') | KeyCode::Enter, FilterItem::Status(status)) => {
+                toggle(&mut filter.hidden_statuses, status)
+            }
+            (KeyCode::Char(' ') | KeyCode::Enter, FilterItem::Severity(severity)) => {
+                filter.hidden_severities ^= 1 << severity
+            }
+            (KeyCode::Char(' ') | KeyCode::Enter, FilterItem::Source(source)) => {
+                toggle(&mut filter.hidden_sources, source)
+            }
+            (KeyCode::Char('a'), FilterItem::Channel(_)) => filter.hidden_channels = 0,
+            (KeyCode::Char('a'), FilterItem::Status(_)) => filter.hidden_statuses.clear(),
+            (KeyCode::Char('a'), FilterItem::Severity(_)) => filter.hidden_severities = 0,
+            (KeyCode::Char('a'), FilterItem::Source(_)) => filter.hidden_sources.clear(),
+            (KeyCode::Char('o'), FilterItem::Channel(ch)) => filter.hidden_channels = !(1 << ch),
+            (KeyCode::Char('o'), FilterItem::Status(status)) => {
+                filter.hidden_statuses = MESSAGE_STATUSES.into_iter().collect();
+                filter.hidden_statuses.remove(&status);
+            }
+            (KeyCode::Char('o'), FilterItem::Severity(severity)) => {
+                filter.hidden_severities = ((1 << SEVERITIES.len()) - 1) & !(1 << severity)
+            }
+            (KeyCode::Char('o'), FilterItem::Source(source)) => {
+                filter.hidden_sources = self.sources.clone();
+                filter.hidden_sources.remove(&source);
+            }
+            (KeyCode::Esc | KeyCode::F(1) | KeyCode::Char('q'), _) => self.filter_dialog = None,
+            _ => {}
+        }
+        if filter != self.filter {
+            self.set_filter(filter);
+        }
+    }
+
+    /// Shows or hides a panel
+    pub fn toggle_panel(&mut self, panel: Panel) {
+        self.layout.toggle(panel);
+    }
+
+    /// Charts the controller of the selected Control Change or Pitch Bend in the watch
+    /// panel, from the updates already received. Any other row hides the panel
+    fn watch_selected(&mut self) {
+        let update = self
+            .selected
+            .and_then(|row| self.position(row))
+            .and_then(|position| self.message_of(position))
+            .and_then(|event| event.message.as_ref())
+            .and_then(Watch::update);
+        let Some((channel, controller, _)) = update else {
+            if self.layout.panels.contains(&Panel::Watch) {
+                self.layout.toggle(Panel::Watch);
+                self.status = String::new();
+            } else {
+                self.status = "Select a Control Change or Pitch Bend to watch".to_string();
+            }
+            return;
+        };
+        let mut watch = Watch::new(channel, controller);
+        for event in self.events.iter().filter(|event| event.source != SENT) {
+            watch.observe(event);
+        }
+        self.watch = Some(watch);
+        if !self.layout.panels.contains(&Panel::Watch) {
+            self.layout.toggle(Panel::Watch);
+        }
+        self.status = format!("Watching Ch {} {}", channel + 1, controller);
+    }
+
+    /// Returns what the application shows, to be restored by the next session
+    fn state(&self) -> UiState {
+        UiState {
+            port: self.options.state.port.clone(),
+            layout: Some(self.layout.clone()),
+            filter: Some(self.filter.clone()),
+            follow: self.follow,
+            messages: self.messages,
+            // Only changed when there is a reference to show
+            show_reference: match self.reference {
+                Some(_) => self.show_reference,
+                None => self.options.state.show_reference,
+            },
+        }
+    }
+
+    /// Shows the reference capture if it is hidden, or hides it if it is shown
+    fn toggle_reference(&mut self) {
+        if self.reference.is_none() {
+            self.status = "No reference capture, give one with `--reference`".to_string();
+            return;
+        }
+        self.show_reference = !self.show_reference;
+    }
+
+    /// Shifts the reference capture so it lines up with the live one
+    fn align_reference(&mut self) {
+        let Some(reference) = &mut self.reference else {
+            self.status = "No reference capture, give one with `--reference`".to_string();
+            return;
+        };
+        self.status = match align::align(&self.events, &reference.events) {
+            Some(alignment) => {
+                reference.set_offset(alignment.offset);
+                format!("Reference aligned at {}", alignment)
+            }
+            None => {
+                "Nothing to align the reference by, no Start messages or notes in both".to_string()
+            }
+        };
+    }
+
+    /// Shifts the reference capture in time against the live one, or back to no offset
+    fn shift_reference(&mut self, forward: Option<bool>) {
+        let Some(reference) = &mut self.reference else {
+            return;
+        };
+        match forward {
+            Some(forward) => reference.shift(forward),
+            None => reference.set_offset(0),
+        }
+        self.status = format!("Reference offset {:+.3} s", reference.offset());
+    }
+
+    /// Returns the pad bound to the key, if the pads are shown. `q`, `p`, and `P` keep their
+    /// meaning so the pads can always be hidden, stuck notes silenced, and the application
+    /// quit
+    fn pad(&self, code: KeyCode) -> Option<usize> {
+        match code {
+            KeyCode::Char('q' | 'p' | 'P') => None,
+            KeyCode::Char(key) if self.layout.panels.contains(&Panel::Pads) => self.pads.find(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the channel of the key in the channel grid, if the grid is shown
+    fn channel_key(&self, code: KeyCode) -> Option<u8> {
+        match code {
+            KeyCode::Char(key) if self.layout.panels.contains(&Panel::Channels) => {
+                let channel = panels::CHANNEL_KEYS.iter().position(|k| *k == key)?;
+                Some(channel as u8)
+            }
+            _ => None,
+        }
+    }
+
+    /// Hides or shows the channel of the key in the channel grid, or with Alt shows it alone,
+    /// and every channel again if it already was
+    fn mute_channel(&mut self, key: KeyEvent) {
+        let Some(channel) = self.channel_key(key.code) else {
+            return;
+        };
+        let mut filter = self.filter.clone();
+        let solo = !(1 << channel);
+        filter.hidden_channels = match key.modifiers.contains(KeyModifiers::ALT) {
+            true if filter.hidden_channels == solo => 0,
+            true => solo,
+            false => filter.hidden_channels ^ 1 << channel,
+        };
+        self.status = match filter.hidden_channels {
+            0 => "All channels shown".to_string(),
+            hidden if hidden == solo => format!("Channel {} solo", channel + 1),
+            hidden if hidden & 1 << channel != 0 => format!("Channel {} muted", channel + 1),
+            _ => format!("Channel {} shown", channel + 1),
+        };
+        self.set_filter(filter);
+    }
+
+    /// Returns the tab shown when bytes come from several inputs: 0 for all of them, or one
+    /// past the position of the only input shown. `None` if the filter shows some other set
+    /// of inputs
+    fn tab(&self) -> Option<usize> {
+        if self.filter.hidden_sources.is_empty() {
+            return Some(0);
+        }
+        self.sources
+            .iter()
+            .position(|source| {
+                self.sources
+                    .iter()
+                    .all(|s| (s == source) != self.filter.hidden_sources.contains(s))
+            })
+            .map(|position| position + 1)
+    }
+
+    /// Shows the events of the next or previous input alone, or of all of them after the
+    /// last, when bytes come from several inputs
+    fn next_tab(&mut self, forward: bool) {
+        if self.options.sources.is_none() {
+            return;
+        }
+        let inputs: Vec<u8> = self.sources.iter().copied().collect();
+        let tab = match (self.tab(), forward) {
+            (None, _) => 0,
+            (Some(tab), true) => (tab + 1) % (inputs.len() + 1),
+            (Some(tab), false) => tab.checked_sub(1).unwrap_or(inputs.len()),
+        };
+        let mut filter = self.filter.clone();
+        filter.hidden_sources.clear();
+        self.status = match tab.checked_sub(1).map(|i| inputs[i]) {
+            Some(shown) => {
+                filter
+                    .hidden_sources
+                    .extend(inputs.iter().filter(|input| **input != shown));
+                let names = self.options.sources.as_deref().unwrap_or_default();
+                format!("Showing {} alone", input_name(names, shown))
+            }
+            None => "Showing all inputs".to_string(),
+        };
+        self.set_filter(filter);
+    }
+
+    /// Describes what the filter hides, a line for each kind of event hidden
+    fn describe_filter(&self) -> Vec<String> {
+        let filter = &self.filter;
+        if filter.is_empty() {
+            return vec!["Everything shown".to_string()];
+        }
+        let mut lines = vec![];
+        let channels: Vec<String> = (0..16)
+            .filter(|ch| filter.hidden_channels & (1 << ch) != 0)
+            .map(|ch| (ch + 1).to_string())
+            .collect();
+        if !channels.is_empty() {
+            lines.push(format!("Channels hidden: {}", channels.join(", ")));
+        }
+        let statuses: Vec<&str> = filter
+            .hidden_statuses
+            .iter()
+            .map(|status| status_label(*status))
+            .collect();
+        if !statuses.is_empty() {
+            lines.push(format!("Messages hidden: {}", statuses.join(", ")));
+        }
+        let severities: Vec<&str> = (0..SEVERITIES.len())
+            .filter(|i| filter.hidden_severities & (1 << i) != 0)
+            .map(|i| SEVERITIES[i])
+            .collect();
+        if !severities.is_empty() {
+            lines.push(format!("Severities hidden: {}", severities.join(", ")));
+        }
+        let names = self.options.sources.as_deref().unwrap_or_default();
+        let sources: Vec<String> = filter
+            .hidden_sources
+            .iter()
+            .map(|source| input_name(names, *source))
+            .collect();
+        if !sources.is_empty() {
+            lines.push(format!("Inputs hidden: {}", sources.join(", ")));
+        }
+        if !filter.pattern.is_empty() {
+            lines.push(format!("Analysis containing `{}`", filter.pattern));
+        }
+        lines.push(format!("{} rows hidden", self.hidden));
+        lines
+    }
+
+    /// Sends the message of the pad bound to the key to MIDI Out
+    fn hit_pad(&mut self, code: KeyCode) {
+        let Some(pad) = self.pad(code) else {
+            return;
+        };
+        if self.out.is_none() {
+            self.status = "No MIDI Out to send pads to, give one with `--out`".to_string();
+            return;
+        }
+        let pad = self.pads.hit(pad, Instant::now()).clone();
+        self.status = format!("Pad {}: {}", pad.label, midi::hex(&pad.bytes));
+        self.send(&pad.bytes);
+    }
+
+    /// Silences stuck notes on every channel of MIDI Out
+    pub fn panic(&mut self) {
+        if self.out.is_none() {
+            self.status = "No MIDI Out to send the panic to, give one with `--out`".to_string();
+            return;
+        }
+        self.status = "Panic: Sustain off, All Sound Off, All Notes Off, and Reset All Controllers on all channels".to_string();
+        self.send(&midi::panic_bytes());
+    }
+
+    /// Sends the next or previous program of the stepper to MIDI Out
+    pub fn step_program(&mut self, forward: bool) {
+        if self.out.is_none() {
+            self.status = "No MIDI Out to send programs to, give one with `--out`".to_string();
+            return;
+        }
+        let bytes = self.stepper.step(forward);
+        self.status = format!(
+            "{}: {}",
+            self.stepper.describe(&self.options.config),
+            midi::hex(&bytes)
+        );
+        self.send(&bytes);
+    }
+
+    /// Moves the stepper to the next or previous channel
+    pub fn step_channel(&mut self, forward: bool) {
+        self.stepper.change_channel(forward);
+        self.status = self.stepper.describe(&self.options.config);
+    }
+
+    /// Returns `true` if the key is taken by the send panel, when it is shown
+    fn composes(&self, code: KeyCode) -> bool {
+        self.layout.panels.contains(&Panel::Send) && self.composer.takes(code)
+    }
+
+    /// Edits the message of the send panel, or sends it to MIDI Out on Enter and logs it
+    /// among the received messages
+    fn compose(&mut self, code: KeyCode) {
+        if code != KeyCode::Enter {
+            self.composer.key(code);
+            return;
+        }
+        if self.out.is_none() {
+            self.status = "No MIDI Out to send messages to, give one with `--out`".to_string();
+            return;
+        }
+        let message = match self.composer.message() {
+            Ok(message) => message,
+            Err(e) => {
+                self.status = e;
+                return;
+            }
+        };
+        let bytes = message.to_bytes();
+        if !self.send(&bytes) {
+            return;
+        }
+        self.status = format!("Sent {}", midi::hex(&bytes));
+        self.log_sent(&bytes);
+    }
+
+    /// Adds bytes sent to MIDI Out to the capture, among the received ones
+    fn log_sent(&mut self, bytes: &[u8]) {
+        let time = self.timeline.time(Instant::now(), 0, None);
+        for byte in bytes {
+            let event = self.capture.process_from(SENT, time, *byte);
+            self.push_event(event);
+        }
+    }
+
+    /// Writes bytes to MIDI Out, reporting failures in the status line. Returns `true` if
+    /// they were written
+    fn send(&mut self, bytes: &[u8]) -> bool {
+        let Some(out) = &mut self.out else {
+            return false;
+        };
+        match out.write_all(bytes).and_then(|_| out.flush()) {
+            Ok(()) => true,
+            Err(e) => {
+                self.status = format!("MIDI Out failed: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Switches to the layout saved with the given number, counting from 1 in name order
+    pub fn switch_layout(&mut self, number: usize) {
+        let layout = self
+            .options
+            .config
+            .layouts
+            .iter()
+            .nth(number.wrapping_sub(1));
+        self.status = match layout {
+            Some((name, layout)) => {
+                self.layout = layout.clone();
+                format!("Layout `{}`", name)
+            }
+            None => format!("No layout {}", number),
+        };
+    }
+
+    /// Handles a key pressed while the name of a layout is being typed
+    fn layout_prompt_key(&mut self, code: KeyCode) {
+        let Some(name) = &mut self.layout_prompt else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::Backspace => {
+                name.pop();
+            }
+            KeyCode::Enter => {
+                let name = name.trim().to_string();
+                self.layout_prompt = None;
+                if !name.is_empty() {
+                    self.save_layout(name);
+                }
+            }
+            KeyCode::Esc => self.layout_prompt = None,
+            _ => {}
+        }
+    }
+
+    /// Handles a key pressed while the file dialog is open, loading or saving once a file is
+    /// picked
+    fn file_dialog_key(&mut self, code: KeyCode) {
+        let Some(dialog) = &mut self.file_dialog else {
+            return;
+        };
+        if code == KeyCode::Esc {
+            self.file_dialog = None;
+            return;
+        }
+        let Some(path) = dialog.key(code) else {
+            return;
+        };
+        let (purpose, filtered) = (dialog.purpose, dialog.filtered);
+        self.file_dialog = None;
+        self.status = match purpose {
+            Purpose::Load => match self.load_capture(&path) {
+                Ok(bytes) => format!("Loaded {} bytes from {:?}", bytes, path),
+                Err(e) => format!("{:#}", e),
+            },
+            Purpose::Save => match self.save_capture(&path, filtered) {
+                Ok(bytes) => format!("Saved {} bytes to {:?}", bytes, path),
+                Err(e) => format!("{:#}", e),
+            },
+            Purpose::Record => return self.start_recording(&path),
+        };
+    }
+
+    /// Opens the dialog of the ports, serial ports to be opened at the configured baud rate
+    fn open_port_dialog(&mut self) {
+        let baud = self.options.config.baud.unwrap_or(midi::MIDI_BAUD_RATE);
+        self.port_dialog = Some(PortDialog::new(baud));
+    }
+
+    /// Handles a key pressed while the dialog of the ports is open, connecting or
+    /// disconnecting once it is picked
+    fn port_dialog_key(&mut self, code: KeyCode) {
+        let Some(dialog) = &mut self.port_dialog else {
+            return;
+        };
+        if code == KeyCode::Esc {
+            self.port_dialog = None;
+            return;
+        }
+        match dialog.key(code) {
+            Some(Choice::Connect(source, remembered)) => {
+                self.port_dialog = None;
+                self.connect(source, remembered);
+            }
+            Some(Choice::Disconnect) => {
+                self.port_dialog = None;
+                self.status = match self.source.take() {
+                    Some(source) => format!("Disconnected from {}", source.name),
+                    None => "Not connected".to_string(),
+                };
+            }
+            None => {}
+        }
+    }
+
+    /// Reads the capture from another source, closing the one before. The port is
+    /// remembered for the next session, if there is one
+    fn connect(&mut self, source: Source, remembered: Option<OsString>) {
+        let name = source.name();
+        if self.source.as_ref().is_some_and(|s| s.name == name) {
+            self.status = format!("Already connected to {}", name);
+            return;
+        }
+        self.status = match source.connect() {
+            // The source before is closed as it is replaced
+            Ok(connection) => {
+                // Bytes of the new source do not carry on the running status of the old one
+                self.capture.interrupt();
+                self.source = Some(connection);
+                if remembered.is_some() {
+                    self.options.state.port = remembered;
+                }
+                format!("Connected to {}", name)
+            }
+            Err(e) => format!("{:#}", e),
+        };
+    }
+
+    /// Replaces the capture with the one read from a file, analyzed from scratch. The source
+    /// is closed so its bytes do not run on from the end of the file. Returns the number of
+    /// bytes read
+    fn load_capture(&mut self, path: &Path) -> Result<usize, anyhow::Error> {
+        let bytes = source::load_file(path)?;
+        self.source = None;
+        self.reanalysis = None;
+        self.events.clear();
+        self.dropped = 0;
+        self.memory = 0;
+        self.index = EventIndex::new();
+        self.capture = Capture::with_settings(self.options.settings);
+        self.clock = ClockAnalyzer::new();
+        self.stats = Statistics::new();
+        self.smoothness = SmoothnessAnalyzer::new();
+        self.intervals = IntervalAnalyzer::new();
+        self.watch = self
+            .watch
+            .as_ref()
+            .map(|watch| Watch::new(watch.channel, watch.controller));
+        self.mpe = MpeTracker::new();
+        self.channels = ChannelTracker::new();
+        self.rate = RateMeter::new();
+        self.strip = Strip::new();
+        self.anchor = None;
+        self.bookmarks.clear();
+        self.selected = None;
+        self.follow = false;
+        for (time, byte) in &bytes {
+            let event = self.capture.process(*time, *byte);
+            self.clock.observe(&event);
+            self.push_event(event);
+        }
+        self.set_filter(self.filter.clone());
+        Ok(bytes.len())
+    }
+
+    /// Writes the capture, or only the events that pass the filter, to a file in the format
+    /// of its extension. Returns the number of bytes written
+    fn save_capture(&mut self, path: &Path, filtered: bool) -> Result<usize, anyhow::Error> {
+        let mut output = files::output(path, self.options.ppq, self.options.bpm)?;
+        let mut saved = 0;
+        for event in self
+            .events
+            .iter()
+            .filter(|event| !filtered || self.filter.matches(event))
+        {
+            output.write(event)?;
+            saved += 1;
+        }
+        output
+            .finish()
+            .context(format!("Unable to save `{:?}`", path))?;
+        Ok(saved)
+    }
+
+    /// Starts a search from the selected row
+    pub fn start_search(&mut self) {
+        self.search_prompt = Some((String::new(), self.selected));
+    }
+
+    /// Handles a key pressed while the search is being typed. Every change selects the first
+    /// match from where the search started, Enter keeps the search, and Esc clears it and
+    /// goes back
+    fn search_prompt_key(&mut self, code: KeyCode) {
+        let Some((text, origin)) = &mut self.search_prompt else {
+            return;
+        };
+        let origin = *origin;
+        match code {
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Enter => {
+                self.search_prompt = None;
+                if self.search.is_some() {
+                    self.status = "n next match, N previous".to_string();
+                }
+                return;
+            }
+            KeyCode::Esc => {
+                self.search_prompt = None;
+                self.search = None;
+                self.selected = origin;
+                return;
+            }
+            _ => return,
+        }
+        self.search = Search::parse(text);
+        let start = origin.unwrap_or(0);
+        match self.find_match(start, true) {
+            Some(row) => {
+                self.follow = false;
+                self.selected = Some(row);
+                self.status = String::new();
+            }
+            None if self.search.is_some() => self.status = "No match".to_string(),
+            None => self.selected = origin,
+        }
+    }
+
+    /// Returns `true` if the event at `position` matches the search
+    fn is_match(&self, position: usize) -> bool {
+        self.search.as_ref().is_some_and(|search| {
+            search.matches(&self.events[position], self.options.settings.naming)
+        })
+    }
+
+    /// Returns the first row matching the search from `start`, toward the end of the table
+    /// or the start of it, wrapping around
+    fn find_match(&self, start: usize, forward: bool) -> Option<usize> {
+        let search = self.search.as_ref()?;
+        self.find_row(start, forward, |event| {
+            search.matches(event, self.options.settings.naming)
+        })
+    }
+
+    /// Returns the first row from `start` whose event is a match, toward the end of the
+    /// table or the start of it, wrapping around
+    fn find_row(
+        &self,
+        start: usize,
+        forward: bool,
+        matches: impl Fn(&CaptureEvent) -> bool,
+    ) -> Option<usize> {
+        let rows = self.rows();
+        (0..rows)
+            .map(|i| match forward {
+                true => (start + i) % rows,
+                false => (start + rows - i) % rows,
+            })
+            .find(|row| {
+                self.position(*row)
+                    .is_some_and(|p| matches(&self.events[p]))
+            })
+    }
+
+    /// Selects the next or previous row with a warning or violation
+    pub fn next_problem(&mut self, forward: bool) {
+        let rows = self.rows();
+        let start = match (self.selected, forward) {
+            (Some(row), true) => row + 1,
+            (Some(row), false) => row + rows.saturating_sub(1),
+            (None, _) => 0,
+        };
+        let found = self.find_row(start % rows.max(1), forward, |event| {
+            matches!(
+                event.analysis,
+                MidiAnalysis::Warning(_) | MidiAnalysis::Violation(_)
+            )
+        });
+        match found {
+            Some(row) => {
+                self.follow = false;
+                self.selected = Some(row);
+            }
+            None => self.status = "No warnings or violations".to_string(),
+        }
+    }
+
+    /// Marks the selected row, or removes its mark
+    pub fn toggle_bookmark(&mut self) {
+        let Some(position) = self.selected.and_then(|row| self.position(row)) else {
+            self.status = "No row selected".to_string();
+            return;
+        };
+        self.status = if self.bookmarks.remove(&position) {
+            "Bookmark removed".to_string()
+        } else {
+            self.bookmarks.insert(position);
+            format!(
+                "Bookmark {} of {}, `(` and `)` jump between them",
+                self.bookmarks.range(..=position).count(),
+                self.bookmarks.len()
+            )
+        };
+    }
+
+    /// Selects the row of the next or previous bookmark, wrapping around. A bookmark hidden by
+    /// the filter selects the row after it
+    pub fn next_bookmark(&mut self, forward: bool) {
+        let current = self.selected.and_then(|row| self.position(row));
+        let found = match (current, forward) {
+            (Some(current), true) => self.bookmarks.range(current + 1..).next(),
+            (Some(current), false) => self.bookmarks.range(..current).next_back(),
+            (None, _) => None,
+        };
+        let wrapped = match forward {
+            true => self.bookmarks.first(),
+            false => self.bookmarks.last(),
+        };
+        let Some(&position) = found.or(wrapped) else {
+            self.status = "No bookmarks, `B` marks the selected row".to_string();
+            return;
+        };
+        self.follow = false;
+        self.selected = Some(self.row_of(position).min(self.rows().saturating_sub(1)));
+        self.status = format!(
+            "Bookmark {} of {}",
+            self.bookmarks.range(..=position).count(),
+            self.bookmarks.len()
+        );
+    }
+
+    /// Selects the next or previous row matching the search
+    pub fn next_match(&mut self, forward: bool) {
+        if self.search.is_none() {
+            self.status = "Nothing searched for, press `/` to search".to_string();
+            return;
+        }
+        let rows = self.rows();
+        let start = match (self.selected, forward) {
+            (Some(row), true) => row + 1,
+            (Some(row), false) => row + rows.saturating_sub(1),
+            (None, _) => 0,
+        };
+        match self.find_match(start % rows.max(1), forward) {
+            Some(row) => {
+                self.follow = false;
+                self.selected = Some(row);
+            }
+            None => self.status = "No match".to_string(),
+        }
+    }
+
+    /// Saves the current layout under a name in the configuration file
+    fn save_layout(&mut self, name: String) {
+        self.status = match self.options.config.save_layout(&name, &self.layout) {
+            Ok(path) => format!("Saved layout `{}` to {:?}", name, path),
+            Err(e) => format!("{:#}", e),
+        };
+    }
+
+    /// Returns the event that completes the message the event at `position` belongs to.
+    /// System Real Time bytes interleaved with the message are skipped
+    fn message_of(&self, position: usize) -> Option<&CaptureEvent> {
+        self.message_end(position).map(|end| &self.events[end])
+    }
+
+    /// Returns the position of the event that completes the message the event at
+    /// `position` belongs to
+    fn message_end(&self, position: usize) -> Option<usize> {
+        let event = self.events.get(position)?;
+        if event.byte >= 0xF8 {
+            return Some(position);
+        }
+        for (i, event) in self.events[position..]
+            .iter()
+            .enumerate()
+            .take(MESSAGE_SEARCH_LIMIT)
+        {
+            if event.byte >= 0xF8 {
+                continue;
+            }
+            if i > 0 && event.is_status() && event.byte != 0xF7 {
+                return None;
+            }
+            if event.message.is_some() {
+                return Some(position + i);
+            }
+        }
+        None
+    }
+
+    /// Returns the positions of the first and last bytes of the message the event at
+    /// `position` belongs to, which starts after the message before it under running status
+    fn message_range(&self, position: usize) -> Option<RangeInclusive<usize>> {
+        let end = self.message_end(position)?;
+        if self.events[end].byte >= 0xF8 {
+            return Some(end..=end);
+        }
+        let lowest = end.saturating_sub(MESSAGE_SEARCH_LIMIT);
+        let start = (lowest..=end)
+            .rev()
+            .find_map(|p| {
+                let event = &self.events[p];
+                match event.byte < 0xF8 {
+                    true if event.is_status() && event.byte != 0xF7 => Some(p),
+                    true if p < end && event.message.is_some() => Some(p + 1),
+                    _ => None,
+                }
+            })
+            .unwrap_or(lowest);
+        Some(start..=end)
+    }
+
+    /// Applies new analysis settings to new bytes immediately and to the
+    /// existing capture in the background
+    fn change_settings(&mut self, settings: analysis::Settings) {
+        self.options.settings = settings;
+        let events = self.events.clone();
+        self.reanalysis = Some((analysis::reanalyze(events, settings), self.dropped));
+        self.status = format!(
+            "Re-analyzing with {} strictness, GM mode {}",
+            settings.strictness,
+            if settings.gm { "on" } else { "off" }
+        );
+    }
+
+    /// Cycles through the strictness levels
+    pub fn cycle_strictness(&mut self) {
+        let mut settings = self.options.settings;
+        settings.strictness = settings.strictness.next();
+        self.change_settings(settings);
+    }
+
+    /// Toggles General MIDI checks
+    pub fn toggle_gm(&mut self) {
+        let mut settings = self.options.settings;
+        settings.gm = !settings.gm;
+        self.change_settings(settings);
+    }
+
+    /// Swaps in the results of a finished re-analysis
+    fn finish_reanalysis(&mut self) {
+        let Some((rx, dropped)) = &self.reanalysis else {
+            return;
+        };
+        let Ok(mut result) = rx.try_recv() else {
+            return;
+        };
+        // Events dropped from the scrollback while re-analyzing are dropped from the result
+        let dropped = (self.dropped - *dropped).min(result.events.len());
+        self.reanalysis = None;
+        if result.settings != self.options.settings {
+            return;
+        }
+        result.events.drain(..dropped);
+
+        // Bytes that arrived while re-analyzing continue from where the re-analysis ended.
+        // Statuses and channels do not depend on the settings, so the index is still valid
+        let analyzed = result.events.len();
+        let newer = self.events.split_off(analyzed);
+        self.events = result.events;
+        self.capture = result.capture;
+        self.clock = result.clock;
+        self.stats = result.stats;
+        for event in newer {
+            let event = self.capture.reprocess(&event);
+            if event.source != SENT && !event.notice {
+                self.clock.observe(&event);
+                self.stats.observe(&event);
+            }
+            self.events.push(event);
+        }
+        self.memory = self.events.iter().map(scrollback::footprint).sum();
+        self.status = format!("Re-analyzed {} bytes", self.events.len());
+    }
+
+    /// Warns in the status line once the bus comes close to saturation
+    fn check_saturation(&mut self) {
+        let saturation = self.rate.saturation(self.timeline.now());
+        if let (Some(lowest), false) = (saturation, self.saturated) {
+            self.status = format!(
+                "MIDI bus at {:.0}% of 31.25 kbaud for seconds on end, timing suffers",
+                lowest * 100.0
+            );
+        }
+        self.saturated = saturation.is_some();
+    }
+
+    /// Gives the alert of the configuration for the violations received since the last call,
+    /// at most once a second. Returns `true` if the bell should ring
+    fn take_alert(&mut self) -> bool {
+        if !std::mem::take(&mut self.violated)
+            || self.options.config.alert == Alert::Off
+            || self.alerted.is_some_and(|t| t.elapsed() < ALERT_INTERVAL)
+        {
+            return false;
+        }
+        self.alerted = Some(Instant::now());
+        self.options.config.alert == Alert::Bell
+    }
+
+    /// Closes the source once the capture reaches one of its limits.
+    /// Returns `true` if it was closed
+    fn stop_at_limit(&mut self) -> bool {
+        match self
+            .options
+            .limits
+            .reached(&self.stats, self.started.elapsed())
+        {
+            Some(reason) => {
+                self.status = format!("{}, capture stopped", reason);
+                self.source = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Raises an alarm for each exceeded budget, and starts writing the capture to the spill
+    /// file so it is safe on disk
+    fn check_budgets(&mut self) {
+        let alarms = self.budgets.check(&self.stats);
+        if alarms.is_empty() {
+            return;
+        }
+        self.alarms.extend(alarms);
+        if !self.spilling {
+            self.spilling = true;
+            let spilled = self.spill();
+            self.alarms.push(match spilled {
+                Ok(()) => format!("Spilling the capture to {:?}", self.options.spill),
+                Err(e) => format!("Unable to spill the capture: {:#}", e),
+            });
+        }
+    }
+
+    /// Writes the capture so far to the spill file, which then receives every new byte
+    fn spill(&mut self) -> Result<(), anyhow::Error> {
+        let mut recorder = CaptureRecorder::create(&self.options.spill)?;
+        for event in &self.events {
+            recorder.write(event)?;
+        }
+        self.sinks.push(Box::new(recorder));
+        Ok(())
+    }
+
+    /// Drains all bytes currently available from the source
+    fn receive(&mut self) {
+        self.finish_reanalysis();
+        while let Some(source) = &self.source {
+            let event = match source.events.try_recv() {
+                Ok(SourceEvent::Byte {
+                    arrival,
+                    timestamp,
+                    byte,
+                    source,
+                }) => {
+                    let time = self.timeline.time(arrival, source, timestamp);
+                    self.capture.process_from(source, time, byte)
+                }
+                // Recorded among the bytes, as bytes were lost
+                Ok(SourceEvent::Disconnected(notice)) => {
+                    self.capture.interrupt();
+                    self.status = notice.clone();
+                    let time = self.timeline.now();
+                    CaptureEvent::notice(0, time, MidiAnalysis::Warning(notice))
+                }
+                Ok(SourceEvent::Reconnected(notice)) => {
+                    self.status = notice.clone();
+                    let time = self.timeline.now();
+                    CaptureEvent::notice(0, time, MidiAnalysis::Info(notice))
+                }
+                Ok(SourceEvent::Notice(notice)) => {
+                    self.status = notice;
+                    continue;
+                }
+                Ok(SourceEvent::Closed) => {
+                    self.status = "Source closed".to_string();
+                    self.source = None;
+                    return;
+                }
+                Ok(SourceEvent::Error(e)) => {
+                    self.status = e;
+                    self.source = None;
+                    return;
+                }
+                Err(TryRecvError::Empty) => {
+                    self.stop_at_limit();
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.source = None;
+                    return;
+                }
+            };
+            let events = match &mut self.arm {
+                Some(arm) if arm.fired().is_none() => {
+                    let events = arm.pass(event);
+                    if let Some(fired) = arm.fired() {
+                        self.status = format!("Triggered by {}", fired);
+                    }
+                    events
+                }
+                _ => vec![event],
+            };
+            for event in events {
+                if !event.notice {
+                    self.clock.observe(&event);
+                }
+                for sink in self.sinks.iter_mut() {
+                    if let Err(e) = sink.write(&event) {
+                        self.status = format!("Output failed: {:#}", e);
+                    }
+                    if let Some(notice) = sink.notices().pop() {
+                        self.status = notice;
+                    }
+                }
+                if let Some(recorder) = &mut self.recorder {
+                    if let Err(e) = recorder.write(&event) {
+                        self.status = format!("Recording failed: {:#}", e);
+                        self.recorder = None;
+                    }
+                }
+                self.push_event(event);
+                self.check_budgets();
+                if self.stop_at_limit() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Starts recording to the file given with `--record-smf`, or stops and saves the active
+    /// recording
+    pub fn toggle_recording(&mut self) {
+        match self.recorder.take() {
+            Some(mut recorder) => {
+                self.status = match recorder.finish() {
+                    Ok(()) => format!("Saved recording to {:?}", recorder.path()),
+                    Err(e) => format!("Unable to save recording: {:#}", e),
+                };
+            }
+            None => self.start_recording(&self.options.smf_path.clone()),
+        }
+    }
+
+    /// Starts recording the bytes received from now on to a file in the format of its
+    /// extension: a Standard MIDI File, a JSONL or CSV log, a capture, or raw bytes
+    fn start_recording(&mut self, path: &Path) {
+        self.status = match Recording::start(path, self.options.ppq, self.options.bpm) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                format!("Recording to {:?}", path)
+            }
+            Err(e) => format!("Unable to record: {:#}", e),
+        };
+    }
+
+    /// Picks the file to record to, or stops and saves the active recording
+    fn pick_recording(&mut self) {
+        match self.recorder {
+            Some(_) => self.toggle_recording(),
+            None => self.file_dialog = Some(FileDialog::new(Purpose::Record)),
+        }
+    }
+
+    /// Returns the SysEx message containing the selected row
+    fn selected_sysex(&self) -> Option<&Vec<u8>> {
+        let selected = self
+            .selected
+            .and_then(|row| self.position(row))
+            .unwrap_or(0);
+        self.events
+            .iter()
+            .skip(selected)
+            .find_map(|e| match &e.message {
+                Some(MidiMessage::SystemExclusive(data)) => Some(data),
+                _ => None,
+            })
+    }
+
+    /// Saves the SysEx message containing the selected row to a `.syx` file
+    pub fn save_selected_sysex(&mut self) {
+        let Some(data) = self.selected_sysex().cloned() else {
+            self.status = "No SysEx message at the selected row".to_string();
+            return;
+        };
+        self.saved_sysex += 1;
+        let path = self
+            .options
+            .sysex_dir
+            .join(syx::file_name(self.saved_sysex, &data));
+        self.status = match syx::save(&path, &data) {
+            Ok(()) => format!("Saved SysEx to {:?}", path),
+            Err(e) => format!("{:#}", e),
+        };
+    }
+
+    /// Returns the positions of the events that complete a SysEx message
+    fn sysex_positions(&self) -> Vec<usize> {
+        let filter = Filter {
+            hidden_statuses: MESSAGE_STATUSES
+                .into_iter()
+                .filter(|s| *s != 0xF0)
+                .collect(),
+            ..Filter::default()
+        };
+        self.index
+            .select(&filter, &self.events)
+            .into_iter()
+            .filter(|p| {
+                matches!(
+                    self.events[*p].message,
+                    Some(MidiMessage::SystemExclusive(_))
+                )
+            })
+            .collect()
+    }
+
+    /// Selects the row of the next or previous SysEx message, wrapping around
+    fn next_sysex(&mut self, forward: bool) {
+        let positions = self.sysex_positions();
+        let current = self.selected.and_then(|row| self.position(row));
+        let found = match (current, forward) {
+            (Some(current), true) => positions.iter().find(|p| **p > current),
+            (Some(current), false) => positions.iter().rev().find(|p| **p < current),
+            (None, _) => None,
+        };
+        let wrapped = match forward {
+            true => positions.first(),
+            false => positions.last(),
+        };
+        let Some(&position) = found.or(wrapped) else {
+            self.status = "No SysEx messages received".to_string();
+            return;
+        };
+        self.follow = false;
+        self.selected = Some(self.row_of(position).min(self.rows().saturating_sub(1)));
+        self.status = format!(
+            "SysEx {} of {}",
+            positions.partition_point(|p| *p <= position),
+            positions.len()
+        );
+    }
+
+    /// Shows the bytes of the SysEx message containing the selected row in a popup
+    fn view_selected_sysex(&mut self) {
+        let Some(data) = self.selected_sysex() else {
+            self.status = "No SysEx message at the selected row".to_string();
+            return;
+        };
+        let bytes: Vec<u8> = [&[0xF0], data.as_slice(), &[0xF7]].concat();
+        self.sysex_dump = bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| format!("{:04X}  {}", i * 16, midi::hex(chunk)))
+            .collect();
+    }
+
+    /// Sends the SysEx message containing the selected row to MIDI Out again
+    fn send_selected_sysex(&mut self) {
+        if self.out.is_none() {
+            self.status = "No MIDI Out to send SysEx to, give one with `--out`".to_string();
+            return;
+        }
+        let Some(data) = self.selected_sysex() else {
+            self.status = "No SysEx message at the selected row".to_string();
+            return;
+        };
+        let bytes: Vec<u8> = [&[0xF0], data.as_slice(), &[0xF7]].concat();
+        if !self.send(&bytes) {
+            return;
+        }
+        self.status = format!("Sent SysEx of {} bytes", bytes.len());
+        self.log_sent(&bytes);
+    }
+
+    /// Compares the SysEx message containing the selected row with the one of the same
+    /// number in the reference capture
+    fn compare_sysex_with_reference(&mut self) {
+        let Some(reference) = &self.reference else {
+            self.status = "No reference capture, give one with `--reference`".to_string();
+            return;
+        };
+        let Some(data) = self.selected_sysex() else {
+            self.status = "No SysEx message at the selected row".to_string();
+            return;
+        };
+        let selected = self
+            .selected
+            .and_then(|row| self.position(row))
+            .unwrap_or(0);
+        let number = self.sysex_positions().partition_point(|p| *p < selected);
+        let found = reference
+            .events
+            .iter()
+            .filter_map(|event| match &event.message {
+                Some(MidiMessage::SystemExclusive(data)) => Some(data),
+                _ => None,
+            })
+            .nth(number);
+        let Some(theirs) = found else {
+            self.status = format!(
+                "No SysEx {} in the reference `{}`",
+                number + 1,
+                reference.name
+            );
+            return;
+        };
+        let mut report = vec![format!(
+            "SysEx {} of the reference `{}` as the marked one",
+            number + 1,
+            reference.name
+        )];
+        report.extend(diff::report(theirs, data));
+        self.sysex_diff = report;
+        self.status = String::new();
+    }
+
+    /// Keeps the state of the channels, to be compared with a later one
+    fn take_snapshot(&mut self) {
+        self.snapshot = Some(self.channels.snapshot());
+        self.status = "Kept the state of the channels, press `Z` to compare".to_string();
+    }
+
+    /// Compares the state of the channels with the snapshot
+    fn diff_snapshot(&mut self) {
+        let Some(snapshot) = &self.snapshot else {
+            self.status = "No snapshot, press `z` to keep the state of the channels".to_string();
+            return;
+        };
+        self.state_diff = snapshot::report(
+            snapshot,
+            &self.channels.snapshot(),
+            &self.options.config.names,
+            self.options.settings.naming,
+        );
+        self.status = String::new();
+    }
+
+    /// Marks the SysEx message containing the selected row, or compares it with the one
+    /// marked before
+    pub fn compare_selected_sysex(&mut self) {
+        let Some(data) = self.selected_sysex().cloned() else {
+            self.status = "No SysEx message at the selected row".to_string();
+            return;
+        };
+        match self.marked_sysex.take() {
+            Some(marked) => {
+                self.sysex_diff = diff::report(&marked, &data);
+                self.status = String::new();
+            }
+            None => {
+                self.status = format!(
+                    "Marked SysEx of {} bytes, select another and press `X` to compare",
+                    data.len() + 2
+                );
+                self.marked_sysex = Some(data);
+            }
+        }
+    }
+
+    /// Starts selecting a range at the selected row, or clears the range
+    pub fn toggle_anchor(&mut self) {
+        if self.anchor.take().is_some() {
+            self.status = "Range cleared".to_string();
+            return;
+        }
+        self.anchor = self.selected.and_then(|row| self.position(row));
+        if self.anchor.is_some() {
+            self.follow = false;
+            self.status =
+                "Range started, copy it with `y` (rows), `Y` (hex), `c` (C), or `C` (Rust)"
+                    .to_string();
+        }
+    }
+
+    /// Returns the positions in the capture between the anchor and the selected row, or just
+    /// the selected row if no range is being selected. In the message view the range starts
+    /// at the first byte of its first message
+    fn range(&self) -> Option<RangeInclusive<usize>> {
+        let selected = self.selected.and_then(|row| self.position(row))?;
+        let anchor = self.anchor.unwrap_or(selected);
+        let start = anchor.min(selected);
+        let start = if self.messages {
+            self.message_start(start)
+        } else {
+            start
+        };
+        Some(start..=anchor.max(selected))
+    }
+
+    /// Returns the position of the first byte of the message completed at `position`,
+    /// skipping the System Real Time bytes and the bytes of other inputs within it
+    fn message_start(&self, position: usize) -> usize {
+        let last = &self.events[position];
+        let mut remaining = last.raw.len().saturating_sub(1);
+        let mut start = position;
+        while remaining > 0 && start > 0 {
+            start -= 1;
+            let event = &self.events[start];
+            if event.source == last.source && event.byte < 0xF8 && !event.notice {
+                remaining -= 1;
+            }
+        }
+        start
+    }
+
+    /// Copies the shown bytes of the range to the clipboard as a byte array
+    pub fn copy_array(&mut self, language: Language) {
+        let Some(range) = self.range() else {
+            self.status = "No byte selected".to_string();
+            return;
+        };
+        let events: Vec<CaptureEvent> = self.events[range]
+            .iter()
+            .filter(|event| self.filter.matches(event))
+            .cloned()
+            .collect();
+        let text = array::array(&events, language, "capture");
+        self.status = match self.copy(text) {
+            Ok(()) => format!("Copied {} bytes as a {:?} array", events.len(), language),
+            Err(e) => format!("Unable to copy: {}", e),
+        };
+    }
+
+    /// Copies the rows of the range shown in the table to the clipboard as text, each with
+    /// its time, or with `hex` only the shown bytes in hexadecimal
+    pub fn copy_rows(&mut self, hex: bool) {
+        let Some((text, copied)) = self.rows_text(hex) else {
+            self.status = "No row selected".to_string();
+            return;
+        };
+        self.status = match self.copy(text) {
+            Ok(()) => format!("Copied {}", copied),
+            Err(e) => format!("Unable to copy: {}", e),
+        };
+    }
+
+    /// Returns the text `copy_rows` puts on the clipboard and what it holds, or none if no
+    /// row is selected
+    fn rows_text(&self, hex: bool) -> Option<(String, String)> {
+        let range = self.range()?;
+        let events: Vec<&CaptureEvent> = self.events[range]
+            .iter()
+            .filter(|event| self.filter.matches(event))
+            .collect();
+        let (text, copied) = if hex {
+            let bytes: Vec<u8> = events
+                .iter()
+                .flat_map(|event| event.received())
+                .copied()
+                .collect();
+            (midi::hex(&bytes), format!("{} bytes", bytes.len()))
+        } else {
+            let time_format = self
+                .options
+                .config
+                .timestamps
+                .unwrap_or(TimeFormat::Seconds);
+            let sources = self.options.sources.as_deref();
+            let lines: Vec<String> = events
+                .iter()
+                .filter(|event| self.shows(event))
+                .map(|event| {
+                    let cells = match self.messages {
+                        true => message_cells(
+                            event,
+                            sources.unwrap_or_default(),
+                            &self.options.config,
+                            self.options.settings.naming,
+                        ),
+                        false => event_cells(event, sources.unwrap_or_default()),
+                    };
+                    let time = time_format.format(event.time);
+                    // The source column only tells inputs apart when there are several
+                    let cells: Vec<&str> = [time.as_str()]
+                        .into_iter()
+                        .chain(
+                            cells
+                                .iter()
+                                .skip(usize::from(sources.is_none()))
+                                .map(|cell| cell.trim()),
+                        )
+                        .collect();
+                    cells.join("  ")
+                })
+                .collect();
+            let rows = match lines.len() {
+                1 => "1 row".to_string(),
+                n => format!("{} rows", n),
+            };
+            (lines.join("\n") + "\n", rows)
+        };
+        Some((text, copied))
+    }
+
+    /// Puts text on the clipboard, opening it the first time
+    fn copy(&mut self, text: String) -> Result<(), arboard::Error> {
+        match &mut self.clipboard {
+            Some(clipboard) => clipboard.set_text(text),
+            None => Clipboard::new()
+                .and_then(|clipboard| self.clipboard.insert(clipboard).set_text(text)),
+        }
+    }
+
+    /// Turns fading of the rows by age on or off
+    pub fn toggle_dim(&mut self) {
+        self.dim = !self.dim;
+        self.status = if self.dim {
+            "Rows fade as they age while following".to_string()
+        } else {
+            "Rows no longer fade".to_string()
+        };
+    }
+
+    /// Switches the timeline between source timestamps and local arrival time
+    pub fn toggle_source_timestamps(&mut self) {
+        self.timeline.toggle_source();
+        self.status = if self.timeline.uses_source() {
+            "Using source timestamps".to_string()
+        } else {
+            "Using arrival time".to_string()
+        };
+    }
+
+    /// Does what a key is bound to, except quitting which the loop does
+    fn act(&mut self, action: Action) {
+        match action {
+            Action::Quit => {}
+            Action::Help => self.show_help = true,
+            Action::Filter => self.toggle_filter_dialog(),
+            Action::Load => self.file_dialog = Some(FileDialog::new(Purpose::Load)),
+            Action::Save => self.file_dialog = Some(FileDialog::new(Purpose::Save)),
+            Action::Ports => self.open_port_dialog(),
+            Action::Record => self.toggle_recording(),
+            Action::RecordTo => self.pick_recording(),
+            Action::SaveSysex => self.save_selected_sysex(),
+            Action::CompareSysex => self.compare_selected_sysex(),
+            Action::NextSysex => self.next_sysex(true),
+            Action::PreviousSysex => self.next_sysex(false),
+            Action::ViewSysex => self.view_selected_sysex(),
+            Action::SendSysex => self.send_selected_sysex(),
+            Action::CompareSysexReference => self.compare_sysex_with_reference(),
+            Action::SourceTimestamps => self.toggle_source_timestamps(),
+            Action::Strictness => self.cycle_strictness(),
+            Action::GeneralMidi => self.toggle_gm(),
+            Action::Realtime => self.toggle_realtime_filter(),
+            Action::Dim => self.toggle_dim(),
+            Action::MessageView => self.toggle_message_view(),
+            Action::Panel(panel) => self.toggle_panel(panel),
+            Action::Watch => self.watch_selected(),
+            Action::Snapshot => self.take_snapshot(),
+            Action::DiffSnapshot => self.diff_snapshot(),
+            Action::Layout(number) => self.switch_layout(number),
+            Action::SaveLayout => self.layout_prompt = Some(String::new()),
+            Action::Panic => self.panic(),
+            Action::Search => self.start_search(),
+            Action::NextMatch => self.next_match(true),
+            Action::PreviousMatch => self.next_match(false),
+            Action::Bookmark => self.toggle_bookmark(),
+            Action::NextBookmark => self.next_bookmark(true),
+            Action::PreviousBookmark => self.next_bookmark(false),
+            Action::NextProblem => self.next_problem(true),
+            Action::PreviousProblem => self.next_problem(false),
+            Action::NextInput => self.next_tab(true),
+            Action::PreviousInput => self.next_tab(false),
+            Action::Reference => self.toggle_reference(),
+            Action::AlignReference => self.align_reference(),
+            Action::ShiftReferenceLater => self.shift_reference(Some(true)),
+            Action::ShiftReferenceEarlier => self.shift_reference(Some(false)),
+            Action::ResetReference => self.shift_reference(None),
+            Action::Dismiss => {
+                self.alarms.clear();
+                self.sysex_diff.clear();
+                self.sysex_dump.clear();
+                self.state_diff.clear();
+                self.search = None;
+            }
+            Action::Range => self.toggle_anchor(),
+            Action::CopyC => self.copy_array(Language::C),
+            Action::CopyRust => self.copy_array(Language::Rust),
+            Action::CopyRows => self.copy_rows(false),
+            Action::CopyBytes => self.copy_rows(true),
+            Action::NextProgram => self.step_program(true),
+            Action::PreviousProgram => self.step_program(false),
+            Action::NextProgramChannel => self.step_channel(true),
+            Action::PreviousProgramChannel => self.step_channel(false),
+            Action::Up => self.move_rows(-1),
+            Action::Down => self.move_rows(1),
+            Action::PageUp => self.previous(),
+            Action::PageDown => self.next(),
+            Action::HalfPageUp => self.move_rows(-(self.viewport as isize / 2).max(1)),
+            Action::HalfPageDown => self.move_rows((self.viewport as isize / 2).max(1)),
+            Action::Top => {
+                self.follow = false;
+                self.selected = (self.rows() > 0).then_some(0);
+            }
+            Action::Follow => self.follow = true,
+            Action::ToggleFollow => self.follow = !self.follow,
+        }
     }
 }
 
-pub(crate) fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> Result<(), anyhow::Error> {
-    let mut app = App::new();
+pub(crate) fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    options: Options,
+    source: Option<Connection>,
+    sinks: Vec<Box<dyn Sink>>,
+    out: Option<Box<dyn Write + Send>>,
+) -> Result<Summary, anyhow::Error> {
+    let record = options.record;
+    let mut app = App::new(options, source, sinks, out);
+    for event in std::mem::take(&mut app.options.history) {
+        app.push_event(event);
+    }
+    app.set_filter(app.options.filter.clone());
+    if record {
+        app.toggle_recording();
+    }
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
 
-        match event::read()? {
-            Event::Key(key) => match key.code {
-                KeyCode::Char('q') => return Ok(()),
-                KeyCode::Down => app.next(),
-                KeyCode::Up => app.previous(),
-                KeyCode::PageDown => app.follow = true,
-                KeyCode::End => app.follow = true,
-                KeyCode::ScrollLock => app.follow = !app.follow,
-                _ => {}
-            },
-            Event::Mouse(mouse) => match mouse.kind {
-                MouseEventKind::ScrollUp => app.previous(),
-                MouseEventKind::ScrollDown => app.next(),
+        if event::poll(POLL_INTERVAL)? {
+            match event::read()? {
+                // Any key closes the help
+                Event::Key(_) if app.show_help => app.show_help = false,
+                Event::Key(key) if app.filter_dialog.is_some() => app.filter_dialog_key(key.code),
+                Event::Key(key) if app.file_dialog.is_some() => app.file_dialog_key(key.code),
+                Event::Key(key) if app.port_dialog.is_some() => app.port_dialog_key(key.code),
+                Event::Key(key) if app.layout_prompt.is_some() => app.layout_prompt_key(key.code),
+                Event::Key(key) if app.search_prompt.is_some() => app.search_prompt_key(key.code),
+                Event::Key(key) if app.pad(key.code).is_some() => app.hit_pad(key.code),
+                Event::Key(key) if app.composes(key.code) => app.compose(key.code),
+                Event::Key(key) if app.channel_key(key.code).is_some() => app.mute_channel(key),
+                Event::Key(key) => match app.options.keymap.action(key) {
+                    Some(Action::Quit) => break,
+                    Some(action) => app.act(action),
+                    None => {}
+                },
+                // Redraw from scratch so no remains of the old layout are left behind
+                Event::Resize(..) => terminal.clear()?,
+                Event::Mouse(mouse) => match app.mouse(mouse) {
+                    Some(Action::Quit) => break,
+                    Some(action) => app.act(action),
+                    None => {}
+                },
                 _ => {}
-            },
-            _ => {}
+            }
+        }
+        app.receive();
+        app.check_saturation();
+        if app.take_alert() {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+        }
+        let releases = app.pads.releases(Instant::now());
+        if !releases.is_empty() {
+            app.send(&releases);
+        }
+    }
+
+    // Save any recording still in progress
+    if let Some(mut recorder) = app.recorder.take() {
+        recorder.finish()?;
+    }
+    for sink in app.sinks.iter_mut() {
+        sink.finish()?;
+    }
+    if let Some(recorder) = &mut app.dropped_spill {
+        recorder.finish()?;
+    }
+    if let Some(store) = &app.options.state_store {
+        store
+            .save(&app.state())
+            .context("Unable to save the state of the TUI")?;
+    }
+    Ok(Summary {
+        ended: "Quit".to_string(),
+        clock: app.clock.quality(),
+        smoothness: app.smoothness.reports(),
+        jitter: app.timeline.jitter(),
+        stats: app.stats,
+    })
+}
+
+/// Draws the lines of a dialog in a popup in the middle of the screen
+pub(super) fn dialog_popup<B: Backend>(frame: &mut Frame<B>, title: &str, lines: Vec<Spans>) {
+    let size = frame.size();
+    let width = 70.min(size.width);
+    let height = (lines.len() as u16 + 2).min(size.height);
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let popup = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Returns the columns of `HEADERS` shown in a table `width` wide, with their widths, or
+/// those of `MESSAGE_HEADERS` for the message view when `messages`. Narrow tables drop the
+/// columns that are least useful. The source column is shown first when `tagged`, taking
+/// its room from the message
+fn table_columns(width: u16, tagged: bool, messages: bool) -> (Vec<usize>, Vec<Constraint>) {
+    let width = if tagged {
+        width.saturating_sub(SOURCE_WIDTH + 1)
+    } else {
+        width
+    };
+    let (mut columns, mut widths) = if messages && width < COMPACT_WIDTH {
+        (
+            COMPACT_MESSAGE_COLUMNS.to_vec(),
+            vec![
+                Constraint::Length(10),
+                Constraint::Length(16),
+                Constraint::Length(3),
+                Constraint::Length(width.saturating_sub(34)),
+            ],
+        )
+    } else if messages {
+        (
+            (1..MESSAGE_HEADERS.len()).collect(),
+            vec![
+                Constraint::Length(10),
+                Constraint::Length(16),
+                Constraint::Length(4),
+                Constraint::Length(width.saturating_sub(48).max(8)),
+                Constraint::Length(8),
+            ],
+        )
+    } else if width < COMPACT_WIDTH {
+        (
+            COMPACT_COLUMNS.to_vec(),
+            vec![
+                Constraint::Length(4),
+                Constraint::Length(3),
+                Constraint::Length(width.saturating_sub(9)),
+            ],
+        )
+    } else {
+        (
+            (1..HEADERS.len()).collect(),
+            vec![
+                Constraint::Length(8),
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(width.saturating_sub(40).max(8)),
+                Constraint::Length(6),
+            ],
+        )
+    };
+    if tagged {
+        columns.insert(0, 0);
+        widths.insert(0, Constraint::Length(SOURCE_WIDTH));
+    }
+    (columns, widths)
+}
+
+/// Builds a table of events under the `headers` of the columns
+fn event_table<'a>(
+    rows: Vec<Row<'a>>,
+    headers: &[&'static str; 6],
+    columns: &[usize],
+    widths: &'a [Constraint],
+    style: Style,
+) -> Table<'a> {
+    let header_cells = columns.iter().map(|c| Cell::from(headers[*c]).style(style));
+    let header = Row::new(header_cells)
+        .style(style)
+        .height(1)
+        .bottom_margin(0);
+    Table::new(rows)
+        .header(header)
+        .widths(widths)
+        .highlight_symbol("*")
+        .column_spacing(1)
+}
+
+/// Builds the table row of an event from the `cells` of the columns. Sent events stand out
+/// in the `sent` style
+fn event_row(
+    event: &CaptureEvent,
+    cells: [String; 6],
+    columns: &[usize],
+    style: Style,
+    sent: Style,
+) -> Row<'static> {
+    let cells = columns.iter().map(|c| Cell::from(cells[*c].clone()));
+    let style = match event.source {
+        SENT => style.patch(sent),
+        _ => style,
+    };
+    Row::new(cells).height(1).bottom_margin(0).style(style)
+}
+
+/// Formats a capture event into the cells of a table row. `sources` names the inputs for
+/// the source column. Sent bytes are marked with `>`
+fn event_cells(event: &CaptureEvent, sources: &[String]) -> [String; 6] {
+    let (byte, kind, data) = match (event.notice, event.is_status()) {
+        (true, _) => ("--".to_string(), "NOTICE", "-".to_string()),
+        (false, true) => (format!("{:02X}", event.byte), "STATUS", "-".to_string()),
+        (false, false) => (
+            format!("{:02X}", event.byte),
+            "DATA  ",
+            event.byte.to_string(),
+        ),
+    };
+    let channel = match event.channel {
+        Some(ch) => format!("{:>2}", ch + 1),
+        None => " -".to_string(),
+    };
+    [
+        input_name(sources, event.source),
+        format!("{}{}", if event.source == SENT { '>' } else { ' ' }, byte),
+        kind.to_string(),
+        channel,
+        event.analysis.text().to_string(),
+        data,
+    ]
+}
+
+/// Formats the message completed by an event into the cells of a row of the message view:
+/// its bytes as they appeared on the wire, its type, and its fields decoded with the names
+/// of the configuration. Bytes that do not complete a message keep the cells of the byte
+/// view
+fn message_cells(
+    event: &CaptureEvent,
+    sources: &[String],
+    config: &Config,
+    naming: NoteNaming,
+) -> [String; 6] {
+    let Some(message) = &event.message else {
+        return event_cells(event, sources);
+    };
+    let mut bytes = midi::hex(&event.raw[..event.raw.len().min(MESSAGE_BYTES)]);
+    if event.raw.len() > MESSAGE_BYTES {
+        bytes.push('…');
+    }
+    let channel = match message.channel() {
+        Some(ch) => format!("{:>2}", ch + 1),
+        None => " -".to_string(),
+    };
+    // The data of SysEx messages is too long for the column
+    let data: &[u8] = match event.raw.first() {
+        Some(0xF0) => &[],
+        Some(status) if *status >= 0x80 => &event.raw[1..],
+        _ => &event.raw,
+    };
+    let data: Vec<String> = data.iter().map(|b| b.to_string()).collect();
+    [
+        input_name(sources, event.source),
+        format!("{}{}", if event.source == SENT { '>' } else { ' ' }, bytes),
+        message.name().to_string(),
+        channel,
+        message_fields(message, config, naming),
+        data.join(" "),
+    ]
+}
+
+/// Describes the fields of a message, naming notes, controllers, and programs
+fn message_fields(message: &MidiMessage, config: &Config, naming: NoteNaming) -> String {
+    let names = &config.names;
+    match message {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        }
+        | MidiMessage::NoteOff {
+            channel,
+            note,
+            velocity,
+        } => format!(
+            "{}, velocity {}",
+            names.note(naming.name(*note), *channel, *note),
+            velocity
+        ),
+        MidiMessage::PolyPressure {
+            channel,
+            note,
+            pressure,
+        } => format!(
+            "{}, pressure {}",
+            names.note(naming.name(*note), *channel, *note),
+            pressure
+        ),
+        MidiMessage::ControlChange { control, value, .. } => {
+            let name = match names.controls.get(&control.to_string()) {
+                Some(name) => name.clone(),
+                None => controls::get_controller_name(*control),
+            };
+            format!("{} {}, value {}", name, control, value)
+        }
+        MidiMessage::ChannelMode { mode, .. } => match mode {
+            MidiChannelMode::LocalControl(true) => "On".to_string(),
+            MidiChannelMode::LocalControl(false) => "Off".to_string(),
+            MidiChannelMode::MonoModeOn(0) => "As many channels as voices".to_string(),
+            MidiChannelMode::MonoModeOn(channels) => format!("{} channels", channels),
+            _ => String::new(),
+        },
+        MidiMessage::ProgramChange { program, .. } => {
+            names.program(format!("Program {}", program), *program)
+        }
+        MidiMessage::ChannelPressure { pressure, .. } => format!("Pressure {}", pressure),
+        MidiMessage::PitchBend { value, .. } => format!("{:+}", *value as i32 - 8192),
+        MidiMessage::MtcQuarterFrame(piece) => {
+            format!("Piece {}, value {}", piece >> 4, piece & 0x0F)
         }
+        MidiMessage::SongPosition(beats) => format!("Beat {}", beats),
+        MidiMessage::SongSelect(song) => format!("Song {}", song),
+        MidiMessage::SystemExclusive(data) => match sysex::manufacturer(data) {
+            Some(id) => format!("{}, {} bytes", id.manufacturer, data.len() + 2),
+            None => format!("{} bytes", data.len() + 2),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Plays a capture through the application without a terminal, handing `on_frame` what the
+/// screen shows every `interval` of the capture and whenever new events arrive after a pause
+pub(super) fn replay(
+    options: Options,
+    events: Vec<CaptureEvent>,
+    (width, height): (u16, u16),
+    interval: Duration,
+    mut on_frame: impl FnMut(Duration, &Buffer),
+) -> Result<(), anyhow::Error> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+    let mut app = App::new(options, None, vec![], None);
+    app.set_filter(app.options.filter.clone());
+    let mut events = events.into_iter().peekable();
+    let mut time = Duration::ZERO;
+    loop {
+        while let Some(event) = events.next_if(|event| event.time <= time) {
+            app.clock.observe(&event);
+            app.push_event(event);
+        }
+        let frame = terminal.draw(|f| ui(f, &mut app))?;
+        on_frame(time, frame.buffer);
+        // Nothing changes on screen until the next event
+        time = match events.peek() {
+            Some(next) => (time + interval).max(next.time),
+            None => return Ok(()),
+        };
     }
 }
 
 fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
     let size = frame.size();
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        let message = Paragraph::new(format!(
+            "Terminal too small ({}x{}), need at least {}x{}",
+            size.width, size.height, MIN_WIDTH, MIN_HEIGHT
+        ))
+        .wrap(Wrap { trim: true });
+        frame.render_widget(message, size);
+        return;
+    }
+    // Taken apart from the application, which drawing changes
+    let palette = app.options.palette.clone();
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -177,74 +2511,1016 @@ fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                 Constraint::Min(0),
                 Constraint::Length(1),
                 Constraint::Length(1),
+                Constraint::Length(1),
             ]
             .as_ref(),
         )
         .margin(0)
         .split(frame.size());
-    app.viewport = chunks[0].height.checked_sub(1).unwrap_or(0);
+    // The keyboards take the whole width below the table and the other panels
+    let keyboard_channels: Vec<u8> = app
+        .channels
+        .played()
+        .filter(|channel| app.filter.hidden_channels & (1 << channel) == 0)
+        .collect();
+    let (main_area, keyboard_area) = if app.layout.panels.contains(&Panel::Keyboard) {
+        let height = keyboard::height(keyboard_channels.len(), &app.options.zones) + 2;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(height.min(chunks[0].height / 2)),
+                ]
+                .as_ref(),
+            )
+            .split(chunks[0]);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (chunks[0], None)
+    };
+    let panels: Vec<Panel> = app
+        .layout
+        .panels
+        .iter()
+        .copied()
+        .filter(|panel| *panel != Panel::Keyboard)
+        .collect();
+    let (table_area, panel_areas) = split_panels(main_area, panels.len());
+    // The reference capture is compared below the live one
+    let (table_area, reference_area) = if app.reference.is_some() && app.show_reference {
+        let halves = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+            .split(table_area);
+        (halves[0], Some(halves[1]))
+    } else {
+        (table_area, None)
+    };
+    // Bytes from several inputs are shown together or an input at a time, in tabs
+    let table_area = match app.options.sources.as_deref() {
+        Some(names) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(table_area);
+            let tab = app.tab();
+            let titles = std::iter::once("All".to_string())
+                .chain(app.sources.iter().map(|source| input_name(names, *source)));
+            let mut spans = vec![];
+            for (i, title) in titles.enumerate() {
+                let style = match tab == Some(i) {
+                    true => palette.header,
+                    false => STYLE_DEFAULT,
+                };
+                spans.push(Span::styled(format!(" {} ", title), style));
+                spans.push(Span::raw("│"));
+            }
+            spans.push(Span::raw(" Tab to switch"));
+            frame.render_widget(Paragraph::new(Spans::from(spans)), chunks[0]);
+            chunks[1]
+        }
+        None => table_area,
+    };
+    app.viewport = table_area.height.saturating_sub(1);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(table_area);
+    let table_area = columns[0];
+    app.table_area = table_area;
+    // Beside the rows, below the header
+    app.scrollbar_area = Rect {
+        y: columns[1].y + 1,
+        height: columns[1].height.saturating_sub(1),
+        ..columns[1]
+    };
+
+    // Status line
+    let jitter = match app.timeline.jitter() {
+        Some(jitter) => format!("Jitter {:.2} ms", jitter.as_secs_f64() * 1e3),
+        None => String::new(),
+    };
+    let clock = match app.clock.quality() {
+        Some(quality) => quality.to_string(),
+        None => String::new(),
+    };
+    // Rows hidden by the filter are counted
+    let status_text = if let Some(name) = &app.layout_prompt {
+        format!("Save layout as: {}_", name)
+    } else if let Some((text, _)) = &app.search_prompt {
+        format!("/{}_ {}", text, app.status)
+    } else {
+        let mut counts = vec![match &app.source {
+            Some(source) => source.name.clone(),
+            None => "disconnected".to_string(),
+        }];
+        if app.messages {
+            counts.push(format!("{} messages", app.rows()));
+        }
+        if app.dropped > 0 {
+            counts.push(format!("{} oldest dropped", app.dropped));
+        }
+        if app.stats.warnings + app.stats.violations > 0 {
+            counts.push(format!(
+                "{} warnings, {} violations",
+                app.stats.warnings, app.stats.violations
+            ));
+        }
+        if !app.filter.is_empty() {
+            counts.push(format!("{} hidden", app.hidden));
+        }
+        format!("[{}] {}", counts.join(", "), app.status)
+    };
+    let (status_cells, status_widths) = if size.width < COMPACT_WIDTH {
+        (
+            vec![Cell::from(status_text)],
+            vec![Constraint::Length(size.width)],
+        )
+    } else {
+        (
+            vec![
+                Cell::from(status_text),
+                Cell::from(clock),
+                Cell::from(jitter),
+            ],
+            vec![
+                Constraint::Length(size.width.saturating_sub(70)),
+                Constraint::Length(50),
+                Constraint::Length(19),
+            ],
+        )
+    };
+    let flashing = app.options.config.alert == Alert::Flash
+        && app.alerted.is_some_and(|t| t.elapsed() < FLASH);
+    let status = Table::new(vec![])
+        .header(Row::new(status_cells))
+        .widths(&status_widths)
+        .style(match flashing {
+            true => palette.violation.add_modifier(Modifier::REVERSED),
+            false => STYLE_DEFAULT,
+        });
+    frame.render_widget(status, chunks[2]);
 
     // Menu bar
     let menu_bar = Table::new(vec![])
         .header(Row::new(vec![
             Cell::from(Spans::from(vec![
-                Span::styled("F1", STYLE_HEADER),
-                Span::styled(" FILTER", STYLE_DEFAULT),
+                Span::styled("F1", palette.header),
+                if app.filter.is_empty() {
+                    Span::styled(" FILTER", STYLE_DEFAULT)
+                } else {
+                    Span::styled(" FILTERED", palette.warning)
+                },
             ])),
             Cell::from(Spans::from(vec![
-                Span::styled("F2", STYLE_HEADER),
+                Span::styled("F2", palette.header),
                 Span::styled(" LOAD", STYLE_DEFAULT),
             ])),
             Cell::from(Spans::from(vec![
-                Span::styled("F3", STYLE_HEADER),
+                Span::styled("F3", palette.header),
                 Span::styled(" SAVE", STYLE_DEFAULT),
             ])),
             Cell::from(Spans::from(vec![
-                Span::styled("Q", STYLE_HEADER),
+                Span::styled("F4", palette.header),
+                Span::styled(" PORTS", STYLE_DEFAULT),
+            ])),
+            Cell::from(Spans::from(vec![
+                Span::styled("R", palette.header),
+                match &app.recorder {
+                    Some(recorder) => {
+                        Span::styled(format!(" ● REC {}", recorder.progress()), palette.violation)
+                    }
+                    None => Span::styled(" REC", STYLE_DEFAULT),
+                },
+            ])),
+            Cell::from(Spans::from(vec![
+                Span::styled("Q", palette.header),
                 Span::styled(" QUIT", STYLE_DEFAULT),
             ])),
         ]))
-        .widths(&[
-            Constraint::Length(10),
-            Constraint::Length(10),
-            Constraint::Length(10),
-            Constraint::Length(10),
-        ]);
-    frame.render_widget(menu_bar, chunks[2]);
-
-    // Table header
-    let header_cells = HEADERS.iter().map(|h| Cell::from(*h).style(STYLE_HEADER));
-    let header = Row::new(header_cells)
-        .style(STYLE_HEADER)
-        .height(1)
-        .bottom_margin(0);
+        .widths(&[Constraint::Ratio(1, 6); 6]);
+    frame.render_widget(menu_bar, chunks[3]);
+    app.menu_area = chunks[3];
 
-    // Table rows
-    let rows = app.analysis.iter().map(|item| {
-        let cells = item.iter().map(|c| Cell::from(*c));
-        Row::new(cells)
-            .height(1)
-            .bottom_margin(0)
-            .style(STYLE_DEFAULT)
-    });
+    let sources = app.options.sources.as_deref();
+    let (columns, table_widths) = table_columns(table_area.width, sources.is_some(), app.messages);
+    let sources = sources.unwrap_or_default();
 
-    // Table
-    let table_widths = [
-        Constraint::Length(8),
-        Constraint::Length(10),
-        Constraint::Length(6),
-        // Constraint::Min(10),
-        Constraint::Length(size.width.checked_sub(40).unwrap_or(8).max(8)),
-        Constraint::Length(6),
-    ];
-    let table = Table::new(rows)
-        .header(header)
-        // .block(Block::default().borders(Borders::ALL).title("MIDI In Raw"))
-        .widths(&table_widths)
-        .highlight_symbol("*")
-        .column_spacing(1);
+    // Only the visible rows of the view are built, so drawing does not slow down
+    // as the capture grows
+    let height = app.viewport.max(1) as usize;
     if app.follow {
-        app.table_state.select(app.analysis.len().checked_sub(1));
+        app.selected = app.rows().checked_sub(1);
+    }
+    if let Some(selected) = app.selected {
+        if selected < app.offset {
+            app.offset = selected;
+        } else if selected >= app.offset + height {
+            app.offset = selected + 1 - height;
+        }
+    }
+    app.offset = app.offset.min(app.rows().saturating_sub(height));
+    let visible = app.offset..(app.offset + height).min(app.rows());
+
+    // Table rows, with the range being selected highlighted, fading with age while
+    // following when dimming is on
+    let range = app.anchor.and(app.range());
+    let now = (app.follow && app.dim).then(|| app.timeline.now());
+    let rows = visible.clone().filter_map(|row| {
+        let position = app.position(row)?;
+        let event = &app.events[position];
+        let style = match &range {
+            Some(range) if range.contains(&position) => palette.range,
+            _ => STYLE_DEFAULT,
+        };
+        let style = match app.bookmarks.contains(&position) {
+            true => style.patch(palette.bookmark),
+            false => style,
+        };
+        // Rows keep the color of their severity as they age
+        let style = match now {
+            Some(now) => style.patch(palette.aged(now.saturating_sub(event.time))),
+            None => style,
+        };
+        let style = style.patch(palette.row(event));
+        let style = match app.is_match(position) {
+            true => style.patch(palette.matched),
+            false => style,
+        };
+        let cells = if app.messages {
+            message_cells(
+                event,
+                sources,
+                &app.options.config,
+                app.options.settings.naming,
+            )
+        } else {
+            event_cells(event, sources)
+        };
+        Some(event_row(event, cells, &columns, style, palette.sent))
+    });
+    let headers = if app.messages {
+        &MESSAGE_HEADERS
+    } else {
+        &HEADERS
+    };
+    let table = event_table(
+        rows.collect(),
+        headers,
+        &columns,
+        &table_widths,
+        palette.header,
+    );
+    let mut table_state = TableState::default();
+    table_state.select(app.selected.and_then(|row| row.checked_sub(visible.start)));
+    frame.render_stateful_widget(table, table_area, &mut table_state);
+    let thumb = scrollbar::thumb(
+        visible.start,
+        visible.len(),
+        app.rows(),
+        app.scrollbar_area.height,
+    );
+    frame.render_widget(
+        Paragraph::new(scrollbar::lines(thumb, app.scrollbar_area.height)).style(palette.range),
+        app.scrollbar_area,
+    );
+
+    // Strip of the whole capture, the stretch shown in the table highlighted
+    app.strip_area = chunks[1];
+    let shown = match (
+        app.position(visible.start),
+        app.position(visible.end.saturating_sub(1)),
+    ) {
+        (Some(first), Some(last)) => Some(app.events[first].time..=app.events[last].time),
+        _ => None,
+    };
+    let columns = app.strip_area.width as usize;
+    let bars: Vec<Span> = app
+        .strip
+        .bars(columns)
+        .into_iter()
+        .enumerate()
+        .map(|(column, bar)| {
+            let span = app.strip.span(column, columns);
+            let style = match &shown {
+                Some(shown) if span.start <= *shown.end() && *shown.start() < span.end => {
+                    palette.range
+                }
+                _ => STYLE_DEFAULT,
+            };
+            Span::styled(bar.to_string(), style)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(Spans::from(bars)), app.strip_area);
+
+    if let (Some(area), Some(reference)) = (reference_area, &app.reference) {
+        let block = Block::default().borders(Borders::TOP).title(format!(
+            " Reference {} ({:+.3} s) ",
+            reference.name,
+            reference.offset()
+        ));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        // Scrolled along with the live capture, the matching event in the middle
+        let time = app
+            .selected
+            .and_then(|row| app.position(row))
+            .map(|position| app.events[position].time);
+        let matched = time.and_then(|time| reference.row_at(time));
+        let view = reference.view();
+        let height = inner.height.saturating_sub(1).max(1) as usize;
+        let first = matched
+            .unwrap_or(0)
+            .saturating_sub(height / 2)
+            .min(view.len().saturating_sub(height));
+        let (columns, widths) = table_columns(inner.width, false, false);
+        let rows = view[first..(first + height).min(view.len())]
+            .iter()
+            .map(|p| {
+                let event = &reference.events[*p];
+                let style = palette.row(event);
+                event_row(
+                    event,
+                    event_cells(event, &[]),
+                    &columns,
+                    style,
+                    palette.sent,
+                )
+            })
+            .collect();
+        let table = event_table(rows, &HEADERS, &columns, &widths, palette.header);
+        let mut state = TableState::default();
+        state.select(matched.map(|row| row - first));
+        frame.render_stateful_widget(table, inner, &mut state);
+    }
+
+    for (panel, area) in panels.iter().zip(panel_areas) {
+        let lines = match panel {
+            Panel::Detail => {
+                let position = app.selected.and_then(|row| app.position(row));
+                panels::detail(
+                    position.map(|p| &app.events[p]),
+                    position.and_then(|p| app.message_of(p)),
+                    &app.options.config,
+                )
+            }
+            Panel::Stats => panels::stats(
+                &app.stats,
+                &app.smoothness,
+                &app.intervals,
+                area.width.saturating_sub(2),
+            ),
+            Panel::Pads => app.pads.lines(Instant::now()),
+            Panel::Send => app.composer.lines(),
+            Panel::Controls => panels::controls(
+                &app.channels,
+                &app.options.config,
+                app.filter.hidden_channels,
+                area.width.saturating_sub(2),
+            ),
+            Panel::Levels => panels::levels(
+                &app.channels,
+                app.options.settings.naming,
+                app.filter.hidden_channels,
+                area.width.saturating_sub(2),
+            ),
+            Panel::Rate => {
+                panels::rate(&app.rate, app.timeline.now(), area.width.saturating_sub(2))
+            }
+            Panel::Channels => panels::channel_grid(
+                &app.channels,
+                app.filter.hidden_channels,
+                app.timeline.now(),
+                area.width.saturating_sub(2),
+            ),
+            Panel::Bookmarks => panels::bookmarks(
+                &app.events,
+                &app.bookmarks,
+                app.selected.and_then(|row| app.position(row)),
+                app.options.config.timestamps.unwrap_or(TimeFormat::Seconds),
+            ),
+            Panel::Sysex => panels::sysex_list(
+                &app.events,
+                &app.sysex_positions(),
+                app.selected.and_then(|row| app.position(row)),
+                app.options.config.timestamps.unwrap_or(TimeFormat::Seconds),
+                area.width.saturating_sub(2),
+                area.height.saturating_sub(2),
+            ),
+            Panel::Hex => {
+                let position = app.selected.and_then(|row| app.position(row));
+                panels::hex_dump(
+                    &app.events,
+                    app.dropped,
+                    position,
+                    position.and_then(|p| app.message_range(p)),
+                    area.height.saturating_sub(2) as usize,
+                )
+            }
+            Panel::Watch => panels::watch(
+                app.watch.as_ref(),
+                &app.smoothness,
+                &app.options.config,
+                area.width.saturating_sub(2),
+                area.height.saturating_sub(2),
+            ),
+            Panel::Keyboard => continue,
+            Panel::Mpe => panels::mpe(
+                &app.mpe,
+                app.options.settings.naming,
+                area.width.saturating_sub(2),
+            ),
+        };
+        let widget = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(panel.title()))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(widget, area);
+    }
+
+    if let Some(area) = keyboard_area {
+        let lines = keyboard::lines(
+            &app.channels,
+            &keyboard_channels,
+            &app.options.zones,
+            area.width.saturating_sub(2) as usize,
+        );
+        let widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Panel::Keyboard.title()),
+        );
+        frame.render_widget(widget, area);
+    }
+
+    if !app.sysex_dump.is_empty() {
+        report_popup(
+            frame,
+            " SysEx, offset, bytes ",
+            &app.sysex_dump,
+            ", `x` saves them all",
+        );
+    }
+    if !app.sysex_diff.is_empty() {
+        report_popup(
+            frame,
+            " SysEx differences, offset, marked, selected, parameter ",
+            &app.sysex_diff,
+            ", see `miditerm sysex-diff`",
+        );
+    }
+    if !app.state_diff.is_empty() {
+        report_popup(frame, " Channels since the snapshot ", &app.state_diff, "");
+    }
+    if !app.alarms.is_empty() {
+        alarm_popup(frame, &app.alarms, &app.options.palette);
+    }
+    if let Some(dialog) = &app.file_dialog {
+        let title = match dialog.purpose {
+            Purpose::Load => " Load capture ",
+            Purpose::Save => " Save capture ",
+            Purpose::Record => " Record ",
+        };
+        dialog_popup(frame, title, dialog.lines());
+    }
+    if let Some(dialog) = &app.port_dialog {
+        let connected = app.source.as_ref().map(|source| source.name.as_str());
+        dialog_popup(frame, " Ports ", dialog.lines(connected));
+    }
+    if let Some(cursor) = app.filter_dialog {
+        let items = app.filter_items();
+        let sources = app.options.sources.as_deref().unwrap_or_default();
+        filter_dialog(
+            frame,
+            &app.filter,
+            &items,
+            sources,
+            cursor,
+            &app.options.palette,
+        );
+    }
+    if app.show_help {
+        help_popup(frame, &app.options.keymap, app.describe_filter());
+    }
+    if app.options.config.theme == Theme::Mono {
+        frame.render_widget(Monochrome, frame.size());
+    }
+}
+
+/// Splits the main area between the event table and `count` panels, placed beside the table
+/// on wide terminals and below it on narrow ones
+fn split_panels(area: Rect, count: usize) -> (Rect, Vec<Rect>) {
+    if count == 0 {
+        return (area, vec![]);
+    }
+    let side = area.width >= PANEL_SIDE_WIDTH;
+    let (direction, panel_size) = if side {
+        (Direction::Horizontal, PANEL_WIDTH)
+    } else {
+        (Direction::Vertical, PANEL_HEIGHT.min(area.height / 2))
+    };
+    let chunks = Layout::default()
+        .direction(direction.clone())
+        .constraints([Constraint::Min(0), Constraint::Length(panel_size)].as_ref())
+        .split(area);
+    // Panels beside the table are stacked, panels below it are side by side
+    let ratios: Vec<Constraint> = (0..count)
+        .map(|_| Constraint::Ratio(1, count as u32))
+        .collect();
+    let panels = Layout::default()
+        .direction(if side {
+            Direction::Vertical
+        } else {
+            Direction::Horizontal
+        })
+        .constraints(ratios)
+        .split(chunks[1]);
+    (chunks[0], panels)
+}
+
+/// An item of the filter dialog
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FilterItem {
+    Channel(u8),
+    Status(u8),
+    /// Index in `SEVERITIES`
+    Severity(u8),
+    Source(u8),
+    /// Text typed into the dialog
+    Pattern,
+}
+
+impl FilterItem {
+    /// Returns the heading of the group of the item
+    fn group(self) -> &'static str {
+        match self {
+            FilterItem::Channel(_) => "Channels",
+            FilterItem::Status(_) => "Messages",
+            FilterItem::Severity(_) => "Severities",
+            FilterItem::Source(_) => "Sources",
+            FilterItem::Pattern => "Analysis containing",
+        }
+    }
+}
+
+/// Adds the value to the set, or removes it if it is already there
+fn toggle(set: &mut BTreeSet<u8>, value: u8) {
+    if !set.remove(&value) {
+        set.insert(value);
+    }
+}
+
+/// Returns the row and column of each item in the filter dialog, every group starting on a
+/// row of its own
+fn dialog_grid(items: &[FilterItem]) -> Vec<(usize, usize)> {
+    let mut grid: Vec<(usize, usize)> = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let position = match grid.last() {
+            None => (0, 0),
+            Some((row, _)) if item.group() != items[i - 1].group() => (row + 1, 0),
+            Some((row, column)) if column + 1 == DIALOG_COLUMNS => (row + 1, 0),
+            Some((row, column)) => (*row, column + 1),
+        };
+        grid.push(position);
+    }
+    grid
+}
+
+/// Draws the filter dialog over the middle of the screen
+fn filter_dialog<B: Backend>(
+    frame: &mut Frame<B>,
+    filter: &Filter,
+    items: &[FilterItem],
+    sources: &[String],
+    cursor: usize,
+    palette: &Palette,
+) {
+    let mut lines = vec![];
+    let mut row: Vec<Span> = vec![];
+    for (i, (item, (_, column))) in items.iter().zip(dialog_grid(items)).enumerate() {
+        if column == 0 && !row.is_empty() {
+            lines.push(Spans::from(std::mem::take(&mut row)));
+        }
+        if i == 0 || item.group() != items[i - 1].group() {
+            if i > 0 {
+                lines.push(Spans::from(""));
+            }
+            lines.push(Spans::from(item.group()));
+        }
+        let (shown, label) = match *item {
+            FilterItem::Channel(ch) => (
+                filter.hidden_channels & (1 << ch) == 0,
+                format!("{:>2}", ch + 1),
+            ),
+            FilterItem::Status(status) => (
+                !filter.hidden_statuses.contains(&status),
+                status_label(status).to_string(),
+            ),
+            FilterItem::Severity(severity) => {
+                let name = SEVERITIES[severity as usize];
+                (
+                    filter.hidden_severities & (1 << severity) == 0,
+                    name[..1].to_uppercase() + &name[1..],
+                )
+            }
+            FilterItem::Source(source) => (
+                !filter.hidden_sources.contains(&source),
+                input_name(sources, source),
+            ),
+            FilterItem::Pattern => (true, String::new()),
+        };
+        let style = if i == cursor {
+            palette.header
+        } else {
+            STYLE_DEFAULT
+        };
+        if *item == FilterItem::Pattern {
+            row.push(Span::styled(format!("[{}_]", filter.pattern), style));
+            continue;
+        }
+        let mark = if shown { "x" } else { " " };
+        row.push(Span::styled(format!("[{}] {:<13}", mark, label), style));
+    }
+    lines.push(Spans::from(row));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from("Space toggle  A all  O only  Esc close"));
+
+    let size = frame.size();
+    let width = 70.min(size.width);
+    let height = (lines.len() as u16 + 2).min(size.height);
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let dialog =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Filter "));
+    frame.render_widget(Clear, area);
+    frame.render_widget(dialog, area);
+}
+
+/// Lists the keys in the middle of the screen, with what the filter hides below them
+fn help_popup<B: Backend>(frame: &mut Frame<B>, keymap: &Keymap, filter: Vec<String>) {
+    let [left, mut right] = help::columns(keymap);
+    right.push(Spans::from(Span::styled(
+        "Filter",
+        Style::default().add_modifier(Modifier::BOLD),
+    )));
+    right.extend(
+        filter
+            .into_iter()
+            .map(|line| Spans::from(format!("  {}", line))),
+    );
+    let size = frame.size();
+    let width = 84.min(size.width);
+    let height = (left.len().max(right.len()) as u16 + 2).min(size.height);
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Keys, any key to close ");
+    let inner = block.inner(area);
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+        .split(inner);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    frame.render_widget(Paragraph::new(left), halves[0]);
+    frame.render_widget(Paragraph::new(right), halves[1]);
+}
+
+/// Shows a report in the middle of the screen, as many lines as fit, with `more` after the
+/// number of those left out
+fn report_popup<B: Backend>(frame: &mut Frame<B>, title: &str, report: &[String], more: &str) {
+    let size = frame.size();
+    let width = 72.min(size.width);
+    let height = (report.len() as u16 + 3).min(size.height);
+    // Room for the borders and the last line
+    let shown = (height as usize).saturating_sub(3);
+    let mut lines: Vec<Spans> = report
+        .iter()
+        .take(shown)
+        .map(|line| Spans::from(line.as_str()))
+        .collect();
+    let hidden = report.len() - lines.len();
+    lines.push(Spans::from(match hidden {
+        0 => "Esc dismiss".to_string(),
+        _ => format!("{} more{}  Esc dismiss", hidden, more),
+    }));
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let popup = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Shows the alarms in the top right corner, over the table but without taking the keys
+fn alarm_popup<B: Backend>(frame: &mut Frame<B>, alarms: &[String], palette: &Palette) {
+    let mut lines: Vec<Spans> = alarms.iter().map(|a| Spans::from(a.as_str())).collect();
+    lines.push(Spans::from("Esc dismiss"));
+    let size = frame.size();
+    let width = 50.min(size.width);
+    let height = (lines.len() as u16 + 2).min(size.height);
+    let area = Rect::new(size.width - width, 0, width, height);
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Alarm ")
+                .border_style(palette.warning),
+        )
+        .wrap(Wrap { trim: true });
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Returns the short name of a message type shown in the filter dialog
+fn status_label(status: u8) -> &'static str {
+    match status {
+        0x80 => "Note Off",
+        0x90 => "Note On",
+        0xA0 => "Poly Press",
+        0xB0 => "CC",
+        0xC0 => "Program",
+        0xD0 => "Ch Pressure",
+        0xE0 => "Pitch Bend",
+        0xF0 => "SysEx",
+        0xF1 => "MTC",
+        0xF2 => "Song Pos",
+        0xF3 => "Song Select",
+        0xF6 => "Tune Req",
+        0xF8 => "Clock",
+        0xFA => "Start",
+        0xFB => "Continue",
+        0xFC => "Stop",
+        0xFE => "Active Sense",
+        0xFF => "Reset",
+        _ => "Undefined",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Settings;
+    use crate::ui::cast;
+
+    /// An application without a terminal or a source, holding `bytes` received a millisecond
+    /// apart
+    fn app(bytes: &[u8]) -> App {
+        let options = cast::options(&Config::default(), Settings::default()).unwrap();
+        let mut app = App::new(options, None, vec![], None);
+        let mut capture = Capture::new();
+        for (i, byte) in bytes.iter().enumerate() {
+            app.push_event(capture.process(Duration::from_millis(i as u64), *byte));
+        }
+        app
+    }
+
+    #[test]
+    fn filters_severities_and_text() {
+        // An orphaned data byte and a stray End of Exclusive are the only warnings
+        let mut app = app(&[0x40, 0x90, 60, 100, 0xF8, 0x80, 60, 0, 0xF7]);
+        let mut filter = Filter {
+            hidden_severities: 0b0001,
+            ..Filter::default()
+        };
+        app.set_filter(filter.clone());
+        assert_eq!((app.rows(), app.hidden), (2, 7));
+        assert_eq!(app.position(1), Some(8));
+
+        filter.hidden_severities = 0;
+        app.set_filter(filter);
+        app.filter_dialog = Some(app.filter_items().len() - 1);
+        "NOTE OFF"
+            .chars()
+            .for_each(|c| app.filter_dialog_key(KeyCode::Char(c)));
+        assert_eq!(app.filter.pattern, "NOTE OFF");
+        assert_eq!((app.rows(), app.hidden), (3, 6));
+        app.filter_dialog_key(KeyCode::Backspace);
+        assert_eq!(app.filter.pattern, "NOTE OF");
+
+        // Received later, hidden rows are counted as they arrive
+        app.push_event(Capture::new().process(Duration::from_secs(1), 0xFE));
+        assert_eq!((app.rows(), app.hidden), (3, 7));
+    }
+
+    #[test]
+    fn records_notices() {
+        let mut app = app(&[]);
+        let mut capture = Capture::new();
+        app.push_event(capture.process(Duration::ZERO, 0x90));
+        app.push_event(capture.process(Duration::ZERO, 60));
+        let lost = MidiAnalysis::Warning("Port `/dev/ttyUSB0` disconnected".to_string());
+        app.push_event(CaptureEvent::notice(0, Duration::from_millis(2), lost));
+        app.push_event(capture.process(Duration::from_millis(3), 100));
+        let cells = event_cells(&app.events[2], &[]);
+        assert_eq!((cells[1].as_str(), cells[2].as_str()), (" --", "NOTICE"));
+        assert_eq!(app.stats.bytes, 3);
+
+        // Re-analysis keeps the notice, and forgets the Note On it cut short
+        app.cycle_strictness();
+        while app.reanalysis.is_some() {
+            std::thread::sleep(Duration::from_millis(1));
+            app.finish_reanalysis();
+        }
+        assert!(app.events[2].notice);
+        assert_eq!(app.events[3].message, None);
+        app.anchor = Some(0);
+        app.selected = Some(3);
+        assert_eq!(app.rows_text(true).unwrap().0, "90 3C 64");
+    }
+
+    #[test]
+    fn shows_a_row_per_message() {
+        let sysex = [0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7];
+        let mut bytes = vec![0x40, 0x90, 60, 0xF8, 100, 0x80, 60, 0];
+        bytes.extend(sysex);
+        let mut app = app(&bytes);
+        app.toggle_message_view();
+        assert_eq!(app.status, "A row per message");
+        // The orphaned byte, then Note On, Clock, Note Off, and the SysEx
+        let rows: Vec<usize> = (0..app.rows())
+            .filter_map(|row| app.position(row))
+            .collect();
+        assert_eq!(rows, [0, 3, 4, 7, 13]);
+
+        let naming = NoteNaming::English;
+        let cells = |position: usize| {
+            message_cells(&app.events[position], &[], &app.options.config, naming)
+        };
+        assert_eq!(cells(4)[1], " 90 3C 64");
+        assert_eq!(cells(13)[1], " F0 7E 7F…");
+        assert_eq!(cells(0), event_cells(&app.events[0], &[]));
+
+        // A range of messages starts at the first byte of the first one, the Clock within it
+        // left aside
+        app.anchor = Some(13);
+        app.selected = Some(2);
+        assert_eq!(app.range(), Some(1..=13));
+
+        app.toggle_message_view();
+        assert_eq!(app.rows(), bytes.len());
+    }
+
+    #[test]
+    fn alerts_on_violations() {
+        let mut app = app(&[0x40]);
+        let violation = |byte| {
+            let mut event = Capture::new().process(Duration::from_secs(1), byte);
+            event.analysis = MidiAnalysis::Violation("Strictly orphaned".to_string());
+            event
+        };
+        app.push_event(violation(0x41));
+        assert!(!app.take_alert());
+        assert_eq!((app.stats.warnings, app.stats.violations), (1, 1));
+
+        app.options.config.alert = Alert::Bell;
+        app.push_event(violation(0x42));
+        assert!(app.take_alert());
+        // Once a second at most, and only for new violations
+        app.push_event(violation(0x43));
+        assert!(!app.take_alert());
+        app.alerted = app.alerted.map(|t| t - ALERT_INTERVAL);
+        assert!(!app.take_alert());
+
+        app.options.config.alert = Alert::Flash;
+        app.push_event(violation(0x44));
+        assert!(!app.take_alert());
+        assert!(app.alerted.is_some_and(|t| t.elapsed() < FLASH));
+        let palette = &app.options.palette;
+        assert_eq!(palette.row(&app.events[4]), palette.violation);
+        assert_eq!(palette.row(&app.events[0]), palette.warning);
+    }
+
+    #[test]
+    fn sends_panic() {
+        let mut app = app(&[]);
+        app.panic();
+        assert!(app.status.starts_with("No MIDI Out"));
+
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        app.out = Some(Box::new(writer));
+        app.panic();
+        drop(app);
+        let mut sent = vec![];
+        std::io::Read::read_to_end(&mut reader, &mut sent).unwrap();
+        // Sustain off, All Sound Off, All Notes Off, and Reset All Controllers on each channel
+        let mut capture = Capture::new();
+        let messages: Vec<MidiMessage> = sent
+            .iter()
+            .filter_map(|byte| capture.process(Duration::ZERO, *byte).message)
+            .collect();
+        assert_eq!(messages.len(), 16 * 4);
+        assert_eq!(
+            messages[0],
+            MidiMessage::ControlChange {
+                channel: 0,
+                control: 64,
+                value: 0
+            }
+        );
+        assert_eq!(messages[63].channel(), Some(15));
+    }
+
+    #[test]
+    fn copies_rows() {
+        let mut app = app(&[0x90, 60, 100, 0xF8]);
+        assert!(app.rows_text(false).is_none());
+        app.selected = Some(0);
+        app.anchor = Some(2);
+        let (text, copied) = app.rows_text(false).unwrap();
+        assert_eq!(copied, "3 rows");
+        assert_eq!(
+            text.lines().next(),
+            Some("0.000000 s  90  STATUS  1  Note On (Channel 0)  -")
+        );
+        let hex = app.rows_text(true).unwrap();
+        assert_eq!(hex, ("90 3C 64".to_string(), "3 bytes".to_string()));
+
+        // The Clock is left out with the rows it is hidden in
+        app.anchor = Some(3);
+        app.set_filter(Filter {
+            hidden_statuses: BTreeSet::from([0xF8]),
+            ..Filter::default()
+        });
+        assert_eq!(app.rows_text(true).unwrap().0, "90 3C 64");
+        app.toggle_message_view();
+        let (text, copied) = app.rows_text(false).unwrap();
+        assert_eq!(copied, "1 row");
+        assert_eq!(
+            text,
+            "0.002000 s  90 3C 64  Note On  1  C4, velocity 100  60 100\n"
+        );
+    }
+
+    #[test]
+    fn shows_inputs_in_tabs() {
+        let mut app = app(&[]);
+        app.next_tab(true);
+        assert_eq!(app.tab(), Some(0));
+
+        app.options.sources = Some(vec!["keys".to_string(), "drums".to_string()]);
+        let mut capture = Capture::new();
+        for (source, byte) in [(0, 0xF8), (1, 0xFA), (0, 0xF8), (1, 0xFC)] {
+            app.push_event(capture.process_from(source, Duration::ZERO, byte));
+        }
+        app.next_tab(true);
+        assert_eq!((app.tab(), app.rows()), (Some(1), 2));
+        assert_eq!(app.status, "Showing keys alone");
+        app.next_tab(true);
+        assert_eq!((app.tab(), app.position(1)), (Some(2), Some(3)));
+        app.next_tab(true);
+        assert_eq!((app.tab(), app.rows()), (Some(0), 4));
+        assert_eq!(app.status, "Showing all inputs");
+        app.next_tab(false);
+        assert_eq!(app.status, "Showing drums alone");
+
+        // Any other set of inputs is on no tab, and the next one is all of them
+        app.set_filter(Filter::default());
+        app.filter.hidden_sources = BTreeSet::from([0, 1]);
+        assert_eq!(app.tab(), None);
+        app.next_tab(true);
+        assert_eq!(app.tab(), Some(0));
+    }
+
+    /// Draws the application on a screen of `width` columns and `height` rows, returning its
+    /// lines
+    fn screen(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let buffer = terminal.draw(|f| ui(f, app)).unwrap().buffer.clone();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fits_the_terminal() {
+        let mut app = app(&[0x90, 60, 100]);
+        let small = screen(&mut app, MIN_WIDTH - 10, MIN_HEIGHT - 1);
+        assert_eq!(small[0].trim_end(), "Terminal too small");
+
+        // Narrow terminals drop the type and data columns
+        let narrow = screen(&mut app, COMPACT_WIDTH - 20, 12);
+        assert!(narrow[0].starts_with(" BYTE CH  MESSAGE"), "{}", narrow[0]);
+        assert!(!narrow[0].contains("DATA"));
+        assert!(narrow[3].starts_with("* 64   1  Note On (Channel 0): Velocity: 100"));
+        let wide = screen(&mut app, COMPACT_WIDTH + 60, 12);
+        assert!(wide[0].starts_with(" BYTE     TYPE       CH     MESSAGE"));
+        assert_eq!(wide[0].split_whitespace().last(), Some("DATA"));
     }
-    frame.render_stateful_widget(table, chunks[0], &mut app.table_state);
 }
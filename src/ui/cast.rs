@@ -0,0 +1,203 @@
+//! Recordings of the TUI replaying a capture, in the asciicast v2 format of asciinema
+//!
+//! The capture is played through the application without a terminal, and every frame is
+//! written as the escape sequences that redraw the lines that changed since the last one
+
+use super::{app, Keymap, Options, Palette};
+use crate::analysis::{stats::Limits, Settings};
+use crate::capture::{CaptureEvent, Filter};
+use crate::config::Config;
+use crate::state::UiState;
+use serde_json::json;
+use std::{fmt::Write, path::PathBuf, time::Duration, time::Instant};
+use tui::{
+    buffer::Buffer,
+    style::{Color, Modifier},
+};
+
+/// Renders the TUI replaying `events` on a screen of `size` columns and rows, drawn `fps`
+/// times a second of the capture
+pub fn cast(
+    config: &Config,
+    settings: Settings,
+    events: Vec<CaptureEvent>,
+    size: (u16, u16),
+    fps: f64,
+) -> Result<String, anyhow::Error> {
+    let header = json!({
+        "version": 2,
+        "width": size.0,
+        "height": size.1,
+        "title": "miditerm",
+        "env": { "TERM": "xterm-256color" },
+    });
+    let mut out = format!("{}\n", header);
+    let mut last: Option<Buffer> = None;
+    app::replay(
+        options(config, settings)?,
+        events,
+        size,
+        Duration::from_secs_f64(1.0 / fps.max(0.1)),
+        |time, buffer| {
+            let text = frame(last.as_ref(), buffer);
+            if !text.is_empty() {
+                let record = json!([time.as_secs_f64(), "o", text]);
+                let _ = writeln!(out, "{}", record);
+            }
+            last = Some(buffer.clone());
+        },
+    )?;
+    Ok(out)
+}
+
+/// Returns the settings of an application replaying a capture without a terminal, a source,
+/// or a place to keep its state
+pub(super) fn options(config: &Config, settings: Settings) -> Result<Options, anyhow::Error> {
+    Ok(Options {
+        smf_path: PathBuf::new(),
+        ppq: 480,
+        bpm: 120.0,
+        record: false,
+        sysex_dir: PathBuf::new(),
+        source_timestamps: false,
+        settings,
+        filter: Filter::default(),
+        limits: Limits::default(),
+        arm: None,
+        spill: PathBuf::new(),
+        scrollback: None,
+        scrollback_spill: None,
+        start: Instant::now(),
+        history: vec![],
+        pads: vec![],
+        keymap: Keymap::default(),
+        palette: Palette::new(config.theme, &config.colors)?,
+        zones: vec![],
+        reference: None,
+        sources: None,
+        state: UiState::default(),
+        state_store: None,
+        // Rows fade by the time of the terminal, which a replay does not follow
+        config: Config {
+            dim: false,
+            ..config.clone()
+        },
+    })
+}
+
+/// Returns the escape sequences that turn the screen from `last` into `buffer`, clearing it
+/// first if there is no last frame
+fn frame(last: Option<&Buffer>, buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut out = match last {
+        Some(_) => String::new(),
+        None => "\x1b[?25l\x1b[2J".to_string(),
+    };
+    for y in area.top()..area.bottom() {
+        let cells = |buffer: &Buffer| -> Vec<_> {
+            (area.left()..area.right())
+                .map(|x| buffer.get(x, y).clone())
+                .collect()
+        };
+        let line = cells(buffer);
+        if last.is_some_and(|last| cells(last) == line) {
+            continue;
+        }
+        let _ = write!(out, "\x1b[{};1H", y - area.top() + 1);
+        let mut style = None;
+        for cell in &line {
+            let current = (cell.fg, cell.bg, cell.modifier);
+            if style != Some(current) {
+                out.push_str(&sgr(cell.fg, cell.bg, cell.modifier));
+                style = Some(current);
+            }
+            out.push_str(&cell.symbol);
+        }
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+/// Returns the Select Graphic Rendition sequence of a style, starting from no style
+fn sgr(fg: Color, bg: Color, modifier: Modifier) -> String {
+    let mut codes = vec!["0".to_string()];
+    for (flag, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ] {
+        if modifier.contains(flag) {
+            codes.push(code.to_string());
+        }
+    }
+    codes.extend(color(fg, false));
+    codes.extend(color(bg, true));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/// Returns the SGR code of a foreground or background color, none for the default
+fn color(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Indexed(i) => return Some(format!("{};5;{}", base + 8, i)),
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    };
+    Some(code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn replays_capture() {
+        let mut capture = Capture::new();
+        let events: Vec<CaptureEvent> = [0x90, 60, 100]
+            .into_iter()
+            .enumerate()
+            .map(|(i, byte)| capture.process(Duration::from_millis(500 * i as u64), byte))
+            .collect();
+        let cast = cast(
+            &Config::default(),
+            Settings::default(),
+            events,
+            (100, 20),
+            10.0,
+        )
+        .unwrap();
+        let lines: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["width"], 100);
+        // A frame for each byte, as they are further apart than a frame
+        assert_eq!(lines.len(), 1 + 3);
+        assert_eq!(lines[1][0], 0.0);
+        assert_eq!(lines[3][0], 1.0);
+        assert!(lines[1][2]
+            .as_str()
+            .unwrap()
+            .starts_with("\x1b[?25l\x1b[2J"));
+        assert!(lines[3][2].as_str().unwrap().contains("Note On"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Composes a message field by field, or from hexadecimal bytes, to send to MIDI Out
+
+use crate::midi::{self, MidiMessage, MidiParser};
+use crossterm::event::KeyCode;
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Types of message the composer builds, the last one being typed as hexadecimal bytes
+const KINDS: [&str; 8] = [
+    "Note On",
+    "Note Off",
+    "Control Change",
+    "Program Change",
+    "Channel Pressure",
+    "Poly Pressure",
+    "Pitch Bend",
+    "Raw hex",
+];
+const RAW: usize = KINDS.len() - 1;
+/// Index of the Pitch Bend type, whose value takes 14 bits
+const PITCH_BEND: usize = 6;
+
+/// A field of the composer after the message type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Channel,
+    /// One of the data values of the message, by its label
+    Value(usize, &'static str),
+    Hex,
+}
+
+/// The message being composed
+#[derive(Debug)]
+pub(super) struct Composer {
+    /// Index in `KINDS`
+    kind: usize,
+    /// Channel from 1 to 16, or 0 while it is being typed
+    channel: u16,
+    /// Data values, each type using the first one or two
+    values: [u16; 2],
+    /// Bytes typed for raw messages
+    hex: String,
+    /// Selected field, 0 being the message type
+    field: usize,
+    /// The next digit replaces the value of the field rather than adding to it
+    fresh: bool,
+}
+
+impl Composer {
+    pub fn new() -> Composer {
+        Composer {
+            kind: 0,
+            channel: 1,
+            values: [60, 100],
+            hex: String::new(),
+            field: 0,
+            fresh: true,
+        }
+    }
+
+    /// Returns the fields of the message type after the type itself
+    fn fields(&self) -> Vec<Field> {
+        let values: &[&str] = match self.kind {
+            0 | 1 => &["Note", "Velocity"],
+            2 => &["Control", "Value"],
+            3 => &["Program"],
+            4 => &["Pressure"],
+            5 => &["Note", "Pressure"],
+            PITCH_BEND => &["Value"],
+            _ => return vec![Field::Hex],
+        };
+        let values = values.iter().enumerate().map(|(i, l)| Field::Value(i, l));
+        [Field::Channel].into_iter().chain(values).collect()
+    }
+
+    /// Returns the highest value of a data field
+    fn max(&self) -> u16 {
+        if self.kind == PITCH_BEND {
+            16383
+        } else {
+            127
+        }
+    }
+
+    /// Returns `true` if the key edits the message: Tab and Shift+Tab move between the
+    /// fields, Left and Right change the type, channel, or value, and digits type the
+    /// channel or value, or hexadecimal bytes for raw messages. Enter sends
+    pub fn takes(&self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Enter => true,
+            KeyCode::Left | KeyCode::Right => self.field == 0 || self.kind != RAW,
+            KeyCode::Backspace => self.field > 0,
+            KeyCode::Char(c) if self.field > 0 && self.kind == RAW => {
+                c.is_ascii_hexdigit() || c == ' '
+            }
+            KeyCode::Char(c) => self.field > 0 && c.is_ascii_digit(),
+            _ => false,
+        }
+    }
+
+    /// Edits the message with a key taken by `takes`
+    pub fn key(&mut self, code: KeyCode) {
+        let fields = self.fields().len() + 1;
+        let field = self.field.checked_sub(1).map(|i| self.fields()[i]);
+        let max = match field {
+            Some(Field::Channel) => 16,
+            _ => self.max(),
+        };
+        let value = match field {
+            Some(Field::Channel) => Some(&mut self.channel),
+            Some(Field::Value(i, _)) => Some(&mut self.values[i]),
+            _ => None,
+        };
+        match (code, value) {
+            (KeyCode::Tab, _) => {
+                self.field = (self.field + 1) % fields;
+                self.fresh = true;
+            }
+            (KeyCode::BackTab, _) => {
+                self.field = (self.field + fields - 1) % fields;
+                self.fresh = true;
+            }
+            (KeyCode::Left | KeyCode::Right, _) if self.field == 0 => {
+                self.kind = match code {
+                    KeyCode::Right => (self.kind + 1) % KINDS.len(),
+                    _ => (self.kind + KINDS.len() - 1) % KINDS.len(),
+                };
+                self.values = match self.kind {
+                    2 => [1, 64],
+                    3 | 4 => [0, 0],
+                    PITCH_BEND => [8192, 0],
+                    _ => [60, 100],
+                };
+            }
+            (KeyCode::Left, Some(value)) => {
+                let min = u16::from(field == Some(Field::Channel));
+                *value = value.saturating_sub(1).max(min);
+                self.fresh = true;
+            }
+            (KeyCode::Right, Some(value)) => {
+                *value = (*value + 1).min(max);
+                self.fresh = true;
+            }
+            // Digits add to the value typed so far, starting over if it would get too high
+            (KeyCode::Char(c), Some(value)) => {
+                let digit = c as u16 - '0' as u16;
+                let typed = *value as u32 * 10 + digit as u32;
+                *value = if !self.fresh && typed <= max as u32 {
+                    typed as u16
+                } else {
+                    digit.min(max)
+                };
+                self.fresh = false;
+            }
+            (KeyCode::Backspace, Some(value)) => {
+                *value /= 10;
+                self.fresh = false;
+            }
+            (KeyCode::Char(c), None) => self.hex.push(c.to_ascii_uppercase()),
+            (KeyCode::Backspace, None) => {
+                self.hex.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Builds the message, or describes what keeps it from being one
+    pub fn message(&self) -> Result<MidiMessage, String> {
+        if self.channel == 0 && self.kind != RAW {
+            return Err("Channels go from 1 to 16".to_string());
+        }
+        let channel = self.channel as u8 - 1;
+        let [a, b] = self.values.map(|v| v.min(127) as u8);
+        Ok(match self.kind {
+            0 => MidiMessage::NoteOn {
+                channel,
+                note: a,
+                velocity: b,
+            },
+            1 => MidiMessage::NoteOff {
+                channel,
+                note: a,
+                velocity: b,
+            },
+            2 => MidiMessage::ControlChange {
+                channel,
+                control: a,
+                value: b,
+            },
+            3 => MidiMessage::ProgramChange {
+                channel,
+                program: a,
+            },
+            4 => MidiMessage::ChannelPressure {
+                channel,
+                pressure: a,
+            },
+            5 => MidiMessage::PolyPressure {
+                channel,
+                note: a,
+                pressure: b,
+            },
+            PITCH_BEND => MidiMessage::PitchBend {
+                channel,
+                value: self.values[0],
+            },
+            _ => return parse_raw(&self.hex),
+        })
+    }
+
+    /// Lists the fields with the selected one highlighted, and the bytes that would be sent
+    pub fn lines(&self) -> Vec<Spans<'static>> {
+        let selected = |field: usize| {
+            if self.field == field {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            }
+        };
+        let mut lines = vec![Spans::from(vec![
+            Span::raw("Type      "),
+            Span::styled(format!("< {} >", KINDS[self.kind]), selected(0)),
+        ])];
+        for (i, field) in self.fields().into_iter().enumerate() {
+            let (label, value) = match field {
+                Field::Channel => ("Channel", self.channel.to_string()),
+                Field::Value(v, label) => (label, self.values[v].to_string()),
+                Field::Hex => ("Bytes", format!("{}_", self.hex)),
+            };
+            lines.push(Spans::from(vec![
+                Span::raw(format!("{:<10}", label)),
+                Span::styled(value, selected(i + 1)),
+            ]));
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(match self.message() {
+            Ok(message) => {
+                format!("Sends     {}", midi::hex(&message.to_bytes()))
+            }
+            Err(e) => e,
+        }));
+        lines.push(Spans::from("Tab field, ←→ change, Enter send"));
+        lines
+    }
+}
+
+/// Parses hexadecimal bytes into the one complete message they must make up
+fn parse_raw(hex: &str) -> Result<MidiMessage, String> {
+    let bytes = hex
+        .split_whitespace()
+        .map(|word| match word.len() {
+            1 | 2 => u8::from_str_radix(word, 16).map_err(|e| e.to_string()),
+            _ => Err(format!("`{}` is not a single byte", word)),
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    let mut parser = MidiParser::new();
+    let mut message = None;
+    for (i, byte) in bytes.iter().enumerate() {
+        if let (Some(parsed), _) = parser.parse_midi(*byte) {
+            if i + 1 < bytes.len() {
+                return Err("Only one message is sent at a time".to_string());
+            }
+            message = Some(parsed);
+        }
+    }
+    message.ok_or_else(|| "Type the bytes of a complete message".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_messages() {
+        let mut composer = Composer::new();
+        composer.key(KeyCode::Right);
+        composer.key(KeyCode::Right);
+        composer.key(KeyCode::Tab);
+        // Channel 10
+        composer.key(KeyCode::Char('1'));
+        composer.key(KeyCode::Char('0'));
+        composer.key(KeyCode::Tab);
+        composer.key(KeyCode::Right);
+        composer.key(KeyCode::Tab);
+        composer.key(KeyCode::Backspace);
+        composer.key(KeyCode::Backspace);
+        for c in "127".chars() {
+            composer.key(KeyCode::Char(c));
+        }
+        assert_eq!(composer.message().unwrap().to_bytes(), [0xB9, 2, 127]);
+        assert!(!composer.takes(KeyCode::Char('q')));
+
+        composer.field = 0;
+        composer.key(KeyCode::Left);
+        composer.key(KeyCode::Left);
+        composer.key(KeyCode::Left);
+        composer.key(KeyCode::Tab);
+        assert!(composer.takes(KeyCode::Char('f')));
+        for c in "f0 41 10 f7".chars() {
+            composer.key(KeyCode::Char(c));
+        }
+        assert_eq!(
+            composer.message().unwrap().to_bytes(),
+            [0xF0, 0x41, 0x10, 0xF7]
+        );
+    }
+
+    #[test]
+    fn raw_bytes_make_one_message() {
+        assert_eq!(
+            parse_raw("E0 00 40"),
+            Ok(MidiMessage::PitchBend {
+                channel: 0,
+                value: 8192
+            })
+        );
+        assert!(parse_raw("90 3C").is_err());
+        assert!(parse_raw("F8 F8").is_err());
+        assert!(parse_raw("903C64").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Picks a file to load a capture from, or to save or record the capture to, browsing the
+//! directories from the current one
+
+use crate::{
+    capture::CaptureEvent,
+    sink::{CaptureRecorder, CsvLogger, JsonlLogger, RawTee, Sink, SmfRecorder},
+};
+use crossterm::event::KeyCode;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Entries of the directory listed at once
+const LISTED: usize = 10;
+/// Name first offered when saving, a capture that loads back with its timing
+const SAVE_NAME: &str = "capture.mtcap";
+/// Name first offered when recording
+const RECORD_NAME: &str = "recording.mid";
+
+/// What the picked file is for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Purpose {
+    Load,
+    Save,
+    /// Writing the bytes received from now on
+    Record,
+}
+
+/// A directory or file of the listing
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    name: String,
+    directory: bool,
+}
+
+/// The directory being browsed and the name typed or picked in it
+#[derive(Debug)]
+pub(super) struct FileDialog {
+    pub purpose: Purpose,
+    directory: PathBuf,
+    /// Subdirectories first, then files, each in name order, after the parent directory
+    entries: Vec<Entry>,
+    /// Entry picked with Up and Down, if any
+    cursor: Option<usize>,
+    name: String,
+    /// Only the events that pass the filter are saved
+    pub filtered: bool,
+    /// Why the directory could not be listed
+    error: Option<String>,
+}
+
+impl FileDialog {
+    /// Opens the dialog on the current directory
+    pub fn new(purpose: Purpose) -> FileDialog {
+        let mut dialog = FileDialog {
+            purpose,
+            directory: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            entries: vec![],
+            cursor: None,
+            name: purpose.name(),
+            filtered: false,
+            error: None,
+        };
+        dialog.list();
+        dialog
+    }
+
+    /// Lists the directory, hidden entries left out
+    fn list(&mut self) {
+        self.cursor = None;
+        self.entries = vec![Entry {
+            name: "..".to_string(),
+            directory: true,
+        }];
+        let read = match fs::read_dir(&self.directory) {
+            Ok(read) => read,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        self.error = None;
+        let mut entries: Vec<Entry> = read
+            .filter_map(Result::ok)
+            .map(|entry| Entry {
+                name: entry.file_name().to_string_lossy().into(),
+                directory: entry.path().is_dir(),
+            })
+            .filter(|entry| !entry.name.starts_with('.'))
+            .collect();
+        entries.sort_by(|a, b| (!a.directory, &a.name).cmp(&(!b.directory, &b.name)));
+        self.entries.extend(entries);
+    }
+
+    /// Handles a key: Up and Down pick an entry, other characters type the name, Tab switches
+    /// between saving everything and saving what the filter shows, and Enter opens the
+    /// directory or returns the path of the file
+    pub fn key(&mut self, code: KeyCode) -> Option<PathBuf> {
+        let last = self.entries.len() - 1;
+        match code {
+            KeyCode::Up | KeyCode::Down => {
+                let cursor = match (self.cursor, code) {
+                    (None, KeyCode::Up) => last,
+                    (None, _) => 0,
+                    (Some(cursor), KeyCode::Up) => cursor.checked_sub(1).unwrap_or(last),
+                    (Some(cursor), _) => (cursor + 1) % self.entries.len(),
+                };
+                self.cursor = Some(cursor);
+                self.name = self.entries[cursor].name.clone();
+            }
+            KeyCode::Char(c) => {
+                self.name.push(c);
+                self.cursor = None;
+            }
+            KeyCode::Backspace => {
+                self.name.pop();
+                self.cursor = None;
+            }
+            KeyCode::Tab if self.purpose == Purpose::Save => self.filtered = !self.filtered,
+            KeyCode::Enter if !self.name.trim().is_empty() => {
+                let path = self.directory.join(self.name.trim());
+                if !path.is_dir() {
+                    return Some(path);
+                }
+                self.directory = path.canonicalize().unwrap_or(path);
+                self.name = self.purpose.name();
+                self.list();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Lists the entries around the one picked, the name, and for saving what is saved
+    pub fn lines(&self) -> Vec<Spans<'static>> {
+        let mut lines = vec![Spans::from(format!("{}", self.directory.display()))];
+        if let Some(error) = &self.error {
+            lines.push(Spans::from(format!("Unable to list: {}", error)));
+        }
+        let first = self
+            .cursor
+            .map_or(0, |cursor| cursor.saturating_sub(LISTED - 1));
+        for (i, entry) in self.entries.iter().enumerate().skip(first).take(LISTED) {
+            let style = match self.cursor == Some(i) {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default(),
+            };
+            let slash = if entry.directory { "/" } else { "" };
+            lines.push(Spans::from(Span::styled(
+                format!("  {}{}", entry.name, slash),
+                style,
+            )));
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(format!("File   {}_", self.name)));
+        match self.purpose {
+            Purpose::Load => {
+                lines.push(Spans::from(".syx, .mtcap, .mid, or raw bytes"));
+                lines.push(Spans::from("↑↓ pick, Enter open, Esc cancel"));
+            }
+            Purpose::Save => {
+                let mark = if self.filtered { "x" } else { " " };
+                lines.push(Spans::from(format!(
+                    "[{}] Only the events shown by the filter",
+                    mark
+                )));
+                lines.push(Spans::from(".mtcap, .jsonl, .csv, .mid, or raw bytes"));
+                lines.push(Spans::from("↑↓ pick, Tab filtered, Enter save, Esc cancel"));
+            }
+            Purpose::Record => {
+                lines.push(Spans::from(".mid, .jsonl, .csv, .mtcap, or raw bytes"));
+                lines.push(Spans::from("↑↓ pick, Enter record, Esc cancel"));
+            }
+        }
+        lines
+    }
+}
+
+impl Purpose {
+    /// Returns the name first offered
+    fn name(self) -> String {
+        match self {
+            Purpose::Load => String::new(),
+            Purpose::Save => SAVE_NAME.to_string(),
+            Purpose::Record => RECORD_NAME.to_string(),
+        }
+    }
+}
+
+/// The capture being written to a file as it is received
+pub(super) struct Recording {
+    path: PathBuf,
+    output: Box<dyn Sink>,
+    started: Instant,
+    /// Bytes of the capture written so far
+    bytes: usize,
+}
+
+impl Recording {
+    /// Starts recording to a file in the format of its extension, as the capture is saved
+    pub fn start(path: &Path, ppq: u16, bpm: f64) -> Result<Recording, anyhow::Error> {
+        Ok(Recording {
+            path: path.to_path_buf(),
+            output: output(path, ppq, bpm)?,
+            started: Instant::now(),
+            bytes: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, event: &CaptureEvent) -> Result<(), anyhow::Error> {
+        self.output.write(event)?;
+        self.bytes += 1;
+        Ok(())
+    }
+
+    /// Writes what is left and closes the file
+    pub fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.output.finish()
+    }
+
+    /// Returns the time spent recording and the bytes recorded, such as `01:05 12.3 KB`
+    pub fn progress(&self) -> String {
+        let seconds = self.started.elapsed().as_secs();
+        let size = match self.bytes {
+            bytes if bytes < 1 << 10 => format!("{} B", bytes),
+            bytes if bytes < 1 << 20 => format!("{:.1} KB", bytes as f64 / 1024.0),
+            bytes => format!("{:.1} MB", bytes as f64 / (1 << 20) as f64),
+        };
+        format!("{:02}:{:02} {}", seconds / 60, seconds % 60, size)
+    }
+}
+
+/// Opens the output the capture is saved to, chosen by the extension of its path: `.mtcap`
+/// captures, `.jsonl` and `.csv` logs, `.mid` files written with `ppq` and `bpm`, or raw
+/// bytes for anything else
+pub(super) fn output(path: &Path, ppq: u16, bpm: f64) -> Result<Box<dyn Sink>, anyhow::Error> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    Ok(match extension.as_deref() {
+        Some("mtcap") => Box::new(CaptureRecorder::create(path)?),
+        Some("jsonl" | "json") => Box::new(JsonlLogger::create(path)?),
+        Some("csv") => Box::new(CsvLogger::create(path)?),
+        Some("mid" | "midi") => Box::new(SmfRecorder::new(path.to_path_buf(), ppq, bpm)),
+        _ => Box::new(RawTee::create(path)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browses_directories() {
+        let root = std::env::temp_dir().join(format!("miditerm-files-{}", std::process::id()));
+        fs::create_dir_all(root.join("songs")).unwrap();
+        fs::write(root.join("a.syx"), [0xF0, 0xF7]).unwrap();
+        let mut dialog = FileDialog::new(Purpose::Load);
+        dialog.directory = root.clone();
+        dialog.list();
+        let names: Vec<&str> = dialog.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["..", "songs", "a.syx"]);
+
+        dialog.key(KeyCode::Up);
+        assert_eq!(dialog.key(KeyCode::Enter), Some(root.join("a.syx")));
+        dialog.key(KeyCode::Down);
+        dialog.key(KeyCode::Down);
+        assert_eq!(dialog.key(KeyCode::Enter), None);
+        assert!(dialog.directory.ends_with("songs"));
+        for c in "b.mid".chars() {
+            dialog.key(KeyCode::Char(c));
+        }
+        assert!(dialog.key(KeyCode::Enter).unwrap().ends_with("songs/b.mid"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn records_bytes() {
+        let path = std::env::temp_dir().join(format!("miditerm-record-{}.bin", std::process::id()));
+        let mut recording = Recording::start(&path, 480, 120.0).unwrap();
+        let mut capture = crate::capture::Capture::new();
+        for byte in [0x90, 60, 100] {
+            let event = capture.process(std::time::Duration::ZERO, byte);
+            recording.write(&event).unwrap();
+        }
+        recording.finish().unwrap();
+        assert_eq!(recording.progress(), "00:00 3 B");
+        assert_eq!(fs::read(&path).unwrap(), [0x90, 60, 100]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+//! Keys of the TUI, listed in the help overlay opened with `?` or F10
+
+use crate::ui::{keys::Action, Keymap, Panel};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Width of the keys in front of what they do
+const KEY_WIDTH: usize = 11;
+
+/// Keys of an entry of the help
+#[derive(Debug, Clone, Copy)]
+enum Keys {
+    /// Those bound to the actions, in order
+    Bound(&'static [Action]),
+    /// Keys that cannot be bound again
+    Fixed(&'static str),
+}
+
+/// A heading and its keys, with what each does
+type Section = (&'static str, &'static [(Keys, &'static str)]);
+
+/// Groups of keys
+const SECTIONS: [Section; 9] = [
+    (
+        "General",
+        &[
+            (Keys::Bound(&[Action::Quit]), "Quit"),
+            (Keys::Bound(&[Action::Help]), "This help"),
+            (Keys::Bound(&[Action::Dismiss]), "Dismiss popups and search"),
+            (Keys::Bound(&[Action::Filter]), "Filter"),
+            (Keys::Bound(&[Action::Ports]), "Ports"),
+            (Keys::Bound(&[Action::Panic]), "Panic on MIDI Out"),
+        ],
+    ),
+    (
+        "Moving",
+        &[
+            (
+                Keys::Bound(&[Action::Up, Action::Down]),
+                "Previous and next row",
+            ),
+            (
+                Keys::Bound(&[Action::PageUp, Action::PageDown]),
+                "Previous and next page",
+            ),
+            (
+                Keys::Bound(&[Action::HalfPageUp, Action::HalfPageDown]),
+                "Up and down half a page",
+            ),
+            (Keys::Bound(&[Action::Top]), "First row"),
+            (Keys::Bound(&[Action::Follow]), "Follow new bytes"),
+            (Keys::Bound(&[Action::ToggleFollow]), "Follow or stop"),
+            (Keys::Bound(&[Action::Search]), "Search"),
+            (
+                Keys::Bound(&[Action::NextMatch, Action::PreviousMatch]),
+                "Next and previous match",
+            ),
+            (
+                Keys::Bound(&[Action::NextProblem, Action::PreviousProblem]),
+                "Next and previous problem",
+            ),
+            (
+                Keys::Bound(&[Action::Bookmark]),
+                "Bookmark the selected row",
+            ),
+            (
+                Keys::Bound(&[Action::NextBookmark, Action::PreviousBookmark]),
+                "Next and previous bookmark",
+            ),
+            (
+                Keys::Bound(&[Action::NextInput, Action::PreviousInput]),
+                "Next and previous input",
+            ),
+        ],
+    ),
+    (
+        "Files",
+        &[
+            (
+                Keys::Bound(&[Action::Load, Action::Save]),
+                "Load and save the capture",
+            ),
+            (Keys::Bound(&[Action::Record]), "Record to --record-smf"),
+            (Keys::Bound(&[Action::RecordTo]), "Record to a file"),
+        ],
+    ),
+    (
+        "SysEx",
+        &[
+            (
+                Keys::Bound(&[
+                    Action::Panel(Panel::Sysex),
+                    Action::NextSysex,
+                    Action::PreviousSysex,
+                ]),
+                "List, next, previous",
+            ),
+            (
+                Keys::Bound(&[Action::ViewSysex, Action::SaveSysex, Action::SendSysex]),
+                "View, save, send again",
+            ),
+            (
+                Keys::Bound(&[Action::CompareSysex, Action::CompareSysexReference]),
+                "Compare two, with reference",
+            ),
+        ],
+    ),
+    (
+        "Selecting",
+        &[
+            (Keys::Bound(&[Action::Range]), "Start or clear a range"),
+            (
+                Keys::Bound(&[Action::CopyRows, Action::CopyBytes]),
+                "Copy rows or their bytes",
+            ),
+            (
+                Keys::Bound(&[Action::CopyC, Action::CopyRust]),
+                "Copy as a C or Rust array",
+            ),
+        ],
+    ),
+    (
+        "Analysis",
+        &[
+            (Keys::Bound(&[Action::Strictness]), "Cycle strictness"),
+            (Keys::Bound(&[Action::GeneralMidi]), "General MIDI checks"),
+            (Keys::Bound(&[Action::Realtime]), "Show or hide Real Time"),
+            (
+                Keys::Bound(&[Action::SourceTimestamps]),
+                "Source timestamps",
+            ),
+            (
+                Keys::Bound(&[Action::MessageView]),
+                "Row per message or byte",
+            ),
+            (Keys::Bound(&[Action::Dim]), "Fade rows by age"),
+            (
+                Keys::Bound(&[Action::Snapshot, Action::DiffSnapshot]),
+                "Snapshot, diff channels",
+            ),
+        ],
+    ),
+    (
+        "Panels",
+        &[
+            (
+                Keys::Bound(&[Action::Panel(Panel::Detail), Action::Panel(Panel::Stats)]),
+                "Detail, statistics",
+            ),
+            (
+                Keys::Bound(&[Action::Panel(Panel::Pads), Action::Panel(Panel::Send)]),
+                "Pads, send",
+            ),
+            (
+                Keys::Bound(&[Action::Panel(Panel::Keyboard), Action::Panel(Panel::Mpe)]),
+                "Keyboard, MPE",
+            ),
+            (
+                Keys::Bound(&[Action::Panel(Panel::Controls), Action::Panel(Panel::Levels)]),
+                "Controllers, bend",
+            ),
+            (
+                Keys::Bound(&[Action::Panel(Panel::Rate), Action::Panel(Panel::Channels)]),
+                "Rate, channels",
+            ),
+            (
+                Keys::Bound(&[Action::Panel(Panel::Bookmarks), Action::Panel(Panel::Hex)]),
+                "Bookmarks, hex dump",
+            ),
+            (Keys::Bound(&[Action::Watch]), "Watch the controller"),
+            (Keys::Bound(&[Action::SaveLayout]), "Save the layout"),
+            (Keys::Fixed("1-9"), "Switch layout"),
+            (Keys::Fixed("1-9 a-g"), "Mute, Alt solo, with h"),
+        ],
+    ),
+    (
+        "Reference",
+        &[
+            (Keys::Bound(&[Action::Reference]), "Show or hide"),
+            (
+                Keys::Bound(&[Action::AlignReference]),
+                "Align with the capture",
+            ),
+            (
+                Keys::Bound(&[
+                    Action::ShiftReferenceEarlier,
+                    Action::ShiftReferenceLater,
+                    Action::ResetReference,
+                ]),
+                "Shift, reset the offset",
+            ),
+        ],
+    ),
+    (
+        "Stepper",
+        &[
+            (
+                Keys::Bound(&[Action::NextProgram, Action::PreviousProgram]),
+                "Next and previous program",
+            ),
+            (
+                Keys::Bound(&[Action::NextProgramChannel, Action::PreviousProgramChannel]),
+                "Next and previous channel",
+            ),
+        ],
+    ),
+];
+
+/// Returns the keys of the entries of a section that have any, each with what it does
+fn entries(keymap: &Keymap, keys: &[(Keys, &'static str)]) -> Vec<(String, &'static str)> {
+    keys.iter()
+        .map(|(keys, what)| match keys {
+            Keys::Bound(actions) => {
+                let keys: Vec<String> = actions
+                    .iter()
+                    .flat_map(|action| keymap.keys(*action))
+                    .collect();
+                (keys.join(" "), *what)
+            }
+            Keys::Fixed(keys) => (keys.to_string(), *what),
+        })
+        .filter(|(keys, _)| !keys.is_empty())
+        .collect()
+}
+
+/// Lists the keys in two columns of about the same height, a section never split between
+/// them
+pub(super) fn columns(keymap: &Keymap) -> [Vec<Spans<'static>>; 2] {
+    let sections: Vec<(&str, Vec<(String, &str)>)> = SECTIONS
+        .iter()
+        .map(|(title, keys)| (*title, entries(keymap, keys)))
+        .collect();
+    let height = |sections: &[(&str, Vec<(String, &str)>)]| -> usize {
+        sections.iter().map(|(_, keys)| keys.len() + 2).sum()
+    };
+    let total = height(&sections);
+    let split = (1..sections.len())
+        .find(|i| height(&sections[..*i]) * 2 >= total)
+        .unwrap_or(sections.len());
+    let lines = |sections: &[(&'static str, Vec<(String, &'static str)>)]| {
+        let mut lines = vec![];
+        for (title, keys) in sections {
+            lines.push(Spans::from(Span::styled(
+                *title,
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            for (key, what) in keys {
+                lines.push(Spans::from(format!(
+                    "  {:<width$} {}",
+                    key,
+                    what,
+                    width = KEY_WIDTH
+                )));
+            }
+            lines.push(Spans::from(""));
+        }
+        lines
+    };
+    [lines(&sections[..split]), lines(&sections[split..])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balances_columns() {
+        let keymap = Keymap::default();
+        let [left, right] = columns(&keymap);
+        // Room is left for the filter below the shorter column on a terminal 40 rows high
+        assert!(right.len() <= left.len() && left.len() <= 34);
+        assert_eq!(left[0].0[0].content, "General");
+        assert_eq!(left[2].0[0].content, format!("  {:<11} This help", "? F10"));
+        let keys: Vec<String> = SECTIONS
+            .iter()
+            .flat_map(|(_, keys)| entries(&keymap, keys))
+            .map(|(keys, _)| keys)
+            .collect();
+        assert!(keys.iter().all(|key| key.chars().count() <= KEY_WIDTH));
+    }
+}
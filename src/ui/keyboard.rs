@@ -0,0 +1,165 @@
+//! Keyboards of all 128 notes, one per channel, lighting the keys held down so notes that
+//! were never released stand out, with the zones of the routes marked above them
+
+use crate::{analysis::channels::ChannelTracker, sink::Zone};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Width of the channel label in front of each keyboard
+const LABEL_WIDTH: usize = 5;
+/// Pitch classes of the black keys
+const BLACK_KEYS: [u8; 5] = [1, 3, 6, 8, 10];
+
+const STYLE_HELD: Style = Style {
+    fg: Some(Color::LightGreen),
+    bg: None,
+    add_modifier: Modifier::empty(),
+    sub_modifier: Modifier::empty(),
+};
+const STYLE_BLACK: Style = Style {
+    fg: Some(Color::DarkGray),
+    bg: None,
+    add_modifier: Modifier::empty(),
+    sub_modifier: Modifier::empty(),
+};
+
+/// Returns the number of keys drawn in each column of a keyboard `width` wide
+fn keys_per_column(width: usize) -> usize {
+    128usize.div_ceil(width.saturating_sub(LABEL_WIDTH).max(1))
+}
+
+/// Returns the number of lines drawn for the channels, with the zones if there are any
+pub(super) fn height(channels: usize, zones: &[Zone]) -> u16 {
+    (1 + usize::from(!zones.is_empty()) + channels.max(1)) as u16
+}
+
+/// Draws the octave ruler, the zones, and a keyboard for each of the `channels`, from 0 to
+/// 15, in a panel `width` wide. Each column shows a key when there is room for all 128,
+/// and two keys as the halves of a block otherwise
+pub(super) fn lines(
+    tracker: &ChannelTracker,
+    channels: &[u8],
+    zones: &[Zone],
+    width: usize,
+) -> Vec<Spans<'static>> {
+    let per_column = keys_per_column(width);
+    let columns = 128usize.div_ceil(per_column);
+    let mut lines = vec![Spans::from(format!(
+        "{:LABEL_WIDTH$}{}",
+        "",
+        ruler(columns, per_column)
+    ))];
+    if !zones.is_empty() {
+        lines.push(Spans::from(format!(
+            "{:<LABEL_WIDTH$}{}",
+            "Zone",
+            zone_ruler(zones, columns, per_column)
+        )));
+    }
+    if channels.is_empty() {
+        lines.push(Spans::from("No notes played yet"));
+    }
+    for &channel in channels {
+        let mut spans = vec![Span::raw(format!("Ch{:<3}", channel + 1))];
+        for column in 0..columns {
+            let first = column * per_column;
+            let keys = first as u8..(first + per_column).min(128) as u8;
+            let held: Vec<bool> = keys
+                .clone()
+                .map(|note| tracker.is_held(channel, note))
+                .collect();
+            let (glyph, style) = match held[..] {
+                [true] | [true, true] => ('█', STYLE_HELD),
+                [false] if BLACK_KEYS.contains(&(first as u8 % 12)) => ('▄', STYLE_BLACK),
+                [true, false] => ('▌', STYLE_HELD),
+                [false, true] => ('▐', STYLE_HELD),
+                _ if held.contains(&true) => ('█', STYLE_HELD),
+                _ => ('▁', Style::default()),
+            };
+            spans.push(Span::styled(glyph.to_string(), style));
+        }
+        let count = tracker.held_count(channel);
+        if count > 0 && LABEL_WIDTH + columns + 4 <= width {
+            spans.push(Span::raw(format!(" {}", count)));
+        }
+        lines.push(Spans::from(spans));
+    }
+    lines
+}
+
+/// Labels the C of each octave where it fits, C4 being middle C
+fn ruler(columns: usize, per_column: usize) -> String {
+    let mut ruler = vec![' '; columns];
+    for octave in 0..11 {
+        let column = octave * 12 / per_column;
+        let label = format!("C{}", octave as i32 - 1);
+        let room = column..column + label.len();
+        if room.end <= columns && ruler[room.clone()].iter().all(|c| *c == ' ') {
+            ruler.splice(room, label.chars());
+        }
+    }
+    ruler.into_iter().collect()
+}
+
+/// Marks the notes of each zone with the channel it goes to, later zones drawn over
+/// earlier ones
+fn zone_ruler(zones: &[Zone], columns: usize, per_column: usize) -> String {
+    let mut ruler = vec![' '; columns];
+    for zone in zones {
+        let low = zone.low as usize / per_column;
+        let high = zone.high as usize / per_column;
+        for (column, c) in ruler.iter_mut().enumerate().take(high + 1).skip(low) {
+            *c = match column {
+                _ if low == high => '│',
+                c if c == low => '├',
+                c if c == high => '┤',
+                _ => '─',
+            };
+        }
+        let label = format!("ch{}", zone.channel + 1);
+        if high > low + label.len() {
+            ruler.splice(low + 1..low + 1 + label.len(), label.chars());
+        }
+    }
+    ruler.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    fn text(line: &Spans) -> String {
+        line.0.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn lights_held_keys() {
+        let mut capture = Capture::new();
+        let mut tracker = ChannelTracker::new();
+        for byte in [0x91, 0, 100, 1, 100, 3, 100, 3, 0] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let zones = [Zone {
+            low: 0,
+            high: 11,
+            channel: 2,
+            transpose: 0,
+        }];
+        let wide = lines(&tracker, &[1], &zones, 140);
+        assert_eq!(height(1, &zones), 3);
+        assert!(text(&wide[0]).starts_with("     C-1         C0          C1"));
+        assert!(text(&wide[1]).starts_with("Zone ├ch3───────┤ "));
+        let keys = text(&wide[2]);
+        assert!(keys.starts_with("Ch2  ██▁▄▁▁▄▁▄▁▄▁▁"));
+        assert!(keys.ends_with(" 2"));
+
+        // Two keys to a column
+        let narrow = lines(&tracker, &[1], &[], 80);
+        assert!(text(&narrow[1]).starts_with("Ch2  █▁▁"));
+        assert_eq!(text(&narrow[0]).trim_end().len(), 5 + 60 + 2);
+    }
+}
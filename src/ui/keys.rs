@@ -0,0 +1,597 @@
+//! Keys of the TUI bound to what they do, from a preset changed by the `[keys]` section of
+//! the configuration
+//!
+//! Keys are written like `j`, `G`, `ctrl-d`, `alt-x`, `F5`, `esc`, or `pagedown`, and a
+//! sequence of keys pressed one after the other like `g g`
+
+use crate::{config::KeysConfig, ui::Panel};
+use anyhow::{bail, Context};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// How long the first key of a sequence waits for the second, like the `timeoutlen` of vim
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Bindings a keymap starts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyPreset {
+    #[default]
+    Default,
+    /// `j` and `k` move a row, `g g` and `G` to the top and bottom, `ctrl-u` and `ctrl-d` half
+    /// a page, and `ctrl-b` and `ctrl-f` a page. The keyboard panel moves to `K` and General
+    /// MIDI checks to `ctrl-g`
+    Vim,
+}
+
+/// Something the TUI does when a key is pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Help,
+    Filter,
+    Load,
+    Save,
+    Ports,
+    Record,
+    RecordTo,
+    SaveSysex,
+    CompareSysex,
+    NextSysex,
+    PreviousSysex,
+    /// Shows the bytes of the SysEx message at the selected row
+    ViewSysex,
+    /// Sends the SysEx message at the selected row to MIDI Out again
+    SendSysex,
+    /// Compares the SysEx message at the selected row with the one of the same number in the
+    /// reference capture
+    CompareSysexReference,
+    SourceTimestamps,
+    Strictness,
+    GeneralMidi,
+    Realtime,
+    Dim,
+    MessageView,
+    Panel(Panel),
+    /// Charts the controller of the selected row in the watch panel
+    Watch,
+    /// Keeps the state of the channels
+    Snapshot,
+    /// Compares the state of the channels with the one kept
+    DiffSnapshot,
+    /// Switches to the layout of this number
+    Layout(usize),
+    SaveLayout,
+    Panic,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    Bookmark,
+    NextBookmark,
+    PreviousBookmark,
+    NextProblem,
+    PreviousProblem,
+    NextInput,
+    PreviousInput,
+    Reference,
+    AlignReference,
+    ShiftReferenceLater,
+    ShiftReferenceEarlier,
+    ResetReference,
+    Dismiss,
+    Range,
+    CopyC,
+    CopyRust,
+    CopyRows,
+    CopyBytes,
+    NextProgram,
+    PreviousProgram,
+    NextProgramChannel,
+    PreviousProgramChannel,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Follow,
+    ToggleFollow,
+}
+
+/// Names of the actions in the configuration, but for the layouts, `layout-1` to `layout-9`
+const NAMES: [(&str, Action); 73] = [
+    ("quit", Action::Quit),
+    ("help", Action::Help),
+    ("filter", Action::Filter),
+    ("load", Action::Load),
+    ("save", Action::Save),
+    ("ports", Action::Ports),
+    ("record", Action::Record),
+    ("record-to", Action::RecordTo),
+    ("save-sysex", Action::SaveSysex),
+    ("compare-sysex", Action::CompareSysex),
+    ("next-sysex", Action::NextSysex),
+    ("previous-sysex", Action::PreviousSysex),
+    ("view-sysex", Action::ViewSysex),
+    ("send-sysex", Action::SendSysex),
+    ("compare-sysex-reference", Action::CompareSysexReference),
+    ("source-timestamps", Action::SourceTimestamps),
+    ("strictness", Action::Strictness),
+    ("general-midi", Action::GeneralMidi),
+    ("realtime", Action::Realtime),
+    ("dim", Action::Dim),
+    ("message-view", Action::MessageView),
+    ("detail", Action::Panel(Panel::Detail)),
+    ("stats", Action::Panel(Panel::Stats)),
+    ("pads", Action::Panel(Panel::Pads)),
+    ("mpe", Action::Panel(Panel::Mpe)),
+    ("send", Action::Panel(Panel::Send)),
+    ("keyboard", Action::Panel(Panel::Keyboard)),
+    ("controls", Action::Panel(Panel::Controls)),
+    ("levels", Action::Panel(Panel::Levels)),
+    ("rate", Action::Panel(Panel::Rate)),
+    ("channels", Action::Panel(Panel::Channels)),
+    ("bookmarks", Action::Panel(Panel::Bookmarks)),
+    ("hex", Action::Panel(Panel::Hex)),
+    ("sysex", Action::Panel(Panel::Sysex)),
+    ("watch", Action::Watch),
+    ("snapshot", Action::Snapshot),
+    ("diff-snapshot", Action::DiffSnapshot),
+    ("save-layout", Action::SaveLayout),
+    ("panic", Action::Panic),
+    ("search", Action::Search),
+    ("next-match", Action::NextMatch),
+    ("previous-match", Action::PreviousMatch),
+    ("bookmark", Action::Bookmark),
+    ("next-bookmark", Action::NextBookmark),
+    ("previous-bookmark", Action::PreviousBookmark),
+    ("next-problem", Action::NextProblem),
+    ("previous-problem", Action::PreviousProblem),
+    ("next-input", Action::NextInput),
+    ("previous-input", Action::PreviousInput),
+    ("reference", Action::Reference),
+    ("align-reference", Action::AlignReference),
+    ("shift-reference-later", Action::ShiftReferenceLater),
+    ("shift-reference-earlier", Action::ShiftReferenceEarlier),
+    ("reset-reference", Action::ResetReference),
+    ("dismiss", Action::Dismiss),
+    ("range", Action::Range),
+    ("copy-c", Action::CopyC),
+    ("copy-rust", Action::CopyRust),
+    ("copy-rows", Action::CopyRows),
+    ("copy-bytes", Action::CopyBytes),
+    ("next-program", Action::NextProgram),
+    ("previous-program", Action::PreviousProgram),
+    ("next-program-channel", Action::NextProgramChannel),
+    ("previous-program-channel", Action::PreviousProgramChannel),
+    ("up", Action::Up),
+    ("down", Action::Down),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("half-page-up", Action::HalfPageUp),
+    ("half-page-down", Action::HalfPageDown),
+    ("top", Action::Top),
+    ("follow", Action::Follow),
+    ("toggle-follow", Action::ToggleFollow),
+];
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some((_, action)) = NAMES.iter().find(|(n, _)| *n == name) {
+            return Ok(*action);
+        }
+        match name.strip_prefix("layout-").and_then(|n| n.parse().ok()) {
+            Some(number @ 1..=9) => Ok(Action::Layout(number)),
+            _ => bail!("`{}` is not an action of the TUI", name),
+        }
+    }
+}
+
+/// Keys of the default preset
+const DEFAULT: [(&str, Action); 77] = [
+    ("q", Action::Quit),
+    ("?", Action::Help),
+    ("F10", Action::Help),
+    ("F1", Action::Filter),
+    ("F2", Action::Load),
+    ("F3", Action::Save),
+    ("F4", Action::Ports),
+    ("r", Action::Record),
+    ("R", Action::RecordTo),
+    ("x", Action::SaveSysex),
+    ("X", Action::CompareSysex),
+    ("'", Action::NextSysex),
+    ("\"", Action::PreviousSysex),
+    ("V", Action::ViewSysex),
+    ("T", Action::SendSysex),
+    ("=", Action::CompareSysexReference),
+    ("t", Action::SourceTimestamps),
+    ("s", Action::Strictness),
+    ("g", Action::GeneralMidi),
+    ("f", Action::Realtime),
+    ("D", Action::Dim),
+    ("M", Action::MessageView),
+    ("d", Action::Panel(Panel::Detail)),
+    ("i", Action::Panel(Panel::Stats)),
+    ("p", Action::Panel(Panel::Pads)),
+    ("e", Action::Panel(Panel::Mpe)),
+    ("m", Action::Panel(Panel::Send)),
+    ("k", Action::Panel(Panel::Keyboard)),
+    ("o", Action::Panel(Panel::Controls)),
+    ("b", Action::Panel(Panel::Levels)),
+    ("u", Action::Panel(Panel::Rate)),
+    ("h", Action::Panel(Panel::Channels)),
+    ("l", Action::Panel(Panel::Bookmarks)),
+    ("H", Action::Panel(Panel::Hex)),
+    ("S", Action::Panel(Panel::Sysex)),
+    ("W", Action::Watch),
+    ("z", Action::Snapshot),
+    ("Z", Action::DiffSnapshot),
+    ("1", Action::Layout(1)),
+    ("2", Action::Layout(2)),
+    ("3", Action::Layout(3)),
+    ("4", Action::Layout(4)),
+    ("5", Action::Layout(5)),
+    ("6", Action::Layout(6)),
+    ("7", Action::Layout(7)),
+    ("8", Action::Layout(8)),
+    ("9", Action::Layout(9)),
+    ("L", Action::SaveLayout),
+    ("P", Action::Panic),
+    ("/", Action::Search),
+    ("n", Action::NextMatch),
+    ("N", Action::PreviousMatch),
+    ("B", Action::Bookmark),
+    (")", Action::NextBookmark),
+    ("(", Action::PreviousBookmark),
+    (".", Action::NextProblem),
+    (",", Action::PreviousProblem),
+    ("tab", Action::NextInput),
+    ("backtab", Action::PreviousInput),
+    ("w", Action::Reference),
+    ("a", Action::AlignReference),
+    (">", Action::ShiftReferenceLater),
+    ("<", Action::ShiftReferenceEarlier),
+    ("0", Action::ResetReference),
+    ("esc", Action::Dismiss),
+    ("v", Action::Range),
+    ("c", Action::CopyC),
+    ("C", Action::CopyRust),
+    ("y", Action::CopyRows),
+    ("Y", Action::CopyBytes),
+    ("]", Action::NextProgram),
+    ("[", Action::PreviousProgram),
+    ("}", Action::NextProgramChannel),
+    ("{", Action::PreviousProgramChannel),
+    ("up", Action::PageUp),
+    ("down", Action::PageDown),
+    ("end", Action::Follow),
+];
+
+/// Keys the vim preset changes from the default
+const VIM: [(&str, Action); 10] = [
+    ("j", Action::Down),
+    ("k", Action::Up),
+    ("K", Action::Panel(Panel::Keyboard)),
+    ("g g", Action::Top),
+    ("G", Action::Follow),
+    ("ctrl-d", Action::HalfPageDown),
+    ("ctrl-u", Action::HalfPageUp),
+    ("ctrl-g", Action::GeneralMidi),
+    ("ctrl-f", Action::PageDown),
+    ("ctrl-b", Action::PageUp),
+];
+
+/// Keys of every preset that are not in the tables, as their names cannot be parsed
+const EXTRA: [(KeyCode, Action); 2] = [
+    (KeyCode::PageDown, Action::Follow),
+    (KeyCode::ScrollLock, Action::ToggleFollow),
+];
+
+/// A key with the modifiers that tell it apart, Shift being part of the character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn plain(code: KeyCode) -> Key {
+        Key {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Key {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        // Some terminals send Shift with the capital letter or Tab, others do not
+        if !matches!(event.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers |= event.modifiers & KeyModifiers::SHIFT;
+        }
+        Key {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+/// Names of the keys that are not characters, in lowercase
+const KEY_NAMES: [(&str, KeyCode); 17] = [
+    ("esc", KeyCode::Esc),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("space", KeyCode::Char(' ')),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("scrolllock", KeyCode::ScrollLock),
+];
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A single character, even `-`, is the key itself
+        while rest.chars().count() > 1 {
+            let Some((modifier, key)) = rest.split_once('-') else {
+                break;
+            };
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => bail!("`{}` is not a modifier, use ctrl, alt, or shift", modifier),
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let lower = rest.to_lowercase();
+                match KEY_NAMES.iter().find(|(name, _)| *name == lower) {
+                    Some((_, code)) => *code,
+                    None => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                        Some(number @ 1..=12) => KeyCode::F(number),
+                        _ => bail!("`{}` is not a key", text),
+                    },
+                }
+            }
+        };
+        Ok(Key::from(KeyEvent::new(code, modifiers)))
+    }
+}
+
+impl fmt::Display for Key {
+    /// Writes the key as short as the help shows it, such as `C-d` or `PgDn`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "M-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "S-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::BackTab => write!(f, "S-Tab"),
+            KeyCode::ScrollLock => write!(f, "ScrLk"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// The keys bound to each action, and the first key of a sequence being typed
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    /// Keys pressed one after the other, most often just one, and what they do
+    bindings: Vec<(Vec<Key>, Action)>,
+    /// First key of a sequence and when it was pressed
+    pending: Option<(Key, Instant)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new(&KeysConfig::default()).expect("default keys are valid")
+    }
+}
+
+impl Keymap {
+    /// Binds the keys of the preset, then those of the configuration over them
+    pub fn new(config: &KeysConfig) -> Result<Keymap, anyhow::Error> {
+        let mut keymap = Keymap {
+            bindings: vec![],
+            pending: None,
+        };
+        let preset: &[(&str, Action)] = match config.preset {
+            KeyPreset::Default => &[],
+            KeyPreset::Vim => &VIM,
+        };
+        for (keys, action) in DEFAULT.iter().chain(preset) {
+            keymap.bind(keys, Some(*action))?;
+        }
+        for (code, action) in EXTRA {
+            keymap.bindings.push((vec![Key::plain(code)], action));
+        }
+        for (keys, action) in &config.bind {
+            let action = match action.as_str() {
+                "none" => None,
+                name => Some(
+                    name.parse()
+                        .context(format!("Invalid binding of `{}`", keys))?,
+                ),
+            };
+            keymap
+                .bind(keys, action)
+                .context(format!("Invalid binding of `{}`", keys))?;
+        }
+        Ok(keymap)
+    }
+
+    /// Binds the keys to the action, or frees them for `None`. A key that starts a sequence
+    /// no longer does anything on its own
+    fn bind(&mut self, keys: &str, action: Option<Action>) -> Result<(), anyhow::Error> {
+        let keys: Vec<Key> = keys
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        if keys.is_empty() || keys.len() > 2 {
+            bail!("Bind a key, or a sequence of two keys separated by a space");
+        }
+        self.bindings
+            .retain(|(bound, _)| *bound != keys && !(keys.len() > 1 && bound[..] == keys[..1]));
+        if let Some(action) = action {
+            self.bindings.push((keys, action));
+        }
+        Ok(())
+    }
+
+    /// Returns what a key pressed does, `None` if nothing or if it starts a sequence. Keys
+    /// pressed with modifiers that are not bound do what they do without them. A key that
+    /// does not end the sequence started, or comes too late to, does what it does on its own
+    pub fn action(&mut self, event: KeyEvent) -> Option<Action> {
+        let key = Key::from(event);
+        let find = |keys: &[Key]| {
+            self.bindings
+                .iter()
+                .find(|(bound, _)| bound[..] == *keys)
+                .map(|(_, action)| *action)
+        };
+        if let Some((first, pressed)) = self.pending.take() {
+            if pressed.elapsed() < SEQUENCE_TIMEOUT {
+                if let Some(action) = find(&[first, key]) {
+                    return Some(action);
+                }
+            }
+        }
+        if self
+            .bindings
+            .iter()
+            .any(|(bound, _)| bound.len() > 1 && bound[0] == key)
+        {
+            self.pending = Some((key, Instant::now()));
+            return None;
+        }
+        find(&[key]).or_else(|| find(&[Key::plain(key.code)]))
+    }
+
+    /// Returns the keys bound to an action as the help shows them
+    pub fn keys(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(keys, _)| {
+                let keys: Vec<String> = keys.iter().map(Key::to_string).collect();
+                keys.join(" ")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn press(keymap: &mut Keymap, key: &str) -> Option<Action> {
+        let key: Key = key.parse().unwrap();
+        keymap.action(KeyEvent::new(key.code, key.modifiers))
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!("-".parse::<Key>().unwrap(), Key::plain(KeyCode::Char('-')));
+        assert_eq!("F5".parse::<Key>().unwrap(), Key::plain(KeyCode::F(5)));
+        assert_eq!("PageDown".parse::<Key>().unwrap().to_string(), "PgDn");
+        assert_eq!("ctrl-d".parse::<Key>().unwrap().to_string(), "C-d");
+        assert!("hyper-x".parse::<Key>().is_err());
+        assert!("F13".parse::<Key>().is_err());
+        assert_eq!("layout-3".parse::<Action>().unwrap(), Action::Layout(3));
+        assert!("layout-0".parse::<Action>().is_err());
+        // Every action has a name
+        for (name, action) in NAMES {
+            assert_eq!(name.parse::<Action>().unwrap(), action);
+        }
+    }
+
+    #[test]
+    fn binds_presets_and_overrides() {
+        let mut keymap = Keymap::default();
+        assert_eq!(press(&mut keymap, "g"), Some(Action::GeneralMidi));
+        assert_eq!(
+            press(&mut keymap, "k"),
+            Some(Action::Panel(Panel::Keyboard))
+        );
+        // Unbound modifiers are ignored
+        assert_eq!(press(&mut keymap, "ctrl-q"), Some(Action::Quit));
+
+        let mut vim = Keymap::new(&KeysConfig {
+            preset: KeyPreset::Vim,
+            bind: BTreeMap::from([
+                ("ctrl-r".to_string(), "record".to_string()),
+                ("x".to_string(), "none".to_string()),
+            ]),
+        })
+        .unwrap();
+        assert_eq!(press(&mut vim, "k"), Some(Action::Up));
+        assert_eq!(press(&mut vim, "g"), None);
+        assert_eq!(press(&mut vim, "g"), Some(Action::Top));
+        assert_eq!(press(&mut vim, "g"), None);
+        assert_eq!(press(&mut vim, "j"), Some(Action::Down));
+        assert_eq!(press(&mut vim, "g"), None);
+        vim.pending = vim
+            .pending
+            .map(|(key, _)| (key, Instant::now() - SEQUENCE_TIMEOUT));
+        assert_eq!(press(&mut vim, "g"), None);
+        assert_eq!(press(&mut vim, "g"), Some(Action::Top));
+        assert_eq!(press(&mut vim, "G"), Some(Action::Follow));
+        assert_eq!(press(&mut vim, "ctrl-d"), Some(Action::HalfPageDown));
+        assert_eq!(press(&mut vim, "d"), Some(Action::Panel(Panel::Detail)));
+        assert_eq!(press(&mut vim, "ctrl-r"), Some(Action::Record));
+        assert_eq!(press(&mut vim, "x"), None);
+        assert_eq!(vim.keys(Action::GeneralMidi), ["C-g"]);
+        assert_eq!(vim.keys(Action::Follow), ["End", "G", "PgDn"]);
+
+        let invalid = |key: &str, action: &str| {
+            Keymap::new(&KeysConfig {
+                preset: KeyPreset::Default,
+                bind: BTreeMap::from([(key.to_string(), action.to_string())]),
+            })
+            .is_err()
+        };
+        assert!(invalid("x", "fly"));
+        assert!(invalid("a b c", "quit"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Panels shown beside the event table and named layouts of them
+
+use serde::{Deserialize, Serialize};
+
+/// A panel that can be shown beside the event table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Panel {
+    /// Everything known about the selected byte and its message
+    Detail,
+    /// Counts of the bytes, messages, and issues received so far
+    Stats,
+    /// Keys that send messages to MIDI Out, lit when hit
+    Pads,
+    /// Gestures of the notes of MPE controllers
+    Mpe,
+    /// A message composed field by field to send to MIDI Out
+    Send,
+    /// The keys held on each channel, across the whole width below the table
+    Keyboard,
+    /// Meters of the controllers moved most recently
+    Controls,
+    /// Meters of the Pitch Bend and pressure of each channel
+    Levels,
+    /// Messages per second and use of the MIDI bus over the last minute
+    Rate,
+    /// A cell of activity for each channel, whose keys mute and solo channels
+    Channels,
+    /// Rows marked to come back to
+    Bookmarks,
+    /// The bytes of the capture around the selected row, in hexadecimal
+    Hex,
+    /// Values of one controller over the last seconds
+    Watch,
+    /// Every SysEx message received, with its manufacturer, size, and checksum
+    Sysex,
+}
+
+impl Panel {
+    /// Returns the title drawn on the border of the panel
+    pub fn title(self) -> &'static str {
+        match self {
+            Panel::Detail => " Detail ",
+            Panel::Stats => " Statistics ",
+            Panel::Pads => " Pads ",
+            Panel::Mpe => " MPE ",
+            Panel::Send => " Send ",
+            Panel::Keyboard => " Keyboard ",
+            Panel::Controls => " Controllers ",
+            Panel::Levels => " Bend and Pressure ",
+            Panel::Rate => " Rate ",
+            Panel::Channels => " Channels ",
+            Panel::Bookmarks => " Bookmarks ",
+            Panel::Hex => " Hex ",
+            Panel::Watch => " Watch ",
+            Panel::Sysex => " SysEx ",
+        }
+    }
+}
+
+/// The panels shown, in the order they are drawn
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub panels: Vec<Panel>,
+}
+
+impl Layout {
+    /// Shows the panel if it is hidden, or hides it if it is shown
+    pub fn toggle(&mut self, panel: Panel) {
+        if let Some(i) = self.panels.iter().position(|p| *p == panel) {
+            self.panels.remove(i);
+        } else {
+            self.panels.push(panel);
+        }
+    }
+}
@@ -1,17 +1,128 @@
 mod app;
+mod cast;
+mod composer;
+mod files;
+mod help;
+mod keyboard;
+mod keys;
+mod layout;
+mod pads;
+mod panels;
+mod ports;
+mod profiles;
+mod scrollback;
+mod scrollbar;
+mod search;
+mod stepper;
+mod strip;
+mod theme;
+mod workspace;
 
+pub use cast::cast;
+pub use keys::{KeyPreset, Keymap};
+pub use layout::{Layout, Panel};
+pub use pads::Pad;
+pub use profiles::pick_profile;
+pub use scrollback::Scrollback;
+pub use theme::{Palette, Theme};
+pub use workspace::Reference;
+
+use crate::analysis::{stats::Limits, summary::Summary, Settings};
+use crate::capture::{CaptureEvent, Filter};
+use crate::config::Config;
+use crate::sink::{trigger::Arm, Sink, Zone};
+use crate::source::Source;
+use crate::state::{StateStore, UiState};
 use anyhow::Context;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use serde::{Deserialize, Serialize};
+use std::{io::Write, path::PathBuf, time::Instant};
 use tui::{backend::CrosstermBackend, Terminal};
 
+/// What the TUI does when a violation is received, besides coloring its row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Alert {
+    #[default]
+    Off,
+    /// Rings the bell of the terminal
+    Bell,
+    /// Flashes the status line
+    Flash,
+}
+
+/// Settings for a TUI session
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Path of the Standard MIDI File written when recording
+    pub smf_path: PathBuf,
+    /// Resolution of recorded Standard MIDI Files
+    pub ppq: u16,
+    /// Tempo of recorded Standard MIDI Files when no MIDI clock is received
+    pub bpm: f64,
+    /// Start recording as soon as the application starts
+    pub record: bool,
+    /// Directory that SysEx messages are saved to
+    pub sysex_dir: PathBuf,
+    /// Use the timestamps sent by network sources instead of local arrival time
+    pub source_timestamps: bool,
+    /// Settings used to analyze the capture
+    pub settings: Settings,
+    /// Events shown when the application starts
+    pub filter: Filter,
+    /// When the source is closed automatically, and when alarms are raised
+    pub limits: Limits,
+    /// Holds the capture back until its condition is met
+    pub arm: Option<Arm>,
+    /// Capture file the capture is written to once an alarm is raised
+    pub spill: PathBuf,
+    /// Most events kept in the table, unlimited if `None`
+    pub scrollback: Option<Scrollback>,
+    /// Capture file the events dropped from the scrollback are written to, if any
+    pub scrollback_spill: Option<PathBuf>,
+    /// Start of the capture timeline
+    pub start: Instant,
+    /// Events of a resumed session, shown before any received events
+    pub history: Vec<CaptureEvent>,
+    /// Keys that send messages to MIDI Out while the pads are shown
+    pub pads: Vec<Pad>,
+    /// What the keys of the TUI do
+    pub keymap: Keymap,
+    /// Styles of the TUI
+    pub palette: Palette,
+    /// Zones of the routes, marked above the keyboards
+    pub zones: Vec<Zone>,
+    /// Capture shown beside the live one for comparison
+    pub reference: Option<Reference>,
+    /// Names of the inputs, when bytes come from several and the table shows the input of
+    /// each byte
+    pub sources: Option<Vec<String>>,
+    /// State restored from the last session
+    pub state: UiState,
+    /// Where the state is saved when the application quits, if anywhere
+    pub state_store: Option<StateStore>,
+    /// User configuration, including the layouts. Layouts saved in the TUI are written to
+    /// its path
+    pub config: Config,
+}
+
 /// Primary function call to start operating the TUI
 ///
-/// Configures the terminal for TUI, runs the app, then restores the terminal and exits
-pub fn run_application() -> Result<(), anyhow::Error> {
+/// Configures the terminal for TUI, runs the app, then restores the terminal and returns
+/// the summary of the capture. Pads and stepped programs are sent to `out`
+pub fn run_application(
+    options: Options,
+    source: Option<Source>,
+    sinks: Vec<Box<dyn Sink>>,
+    out: Option<Box<dyn Write + Send>>,
+) -> Result<Summary, anyhow::Error> {
+    // Open the source before taking over the terminal so errors are readable
+    let source = source.map(Source::connect).transpose()?;
+
     // Set up terminal
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
@@ -20,7 +131,7 @@ pub fn run_application() -> Result<(), anyhow::Error> {
     let mut terminal = Terminal::new(backend).context("Unable to create TUI terminal")?;
 
     // Run the application
-    let result = app::run_app(&mut terminal);
+    let result = app::run_app(&mut terminal, options, source, sinks, out);
 
     // Restore terminal after application exits
     disable_raw_mode().context("Failed to disable raw mode")?;
@@ -0,0 +1,114 @@
+//! Keys that send MIDI messages to MIDI Out, for playing a drum module or synthesizer
+//! without a controller at hand
+
+use std::time::{Duration, Instant};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// How long a note played by a pad is held before its Note Off is sent
+const NOTE_LENGTH: Duration = Duration::from_millis(100);
+/// How long a pad stays lit after it was hit
+const LIT_TIME: Duration = Duration::from_millis(150);
+
+/// A key bound to the bytes it sends
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pad {
+    pub key: char,
+    pub label: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The pads and the notes they hold
+#[derive(Debug)]
+pub(super) struct Pads {
+    pads: Vec<Pad>,
+    /// When each pad was last hit
+    hits: Vec<Option<Instant>>,
+    /// Note Offs to send once their time has come
+    releases: Vec<(Instant, Vec<u8>)>,
+}
+
+impl Pads {
+    pub fn new(pads: Vec<Pad>) -> Pads {
+        Pads {
+            hits: vec![None; pads.len()],
+            pads,
+            releases: vec![],
+        }
+    }
+
+    /// Returns the number of the pad bound to `key`
+    pub fn find(&self, key: char) -> Option<usize> {
+        self.pads.iter().position(|pad| pad.key == key)
+    }
+
+    /// Lights up the pad and returns it, with the message to send
+    pub fn hit(&mut self, pad: usize, now: Instant) -> &Pad {
+        self.hits[pad] = Some(now);
+        // Notes are released shortly after, as terminals do not report released keys
+        if let [status @ 0x90..=0x9F, note, velocity] = self.pads[pad].bytes[..] {
+            if velocity > 0 {
+                self.releases
+                    .push((now + NOTE_LENGTH, vec![0x80 | (status & 0x0F), note, 0]));
+            }
+        }
+        &self.pads[pad]
+    }
+
+    /// Takes the Note Offs that are due
+    pub fn releases(&mut self, now: Instant) -> Vec<u8> {
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.releases)
+            .into_iter()
+            .partition(|(time, _)| *time <= now);
+        self.releases = pending;
+        due.into_iter().flat_map(|(_, bytes)| bytes).collect()
+    }
+
+    /// Lists the pads with their keys, lighting up the ones hit just now
+    pub fn lines(&self, now: Instant) -> Vec<Spans<'static>> {
+        self.pads
+            .iter()
+            .zip(&self.hits)
+            .map(|(pad, hit)| {
+                let lit = hit.is_some_and(|time| now.duration_since(time) < LIT_TIME);
+                let style = if lit {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Spans::from(Span::styled(format!(" {}  {} ", pad.key, pad.label), style))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_released() {
+        let kick = Pad {
+            key: 'z',
+            label: "Kick".to_string(),
+            bytes: vec![0x99, 36, 100],
+        };
+        let program = Pad {
+            key: '1',
+            label: "Program 5".to_string(),
+            bytes: vec![0xC0, 5],
+        };
+        let mut pads = Pads::new(vec![kick, program]);
+        assert_eq!(pads.find('z'), Some(0));
+        assert_eq!(pads.find('x'), None);
+
+        let now = Instant::now();
+        assert_eq!(pads.hit(0, now).bytes, [0x99, 36, 100]);
+        assert_eq!(pads.hit(1, now).label, "Program 5");
+        assert!(pads.releases(now).is_empty());
+        assert_eq!(pads.releases(now + NOTE_LENGTH), [0x89, 36, 0]);
+        assert!(pads.releases(now + NOTE_LENGTH).is_empty());
+    }
+}
@@ -0,0 +1,877 @@
+//! Contents of the panels shown beside the event table
+
+use crate::{
+    analysis::{
+        channels::ChannelTracker,
+        diff,
+        intervals::{self, Histogram, IntervalAnalyzer},
+        mpe::MpeTracker,
+        rate::{Rate, RateMeter},
+        smoothness::{Controller, SmoothnessAnalyzer},
+        stats::Statistics,
+        watch::Watch,
+    },
+    capture::{CaptureEvent, TimeFormat},
+    config::Config,
+    midi::{self, controls, notes::NoteNaming, sysex, MidiMessage},
+};
+use std::{collections::BTreeSet, ops::RangeInclusive, time::Duration};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Bars of the gesture charts, from lowest to highest
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Width taken by the label and value around a gesture chart
+const CHART_MARGIN: usize = 18;
+/// Width of the names of controllers in front of their meters
+const CONTROL_NAME_WIDTH: usize = 14;
+/// Width taken by the channel, number, name, and value around a controller meter
+const METER_MARGIN: usize = CONTROL_NAME_WIDTH + 12;
+/// Width taken by the label and value around a Pitch Bend or pressure meter
+const LEVEL_MARGIN: usize = 16;
+/// Width taken by the label and value around a rate sparkline
+const RATE_MARGIN: usize = 13;
+/// Width of a cell of the channel grid, with the space between cells
+const CELL_WIDTH: usize = 13;
+/// Width taken by the label and count around a bar of an interval histogram
+const HISTOGRAM_MARGIN: usize = 18;
+/// Time charted by the watch panel, ending with the latest update
+const WATCH_WINDOW: Duration = Duration::from_secs(5);
+/// Bytes on each line of the hex dump
+const DUMP_WIDTH: usize = 8;
+/// Keys that mute the channels of the channel grid, in order
+pub(super) const CHANNEL_KEYS: [char; 16] = [
+    '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g',
+];
+
+/// Describes the selected event and the message it completes or belongs to
+pub(super) fn detail(
+    event: Option<&CaptureEvent>,
+    message: Option<&CaptureEvent>,
+    config: &Config,
+) -> Vec<Spans<'static>> {
+    let Some(event) = event else {
+        return vec![Spans::from("No byte selected")];
+    };
+    let time_format = config.timestamps.unwrap_or(TimeFormat::Seconds);
+    let mut lines = vec![
+        Spans::from(format!("Time      {}", time_format.format(event.time))),
+        Spans::from(match event.notice {
+            true => "Byte      none, a notice about the source".to_string(),
+            false => format!(
+                "Byte      {:02X} ({})",
+                event.byte,
+                if event.is_status() { "status" } else { "data" }
+            ),
+        }),
+    ];
+    if let Some(channel) = event.channel {
+        lines.push(Spans::from(
+            config
+                .names
+                .channel(format!("Channel   {}", channel + 1), channel),
+        ));
+    }
+    lines.push(Spans::from(format!(
+        "Analysis  {}",
+        event.analysis.severity()
+    )));
+    lines.push(Spans::from(format!("  {}", event.analysis.text())));
+    if let Some(message) = message.and_then(|e| e.message.as_ref().map(|m| (e, m))) {
+        let (event, message) = message;
+        let hex = midi::hex(&event.raw);
+        let name = match message {
+            MidiMessage::ControlChange { control, .. } => config
+                .names
+                .control(format!("{} {}", message.name(), control), *control),
+            MidiMessage::NoteOff { channel, note, .. }
+            | MidiMessage::NoteOn { channel, note, .. }
+            | MidiMessage::PolyPressure { channel, note, .. } => {
+                config
+                    .names
+                    .note(message.name().to_string(), *channel, *note)
+            }
+            _ => message.name().to_string(),
+        };
+        lines.push(Spans::from(format!("Message   {}", name)));
+        lines.push(Spans::from(format!("  {}", hex)));
+        if let MidiMessage::SystemExclusive(data) = message {
+            if let Some(text) = sysex::text(data) {
+                lines.push(Spans::from(format!("Text      {}", text)));
+            }
+        }
+    }
+    lines
+}
+
+/// Summarizes the counts of the capture, most frequent messages first
+/// Lists the counts of the capture, the smoothness of its controllers, and histograms of
+/// the intervals between its messages fitting in `width` columns
+pub(super) fn stats(
+    stats: &Statistics,
+    smoothness: &SmoothnessAnalyzer,
+    intervals: &IntervalAnalyzer,
+    width: u16,
+) -> Vec<Spans<'static>> {
+    let mut lines = vec![
+        Spans::from(format!("Bytes       {}", stats.bytes)),
+        Spans::from(format!("Messages    {}", stats.message_count())),
+        Spans::from(format!("Warnings    {}", stats.warnings)),
+        Spans::from(format!("Violations  {}", stats.violations)),
+    ];
+    let mut messages: Vec<(&str, usize)> = stats.messages.iter().map(|(n, c)| (*n, *c)).collect();
+    messages.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    for (name, count) in messages {
+        lines.push(Spans::from(format!("  {:<22}{}", name, count)));
+    }
+    let channels: Vec<String> = stats
+        .channels
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(ch, count)| format!("{}:{}", ch + 1, count))
+        .collect();
+    if !channels.is_empty() {
+        lines.push(Spans::from(format!("Channels    {}", channels.join(" "))));
+    }
+    let reports = smoothness.reports();
+    if !reports.is_empty() {
+        lines.push(Spans::from("Smoothness"));
+    }
+    for report in reports {
+        lines.push(Spans::from(format!(
+            "  Ch {} {:<14}{}",
+            report.channel + 1,
+            report.controller.to_string(),
+            report.score
+        )));
+    }
+    lines.extend(histogram("Message intervals", &intervals.messages, width));
+    lines.extend(histogram("Clock intervals", &intervals.clocks, width));
+    lines
+}
+
+/// Draws the buckets of a histogram from the first to the last that counted any interval,
+/// as bars fitting in `width` columns. Nothing is drawn before an interval is counted
+fn histogram(title: &str, histogram: &Histogram, width: u16) -> Vec<Spans<'static>> {
+    let counts = histogram.counts();
+    let (Some(first), Some(last)) = (
+        counts.iter().position(|count| *count > 0),
+        counts.iter().rposition(|count| *count > 0),
+    ) else {
+        return vec![];
+    };
+    let width = (width as usize).saturating_sub(HISTOGRAM_MARGIN).max(1);
+    let most = counts.iter().max().copied().unwrap_or(1);
+    let mut lines = vec![Spans::from(format!("{} ({})", title, histogram.total()))];
+    for (label, count) in intervals::LABELS
+        .iter()
+        .zip(counts)
+        .take(last + 1)
+        .skip(first)
+    {
+        // Any count at all shows up
+        let filled = (count * width).div_ceil(most);
+        lines.push(Spans::from(vec![
+            Span::raw(format!("  {:<8}", label)),
+            Span::styled("█".repeat(filled), Style::default().fg(Color::LightCyan)),
+            Span::raw(format!(" {}", count)),
+        ]));
+    }
+    lines
+}
+
+/// Charts the value of the watched controller over the `WATCH_WINDOW` that ends with its
+/// latest update, in `height` lines of `width` columns with its name, value, and smoothness
+pub(super) fn watch(
+    watch: Option<&Watch>,
+    smoothness: &SmoothnessAnalyzer,
+    config: &Config,
+    width: u16,
+    height: u16,
+) -> Vec<Spans<'static>> {
+    let Some(watch) = watch else {
+        return vec![Spans::from(
+            "Press W on a Control Change or Pitch Bend to watch it",
+        )];
+    };
+    let name = match watch.controller {
+        Controller::Control(control) => {
+            let name = match config.names.controls.get(&control.to_string()) {
+                Some(name) => name.clone(),
+                None => controls::get_controller_name(control),
+            };
+            format!("CC {} {}", control, name)
+        }
+        Controller::PitchBend => "Pitch Bend".to_string(),
+    };
+    let value = match (watch.value(), watch.controller) {
+        (None, _) => "-".to_string(),
+        (Some(value), Controller::Control(_)) => format!("{:.0}", value * 127.0),
+        (Some(value), Controller::PitchBend) => format!("{:+.0}", value * 16383.0 - 8192.0),
+    };
+    let mut lines = vec![Spans::from(Span::styled(
+        format!("Ch {} {}  {}", watch.channel + 1, name, value),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    let report = smoothness
+        .reports()
+        .into_iter()
+        .find(|r| (r.channel, r.controller) == (watch.channel, watch.controller));
+    let rows = height.saturating_sub(2 + report.is_some() as u16).max(1);
+    let series = watch.series(WATCH_WINDOW, width.max(1) as usize);
+    let style = Style::default().fg(Color::LightCyan);
+    for row in area_chart(&series, rows as usize) {
+        lines.push(Spans::from(Span::styled(row, style)));
+    }
+    lines.push(Spans::from(format!(
+        "Last {} s, {} updates",
+        WATCH_WINDOW.as_secs(),
+        watch.updates()
+    )));
+    if let Some(report) = report {
+        lines.push(Spans::from(format!(
+            "Smoothness {}, {:.1} bits",
+            report.score, report.resolution
+        )));
+    }
+    lines
+}
+
+/// Draws values from 0 to 1 as columns of blocks `rows` lines high, eight steps to a line,
+/// the top line first. Columns without a value are left blank
+fn area_chart(values: &[Option<f64>], rows: usize) -> Vec<String> {
+    let levels: Vec<usize> = values
+        .iter()
+        .map(|value| (value.unwrap_or(0.0).clamp(0.0, 1.0) * (rows * 8) as f64).round() as usize)
+        .collect();
+    (0..rows)
+        .rev()
+        .map(|row| {
+            levels
+                .iter()
+                .map(|level| match level.saturating_sub(row * 8).min(8) {
+                    0 => ' ',
+                    fill => BARS[fill - 1],
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Charts the Pitch Bend, Channel Pressure, and CC74 of the most recent MPE notes,
+/// three lines each, their latest values last. Charts fit in `width` columns
+pub(super) fn mpe(tracker: &MpeTracker, naming: NoteNaming, width: u16) -> Vec<Spans<'static>> {
+    if tracker.notes().is_empty() {
+        return vec![Spans::from("No notes received")];
+    }
+    let width = (width as usize).saturating_sub(CHART_MARGIN).max(1);
+    let mut lines = vec![];
+    for note in tracker.notes() {
+        let header = format!(
+            "Ch {} {} vel {}{}",
+            note.channel + 1,
+            naming.name(note.note),
+            note.velocity,
+            if note.released { ", released" } else { "" }
+        );
+        let style = if note.released {
+            Style::default().add_modifier(Modifier::DIM)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        lines.push(Spans::from(Span::styled(header, style)));
+        let bend = note.bend.iter().map(|v| *v as f64 / 16383.0);
+        lines.push(Spans::from(format!(
+            "  Bend  {:<width$} {:+.2} st",
+            chart(bend, width),
+            note.semitones(),
+            width = width
+        )));
+        let pressure = note.pressure.iter().map(|v| *v as f64 / 127.0);
+        lines.push(Spans::from(format!(
+            "  Press {:<width$} {}",
+            chart(pressure, width),
+            note.pressure.last().unwrap_or(&0),
+            width = width
+        )));
+        let timbre = note.timbre.iter().map(|v| *v as f64 / 127.0);
+        lines.push(Spans::from(format!(
+            "  CC74  {:<width$} {}",
+            chart(timbre, width),
+            note.timbre.last().unwrap_or(&0),
+            width = width
+        )));
+    }
+    lines
+}
+
+/// Draws a meter of the value of each recently moved controller of the channels not hidden
+/// by `hidden_channels`, the latest first. Controllers are named from the `controls` of the
+/// configuration, or by the MIDI specification. Meters fit in `width` columns
+pub(super) fn controls(
+    tracker: &ChannelTracker,
+    config: &Config,
+    hidden_channels: u16,
+    width: u16,
+) -> Vec<Spans<'static>> {
+    let width = (width as usize).saturating_sub(METER_MARGIN).max(1);
+    let lines: Vec<Spans> = tracker
+        .controls()
+        .iter()
+        .filter(|c| hidden_channels & (1 << c.channel) == 0)
+        .map(|c| {
+            let name = match config.names.controls.get(&c.control.to_string()) {
+                Some(name) => name.clone(),
+                None => controls::get_controller_name(c.control),
+            };
+            let name: String = name.chars().take(CONTROL_NAME_WIDTH).collect();
+            let filled = (c.value as usize * width + 63) / 127;
+            Spans::from(vec![
+                Span::raw(format!(
+                    "{:>2} {:>3} {:<CONTROL_NAME_WIDTH$} ",
+                    c.channel + 1,
+                    c.control,
+                    name
+                )),
+                Span::styled("█".repeat(filled), Style::default().fg(Color::LightCyan)),
+                Span::raw(format!("{} {:>3}", "░".repeat(width - filled), c.value)),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        return vec![Spans::from("No Control Changes received")];
+    }
+    lines
+}
+
+/// Draws the Pitch Bend, Channel Pressure, and Poly Pressure of the channels not hidden by
+/// `hidden_channels` that received any, Pitch Bend as a meter from its center. Meters fit
+/// in `width` columns
+pub(super) fn levels(
+    tracker: &ChannelTracker,
+    naming: NoteNaming,
+    hidden_channels: u16,
+    width: u16,
+) -> Vec<Spans<'static>> {
+    let width = (width as usize).saturating_sub(LEVEL_MARGIN).max(2);
+    let style = Style::default().fg(Color::LightCyan);
+    let meter = |value: u8| {
+        let filled = (value as usize * width + 63) / 127;
+        vec![
+            Span::styled("█".repeat(filled), style),
+            Span::raw("░".repeat(width - filled)),
+        ]
+    };
+    let mut lines = vec![];
+    for channel in (0..16).filter(|ch| hidden_channels & (1 << ch) == 0) {
+        let bend = tracker.bend(channel);
+        let pressure = tracker.pressure(channel);
+        let poly = tracker.poly_pressure(channel);
+        if bend.is_none() && pressure.is_none() && poly.is_none() {
+            continue;
+        }
+        lines.push(Spans::from(Span::styled(
+            format!("Ch {}", channel + 1),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        if let Some(bend) = bend {
+            let mut spans = vec![Span::raw("  Bend  ")];
+            spans.extend(bend_meter(bend, width, style));
+            spans.push(Span::raw(format!(" {:+}", bend as i32 - 8192)));
+            lines.push(Spans::from(spans));
+        }
+        if let Some(pressure) = pressure {
+            let mut spans = vec![Span::raw("  Press ")];
+            spans.extend(meter(pressure));
+            spans.push(Span::raw(format!(" {}", pressure)));
+            lines.push(Spans::from(spans));
+        }
+        if let Some((note, pressure)) = poly {
+            let mut spans = vec![Span::raw("  Poly  ")];
+            spans.extend(meter(pressure));
+            spans.push(Span::raw(format!(" {} {}", pressure, naming.name(note))));
+            lines.push(Spans::from(spans));
+        }
+    }
+    if lines.is_empty() {
+        return vec![Spans::from("No Pitch Bend or pressure received")];
+    }
+    lines
+}
+
+/// Draws a Pitch Bend as a bar from the center of a meter up to `width` wide, toward the
+/// left when bent down and the right when bent up
+fn bend_meter(bend: u16, width: usize, style: Style) -> Vec<Span<'static>> {
+    let half = (width - 1) / 2;
+    let offset = (bend as f64 - 8192.0) / 8192.0;
+    let filled = ((offset.abs() * half as f64).round() as usize).min(half);
+    let (left, right) = if offset < 0.0 {
+        (filled, 0)
+    } else {
+        (0, filled)
+    };
+    vec![
+        Span::raw("─".repeat(half - left)),
+        Span::styled("█".repeat(left), style),
+        Span::raw("┼"),
+        Span::styled("█".repeat(right), style),
+        Span::raw("─".repeat(half - right)),
+    ]
+}
+
+/// Draws sparklines of the messages per second and of the share of the MIDI bus taken
+/// over the last minute before `now`, with a warning while the bus is close to saturation.
+/// Sparklines fit in `width` columns
+pub(super) fn rate(meter: &RateMeter, now: Duration, width: u16) -> Vec<Spans<'static>> {
+    let rates = meter.rates(now);
+    let width = (width as usize).saturating_sub(RATE_MARGIN).max(1);
+    let peak = rates.iter().map(|r| r.messages).max().unwrap_or(0);
+    let messages = rates.iter().map(|r| r.messages as f64 / peak.max(1) as f64);
+    let utilization = rates.iter().map(|r| r.utilization);
+    let last = rates.last().copied().unwrap_or(Rate {
+        messages: 0,
+        utilization: 0.0,
+    });
+    let busiest = rates.iter().map(|r| r.utilization).fold(0.0, f64::max);
+    let mut lines = vec![
+        Spans::from(format!(
+            "Msg/s {:<width$} {}",
+            chart(messages, width),
+            last.messages,
+            width = width
+        )),
+        Spans::from(format!(
+            "Bus   {:<width$} {:.0}%",
+            chart(utilization, width),
+            last.utilization * 100.0,
+            width = width
+        )),
+        Spans::from(format!(
+            "Peak  {} msg/s, {:.0}% of 31.25 kbaud",
+            peak,
+            busiest * 100.0
+        )),
+    ];
+    if let Some(lowest) = meter.saturation(now) {
+        lines.push(Spans::from(Span::styled(
+            format!("Bus above {:.0}%, timing suffers", lowest * 100.0),
+            Style::default().fg(Color::LightRed),
+        )));
+    }
+    lines
+}
+
+/// Lists the bookmarks with the time and analysis of their events, the one at `selected`
+/// marked
+pub(super) fn bookmarks(
+    events: &[CaptureEvent],
+    bookmarks: &BTreeSet<usize>,
+    selected: Option<usize>,
+    time_format: TimeFormat,
+) -> Vec<Spans<'static>> {
+    if bookmarks.is_empty() {
+        return vec![Spans::from("No bookmarks, `B` marks the selected row")];
+    }
+    let mut lines: Vec<Spans> = bookmarks
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let event = &events[*position];
+            let mark = if selected == Some(*position) {
+                '>'
+            } else {
+                ' '
+            };
+            Spans::from(format!(
+                "{}{:>2} {} {}",
+                mark,
+                i + 1,
+                time_format.format(event.time),
+                event.analysis.text()
+            ))
+        })
+        .collect();
+    lines.push(Spans::from("( ) previous and next"));
+    lines
+}
+
+/// Lists the SysEx messages completed at `positions` with their time, manufacturer, size,
+/// and checksum, `height` lines around the one at or after `selected`, which is marked
+pub(super) fn sysex_list(
+    events: &[CaptureEvent],
+    positions: &[usize],
+    selected: Option<usize>,
+    time_format: TimeFormat,
+    width: u16,
+    height: u16,
+) -> Vec<Spans<'static>> {
+    if positions.is_empty() {
+        return vec![Spans::from("No SysEx messages received")];
+    }
+    let marked = selected
+        .map(|selected| positions.partition_point(|p| *p < selected))
+        .filter(|i| *i < positions.len());
+    // Room for the count and the keys
+    let rows = (height as usize).saturating_sub(2).max(1);
+    let first = match marked {
+        Some(i) => i.saturating_sub(rows / 2),
+        None => positions.len(),
+    }
+    .min(positions.len().saturating_sub(rows));
+    let mut lines = vec![Spans::from(format!("{} SysEx messages", positions.len()))];
+    for (i, position) in positions.iter().enumerate().skip(first).take(rows) {
+        let event = &events[*position];
+        let Some(MidiMessage::SystemExclusive(data)) = &event.message else {
+            continue;
+        };
+        let time = time_format.format(event.time);
+        let size = format!("{} B", data.len() + 2);
+        let name = sysex::manufacturer(data).map_or("Unknown", |id| id.manufacturer.as_str());
+        // The mark, number, time, size, checksum, and the spaces between them
+        let room = (width as usize).saturating_sub(time.chars().count() + 18);
+        let (check, style) = match diff::checksum(data) {
+            Some(true) => ("ok", Style::default()),
+            Some(false) => ("bad", Style::default().fg(Color::LightRed)),
+            None => ("", Style::default()),
+        };
+        let mark = if marked == Some(i) { '>' } else { ' ' };
+        lines.push(Spans::from(vec![
+            Span::raw(format!(
+                "{}{:>3} {} {:<room$} {:>7} ",
+                mark,
+                i + 1,
+                time,
+                name.chars().take(room).collect::<String>(),
+                size,
+                room = room
+            )),
+            Span::styled(check, style),
+        ]));
+    }
+    lines.push(Spans::from("' \" next and previous"));
+    lines
+}
+
+/// Dumps the bytes of the capture in hexadecimal, `height` lines around the line of
+/// `selected`, with the bytes of the message at `message` highlighted. Offsets count from
+/// the first byte received, `dropped` bytes before the first of `events`
+pub(super) fn hex_dump(
+    events: &[CaptureEvent],
+    dropped: usize,
+    selected: Option<usize>,
+    message: Option<RangeInclusive<usize>>,
+    height: usize,
+) -> Vec<Spans<'static>> {
+    if events.is_empty() {
+        return vec![Spans::from("No bytes received")];
+    }
+    let lines = events.len().div_ceil(DUMP_WIDTH);
+    let first = match selected {
+        Some(position) => (position / DUMP_WIDTH).saturating_sub(height / 2),
+        None => lines,
+    }
+    .min(lines.saturating_sub(height));
+    (first..(first + height).min(lines))
+        .map(|line| {
+            let start = line * DUMP_WIDTH;
+            let mut spans = vec![Span::raw(format!("{:06X} ", dropped + start))];
+            for (position, event) in events.iter().enumerate().skip(start).take(DUMP_WIDTH) {
+                // Real Time bytes interleaved with the message are not part of it
+                let style = match &message {
+                    Some(range) if range.contains(&position) && event.byte < 0xF8 => {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    }
+                    _ if selected == Some(position) => {
+                        Style::default().add_modifier(Modifier::UNDERLINED)
+                    }
+                    _ => Style::default(),
+                };
+                spans.push(Span::raw(" "));
+                let byte = match event.notice {
+                    true => "--".to_string(),
+                    false => format!("{:02X}", event.byte),
+                };
+                spans.push(Span::styled(byte, style));
+            }
+            Spans::from(spans)
+        })
+        .collect()
+}
+
+/// Draws a cell for each of the 16 channels with the notes started on it, how long ago it
+/// was last heard from before `now`, and its program and bank. Channels heard from in the
+/// last second are lit and those hidden by the filter are grayed. Cells are laid out in
+/// four, two, or one column, whichever fits in `width`
+pub(super) fn channel_grid(
+    tracker: &ChannelTracker,
+    hidden_channels: u16,
+    now: Duration,
+    width: u16,
+) -> Vec<Spans<'static>> {
+    let columns = [4, 2, 1]
+        .into_iter()
+        .find(|columns| columns * CELL_WIDTH <= width as usize + 1)
+        .unwrap_or(1);
+    let mut lines = vec![];
+    for row in 0..16 / columns {
+        let mut cells: [Vec<Span>; 3] = Default::default();
+        for channel in (row * columns..(row + 1) * columns).map(|ch| ch as u8) {
+            let activity = tracker.activity(channel);
+            let age = activity.last.map(|last| now.saturating_sub(last));
+            let style = match age {
+                _ if hidden_channels & (1 << channel) != 0 => Style::default().fg(Color::DarkGray),
+                Some(age) if age < Duration::from_secs(1) => Style::default()
+                    .fg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+                None => Style::default().fg(Color::DarkGray),
+                Some(_) => Style::default(),
+            };
+            let header = format!(
+                "{} Ch{:<2} {:>5}",
+                CHANNEL_KEYS[channel as usize],
+                channel + 1,
+                age.map_or("-".to_string(), format_age)
+            );
+            let notes = match activity.notes {
+                _ if hidden_channels & (1 << channel) != 0 => "muted".to_string(),
+                1 => "1 note".to_string(),
+                notes => format!("{} notes", notes),
+            };
+            let sound = match (activity.program, activity.bank) {
+                (Some(program), Some(bank)) => format!("P{} B{}", program, bank),
+                (Some(program), None) => format!("P{}", program),
+                (None, Some(bank)) => format!("B{}", bank),
+                (None, None) => String::new(),
+            };
+            for (cell, text) in cells.iter_mut().zip([header, notes, sound]) {
+                cell.push(Span::styled(
+                    format!("{:<w$}", text, w = CELL_WIDTH - 1),
+                    style,
+                ));
+                cell.push(Span::raw(" "));
+            }
+        }
+        lines.extend(cells.map(Spans::from));
+    }
+    lines.push(Spans::from("1-9 a-g mute, Alt solo"));
+    lines
+}
+
+/// Writes how long ago something happened in a few characters
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        0..=9 => format!("{:.1}s", age.as_secs_f64()),
+        seconds @ 10..=59 => format!("{}s", seconds),
+        seconds @ 60..=3599 => format!("{}m", seconds / 60),
+        seconds => format!("{}h", seconds / 3600),
+    }
+}
+
+/// Draws the last `width` values from 0 to 1 as bars
+fn chart(values: impl ExactSizeIterator<Item = f64>, width: usize) -> String {
+    let skip = values.len().saturating_sub(width);
+    values
+        .skip(skip)
+        .map(|value| BARS[(value.clamp(0.0, 1.0) * 7.0).round() as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charts() {
+        let values = [0.0, 0.5, 1.0, 2.0];
+        assert_eq!(chart(values.into_iter(), 10), "▁▅██");
+        assert_eq!(chart(values.into_iter(), 2), "██");
+    }
+
+    #[test]
+    fn lists_sysex() {
+        let mut capture = crate::capture::Capture::new();
+        let mut events = vec![];
+        let bytes = [
+            0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7, 0x90, 60, 100, 0xF0,
+            0x7E, 0x7F, 0x06, 0x01, 0xF7,
+        ];
+        for (i, byte) in bytes.into_iter().enumerate() {
+            events.push(capture.process(Duration::from_millis(i as u64), byte));
+        }
+        let lines = sysex_list(&events, &[10, 19], Some(12), TimeFormat::Seconds, 40, 10);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].0[0].content, "2 SysEx messages");
+        let line = |i: usize| -> String { lines[i].0.iter().map(|s| s.content.as_ref()).collect() };
+        assert!(line(1).starts_with("   1 "), "{}", line(1));
+        assert!(line(1).contains("Roland") && line(1).ends_with("11 B ok"));
+        assert!(line(2).starts_with(">  2 ") && line(2).ends_with(" 6 B "));
+        assert_eq!(line(1).chars().count(), 39);
+    }
+
+    #[test]
+    fn area_charts() {
+        let values = [None, Some(0.0), Some(0.25), Some(0.5), Some(1.0)];
+        assert_eq!(area_chart(&values, 2), ["    █", "  ▄██"]);
+        let mut capture = crate::capture::Capture::new();
+        let mut watched = Watch::new(0, Controller::Control(1));
+        for (millis, value) in [(0, 0), (1_000, 64), (2_000, 127)] {
+            for byte in [0xB0, 0x01, value] {
+                watched.observe(&capture.process(Duration::from_millis(millis), byte));
+            }
+        }
+        let lines = watch(
+            Some(&watched),
+            &SmoothnessAnalyzer::new(),
+            &Config::default(),
+            10,
+            5,
+        );
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0].0[0].content, "Ch 1 CC 1 Mod wheel  127");
+        assert_eq!(lines[1].0[0].content, "         █");
+        assert_eq!(lines[2].0[0].content, "       ▄▄█");
+        assert_eq!(lines[4].0[0].content, "Last 5 s, 3 updates");
+    }
+
+    #[test]
+    fn interval_histograms() {
+        let mut histogram = Histogram::default();
+        assert!(super::histogram("Clock intervals", &histogram, 40).is_empty());
+        for millis in [4, 4, 4, 4, 50] {
+            histogram.add(Duration::from_millis(millis));
+        }
+        let lines = super::histogram("Clock intervals", &histogram, 40);
+        // From ~5 ms to ~50 ms, the longest bar filling the width
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0].0[0].content, "Clock intervals (5)");
+        assert_eq!(lines[1].0[0].content, "  ~5 ms   ");
+        assert_eq!(lines[1].0[1].content.chars().count(), 22);
+        assert_eq!(lines[2].0[1].content, "");
+        assert_eq!(lines[4].0[1].content.chars().count(), 6);
+        assert_eq!(lines[4].0[2].content, " 1");
+    }
+
+    #[test]
+    fn dumps_bytes() {
+        let mut capture = crate::capture::Capture::new();
+        let events: Vec<CaptureEvent> = (0..20)
+            .map(|byte| capture.process(Duration::ZERO, byte))
+            .collect();
+        let text =
+            |line: &Spans| -> String { line.0.iter().map(|span| span.content.as_ref()).collect() };
+        let lines = hex_dump(&events, 0x100, Some(9), Some(8..=9), 2);
+        assert_eq!(text(&lines[0]), "000100  00 01 02 03 04 05 06 07");
+        assert_eq!(text(&lines[1]), "000108  08 09 0A 0B 0C 0D 0E 0F");
+        assert!(lines[1].0[2]
+            .style
+            .add_modifier
+            .contains(Modifier::REVERSED));
+        assert!(!lines[1].0[6]
+            .style
+            .add_modifier
+            .contains(Modifier::REVERSED));
+        // Following the end of the capture
+        let lines = hex_dump(&events, 0, None, None, 2);
+        assert_eq!(text(&lines[1]), "000010  10 11 12 13");
+    }
+
+    #[test]
+    fn controller_meters() {
+        let mut capture = crate::capture::Capture::new();
+        let mut tracker = ChannelTracker::new();
+        for byte in [0xB0, 11, 127, 0xB9, 2, 0] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let mut config = Config::default();
+        config
+            .names
+            .controls
+            .insert("11".to_string(), "Pedal".to_string());
+        let text =
+            |line: &Spans| -> String { line.0.iter().map(|span| span.content.as_ref()).collect() };
+        let lines = controls(&tracker, &config, 0, METER_MARGIN as u16 + 4);
+        assert_eq!(text(&lines[0]), "10   2 Breath control ░░░░   0");
+        assert_eq!(text(&lines[1]), " 1  11 Pedal          ████ 127");
+        let hidden = controls(&tracker, &config, 0x201, 40);
+        assert_eq!(text(&hidden[0]), "No Control Changes received");
+    }
+
+    #[test]
+    fn bend_and_pressure_meters() {
+        let mut capture = crate::capture::Capture::new();
+        let mut tracker = ChannelTracker::new();
+        for byte in [0xE1, 0, 0, 0xD1, 127, 0xE3, 0, 0x60] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        let text =
+            |line: &Spans| -> String { line.0.iter().map(|span| span.content.as_ref()).collect() };
+        let lines: Vec<String> = levels(&tracker, NoteNaming::English, 0, 25)
+            .iter()
+            .map(text)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "Ch 2",
+                "  Bend  ████┼──── -8192",
+                "  Press █████████ 127",
+                "Ch 4",
+                "  Bend  ────┼██── +4096",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_bookmarks() {
+        let mut capture = crate::capture::Capture::new();
+        let events: Vec<CaptureEvent> = [0xF8, 0xFA, 0xFC]
+            .into_iter()
+            .enumerate()
+            .map(|(i, byte)| capture.process(Duration::from_millis(i as u64 * 500), byte))
+            .collect();
+        let marked = BTreeSet::from([0, 2]);
+        let lines = bookmarks(&events, &marked, Some(2), TimeFormat::Milliseconds);
+        let text: Vec<String> = lines
+            .iter()
+            .map(|line| line.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect();
+        assert_eq!(text.len(), 3);
+        assert!(text[0].starts_with("  1 0"));
+        assert!(text[1].starts_with("> 2 1000.000 ms"));
+    }
+
+    #[test]
+    fn channel_grid_cells() {
+        let mut capture = crate::capture::Capture::new();
+        let mut tracker = ChannelTracker::new();
+        for byte in [0x92, 60, 100, 0xC2, 12] {
+            tracker.observe(&capture.process(Duration::ZERO, byte));
+        }
+        for byte in [0x9A, 36, 100] {
+            tracker.observe(&capture.process(Duration::from_secs(90), byte));
+        }
+        let text =
+            |line: &Spans| -> String { line.0.iter().map(|span| span.content.as_ref()).collect() };
+        let lines = channel_grid(&tracker, 1 << 1, Duration::from_secs(95), 52);
+        assert_eq!(lines.len(), 4 * 3 + 1);
+        assert_eq!(
+            text(&lines[0]),
+            "1 Ch1      - 2 Ch2      - 3 Ch3     1m 4 Ch4      - "
+        );
+        assert_eq!(
+            text(&lines[1]).split_whitespace().collect::<Vec<_>>(),
+            ["0", "notes", "muted", "1", "note", "0", "notes"]
+        );
+        assert!(text(&lines[2]).contains("P12"));
+        assert!(text(&lines[6]).contains("b Ch11  5.0s"));
+
+        // Two columns when four do not fit
+        assert_eq!(
+            channel_grid(&tracker, 0, Duration::ZERO, 40).len(),
+            8 * 3 + 1
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Picks the serial port or raw MIDI device the capture is read from, so a wrong port can be
+//! swapped for the right one without leaving the TUI
+
+use crate::source::{port, Source};
+use crossterm::event::KeyCode;
+use std::ffi::OsString;
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Ports listed at once
+const LISTED: usize = 10;
+
+/// A serial port or raw MIDI device of this machine
+#[derive(Debug, Clone)]
+struct Port {
+    path: String,
+    /// How the port is read, raw MIDI devices like files
+    source: Source,
+    /// Name the next session opens, the USB identity of serial ports that have one
+    remembered: Option<OsString>,
+    description: String,
+}
+
+/// What was picked in the dialog
+#[derive(Debug)]
+pub(super) enum Choice {
+    /// Read from the source, remembering the port for the next session if there is one
+    Connect(Source, Option<OsString>),
+    Disconnect,
+}
+
+/// The ports of this machine and the one under the cursor
+#[derive(Debug)]
+pub(super) struct PortDialog {
+    /// Baud rate serial ports are opened at
+    baud: u32,
+    ports: Vec<Port>,
+    cursor: usize,
+    /// Why the serial ports could not be listed
+    error: Option<String>,
+}
+
+impl PortDialog {
+    /// Opens the dialog on the ports plugged in now, serial ports to be opened at `baud`
+    pub fn new(baud: u32) -> PortDialog {
+        let mut dialog = PortDialog {
+            baud,
+            ports: vec![],
+            cursor: 0,
+            error: None,
+        };
+        dialog.list();
+        dialog
+    }
+
+    /// Lists the serial ports, then the raw MIDI devices
+    fn list(&mut self) {
+        self.ports.clear();
+        self.cursor = 0;
+        let baud = self.baud;
+        match serialport::available_ports() {
+            Ok(ports) => {
+                self.error = None;
+                self.ports.extend(ports.iter().map(|info| {
+                    let (description, midi) = port::describe(info);
+                    let midi = if midi { "  [MIDI]" } else { "" };
+                    Port {
+                        path: info.port_name.clone(),
+                        source: Source::Serial {
+                            port: info.port_name.clone().into(),
+                            baud,
+                        },
+                        remembered: Some(
+                            port::identity(info)
+                                .unwrap_or_else(|| info.port_name.clone())
+                                .into(),
+                        ),
+                        description: format!("{}{}", description, midi),
+                    }
+                }));
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.ports
+            .extend(port::midi_devices().into_iter().map(|(path, name)| Port {
+                source: Source::File(path.clone().into()),
+                path,
+                remembered: None,
+                description: format!("{}  [MIDI]", name),
+            }));
+    }
+
+    /// Handles a key: Up and Down pick a port, Enter connects to it, `d` disconnects, and `r`
+    /// lists the ports again after one was plugged in
+    pub fn key(&mut self, code: KeyCode) -> Option<Choice> {
+        let count = self.ports.len();
+        match code {
+            KeyCode::Up if count > 0 => {
+                self.cursor = self.cursor.checked_sub(1).unwrap_or(count - 1)
+            }
+            KeyCode::Down if count > 0 => self.cursor = (self.cursor + 1) % count,
+            KeyCode::Char('r') => self.list(),
+            KeyCode::Char('d') | KeyCode::Delete => return Some(Choice::Disconnect),
+            KeyCode::Enter => {
+                let port = self.ports.get(self.cursor)?;
+                return Some(Choice::Connect(
+                    port.source.clone(),
+                    port.remembered.clone(),
+                ));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Lists the ports around the one picked, marking the one named `connected`
+    pub fn lines(&self, connected: Option<&str>) -> Vec<Spans<'static>> {
+        let mut lines = vec![Spans::from(match connected {
+            Some(name) => format!("Connected to {}", name),
+            None => "Disconnected".to_string(),
+        })];
+        lines.push(Spans::from(""));
+        if let Some(error) = &self.error {
+            lines.push(Spans::from(format!(
+                "Unable to list serial ports: {}",
+                error
+            )));
+        }
+        if self.ports.is_empty() {
+            lines.push(Spans::from("  No ports found"));
+        }
+        let first = self.cursor.saturating_sub(LISTED - 1);
+        for (i, port) in self.ports.iter().enumerate().skip(first).take(LISTED) {
+            let style = match self.cursor == i {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default(),
+            };
+            let mark = match connected == Some(port.source.name().as_str()) {
+                true => '*',
+                false => ' ',
+            };
+            lines.push(Spans::from(Span::styled(
+                format!("{} {:<20} {}", mark, port.path, port.description)
+                    .trim_end()
+                    .to_string(),
+                style,
+            )));
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from("↑↓ pick, Enter connect, d disconnect"));
+        lines.push(Spans::from("r list again, Esc close"));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_ports() {
+        let mut dialog = PortDialog {
+            baud: 38400,
+            ports: vec![],
+            cursor: 0,
+            error: None,
+        };
+        assert!(dialog.key(KeyCode::Enter).is_none());
+        assert!(matches!(
+            dialog.key(KeyCode::Char('d')),
+            Some(Choice::Disconnect)
+        ));
+
+        dialog.ports = vec![
+            Port {
+                path: "/dev/ttyUSB0".to_string(),
+                source: Source::Serial {
+                    port: "/dev/ttyUSB0".into(),
+                    baud: 38400,
+                },
+                remembered: Some("usb:0582:012A".into()),
+                description: "USB 0582:012A Roland UM-ONE  [MIDI]".to_string(),
+            },
+            Port {
+                path: "/dev/snd/midiC1D0".to_string(),
+                source: Source::File("/dev/snd/midiC1D0".into()),
+                remembered: None,
+                description: "UM-ONE MIDI 1  [MIDI]".to_string(),
+            },
+        ];
+        match dialog.key(KeyCode::Enter) {
+            Some(Choice::Connect(Source::Serial { port, baud }, remembered)) => {
+                assert_eq!((port.to_str(), baud), (Some("/dev/ttyUSB0"), 38400));
+                assert_eq!(remembered, Some("usb:0582:012A".into()));
+            }
+            choice => panic!("{:?}", choice),
+        }
+        dialog.key(KeyCode::Up);
+        assert!(matches!(
+            dialog.key(KeyCode::Enter),
+            Some(Choice::Connect(Source::File(_), None))
+        ));
+        let lines = dialog.lines(Some("ttyUSB0"));
+        assert!(lines[2].0[0].content.starts_with("* /dev/ttyUSB0"));
+    }
+}
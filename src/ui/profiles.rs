@@ -0,0 +1,169 @@
+//! Picks the profile of the configuration the TUI starts with, when the file has profiles
+//! and none was given with `--profile`
+
+use crate::config::Config;
+use crate::ui::app;
+use anyhow::Context;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use tui::{
+    backend::CrosstermBackend,
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    Terminal,
+};
+
+/// What was picked on the start screen
+#[derive(Debug, PartialEq, Eq)]
+enum Choice {
+    /// Start with the profile, or with the settings of the rest of the file if none
+    Start(Option<String>),
+    Quit,
+}
+
+/// The profiles of the configuration and the one under the cursor
+#[derive(Debug)]
+struct ProfilePicker {
+    /// Name of each profile, none for the rest of the file, and what it uses
+    profiles: Vec<(Option<String>, String)>,
+    cursor: usize,
+}
+
+impl ProfilePicker {
+    /// Lists the profiles of `config` after the settings of the rest of the file
+    fn new(config: &Config) -> ProfilePicker {
+        let mut profiles = vec![(None, "Settings of the file".to_string())];
+        profiles.extend(config.profiles.iter().map(|(name, profile)| {
+            let mut uses = vec![];
+            if let Some(port) = &profile.port {
+                uses.push(port.to_string_lossy().into_owned());
+            }
+            if !profile.devices.is_empty() {
+                uses.push(profile.devices.join(" + "));
+            }
+            (Some(name.clone()), uses.join(", "))
+        }));
+        ProfilePicker {
+            profiles,
+            cursor: 0,
+        }
+    }
+
+    /// Handles a key: Up and Down pick a profile, Enter starts with it, and Esc or `q` quits
+    fn key(&mut self, code: KeyCode) -> Option<Choice> {
+        let count = self.profiles.len();
+        match code {
+            KeyCode::Up => self.cursor = self.cursor.checked_sub(1).unwrap_or(count - 1),
+            KeyCode::Down => self.cursor = (self.cursor + 1) % count,
+            KeyCode::Enter => return Some(Choice::Start(self.profiles[self.cursor].0.clone())),
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Choice::Quit),
+            _ => {}
+        }
+        None
+    }
+
+    /// Lists the profiles, highlighting the one picked
+    fn lines(&self) -> Vec<Spans<'static>> {
+        let mut lines = vec![Spans::from("Settings to start with"), Spans::from("")];
+        for (i, (name, uses)) in self.profiles.iter().enumerate() {
+            let style = match self.cursor == i {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default(),
+            };
+            let name = name.as_deref().unwrap_or("(none)");
+            lines.push(Spans::from(Span::styled(
+                format!("  {:<20} {}", name, uses).trim_end().to_string(),
+                style,
+            )));
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from("↑↓ pick, Enter start, Esc quit"));
+        lines
+    }
+}
+
+/// Shows the profiles of `config` on a start screen and returns the configuration with the
+/// one picked, or none if the user quit
+pub fn pick_profile(config: &Config) -> Result<Option<Config>, anyhow::Error> {
+    let mut picker = ProfilePicker::new(config);
+
+    enable_raw_mode()?;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal =
+        Terminal::new(CrosstermBackend::new(stdout)).context("Unable to create TUI terminal")?;
+    let choice = loop {
+        if let Err(e) = terminal.draw(|f| app::dialog_popup(f, "Profile", picker.lines())) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Release => {}
+            Ok(Event::Key(key))
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && key.code == KeyCode::Char('c') =>
+            {
+                break Ok(Choice::Quit)
+            }
+            Ok(Event::Key(key)) => {
+                if let Some(choice) = picker.key(key.code) {
+                    break Ok(choice);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+
+    disable_raw_mode().context("Failed to disable raw mode")?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal
+        .show_cursor()
+        .context("Failed to restore terminal cursor")?;
+    match choice? {
+        Choice::Start(Some(name)) => config.with_profile(&name).map(Some),
+        Choice::Start(None) => Ok(Some(config.clone())),
+        Choice::Quit => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [profiles.live]
+            port = "/dev/ttyACM0"
+            devices = ["td17"]
+
+            [profiles.td17]
+            "#,
+        )
+        .unwrap();
+        let mut picker = ProfilePicker::new(&config);
+        assert_eq!(picker.key(KeyCode::Enter), Some(Choice::Start(None)));
+        assert_eq!(picker.key(KeyCode::Down), None);
+        let lines = picker.lines();
+        assert_eq!(
+            lines[3].0[0].content,
+            format!("  {:<20} /dev/ttyACM0, td17", "live")
+        );
+        assert_eq!(lines[3].0[0].style.add_modifier, Modifier::REVERSED);
+        assert_eq!(
+            picker.key(KeyCode::Enter),
+            Some(Choice::Start(Some("live".to_string())))
+        );
+        picker.key(KeyCode::Up);
+        picker.key(KeyCode::Up);
+        assert_eq!(
+            picker.key(KeyCode::Enter),
+            Some(Choice::Start(Some("td17".to_string())))
+        );
+        assert_eq!(picker.key(KeyCode::Char('q')), Some(Choice::Quit));
+    }
+}
@@ -0,0 +1,107 @@
+//! Bounds the events the TUI keeps so a capture left running for days does not take all the
+//! memory, the oldest being dropped first
+
+use crate::capture::CaptureEvent;
+use anyhow::{bail, Context};
+use std::{mem, str::FromStr};
+
+/// Most events the TUI keeps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scrollback {
+    /// As many events, one per byte received
+    Events(usize),
+    /// As many bytes of memory taken by the events
+    Memory(usize),
+}
+
+impl Scrollback {
+    /// Returns the number of the oldest events to drop, none until the scrollback is full.
+    /// `memory` is what the events take. A tenth of the scrollback is freed at once, so the
+    /// table is not rebuilt for every event received
+    pub(super) fn excess(&self, events: &[CaptureEvent], memory: usize) -> usize {
+        match *self {
+            Scrollback::Events(most) if events.len() > most => events.len() - (most - most / 10),
+            Scrollback::Memory(most) if memory > most => {
+                let mut freed = 0;
+                let target = memory - (most - most / 10);
+                events
+                    .iter()
+                    .take_while(|event| {
+                        let more = freed < target;
+                        freed += footprint(event);
+                        more
+                    })
+                    .count()
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for Scrollback {
+    type Err = anyhow::Error;
+
+    /// Parses a number of events, such as `100000`, or a size of memory followed by `KB`,
+    /// `MB`, or `GB`, such as `50MB`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let split = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let number: usize = number
+            .parse()
+            .context(format!("`{}` is not a number of events or a size", text))?;
+        let shift = match unit.trim().to_uppercase().as_str() {
+            "" => return Ok(Scrollback::Events(number)),
+            "KB" => 10,
+            "MB" => 20,
+            "GB" => 30,
+            unit => bail!("`{}` is not a unit of size, use KB, MB, or GB", unit),
+        };
+        match number.checked_mul(1 << shift) {
+            Some(size) => Ok(Scrollback::Memory(size)),
+            None => bail!("`{}` is too large a size", text),
+        }
+    }
+}
+
+/// Returns about how much memory an event takes
+pub(super) fn footprint(event: &CaptureEvent) -> usize {
+    mem::size_of::<CaptureEvent>() + event.raw.len() + event.analysis.text().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn drops_a_tenth_once_full() {
+        assert_eq!(
+            "1000".parse::<Scrollback>().unwrap(),
+            Scrollback::Events(1000)
+        );
+        assert_eq!(
+            "50MB".parse::<Scrollback>().unwrap(),
+            Scrollback::Memory(50 << 20)
+        );
+        assert!("50 bytes".parse::<Scrollback>().is_err());
+        assert!("MB".parse::<Scrollback>().is_err());
+        assert!(format!("{}GB", usize::MAX >> 20)
+            .parse::<Scrollback>()
+            .is_err());
+
+        let mut capture = Capture::new();
+        let events: Vec<CaptureEvent> = (0..101)
+            .map(|_| capture.process(Duration::ZERO, 0xF8))
+            .collect();
+        assert_eq!(Scrollback::Events(101).excess(&events, 0), 0);
+        assert_eq!(Scrollback::Events(100).excess(&events, 0), 11);
+
+        let memory: usize = events.iter().map(footprint).sum();
+        let each = footprint(&events[0]);
+        assert_eq!(Scrollback::Memory(memory).excess(&events, memory), 0);
+        assert_eq!(Scrollback::Memory(100 * each).excess(&events, memory), 11);
+    }
+}
@@ -0,0 +1,54 @@
+//! Scrollbar beside the event table, showing where the rows in view are in the capture and
+//! dragged with the mouse to scroll through it
+
+use std::ops::Range;
+use tui::text::Spans;
+
+/// Returns the cells of a track `height` high covered by the thumb, for the `shown` rows
+/// from `first` out of `rows`. The thumb fills the track when every row is shown
+pub(super) fn thumb(first: usize, shown: usize, rows: usize, height: u16) -> Range<u16> {
+    if rows <= shown || height == 0 {
+        return 0..height;
+    }
+    let size = ((height as usize * shown).div_ceil(rows)).clamp(1, height as usize);
+    let start = (height as usize - size) * first / (rows - shown);
+    start as u16..(start + size) as u16
+}
+
+/// Returns the row of `rows` that the cell `y` of a track `height` high stands for, the
+/// first at the top and the last at the bottom
+pub(super) fn row_at(y: u16, height: u16, rows: usize) -> usize {
+    let last = rows.saturating_sub(1);
+    match height {
+        0 | 1 => last,
+        _ => (y.min(height - 1) as usize * last).div_ceil(height as usize - 1),
+    }
+}
+
+/// Draws the track, the thumb solid
+pub(super) fn lines(thumb: Range<u16>, height: u16) -> Vec<Spans<'static>> {
+    (0..height)
+        .map(|y| Spans::from(if thumb.contains(&y) { "█" } else { "│" }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumb_follows_rows() {
+        assert_eq!(thumb(0, 10, 5, 10), 0..10);
+        assert_eq!(thumb(0, 10, 100, 10), 0..1);
+        assert_eq!(thumb(90, 10, 100, 10), 9..10);
+        assert_eq!(thumb(45, 10, 100, 10), 4..5);
+        assert_eq!(thumb(0, 10, 20, 10), 0..5);
+        assert_eq!(thumb(10, 10, 20, 10), 5..10);
+
+        assert_eq!(row_at(0, 10, 100), 0);
+        assert_eq!(row_at(9, 10, 100), 99);
+        assert_eq!(row_at(20, 10, 100), 99);
+        assert_eq!(row_at(0, 10, 0), 0);
+        assert_eq!(lines(1..2, 3)[1].0[0].content, "█");
+    }
+}
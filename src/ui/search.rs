@@ -0,0 +1,101 @@
+//! Search of the event table by message type, channel, note, controller, bytes, or
+//! analysis text, typed after `/`
+
+use crate::{
+    capture::CaptureEvent,
+    midi::{notes::NoteNaming, MidiMessage},
+};
+
+/// What a search looks for
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Search {
+    /// `ch N` or `channel N`, the channel from 1 to 16
+    Channel(u8),
+    /// `cc N`, the number of a controller
+    Control(u8),
+    /// Anything else, matched regardless of case against the type of the message, the name
+    /// of its note, its bytes in hexadecimal, and the analysis
+    Text(String),
+}
+
+impl Search {
+    /// Parses the text typed, `None` if there is nothing to search for
+    pub fn parse(text: &str) -> Option<Search> {
+        let text = text.trim().to_lowercase();
+        let number = |prefixes: &[&str]| {
+            prefixes
+                .iter()
+                .find_map(|prefix| text.strip_prefix(prefix)?.trim().parse::<u8>().ok())
+        };
+        if let Some(channel) = number(&["channel", "ch"]).filter(|ch| (1..=16).contains(ch)) {
+            return Some(Search::Channel(channel - 1));
+        }
+        if let Some(control) = number(&["cc"]).filter(|cc| *cc < 128) {
+            return Some(Search::Control(control));
+        }
+        (!text.is_empty()).then_some(Search::Text(text))
+    }
+
+    /// Returns `true` if the event is a match, notes named with `naming`
+    pub fn matches(&self, event: &CaptureEvent, naming: NoteNaming) -> bool {
+        match self {
+            Search::Channel(channel) => event.channel == Some(*channel),
+            Search::Control(control) => matches!(
+                event.message,
+                Some(MidiMessage::ControlChange { control: c, .. }) if c == *control
+            ),
+            Search::Text(text) => {
+                let bytes = match event.raw.is_empty() {
+                    true => event.received(),
+                    false => &event.raw,
+                };
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                let note = match &event.message {
+                    Some(
+                        MidiMessage::NoteOn { note, .. }
+                        | MidiMessage::NoteOff { note, .. }
+                        | MidiMessage::PolyPressure { note, .. },
+                    ) => Some(naming.name(*note).to_lowercase()),
+                    _ => None,
+                };
+                event.analysis.text().to_lowercase().contains(text)
+                    || event
+                        .message
+                        .as_ref()
+                        .is_some_and(|m| m.name().to_lowercase().contains(text))
+                    || hex.join(" ").contains(text)
+                    || note.is_some_and(|note| note == *text)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+    use std::time::Duration;
+
+    #[test]
+    fn finds_events() {
+        let mut capture = Capture::new();
+        let events: Vec<CaptureEvent> = [0x92, 60, 100, 0xB0, 7, 90]
+            .into_iter()
+            .map(|byte| capture.process(Duration::ZERO, byte))
+            .collect();
+        let found = |text: &str| -> Vec<usize> {
+            let search = Search::parse(text).unwrap();
+            (0..events.len())
+                .filter(|i| search.matches(&events[*i], NoteNaming::default()))
+                .collect()
+        };
+        assert_eq!(Search::parse(" Ch 3"), Some(Search::Channel(2)));
+        assert_eq!(found("ch3"), [0, 1, 2]);
+        assert_eq!(found("cc 7"), [5]);
+        assert_eq!(found("note on"), [0, 1, 2]);
+        assert_eq!(found("c4"), [1, 2]);
+        assert_eq!(found("3c 64"), [2]);
+        assert_eq!(found("volume"), [4, 5]);
+        assert_eq!(Search::parse("  "), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Steps through the programs of a sound module to audition its patches one by one
+
+use crate::{config::Config, midi::MidiMessage};
+
+/// Channel, bank, and program that Program Changes are sent to
+#[derive(Debug)]
+pub(super) struct Stepper {
+    /// Channel from 0 to 15
+    channel: u8,
+    /// Sent as Bank Select MSB and LSB before each program, if set
+    bank: Option<u16>,
+    /// Program last sent, or `None` before the first step
+    program: Option<u8>,
+}
+
+impl Stepper {
+    pub fn new(config: &Config) -> Stepper {
+        Stepper {
+            channel: config.stepper.channel.clamp(1, 16) - 1,
+            bank: config.stepper.bank,
+            program: None,
+        }
+    }
+
+    /// Moves to the next program, or the previous one if `forward` is `false`, wrapping
+    /// around at the ends, and returns the bytes that select it
+    pub fn step(&mut self, forward: bool) -> Vec<u8> {
+        let program = match (self.program, forward) {
+            (None, _) => 0,
+            (Some(program), true) => (program + 1) % 128,
+            (Some(program), false) => program.checked_sub(1).unwrap_or(127),
+        };
+        self.program = Some(program);
+        let mut bytes = vec![];
+        if let Some(bank) = self.bank {
+            for (control, value) in [(0, (bank >> 7) as u8 & 0x7F), (32, bank as u8 & 0x7F)] {
+                bytes.extend(
+                    MidiMessage::ControlChange {
+                        channel: self.channel,
+                        control,
+                        value,
+                    }
+                    .to_bytes(),
+                );
+            }
+        }
+        bytes.extend(
+            MidiMessage::ProgramChange {
+                channel: self.channel,
+                program,
+            }
+            .to_bytes(),
+        );
+        bytes
+    }
+
+    /// Moves to the next channel, or the previous one if `forward` is `false`, starting over
+    /// from the first program
+    pub fn change_channel(&mut self, forward: bool) {
+        self.channel = if forward {
+            (self.channel + 1) % 16
+        } else {
+            self.channel.checked_sub(1).unwrap_or(15)
+        };
+        self.program = None;
+    }
+
+    /// Describes the current program with its name from the patch map of the configuration
+    pub fn describe(&self, config: &Config) -> String {
+        let bank = match self.bank {
+            Some(bank) => format!(", bank {}", bank),
+            None => String::new(),
+        };
+        match self.program {
+            Some(program) => format!(
+                "{}, channel {}{}",
+                config
+                    .names
+                    .program(format!("Program {}", program), program),
+                self.channel + 1,
+                bank
+            ),
+            None => format!("Program Changes go to channel {}{}", self.channel + 1, bank),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_wrap_around() {
+        let mut config = Config::default();
+        config.stepper.channel = 10;
+        config
+            .names
+            .programs
+            .insert("127".to_string(), "Gunshot".to_string());
+        let mut stepper = Stepper::new(&config);
+        assert_eq!(stepper.step(true), [0xC9, 0]);
+        assert_eq!(stepper.step(false), [0xC9, 127]);
+        assert_eq!(
+            stepper.describe(&config),
+            "Program 127 (Gunshot), channel 10"
+        );
+        assert_eq!(stepper.step(true), [0xC9, 0]);
+
+        config.stepper.bank = Some(130);
+        let mut stepper = Stepper::new(&config);
+        stepper.change_channel(false);
+        assert_eq!(stepper.step(true), [0xB8, 0, 1, 0xB8, 32, 2, 0xC8, 0]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Overview of the byte density over the whole capture, like the waveform overview of an
+//! audio editor, for finding and jumping to the busy parts of long captures
+
+use std::{ops::Range, time::Duration};
+
+/// Counts kept of the capture. Pairs of them are merged once the capture outgrows them
+const BINS: usize = 1024;
+/// Time covered by each count until the capture outgrows them
+const FIRST_BIN_WIDTH: Duration = Duration::from_millis(10);
+/// Bars of the strip, from fewest to most bytes
+const BARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Bytes received in each stretch of the capture, updated as bytes arrive
+#[derive(Debug)]
+pub(super) struct Strip {
+    bins: Vec<usize>,
+    /// Time covered by each bin
+    width: Duration,
+    /// Time of the last byte
+    end: Duration,
+}
+
+impl Strip {
+    pub(super) fn new() -> Strip {
+        Strip {
+            bins: vec![0; BINS],
+            width: FIRST_BIN_WIDTH,
+            end: Duration::ZERO,
+        }
+    }
+
+    /// Counts a byte received at `time`
+    pub(super) fn observe(&mut self, time: Duration) {
+        let mut bin = (time.as_nanos() / self.width.as_nanos()) as usize;
+        while bin >= BINS {
+            for i in 0..BINS / 2 {
+                self.bins[i] = self.bins[2 * i] + self.bins[2 * i + 1];
+            }
+            self.bins[BINS / 2..].fill(0);
+            self.width *= 2;
+            bin /= 2;
+        }
+        self.bins[bin] += 1;
+        self.end = self.end.max(time);
+    }
+
+    /// Returns the number of bins the capture spans
+    fn used(&self) -> usize {
+        (self.end.as_nanos() / self.width.as_nanos()) as usize + 1
+    }
+
+    /// Returns the bins drawn in a column of a strip `columns` wide. Short captures are
+    /// stretched over the strip
+    fn bins_of(&self, column: usize, columns: usize) -> Range<usize> {
+        let used = self.used();
+        let start = column * used / columns;
+        start..((column + 1) * used / columns).max(start + 1)
+    }
+
+    /// Returns the stretch of the capture drawn in a column
+    pub(super) fn span(&self, column: usize, columns: usize) -> Range<Duration> {
+        let bins = self.bins_of(column, columns);
+        self.width * bins.start as u32..self.width * bins.end as u32
+    }
+
+    /// Returns the bar of each column of a strip `columns` wide
+    pub(super) fn bars(&self, columns: usize) -> Vec<char> {
+        let counts: Vec<usize> = (0..columns)
+            .map(|column| self.bins[self.bins_of(column, columns)].iter().sum())
+            .collect();
+        let most = counts.iter().copied().max().unwrap_or(0).max(1);
+        counts
+            .into_iter()
+            .map(|count| {
+                // Any byte at all shows, however few
+                let level = (count * (BARS.len() - 1)).div_ceil(most);
+                BARS[level]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density() {
+        let mut strip = Strip::new();
+        for ms in [0, 1, 2, 3, 35] {
+            strip.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(strip.bars(4).iter().collect::<String>(), "█  ▂");
+        assert_eq!(strip.span(3, 4), FIRST_BIN_WIDTH * 3..FIRST_BIN_WIDTH * 4);
+
+        // Bins are merged once the capture outgrows them
+        strip.observe(FIRST_BIN_WIDTH * BINS as u32 * 3);
+        assert_eq!(strip.width, FIRST_BIN_WIDTH * 4);
+        assert_eq!(strip.bins[0], 5);
+        assert_eq!(strip.bars(2).iter().collect::<String>(), "█▂");
+    }
+}
@@ -0,0 +1,370 @@
+//! Colors of the TUI
+
+use crate::{capture::CaptureEvent, config::ColorsConfig, midi::MidiAnalysis, store};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::Widget,
+};
+
+/// Rows of events younger than this are drawn at full brightness when dimming by age
+const BRIGHT: Duration = Duration::from_secs(1);
+/// Time over which older rows fade from the lightest gray to the darkest
+const FADE: Duration = Duration::from_secs(8);
+
+/// Color scheme of the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Colors suited to dark terminal backgrounds
+    #[default]
+    Dark,
+    /// Darker colors suited to light terminal backgrounds
+    Light,
+    /// Bold text and solid backgrounds that stand out on any background
+    HighContrast,
+    /// The blue, orange, and vermillion of the Okabe-Ito palette, told apart with any kind
+    /// of color blindness
+    Colorblind,
+    /// No colors at all, only bold and reversed text, for terminals with unusual palettes
+    /// and for screen recordings
+    Mono,
+}
+
+/// Styles of the TUI, those of a theme with some changed in the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    /// Headers of the table and keys of the menu bar
+    pub(super) header: Style,
+    /// Rows of each severity
+    pub(super) comment: Style,
+    pub(super) info: Style,
+    pub(super) warning: Style,
+    pub(super) violation: Style,
+    /// Rows of the types of messages given a style of their own, by status, used in place
+    /// of the comment style
+    messages: BTreeMap<u8, Style>,
+    /// Messages sent from the send panel
+    pub(super) sent: Style,
+    /// Rows matching the search
+    pub(super) matched: Style,
+    /// Rows marked with `B`
+    pub(super) bookmark: Style,
+    /// Rows of the range being selected, and the stretch of the strip in view
+    pub(super) range: Style,
+    /// Grays of the 256 color palette that rows fade from and to as they age
+    fade: (u8, u8),
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::preset(Theme::Dark)
+    }
+}
+
+impl Palette {
+    /// Returns the styles of a theme, `Mono` keeping those of `Dark` as its colors are
+    /// removed once drawn
+    fn preset(theme: Theme) -> Palette {
+        let fg = |color| Style::default().fg(color);
+        let dark = Palette {
+            header: Style::default()
+                .fg(Color::Blue)
+                .bg(Color::Gray)
+                .add_modifier(Modifier::BOLD),
+            comment: Style::default(),
+            info: fg(Color::Cyan),
+            warning: fg(Color::LightYellow),
+            violation: fg(Color::LightRed),
+            messages: BTreeMap::new(),
+            sent: fg(Color::LightMagenta),
+            matched: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+            bookmark: Style::default().add_modifier(Modifier::UNDERLINED),
+            range: Style::default().bg(Color::DarkGray),
+            fade: (250, 239),
+        };
+        match theme {
+            Theme::Dark | Theme::Mono => dark,
+            Theme::Light => Palette {
+                header: Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+                info: fg(Color::Blue),
+                warning: fg(Color::Indexed(130)),
+                violation: fg(Color::Red),
+                sent: fg(Color::Magenta),
+                matched: fg(Color::Indexed(130)).add_modifier(Modifier::BOLD),
+                range: Style::default().bg(Color::Indexed(252)),
+                fade: (242, 251),
+                ..dark
+            },
+            Theme::HighContrast => Palette {
+                header: Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                info: fg(Color::LightCyan).add_modifier(Modifier::BOLD),
+                warning: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                violation: Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Red)
+                    .add_modifier(Modifier::BOLD),
+                sent: fg(Color::LightMagenta).add_modifier(Modifier::BOLD),
+                matched: Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::LightYellow)
+                    .add_modifier(Modifier::BOLD),
+                bookmark: Style::default().add_modifier(Modifier::UNDERLINED | Modifier::BOLD),
+                range: Style::default().bg(Color::Blue),
+                ..dark
+            },
+            Theme::Colorblind => Palette {
+                header: Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Indexed(31))
+                    .add_modifier(Modifier::BOLD),
+                info: fg(Color::Indexed(74)),
+                warning: fg(Color::Indexed(214)),
+                // Also told apart from warnings by weight, not only by hue
+                violation: fg(Color::Indexed(166)).add_modifier(Modifier::BOLD),
+                sent: fg(Color::Indexed(175)),
+                matched: fg(Color::Indexed(227)).add_modifier(Modifier::BOLD),
+                ..dark
+            },
+        }
+    }
+
+    /// Returns the styles of a theme with those given in the configuration in place of its
+    /// own
+    pub fn new(theme: Theme, colors: &ColorsConfig) -> Result<Palette, anyhow::Error> {
+        let mut palette = Palette::preset(theme);
+        for (name, text) in &colors.severity {
+            let style = match name.as_str() {
+                "comment" => &mut palette.comment,
+                "info" => &mut palette.info,
+                "warning" => &mut palette.warning,
+                "violation" => &mut palette.violation,
+                _ => bail!(
+                    "`{}` is not a severity, use comment, info, warning, or violation",
+                    name
+                ),
+            };
+            *style = parse_style(text).context(format!("Invalid color of {}", name))?;
+        }
+        for (name, text) in &colors.messages {
+            let style = parse_style(text).context(format!("Invalid color of {}", name))?;
+            for status in store::parse_types(name)? {
+                palette.messages.insert(status, style);
+            }
+        }
+        for (name, text) in &colors.parts {
+            let style = match name.as_str() {
+                "header" => &mut palette.header,
+                "sent" => &mut palette.sent,
+                "match" => &mut palette.matched,
+                "bookmark" => &mut palette.bookmark,
+                "range" => &mut palette.range,
+                _ => bail!(
+                    "`{}` is not a part of the TUI, use header, sent, match, bookmark, or range",
+                    name
+                ),
+            };
+            *style = parse_style(text).context(format!("Invalid color of {}", name))?;
+        }
+        Ok(palette)
+    }
+
+    /// Returns the style of the row of an event by the severity of its analysis, or by the
+    /// type of its message if it has no more to say than a comment
+    pub(super) fn row(&self, event: &CaptureEvent) -> Style {
+        match event.analysis {
+            MidiAnalysis::Comment(_) => event
+                .status
+                .and_then(|status| self.messages.get(&status))
+                .copied()
+                .unwrap_or(self.comment),
+            MidiAnalysis::Info(_) => self.info,
+            MidiAnalysis::Warning(_) => self.warning,
+            MidiAnalysis::Violation(_) => self.violation,
+        }
+    }
+
+    /// Returns the style of a row whose event is `age` old, grayer the older it is. Rows
+    /// that have faded halfway are also dimmed, which is all that is left of them in `Mono`
+    pub(super) fn aged(&self, age: Duration) -> Style {
+        let Some(faded) = age.checked_sub(BRIGHT) else {
+            return Style::default();
+        };
+        let fade = (faded.as_secs_f64() / FADE.as_secs_f64()).min(1.0);
+        let (from, to) = self.fade;
+        let gray = from as f64 + fade * (to as f64 - from as f64);
+        let style = Style::default().fg(Color::Indexed(gray.round() as u8));
+        if fade >= 0.5 {
+            style.add_modifier(Modifier::DIM)
+        } else {
+            style
+        }
+    }
+}
+
+/// Parses a style written as a foreground color, `on` and a background color, and
+/// modifiers, such as `yellow`, `white on red bold`, or `on 236`. Colors are named like
+/// `light-red`, or given as a number of the 256 color palette or as `#rrggbb`
+fn parse_style(text: &str) -> Result<Style, anyhow::Error> {
+    let mut style = Style::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word.to_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "on" => {
+                let color = words.next().context("A color is expected after `on`")?;
+                style = style.bg(parse_color(color)?);
+                continue;
+            }
+            _ if style.fg.is_none() => {
+                style = style.fg(parse_color(word)?);
+                continue;
+            }
+            _ => bail!(
+                "`{}` is not a modifier, use bold, dim, italic, underlined, or reversed",
+                word
+            ),
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+/// Parses a color named like `light-red`, or given as a number of the 256 color palette or
+/// as `#rrggbb`
+fn parse_color(text: &str) -> Result<Color, anyhow::Error> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .context(format!("`{}` is not a color like #ff8000", text))?;
+        return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+    if let Ok(index) = text.parse() {
+        return Ok(Color::Indexed(index));
+    }
+    let name: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+        .replace("grey", "gray");
+    Ok(match name.as_str() {
+        "default" | "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "lightgray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => bail!("`{}` is not a color", text),
+    })
+}
+
+/// Removes the colors of everything drawn beneath it, keeping text modifiers
+pub(super) struct Monochrome;
+
+impl Widget for Monochrome {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                buf.get_mut(x, y).set_fg(Color::Reset).set_bg(Color::Reset);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn rows_fade_with_age() {
+        let dark = Palette::default();
+        assert_eq!(dark.aged(Duration::from_millis(900)), Style::default());
+        assert_eq!(dark.aged(BRIGHT).fg, Some(Color::Indexed(250)));
+        let middle = dark.aged(BRIGHT + FADE / 2);
+        assert!(middle.add_modifier.contains(Modifier::DIM));
+        assert_eq!(
+            dark.aged(Duration::from_secs(60)).fg,
+            Some(Color::Indexed(239))
+        );
+        // Rows fade toward the background, lighter on light ones
+        let light = Palette::preset(Theme::Light);
+        assert_eq!(
+            light.aged(Duration::from_secs(60)).fg,
+            Some(Color::Indexed(251))
+        );
+    }
+
+    #[test]
+    fn overrides_theme() {
+        assert_eq!(
+            parse_style("white on #ff8000 bold").unwrap(),
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Rgb(255, 128, 0))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(
+            parse_style("on 236").unwrap(),
+            Style::default().bg(Color::Indexed(236))
+        );
+        assert_eq!(parse_style("Light-Grey").unwrap().fg, Some(Color::Gray));
+        assert!(parse_style("red green").is_err());
+        assert!(parse_style("on").is_err());
+        assert!(parse_style("#ff80").is_err());
+
+        let colors = ColorsConfig {
+            severity: BTreeMap::from([("warning".to_string(), "magenta".to_string())]),
+            messages: BTreeMap::from([("clock".to_string(), "dark-gray".to_string())]),
+            parts: BTreeMap::from([("header".to_string(), "reversed".to_string())]),
+        };
+        let palette = Palette::new(Theme::Colorblind, &colors).unwrap();
+        assert_eq!(palette.warning.fg, Some(Color::Magenta));
+        assert_eq!(
+            palette.violation,
+            Palette::preset(Theme::Colorblind).violation
+        );
+        assert_eq!(palette.header.add_modifier, Modifier::REVERSED);
+
+        let mut capture = Capture::new();
+        let clock = capture.process(Duration::ZERO, 0xF8);
+        assert_eq!(palette.row(&clock).fg, Some(Color::DarkGray));
+        let start = capture.process(Duration::ZERO, 0xFA);
+        assert_eq!(palette.row(&start), Style::default());
+
+        let invalid = |severity: &str, message: &str| ColorsConfig {
+            severity: BTreeMap::from([(severity.to_string(), "red".to_string())]),
+            messages: BTreeMap::from([(message.to_string(), "red".to_string())]),
+            ..ColorsConfig::default()
+        };
+        assert!(Palette::new(Theme::Dark, &invalid("error", "clock")).is_err());
+        assert!(Palette::new(Theme::Dark, &invalid("info", "bleep")).is_err());
+    }
+}
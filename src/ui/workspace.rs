@@ -0,0 +1,100 @@
+//! A reference capture shown beside the live one, such as a known-good recording, scrolled
+//! along with the live capture so both can be compared as the session runs
+
+use crate::capture::{CaptureEvent, Filter};
+use std::time::Duration;
+
+/// How far the reference is shifted in time by each press of `<` or `>`
+pub(super) const OFFSET_STEP: Duration = Duration::from_millis(10);
+
+/// A capture loaded for comparison
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub(super) name: String,
+    pub(super) events: Vec<CaptureEvent>,
+    /// Positions of the events that pass the filter of the live capture
+    view: Vec<usize>,
+    /// Microseconds added to the time of a live event to find the matching reference event
+    offset: i64,
+}
+
+impl Reference {
+    pub fn new(name: String, events: Vec<CaptureEvent>) -> Reference {
+        Reference {
+            name,
+            view: (0..events.len()).collect(),
+            events,
+            offset: 0,
+        }
+    }
+
+    /// Shows only the events that pass the filter, like the live capture. The inputs of the
+    /// live capture are not those of the reference, so they are not filtered
+    pub(super) fn set_filter(&mut self, filter: &Filter) {
+        let filter = Filter {
+            hidden_sources: Default::default(),
+            ..filter.clone()
+        };
+        self.view = (0..self.events.len())
+            .filter(|p| filter.matches(&self.events[*p]))
+            .collect();
+    }
+
+    /// Returns the positions of the events shown
+    pub(super) fn view(&self) -> &[usize] {
+        &self.view
+    }
+
+    /// Returns the row of the last shown event at or before the live time `time`,
+    /// shifted by the offset
+    pub(super) fn row_at(&self, time: Duration) -> Option<usize> {
+        let time = time.as_micros() as i64 + self.offset;
+        self.view
+            .partition_point(|p| self.events[*p].time.as_micros() as i64 <= time)
+            .checked_sub(1)
+    }
+
+    /// Returns the offset in seconds
+    pub(super) fn offset(&self) -> f64 {
+        self.offset as f64 / 1e6
+    }
+
+    /// Shifts the reference in time
+    pub(super) fn shift(&mut self, forward: bool) {
+        let step = OFFSET_STEP.as_micros() as i64;
+        self.offset += if forward { step } else { -step };
+    }
+
+    /// Sets the offset in microseconds
+    pub(super) fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Capture;
+
+    #[test]
+    fn rows_follow_time() {
+        let mut capture = Capture::new();
+        let events: Vec<CaptureEvent> = [(0, 0xF8), (20, 0xFE), (40, 0xF8), (60, 0xFE)]
+            .into_iter()
+            .map(|(ms, byte)| capture.process(Duration::from_millis(ms), byte))
+            .collect();
+        let mut reference = Reference::new("ref".to_string(), events);
+        assert_eq!(reference.row_at(Duration::from_millis(25)), Some(1));
+        reference.shift(true);
+        reference.shift(true);
+        assert_eq!(reference.row_at(Duration::from_millis(25)), Some(2));
+        reference.set_offset(-10_000);
+        assert_eq!(reference.row_at(Duration::from_millis(5)), None);
+
+        let mut filter = Filter::default();
+        filter.hidden_statuses.insert(0xFE);
+        reference.set_filter(&filter);
+        assert_eq!(reference.view(), [0, 2]);
+        assert_eq!(reference.row_at(Duration::from_millis(65)), Some(1));
+    }
+}
@@ -0,0 +1,120 @@
+//! Translation of MIDI 1.0 messages into Universal MIDI Packets
+//!
+//! Channel Voice messages become MIDI 1.0 Channel Voice packets (message type 0x2),
+//! System Common and System Real Time messages become System packets (message type 0x1)
+//! and System Exclusive messages are split into 7-bit SysEx data packets (message type 0x3)
+
+use crate::midi::MidiMessage;
+
+const MT_SYSTEM: u32 = 0x1;
+const MT_CHANNEL_VOICE_1: u32 = 0x2;
+const MT_SYSEX_7: u32 = 0x3;
+
+// SysEx packet status
+const SYSEX_COMPLETE: u32 = 0x0;
+const SYSEX_START: u32 = 0x1;
+const SYSEX_CONTINUE: u32 = 0x2;
+const SYSEX_END: u32 = 0x3;
+
+/// Maximum number of SysEx data bytes carried by one 64-bit packet
+const SYSEX_BYTES_PER_PACKET: usize = 6;
+
+/// Builds the single word of a 32-bit packet
+fn word(message_type: u32, group: u8, status: u8, d0: u8, d1: u8) -> u32 {
+    (message_type << 28)
+        | ((group as u32 & 0x0F) << 24)
+        | ((status as u32) << 16)
+        | ((d0 as u32) << 8)
+        | d1 as u32
+}
+
+/// Translates a MIDI 1.0 message into the 32-bit words of its UMP packets on the given group
+pub fn translate(message: &MidiMessage, group: u8) -> Vec<u32> {
+    if let MidiMessage::SystemExclusive(data) = message {
+        return sysex_packets(data, group);
+    }
+
+    let bytes = message.clone().to_bytes();
+    let status = bytes[0];
+    let d0 = bytes.get(1).copied().unwrap_or(0);
+    let d1 = bytes.get(2).copied().unwrap_or(0);
+    let message_type = if status >= 0xF0 {
+        MT_SYSTEM
+    } else {
+        MT_CHANNEL_VOICE_1
+    };
+    vec![word(message_type, group, status, d0, d1)]
+}
+
+/// Splits SysEx data (without `F0`/`F7`) into 64-bit packets of two words each
+fn sysex_packets(data: &[u8], group: u8) -> Vec<u32> {
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[]]
+    } else {
+        data.chunks(SYSEX_BYTES_PER_PACKET).collect()
+    };
+    let last = chunks.len() - 1;
+
+    let mut words = vec![];
+    for (i, chunk) in chunks.iter().enumerate() {
+        let status = match (i, last) {
+            (_, 0) => SYSEX_COMPLETE,
+            (0, _) => SYSEX_START,
+            (i, last) if i == last => SYSEX_END,
+            _ => SYSEX_CONTINUE,
+        };
+        let mut bytes = [0_u8; 8];
+        bytes[0] = ((MT_SYSEX_7 << 4) as u8) | (group & 0x0F);
+        bytes[1] = ((status << 4) as u8) | chunk.len() as u8;
+        bytes[2..2 + chunk.len()].copy_from_slice(chunk);
+        words.push(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        words.push(u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]));
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_voice() {
+        let message = MidiMessage::NoteOn {
+            channel: 3,
+            note: 60,
+            velocity: 100,
+        };
+        assert_eq!(translate(&message, 0), vec![0x2093_3C64]);
+        assert_eq!(translate(&message, 5), vec![0x2593_3C64]);
+        let bend = MidiMessage::PitchBend {
+            channel: 0,
+            value: 0x2000,
+        };
+        assert_eq!(translate(&bend, 0), vec![0x20E0_0040]);
+    }
+
+    #[test]
+    fn system() {
+        assert_eq!(translate(&MidiMessage::TimingClock, 0), vec![0x10F8_0000]);
+        assert_eq!(translate(&MidiMessage::SongSelect(7), 1), vec![0x11F3_0700]);
+    }
+
+    #[test]
+    fn sysex() {
+        let short = MidiMessage::SystemExclusive(vec![0x7E, 0x7F, 0x06, 0x01]);
+        assert_eq!(translate(&short, 0), vec![0x3004_7E7F, 0x0601_0000]);
+
+        let long = MidiMessage::SystemExclusive((1..=14).collect());
+        assert_eq!(
+            translate(&long, 0),
+            vec![
+                0x3016_0102,
+                0x0304_0506,
+                0x3026_0708,
+                0x090A_0B0C,
+                0x3032_0D0E,
+                0x0000_0000,
+            ]
+        );
+    }
+}